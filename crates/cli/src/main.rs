@@ -6,7 +6,7 @@ use std::process;
 use anyhow::{Context, Result, bail};
 use clap::{Parser, Subcommand, ValueEnum};
 
-use text_document::{FindOptions, TextDocument};
+use text_document::{EpubOptions, FindOptions, TextDocument};
 
 #[derive(Parser)]
 #[command(
@@ -23,9 +23,9 @@ struct Cli {
 enum Commands {
     /// Convert a document between formats (detected by file extension)
     Convert {
-        /// Input file (.txt, .md, .html, .htm)
+        /// Input file (.txt, .md, .html, .htm, .docx, .odt, .rtf)
         input: String,
-        /// Output file (.txt, .md, .html, .htm, .tex, .latex, .docx)
+        /// Output file (.txt, .md, .html, .htm, .tex, .latex, .docx, .odt, .rtf, .epub)
         output: String,
         /// LaTeX document class (only for .tex output)
         #[arg(long, default_value = "article")]
@@ -53,6 +53,10 @@ enum Commands {
         whole_word: bool,
         #[arg(long, short = 'e')]
         regex: bool,
+        /// Approximate search allowing up to N edits per match; best
+        /// matches are listed first
+        #[arg(long, value_name = "N", conflicts_with = "regex")]
+        fuzzy: Option<usize>,
     },
 
     /// Find and replace text
@@ -70,8 +74,16 @@ enum Commands {
         case_sensitive: bool,
         #[arg(long, short = 'w')]
         whole_word: bool,
+        /// Regex query; the replacement may use `$1` / `${name}`
         #[arg(long, short = 'e')]
         regex: bool,
+        /// Match each occurrence's capitalization (Foo → Bar, FOO → BAR)
+        #[arg(long, short = 'p')]
+        preserve_case: bool,
+        /// Also replace matches spanning paragraph breaks; newlines in the
+        /// replacement split paragraphs
+        #[arg(long)]
+        cross_block: bool,
     },
 
     /// Print document content to stdout in a given format
@@ -103,6 +115,9 @@ enum FileFormat {
     Html,
     Latex,
     Docx,
+    Odt,
+    Rtf,
+    Epub,
 }
 
 fn detect_format(path: &str) -> FileFormat {
@@ -116,6 +131,9 @@ fn detect_format(path: &str) -> FileFormat {
         Some("html" | "htm") => FileFormat::Html,
         Some("tex" | "latex") => FileFormat::Latex,
         Some("docx") => FileFormat::Docx,
+        Some("odt") => FileFormat::Odt,
+        Some("rtf") => FileFormat::Rtf,
+        Some("epub") => FileFormat::Epub,
         _ => FileFormat::PlainText,
     }
 }
@@ -123,10 +141,20 @@ fn detect_format(path: &str) -> FileFormat {
 // ── Document loading ────────────────────────────────────────────
 
 fn load_document(path: &str) -> Result<TextDocument> {
+    let doc = TextDocument::new();
+    let format = detect_format(path);
+    if matches!(format, FileFormat::Docx | FileFormat::Odt) {
+        let bytes = std::fs::read(path).with_context(|| format!("failed to read '{path}'"))?;
+        if format == FileFormat::Docx {
+            doc.set_docx(&bytes)?.wait().context("DOCX import failed")?;
+        } else {
+            doc.set_odt(&bytes)?.wait().context("ODT import failed")?;
+        }
+        return Ok(doc);
+    }
     let content =
         std::fs::read_to_string(path).with_context(|| format!("failed to read '{path}'"))?;
-    let doc = TextDocument::new();
-    match detect_format(path) {
+    match format {
         FileFormat::PlainText => {
             doc.set_plain_text(&content)?;
         }
@@ -140,6 +168,9 @@ fn load_document(path: &str) -> Result<TextDocument> {
                 .wait()
                 .context("HTML import failed")?;
         }
+        FileFormat::Rtf => {
+            doc.set_rtf(&content)?.wait().context("RTF import failed")?;
+        }
         other => bail!("unsupported input format: {}", format_name(other)),
     }
     Ok(doc)
@@ -152,6 +183,9 @@ fn format_name(fmt: FileFormat) -> &'static str {
         FileFormat::Html => "HTML",
         FileFormat::Latex => "LaTeX",
         FileFormat::Docx => "DOCX",
+        FileFormat::Odt => "ODT",
+        FileFormat::Rtf => "RTF",
+        FileFormat::Epub => "EPUB",
     }
 }
 
@@ -181,6 +215,18 @@ fn cmd_convert(input: &str, output: &str, document_class: &str, preamble: bool)
         FileFormat::Docx => {
             doc.to_docx(output)?.wait().context("DOCX export failed")?;
         }
+        FileFormat::Odt => {
+            doc.to_odt(output)?.wait().context("ODT export failed")?;
+        }
+        FileFormat::Rtf => {
+            let text = doc.to_rtf()?;
+            std::fs::write(output, text)?;
+        }
+        FileFormat::Epub => {
+            doc.to_epub(output, &EpubOptions::default())?
+                .wait()
+                .context("EPUB export failed")?;
+        }
     }
 
     eprintln!("{} -> {} ({})", input, output, format_name(out_format));
@@ -207,32 +253,41 @@ fn cmd_find(
     case_sensitive: bool,
     whole_word: bool,
     use_regex: bool,
+    fuzzy: Option<usize>,
 ) -> Result<()> {
     let doc = load_document(file)?;
     let opts = FindOptions {
         case_sensitive,
         whole_word,
         use_regex,
-        search_backward: false,
+        context_chars: 20,
+        fuzzy,
+        ..Default::default()
     };
-    let matches = doc.find_all(query, &opts)?;
 
-    if matches.is_empty() {
-        eprintln!("no matches found");
-        return Ok(());
+    let mut count = 0;
+    for hit in doc.find_iter(query, &opts, 100) {
+        let hit = hit?;
+        let matched = doc
+            .text_at(hit.position, hit.length)
+            .unwrap_or_default()
+            .replace('\n', "\\n");
+        let distance = match fuzzy {
+            Some(_) => format!(" ~{}", hit.distance),
+            None => String::new(),
+        };
+        println!(
+            "{}:{}{} {}{}{}",
+            hit.position, hit.length, distance, hit.context_before, matched, hit.context_after
+        );
+        count += 1;
     }
 
-    for m in &matches {
-        let context_start = m.position.saturating_sub(20);
-        let context_len = (m.length + 40).min(200);
-        let text = doc.text_at(context_start, context_len).unwrap_or_default();
-        let offset_in_context = m.position - context_start;
-        let display = text.replace('\n', "\\n");
-        println!("{}:{} {}", m.position, m.length, display);
-        let _ = offset_in_context; // available for future highlighting
+    if count == 0 {
+        eprintln!("no matches found");
+        return Ok(());
     }
-
-    eprintln!("{} match(es) found", matches.len());
+    eprintln!("{} match(es) found", count);
     Ok(())
 }
 
@@ -241,18 +296,10 @@ fn cmd_replace(
     query: &str,
     replacement: &str,
     output: Option<&str>,
-    case_sensitive: bool,
-    whole_word: bool,
-    use_regex: bool,
+    opts: &FindOptions,
 ) -> Result<()> {
     let doc = load_document(file)?;
-    let opts = FindOptions {
-        case_sensitive,
-        whole_word,
-        use_regex,
-        search_backward: false,
-    };
-    let count = doc.replace_text(query, replacement, true, &opts)?;
+    let count = doc.replace_text(query, replacement, true, opts)?;
 
     if count == 0 {
         eprintln!("no matches found, file unchanged");
@@ -261,13 +308,29 @@ fn cmd_replace(
 
     let out_path = output.unwrap_or(file);
     let out_format = detect_format(out_path);
-    let content = match out_format {
-        FileFormat::PlainText => doc.to_plain_text()?,
-        FileFormat::Markdown => doc.to_markdown()?,
-        FileFormat::Html => doc.to_html()?,
-        _ => doc.to_plain_text()?,
-    };
-    std::fs::write(out_path, content)?;
+    match out_format {
+        FileFormat::Docx => {
+            doc.to_docx(out_path)?
+                .wait()
+                .context("DOCX export failed")?;
+        }
+        FileFormat::Odt => {
+            doc.to_odt(out_path)?.wait().context("ODT export failed")?;
+        }
+        FileFormat::PlainText | FileFormat::Markdown | FileFormat::Html | FileFormat::Rtf => {
+            let content = match out_format {
+                FileFormat::Markdown => doc.to_markdown()?,
+                FileFormat::Html => doc.to_html()?,
+                FileFormat::Rtf => doc.to_rtf()?,
+                _ => doc.to_plain_text()?,
+            };
+            std::fs::write(out_path, content)?;
+        }
+        other => bail!(
+            "replace cannot write {} output; use convert",
+            format_name(other)
+        ),
+    }
     eprintln!("{count} replacement(s), written to {out_path}");
     Ok(())
 }
@@ -305,7 +368,8 @@ fn main() {
             case_sensitive,
             whole_word,
             regex,
-        } => cmd_find(file, query, *case_sensitive, *whole_word, *regex),
+            fuzzy,
+        } => cmd_find(file, query, *case_sensitive, *whole_word, *regex, *fuzzy),
 
         Commands::Replace {
             file,
@@ -315,14 +379,19 @@ fn main() {
             case_sensitive,
             whole_word,
             regex,
+            preserve_case,
+            cross_block,
         } => cmd_replace(
             file,
             query,
             replacement,
             output.as_deref(),
-            *case_sensitive,
-            *whole_word,
-            *regex,
+            &FindOptions::default()
+                .with_case_sensitive(*case_sensitive)
+                .with_whole_word(*whole_word)
+                .with_use_regex(*regex)
+                .with_preserve_case(*preserve_case)
+                .with_cross_block(*cross_block),
         ),
 
         Commands::Cat { file, format } => cmd_cat(file, format),
@@ -212,6 +212,27 @@ fn find_case_sensitive() {
     assert!(stderr.contains("1 match(es) found"));
 }
 
+#[test]
+fn find_fuzzy() {
+    let input = tmp_path("find_fuzzy_input.txt");
+    fs::write(&input, "recieve, receive and recive").unwrap();
+
+    let output = text_document_bin()
+        .args(["find", input.to_str().unwrap(), "receive", "--fuzzy", "1"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 2, "{stdout}");
+    assert!(lines[0].starts_with("9:7 ~0 "), "{stdout}");
+    assert!(lines[1].starts_with("21:6 ~1 "), "{stdout}");
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("2 match(es) found"));
+}
+
 // ── Replace ──────────────────────────────────────────────────────
 
 #[test]
@@ -270,6 +291,83 @@ fn replace_in_place() {
     assert_eq!(content, "baz bar baz");
 }
 
+#[test]
+fn replace_in_place_keeps_docx() {
+    let source = tmp_path("replace_docx_source.txt");
+    let docx = tmp_path("replace_docx.docx");
+    let text = tmp_path("replace_docx_result.txt");
+    fs::write(&source, "Title\nHello world").unwrap();
+    let status = text_document_bin()
+        .args(["convert", source.to_str().unwrap(), docx.to_str().unwrap()])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let status = text_document_bin()
+        .args(["replace", docx.to_str().unwrap(), "world", "earth"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert!(fs::read(&docx).unwrap().starts_with(b"PK"));
+
+    let status = text_document_bin()
+        .args(["convert", docx.to_str().unwrap(), text.to_str().unwrap()])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert_eq!(fs::read_to_string(&text).unwrap(), "Title\nHello earth");
+}
+
+#[test]
+fn replace_in_place_keeps_odt() {
+    let source = tmp_path("replace_odt_source.txt");
+    let odt = tmp_path("replace_odt.odt");
+    let text = tmp_path("replace_odt_result.txt");
+    fs::write(&source, "Title\nHello world").unwrap();
+    let status = text_document_bin()
+        .args(["convert", source.to_str().unwrap(), odt.to_str().unwrap()])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let status = text_document_bin()
+        .args(["replace", odt.to_str().unwrap(), "world", "earth"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert!(fs::read(&odt).unwrap().starts_with(b"PK"));
+
+    let status = text_document_bin()
+        .args(["convert", odt.to_str().unwrap(), text.to_str().unwrap()])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert_eq!(fs::read_to_string(&text).unwrap(), "Title\nHello earth");
+}
+
+#[test]
+fn replace_refuses_unwritable_output() {
+    let input = tmp_path("replace_refuse_input.txt");
+    let output_file = tmp_path("replace_refuse_output.epub");
+    fs::write(&input, "Hello world").unwrap();
+    let _ = fs::remove_file(&output_file);
+
+    let output = text_document_bin()
+        .args([
+            "replace",
+            input.to_str().unwrap(),
+            "world",
+            "Rust",
+            "-o",
+            output_file.to_str().unwrap(),
+        ])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(!output_file.exists());
+    assert_eq!(fs::read_to_string(&input).unwrap(), "Hello world");
+}
+
 // ── Cat ──────────────────────────────────────────────────────────
 
 #[test]
@@ -0,0 +1,876 @@
+//! Replicated sequence for collaborative editing.
+//!
+//! A [`Replica`] mirrors a document as a sequence of *units*, one per
+//! cursor position: a character, an inline image, or the break in front
+//! of a block. Every unit has a globally unique [`UnitId`], so edits are
+//! addressed by identity instead of by position and can be exchanged
+//! between sites in any causal order:
+//!
+//! - insertions are ordered with the RGA rule (concurrent insertions
+//!   after the same unit go newest first), and deletions leave
+//!   tombstones so later operations can still refer to the unit;
+//! - each field of a character format or of block properties is a
+//!   last-writer-wins register, compared by `(version, site)`, so
+//!   concurrent changes to different fields (bold here, italic there)
+//!   both survive.
+//!
+//! Versions are Lamport clocks: every operation carries the site that
+//! made it and a version greater than any the site had seen. Two
+//! replicas that started from the same content and applied the same set
+//! of operations hold the same sequence, whatever order the operations
+//! arrived in. [`Replica::apply`] returns the resulting changes as
+//! position-based [`Effect`]s so a document can follow along.
+//!
+//! A local operation is taken back with [`Replica::revert`], which
+//! makes new operations instead of rewinding the sequence: it deletes
+//! the units the operation inserted, inserts copies of the units it
+//! deleted, and writes back the field values it overwrote, so the
+//! edits other sites made since stay.
+
+use crate::entities::{Alignment, Block, ListStyle, MarkerType, TextDirection};
+use crate::format_runs::CharacterFormat;
+use anyhow::{Result, anyhow};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// Changed fields of a [`CharacterFormat`] or [`BlockProps`]: field name
+/// to new value, in serde's representation.
+pub type FieldChanges = BTreeMap<String, serde_json::Value>;
+
+/// Identifies a replica. Site `0` is reserved for the units of the
+/// shared starting content.
+pub type SiteId = u64;
+
+/// Identity of a unit, and the timestamp of the operation that created
+/// or last formatted it. Orders by version first, so a later write
+/// wins and ties between sites break the same way everywhere.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct UnitId {
+    pub version: u64,
+    pub site: SiteId,
+}
+
+impl UnitId {
+    /// The break in front of the first block. Always present, never
+    /// deleted.
+    pub const ROOT: UnitId = UnitId {
+        version: 0,
+        site: 0,
+    };
+}
+
+/// List membership of a block. `list` names the list on every replica
+/// (see [`Replica::new_key`]); the format is carried by each member.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ListProps {
+    pub list: UnitId,
+    pub style: ListStyle,
+    pub indent: i64,
+    pub prefix: String,
+    pub suffix: String,
+}
+
+/// Table cell a block opens. `table` names the table on every replica
+/// (see [`Replica::new_key`]).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CellProps {
+    pub table: UnitId,
+    pub row: usize,
+    pub column: usize,
+}
+
+/// Properties of the block that follows a break. The format fields
+/// mirror the `fmt_*` fields of [`Block`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockProps {
+    pub alignment: Option<Alignment>,
+    pub top_margin: Option<i64>,
+    pub bottom_margin: Option<i64>,
+    pub left_margin: Option<i64>,
+    pub right_margin: Option<i64>,
+    pub heading_level: Option<i64>,
+    pub indent: Option<i64>,
+    pub text_indent: Option<i64>,
+    pub marker: Option<MarkerType>,
+    pub tab_positions: Vec<i64>,
+    pub line_height: Option<i64>,
+    pub non_breakable_lines: Option<bool>,
+    pub direction: Option<TextDirection>,
+    pub background_color: Option<String>,
+    pub is_code_block: Option<bool>,
+    pub code_language: Option<String>,
+    pub list: Option<ListProps>,
+    pub cell: Option<CellProps>,
+}
+
+impl BlockProps {
+    /// The format fields of `block`, with the given list and cell.
+    pub fn from_block(block: &Block, list: Option<ListProps>, cell: Option<CellProps>) -> Self {
+        BlockProps {
+            alignment: block.fmt_alignment.clone(),
+            top_margin: block.fmt_top_margin,
+            bottom_margin: block.fmt_bottom_margin,
+            left_margin: block.fmt_left_margin,
+            right_margin: block.fmt_right_margin,
+            heading_level: block.fmt_heading_level,
+            indent: block.fmt_indent,
+            text_indent: block.fmt_text_indent,
+            marker: block.fmt_marker.clone(),
+            tab_positions: block.fmt_tab_positions.clone(),
+            line_height: block.fmt_line_height,
+            non_breakable_lines: block.fmt_non_breakable_lines,
+            direction: block.fmt_direction.clone(),
+            background_color: block.fmt_background_color.clone(),
+            is_code_block: block.fmt_is_code_block,
+            code_language: block.fmt_code_language.clone(),
+            list,
+            cell,
+        }
+    }
+
+    /// Copy the format fields onto `block`. List and cell are
+    /// structural and left to the caller.
+    pub fn apply_format_to(&self, block: &mut Block) {
+        block.fmt_alignment = self.alignment.clone();
+        block.fmt_top_margin = self.top_margin;
+        block.fmt_bottom_margin = self.bottom_margin;
+        block.fmt_left_margin = self.left_margin;
+        block.fmt_right_margin = self.right_margin;
+        block.fmt_heading_level = self.heading_level;
+        block.fmt_indent = self.indent;
+        block.fmt_text_indent = self.text_indent;
+        block.fmt_marker = self.marker.clone();
+        block.fmt_tab_positions = self.tab_positions.clone();
+        block.fmt_line_height = self.line_height;
+        block.fmt_non_breakable_lines = self.non_breakable_lines;
+        block.fmt_direction = self.direction.clone();
+        block.fmt_background_color = self.background_color.clone();
+        block.fmt_is_code_block = self.is_code_block;
+        block.fmt_code_language = self.code_language.clone();
+    }
+}
+
+/// What occupies one cursor position.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum UnitContent {
+    Char {
+        ch: char,
+        format: CharacterFormat,
+    },
+    Image {
+        name: String,
+        width: i64,
+        height: i64,
+        quality: i64,
+        format: CharacterFormat,
+    },
+    /// Start of a new block.
+    Break {
+        props: BlockProps,
+    },
+}
+
+impl UnitContent {
+    /// Equal apart from what format and block-property operations can
+    /// change: character formats, and block properties other than the
+    /// table cell.
+    pub fn same_content(&self, other: &UnitContent) -> bool {
+        match (self, other) {
+            (UnitContent::Char { ch: a, .. }, UnitContent::Char { ch: b, .. }) => a == b,
+            (
+                UnitContent::Image {
+                    name,
+                    width,
+                    height,
+                    quality,
+                    ..
+                },
+                UnitContent::Image {
+                    name: other_name,
+                    width: other_width,
+                    height: other_height,
+                    quality: other_quality,
+                    ..
+                },
+            ) => {
+                name == other_name
+                    && width == other_width
+                    && height == other_height
+                    && quality == other_quality
+            }
+            (UnitContent::Break { props: a }, UnitContent::Break { props: b }) => a.cell == b.cell,
+            _ => false,
+        }
+    }
+
+    /// Character format of a character or image, `None` for a break.
+    pub fn format(&self) -> Option<&CharacterFormat> {
+        match self {
+            UnitContent::Char { format, .. } | UnitContent::Image { format, .. } => Some(format),
+            UnitContent::Break { .. } => None,
+        }
+    }
+}
+
+/// One edit made on a replica, to be applied on the others.
+///
+/// `site` and `version` identify it; an insertion also names its units
+/// `(version, site)`, `(version + 1, site)` and so on.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Operation {
+    pub site: SiteId,
+    pub version: u64,
+    pub kind: OperationKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OperationKind {
+    /// Insert `units` in order, the first one right after `after`.
+    Insert {
+        after: UnitId,
+        units: Vec<UnitContent>,
+    },
+    Delete {
+        units: Vec<UnitId>,
+    },
+    /// Set fields of the character format of `units`.
+    Format {
+        units: Vec<UnitId>,
+        changes: FieldChanges,
+    },
+    /// Set fields of the properties of the block opened by the break
+    /// `block`. The cell is fixed when the break is inserted, except
+    /// on the root, which opens the document and is never inserted.
+    BlockProps {
+        block: UnitId,
+        changes: FieldChanges,
+    },
+}
+
+impl Operation {
+    fn id(&self) -> UnitId {
+        UnitId {
+            version: self.version,
+            site: self.site,
+        }
+    }
+
+    /// Highest version the operation uses.
+    fn last_version(&self) -> u64 {
+        match &self.kind {
+            OperationKind::Insert { units, .. } => self.version + units.len().max(1) as u64 - 1,
+            _ => self.version,
+        }
+    }
+}
+
+/// A change a remote operation made to the visible sequence, in
+/// positions valid at the moment it is applied. Apply the effects of
+/// one [`Replica::apply`] call in order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Effect {
+    Insert {
+        position: usize,
+        units: Vec<UnitContent>,
+    },
+    Delete {
+        position: usize,
+        length: usize,
+    },
+    /// The `length` units from `position` now have `format`.
+    Format {
+        position: usize,
+        length: usize,
+        format: CharacterFormat,
+    },
+    /// Properties of the block starting at `position` changed.
+    BlockProps {
+        position: usize,
+        props: BlockProps,
+    },
+}
+
+#[derive(Debug, Clone)]
+struct Unit {
+    id: UnitId,
+    content: UnitContent,
+    deleted: bool,
+    /// Timestamp of the last write to each format or block-property
+    /// field. Fields not listed were last written by the insertion.
+    stamps: Vec<(String, UnitId)>,
+}
+
+impl Unit {
+    fn stamp(&self, field: &str) -> UnitId {
+        self.stamps
+            .iter()
+            .find(|(f, _)| f == field)
+            .map_or(self.id, |(_, stamp)| *stamp)
+    }
+
+    /// Apply the `changes` newer than this unit's own writes. Returns
+    /// whether any applied.
+    fn write(&mut self, changes: &FieldChanges, stamp: UnitId) -> bool {
+        let newer: FieldChanges = changes
+            .iter()
+            .filter(|(field, _)| {
+                (field.as_str() != "cell" || self.id == UnitId::ROOT) && self.stamp(field) < stamp
+            })
+            .map(|(field, value)| (field.clone(), value.clone()))
+            .collect();
+        if newer.is_empty() {
+            return false;
+        }
+        let applied = match &mut self.content {
+            UnitContent::Char { format, .. } | UnitContent::Image { format, .. } => {
+                apply_changes(format, &newer)
+            }
+            UnitContent::Break { props } => apply_changes(props, &newer),
+        };
+        if applied {
+            for field in newer.into_keys() {
+                match self.stamps.iter_mut().find(|(f, _)| *f == field) {
+                    Some((_, s)) => *s = stamp,
+                    None => self.stamps.push((field, stamp)),
+                }
+            }
+        }
+        applied
+    }
+}
+
+/// The fields of `to` that differ from `from`.
+pub fn field_changes<T: Serialize>(from: &T, to: &T) -> FieldChanges {
+    let (Ok(serde_json::Value::Object(from)), Ok(serde_json::Value::Object(to))) =
+        (serde_json::to_value(from), serde_json::to_value(to))
+    else {
+        return FieldChanges::new();
+    };
+    to.into_iter()
+        .filter(|(field, value)| from.get(field) != Some(value))
+        .collect()
+}
+
+/// The current values of `fields` of `value`.
+fn field_values<'a, T: Serialize>(
+    value: &T,
+    fields: impl Iterator<Item = &'a String>,
+) -> FieldChanges {
+    let Ok(serde_json::Value::Object(current)) = serde_json::to_value(value) else {
+        return FieldChanges::new();
+    };
+    fields
+        .filter_map(|field| Some((field.clone(), current.get(field)?.clone())))
+        .collect()
+}
+
+/// Overwrite the fields of `value` named in `changes`. Leaves `value`
+/// alone and returns false if the result does not deserialize.
+fn apply_changes<T: Serialize + DeserializeOwned>(value: &mut T, changes: &FieldChanges) -> bool {
+    let Ok(serde_json::Value::Object(mut fields)) = serde_json::to_value(&*value) else {
+        return false;
+    };
+    for (field, new) in changes {
+        fields.insert(field.clone(), new.clone());
+    }
+    match serde_json::from_value(serde_json::Value::Object(fields)) {
+        Ok(updated) => {
+            *value = updated;
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+/// One site's copy of the replicated sequence.
+#[derive(Debug, Clone)]
+pub struct Replica {
+    site: SiteId,
+    clock: u64,
+    /// All units in order, tombstones included. `units[0]` is the root
+    /// break, whose content holds the first block's properties.
+    units: Vec<Unit>,
+    applied: HashSet<UnitId>,
+    version_vector: BTreeMap<SiteId, u64>,
+    /// Remote operations waiting for the units they refer to.
+    pending: Vec<Operation>,
+    /// Local format and block-property operation → the values the
+    /// fields it changed had before, per unit, for [`Replica::revert`].
+    overwritten: HashMap<UnitId, Vec<(UnitId, FieldChanges)>>,
+}
+
+impl Replica {
+    /// A replica of the shared starting content: the first block's
+    /// properties and the units after it. Every site must start from
+    /// the same content for their unit ids to agree.
+    pub fn new(site: SiteId, first_block: BlockProps, content: Vec<UnitContent>) -> Result<Self> {
+        if site == 0 {
+            return Err(anyhow!("site 0 is reserved for the starting content"));
+        }
+        let root = Unit {
+            id: UnitId::ROOT,
+            content: UnitContent::Break { props: first_block },
+            deleted: false,
+            stamps: Vec::new(),
+        };
+        let units = std::iter::once(root)
+            .chain(content.into_iter().enumerate().map(|(i, content)| {
+                let id = UnitId {
+                    version: i as u64 + 1,
+                    site: 0,
+                };
+                Unit {
+                    id,
+                    content,
+                    deleted: false,
+                    stamps: Vec::new(),
+                }
+            }))
+            .collect::<Vec<_>>();
+        Ok(Replica {
+            site,
+            clock: units.len() as u64,
+            units,
+            applied: HashSet::new(),
+            version_vector: BTreeMap::new(),
+            pending: Vec::new(),
+            overwritten: HashMap::new(),
+        })
+    }
+
+    pub fn site(&self) -> SiteId {
+        self.site
+    }
+
+    /// Highest version applied from each site, this one included.
+    pub fn version_vector(&self) -> &BTreeMap<SiteId, u64> {
+        &self.version_vector
+    }
+
+    /// Remote operations received but not applied yet because they
+    /// refer to units from operations that have not arrived.
+    pub fn pending_count(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Properties of the first block.
+    pub fn first_block(&self) -> &BlockProps {
+        match &self.units[0].content {
+            UnitContent::Break { props } => props,
+            _ => unreachable!("the root unit is a break"),
+        }
+    }
+
+    /// The visible units after the root, in order.
+    pub fn content(&self) -> impl Iterator<Item = &UnitContent> {
+        self.units[1..]
+            .iter()
+            .filter(|u| !u.deleted)
+            .map(|u| &u.content)
+    }
+
+    /// Number of visible units after the root.
+    pub fn len(&self) -> usize {
+        self.units[1..].iter().filter(|u| !u.deleted).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// A fresh id to name a list or table by. Unique across sites, like
+    /// unit ids, and never reused as one.
+    pub fn new_key(&mut self) -> UnitId {
+        self.clock += 1;
+        UnitId {
+            version: self.clock,
+            site: self.site,
+        }
+    }
+
+    // ── Local edits ──────────────────────────────────────────
+
+    /// Record a local insertion of `units` at `position`.
+    pub fn insert(&mut self, position: usize, units: Vec<UnitContent>) -> Result<Operation> {
+        if units.is_empty() {
+            return Err(anyhow!("nothing to insert"));
+        }
+        let after = if position == 0 {
+            UnitId::ROOT
+        } else {
+            self.units[self.index_of_position(position - 1)?].id
+        };
+        Ok(self.local(OperationKind::Insert { after, units }))
+    }
+
+    /// Record a local deletion of `length` units from `position`.
+    pub fn delete(&mut self, position: usize, length: usize) -> Result<Operation> {
+        let units = self.ids_in_range(position, length)?;
+        Ok(self.local(OperationKind::Delete { units }))
+    }
+
+    /// Record that the `length` units from `position` were given
+    /// `format`. Breaks in the range are skipped. The operation carries
+    /// the fields that changed on any of the units.
+    pub fn format(
+        &mut self,
+        position: usize,
+        length: usize,
+        format: &CharacterFormat,
+    ) -> Result<Operation> {
+        let units = self.ids_in_range(position, length)?;
+        let mut changes = FieldChanges::new();
+        for unit in self.units[1..]
+            .iter()
+            .filter(|u| !u.deleted)
+            .skip(position)
+            .take(length)
+        {
+            if let Some(current) = unit.content.format() {
+                changes.extend(field_changes(current, format));
+            }
+        }
+        Ok(self.local(OperationKind::Format { units, changes }))
+    }
+
+    /// Record a local change of the properties of the block starting at
+    /// `position`.
+    pub fn set_block_props(&mut self, position: usize, props: &BlockProps) -> Result<Operation> {
+        let block = if position == 0 {
+            UnitId::ROOT
+        } else {
+            let index = self.index_of_position(position - 1)?;
+            if !matches!(self.units[index].content, UnitContent::Break { .. }) {
+                return Err(anyhow!("no block starts at position {position}"));
+            }
+            self.units[index].id
+        };
+        let index = self.index_of(block).expect("visible unit");
+        let UnitContent::Break { props: current } = &self.units[index].content else {
+            unreachable!("checked above");
+        };
+        let changes = field_changes(current, props);
+        Ok(self.local(OperationKind::BlockProps { block, changes }))
+    }
+
+    /// Record local operations that take back the local operation `op`
+    /// and return them.
+    ///
+    /// The units `op` inserted that are still there are deleted, and
+    /// copies of the units it deleted are inserted where they were.
+    /// Fields it set are written back to their earlier values, unless
+    /// a later write has changed them since. Edits made after `op`, on
+    /// any site, are kept. Returns no operations when nothing of `op`
+    /// is left to take back.
+    pub fn revert(&mut self, op: &Operation) -> Result<Vec<Operation>> {
+        if op.site != self.site || !self.applied.contains(&op.id()) {
+            return Err(anyhow!(
+                "only operations made on this replica can be reverted"
+            ));
+        }
+        let kinds = match &op.kind {
+            OperationKind::Insert { units, .. } => {
+                let inserted: Vec<UnitId> = (0..units.len() as u64)
+                    .map(|i| UnitId {
+                        version: op.version + i,
+                        site: op.site,
+                    })
+                    .filter(|id| self.index_of(*id).is_some_and(|i| !self.units[i].deleted))
+                    .collect();
+                if inserted.is_empty() {
+                    Vec::new()
+                } else {
+                    vec![OperationKind::Delete { units: inserted }]
+                }
+            }
+            OperationKind::Delete { units } => {
+                // Each run of deleted units that were next to each other
+                // goes back after the unit in front of it, tombstones
+                // included, so it lands where it was.
+                let targets: HashSet<UnitId> = units.iter().copied().collect();
+                let mut runs: Vec<(usize, UnitId, Vec<UnitContent>)> = Vec::new();
+                for (index, unit) in self.units.iter().enumerate().skip(1) {
+                    if !targets.contains(&unit.id) {
+                        continue;
+                    }
+                    match runs.last_mut() {
+                        Some((last, _, contents)) if *last + 1 == index => {
+                            *last = index;
+                            contents.push(unit.content.clone());
+                        }
+                        _ => {
+                            runs.push((index, self.units[index - 1].id, vec![unit.content.clone()]))
+                        }
+                    }
+                }
+                runs.into_iter()
+                    .map(|(_, after, units)| OperationKind::Insert { after, units })
+                    .collect()
+            }
+            OperationKind::Format { .. } | OperationKind::BlockProps { .. } => {
+                let block = matches!(op.kind, OperationKind::BlockProps { .. });
+                let mut writes: Vec<(FieldChanges, Vec<UnitId>)> = Vec::new();
+                for (id, values) in self.overwritten.get(&op.id()).into_iter().flatten() {
+                    let Some(index) = self.index_of(*id) else {
+                        continue;
+                    };
+                    let unit = &self.units[index];
+                    let values: FieldChanges = values
+                        .iter()
+                        .filter(|(field, _)| unit.stamp(field) == op.id())
+                        .map(|(field, value)| (field.clone(), value.clone()))
+                        .collect();
+                    if values.is_empty() {
+                        continue;
+                    }
+                    match writes.iter_mut().find(|(same, _)| *same == values) {
+                        Some((_, ids)) => ids.push(*id),
+                        None => writes.push((values, vec![*id])),
+                    }
+                }
+                writes
+                    .into_iter()
+                    .map(|(changes, units)| {
+                        if block {
+                            OperationKind::BlockProps {
+                                block: units[0],
+                                changes,
+                            }
+                        } else {
+                            OperationKind::Format { units, changes }
+                        }
+                    })
+                    .collect()
+            }
+        };
+        Ok(kinds.into_iter().map(|kind| self.local(kind)).collect())
+    }
+
+    // ── Remote operations ────────────────────────────────────
+
+    /// Apply an operation from another site and return what changed.
+    ///
+    /// Operations may arrive in any order: one that refers to units
+    /// this replica has not seen yet is held back and applied, with its
+    /// effects, once they arrive. Operations already applied are
+    /// ignored.
+    pub fn apply(&mut self, op: Operation) -> Vec<Effect> {
+        let mut effects = Vec::new();
+        self.pending.push(op);
+        while let Some(ready) = self.pending.iter().position(|op| self.is_ready(op)) {
+            let op = self.pending.swap_remove(ready);
+            if self.applied.contains(&op.id()) {
+                continue;
+            }
+            self.clock = self.clock.max(op.last_version());
+            effects.extend(self.integrate(&op));
+        }
+        self.pending.retain(|op| !self.applied.contains(&op.id()));
+        effects
+    }
+
+    fn is_ready(&self, op: &Operation) -> bool {
+        match &op.kind {
+            OperationKind::Insert { after, .. } => self.contains(*after),
+            OperationKind::Delete { units } | OperationKind::Format { units, .. } => {
+                units.iter().all(|id| self.contains(*id))
+            }
+            OperationKind::BlockProps { block, .. } => self.contains(*block),
+        }
+    }
+
+    fn contains(&self, id: UnitId) -> bool {
+        self.units.iter().any(|u| u.id == id)
+    }
+
+    // ── Internals ────────────────────────────────────────────
+
+    /// Stamp a local operation, remember what it overwrites and apply
+    /// it.
+    fn local(&mut self, kind: OperationKind) -> Operation {
+        let op = self.stamp_local(kind);
+        let before: Vec<(UnitId, FieldChanges)> = match &op.kind {
+            OperationKind::Format { units, changes } => units
+                .iter()
+                .filter_map(|id| {
+                    let format = self.units[self.index_of(*id)?].content.format()?;
+                    Some((*id, field_values(format, changes.keys())))
+                })
+                .collect(),
+            OperationKind::BlockProps { block, changes } => self
+                .index_of(*block)
+                .and_then(|index| match &self.units[index].content {
+                    UnitContent::Break { props } => {
+                        Some((*block, field_values(props, changes.keys())))
+                    }
+                    _ => None,
+                })
+                .into_iter()
+                .collect(),
+            _ => Vec::new(),
+        };
+        if !before.is_empty() {
+            self.overwritten.insert(op.id(), before);
+        }
+        self.integrate(&op);
+        op
+    }
+
+    fn stamp_local(&mut self, kind: OperationKind) -> Operation {
+        let op = Operation {
+            site: self.site,
+            version: self.clock + 1,
+            kind,
+        };
+        self.clock = op.last_version();
+        op
+    }
+
+    /// Apply `op` to the sequence and return its effects.
+    fn integrate(&mut self, op: &Operation) -> Vec<Effect> {
+        self.applied.insert(op.id());
+        let seen = self.version_vector.entry(op.site).or_default();
+        *seen = (*seen).max(op.last_version());
+        let stamp = op.id();
+        match &op.kind {
+            OperationKind::Insert { after, units } => {
+                let origin = self.index_of(*after).expect("checked by is_ready");
+                // RGA: skip the units inserted after the same origin by
+                // later operations.
+                let mut index = origin + 1;
+                while index < self.units.len() && self.units[index].id > stamp {
+                    index += 1;
+                }
+                let position = self.position_of_index(index);
+                let new_units = units.iter().enumerate().map(|(i, content)| {
+                    let id = UnitId {
+                        version: op.version + i as u64,
+                        site: op.site,
+                    };
+                    Unit {
+                        id,
+                        content: content.clone(),
+                        deleted: false,
+                        stamps: Vec::new(),
+                    }
+                });
+                self.units.splice(index..index, new_units);
+                vec![Effect::Insert {
+                    position,
+                    units: units.clone(),
+                }]
+            }
+            OperationKind::Delete { units } => {
+                let targets: HashSet<UnitId> = units.iter().copied().collect();
+                let mut runs: Vec<(usize, usize)> = Vec::new();
+                let mut position = 0;
+                for unit in self.units[1..].iter_mut() {
+                    if unit.deleted {
+                        continue;
+                    }
+                    if targets.contains(&unit.id) {
+                        unit.deleted = true;
+                        match runs.last_mut() {
+                            Some((start, length)) if *start + *length == position => *length += 1,
+                            _ => runs.push((position, 1)),
+                        }
+                    }
+                    position += 1;
+                }
+                // Last run first so earlier positions stay valid.
+                runs.into_iter()
+                    .rev()
+                    .map(|(position, length)| Effect::Delete { position, length })
+                    .collect()
+            }
+            OperationKind::Format { units, changes } => {
+                let targets: HashSet<UnitId> = units.iter().copied().collect();
+                let mut runs: Vec<(usize, usize, CharacterFormat)> = Vec::new();
+                let mut position = 0;
+                for unit in self.units[1..].iter_mut() {
+                    if targets.contains(&unit.id)
+                        && unit.content.format().is_some()
+                        && unit.write(changes, stamp)
+                        && !unit.deleted
+                    {
+                        let format = unit.content.format().cloned().unwrap_or_default();
+                        match runs.last_mut() {
+                            Some((start, length, run))
+                                if *start + *length == position && *run == format =>
+                            {
+                                *length += 1
+                            }
+                            _ => runs.push((position, 1, format)),
+                        }
+                    }
+                    if !unit.deleted {
+                        position += 1;
+                    }
+                }
+                runs.into_iter()
+                    .map(|(position, length, format)| Effect::Format {
+                        position,
+                        length,
+                        format,
+                    })
+                    .collect()
+            }
+            OperationKind::BlockProps { block, changes } => {
+                let index = self.index_of(*block).expect("checked by is_ready");
+                let unit = &mut self.units[index];
+                if !unit.write(changes, stamp) || unit.deleted {
+                    return Vec::new();
+                }
+                let UnitContent::Break { props } = &unit.content else {
+                    return Vec::new();
+                };
+                let props = props.clone();
+                let position = if index == 0 {
+                    0
+                } else {
+                    self.position_of_index(index) + 1
+                };
+                vec![Effect::BlockProps { position, props }]
+            }
+        }
+    }
+
+    fn index_of(&self, id: UnitId) -> Option<usize> {
+        self.units.iter().position(|u| u.id == id)
+    }
+
+    /// Index in `units` of the visible unit at `position`.
+    fn index_of_position(&self, position: usize) -> Result<usize> {
+        self.units
+            .iter()
+            .enumerate()
+            .skip(1)
+            .filter(|(_, u)| !u.deleted)
+            .nth(position)
+            .map(|(i, _)| i)
+            .ok_or_else(|| anyhow!("position {position} is past the end"))
+    }
+
+    /// Number of visible units before `index`, the root excluded.
+    fn position_of_index(&self, index: usize) -> usize {
+        self.units[1..index].iter().filter(|u| !u.deleted).count()
+    }
+
+    fn ids_in_range(&self, position: usize, length: usize) -> Result<Vec<UnitId>> {
+        let ids: Vec<UnitId> = self.units[1..]
+            .iter()
+            .filter(|u| !u.deleted)
+            .skip(position)
+            .take(length)
+            .map(|u| u.id)
+            .collect();
+        if ids.len() != length {
+            return Err(anyhow!(
+                "range {position}..{} is past the end",
+                position + length
+            ));
+        }
+        Ok(ids)
+    }
+}
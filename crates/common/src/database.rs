@@ -1,9 +1,14 @@
 // Generated by Qleany v1.4.8 from database.tera
 
+pub mod block_delta;
 pub mod block_offset_index;
 pub mod db_context;
+pub mod edit_record;
+pub mod native_format;
 pub mod rope_helpers;
 pub mod rope_store;
+pub mod store_delta;
+pub mod tracked;
 pub mod transactions;
 
 /// Active storage backend.
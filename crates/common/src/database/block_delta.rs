@@ -0,0 +1,245 @@
+//! Minimal inverse deltas for edits confined to a single block.
+//!
+//! Whole-document snapshots (`snapshot_document` / `restore_document`)
+//! are cheap to take but expensive to live with: the first write after
+//! taking one has to copy the shared offset index, and turning one into
+//! a [`StoreDelta`](crate::database::store_delta::StoreDelta) compares
+//! every table. For edits that only touch one block's text, runs and
+//! images, a `BlockDelta` records just the replaced text and that
+//! block's prior runs and images, so undo cost scales with the edit
+//! instead of the document.
+
+use crate::database::Store;
+use crate::database::rope_helpers::{
+    rope_delete_in_block, rope_insert_in_block, rope_positions_match_flow,
+};
+use crate::entities::Block;
+use crate::format_runs::{FormatRun, ImageAnchor};
+use crate::types::EntityId;
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+/// Prior state of one block plus the bookkeeping an in-block edit
+/// touches elsewhere (document character count, and the stored
+/// `document_position` of later blocks when the rope is not the source
+/// of truth for positions).
+///
+/// The text is kept as a splice: the edit replaced `removed`, starting
+/// at byte `at` of the block, with `inserted_len` bytes. Later blocks
+/// are not recorded; undo moves every block after this one by the
+/// characters the splice added, the same single shift a
+/// [`PositionShift`](crate::database::store_delta::PositionShift)
+/// describes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockDelta {
+    pub block: Block,
+    pub at: u32,
+    pub removed: String,
+    pub inserted_len: u32,
+    pub format_runs: Vec<FormatRun>,
+    pub block_images: Vec<ImageAnchor>,
+    pub doc_id: EntityId,
+    pub character_count: i64,
+}
+
+impl BlockDelta {
+    /// Capture `block`'s runs and images, and the bytes in `removed`,
+    /// before an edit that replaces those bytes with `inserted_len`
+    /// others.
+    pub fn capture(
+        store: &Store,
+        block: &Block,
+        doc_id: EntityId,
+        character_count: i64,
+        removed: Range<u32>,
+        inserted_len: u32,
+    ) -> Self {
+        let format_runs = store
+            .format_runs
+            .read()
+            .unwrap()
+            .get(&block.id)
+            .cloned()
+            .unwrap_or_default();
+        let block_images = store
+            .block_images
+            .read()
+            .unwrap()
+            .get(&block.id)
+            .cloned()
+            .unwrap_or_default();
+        BlockDelta {
+            block: block.clone(),
+            at: removed.start,
+            removed: block_slice(store, block.id, removed),
+            inserted_len,
+            format_runs,
+            block_images,
+            doc_id,
+            character_count,
+        }
+    }
+
+    /// The inverse splice, with the block's runs and images as they
+    /// are now. Taken just before an undo restores `self`, it is the
+    /// state a redo brings back (see
+    /// [`EditRecord::Block`](crate::database::edit_record::EditRecord::Block)).
+    pub fn recapture(&self, store: &Store) -> Self {
+        let block = store
+            .blocks
+            .read()
+            .unwrap()
+            .get(&self.block.id)
+            .cloned()
+            .unwrap_or_else(|| self.block.clone());
+        let character_count = store
+            .documents
+            .read()
+            .unwrap()
+            .get(&self.doc_id)
+            .map_or(self.character_count, |doc| doc.character_count);
+        Self::capture(
+            store,
+            &block,
+            self.doc_id,
+            character_count,
+            self.inserted(),
+            self.removed.len() as u32,
+        )
+    }
+
+    /// Fold `later`, an edit of the same block made right after this
+    /// one, into this delta so that undoing it reverts both. Returns
+    /// false, leaving `self` unchanged, when `later` is in another
+    /// block or does not overlap or touch the bytes this edit inserted.
+    pub fn absorb(&mut self, later: &BlockDelta) -> bool {
+        let inserted = self.inserted();
+        let later_end = later.at + later.removed.len() as u32;
+        if later.block.id != self.block.id || later.at > inserted.end || later_end < inserted.start
+        {
+            return false;
+        }
+        // `later.removed` is text after this edit: whatever of it lies
+        // outside the bytes this edit inserted was there before too.
+        let prefix = (inserted.start.saturating_sub(later.at) as usize).min(later.removed.len());
+        let suffix = (inserted.end.saturating_sub(later.at) as usize).min(later.removed.len());
+        let mut removed = String::with_capacity(prefix + self.removed.len());
+        removed.push_str(&later.removed[..prefix]);
+        removed.push_str(&self.removed);
+        removed.push_str(&later.removed[suffix..]);
+        let start = inserted.start.min(later.at);
+        let end = inserted.end.max(later_end);
+        self.inserted_len = end - start - later.removed.len() as u32 + later.inserted_len;
+        self.at = start;
+        self.removed = removed;
+        true
+    }
+
+    /// Byte range in the block the edit's text occupies now.
+    fn inserted(&self) -> Range<u32> {
+        self.at..self.at + self.inserted_len
+    }
+
+    /// Characters the edit since [`capture`](Self::capture) added to
+    /// the block, negative when it removed some. Read it before
+    /// [`restore_content`](Self::restore_content): undo moves the
+    /// document's character count and later blocks' positions back by
+    /// this amount rather than resetting them, so edits made elsewhere
+    /// in the meantime are kept.
+    pub fn chars_added(&self, store: &Store) -> i64 {
+        block_slice(store, self.block.id, self.inserted())
+            .chars()
+            .count() as i64
+            - self.removed.chars().count() as i64
+    }
+    /// The blocks now after this one, whose stored positions an undo
+    /// moves back by [`chars_added`](Self::chars_added). Looked up at
+    /// undo time rather than recorded, so blocks added since by edits
+    /// on other undo stacks move too. Empty when
+    /// the rope is the source of truth for positions.
+    pub fn blocks_after(&self, store: &Store) -> Vec<EntityId> {
+        if rope_positions_match_flow(store) {
+            return Vec::new();
+        }
+        let blocks = store.blocks.read().unwrap();
+        let Some(start) = blocks.get(&self.block.id).map(|b| b.document_position) else {
+            return Vec::new();
+        };
+        blocks
+            .values()
+            .filter(|b| b.id != self.block.id && b.document_position > start)
+            .map(|b| b.id)
+            .collect()
+    }
+
+    /// Approximate number of bytes this delta holds, for undo memory
+    /// budgets.
+    pub fn approximate_size(&self) -> usize {
+        use std::mem::size_of;
+        size_of::<Self>()
+            + self.removed.len()
+            + self.format_runs.len() * size_of::<FormatRun>()
+            + self.block_images.len() * size_of::<ImageAnchor>()
+    }
+
+    /// Put the removed text, runs and images back. Entity rows (`block`,
+    /// later blocks, the document's character count) are left to the
+    /// caller so the writes go through its unit of work.
+    pub fn restore_content(&self, store: &Store) {
+        let inserted = self.inserted();
+        rope_delete_in_block(store, self.block.id, inserted.start, inserted.end);
+        rope_insert_in_block(store, self.block.id, self.at, &self.removed);
+        store
+            .format_runs
+            .write()
+            .unwrap()
+            .insert(self.block.id, self.format_runs.clone());
+        store
+            .block_images
+            .write()
+            .unwrap()
+            .insert(self.block.id, self.block_images.clone());
+    }
+
+    /// Put the captured state back directly on `store`, entity rows
+    /// included, the way an undo through a unit of work would. Used
+    /// on scratch stores that hold a copy of the document.
+    pub fn restore_into(&self, store: &Store) {
+        let added = self.chars_added(store);
+        let after = self.blocks_after(store);
+        self.restore_content(store);
+        let mut blocks = store.blocks.write().unwrap();
+        let mut block = self.block.clone();
+        if let Some(current) = blocks.get(&block.id) {
+            block.document_position = current.document_position;
+        }
+        blocks.insert(block.id, block);
+        for id in &after {
+            if let Some(b) = blocks.get_mut(id) {
+                b.document_position -= added;
+            }
+        }
+        drop(blocks);
+        if let Some(doc) = store.documents.write().unwrap().get_mut(&self.doc_id) {
+            doc.character_count -= added;
+        }
+    }
+}
+
+/// Bytes `range` of the block's content, read from the rope without
+/// materializing the rest of the block. Empty for blocks not in the
+/// offset index.
+fn block_slice(store: &Store, block_id: EntityId, range: Range<u32>) -> String {
+    if range.is_empty() {
+        return String::new();
+    }
+    let Some((start, _)) = store.block_offsets.read().unwrap().range_of_block(block_id) else {
+        return String::new();
+    };
+    store
+        .rope
+        .read()
+        .unwrap()
+        .byte_slice((start + range.start) as usize..(start + range.end) as usize)
+        .to_string()
+}
@@ -16,12 +16,13 @@
 
 use crate::types::EntityId;
 use im::HashMap as ImHashMap;
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
 /// Discriminates real blocks from table-anchor sentinels in the
 /// offset index. The wrapped `EntityId` is the corresponding entity's
 /// id (a Block id or a Table id).
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum OffsetMarker {
     Block(EntityId),
     TableAnchor(EntityId),
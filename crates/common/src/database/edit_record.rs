@@ -0,0 +1,136 @@
+//! Serializable records of what undo commands changed.
+//!
+//! Commands undo and redo themselves against the live store, which is
+//! no use when saving the undo history: walking the history that way
+//! would change the document being saved. Instead every command can
+//! hand out [`EditRecord`]s, recorded when it ran, that move a copy of
+//! the store across it. Replaying them on a
+//! [`RopeStoreSnapshot`] yields the state on either side of each
+//! record, from which the saved [`StoreDelta`]s are computed.
+
+use crate::database::Store;
+use crate::database::block_delta::BlockDelta;
+use crate::database::rope_store::{RopeStore, RopeStoreSnapshot};
+use crate::database::store_delta::StoreDelta;
+use crate::format_runs::{
+    CharacterFormat, FormatRun, capture_image_formats_in_range, capture_runs_in_range, splice_range,
+};
+use crate::types::EntityId;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+/// One change a command made. While the command can be undone, a
+/// record describes how to undo it; once undone, how to redo it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum EditRecord {
+    /// Every change, reverted to undo and applied to redo.
+    Store(StoreDelta),
+    /// One block's content, runs and images as they are on the other
+    /// side of the command.
+    Block(BlockDelta),
+    /// The formats of a byte range of one block as they are on the
+    /// other side of the command.
+    Formats(RangeFormats),
+}
+
+impl EditRecord {
+    /// `snap` with the command this record belongs to undone.
+    pub fn undo(&self, snap: &RopeStoreSnapshot) -> Result<RopeStoreSnapshot> {
+        match self {
+            EditRecord::Store(delta) => delta.revert(snap),
+            EditRecord::Block(delta) => Ok(on_copy(snap, |store| delta.restore_into(store))),
+            EditRecord::Formats(formats) => Ok(on_copy(snap, |store| formats.restore(store))),
+        }
+    }
+
+    /// `snap` with the command this record belongs to redone.
+    /// The record as a delta between `before` and `after`, the states on
+    /// either side of the command. A store delta is kept as recorded;
+    /// the others change one block, so comparing the states finds only
+    /// that block's change.
+    pub fn to_delta(&self, before: &RopeStoreSnapshot, after: &RopeStoreSnapshot) -> StoreDelta {
+        match self {
+            EditRecord::Store(delta) => delta.clone(),
+            _ => StoreDelta::between(before, after),
+        }
+    }
+
+    pub fn redo(&self, snap: &RopeStoreSnapshot) -> Result<RopeStoreSnapshot> {
+        match self {
+            EditRecord::Store(delta) => delta.apply(snap),
+            _ => self.undo(snap),
+        }
+    }
+}
+
+/// Run `edit` on a scratch store holding `snap` and return the result.
+fn on_copy(snap: &RopeStoreSnapshot, edit: impl FnOnce(&Store)) -> RopeStoreSnapshot {
+    let store = RopeStore::new();
+    store.restore(snap);
+    edit(&store);
+    store.snapshot()
+}
+
+/// Format runs and image formats of a byte range inside one block, for
+/// undoing format-only edits without copying the block's text.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RangeFormats {
+    pub block_id: EntityId,
+    pub byte_range: (u32, u32),
+    pub runs: Vec<FormatRun>,
+    pub image_formats: Vec<(u32, CharacterFormat)>,
+}
+
+impl RangeFormats {
+    /// Capture the formats of `byte_range` in `block_id`.
+    pub fn capture(store: &Store, block_id: EntityId, byte_range: (u32, u32)) -> Self {
+        let (start, end) = byte_range;
+        let runs = store
+            .format_runs
+            .read()
+            .unwrap()
+            .get(&block_id)
+            .map(|runs| capture_runs_in_range(runs, start, end))
+            .unwrap_or_default();
+        let image_formats = store
+            .block_images
+            .read()
+            .unwrap()
+            .get(&block_id)
+            .map(|images| capture_image_formats_in_range(images, start, end))
+            .unwrap_or_default();
+        RangeFormats {
+            block_id,
+            byte_range,
+            runs,
+            image_formats,
+        }
+    }
+
+    /// Put the captured formats back over the range.
+    pub fn restore(&self, store: &Store) {
+        let (start, end) = self.byte_range;
+        {
+            let mut runs_map = store.format_runs.write().unwrap();
+            let runs = runs_map.entry(self.block_id).or_default();
+            splice_range(runs, start..end, self.runs.clone());
+        }
+        let mut images_map = store.block_images.write().unwrap();
+        if let Some(images) = images_map.get_mut(&self.block_id) {
+            for (byte_offset, format) in &self.image_formats {
+                if let Some(img) = images.iter_mut().find(|i| i.byte_offset == *byte_offset) {
+                    img.format = format.clone();
+                }
+            }
+        }
+    }
+
+    /// Approximate number of bytes this capture holds, for undo memory
+    /// budgets.
+    pub fn approximate_size(&self) -> usize {
+        use std::mem::size_of;
+        size_of::<Self>()
+            + self.runs.len() * size_of::<FormatRun>()
+            + self.image_formats.len() * size_of::<(u32, CharacterFormat)>()
+    }
+}
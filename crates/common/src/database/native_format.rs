@@ -0,0 +1,339 @@
+//! Lossless native (de)serialization of the whole store.
+//!
+//! The native format is a versioned JSON document holding everything a
+//! [`RopeStoreSnapshot`] holds — rope contents, every entity table,
+//! per-block `FormatRun`s / `ImageAnchor`s, the block offset index and
+//! the ID counters — so that a save → load cycle reproduces the exact
+//! same entity tree. Unlike the Markdown/HTML/DOCX exporters nothing is
+//! dropped (frames, table formats, list prefixes, resources, ...).
+//!
+//! Every file carries a `format` tag and a `version` number. Readers
+//! accept any version up to [`NATIVE_FORMAT_VERSION`] and upgrade older
+//! payloads in [`decode`]; files written by a newer version are rejected
+//! instead of being half-read.
+//!
+//! A file may also carry the undo history as a list of [`StoreDelta`]s.
+//! The history has its own [`UNDO_HISTORY_VERSION`] and is optional:
+//! a history that is missing, from an unknown version, malformed, or
+//! that does not replay cleanly against the loaded document is dropped
+//! by [`decode_with_history`] and the document itself still loads.
+
+use crate::database::Store;
+use crate::database::block_offset_index::{BlockOffsetIndex, OffsetMarker};
+use crate::database::rope_store::RopeStoreSnapshot;
+use crate::database::store_delta::StoreDelta;
+use crate::entities::*;
+use crate::format_runs::{FormatRun, ImageAnchor};
+use crate::types::{EntityId, HasId, ROOT_ENTITY_ID};
+use anyhow::{Context, Result, anyhow, bail};
+use im::HashMap;
+use ropey::Rope;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap as StdHashMap;
+
+/// Value of the `format` tag written at the top of every native file.
+pub const NATIVE_FORMAT_TAG: &str = "text-document-native";
+
+/// Current native format version. Bump when the payload shape changes
+/// and teach [`decode`] how to upgrade the previous version.
+pub const NATIVE_FORMAT_VERSION: u32 = 1;
+
+/// Current undo history version. Histories written with any other
+/// version are discarded on load rather than interpreted.
+pub const UNDO_HISTORY_VERSION: u32 = 2;
+
+/// Undo history saved alongside a native document. Reverting the
+/// deltas of `undo` backwards, newest first, walks the saved document
+/// back to the oldest recorded state; applying `redo` forwards, first
+/// entry first, walks it to the newest undone state.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct NativeUndoHistory {
+    /// Undoable edits, oldest first.
+    pub undo: Vec<NativeUndoEntry>,
+    /// Redoable edits, next to redo first.
+    pub redo: Vec<NativeUndoEntry>,
+}
+
+/// One saved edit: its user-facing description and the changes it
+/// made, one per step of the command, first step first.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NativeUndoEntry {
+    pub description: String,
+    pub deltas: Vec<StoreDelta>,
+}
+
+/// Envelope of the saved history, versioned separately from the
+/// document so an incompatible history can be skipped on its own.
+#[derive(Debug, Serialize, Deserialize)]
+struct NativeUndoHistoryEnvelope {
+    version: u32,
+    #[serde(flatten)]
+    history: NativeUndoHistory,
+}
+
+/// Envelope read first so the version can be checked before the body
+/// is interpreted.
+#[derive(Debug, Deserialize)]
+struct NativeHeader {
+    format: String,
+    version: u32,
+}
+
+/// Version 1 payload. Entity tables are stored as id-sorted vectors so
+/// the output is deterministic for identical documents.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct NativeDocumentV1 {
+    format: String,
+    version: u32,
+    rope: String,
+    roots: Vec<Root>,
+    documents: Vec<Document>,
+    frames: Vec<Frame>,
+    blocks: Vec<Block>,
+    lists: Vec<List>,
+    resources: Vec<Resource>,
+    tables: Vec<Table>,
+    table_cells: Vec<TableCell>,
+    #[serde(default)]
+    format_runs: Vec<(EntityId, Vec<FormatRun>)>,
+    #[serde(default)]
+    block_images: Vec<(EntityId, Vec<ImageAnchor>)>,
+    block_offsets: Vec<(OffsetMarker, u32)>,
+    #[serde(default)]
+    counters: Vec<(String, EntityId)>,
+    /// Kept as raw JSON so a history this reader cannot interpret does
+    /// not prevent the document from loading.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    undo_history: Option<serde_json::Value>,
+}
+
+fn sorted_values<T: Clone + HasId>(map: &HashMap<EntityId, T>) -> Vec<T> {
+    let mut values: Vec<T> = map.values().cloned().collect();
+    values.sort_by_key(|v| v.id());
+    values
+}
+
+fn sorted_entries<T: Clone>(map: &HashMap<EntityId, Vec<T>>) -> Vec<(EntityId, Vec<T>)> {
+    let mut entries: Vec<(EntityId, Vec<T>)> = map
+        .iter()
+        .filter(|(_, v)| !v.is_empty())
+        .map(|(k, v)| (*k, v.clone()))
+        .collect();
+    entries.sort_by_key(|(k, _)| *k);
+    entries
+}
+
+fn to_map<T: Clone + HasId>(values: Vec<T>) -> HashMap<EntityId, T> {
+    values.into_iter().map(|v| (v.id(), v)).collect()
+}
+
+/// Serialize the current store contents to the native format.
+pub fn encode(store: &Store) -> Result<String> {
+    Ok(serde_json::to_string(&document_v1(&store.snapshot()))?)
+}
+
+/// Serialize `snap` together with `history`, so that
+/// [`decode_with_history`] can restore both. The history's deltas must
+/// lead away from `snap`; a history that does not replay from it is an
+/// error.
+pub fn encode_with_history(
+    snap: &RopeStoreSnapshot,
+    history: &NativeUndoHistory,
+) -> Result<String> {
+    // Loading drops a history that doesn't replay, so refuse to write one.
+    check_history(history, snap).context("the undo history does not match the document")?;
+    let mut doc = document_v1(snap);
+    doc.undo_history = Some(serde_json::to_value(NativeUndoHistoryEnvelope {
+        version: UNDO_HISTORY_VERSION,
+        history: history.clone(),
+    })?);
+    Ok(serde_json::to_string(&doc)?)
+}
+
+fn is_known(snap: &RopeStoreSnapshot, marker: OffsetMarker) -> bool {
+    match marker {
+        OffsetMarker::Block(id) => snap.blocks.contains_key(&id),
+        OffsetMarker::TableAnchor(id) => snap.tables.contains_key(&id),
+    }
+}
+
+fn document_v1(snap: &RopeStoreSnapshot) -> NativeDocumentV1 {
+    let mut counters: Vec<(String, EntityId)> =
+        snap.counters.iter().map(|(k, v)| (k.clone(), *v)).collect();
+    counters.sort();
+
+    NativeDocumentV1 {
+        format: NATIVE_FORMAT_TAG.to_string(),
+        version: NATIVE_FORMAT_VERSION,
+        rope: snap.rope.to_string(),
+        roots: sorted_values(&snap.roots),
+        documents: sorted_values(&snap.documents),
+        frames: sorted_values(&snap.frames),
+        blocks: sorted_values(&snap.blocks),
+        lists: sorted_values(&snap.lists),
+        resources: sorted_values(&snap.resources),
+        tables: sorted_values(&snap.tables),
+        table_cells: sorted_values(&snap.table_cells),
+        format_runs: sorted_entries(&snap.format_runs),
+        block_images: sorted_entries(&snap.block_images),
+        block_offsets: snap.block_offsets.entries.to_vec(),
+        counters,
+        undo_history: None,
+    }
+}
+
+/// Parse a native payload into a store snapshot, upgrading older
+/// versions. Does not touch the live store.
+pub fn decode(data: &str) -> Result<RopeStoreSnapshot> {
+    Ok(decode_with_history(data)?.0)
+}
+
+/// Like [`decode`], also returning the saved undo history when there is
+/// one that this reader supports and that replays cleanly against the
+/// decoded document. Any other history is dropped with a warning; only
+/// problems with the document itself are errors.
+pub fn decode_with_history(data: &str) -> Result<(RopeStoreSnapshot, Option<NativeUndoHistory>)> {
+    let header: NativeHeader =
+        serde_json::from_str(data).map_err(|e| anyhow!("Not a native document: {e}"))?;
+    if header.format != NATIVE_FORMAT_TAG {
+        bail!("Unknown native format tag '{}'", header.format);
+    }
+    let mut doc: NativeDocumentV1 = match header.version {
+        1 => serde_json::from_str(data)?,
+        v if v > NATIVE_FORMAT_VERSION => bail!(
+            "Native format version {v} is newer than the supported version {NATIVE_FORMAT_VERSION}"
+        ),
+        v => bail!("Unknown native format version {v}"),
+    };
+    let raw_history = doc.undo_history.take();
+    let snap = snapshot_from_v1(doc)?;
+    let history = raw_history.and_then(|raw| match history_from_json(raw, &snap) {
+        Ok(history) => Some(history),
+        Err(e) => {
+            log::warn!("Discarding saved undo history: {e}");
+            None
+        }
+    });
+    Ok((snap, history))
+}
+
+fn history_from_json(
+    raw: serde_json::Value,
+    snap: &RopeStoreSnapshot,
+) -> Result<NativeUndoHistory> {
+    let version = raw
+        .get("version")
+        .and_then(serde_json::Value::as_u64)
+        .ok_or_else(|| anyhow!("missing version"))?;
+    if version != UNDO_HISTORY_VERSION as u64 {
+        bail!("unsupported version {version} (expected {UNDO_HISTORY_VERSION})");
+    }
+    let envelope: NativeUndoHistoryEnvelope = serde_json::from_value(raw)?;
+    check_history(&envelope.history, snap)?;
+    Ok(envelope.history)
+}
+
+/// Check that `history` replays cleanly in both directions from `snap`.
+fn check_history(history: &NativeUndoHistory, snap: &RopeStoreSnapshot) -> Result<()> {
+    let mut state = snap.clone();
+    for delta in history
+        .undo
+        .iter()
+        .rev()
+        .flat_map(|e| e.deltas.iter().rev())
+    {
+        state = delta.revert(&state)?;
+    }
+    let mut state = snap.clone();
+    for delta in history.redo.iter().flat_map(|e| &e.deltas) {
+        state = delta.apply(&state)?;
+    }
+    Ok(())
+}
+
+fn snapshot_from_v1(doc: NativeDocumentV1) -> Result<RopeStoreSnapshot> {
+    let rope = Rope::from_str(&doc.rope);
+
+    let mut block_offsets = BlockOffsetIndex::new();
+    for (marker, byte_start) in doc.block_offsets {
+        if block_offsets
+            .entries
+            .last()
+            .is_some_and(|(_, last)| byte_start < *last)
+        {
+            bail!("Native document has an unsorted block offset index");
+        }
+        block_offsets.push(marker, byte_start);
+    }
+    block_offsets.set_total_bytes(rope.len_bytes() as u32);
+
+    let snap = RopeStoreSnapshot {
+        rope,
+        roots: to_map(doc.roots),
+        documents: to_map(doc.documents),
+        frames: to_map(doc.frames),
+        blocks: to_map(doc.blocks),
+        lists: to_map(doc.lists),
+        resources: to_map(doc.resources),
+        tables: to_map(doc.tables),
+        table_cells: to_map(doc.table_cells),
+        format_runs: doc.format_runs.into_iter().collect(),
+        block_images: doc.block_images.into_iter().collect(),
+        block_offsets,
+        counters: doc.counters.into_iter().collect::<StdHashMap<_, _>>(),
+        journal: 0,
+    };
+    validate(&snap)?;
+    Ok(snap)
+}
+
+/// Structural sanity checks so a damaged file fails loudly instead of
+/// producing a document that panics later.
+fn validate(snap: &RopeStoreSnapshot) -> Result<()> {
+    let root = snap
+        .roots
+        .get(&ROOT_ENTITY_ID)
+        .ok_or_else(|| anyhow!("Native document has no root entity"))?;
+    let document = snap
+        .documents
+        .get(&root.document)
+        .ok_or_else(|| anyhow!("Native document root points to a missing document"))?;
+    if document.frames.is_empty() {
+        bail!("Native document has no frames");
+    }
+    for frame_id in &document.frames {
+        let frame = snap
+            .frames
+            .get(frame_id)
+            .ok_or_else(|| anyhow!("Native document references missing frame {frame_id}"))?;
+        if let Some(missing) = frame.blocks.iter().find(|b| !snap.blocks.contains_key(b)) {
+            bail!("Frame {frame_id} references missing block {missing}");
+        }
+    }
+    for (marker, _) in snap.block_offsets.entries.iter() {
+        if !is_known(snap, *marker) {
+            bail!("Block offset index references unknown entity {marker:?}");
+        }
+    }
+    let total = snap.block_offsets.total_bytes();
+    for (block_id, runs) in &snap.format_runs {
+        let (start, end) = snap
+            .block_offsets
+            .range_of_block(*block_id)
+            .ok_or_else(|| anyhow!("Format runs attached to unindexed block {block_id}"))?;
+        if runs.iter().any(|r| r.byte_end > end - start || end > total) {
+            bail!("Format runs of block {block_id} exceed its text");
+        }
+    }
+    Ok(())
+}
+
+/// Replace the live store contents with `snap`. ID counters keep the
+/// larger of the current and loaded values so IDs stay unique.
+pub fn load_into(store: &Store, mut snap: RopeStoreSnapshot) {
+    for (name, current) in store.counters.read().unwrap().iter() {
+        let loaded = snap.counters.entry(name.clone()).or_insert(*current);
+        *loaded = (*loaded).max(*current);
+    }
+    store.restore(&snap);
+}
@@ -105,6 +105,18 @@ pub fn rope_positions_match_flow(store: &Store) -> bool {
     indexed_block_count == total_block_count
 }
 
+/// Take `blocks`' `document_position` from the rope when it mirrors the
+/// flow: main-flow edits skip refreshing the stored field then, so it
+/// can be stale. Left as stored in documents with tables.
+pub fn refresh_block_positions(blocks: &mut [Block], store: &Store) {
+    if !store.tables.read().unwrap().is_empty() || !rope_positions_match_flow(store) {
+        return;
+    }
+    for block in blocks {
+        block.document_position = block_document_position(block, store);
+    }
+}
+
 /// Locate which block contains a given absolute char position in the
 /// document, returning `(block_id, char_offset_in_block, block_char_start)`
 /// in O(log n) using the rope + `BlockOffsetIndex` instead of an O(N)
@@ -260,9 +272,12 @@ pub fn rope_flat_text_if_simple(store: &Store, top_frame_count: usize) -> Option
 /// Reset the rope to empty and clear `block_offsets`. Called by
 /// importers when they replace the entire document content.
 pub fn rope_reset(store: &Store) {
-    *store.rope.write().unwrap() = ropey::Rope::new();
-    *store.block_offsets.write().unwrap() =
-        crate::database::block_offset_index::BlockOffsetIndex::new();
+    store.rope.write().unwrap().replace(ropey::Rope::new());
+    store
+        .block_offsets
+        .write()
+        .unwrap()
+        .replace(crate::database::block_offset_index::BlockOffsetIndex::new());
 }
 
 /// Append `text` to the end of the rope and register `block_id` at
@@ -600,13 +615,28 @@ pub fn rope_insert_table_anchor(
     table_id: EntityId,
     target_block_id: EntityId,
     after: bool,
+) {
+    rope_insert_table_anchor_at(store, table_id, OffsetMarker::Block(target_block_id), after);
+}
+
+/// Like [`rope_insert_table_anchor`], next to any entry of the offset
+/// index: a table inserted from inside another table's cell goes
+/// right after that table's own anchor, not after the cell's block
+/// (which sits in the cell region at the end of the rope).
+///
+/// No-op if `target` is not in the index.
+pub fn rope_insert_table_anchor_at(
+    store: &Store,
+    table_id: EntityId,
+    target: OffsetMarker,
+    after: bool,
 ) {
     const SENTINEL: &str = "\u{FFFC}"; // 3 bytes
     const SENTINEL_BYTES: u32 = 3;
 
     let (insert_pos, target_idx, target_is_last) = {
         let offsets = store.block_offsets.read().unwrap();
-        let target_marker = OffsetMarker::Block(target_block_id);
+        let target_marker = target;
         let Some((start, end)) = offsets.range_of(target_marker) else {
             return;
         };
@@ -731,10 +761,11 @@ pub fn rope_remove_table_anchor(store: &Store, table_id: EntityId) {
     //   preceding `\n` + the 3-byte sentinel
     // - otherwise: [byte_start .. byte_start + 4) — the sentinel
     //   + the following `\n`
-    let (remove_start, remove_end) = if anchor_is_last && has_predecessor {
-        (anchor_byte_start - 1, anchor_byte_start + 3)
-    } else {
-        (anchor_byte_start, anchor_byte_start + 4)
+    // A sole anchor has no `\n` beside it: only its 3 bytes go.
+    let (remove_start, remove_end) = match (anchor_is_last, has_predecessor) {
+        (true, true) => (anchor_byte_start - 1, anchor_byte_start + 3),
+        (true, false) => (anchor_byte_start, anchor_byte_start + 3),
+        (false, _) => (anchor_byte_start, anchor_byte_start + 4),
     };
 
     {
@@ -747,11 +778,13 @@ pub fn rope_remove_table_anchor(store: &Store, table_id: EntityId) {
         let mut offsets = store.block_offsets.write().unwrap();
         offsets.remove_at(anchor_idx);
     }
+    // As in `rope_remove_block`: an empty predecessor starting at
+    // `remove_start` only loses its trailing boundary and stays put.
     store
         .block_offsets
         .write()
         .unwrap()
-        .shift_after(remove_start, -4);
+        .shift_after(remove_end, remove_start as i32 - remove_end as i32);
 }
 
 /// Remove a registered block from the rope: drops its content bytes
@@ -837,16 +870,18 @@ pub fn rope_remove_block(store: &Store, block_id: EntityId) {
 pub fn rope_replace_block_content(store: &Store, block_id: EntityId, new_text: &str) {
     let (block_byte_start, content_bytes) = {
         let offsets = store.block_offsets.read().unwrap();
-        let Some((start, end)) = offsets.range_of_block(block_id) else {
+        let Some((start, end, has_trailing_boundary)) =
+            offsets.range_with_successor(OffsetMarker::Block(block_id))
+        else {
             return;
         };
-        let total = offsets.total_bytes();
-        // `range_of` extends to the next entry's `byte_start` (or to
-        // `total_bytes`). If there's a following entry, the byte at
-        // `end - 1` is the inter-block boundary `\n` that belongs to
-        // the boundary between this block and the next, not to this
-        // block's content.
-        let has_trailing_boundary = end < total;
+        // `range_with_successor` extends to the next entry's
+        // `byte_start` (or to `total_bytes`). If there's a following
+        // entry, the byte at `end - 1` is the inter-block boundary `\n`
+        // that belongs to the boundary between this block and the next,
+        // not to this block's content. Comparing `end` against
+        // `total_bytes` instead would misread an empty last block as
+        // "no successor".
         let content_bytes = if has_trailing_boundary {
             end - start - 1
         } else {
@@ -7,7 +7,8 @@
 //! inlining and §1.6 for the rope layout (block boundary `\n` +
 //! U+FFFC table anchor).
 
-use crate::database::block_offset_index::BlockOffsetIndex;
+use crate::database::block_offset_index::{BlockOffsetIndex, OffsetMarker};
+use crate::database::tracked::{TrackedMap, TrackedOffsets, TrackedRope};
 use crate::entities::*;
 use crate::format_runs::{FormatRun, ImageAnchor};
 use crate::snapshot::{StoreSnapshot, StoreSnapshotTrait};
@@ -16,6 +17,7 @@ use im::HashMap;
 use ropey::Rope;
 use std::collections::HashMap as StdHashMap;
 use std::sync::RwLock;
+use std::sync::atomic::{AtomicU64, Ordering};
 
 // ─────────────────────────────────────────────────────────────────────────────
 // The Store
@@ -24,24 +26,24 @@ use std::sync::RwLock;
 #[derive(Debug, Default)]
 pub struct RopeStore {
     // ── Character content (shared across all blocks, including cells) ──
-    pub rope: RwLock<Rope>,
+    pub rope: RwLock<TrackedRope>,
 
     // ── Structural entity tables ──────────────────────────────────────
-    pub roots: RwLock<HashMap<EntityId, Root>>,
-    pub documents: RwLock<HashMap<EntityId, Document>>,
-    pub frames: RwLock<HashMap<EntityId, Frame>>,
-    pub blocks: RwLock<HashMap<EntityId, Block>>,
-    pub lists: RwLock<HashMap<EntityId, List>>,
-    pub resources: RwLock<HashMap<EntityId, Resource>>,
-    pub tables: RwLock<HashMap<EntityId, Table>>,
-    pub table_cells: RwLock<HashMap<EntityId, TableCell>>,
+    pub roots: RwLock<TrackedMap<Root>>,
+    pub documents: RwLock<TrackedMap<Document>>,
+    pub frames: RwLock<TrackedMap<Frame>>,
+    pub blocks: RwLock<TrackedMap<Block>>,
+    pub lists: RwLock<TrackedMap<List>>,
+    pub resources: RwLock<TrackedMap<Resource>>,
+    pub tables: RwLock<TrackedMap<Table>>,
+    pub table_cells: RwLock<TrackedMap<TableCell>>,
 
     // ── Per-block character formatting + image anchors ────────────────
-    pub format_runs: RwLock<HashMap<EntityId, Vec<FormatRun>>>,
-    pub block_images: RwLock<HashMap<EntityId, Vec<ImageAnchor>>>,
+    pub format_runs: RwLock<TrackedMap<Vec<FormatRun>>>,
+    pub block_images: RwLock<TrackedMap<Vec<ImageAnchor>>>,
 
     // ── Document-wide block ordering (sorted by rope position) ────────
-    pub block_offsets: RwLock<BlockOffsetIndex>,
+    pub block_offsets: RwLock<TrackedOffsets>,
 
     // ── ID counters ───────────────────────────────────────────────────
     // Never restored by undo (only by transaction rollback).
@@ -50,6 +52,11 @@ pub struct RopeStore {
     // ── Savepoints (in-memory, transaction-scoped) ────────────────────
     savepoints: RwLock<StdHashMap<u64, RopeStoreSnapshot>>,
     next_savepoint_id: RwLock<u64>,
+
+    // ── Write journal ─────────────────────────────────────────────────
+    // Id of the journal the tables record their writes in, 0 before the
+    // first one starts. Ids are unique across stores.
+    journal: RwLock<u64>,
 }
 
 impl RopeStore {
@@ -62,55 +69,120 @@ impl RopeStore {
     /// `Vec` cloned outright).
     pub fn snapshot(&self) -> RopeStoreSnapshot {
         RopeStoreSnapshot {
-            rope: self.rope.read().unwrap().clone(),
-            roots: self.roots.read().unwrap().clone(),
-            documents: self.documents.read().unwrap().clone(),
-            frames: self.frames.read().unwrap().clone(),
-            blocks: self.blocks.read().unwrap().clone(),
-            lists: self.lists.read().unwrap().clone(),
-            resources: self.resources.read().unwrap().clone(),
-            tables: self.tables.read().unwrap().clone(),
-            table_cells: self.table_cells.read().unwrap().clone(),
-            format_runs: self.format_runs.read().unwrap().clone(),
-            block_images: self.block_images.read().unwrap().clone(),
-            block_offsets: self.block_offsets.read().unwrap().clone(),
+            rope: (**self.rope.read().unwrap()).clone(),
+            roots: (**self.roots.read().unwrap()).clone(),
+            documents: (**self.documents.read().unwrap()).clone(),
+            frames: (**self.frames.read().unwrap()).clone(),
+            blocks: (**self.blocks.read().unwrap()).clone(),
+            lists: (**self.lists.read().unwrap()).clone(),
+            resources: (**self.resources.read().unwrap()).clone(),
+            tables: (**self.tables.read().unwrap()).clone(),
+            table_cells: (**self.table_cells.read().unwrap()).clone(),
+            format_runs: (**self.format_runs.read().unwrap()).clone(),
+            block_images: (**self.block_images.read().unwrap()).clone(),
+            block_offsets: (**self.block_offsets.read().unwrap()).clone(),
             counters: self.counters.read().unwrap().clone(),
+            journal: *self.journal.read().unwrap(),
         }
     }
 
+    /// Start a new journal of writes: from here on each table records
+    /// where it is written, for [`StoreDelta::since`] to compare only
+    /// that against snapshots taken during this journal.
+    ///
+    /// [`StoreDelta::since`]: crate::database::store_delta::StoreDelta::since
+    pub fn start_journal(&self) {
+        static NEXT_JOURNAL: AtomicU64 = AtomicU64::new(1);
+        *self.journal.write().unwrap() = NEXT_JOURNAL.fetch_add(1, Ordering::Relaxed);
+        self.rope.write().unwrap().clear_journal();
+        self.roots.write().unwrap().clear_journal();
+        self.documents.write().unwrap().clear_journal();
+        self.frames.write().unwrap().clear_journal();
+        self.blocks.write().unwrap().clear_journal();
+        self.lists.write().unwrap().clear_journal();
+        self.resources.write().unwrap().clear_journal();
+        self.tables.write().unwrap().clear_journal();
+        self.table_cells.write().unwrap().clear_journal();
+        self.format_runs.write().unwrap().clear_journal();
+        self.block_images.write().unwrap().clear_journal();
+        self.block_offsets.write().unwrap().clear_journal();
+    }
+
+    /// Whether the journal of writes covers everything written since
+    /// `snap` was taken from this store.
+    pub fn journal_covers(&self, snap: &RopeStoreSnapshot) -> bool {
+        snap.journal != 0 && snap.journal == *self.journal.read().unwrap()
+    }
+
     /// Restore from a snapshot. Overwrites counters too — used for
     /// transaction rollback (`Drop` of an uncommitted write txn).
     pub fn restore(&self, snap: &RopeStoreSnapshot) {
-        *self.rope.write().unwrap() = snap.rope.clone();
-        *self.roots.write().unwrap() = snap.roots.clone();
-        *self.documents.write().unwrap() = snap.documents.clone();
-        *self.frames.write().unwrap() = snap.frames.clone();
-        *self.blocks.write().unwrap() = snap.blocks.clone();
-        *self.lists.write().unwrap() = snap.lists.clone();
-        *self.resources.write().unwrap() = snap.resources.clone();
-        *self.tables.write().unwrap() = snap.tables.clone();
-        *self.table_cells.write().unwrap() = snap.table_cells.clone();
-        *self.format_runs.write().unwrap() = snap.format_runs.clone();
-        *self.block_images.write().unwrap() = snap.block_images.clone();
-        *self.block_offsets.write().unwrap() = snap.block_offsets.clone();
+        self.rope.write().unwrap().replace(snap.rope.clone());
+        self.roots.write().unwrap().replace(snap.roots.clone());
+        self.documents
+            .write()
+            .unwrap()
+            .replace(snap.documents.clone());
+        self.frames.write().unwrap().replace(snap.frames.clone());
+        self.blocks.write().unwrap().replace(snap.blocks.clone());
+        self.lists.write().unwrap().replace(snap.lists.clone());
+        self.resources
+            .write()
+            .unwrap()
+            .replace(snap.resources.clone());
+        self.tables.write().unwrap().replace(snap.tables.clone());
+        self.table_cells
+            .write()
+            .unwrap()
+            .replace(snap.table_cells.clone());
+        self.format_runs
+            .write()
+            .unwrap()
+            .replace(snap.format_runs.clone());
+        self.block_images
+            .write()
+            .unwrap()
+            .replace(snap.block_images.clone());
+        self.block_offsets
+            .write()
+            .unwrap()
+            .replace(snap.block_offsets.clone());
         *self.counters.write().unwrap() = snap.counters.clone();
     }
 
     /// Restore everything *except* counters — used for undo, where IDs
     /// must remain monotonically increasing across undo/redo cycles.
     pub fn restore_without_counters(&self, snap: &RopeStoreSnapshot) {
-        *self.rope.write().unwrap() = snap.rope.clone();
-        *self.roots.write().unwrap() = snap.roots.clone();
-        *self.documents.write().unwrap() = snap.documents.clone();
-        *self.frames.write().unwrap() = snap.frames.clone();
-        *self.blocks.write().unwrap() = snap.blocks.clone();
-        *self.lists.write().unwrap() = snap.lists.clone();
-        *self.resources.write().unwrap() = snap.resources.clone();
-        *self.tables.write().unwrap() = snap.tables.clone();
-        *self.table_cells.write().unwrap() = snap.table_cells.clone();
-        *self.format_runs.write().unwrap() = snap.format_runs.clone();
-        *self.block_images.write().unwrap() = snap.block_images.clone();
-        *self.block_offsets.write().unwrap() = snap.block_offsets.clone();
+        self.rope.write().unwrap().replace(snap.rope.clone());
+        self.roots.write().unwrap().replace(snap.roots.clone());
+        self.documents
+            .write()
+            .unwrap()
+            .replace(snap.documents.clone());
+        self.frames.write().unwrap().replace(snap.frames.clone());
+        self.blocks.write().unwrap().replace(snap.blocks.clone());
+        self.lists.write().unwrap().replace(snap.lists.clone());
+        self.resources
+            .write()
+            .unwrap()
+            .replace(snap.resources.clone());
+        self.tables.write().unwrap().replace(snap.tables.clone());
+        self.table_cells
+            .write()
+            .unwrap()
+            .replace(snap.table_cells.clone());
+        self.format_runs
+            .write()
+            .unwrap()
+            .replace(snap.format_runs.clone());
+        self.block_images
+            .write()
+            .unwrap()
+            .replace(snap.block_images.clone());
+        self.block_offsets
+            .write()
+            .unwrap()
+            .replace(snap.block_offsets.clone());
         // counters intentionally not restored
     }
 
@@ -183,6 +255,8 @@ pub struct RopeStoreSnapshot {
     pub(crate) block_images: HashMap<EntityId, Vec<ImageAnchor>>,
     pub(crate) block_offsets: BlockOffsetIndex,
     pub(crate) counters: StdHashMap<String, EntityId>,
+    /// The store's journal when taken.
+    pub(crate) journal: u64,
 }
 
 impl StoreSnapshotTrait for RopeStoreSnapshot {
@@ -193,4 +267,26 @@ impl StoreSnapshotTrait for RopeStoreSnapshot {
     fn as_any(&self) -> &dyn std::any::Any {
         self
     }
+
+    /// Counts the whole captured document. The structure is shared with
+    /// the live store when taken, but stops being shared as soon as the
+    /// document diverges, so the full size is what an old snapshot ends
+    /// up costing. Format runs are estimated at one per block to keep
+    /// this O(1) in the block count.
+    fn approximate_size(&self) -> usize {
+        use std::mem::size_of;
+        let resources: usize = self
+            .resources
+            .values()
+            .map(|r| size_of::<Resource>() + r.name.len() + r.url.len() + r.data_base64.len())
+            .sum();
+        self.rope.len_bytes()
+            + self.blocks.len() * (size_of::<Block>() + size_of::<FormatRun>())
+            + self.frames.len() * size_of::<Frame>()
+            + self.lists.len() * size_of::<List>()
+            + self.tables.len() * size_of::<Table>()
+            + self.table_cells.len() * size_of::<TableCell>()
+            + self.block_offsets.len() * size_of::<(OffsetMarker, u32)>()
+            + resources
+    }
 }
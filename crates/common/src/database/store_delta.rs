@@ -0,0 +1,1429 @@
+//! Serializable, reversible differences between two store states.
+//!
+//! A `StoreDelta` records only what changed between two
+//! [`RopeStoreSnapshot`]s — the text splices, the entity rows that differ,
+//! the changed stretches of the block offset index and of the frames'
+//! block lists, and how the blocks the edit only moved along shift.
+//! Structural undo
+//! commands keep one instead of the snapshot they took before editing,
+//! and an undo history can be saved alongside a document and replayed
+//! after it is loaded. An edit's delta is built from the rows, text and
+//! index entries the store's write journal says it touched, so recording
+//! it does not walk the rest of the document.
+//!
+//! Applying a delta checks that the state it is applied to holds exactly
+//! the values the delta expects to replace, modification times aside:
+//! undoing an edit re-stamps the rows it touches instead of restoring
+//! their times. A delta that does not match is rejected with an error and
+//! the input snapshot is left untouched.
+//!
+//! Edits made elsewhere in the document since the delta was recorded do
+//! not stop it from applying: the change is found again from the blocks
+//! around it rather than at its recorded offsets, and the document's
+//! counters and the blocks' positions move by the delta's difference
+//! instead of being checked and reset.
+
+use crate::database::Store;
+use crate::database::block_offset_index::{BlockOffsetIndex, OffsetMarker};
+use crate::database::rope_store::RopeStoreSnapshot;
+use crate::database::tracked::{Journal, Stretches};
+use crate::entities::*;
+use crate::format_runs::{FormatRun, ImageAnchor};
+use crate::snapshot::{EntityTreeSnapshot, StoreSnapshot};
+use crate::types::EntityId;
+use anyhow::{Result, anyhow, bail};
+use im::HashMap;
+use ropey::{Rope, RopeSlice};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::mem::size_of;
+use std::ops::Range;
+
+/// Replacement of `removed` by `inserted` at char offset `char_start`
+/// of the state the delta moves from.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TextSplice {
+    pub char_start: usize,
+    pub removed: String,
+    pub inserted: String,
+}
+
+/// One entity row that differs. `None` means the row does not exist on
+/// that side.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RowChange<T> {
+    pub id: EntityId,
+    pub before: Option<T>,
+    pub after: Option<T>,
+}
+
+/// Replacement of a stretch of the block offset index, at entry `index`
+/// of the state the delta moves from. Entries are stored as
+/// `(marker, byte_length)` so an edit inside one block changes a single
+/// entry instead of every later `byte_start`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OffsetSplice {
+    pub first_start_before: u32,
+    pub first_start_after: u32,
+    pub index: usize,
+    pub removed: Vec<(OffsetMarker, u32)>,
+    pub inserted: Vec<(OffsetMarker, u32)>,
+}
+
+/// Replacement of `removed` by `inserted` at `index` of a list.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ListSplice<T> {
+    pub index: usize,
+    pub removed: Vec<T>,
+    pub inserted: Vec<T>,
+}
+
+/// The changes to the block lists of a frame found on both sides of a
+/// delta. Its rows in [`StoreDelta::frames`] hold these lists empty.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FrameLists {
+    pub id: EntityId,
+    pub blocks: ListSplice<EntityId>,
+    pub child_order: ListSplice<i64>,
+}
+
+/// Everything that differs between two store states. ID counters are not
+/// recorded: undo never rewinds them.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StoreDelta {
+    /// Text splices, in order and apart from each other.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub text: Vec<TextSplice>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub roots: Vec<RowChange<Root>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub documents: Vec<RowChange<Document>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub frames: Vec<RowChange<Frame>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub frame_lists: Vec<FrameLists>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub blocks: Vec<RowChange<Block>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub lists: Vec<RowChange<List>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub resources: Vec<RowChange<Resource>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tables: Vec<RowChange<Table>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub table_cells: Vec<RowChange<TableCell>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub format_runs: Vec<RowChange<Vec<FormatRun>>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub block_images: Vec<RowChange<Vec<ImageAnchor>>>,
+    /// Offset index splices, in order and apart from each other.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub block_offsets: Vec<OffsetSplice>,
+    /// How far the blocks left out of `blocks` move: blocks whose only
+    /// change is their position are not recorded as rows.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub moved_blocks: Vec<PositionShift>,
+    /// Where the change sits among the blocks. `None` in deltas saved
+    /// before it was recorded, which only apply at their recorded
+    /// offsets.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub anchor: Option<ChangeAnchor>,
+    /// The blocks whose content or format the delta rewrites: blocks it
+    /// adds or removes, and blocks whose text, format, list, runs or
+    /// images change. Blocks it only moves along are left out. `None`
+    /// in deltas saved before it was recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub changed_blocks: Option<Vec<EntityId>>,
+}
+
+/// Blocks at `from` or later, up to the next shift's `from`, move by
+/// `by`. Positions are those of the state the delta moves from; blocks
+/// before the first shift stay where they are.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PositionShift {
+    pub from: i64,
+    pub by: i64,
+}
+
+/// The place in the block offset index where a delta's change starts,
+/// recorded so the change can be found after edits before it moved it.
+/// Entries before `index` are the same on both sides of the delta, so
+/// the entry at `index` starts at `char_start` on both.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChangeAnchor {
+    pub index: usize,
+    pub char_start: usize,
+    /// The entry at `index` before and after the change, if any.
+    pub before_marker: Option<OffsetMarker>,
+    pub after_marker: Option<OffsetMarker>,
+    /// The entry just before `index`, if any.
+    pub previous_marker: Option<OffsetMarker>,
+    /// The stored positions of the blocks `before_marker` and
+    /// `after_marker` name, on their side, where stored positions count.
+    /// The rope keeps the text of table cells apart from the flow, so
+    /// chars there do not tell how far blocks moved.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub before_position: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub after_position: Option<i64>,
+}
+
+/// Which side of a delta to move towards.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Side {
+    Before,
+    After,
+}
+
+impl StoreDelta {
+    /// Compute the delta that turns `before` into `after`, comparing the
+    /// two states in full.
+    pub fn between(before: &RopeStoreSnapshot, after: &RopeStoreSnapshot) -> Self {
+        // An edit that adds or removes the last table moves between a
+        // state whose stored block positions are left to go stale and
+        // one where they count. Record the first from the rope.
+        let (before, after) = if positions_match_flow(before) != positions_match_flow(after) {
+            (with_flow_positions(before), with_flow_positions(after))
+        } else {
+            (Cow::Borrowed(before), Cow::Borrowed(after))
+        };
+        Self::compare(before.as_ref(), after.as_ref(), &Touched::everything())
+    }
+
+    /// Compute the delta from the state an undo snapshot captured to the
+    /// current state of `store`. Lets an edit keep only what it changed
+    /// instead of holding on to the whole store. When the snapshot was
+    /// taken during the store's current write journal, only what the
+    /// journal says was written since is compared.
+    pub fn since(snapshot: &EntityTreeSnapshot, store: &Store) -> Result<Self> {
+        let before = snapshot
+            .store_snapshot
+            .as_ref()
+            .and_then(|s| s.downcast_ref::<RopeStoreSnapshot>())
+            .ok_or_else(|| anyhow!("Snapshot does not hold a store state"))?;
+        let after = store.snapshot();
+        if !store.journal_covers(before) {
+            return Ok(Self::between(before, &after));
+        }
+        let mut touched = Touched::of(store);
+        if positions_match_flow(before) != positions_match_flow(&after) {
+            // The journal does not say where positions went stale or
+            // were refreshed, so the blocks are compared in full, as
+            // `between` records them.
+            touched.blocks = Journal::Everything;
+            return Ok(Self::compare(
+                &with_flow_positions(before),
+                &with_flow_positions(&after),
+                &touched,
+            ));
+        }
+        Ok(Self::compare(before, &after, &touched))
+    }
+
+    /// The delta between the states, comparing only what `touched` covers.
+    fn compare(before: &RopeStoreSnapshot, after: &RopeStoreSnapshot, touched: &Touched) -> Self {
+        let (blocks, moved_blocks) = block_changes(&before.blocks, &after.blocks, &touched.blocks);
+        let (frames, frame_lists) = frame_changes(&before.frames, &after.frames, &touched.frames);
+        let mut delta = StoreDelta {
+            text: text_splices(&before.rope, &after.rope, &touched.text),
+            roots: row_changes(&before.roots, &after.roots, &touched.roots),
+            documents: row_changes(&before.documents, &after.documents, &touched.documents),
+            frames,
+            frame_lists,
+            blocks,
+            lists: row_changes(&before.lists, &after.lists, &touched.lists),
+            resources: row_changes(&before.resources, &after.resources, &touched.resources),
+            tables: row_changes(&before.tables, &after.tables, &touched.tables),
+            table_cells: row_changes(
+                &before.table_cells,
+                &after.table_cells,
+                &touched.table_cells,
+            ),
+            format_runs: list_changes(
+                &before.format_runs,
+                &after.format_runs,
+                &touched.format_runs,
+            ),
+            block_images: list_changes(
+                &before.block_images,
+                &after.block_images,
+                &touched.block_images,
+            ),
+            block_offsets: offset_splices(
+                &before.block_offsets,
+                &after.block_offsets,
+                &touched.offsets,
+            ),
+            moved_blocks,
+            anchor: None,
+            changed_blocks: None,
+        };
+        delta.anchor = change_anchor(before, after, &delta);
+        delta.changed_blocks = Some(changed_blocks(before, after, &delta));
+        delta
+    }
+
+    /// The current state of `store` with this delta reverted, ready to
+    /// hand to `restore_document`.
+    pub fn reverted(&self, store: &Store) -> Result<EntityTreeSnapshot> {
+        Self::reverted_all(std::slice::from_ref(self), store)
+    }
+
+    /// The current state of `store` with `deltas`, recorded in that
+    /// order, all reverted.
+    pub fn reverted_all(deltas: &[StoreDelta], store: &Store) -> Result<EntityTreeSnapshot> {
+        let mut snap = store.snapshot();
+        for delta in deltas.iter().rev() {
+            snap = delta.revert(&snap)?;
+        }
+        Ok(tree_snapshot(snap))
+    }
+
+    /// The current state of `store` with this delta applied, ready to
+    /// hand to `restore_document`.
+    pub fn applied(&self, store: &Store) -> Result<EntityTreeSnapshot> {
+        Ok(tree_snapshot(self.apply(&store.snapshot())?))
+    }
+
+    /// Move `snap` from the `before` state to the `after` state.
+    pub fn apply(&self, snap: &RopeStoreSnapshot) -> Result<RopeStoreSnapshot> {
+        self.transform(snap, Side::After)
+    }
+
+    /// Move `snap` from the `after` state back to the `before` state.
+    pub fn revert(&self, snap: &RopeStoreSnapshot) -> Result<RopeStoreSnapshot> {
+        self.transform(snap, Side::Before)
+    }
+
+    /// Approximate number of bytes this delta holds, for undo memory
+    /// budgets.
+    pub fn approximate_size(&self) -> usize {
+        size_of::<Self>()
+            + self
+                .text
+                .iter()
+                .map(|splice| {
+                    size_of::<TextSplice>() + splice.removed.len() + splice.inserted.len()
+                })
+                .sum::<usize>()
+            + rows_size(&self.roots)
+            + rows_size(&self.documents)
+            + rows_size(&self.frames)
+            + rows_size(&self.blocks)
+            + rows_size(&self.lists)
+            + rows_size(&self.resources)
+            + rows_size(&self.tables)
+            + rows_size(&self.table_cells)
+            + nested_rows_size(&self.format_runs)
+            + nested_rows_size(&self.block_images)
+            + self
+                .block_offsets
+                .iter()
+                .map(|splice| {
+                    size_of::<OffsetSplice>()
+                        + (splice.removed.len() + splice.inserted.len())
+                            * size_of::<(OffsetMarker, u32)>()
+                })
+                .sum::<usize>()
+            + self
+                .frame_lists
+                .iter()
+                .map(|lists| {
+                    size_of::<FrameLists>()
+                        + (lists.blocks.removed.len() + lists.blocks.inserted.len())
+                            * size_of::<EntityId>()
+                        + (lists.child_order.removed.len() + lists.child_order.inserted.len())
+                            * size_of::<i64>()
+                })
+                .sum::<usize>()
+            + std::mem::size_of_val(self.moved_blocks.as_slice())
+    }
+
+    fn transform(&self, snap: &RopeStoreSnapshot, to: Side) -> Result<RopeStoreSnapshot> {
+        let out = self.transform_from(snap, to)?;
+        if positions_match_flow(snap) && !positions_match_flow(&out) {
+            // The blocks' positions count again from here on: move them
+            // from where the rope puts them, not from stale stored ones.
+            return self.transform_from(&with_flow_positions(snap), to);
+        }
+        Ok(out)
+    }
+
+    fn transform_from(&self, snap: &RopeStoreSnapshot, to: Side) -> Result<RopeStoreSnapshot> {
+        let (index, shift) = self.locate(snap, to)?;
+        let moved = self.moved(snap, to).unwrap_or(shift);
+        let mut out = snap.clone();
+        out.rope = splice_text(&snap.rope, &self.text, shift, to)?;
+        apply_rows(&mut out.roots, &self.roots, shift, to, "root")?;
+        apply_rows(&mut out.documents, &self.documents, shift, to, "document")?;
+        let frames = with_frame_lists(&snap.frames, &self.frames, &self.frame_lists, to)?;
+        apply_rows(&mut out.frames, &frames, shift, to, "frame")?;
+        apply_rows(&mut out.blocks, &self.blocks, moved, to, "block")?;
+        move_blocks(&mut out.blocks, &self.blocks, &self.moved_blocks, moved, to);
+        apply_rows(&mut out.lists, &self.lists, shift, to, "list")?;
+        apply_rows(&mut out.resources, &self.resources, shift, to, "resource")?;
+        apply_rows(&mut out.tables, &self.tables, shift, to, "table")?;
+        apply_rows(
+            &mut out.table_cells,
+            &self.table_cells,
+            shift,
+            to,
+            "table cell",
+        )?;
+        apply_lists(&mut out.format_runs, &self.format_runs, to, "format runs")?;
+        apply_lists(
+            &mut out.block_images,
+            &self.block_images,
+            to,
+            "block images",
+        )?;
+        out.block_offsets = splice_offsets(
+            &snap.block_offsets,
+            &self.block_offsets,
+            index,
+            out.rope.len_bytes() as u32,
+            to,
+        )?;
+        Ok(out)
+    }
+
+    /// How far edits before the change moved the stored positions of
+    /// the blocks at it since it was recorded, where those count.
+    fn moved(&self, snap: &RopeStoreSnapshot, to: Side) -> Option<isize> {
+        let anchor = self.anchor.as_ref()?;
+        let (marker, recorded) = match to {
+            Side::After => (anchor.before_marker, anchor.before_position?),
+            Side::Before => (anchor.after_marker, anchor.after_position?),
+        };
+        let position = stored_position(snap, marker)?;
+        Some((position - recorded) as isize)
+    }
+
+    /// The offset index entry the [`OffsetSplice`] applies at in `snap`,
+    /// and how many chars edits before the change moved it since it was
+    /// recorded.
+    fn locate(&self, snap: &RopeStoreSnapshot, to: Side) -> Result<(usize, isize)> {
+        let first_index = self.block_offsets.first().map(|splice| splice.index);
+        let Some(anchor) = &self.anchor else {
+            return Ok((first_index.unwrap_or(0), 0));
+        };
+        let marker = match to {
+            Side::After => anchor.before_marker,
+            Side::Before => anchor.after_marker,
+        };
+        let index = &snap.block_offsets;
+        let found = match (marker, anchor.previous_marker) {
+            (Some(marker), _) => index.position_of(marker),
+            (None, Some(previous)) => index.position_of(previous).map(|i| i + 1),
+            (None, None) => return Ok((first_index.unwrap_or(0), 0)),
+        };
+        let Some(position) = found else {
+            bail!("Block offset index does not match the recorded edit");
+        };
+        let byte_start = index
+            .entries
+            .get(position)
+            .map_or(index.total_bytes(), |(_, start)| *start) as usize;
+        if byte_start > snap.rope.len_bytes() {
+            bail!("Block offset index does not match the recorded edit");
+        }
+        let char_start = snap.rope.byte_to_char(byte_start);
+        Ok((
+            position + first_index.map_or(0, |index| index.saturating_sub(anchor.index)),
+            char_start as isize - anchor.char_start as isize,
+        ))
+    }
+}
+
+/// Where a store was written since a snapshot, as its write journal
+/// records it, or everywhere.
+struct Touched {
+    text: Journal<Stretches>,
+    roots: Journal<HashSet<EntityId>>,
+    documents: Journal<HashSet<EntityId>>,
+    frames: Journal<HashSet<EntityId>>,
+    blocks: Journal<HashSet<EntityId>>,
+    lists: Journal<HashSet<EntityId>>,
+    resources: Journal<HashSet<EntityId>>,
+    tables: Journal<HashSet<EntityId>>,
+    table_cells: Journal<HashSet<EntityId>>,
+    format_runs: Journal<HashSet<EntityId>>,
+    block_images: Journal<HashSet<EntityId>>,
+    offsets: Journal<Stretches>,
+}
+
+impl Touched {
+    fn everything() -> Self {
+        Touched {
+            text: Journal::Everything,
+            roots: Journal::Everything,
+            documents: Journal::Everything,
+            frames: Journal::Everything,
+            blocks: Journal::Everything,
+            lists: Journal::Everything,
+            resources: Journal::Everything,
+            tables: Journal::Everything,
+            table_cells: Journal::Everything,
+            format_runs: Journal::Everything,
+            block_images: Journal::Everything,
+            offsets: Journal::Everything,
+        }
+    }
+
+    fn of(store: &Store) -> Self {
+        Touched {
+            text: store.rope.read().unwrap().journal().clone(),
+            roots: store.roots.read().unwrap().journal().clone(),
+            documents: store.documents.read().unwrap().journal().clone(),
+            frames: store.frames.read().unwrap().journal().clone(),
+            blocks: store.blocks.read().unwrap().journal().clone(),
+            lists: store.lists.read().unwrap().journal().clone(),
+            resources: store.resources.read().unwrap().journal().clone(),
+            tables: store.tables.read().unwrap().journal().clone(),
+            table_cells: store.table_cells.read().unwrap().journal().clone(),
+            format_runs: store.format_runs.read().unwrap().journal().clone(),
+            block_images: store.block_images.read().unwrap().journal().clone(),
+            offsets: store.block_offsets.read().unwrap().journal().clone(),
+        }
+    }
+}
+
+/// Record where the change between `before` and `after` starts: at the
+/// first offset index entry that differs, or earlier when the text
+/// changes inside an entry whose length did not.
+fn change_anchor(
+    before: &RopeStoreSnapshot,
+    after: &RopeStoreSnapshot,
+    delta: &StoreDelta,
+) -> Option<ChangeAnchor> {
+    let (b, a) = (&before.block_offsets, &after.block_offsets);
+    if b.is_empty() || first_start(b) != first_start(a) {
+        return None;
+    }
+    let text_entry = delta.text.first().map(|splice| {
+        let text_byte = before.rope.char_to_byte(splice.char_start) as u32;
+        b.entries
+            .partition_point(|(_, start)| *start <= text_byte)
+            .saturating_sub(1)
+    });
+    let offsets_entry = delta.block_offsets.first().map(|splice| splice.index);
+    let index = match (text_entry, offsets_entry) {
+        (Some(text), Some(offsets)) => text.min(offsets),
+        (Some(index), None) | (None, Some(index)) => index,
+        (None, None) => return None,
+    };
+    let byte_start = b
+        .entries
+        .get(index)
+        .map_or(b.total_bytes(), |(_, start)| *start) as usize;
+    Some(ChangeAnchor {
+        index,
+        char_start: before
+            .rope
+            .byte_to_char(byte_start.min(before.rope.len_bytes())),
+        before_marker: b.entries.get(index).map(|(marker, _)| *marker),
+        after_marker: a.entries.get(index).map(|(marker, _)| *marker),
+        previous_marker: index
+            .checked_sub(1)
+            .and_then(|i| b.entries.get(i))
+            .map(|(marker, _)| *marker),
+        before_position: stored_position(before, b.entries.get(index).map(|(marker, _)| *marker)),
+        after_position: stored_position(after, a.entries.get(index).map(|(marker, _)| *marker)),
+    })
+}
+
+/// The stored position of the block `marker` names in `snap`, if
+/// stored positions count there.
+fn stored_position(snap: &RopeStoreSnapshot, marker: Option<OffsetMarker>) -> Option<i64> {
+    match marker {
+        Some(OffsetMarker::Block(id)) if !positions_match_flow(snap) => {
+            snap.blocks.get(&id).map(|block| block.document_position)
+        }
+        _ => None,
+    }
+}
+
+/// The blocks `delta` rewrites, for [`StoreDelta::changed_blocks`].
+fn changed_blocks(
+    before: &RopeStoreSnapshot,
+    after: &RopeStoreSnapshot,
+    delta: &StoreDelta,
+) -> Vec<EntityId> {
+    let mut ids: Vec<EntityId> = delta
+        .blocks
+        .iter()
+        .filter(|change| match (&change.before, &change.after) {
+            (Some(old), Some(new)) => {
+                old != &Block {
+                    updated_at: old.updated_at,
+                    document_position: old.document_position,
+                    ..new.clone()
+                }
+            }
+            _ => true,
+        })
+        .map(|change| change.id)
+        .chain(delta.format_runs.iter().map(|change| change.id))
+        .chain(delta.block_images.iter().map(|change| change.id))
+        .chain(
+            delta
+                .block_offsets
+                .iter()
+                .flat_map(|splice| splice.removed.iter().chain(&splice.inserted))
+                .filter_map(|(marker, _)| marker.as_block()),
+        )
+        .collect();
+    // Blocks whose text changed without changing length. Splices after
+    // the first start later on the after side by what those before
+    // them grew.
+    let mut growth = 0i64;
+    for text in &delta.text {
+        let start = before.rope.char_to_byte(text.char_start) as u32;
+        let after_start = (start as i64 + growth) as u32;
+        growth += text.inserted.len() as i64 - text.removed.len() as i64;
+        for (snap, start, len) in [
+            (before, start, text.removed.len()),
+            (after, after_start, text.inserted.len()),
+        ] {
+            let entries = &snap.block_offsets.entries;
+            let first = entries
+                .partition_point(|(_, s)| *s <= start)
+                .saturating_sub(1);
+            let end = start + len as u32;
+            ids.extend(
+                entries[first.min(entries.len())..]
+                    .iter()
+                    .take_while(|(_, s)| *s <= end)
+                    .filter_map(|(marker, _)| marker.as_block()),
+            );
+        }
+    }
+    ids.sort_unstable();
+    ids.dedup();
+    ids
+}
+
+/// Whether `snap`'s rope mirrors the flow, as
+/// [`rope_positions_match_flow`](crate::database::rope_helpers::rope_positions_match_flow)
+/// decides for a live store. Edits then leave the blocks' stored
+/// positions stale.
+fn positions_match_flow(snap: &RopeStoreSnapshot) -> bool {
+    snap.block_offsets.table_anchor_count() == 0
+        && snap.block_offsets.entries.len() == snap.blocks.len()
+}
+
+/// `snap` with its blocks' stored positions taken from the rope, when
+/// the rope mirrors the flow.
+fn with_flow_positions(snap: &RopeStoreSnapshot) -> Cow<'_, RopeStoreSnapshot> {
+    if !positions_match_flow(snap) {
+        return Cow::Borrowed(snap);
+    }
+    let mut out = snap.clone();
+    for (marker, byte_start) in snap.block_offsets.entries.iter() {
+        let Some(id) = marker.as_block() else {
+            continue;
+        };
+        let position = snap.rope.byte_to_char(*byte_start as usize) as i64;
+        if let Some(block) = out.blocks.get_mut(&id) {
+            block.document_position = position;
+        }
+    }
+    Cow::Owned(out)
+}
+
+fn tree_snapshot(snap: RopeStoreSnapshot) -> EntityTreeSnapshot {
+    EntityTreeSnapshot {
+        store_snapshot: Some(StoreSnapshot::new(snap)),
+    }
+}
+
+fn rows_size<T>(rows: &[RowChange<T>]) -> usize {
+    std::mem::size_of_val(rows)
+}
+
+fn nested_rows_size<T>(rows: &[RowChange<Vec<T>>]) -> usize {
+    rows_size(rows)
+        + rows
+            .iter()
+            .map(|r| {
+                (r.before.as_ref().map_or(0, Vec::len) + r.after.as_ref().map_or(0, Vec::len))
+                    * size_of::<T>()
+            })
+            .sum::<usize>()
+}
+
+/// The text splices between the ropes, looked for in the chars
+/// `touched` says were written.
+fn text_splices(before: &Rope, after: &Rope, touched: &Journal<Stretches>) -> Vec<TextSplice> {
+    let Some(ranges) = stretch_ranges(touched, before.len_chars(), after.len_chars()) else {
+        return non_empty_splices([slice_splice(before.slice(..), after.slice(..))]);
+    };
+    non_empty_splices(ranges.into_iter().map(|(b, a)| {
+        let mut splice = slice_splice(before.slice(b.clone()), after.slice(a));
+        splice.char_start += b.start;
+        splice
+    }))
+}
+
+fn non_empty_splices(splices: impl IntoIterator<Item = TextSplice>) -> Vec<TextSplice> {
+    splices
+        .into_iter()
+        .filter(|splice| !splice.removed.is_empty() || !splice.inserted.is_empty())
+        .collect()
+}
+
+/// The stretches `touched` records, each as its range on the `before`
+/// side and on the `after` side. `None` when the table was replaced
+/// wholesale, or the stretches do not account for the lengths.
+fn stretch_ranges(
+    touched: &Journal<Stretches>,
+    before_len: usize,
+    after_len: usize,
+) -> Option<Vec<(Range<usize>, Range<usize>)>> {
+    let Journal::Partial(stretches) = touched else {
+        return None;
+    };
+    let mut growth = 0isize;
+    let mut ranges = Vec::with_capacity(stretches.len());
+    for stretch in stretches {
+        let start = stretch.range.start.checked_add_signed(-growth)?;
+        ranges.push((start..start + stretch.original_len, stretch.range.clone()));
+        growth += stretch.range.len() as isize - stretch.original_len as isize;
+    }
+    let fits = ranges
+        .last()
+        .is_none_or(|(b, a)| b.end <= before_len && a.end <= after_len);
+    (fits && before_len.checked_add_signed(growth) == Some(after_len)).then_some(ranges)
+}
+
+fn slice_splice(before: RopeSlice, after: RopeSlice) -> TextSplice {
+    // Compare chunk by chunk rather than char by char: an edit anywhere
+    // in a large document would otherwise walk every char around it.
+    let prefix = common_bytes(before.chunks(), after.chunks(), false);
+    let max_suffix = before.len_bytes().min(after.len_bytes()) - prefix;
+    let suffix = common_bytes(
+        before.chunks_at_byte(before.len_bytes()).0.reversed(),
+        after.chunks_at_byte(after.len_bytes()).0.reversed(),
+        true,
+    )
+    .min(max_suffix);
+    // Back off to char boundaries on both sides.
+    let char_start = before.byte_to_char(prefix);
+    let before_end = before.char_to_byte(before.byte_to_char(before.len_bytes() - suffix));
+    let before_end = if before_end < before.len_bytes() - suffix {
+        before.char_to_byte(before.byte_to_char(before_end) + 1)
+    } else {
+        before_end
+    };
+    let suffix = before.len_bytes() - before_end;
+    let after_end = after.len_bytes() - suffix;
+    let start = before.char_to_byte(char_start);
+    TextSplice {
+        char_start,
+        removed: before.byte_slice(start..before_end).to_string(),
+        inserted: after.byte_slice(start..after_end).to_string(),
+    }
+}
+
+/// Number of leading bytes two chunk sequences share, or trailing bytes
+/// with `from_end` and reversed chunk iterators.
+fn common_bytes<'a>(
+    mut left: impl Iterator<Item = &'a str>,
+    mut right: impl Iterator<Item = &'a str>,
+    from_end: bool,
+) -> usize {
+    let (mut l, mut r): (&[u8], &[u8]) = (&[], &[]);
+    let mut count = 0;
+    loop {
+        if l.is_empty() {
+            match left.next() {
+                Some(chunk) => l = chunk.as_bytes(),
+                None => return count,
+            }
+            continue;
+        }
+        if r.is_empty() {
+            match right.next() {
+                Some(chunk) => r = chunk.as_bytes(),
+                None => return count,
+            }
+            continue;
+        }
+        let n = l.len().min(r.len());
+        let (ls, rs) = if from_end {
+            (&l[l.len() - n..], &r[r.len() - n..])
+        } else {
+            (&l[..n], &r[..n])
+        };
+        let same = if from_end {
+            ls.iter()
+                .rev()
+                .zip(rs.iter().rev())
+                .take_while(|(a, b)| a == b)
+                .count()
+        } else {
+            ls.iter().zip(rs).take_while(|(a, b)| a == b).count()
+        };
+        count += same;
+        if same < n {
+            return count;
+        }
+        if from_end {
+            l = &l[..l.len() - n];
+            r = &r[..r.len() - n];
+        } else {
+            l = &l[n..];
+            r = &r[n..];
+        }
+    }
+}
+
+/// Apply `splices`, moved `shift` chars from where they were recorded.
+fn splice_text(rope: &Rope, splices: &[TextSplice], shift: isize, to: Side) -> Result<Rope> {
+    // Each splice starts where the ones before it left it on the side
+    // moved from. Applying them from the last keeps those starts valid.
+    let mut growth = 0isize;
+    let mut starts = Vec::with_capacity(splices.len());
+    for splice in splices {
+        let Some(start) = splice.char_start.checked_add_signed(shift + growth) else {
+            bail!(
+                "Text at char {} does not match the recorded edit",
+                splice.char_start
+            );
+        };
+        starts.push(start);
+        if to == Side::Before {
+            growth +=
+                splice.inserted.chars().count() as isize - splice.removed.chars().count() as isize;
+        }
+    }
+    let mut rope = rope.clone();
+    for (splice, start) in splices.iter().zip(starts).rev() {
+        let (expected, replacement) = match to {
+            Side::After => (&splice.removed, &splice.inserted),
+            Side::Before => (&splice.inserted, &splice.removed),
+        };
+        let end = start + expected.chars().count();
+        if end > rope.len_chars() || rope.slice(start..end) != expected.as_str() {
+            bail!("Text at char {start} does not match the recorded edit");
+        }
+        rope.remove(start..end);
+        rope.insert(start, replacement);
+    }
+    Ok(rope)
+}
+
+/// The rows that differ among those `touched` says were written.
+fn row_changes<T: Clone + PartialEq>(
+    before: &HashMap<EntityId, T>,
+    after: &HashMap<EntityId, T>,
+    touched: &Journal<HashSet<EntityId>>,
+) -> Vec<RowChange<T>> {
+    match touched {
+        Journal::Partial(ids) => touched_changes(ids, |id| before.get(id), |id| after.get(id)),
+        Journal::Everything => all_row_changes(before, after),
+    }
+}
+
+fn touched_changes<'a, T: Clone + PartialEq + 'a>(
+    ids: &HashSet<EntityId>,
+    before: impl Fn(&EntityId) -> Option<&'a T>,
+    after: impl Fn(&EntityId) -> Option<&'a T>,
+) -> Vec<RowChange<T>> {
+    let mut changes: Vec<RowChange<T>> = ids
+        .iter()
+        .filter_map(|id| {
+            let (old, new) = (before(id), after(id));
+            (old != new).then(|| RowChange {
+                id: *id,
+                before: old.cloned(),
+                after: new.cloned(),
+            })
+        })
+        .collect();
+    changes.sort_by_key(|c| c.id);
+    changes
+}
+
+fn all_row_changes<T: Clone + PartialEq>(
+    before: &HashMap<EntityId, T>,
+    after: &HashMap<EntityId, T>,
+) -> Vec<RowChange<T>> {
+    if before.ptr_eq(after) {
+        return Vec::new();
+    }
+    let mut changes: Vec<RowChange<T>> = before
+        .iter()
+        .filter(|(id, value)| after.get(id) != Some(value))
+        .map(|(id, value)| RowChange {
+            id: *id,
+            before: Some(value.clone()),
+            after: after.get(id).cloned(),
+        })
+        .chain(
+            after
+                .iter()
+                .filter(|(id, _)| !before.contains_key(id))
+                .map(|(id, value)| RowChange {
+                    id: *id,
+                    before: None,
+                    after: Some(value.clone()),
+                }),
+        )
+        .collect();
+    changes.sort_by_key(|c| c.id);
+    changes
+}
+
+/// The block rows that differ among those `touched` says were written,
+/// leaving out blocks that only moved, and the shifts that move those.
+/// A block the shifts would not move right either way is kept as a row.
+fn block_changes(
+    before: &HashMap<EntityId, Block>,
+    after: &HashMap<EntityId, Block>,
+    touched: &Journal<HashSet<EntityId>>,
+) -> (Vec<RowChange<Block>>, Vec<PositionShift>) {
+    let moved_only = |old: &Block, new: &Block| {
+        old == &Block {
+            updated_at: old.updated_at,
+            document_position: old.document_position,
+            ..new.clone()
+        }
+    };
+    // Blocks changed beyond their position become rows; the others
+    // are kept with their position on each side.
+    let mut rows = Vec::new();
+    let mut positions: Vec<(i64, i64, EntityId)> = Vec::new();
+    let mut sort = |id: EntityId, old: Option<&Block>, new: Option<&Block>| match (old, new) {
+        (Some(old), Some(new)) if moved_only(old, new) => {
+            positions.push((old.document_position, new.document_position, id));
+        }
+        _ if old != new => rows.push(RowChange {
+            id,
+            before: old.cloned(),
+            after: new.cloned(),
+        }),
+        _ => {}
+    };
+    match touched {
+        Journal::Partial(ids) => {
+            for id in ids {
+                sort(*id, before.get(id), after.get(id));
+            }
+        }
+        Journal::Everything if before.ptr_eq(after) => {}
+        Journal::Everything => {
+            for (id, old) in before.iter() {
+                sort(*id, Some(old), after.get(id));
+            }
+            for (id, new) in after.iter() {
+                if !before.contains_key(id) {
+                    sort(*id, None, Some(new));
+                }
+            }
+        }
+    }
+    if positions.iter().all(|(from, to, _)| from == to) {
+        rows.sort_by_key(|c| c.id);
+        return (rows, Vec::new());
+    }
+    // Blocks the journal never saw stay where they were.
+    if let Journal::Partial(ids) = touched {
+        positions.extend(
+            before
+                .iter()
+                .filter(|(id, _)| !ids.contains(*id))
+                .map(|(id, old)| (old.document_position, old.document_position, *id)),
+        );
+    }
+    positions.sort_unstable();
+
+    let mut shifts: Vec<PositionShift> = Vec::new();
+    for (from, to, _) in &positions {
+        let by = to - from;
+        if shift_at(&shifts, *from) != by {
+            shifts.push(PositionShift { from: *from, by });
+        }
+    }
+    let inverse = inverse_shifts(&shifts);
+    for (from, to, id) in positions {
+        if from + shift_at(&shifts, from) != to || to + shift_at(&inverse, to) != from {
+            rows.push(RowChange {
+                id,
+                before: before.get(&id).cloned(),
+                after: after.get(&id).cloned(),
+            });
+        }
+    }
+    rows.sort_by_key(|c| c.id);
+    (rows, shifts)
+}
+
+/// How far `shifts` move a block at `position`.
+fn shift_at(shifts: &[PositionShift], position: i64) -> i64 {
+    let index = shifts.partition_point(|shift| shift.from <= position);
+    index.checked_sub(1).map_or(0, |i| shifts[i].by)
+}
+
+/// The shifts moving blocks back, keyed on the positions they moved to.
+fn inverse_shifts(shifts: &[PositionShift]) -> Vec<PositionShift> {
+    let mut inverse: Vec<PositionShift> = shifts
+        .iter()
+        .map(|shift| PositionShift {
+            from: shift.from + shift.by,
+            by: -shift.by,
+        })
+        .collect();
+    inverse.sort_by_key(|shift| shift.from);
+    inverse
+}
+
+/// Move the blocks `changes` leaves out by `shifts`, found `shift` chars
+/// from where they were recorded.
+fn move_blocks(
+    table: &mut HashMap<EntityId, Block>,
+    changes: &[RowChange<Block>],
+    shifts: &[PositionShift],
+    shift: isize,
+    to: Side,
+) {
+    if shifts.is_empty() {
+        return;
+    }
+    let shifts = match to {
+        Side::After => Cow::Borrowed(shifts),
+        Side::Before => Cow::Owned(inverse_shifts(shifts)),
+    };
+    let kept: HashSet<EntityId> = changes.iter().map(|change| change.id).collect();
+    let moves: Vec<(EntityId, i64)> = table
+        .iter()
+        .filter(|(id, _)| !kept.contains(*id))
+        .map(|(id, block)| {
+            let by = shift_at(&shifts, block.document_position - shift as i64);
+            (*id, by)
+        })
+        .filter(|(_, by)| *by != 0)
+        .collect();
+    for (id, by) in moves {
+        if let Some(block) = table.get_mut(&id) {
+            block.document_position += by;
+        }
+    }
+}
+
+/// The frame rows that differ among those `touched` says were written,
+/// with the block lists of frames found on both sides recorded as
+/// splices instead.
+fn frame_changes(
+    before: &HashMap<EntityId, Frame>,
+    after: &HashMap<EntityId, Frame>,
+    touched: &Journal<HashSet<EntityId>>,
+) -> (Vec<RowChange<Frame>>, Vec<FrameLists>) {
+    let mut rows = row_changes(before, after, touched);
+    let mut lists = Vec::new();
+    for change in &mut rows {
+        if let (Some(old), Some(new)) = (&mut change.before, &mut change.after) {
+            lists.push(FrameLists {
+                id: change.id,
+                blocks: list_splice(
+                    std::mem::take(&mut old.blocks),
+                    std::mem::take(&mut new.blocks),
+                ),
+                child_order: list_splice(
+                    std::mem::take(&mut old.child_order),
+                    std::mem::take(&mut new.child_order),
+                ),
+            });
+        }
+    }
+    (rows, lists)
+}
+
+fn list_splice<T: PartialEq>(mut before: Vec<T>, mut after: Vec<T>) -> ListSplice<T> {
+    let prefix = before
+        .iter()
+        .zip(&after)
+        .take_while(|(x, y)| x == y)
+        .count();
+    let max_suffix = before.len().min(after.len()) - prefix;
+    let suffix = before
+        .iter()
+        .rev()
+        .zip(after.iter().rev())
+        .take(max_suffix)
+        .take_while(|(x, y)| x == y)
+        .count();
+    before.truncate(before.len() - suffix);
+    after.truncate(after.len() - suffix);
+    ListSplice {
+        index: prefix,
+        removed: before.split_off(prefix),
+        inserted: after.split_off(prefix),
+    }
+}
+
+/// `changes` with the block lists `lists` records filled in from the
+/// frames in `table`, checking that those match.
+fn with_frame_lists<'a>(
+    table: &HashMap<EntityId, Frame>,
+    changes: &'a [RowChange<Frame>],
+    lists: &[FrameLists],
+    to: Side,
+) -> Result<Cow<'a, [RowChange<Frame>]>> {
+    if lists.is_empty() {
+        return Ok(Cow::Borrowed(changes));
+    }
+    let mut changes = changes.to_vec();
+    for frame_lists in lists {
+        let (Some(change), Some(frame)) = (
+            changes
+                .iter_mut()
+                .find(|change| change.id == frame_lists.id),
+            table.get(&frame_lists.id),
+        ) else {
+            bail!(
+                "The frame row {} does not match the recorded edit",
+                frame_lists.id
+            );
+        };
+        let (expected, replacement) = match to {
+            Side::After => (&mut change.before, &mut change.after),
+            Side::Before => (&mut change.after, &mut change.before),
+        };
+        let (Some(expected), Some(replacement)) = (expected, replacement) else {
+            bail!(
+                "The frame row {} does not match the recorded edit",
+                frame_lists.id
+            );
+        };
+        replacement.blocks = splice_list(&frame.blocks, &frame_lists.blocks, to)?;
+        replacement.child_order = splice_list(&frame.child_order, &frame_lists.child_order, to)?;
+        expected.blocks = frame.blocks.clone();
+        expected.child_order = frame.child_order.clone();
+    }
+    Ok(Cow::Owned(changes))
+}
+
+fn splice_list<T: Clone + PartialEq>(
+    list: &[T],
+    splice: &ListSplice<T>,
+    to: Side,
+) -> Result<Vec<T>> {
+    let (expected, replacement) = match to {
+        Side::After => (&splice.removed, &splice.inserted),
+        Side::Before => (&splice.inserted, &splice.removed),
+    };
+    let end = splice.index + expected.len();
+    if end > list.len() || list[splice.index..end] != expected[..] {
+        bail!("Frame block list does not match the recorded edit");
+    }
+    let mut list = list.to_vec();
+    list.splice(splice.index..end, replacement.iter().cloned());
+    Ok(list)
+}
+
+/// A row a delta checks before replacing it.
+trait DeltaRow: Clone + PartialEq {
+    /// Whether `self` holds the values `expected` records.
+    fn matches(&self, expected: &Self) -> bool {
+        self == expected
+    }
+
+    /// The row to put in place of `self`, which matched `expected`.
+    fn replaced(&self, _expected: &Self, replacement: &Self) -> Self {
+        replacement.clone()
+    }
+
+    /// The row to add back for `self`, a row whose change was found
+    /// `shift` chars from where it was recorded.
+    fn shifted(&self, _shift: isize) -> Self {
+        self.clone()
+    }
+}
+
+impl<T: Clone + PartialEq> DeltaRow for Vec<T> {}
+
+/// Entity rows match whatever their `updated_at`.
+macro_rules! timestamped_rows {
+    ($($entity:ty),* $(,)?) => {
+        $(impl DeltaRow for $entity {
+            fn matches(&self, expected: &Self) -> bool {
+                self == &Self {
+                    updated_at: self.updated_at,
+                    ..expected.clone()
+                }
+            }
+        })*
+    };
+}
+
+timestamped_rows!(Root, List, Resource, Table, TableCell);
+
+/// Edits elsewhere in the document change its counters too, so they are
+/// moved by the delta's difference rather than checked and reset.
+impl DeltaRow for Document {
+    fn matches(&self, expected: &Self) -> bool {
+        self == &Self {
+            updated_at: self.updated_at,
+            character_count: self.character_count,
+            block_count: self.block_count,
+            ..expected.clone()
+        }
+    }
+
+    fn replaced(&self, expected: &Self, replacement: &Self) -> Self {
+        Self {
+            character_count: self.character_count + replacement.character_count
+                - expected.character_count,
+            block_count: self.block_count + replacement.block_count - expected.block_count,
+            ..replacement.clone()
+        }
+    }
+}
+
+/// Block positions move with every edit before them, so like the
+/// document's counters they are moved rather than checked.
+impl DeltaRow for Block {
+    fn matches(&self, expected: &Self) -> bool {
+        self == &Self {
+            updated_at: self.updated_at,
+            document_position: self.document_position,
+            ..expected.clone()
+        }
+    }
+
+    fn replaced(&self, expected: &Self, replacement: &Self) -> Self {
+        Self {
+            document_position: self.document_position + replacement.document_position
+                - expected.document_position,
+            ..replacement.clone()
+        }
+    }
+
+    fn shifted(&self, shift: isize) -> Self {
+        Self {
+            document_position: self.document_position + shift as i64,
+            ..self.clone()
+        }
+    }
+}
+
+/// Frames also match whatever their `byte_range`: every commit recomputes
+/// it from the rope, so a delta taken inside a transaction records a
+/// value that is stale by the time it is applied.
+impl DeltaRow for Frame {
+    fn matches(&self, expected: &Self) -> bool {
+        self == &Self {
+            updated_at: self.updated_at,
+            byte_range: self.byte_range,
+            ..expected.clone()
+        }
+    }
+}
+
+fn apply_rows<T: DeltaRow>(
+    table: &mut HashMap<EntityId, T>,
+    changes: &[RowChange<T>],
+    shift: isize,
+    to: Side,
+    kind: &str,
+) -> Result<()> {
+    for change in changes {
+        let (expected, replacement) = match to {
+            Side::After => (&change.before, &change.after),
+            Side::Before => (&change.after, &change.before),
+        };
+        let row = match (table.get(&change.id), expected, replacement) {
+            (Some(row), Some(expected), replacement) if row.matches(expected) => {
+                replacement.as_ref().map(|r| row.replaced(expected, r))
+            }
+            (None, None, replacement) => replacement.as_ref().map(|r| r.shifted(shift)),
+            _ => bail!(
+                "The {kind} row {} does not match the recorded edit",
+                change.id
+            ),
+        };
+        match row {
+            Some(value) => table.insert(change.id, value),
+            None => table.remove(&change.id),
+        };
+    }
+    Ok(())
+}
+
+/// Per-block lists treat an empty list like a missing one, as the
+/// native format does when it drops empty lists.
+fn list_changes<T: Clone + PartialEq>(
+    before: &HashMap<EntityId, Vec<T>>,
+    after: &HashMap<EntityId, Vec<T>>,
+    touched: &Journal<HashSet<EntityId>>,
+) -> Vec<RowChange<Vec<T>>> {
+    if let Journal::Partial(ids) = touched {
+        return touched_changes(
+            ids,
+            |id| before.get(id).filter(|list| !list.is_empty()),
+            |id| after.get(id).filter(|list| !list.is_empty()),
+        );
+    }
+    if before.ptr_eq(after) {
+        return Vec::new();
+    }
+    let non_empty = |map: &HashMap<EntityId, Vec<T>>| -> HashMap<EntityId, Vec<T>> {
+        map.iter()
+            .filter(|(_, list)| !list.is_empty())
+            .map(|(id, list)| (*id, list.clone()))
+            .collect()
+    };
+    all_row_changes(&non_empty(before), &non_empty(after))
+}
+
+fn apply_lists<T: Clone + PartialEq>(
+    table: &mut HashMap<EntityId, Vec<T>>,
+    changes: &[RowChange<Vec<T>>],
+    to: Side,
+    kind: &str,
+) -> Result<()> {
+    for change in changes {
+        if table.get(&change.id).is_some_and(Vec::is_empty) {
+            table.remove(&change.id);
+        }
+    }
+    apply_rows(table, changes, 0, to, kind)
+}
+
+/// `(marker, byte_length)` view of an offset index.
+fn offset_lengths(index: &BlockOffsetIndex) -> Vec<(OffsetMarker, u32)> {
+    offset_lengths_in(index, 0..index.len())
+}
+
+/// `(marker, byte_length)` view of the entries in `range`.
+fn offset_lengths_in(
+    index: &BlockOffsetIndex,
+    range: std::ops::Range<usize>,
+) -> Vec<(OffsetMarker, u32)> {
+    let entries = &index.entries;
+    entries[range.clone()]
+        .iter()
+        .zip(range)
+        .map(|((marker, start), i)| {
+            let end = entries
+                .get(i + 1)
+                .map_or(index.total_bytes(), |(_, next)| *next);
+            (*marker, end.saturating_sub(*start))
+        })
+        .collect()
+}
+
+fn first_start(index: &BlockOffsetIndex) -> u32 {
+    index.entries.first().map_or(0, |(_, start)| *start)
+}
+
+/// The offset index splices, looked for in the entries `touched` says
+/// were written.
+fn offset_splices(
+    before: &BlockOffsetIndex,
+    after: &BlockOffsetIndex,
+    touched: &Journal<Stretches>,
+) -> Vec<OffsetSplice> {
+    let ranges = stretch_ranges(touched, before.len(), after.len())
+        .unwrap_or_else(|| vec![(0..before.len(), 0..after.len())]);
+    let (first_before, first_after) = (first_start(before), first_start(after));
+    let mut splices: Vec<OffsetSplice> = ranges
+        .into_iter()
+        .map(|(b, a)| {
+            let start = b.start;
+            let mut splice =
+                lengths_splice(offset_lengths_in(before, b), offset_lengths_in(after, a));
+            splice.index += start;
+            splice.first_start_before = first_before;
+            splice.first_start_after = first_after;
+            splice
+        })
+        .filter(|splice| !splice.removed.is_empty() || !splice.inserted.is_empty())
+        .collect();
+    if splices.is_empty() && first_before != first_after {
+        splices.push(OffsetSplice {
+            first_start_before: first_before,
+            first_start_after: first_after,
+            ..OffsetSplice::default()
+        });
+    }
+    splices
+}
+
+fn lengths_splice(b: Vec<(OffsetMarker, u32)>, a: Vec<(OffsetMarker, u32)>) -> OffsetSplice {
+    let prefix = b.iter().zip(&a).take_while(|(x, y)| x == y).count();
+    let max_suffix = b.len().min(a.len()) - prefix;
+    let suffix = b
+        .iter()
+        .rev()
+        .zip(a.iter().rev())
+        .take(max_suffix)
+        .take_while(|(x, y)| x == y)
+        .count();
+    OffsetSplice {
+        first_start_before: 0,
+        first_start_after: 0,
+        index: prefix,
+        removed: b[prefix..b.len() - suffix].to_vec(),
+        inserted: a[prefix..a.len() - suffix].to_vec(),
+    }
+}
+
+/// Apply `splices`, the first at entry `at`, where
+/// [`StoreDelta::locate`] found it.
+fn splice_offsets(
+    index: &BlockOffsetIndex,
+    splices: &[OffsetSplice],
+    at: usize,
+    total_bytes: u32,
+    to: Side,
+) -> Result<BlockOffsetIndex> {
+    let mut lengths = offset_lengths(index);
+    let mut first = first_start(index);
+    if let Some(splice) = splices.first() {
+        let (expected_first, replacement_first) = match to {
+            Side::After => (splice.first_start_before, splice.first_start_after),
+            Side::Before => (splice.first_start_after, splice.first_start_before),
+        };
+        if first != expected_first {
+            bail!("Block offset index does not match the recorded edit");
+        }
+        first = replacement_first;
+    }
+    // Like text splices, each starts where the ones before it left it,
+    // and they apply from the last.
+    let base = splices.first().map_or(0, |splice| splice.index);
+    let mut growth = 0isize;
+    let mut starts = Vec::with_capacity(splices.len());
+    for splice in splices {
+        let Some(start) = (at + splice.index - base).checked_add_signed(growth) else {
+            bail!("Block offset index does not match the recorded edit");
+        };
+        starts.push(start);
+        if to == Side::Before {
+            growth += splice.inserted.len() as isize - splice.removed.len() as isize;
+        }
+    }
+    for (splice, start) in splices.iter().zip(starts).rev() {
+        let (expected, replacement) = match to {
+            Side::After => (&splice.removed, &splice.inserted),
+            Side::Before => (&splice.inserted, &splice.removed),
+        };
+        let end = start + expected.len();
+        if end > lengths.len() || lengths[start..end] != expected[..] {
+            bail!("Block offset index does not match the recorded edit");
+        }
+        lengths.splice(start..end, replacement.iter().copied());
+    }
+
+    let mut rebuilt = BlockOffsetIndex::new();
+    let mut start = first;
+    for (marker, len) in lengths {
+        rebuilt.push(marker, start);
+        start += len;
+    }
+    rebuilt.set_total_bytes(total_bytes);
+    Ok(rebuilt)
+}
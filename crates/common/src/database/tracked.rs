@@ -0,0 +1,303 @@
+//! Store tables that remember where they were written.
+//!
+//! The store's entity tables, rope and block offset index are wrapped
+//! so every write records the rows, chars or index entries it touched
+//! since the store's journal was last started (see
+//! [`RopeStore::start_journal`](crate::database::rope_store::RopeStore::start_journal)).
+//! [`StoreDelta::since`](crate::database::store_delta::StoreDelta::since)
+//! then compares only those instead of the whole document. Reads go
+//! through `Deref`; writes only through the methods here, so none can
+//! slip past the journal. A table replaced wholesale forgets what it
+//! touched and is compared in full.
+
+use crate::database::block_offset_index::{BlockOffsetIndex, OffsetMarker};
+use crate::types::EntityId;
+use im::HashMap;
+use im::hashmap::Entry;
+use ropey::Rope;
+use std::collections::HashSet;
+use std::collections::hash_map::RandomState;
+use std::ops::{Deref, Range};
+
+/// What was written to a table since its journal started.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Journal<T> {
+    /// Written only where `T` says.
+    Partial(T),
+    /// Replaced wholesale.
+    Everything,
+}
+
+impl<T: Default> Default for Journal<T> {
+    fn default() -> Self {
+        Journal::Partial(T::default())
+    }
+}
+
+/// A stretch touched by splices, in current coordinates, and how long
+/// it was when the journal started.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stretch {
+    pub range: Range<usize>,
+    pub original_len: usize,
+}
+
+/// The stretches touched by splices, in order and apart from each other:
+/// what lies between them is as it was, only moved.
+pub type Stretches = Vec<Stretch>;
+
+/// Record the splice of `removed` items at `at` by `inserted` ones.
+fn touch(journal: &mut Journal<Stretches>, at: usize, removed: usize, inserted: usize) {
+    let Journal::Partial(stretches) = journal else {
+        return;
+    };
+    let end = at + removed;
+    // The stretches the splice overlaps or adjoins merge with it.
+    let first = stretches.partition_point(|s| s.range.end < at);
+    let last = first + stretches[first..].partition_point(|s| s.range.start <= end);
+    let merged = &stretches[first..last];
+    let start = merged.first().map_or(at, |s| s.range.start.min(at));
+    let merged_end = merged.last().map_or(end, |s| s.range.end.max(end));
+    // Items of the merged stretch no earlier splice touched are as long
+    // as they were.
+    let touched_len: usize = merged.iter().map(|s| s.range.len()).sum();
+    let original_len =
+        merged.iter().map(|s| s.original_len).sum::<usize>() + (merged_end - start - touched_len);
+    let stretch = Stretch {
+        range: start..merged_end - removed + inserted,
+        original_len,
+    };
+    for later in &mut stretches[last..] {
+        later.range = later.range.start - removed + inserted..later.range.end - removed + inserted;
+    }
+    stretches.splice(first..last, [stretch]);
+}
+
+// ── Entity tables ────────────────────────────────────────────
+
+/// An entity table that remembers the ids written to.
+#[derive(Debug)]
+pub struct TrackedMap<V: Clone> {
+    map: HashMap<EntityId, V>,
+    journal: Journal<HashSet<EntityId>>,
+}
+
+impl<V: Clone> Default for TrackedMap<V> {
+    fn default() -> Self {
+        Self {
+            map: HashMap::new(),
+            journal: Journal::default(),
+        }
+    }
+}
+
+impl<V: Clone> Deref for TrackedMap<V> {
+    type Target = HashMap<EntityId, V>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.map
+    }
+}
+
+/// Tables are equal when their rows are, whatever they were written at.
+impl<V: Clone + PartialEq> PartialEq for TrackedMap<V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.map == other.map
+    }
+}
+
+impl<V: Clone> TrackedMap<V> {
+    pub fn insert(&mut self, id: EntityId, value: V) -> Option<V> {
+        self.mark(id);
+        self.map.insert(id, value)
+    }
+
+    pub fn remove(&mut self, id: &EntityId) -> Option<V> {
+        self.mark(*id);
+        self.map.remove(id)
+    }
+
+    pub fn get_mut(&mut self, id: &EntityId) -> Option<&mut V> {
+        self.mark(*id);
+        self.map.get_mut(id)
+    }
+
+    pub fn entry(&mut self, id: EntityId) -> Entry<'_, EntityId, V, RandomState> {
+        self.mark(id);
+        self.map.entry(id)
+    }
+
+    pub fn clear(&mut self) {
+        self.replace(HashMap::new());
+    }
+
+    /// Put `map` in place of the whole table.
+    pub fn replace(&mut self, map: HashMap<EntityId, V>) {
+        self.map = map;
+        self.journal = Journal::Everything;
+    }
+
+    pub fn journal(&self) -> &Journal<HashSet<EntityId>> {
+        &self.journal
+    }
+
+    pub(crate) fn clear_journal(&mut self) {
+        self.journal = Journal::default();
+    }
+
+    fn mark(&mut self, id: EntityId) {
+        if let Journal::Partial(ids) = &mut self.journal {
+            ids.insert(id);
+        }
+    }
+}
+
+// ── Rope ─────────────────────────────────────────────────────
+
+/// The document rope, remembering the chars it spliced.
+#[derive(Debug, Default)]
+pub struct TrackedRope {
+    rope: Rope,
+    journal: Journal<Stretches>,
+}
+
+impl Deref for TrackedRope {
+    type Target = Rope;
+
+    fn deref(&self) -> &Rope {
+        &self.rope
+    }
+}
+
+impl TrackedRope {
+    pub fn insert(&mut self, char_idx: usize, text: &str) {
+        touch(&mut self.journal, char_idx, 0, text.chars().count());
+        self.rope.insert(char_idx, text);
+    }
+
+    pub fn remove(&mut self, char_range: Range<usize>) {
+        touch(&mut self.journal, char_range.start, char_range.len(), 0);
+        self.rope.remove(char_range);
+    }
+
+    /// Put `rope` in place of the whole text.
+    pub fn replace(&mut self, rope: Rope) {
+        self.rope = rope;
+        self.journal = Journal::Everything;
+    }
+
+    /// The chars touched, in the current text.
+    pub fn journal(&self) -> &Journal<Stretches> {
+        &self.journal
+    }
+
+    pub(crate) fn clear_journal(&mut self) {
+        self.journal = Journal::default();
+    }
+}
+
+// ── Block offset index ───────────────────────────────────────
+
+/// The block offset index, remembering the entries whose marker or
+/// byte length it changed. Shifting entries along leaves their lengths
+/// alone, so only the entry before a shift counts as touched.
+#[derive(Debug, Default)]
+pub struct TrackedOffsets {
+    index: BlockOffsetIndex,
+    journal: Journal<Stretches>,
+}
+
+impl Deref for TrackedOffsets {
+    type Target = BlockOffsetIndex;
+
+    fn deref(&self) -> &BlockOffsetIndex {
+        &self.index
+    }
+}
+
+impl PartialEq for TrackedOffsets {
+    fn eq(&self, other: &Self) -> bool {
+        self.index == other.index
+    }
+}
+
+impl TrackedOffsets {
+    pub fn insert_at(&mut self, position: usize, marker: OffsetMarker, byte_start: u32) {
+        self.touch_from(position, 0, 1);
+        self.index.insert_at(position, marker, byte_start);
+    }
+
+    pub fn push(&mut self, marker: OffsetMarker, byte_start: u32) {
+        self.touch_from(self.index.len(), 0, 1);
+        self.index.push(marker, byte_start);
+    }
+
+    pub fn push_block(&mut self, block_id: EntityId, byte_start: u32) {
+        self.push(OffsetMarker::Block(block_id), byte_start);
+    }
+
+    pub fn remove_at(&mut self, position: usize) -> (OffsetMarker, u32) {
+        self.touch_from(position, 1, 0);
+        self.index.remove_at(position)
+    }
+
+    pub fn drain_inclusive(
+        &mut self,
+        start: usize,
+        end_inclusive: usize,
+    ) -> Vec<(OffsetMarker, u32)> {
+        self.touch_from(start, end_inclusive + 1 - start, 0);
+        self.index.drain_inclusive(start, end_inclusive)
+    }
+
+    pub fn clear(&mut self) {
+        touch(&mut self.journal, 0, self.index.len(), 0);
+        self.index.clear();
+    }
+
+    pub fn rebuild_marker_index(&mut self) {
+        self.index.rebuild_marker_index();
+    }
+
+    pub fn shift_after(&mut self, threshold: u32, delta: i32) {
+        let start = self
+            .index
+            .entries
+            .partition_point(|(_, bs)| *bs < threshold);
+        if delta != 0 && start > 0 {
+            touch(&mut self.journal, start - 1, 1, 1);
+        }
+        self.index.shift_after(threshold, delta);
+    }
+
+    pub fn set_total_bytes(&mut self, total: u32) {
+        if let Some(last) = self.index.len().checked_sub(1) {
+            touch(&mut self.journal, last, 1, 1);
+        }
+        self.index.set_total_bytes(total);
+    }
+
+    /// Put `index` in place of the whole index.
+    pub fn replace(&mut self, index: BlockOffsetIndex) {
+        self.index = index;
+        self.journal = Journal::Everything;
+    }
+
+    /// The entries touched, in the current index.
+    pub fn journal(&self) -> &Journal<Stretches> {
+        &self.journal
+    }
+
+    pub(crate) fn clear_journal(&mut self) {
+        self.journal = Journal::default();
+    }
+
+    /// Splice at `position`, which also changes the length of the entry
+    /// before it.
+    fn touch_from(&mut self, position: usize, removed: usize, inserted: usize) {
+        match position.checked_sub(1) {
+            Some(before) => touch(&mut self.journal, before, removed + 1, inserted + 1),
+            None => touch(&mut self.journal, position, removed, inserted),
+        }
+    }
+}
@@ -17,6 +17,7 @@ pub struct Transaction {
 impl Transaction {
     pub fn begin_write_transaction(db_context: &DbContext) -> Result<Transaction> {
         let store = Arc::clone(db_context.get_store());
+        store.start_journal();
         let savepoint = Some(store.create_savepoint());
         Ok(Transaction {
             store,
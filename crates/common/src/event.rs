@@ -98,12 +98,21 @@ pub enum DocumentIoEvent {
     ExportHtml,
     ExportLatex,
     ExportDocx,
+    ImportNative,
+    ExportNative,
+    ImportDocx,
+    ImportOdt,
+    ExportOdt,
+    ImportRtf,
+    ExportRtf,
+    ExportEpub,
 }
 
 #[derive(Debug, Clone, Hash, PartialEq, Eq, Serialize)]
 pub enum DocumentSearchEvent {
     FindText,
     FindAll,
+    FindByFormat,
     ReplaceText,
 }
 
@@ -58,6 +58,9 @@ pub struct InlineSegment {
     pub fmt_tooltip: Option<String>,
     pub fmt_underline_style: Option<UnderlineStyle>,
     pub fmt_vertical_alignment: Option<CharVerticalAlignment>,
+    pub fmt_foreground_color: Option<String>,
+    pub fmt_background_color: Option<String>,
+    pub fmt_underline_color: Option<String>,
 }
 
 /// Character-level formatting for a contiguous byte span. One per
@@ -82,6 +85,10 @@ pub struct CharacterFormat {
     pub tooltip: Option<String>,
     pub underline_style: Option<UnderlineStyle>,
     pub vertical_alignment: Option<CharVerticalAlignment>,
+    /// Colors as `#rrggbb`, or `#rrggbbaa` when not fully opaque.
+    pub foreground_color: Option<String>,
+    pub background_color: Option<String>,
+    pub underline_color: Option<String>,
 }
 
 /// One run of identical character formatting inside a block. Byte offsets
@@ -514,6 +521,9 @@ pub fn character_format_from_segment(seg: &InlineSegment) -> CharacterFormat {
         tooltip: seg.fmt_tooltip.clone(),
         underline_style: seg.fmt_underline_style.clone(),
         vertical_alignment: seg.fmt_vertical_alignment.clone(),
+        foreground_color: seg.fmt_foreground_color.clone(),
+        background_color: seg.fmt_background_color.clone(),
+        underline_color: seg.fmt_underline_color.clone(),
     }
 }
 
@@ -535,6 +545,9 @@ pub fn apply_character_format_to_segment(seg: &mut InlineSegment, fmt: &Characte
     seg.fmt_tooltip = fmt.tooltip.clone();
     seg.fmt_underline_style = fmt.underline_style.clone();
     seg.fmt_vertical_alignment = fmt.vertical_alignment.clone();
+    seg.fmt_foreground_color = fmt.foreground_color.clone();
+    seg.fmt_background_color = fmt.background_color.clone();
+    seg.fmt_underline_color = fmt.underline_color.clone();
 }
 
 /// Synthesize a `Vec<InlineSegment>` view of a block from its
@@ -546,11 +559,17 @@ pub fn apply_character_format_to_segment(seg: &mut InlineSegment, fmt: &Characte
 /// The canonical reader-side accessor for per-segment data — there is
 /// no persistent inline-element table; this view is computed fresh
 /// each call.
+///
+/// When the text carries the U+FFFC sentinel at an anchor's byte
+/// offset (the rope representation written by `insert_image` and the
+/// importers), the sentinel is folded into the Image segment instead
+/// of leaking into the neighbouring Text segment.
 pub fn inline_segments_view(
     plain_text: &str,
     runs: &[FormatRun],
     images: &[ImageAnchor],
 ) -> Vec<InlineSegment> {
+    const SENTINEL: &str = "\u{FFFC}";
     let mut out: Vec<InlineSegment> = Vec::new();
     let bytes = plain_text.as_bytes();
 
@@ -588,9 +607,20 @@ pub fn inline_segments_view(
         out.push(seg);
     };
 
+    // Byte offset just past the image anchored at `offset`: skips the
+    // U+FFFC sentinel when the text carries one there.
+    let after_image = |offset: u32| -> u32 {
+        let start = offset as usize;
+        if bytes.get(start..start + SENTINEL.len()) == Some(SENTINEL.as_bytes()) {
+            offset + SENTINEL.len() as u32
+        } else {
+            offset
+        }
+    };
+
     for run in runs {
         while let Some(img) = img_iter.peek() {
-            if img.byte_offset < run.byte_start {
+            if img.byte_offset <= run.byte_start {
                 emit_text(
                     &mut out,
                     bytes,
@@ -599,7 +629,7 @@ pub fn inline_segments_view(
                     CharacterFormat::default(),
                 );
                 emit_image(&mut out, img);
-                cursor = img.byte_offset;
+                cursor = cursor.max(after_image(img.byte_offset));
                 img_iter.next();
             } else {
                 break;
@@ -616,14 +646,19 @@ pub fn inline_segments_view(
             );
         }
 
-        emit_text(
-            &mut out,
-            bytes,
-            run.byte_start,
-            run.byte_end,
-            run.format.clone(),
-        );
-        cursor = run.byte_end;
+        // Images anchored inside the run split it.
+        let mut start = run.byte_start.max(cursor);
+        while let Some(&img) = img_iter.peek()
+            && img.byte_offset < run.byte_end
+        {
+            emit_text(&mut out, bytes, start, img.byte_offset, run.format.clone());
+            emit_image(&mut out, img);
+            start = start.max(after_image(img.byte_offset));
+            img_iter.next();
+        }
+
+        emit_text(&mut out, bytes, start, run.byte_end, run.format.clone());
+        cursor = cursor.max(run.byte_end).max(start);
     }
 
     for img in img_iter {
@@ -638,6 +673,7 @@ pub fn inline_segments_view(
             cursor = img.byte_offset;
         }
         emit_image(&mut out, img);
+        cursor = cursor.max(after_image(img.byte_offset));
     }
 
     if (cursor as usize) < bytes.len() {
@@ -718,4 +754,24 @@ mod tests {
         assert_eq!(rs[1].byte_start, 13);
         assert_eq!(rs[1].byte_end, 18);
     }
+
+    #[test]
+    fn segments_split_run_around_inner_image() {
+        let text = "H\u{FFFC}ead";
+        let image = ImageAnchor {
+            byte_offset: 1,
+            name: "img.png".into(),
+            width: 1,
+            height: 1,
+            quality: 100,
+            format: CharacterFormat::default(),
+        };
+        let segments = inline_segments_view(text, &[run(0, text.len() as u32, true)], &[image]);
+        let contents: Vec<&InlineContent> = segments.iter().map(|s| &s.content).collect();
+        assert_eq!(contents.len(), 3);
+        assert_eq!(contents[0], &InlineContent::Text("H".into()));
+        assert!(matches!(contents[1], InlineContent::Image { .. }));
+        assert_eq!(contents[2], &InlineContent::Text("ead".into()));
+        assert_eq!(segments[2].fmt_font_bold, Some(true));
+    }
 }
@@ -1,5 +1,6 @@
 // Generated by Qleany v1.5.1 from common_lib.tera
 
+pub mod collab;
 pub mod database;
 pub mod direct_access;
 pub mod entities;
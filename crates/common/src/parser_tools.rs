@@ -1,3 +1,7 @@
 pub mod content_parser;
+pub mod css_color;
 pub mod fragment_schema;
 pub mod list_grouper;
+pub mod rich_document;
+pub mod rtf_reader;
+pub mod rtf_writer;
@@ -1,4 +1,5 @@
 use crate::entities::{ListStyle, TextDirection};
+use crate::parser_tools::css_color::normalize_css_color;
 
 /// A parsed inline span with formatting info
 #[derive(Debug, Clone, Default)]
@@ -10,6 +11,10 @@ pub struct ParsedSpan {
     pub strikeout: bool,
     pub code: bool,
     pub link_href: Option<String>,
+    /// Colors from inline CSS, normalized to `#rrggbb` / `#rrggbbaa`.
+    pub foreground_color: Option<String>,
+    pub background_color: Option<String>,
+    pub underline_color: Option<String>,
 }
 
 /// A parsed table cell containing inline spans.
@@ -365,6 +370,7 @@ pub fn parse_markdown(markdown: &str) -> Vec<ParsedElement> {
                     strikeout,
                     code: is_code_block,
                     link_href: link_href.clone(),
+                    ..Default::default()
                 };
                 if in_table {
                     current_cell_spans.push(span);
@@ -384,6 +390,7 @@ pub fn parse_markdown(markdown: &str) -> Vec<ParsedElement> {
                     strikeout,
                     code: true,
                     link_href: link_href.clone(),
+                    ..Default::default()
                 };
                 if in_table {
                     current_cell_spans.push(span);
@@ -403,6 +410,7 @@ pub fn parse_markdown(markdown: &str) -> Vec<ParsedElement> {
                     strikeout,
                     code: false,
                     link_href: link_href.clone(),
+                    ..Default::default()
                 };
                 if in_table {
                     current_cell_spans.push(span);
@@ -558,6 +566,56 @@ pub fn parse_html_elements(html: &str) -> Vec<ParsedElement> {
         strikeout: bool,
         code: bool,
         link_href: Option<String>,
+        foreground_color: Option<String>,
+        background_color: Option<String>,
+        underline_color: Option<String>,
+    }
+
+    impl FmtState {
+        fn span(&self, text: String) -> ParsedSpan {
+            ParsedSpan {
+                text,
+                bold: self.bold,
+                italic: self.italic,
+                underline: self.underline,
+                strikeout: self.strikeout,
+                code: self.code,
+                link_href: self.link_href.clone(),
+                foreground_color: self.foreground_color.clone(),
+                background_color: self.background_color.clone(),
+                underline_color: self.underline_color.clone(),
+            }
+        }
+
+        /// Pick up character colors from a `style` attribute and from
+        /// `<font color>`. A block element's `background-color` belongs to
+        /// the block, so it is only applied for inline elements.
+        fn apply_colors(&mut self, el: &scraper::node::Element, inline: bool) {
+            if let Some(color) = el.attr("color").filter(|_| el.name() == "font")
+                && let Some(color) = normalize_css_color(color)
+            {
+                self.foreground_color = Some(color);
+            }
+            let Some(style) = el.attr("style") else {
+                return;
+            };
+            for part in style.split(';') {
+                let Some((prop, val)) = part.split_once(':') else {
+                    continue;
+                };
+                let Some(color) = normalize_css_color(val) else {
+                    continue;
+                };
+                match prop.trim().to_ascii_lowercase().as_str() {
+                    "color" => self.foreground_color = Some(color),
+                    "background-color" | "background" if inline => {
+                        self.background_color = Some(color)
+                    }
+                    "text-decoration-color" => self.underline_color = Some(color),
+                    _ => {}
+                }
+            }
+        }
     }
 
     const MAX_RECURSION_DEPTH: usize = 256;
@@ -577,15 +635,7 @@ pub fn parse_html_elements(html: &str) -> Vec<ParsedElement> {
                 Node::Text(text) => {
                     let t = text.text.to_string();
                     if !t.is_empty() {
-                        spans.push(ParsedSpan {
-                            text: t,
-                            bold: state.bold,
-                            italic: state.italic,
-                            underline: state.underline,
-                            strikeout: state.strikeout,
-                            code: state.code,
-                            link_href: state.link_href.clone(),
-                        });
+                        spans.push(state.span(t));
                     }
                 }
                 Node::Element(el) => {
@@ -604,6 +654,7 @@ pub fn parse_html_elements(html: &str) -> Vec<ParsedElement> {
                         }
                         _ => {}
                     }
+                    new_state.apply_colors(el, true);
                     collect_cell_spans(child, &new_state, spans, depth + 1);
                 }
                 _ => {}
@@ -728,6 +779,7 @@ pub fn parse_html_elements(html: &str) -> Vec<ParsedElement> {
                     }
                     _ => {}
                 }
+                new_state.apply_colors(el, !is_block_tag);
 
                 // Determine heading level
                 let heading_level = match tag {
@@ -891,15 +943,7 @@ pub fn parse_html_elements(html: &str) -> Vec<ParsedElement> {
                 if !trimmed.is_empty() {
                     // Bare text not in a block — create a paragraph
                     elements.push(ParsedElement::Block(ParsedBlock {
-                        spans: vec![ParsedSpan {
-                            text: trimmed.to_string(),
-                            bold: state.bold,
-                            italic: state.italic,
-                            underline: state.underline,
-                            strikeout: state.strikeout,
-                            code: state.code,
-                            link_href: state.link_href.clone(),
-                        }],
+                        spans: vec![state.span(trimmed.to_string())],
                         heading_level: None,
                         list_style: None,
                         list_indent: 0,
@@ -952,15 +996,7 @@ pub fn parse_html_elements(html: &str) -> Vec<ParsedElement> {
                 Node::Text(text) => {
                     let t = text.text.to_string();
                     if !t.is_empty() {
-                        spans.push(ParsedSpan {
-                            text: t,
-                            bold: state.bold,
-                            italic: state.italic,
-                            underline: state.underline,
-                            strikeout: state.strikeout,
-                            code: state.code,
-                            link_href: state.link_href.clone(),
-                        });
+                        spans.push(state.span(t));
                     }
                 }
                 Node::Element(el) => {
@@ -997,6 +1033,7 @@ pub fn parse_html_elements(html: &str) -> Vec<ParsedElement> {
                             | "ul"
                             | "ol"
                     );
+                    new_state.apply_colors(el, !nested_block);
 
                     if tag == "br" {
                         // br within a block: treat as splitting into new block
@@ -1112,6 +1149,9 @@ pub fn character_format_from_span(
         } else {
             None
         },
+        foreground_color: span.foreground_color.clone(),
+        background_color: span.background_color.clone(),
+        underline_color: span.underline_color.clone(),
         ..Default::default()
     }
 }
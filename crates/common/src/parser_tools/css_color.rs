@@ -0,0 +1,153 @@
+//! CSS color values ↔ the `#rrggbb` / `#rrggbbaa` strings stored in
+//! [`CharacterFormat`](crate::format_runs::CharacterFormat).
+//!
+//! Accepts hex notation (`#rgb`, `#rgba`, `#rrggbb`, `#rrggbbaa`),
+//! `rgb()` / `rgba()` with integer or percentage channels, `transparent`
+//! and the CSS 2.1 named colors.
+
+const NAMED_COLORS: &[(&str, [u8; 3])] = &[
+    ("black", [0x00, 0x00, 0x00]),
+    ("silver", [0xc0, 0xc0, 0xc0]),
+    ("gray", [0x80, 0x80, 0x80]),
+    ("grey", [0x80, 0x80, 0x80]),
+    ("white", [0xff, 0xff, 0xff]),
+    ("maroon", [0x80, 0x00, 0x00]),
+    ("red", [0xff, 0x00, 0x00]),
+    ("purple", [0x80, 0x00, 0x80]),
+    ("fuchsia", [0xff, 0x00, 0xff]),
+    ("magenta", [0xff, 0x00, 0xff]),
+    ("green", [0x00, 0x80, 0x00]),
+    ("lime", [0x00, 0xff, 0x00]),
+    ("olive", [0x80, 0x80, 0x00]),
+    ("yellow", [0xff, 0xff, 0x00]),
+    ("navy", [0x00, 0x00, 0x80]),
+    ("blue", [0x00, 0x00, 0xff]),
+    ("teal", [0x00, 0x80, 0x80]),
+    ("aqua", [0x00, 0xff, 0xff]),
+    ("cyan", [0x00, 0xff, 0xff]),
+    ("orange", [0xff, 0xa5, 0x00]),
+];
+
+/// Parse a CSS color value into `[red, green, blue, alpha]`.
+pub fn parse_css_color(value: &str) -> Option<[u8; 4]> {
+    let value = value.trim().trim_end_matches("!important").trim();
+    if let Some(hex) = value.strip_prefix('#') {
+        return parse_hex(hex);
+    }
+    let lower = value.to_ascii_lowercase();
+    if lower == "transparent" {
+        return Some([0, 0, 0, 0]);
+    }
+    if let Some(args) = lower
+        .strip_prefix("rgba(")
+        .or_else(|| lower.strip_prefix("rgb("))
+        .and_then(|rest| rest.strip_suffix(')'))
+    {
+        return parse_rgb_args(args);
+    }
+    NAMED_COLORS
+        .iter()
+        .find(|(name, _)| *name == lower)
+        .map(|(_, [r, g, b])| [*r, *g, *b, 255])
+}
+
+/// Format as `#rrggbb`, or `#rrggbbaa` when the color is not opaque.
+pub fn format_hex_color([r, g, b, a]: [u8; 4]) -> String {
+    if a == 255 {
+        format!("#{:02x}{:02x}{:02x}", r, g, b)
+    } else {
+        format!("#{:02x}{:02x}{:02x}{:02x}", r, g, b, a)
+    }
+}
+
+/// Parse any supported CSS color and return its canonical hex form.
+pub fn normalize_css_color(value: &str) -> Option<String> {
+    parse_css_color(value).map(format_hex_color)
+}
+
+/// Upper-case `RRGGBB` digits without `#` or alpha, as DOCX, RTF and
+/// LaTeX's `[HTML]` color model expect.
+pub fn rgb_hex_digits(value: &str) -> Option<String> {
+    parse_css_color(value).map(|[r, g, b, _]| format!("{:02X}{:02X}{:02X}", r, g, b))
+}
+
+fn parse_hex(hex: &str) -> Option<[u8; 4]> {
+    if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let digit = |i: usize| u8::from_str_radix(&hex[i..i + 1], 16).ok().map(|v| v * 17);
+    let pair = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    match hex.len() {
+        3 => Some([digit(0)?, digit(1)?, digit(2)?, 255]),
+        4 => Some([digit(0)?, digit(1)?, digit(2)?, digit(3)?]),
+        6 => Some([pair(0)?, pair(2)?, pair(4)?, 255]),
+        8 => Some([pair(0)?, pair(2)?, pair(4)?, pair(6)?]),
+        _ => None,
+    }
+}
+
+fn parse_rgb_args(args: &str) -> Option<[u8; 4]> {
+    // Both the legacy `r, g, b, a` and the modern `r g b / a` syntax.
+    let parts: Vec<&str> = args
+        .split([',', '/', ' '])
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .collect();
+    if parts.len() != 3 && parts.len() != 4 {
+        return None;
+    }
+    let channel = |p: &str| -> Option<u8> {
+        if let Some(pct) = p.strip_suffix('%') {
+            let v: f64 = pct.parse().ok()?;
+            Some((v.clamp(0.0, 100.0) * 2.55).round() as u8)
+        } else {
+            let v: f64 = p.parse().ok()?;
+            Some(v.clamp(0.0, 255.0).round() as u8)
+        }
+    };
+    let alpha = match parts.get(3) {
+        None => 255,
+        Some(p) => {
+            let v: f64 = match p.strip_suffix('%') {
+                Some(pct) => pct.parse::<f64>().ok()? / 100.0,
+                None => p.parse().ok()?,
+            };
+            (v.clamp(0.0, 1.0) * 255.0).round() as u8
+        }
+    };
+    Some([
+        channel(parts[0])?,
+        channel(parts[1])?,
+        channel(parts[2])?,
+        alpha,
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_supported_notations() {
+        assert_eq!(parse_css_color("#f00"), Some([255, 0, 0, 255]));
+        assert_eq!(parse_css_color("#00FF0080"), Some([0, 255, 0, 128]));
+        assert_eq!(parse_css_color(" Navy "), Some([0, 0, 128, 255]));
+        assert_eq!(parse_css_color("rgb(1, 2, 3)"), Some([1, 2, 3, 255]));
+        assert_eq!(parse_css_color("rgba(1,2,3,0.5)"), Some([1, 2, 3, 128]));
+        assert_eq!(
+            parse_css_color("rgb(100% 0% 0% / 50%)"),
+            Some([255, 0, 0, 128])
+        );
+        assert_eq!(parse_css_color("transparent"), Some([0, 0, 0, 0]));
+        assert_eq!(parse_css_color("inherit"), None);
+        assert_eq!(parse_css_color("#12345"), None);
+    }
+
+    #[test]
+    fn formats_hex_with_alpha_only_when_translucent() {
+        assert_eq!(format_hex_color([255, 16, 0, 255]), "#ff1000");
+        assert_eq!(format_hex_color([255, 16, 0, 1]), "#ff100001");
+        assert_eq!(normalize_css_color("RED").as_deref(), Some("#ff0000"));
+        assert_eq!(rgb_hex_digits("#0a0b0c80").as_deref(), Some("0A0B0C"));
+    }
+}
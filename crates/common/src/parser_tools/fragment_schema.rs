@@ -1,7 +1,7 @@
 use serde::{Deserialize, Serialize};
 
 use crate::entities::*;
-use crate::format_runs::{InlineContent, InlineSegment};
+use crate::format_runs::{CharacterFormat, InlineContent, InlineSegment};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FragmentData {
@@ -98,6 +98,9 @@ pub struct FragmentElement {
     pub fmt_tooltip: Option<String>,
     pub fmt_underline_style: Option<UnderlineStyle>,
     pub fmt_vertical_alignment: Option<CharVerticalAlignment>,
+    pub fmt_foreground_color: Option<String>,
+    pub fmt_background_color: Option<String>,
+    pub fmt_underline_color: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -128,6 +131,58 @@ impl FragmentElement {
             fmt_tooltip: seg.fmt_tooltip.clone(),
             fmt_underline_style: seg.fmt_underline_style.clone(),
             fmt_vertical_alignment: seg.fmt_vertical_alignment.clone(),
+            fmt_foreground_color: seg.fmt_foreground_color.clone(),
+            fmt_background_color: seg.fmt_background_color.clone(),
+            fmt_underline_color: seg.fmt_underline_color.clone(),
+        }
+    }
+
+    pub fn from_format(content: InlineContent, format: &CharacterFormat) -> Self {
+        FragmentElement {
+            content,
+            fmt_font_family: format.font_family.clone(),
+            fmt_font_point_size: format.font_point_size,
+            fmt_font_weight: format.font_weight,
+            fmt_font_bold: format.font_bold,
+            fmt_font_italic: format.font_italic,
+            fmt_font_underline: format.font_underline,
+            fmt_font_overline: format.font_overline,
+            fmt_font_strikeout: format.font_strikeout,
+            fmt_letter_spacing: format.letter_spacing,
+            fmt_word_spacing: format.word_spacing,
+            fmt_anchor_href: format.anchor_href.clone(),
+            fmt_anchor_names: format.anchor_names.clone(),
+            fmt_is_anchor: format.is_anchor,
+            fmt_tooltip: format.tooltip.clone(),
+            fmt_underline_style: format.underline_style.clone(),
+            fmt_vertical_alignment: format.vertical_alignment.clone(),
+            fmt_foreground_color: format.foreground_color.clone(),
+            fmt_background_color: format.background_color.clone(),
+            fmt_underline_color: format.underline_color.clone(),
+        }
+    }
+
+    pub fn to_character_format(&self) -> CharacterFormat {
+        CharacterFormat {
+            font_family: self.fmt_font_family.clone(),
+            font_point_size: self.fmt_font_point_size,
+            font_weight: self.fmt_font_weight,
+            font_bold: self.fmt_font_bold,
+            font_italic: self.fmt_font_italic,
+            font_underline: self.fmt_font_underline,
+            font_overline: self.fmt_font_overline,
+            font_strikeout: self.fmt_font_strikeout,
+            letter_spacing: self.fmt_letter_spacing,
+            word_spacing: self.fmt_word_spacing,
+            anchor_href: self.fmt_anchor_href.clone(),
+            anchor_names: self.fmt_anchor_names.clone(),
+            is_anchor: self.fmt_is_anchor,
+            tooltip: self.fmt_tooltip.clone(),
+            underline_style: self.fmt_underline_style.clone(),
+            vertical_alignment: self.fmt_vertical_alignment.clone(),
+            foreground_color: self.fmt_foreground_color.clone(),
+            background_color: self.fmt_background_color.clone(),
+            underline_color: self.fmt_underline_color.clone(),
         }
     }
 }
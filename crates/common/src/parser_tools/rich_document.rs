@@ -0,0 +1,110 @@
+//! Format-neutral document model shared by the rich format readers and
+//! writers (DOCX, ODT, RTF).
+//!
+//! Unlike [`ParsedElement`](super::content_parser::ParsedElement), blocks
+//! carry full [`CharacterFormat`] runs and inline images, tables carry
+//! row/column spans and multi-paragraph cells, and embedded resources
+//! travel with the document. `document_io` builds entities from it on
+//! import and collects it from the store on export.
+
+use crate::entities::{Alignment, ListStyle};
+use crate::format_runs::CharacterFormat;
+
+/// One inline piece of a [`RichBlock`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum RichInline {
+    Text {
+        text: String,
+        format: CharacterFormat,
+    },
+    Image {
+        name: String,
+        width: i64,
+        height: i64,
+        format: CharacterFormat,
+    },
+}
+
+/// List membership of a [`RichBlock`]. Items sharing `key` and `indent`
+/// belong to the same `List` entity, even when other paragraphs come
+/// between them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RichListItem {
+    pub key: String,
+    pub style: ListStyle,
+    pub indent: u32,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RichBlock {
+    pub inlines: Vec<RichInline>,
+    pub heading_level: Option<i64>,
+    pub alignment: Option<Alignment>,
+    /// Paragraph margins and first-line indent, in pixels.
+    pub left_margin: Option<i64>,
+    pub right_margin: Option<i64>,
+    pub text_indent: Option<i64>,
+    pub list: Option<RichListItem>,
+    pub is_code_block: bool,
+}
+
+impl RichBlock {
+    /// Append text with `format`, merging into the previous run when the
+    /// format is identical.
+    pub fn push_text(&mut self, text: &str, format: &CharacterFormat) {
+        if text.is_empty() {
+            return;
+        }
+        if let Some(RichInline::Text {
+            text: last,
+            format: last_format,
+        }) = self.inlines.last_mut()
+            && last_format == format
+        {
+            last.push_str(text);
+            return;
+        }
+        self.inlines.push(RichInline::Text {
+            text: text.to_string(),
+            format: format.clone(),
+        });
+    }
+}
+
+/// A table cell anchored at (`row`, `column`). Positions covered by a
+/// span have no cell of their own.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RichCell {
+    pub row: usize,
+    pub column: usize,
+    pub row_span: usize,
+    pub column_span: usize,
+    pub blocks: Vec<RichBlock>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RichTable {
+    pub rows: usize,
+    pub columns: usize,
+    pub cells: Vec<RichCell>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RichElement {
+    Block(RichBlock),
+    Table(RichTable),
+}
+
+/// Binary payload referenced by name from [`RichInline::Image`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct RichResource {
+    pub name: String,
+    pub mime_type: String,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RichDocument {
+    pub elements: Vec<RichElement>,
+    pub resources: Vec<RichResource>,
+}
@@ -0,0 +1,821 @@
+//! RTF reader producing a [`RichDocument`].
+//!
+//! Handles the subset of RTF 1.9 that word processors and clipboards
+//! exchange: the font and color tables, character formatting and colors, paragraph alignment
+//! and indents, `\outlinelevel` headings, `HYPERLINK` fields, simple
+//! tables (`\trowd` … `\cell` … `\row`, with horizontal and vertical
+//! merges) and PNG/JPEG/metafile `\pict` images. Unknown destinations are
+//! skipped; unknown control words are ignored.
+
+use super::css_color::format_hex_color;
+use super::rich_document::{
+    RichBlock, RichCell, RichDocument, RichElement, RichInline, RichResource, RichTable,
+};
+use crate::entities::{Alignment, CharVerticalAlignment, UnderlineStyle};
+use crate::format_runs::CharacterFormat;
+use anyhow::{Result, anyhow};
+use std::collections::HashMap;
+
+/// Twips (1/1440 inch) per pixel at 96 DPI.
+pub const TWIPS_PER_PIXEL: i64 = 15;
+
+/// Parse an RTF document. Fails when the input does not start with an
+/// `{\rtf` group.
+pub fn parse_rtf(rtf: &str) -> Result<RichDocument> {
+    if !rtf.trim_start().starts_with("{\\rtf") {
+        return Err(anyhow!("Not an RTF document: missing {{\\rtf header"));
+    }
+    let mut parser = Parser::new(rtf);
+    parser.run();
+    Ok(parser.finish())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Destination {
+    Text,
+    Skip,
+    FontTable,
+    ColorTable,
+    FieldInstruction,
+    Picture,
+}
+
+#[derive(Debug, Clone, Default)]
+struct ParagraphProps {
+    alignment: Option<Alignment>,
+    left_margin: Option<i64>,
+    right_margin: Option<i64>,
+    text_indent: Option<i64>,
+    heading_level: Option<i64>,
+    in_table: bool,
+}
+
+#[derive(Debug, Clone)]
+struct GroupState {
+    destination: Destination,
+    format: CharacterFormat,
+    paragraph: ParagraphProps,
+    /// Fallback characters to skip after `\uN`.
+    unicode_skip: usize,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+enum Merge {
+    #[default]
+    None,
+    First,
+    Continue,
+}
+
+#[derive(Debug, Clone, Default)]
+struct CellDef {
+    right: i64,
+    horizontal: Merge,
+    vertical: Merge,
+}
+
+/// A finished table row: cell definitions with their content.
+struct Row {
+    cells: Vec<(CellDef, Vec<RichBlock>)>,
+}
+
+#[derive(Default)]
+struct Picture {
+    format: Option<&'static str>,
+    width: i64,
+    height: i64,
+    goal_width: i64,
+    goal_height: i64,
+    scale_x: i64,
+    scale_y: i64,
+    hex: String,
+    binary: Vec<u8>,
+}
+
+struct Parser<'a> {
+    input: &'a [u8],
+    pos: usize,
+    stack: Vec<GroupState>,
+    state: GroupState,
+    fonts: HashMap<i64, String>,
+    default_font: Option<i64>,
+    /// Font table entry being read: number and name so far.
+    font_entry: Option<(i64, String)>,
+    /// Color table entries; `None` is the automatic color.
+    colors: Vec<Option<String>>,
+    /// Color table entry being read, once a component was seen.
+    color_entry: Option<[u8; 4]>,
+    field_instruction: String,
+    picture: Option<Picture>,
+    /// Stack depth at which the current picture group started.
+    picture_depth: usize,
+    /// Pending high surrogate from a `\u` pair.
+    high_surrogate: Option<u16>,
+
+    elements: Vec<RichElement>,
+    resources: Vec<RichResource>,
+    block: RichBlock,
+    /// `\pard` seen since the last paragraph end.
+    paragraph_open: bool,
+
+    row_defs: Vec<CellDef>,
+    /// Merge flags seen before the next `\cellx`.
+    pending_def: CellDef,
+    row_cells: Vec<Vec<RichBlock>>,
+    cell_blocks: Vec<RichBlock>,
+    rows: Vec<Row>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(rtf: &'a str) -> Self {
+        let state = GroupState {
+            destination: Destination::Text,
+            format: CharacterFormat::default(),
+            paragraph: ParagraphProps::default(),
+            unicode_skip: 1,
+        };
+        Parser {
+            input: rtf.as_bytes(),
+            pos: 0,
+            stack: Vec::new(),
+            state,
+            fonts: HashMap::new(),
+            default_font: None,
+            font_entry: None,
+            colors: Vec::new(),
+            color_entry: None,
+            field_instruction: String::new(),
+            picture: None,
+            picture_depth: 0,
+            high_surrogate: None,
+            elements: Vec::new(),
+            resources: Vec::new(),
+            block: RichBlock::default(),
+            paragraph_open: false,
+            row_defs: Vec::new(),
+            pending_def: CellDef::default(),
+            row_cells: Vec::new(),
+            cell_blocks: Vec::new(),
+            rows: Vec::new(),
+        }
+    }
+
+    fn run(&mut self) {
+        while self.pos < self.input.len() {
+            match self.input[self.pos] {
+                b'{' => {
+                    self.pos += 1;
+                    self.stack.push(self.state.clone());
+                }
+                b'}' => {
+                    self.pos += 1;
+                    self.end_group();
+                }
+                b'\\' => self.control(),
+                b'\r' | b'\n' => self.pos += 1,
+                _ => {
+                    let start = self.pos;
+                    while self.pos < self.input.len()
+                        && !matches!(self.input[self.pos], b'{' | b'}' | b'\\' | b'\r' | b'\n')
+                    {
+                        self.pos += 1;
+                    }
+                    let text = String::from_utf8_lossy(&self.input[start..self.pos]).into_owned();
+                    self.text(&text);
+                }
+            }
+        }
+    }
+
+    fn next_is_hex_escape(&self) -> bool {
+        self.input.get(self.pos + 1) == Some(&b'\'')
+    }
+
+    fn end_group(&mut self) {
+        if self.state.destination == Destination::FontTable
+            && let Some((number, name)) = self.font_entry.take()
+        {
+            self.add_font(number, &name);
+        }
+        if self.state.destination == Destination::Picture && self.stack.len() == self.picture_depth
+        {
+            self.finish_picture();
+        }
+        if self.stack.len() == 1 {
+            // Closing the `{\rtf` group: the last paragraph may lack `\par`.
+            self.flush_pending();
+        }
+        if let Some(state) = self.stack.pop() {
+            self.state = state;
+        }
+    }
+
+    fn add_font(&mut self, number: i64, name: &str) {
+        let name = name.trim().trim_end_matches(';').trim();
+        if !name.is_empty() {
+            self.fonts.insert(number, name.to_string());
+        }
+    }
+
+    /// Color table entry `param`; index 0 and unknown indices are the
+    /// automatic color.
+    fn table_color(&self, param: Option<i64>) -> Option<String> {
+        let index = usize::try_from(param.unwrap_or(0)).ok()?;
+        self.colors.get(index).cloned().flatten()
+    }
+
+    /// Read a control word or control symbol at `self.pos`.
+    fn control(&mut self) {
+        self.pos += 1;
+        let Some(&c) = self.input.get(self.pos) else {
+            return;
+        };
+        if !c.is_ascii_alphabetic() {
+            self.pos += 1;
+            match c {
+                b'\'' => {
+                    let hex = self.input.get(self.pos..self.pos + 2).unwrap_or_default();
+                    self.pos += hex.len();
+                    if let Ok(byte) = u8::from_str_radix(std::str::from_utf8(hex).unwrap_or(""), 16)
+                    {
+                        self.text(&cp1252_char(byte).to_string());
+                    }
+                }
+                b'\\' | b'{' | b'}' => self.text(&(c as char).to_string()),
+                b'~' => self.text("\u{00A0}"),
+                b'_' => self.text("\u{2011}"),
+                // Ignorable destination, unless it is one we read.
+                // `\shppict` holds the preferred picture.
+                b'*' if !["shppict", "fldinst"]
+                    .iter()
+                    .any(|word| self.upcoming_word_is(word)) =>
+                {
+                    self.state.destination = Destination::Skip;
+                }
+                b'\r' | b'\n' => self.paragraph_end(),
+                _ => {}
+            }
+            return;
+        }
+
+        let start = self.pos;
+        while self.pos < self.input.len() && self.input[self.pos].is_ascii_alphabetic() {
+            self.pos += 1;
+        }
+        let word = String::from_utf8_lossy(&self.input[start..self.pos]).into_owned();
+        let num_start = self.pos;
+        if self.input.get(self.pos) == Some(&b'-') {
+            self.pos += 1;
+        }
+        while self.pos < self.input.len() && self.input[self.pos].is_ascii_digit() {
+            self.pos += 1;
+        }
+        let param = std::str::from_utf8(&self.input[num_start..self.pos])
+            .ok()
+            .and_then(|n| n.parse::<i64>().ok());
+        if self.input.get(self.pos) == Some(&b' ') {
+            self.pos += 1;
+        }
+        self.word(&word, param);
+    }
+
+    fn upcoming_word_is(&self, word: &str) -> bool {
+        let rest = &self.input[self.pos..];
+        let rest = rest.strip_prefix(b" ").unwrap_or(rest);
+        rest.strip_prefix(b"\\")
+            .is_some_and(|r| r.starts_with(word.as_bytes()))
+    }
+
+    fn word(&mut self, word: &str, param: Option<i64>) {
+        if self.state.destination == Destination::Picture {
+            self.picture_word(word, param);
+            return;
+        }
+        if self.state.destination == Destination::FontTable && word == "f" {
+            if let Some((number, name)) = self.font_entry.take() {
+                self.add_font(number, &name);
+            }
+            self.font_entry = Some((param.unwrap_or(0), String::new()));
+            return;
+        }
+        if self.state.destination == Destination::ColorTable {
+            let entry = self.color_entry.get_or_insert([0, 0, 0, 255]);
+            let component = param.unwrap_or(0).clamp(0, 255) as u8;
+            match word {
+                "red" => entry[0] = component,
+                "green" => entry[1] = component,
+                "blue" => entry[2] = component,
+                _ => {}
+            }
+            return;
+        }
+        if self.state.destination == Destination::Skip {
+            if word == "bin" {
+                self.pos += param.unwrap_or(0).max(0) as usize;
+            }
+            return;
+        }
+
+        let on = param != Some(0);
+        let format = &mut self.state.format;
+        match word {
+            // Destinations
+            "fonttbl" => self.state.destination = Destination::FontTable,
+            "colortbl" => self.state.destination = Destination::ColorTable,
+            "stylesheet" | "info" | "header" | "headerl" | "headerr" | "headerf" | "footer"
+            | "footerl" | "footerr" | "footerf" | "footnote" | "listtable"
+            | "listoverridetable" | "pntext" | "listtext" | "nonshppict" | "revtbl"
+            | "xmlnstbl" | "latentstyles" | "themedata" | "colorschememapping" | "datastore"
+            | "generator" | "rsidtbl" | "mmathPr" => self.state.destination = Destination::Skip,
+            "fldinst" => {
+                self.field_instruction.clear();
+                self.state.destination = Destination::FieldInstruction;
+            }
+            "fldrslt" => {
+                if let Some(href) = hyperlink_target(&self.field_instruction) {
+                    self.state.format.anchor_href = Some(href);
+                    self.state.format.is_anchor = Some(true);
+                }
+            }
+            "pict" => {
+                self.state.destination = Destination::Picture;
+                self.picture = Some(Picture {
+                    scale_x: 100,
+                    scale_y: 100,
+                    ..Picture::default()
+                });
+                self.picture_depth = self.stack.len();
+            }
+            "deff" => self.default_font = param,
+            "uc" => self.state.unicode_skip = param.unwrap_or(1).max(0) as usize,
+            "u" => {
+                if let Some(code) = param {
+                    let unit = if code < 0 { code + 65536 } else { code } as u16;
+                    self.unicode(unit);
+                    self.skip_fallback();
+                }
+            }
+
+            // Character formatting
+            "plain" => {
+                self.state.format = CharacterFormat::default();
+            }
+            "b" => format.font_bold = on.then_some(true),
+            "i" => format.font_italic = on.then_some(true),
+            "strike" | "striked" => format.font_strikeout = on.then_some(true),
+            "ul" | "ulw" | "uldb" | "ulth" => set_underline(format, on, None),
+            "uld" | "ulthd" => set_underline(format, on, Some(UnderlineStyle::DotLine)),
+            "uldash" | "ulldash" | "ulthdash" | "ulthldash" => {
+                set_underline(format, on, Some(UnderlineStyle::DashUnderline))
+            }
+            "uldashd" | "ulthdashd" => set_underline(format, on, Some(UnderlineStyle::DashDotLine)),
+            "uldashdd" | "ulthdashdd" => {
+                set_underline(format, on, Some(UnderlineStyle::DashDotDotLine))
+            }
+            "ulwave" | "ulhwave" | "ululdbwave" => {
+                set_underline(format, on, Some(UnderlineStyle::WaveUnderline))
+            }
+            "ulnone" => set_underline(format, false, None),
+            "super" => format.vertical_alignment = on.then_some(CharVerticalAlignment::SuperScript),
+            "sub" => format.vertical_alignment = on.then_some(CharVerticalAlignment::SubScript),
+            "nosupersub" => format.vertical_alignment = None,
+            "up" => {
+                format.vertical_alignment =
+                    (param.unwrap_or(6) > 0).then_some(CharVerticalAlignment::SuperScript)
+            }
+            "dn" => {
+                format.vertical_alignment =
+                    (param.unwrap_or(6) > 0).then_some(CharVerticalAlignment::SubScript)
+            }
+            "fs" => format.font_point_size = param.map(|half_points| half_points / 2),
+            "cf" => self.state.format.foreground_color = self.table_color(param),
+            "cb" | "chcbpat" | "highlight" => {
+                self.state.format.background_color = self.table_color(param)
+            }
+            "ulc" => self.state.format.underline_color = self.table_color(param),
+            "f" => {
+                let font = param.unwrap_or(0);
+                format.font_family = if Some(font) == self.default_font {
+                    None
+                } else {
+                    self.fonts.get(&font).cloned()
+                };
+            }
+
+            // Paragraph formatting
+            "pard" => {
+                self.state.paragraph = ParagraphProps::default();
+                self.paragraph_open = true;
+            }
+            "ql" => self.state.paragraph.alignment = Some(Alignment::Left),
+            "qr" => self.state.paragraph.alignment = Some(Alignment::Right),
+            "qc" => self.state.paragraph.alignment = Some(Alignment::Center),
+            "qj" | "qd" => self.state.paragraph.alignment = Some(Alignment::Justify),
+            "li" | "lin" => {
+                self.state.paragraph.left_margin = twips_to_pixels(param);
+            }
+            "ri" | "rin" => {
+                self.state.paragraph.right_margin = twips_to_pixels(param);
+            }
+            "fi" => self.state.paragraph.text_indent = twips_to_pixels(param),
+            "outlinelevel" => {
+                self.state.paragraph.heading_level = param
+                    .filter(|level| (0..6).contains(level))
+                    .map(|level| level + 1);
+            }
+            "intbl" => self.state.paragraph.in_table = true,
+
+            // Special characters and breaks
+            "par" | "sect" => self.paragraph_end(),
+            "line" => self.line_break(),
+            "tab" => self.text("\t"),
+            "emdash" => self.text("\u{2014}"),
+            "endash" => self.text("\u{2013}"),
+            "bullet" => self.text("\u{2022}"),
+            "lquote" => self.text("\u{2018}"),
+            "rquote" => self.text("\u{2019}"),
+            "ldblquote" => self.text("\u{201C}"),
+            "rdblquote" => self.text("\u{201D}"),
+            "emspace" | "enspace" | "qmspace" => self.text(" "),
+
+            // Tables
+            "trowd" => {
+                self.row_defs.clear();
+                self.pending_def = CellDef::default();
+            }
+            "cellx" => {
+                let mut def = std::mem::take(&mut self.pending_def);
+                def.right = param.unwrap_or(0);
+                self.row_defs.push(def);
+            }
+            "clmgf" => self.pending_def.horizontal = Merge::First,
+            "clmrg" => self.pending_def.horizontal = Merge::Continue,
+            "clvmgf" => self.pending_def.vertical = Merge::First,
+            "clvmrg" => self.pending_def.vertical = Merge::Continue,
+            "cell" | "nestcell" => self.cell_end(),
+            "row" | "nestrow" => self.row_end(),
+            _ => {}
+        }
+    }
+
+    fn picture_word(&mut self, word: &str, param: Option<i64>) {
+        let Some(picture) = self.picture.as_mut() else {
+            return;
+        };
+        let value = param.unwrap_or(0);
+        match word {
+            "pngblip" => picture.format = Some("png"),
+            "jpegblip" => picture.format = Some("jpeg"),
+            "emfblip" => picture.format = Some("emf"),
+            "wmetafile" => picture.format = Some("wmf"),
+            "picw" => picture.width = value,
+            "pich" => picture.height = value,
+            "picwgoal" => picture.goal_width = value,
+            "pichgoal" => picture.goal_height = value,
+            "picscalex" => picture.scale_x = value,
+            "picscaley" => picture.scale_y = value,
+            "bin" => {
+                let len = value.max(0) as usize;
+                let end = (self.pos + len).min(self.input.len());
+                picture.binary.extend_from_slice(&self.input[self.pos..end]);
+                self.pos = end;
+            }
+            _ => {}
+        }
+    }
+
+    fn finish_picture(&mut self) {
+        let Some(picture) = self.picture.take() else {
+            return;
+        };
+        let Some(format) = picture.format else {
+            return;
+        };
+        let mut data = picture.binary;
+        let hex: Vec<u8> = picture
+            .hex
+            .bytes()
+            .filter(|b| b.is_ascii_hexdigit())
+            .collect();
+        for pair in hex.chunks_exact(2) {
+            if let Ok(byte) = u8::from_str_radix(std::str::from_utf8(pair).unwrap_or(""), 16) {
+                data.push(byte);
+            }
+        }
+        if data.is_empty() {
+            return;
+        }
+
+        let (width, height) = if picture.goal_width > 0 && picture.goal_height > 0 {
+            (
+                picture.goal_width / TWIPS_PER_PIXEL,
+                picture.goal_height / TWIPS_PER_PIXEL,
+            )
+        } else {
+            (picture.width, picture.height)
+        };
+        let width = width * picture.scale_x / 100;
+        let height = height * picture.scale_y / 100;
+
+        let extension = match format {
+            "jpeg" => "jpg",
+            other => other,
+        };
+        let name = format!("image{}.{}", self.resources.len() + 1, extension);
+        let mime_type = match format {
+            "png" => "image/png",
+            "jpeg" => "image/jpeg",
+            "emf" => "image/emf",
+            _ => "image/wmf",
+        };
+        self.resources.push(RichResource {
+            name: name.clone(),
+            mime_type: mime_type.to_string(),
+            data,
+        });
+        self.block.inlines.push(RichInline::Image {
+            name,
+            width,
+            height,
+            format: self.stack_format_outside_picture(),
+        });
+    }
+
+    /// Character format of the group enclosing the picture.
+    fn stack_format_outside_picture(&self) -> CharacterFormat {
+        self.stack
+            .get(self.picture_depth.saturating_sub(1))
+            .map(|s| s.format.clone())
+            .unwrap_or_default()
+    }
+
+    fn skip_fallback(&mut self) {
+        let mut remaining = self.state.unicode_skip;
+        while remaining > 0 && self.pos < self.input.len() {
+            match self.input[self.pos] {
+                b'{' | b'}' => break,
+                b'\\' => {
+                    if self.next_is_hex_escape() {
+                        self.pos += 4;
+                    } else {
+                        // Any other control word counts as one character.
+                        self.pos += 1;
+                        while self.pos < self.input.len()
+                            && self.input[self.pos].is_ascii_alphanumeric()
+                        {
+                            self.pos += 1;
+                        }
+                        if self.input.get(self.pos) == Some(&b' ') {
+                            self.pos += 1;
+                        }
+                    }
+                }
+                _ => {
+                    // Skip one UTF-8 character.
+                    self.pos += 1;
+                    while self.pos < self.input.len() && (self.input[self.pos] & 0xC0) == 0x80 {
+                        self.pos += 1;
+                    }
+                }
+            }
+            remaining -= 1;
+        }
+    }
+
+    fn unicode(&mut self, unit: u16) {
+        if (0xD800..0xDC00).contains(&unit) {
+            self.high_surrogate = Some(unit);
+            return;
+        }
+        let c = match self.high_surrogate.take() {
+            Some(high) if (0xDC00..0xE000).contains(&unit) => {
+                char::decode_utf16([high, unit]).next().and_then(|r| r.ok())
+            }
+            _ => char::from_u32(unit as u32),
+        };
+        if let Some(c) = c {
+            self.text(&c.to_string());
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        match self.state.destination {
+            Destination::Text => self.block.push_text(text, &self.state.format),
+            Destination::FontTable => {
+                if let Some((_, name)) = self.font_entry.as_mut() {
+                    name.push_str(text);
+                }
+            }
+            Destination::ColorTable => {
+                for _ in text.matches(';') {
+                    let entry = self.color_entry.take().map(format_hex_color);
+                    self.colors.push(entry);
+                }
+            }
+            Destination::FieldInstruction => self.field_instruction.push_str(text),
+            Destination::Picture => {
+                if let Some(picture) = self.picture.as_mut() {
+                    picture.hex.push_str(text);
+                }
+            }
+            Destination::Skip => {}
+        }
+    }
+
+    /// Take the block being built, with the paragraph properties in
+    /// effect at its end.
+    fn take_block(&mut self) -> RichBlock {
+        let props = &self.state.paragraph;
+        let mut block = std::mem::take(&mut self.block);
+        block.alignment = props.alignment.clone();
+        block.left_margin = props.left_margin;
+        block.right_margin = props.right_margin;
+        block.text_indent = props.text_indent;
+        block.heading_level = props.heading_level;
+        block
+    }
+
+    fn paragraph_end(&mut self) {
+        if self.state.destination != Destination::Text {
+            return;
+        }
+        let block = self.take_block();
+        self.paragraph_open = false;
+        if self.state.paragraph.in_table {
+            self.cell_blocks.push(block);
+        } else {
+            self.flush_table();
+            self.elements.push(RichElement::Block(block));
+        }
+    }
+
+    /// `\line` starts a new block with the same paragraph properties.
+    fn line_break(&mut self) {
+        let open = self.paragraph_open;
+        self.paragraph_end();
+        self.paragraph_open = open;
+    }
+
+    fn cell_end(&mut self) {
+        let block = self.take_block();
+        self.cell_blocks.push(block);
+        let blocks = std::mem::take(&mut self.cell_blocks);
+        self.row_cells.push(blocks);
+        self.paragraph_open = false;
+    }
+
+    fn row_end(&mut self) {
+        let cells = std::mem::take(&mut self.row_cells);
+        if cells.is_empty() {
+            return;
+        }
+        let mut defs = self.row_defs.clone();
+        // Rows without usable definitions get equal-width columns.
+        while defs.len() < cells.len() {
+            let right = defs.last().map_or(0, |d| d.right) + 1440;
+            defs.push(CellDef {
+                right,
+                ..CellDef::default()
+            });
+        }
+        self.rows.push(Row {
+            cells: defs.into_iter().zip(cells).collect(),
+        });
+    }
+
+    /// Turn the collected rows into a table element.
+    fn flush_table(&mut self) {
+        if !self.row_cells.is_empty() {
+            self.row_end();
+        }
+        let rows = std::mem::take(&mut self.rows);
+        if rows.is_empty() {
+            return;
+        }
+
+        // Columns are the distinct right edges of all cell definitions,
+        // so wider cells span the columns other rows split.
+        let mut edges: Vec<i64> = rows
+            .iter()
+            .flat_map(|r| r.cells.iter().map(|(d, _)| d.right))
+            .collect();
+        edges.sort_unstable();
+        edges.dedup();
+        let column_of = |edge: i64| edges.iter().position(|e| *e == edge).unwrap_or(0);
+
+        let mut cells: Vec<RichCell> = Vec::new();
+        for (row_index, row) in rows.iter().enumerate() {
+            let mut left_column = 0;
+            for (def, blocks) in &row.cells {
+                let right_column = column_of(def.right) + 1;
+                let column = left_column;
+                let column_span = right_column.saturating_sub(column).max(1);
+                left_column = right_column;
+
+                if def.horizontal == Merge::Continue
+                    && let Some(previous) = cells
+                        .iter_mut()
+                        .rev()
+                        .find(|c| c.row == row_index && c.column + c.column_span == column)
+                {
+                    previous.column_span += column_span;
+                    continue;
+                }
+                if def.vertical == Merge::Continue
+                    && let Some(above) = cells
+                        .iter_mut()
+                        .rev()
+                        .find(|c| c.column == column && c.row + c.row_span == row_index)
+                {
+                    above.row_span += 1;
+                    continue;
+                }
+                cells.push(RichCell {
+                    row: row_index,
+                    column,
+                    row_span: 1,
+                    column_span,
+                    blocks: blocks.clone(),
+                });
+            }
+        }
+
+        self.elements.push(RichElement::Table(RichTable {
+            rows: rows.len(),
+            columns: edges.len(),
+            cells,
+        }));
+    }
+
+    /// Emit the paragraph or cell left open at the end of the document.
+    fn flush_pending(&mut self) {
+        if self.state.destination != Destination::Text {
+            return;
+        }
+        let pending = !self.block.inlines.is_empty() || self.paragraph_open;
+        if self.state.paragraph.in_table {
+            if pending {
+                self.cell_end();
+            }
+            if !self.row_cells.is_empty() {
+                self.row_end();
+            }
+        } else if pending {
+            self.paragraph_end();
+        }
+    }
+
+    fn finish(mut self) -> RichDocument {
+        self.flush_pending();
+        self.flush_table();
+        RichDocument {
+            elements: self.elements,
+            resources: self.resources,
+        }
+    }
+}
+
+fn set_underline(format: &mut CharacterFormat, on: bool, style: Option<UnderlineStyle>) {
+    format.font_underline = on.then_some(true);
+    format.underline_style = if on { style } else { None };
+}
+
+fn twips_to_pixels(twips: Option<i64>) -> Option<i64> {
+    twips
+        .map(|t| t / TWIPS_PER_PIXEL)
+        .filter(|pixels| *pixels != 0)
+}
+
+/// Extract the target of a `HYPERLINK "url"` (or `HYPERLINK \l "anchor"`)
+/// field instruction.
+fn hyperlink_target(instruction: &str) -> Option<String> {
+    let rest = instruction.trim().strip_prefix("HYPERLINK")?.trim();
+    let (local, rest) = match rest.strip_prefix("\\l") {
+        Some(r) => (true, r.trim()),
+        None => (false, rest),
+    };
+    let target = match rest.strip_prefix('"') {
+        Some(quoted) => quoted.split('"').next().unwrap_or(""),
+        None => rest.split_whitespace().next().unwrap_or(""),
+    };
+    if target.is_empty() {
+        return None;
+    }
+    Some(if local {
+        format!("#{}", target)
+    } else {
+        target.to_string()
+    })
+}
+
+fn cp1252_char(byte: u8) -> char {
+    const HIGH: [char; 32] = [
+        '€', '\u{81}', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\u{8D}', 'Ž',
+        '\u{8F}', '\u{90}', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\u{9D}',
+        'ž', 'Ÿ',
+    ];
+    match byte {
+        0x80..=0x9F => HIGH[(byte - 0x80) as usize],
+        b => b as char,
+    }
+}
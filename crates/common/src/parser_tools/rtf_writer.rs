@@ -0,0 +1,440 @@
+//! RTF writer for a [`RichDocument`].
+//!
+//! Emits plain RTF 1.9 that word processors and clipboards read back:
+//! font and color tables, one `\pard` paragraph per block, character formatting
+//! as groups, `HYPERLINK` fields, `\trowd` tables and PNG/JPEG `\pict`
+//! images. Lists become indented paragraphs with a `\pntext` label;
+//! code blocks and anything else RTF has no notion of are written as
+//! plain paragraphs.
+
+use super::css_color::parse_css_color;
+use super::rich_document::{RichBlock, RichDocument, RichElement, RichInline, RichTable};
+use super::rtf_reader::TWIPS_PER_PIXEL;
+use crate::entities::{Alignment, CharVerticalAlignment, ListStyle, UnderlineStyle};
+use crate::format_runs::CharacterFormat;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+/// Width given to each table column, in twips (1.5 inch).
+const COLUMN_WIDTH: i64 = 2160;
+/// List indent per level, in twips (0.5 inch).
+const LIST_INDENT: i64 = 720;
+
+/// Serialize `doc` as an RTF document.
+pub fn write_rtf(doc: &RichDocument) -> String {
+    let fonts = FontTable::collect(doc);
+    let colors = ColorTable::collect(doc);
+    let mut writer = Writer {
+        out: String::new(),
+        fonts: &fonts,
+        colors: &colors,
+        doc,
+        counters: HashMap::new(),
+    };
+
+    writer
+        .out
+        .push_str("{\\rtf1\\ansi\\ansicpg1252\\deff0\\uc1\n{\\fonttbl{\\f0\\fnil Calibri;}");
+    for (index, name) in fonts.names.iter().enumerate() {
+        let _ = write!(writer.out, "{{\\f{}\\fnil {};}}", index + 1, escape(name));
+    }
+    writer.out.push_str("}\n{\\colortbl;");
+    for [r, g, b] in &colors.colors {
+        let _ = write!(writer.out, "\\red{}\\green{}\\blue{};", r, g, b);
+    }
+    writer.out.push_str("}\n");
+
+    let paragraphs = count_paragraphs(doc);
+    let mut written = 0;
+    for element in &doc.elements {
+        match element {
+            RichElement::Block(block) => {
+                written += 1;
+                writer.block(block, false);
+                if written < paragraphs {
+                    writer.out.push_str("\\par");
+                }
+                writer.out.push('\n');
+            }
+            RichElement::Table(table) => writer.table(table),
+        }
+    }
+    writer.out.push('}');
+    writer.out
+}
+
+/// Top-level paragraphs; the last one is written without `\par` so that
+/// reading the document back does not add an empty paragraph.
+fn count_paragraphs(doc: &RichDocument) -> usize {
+    doc.elements
+        .iter()
+        .filter(|e| matches!(e, RichElement::Block(_)))
+        .count()
+}
+
+/// Call `f` with the character format of every inline in `doc`.
+fn for_each_format(doc: &RichDocument, mut f: impl FnMut(&CharacterFormat)) {
+    let mut visit = |block: &RichBlock| {
+        for inline in &block.inlines {
+            match inline {
+                RichInline::Text { format, .. } | RichInline::Image { format, .. } => f(format),
+            }
+        }
+    };
+    for element in &doc.elements {
+        match element {
+            RichElement::Block(block) => visit(block),
+            RichElement::Table(table) => table
+                .cells
+                .iter()
+                .flat_map(|c| &c.blocks)
+                .for_each(&mut visit),
+        }
+    }
+}
+
+/// Font families used by the document, numbered from `\f1`.
+struct FontTable {
+    names: Vec<String>,
+}
+
+impl FontTable {
+    fn collect(doc: &RichDocument) -> Self {
+        let mut names: Vec<String> = Vec::new();
+        for_each_format(doc, |format| {
+            if let Some(family) = &format.font_family
+                && !names.contains(family)
+            {
+                names.push(family.clone());
+            }
+        });
+        FontTable { names }
+    }
+
+    fn index_of(&self, family: &str) -> Option<usize> {
+        self.names.iter().position(|n| n == family).map(|i| i + 1)
+    }
+}
+
+/// Colors used by the document, numbered from 1; entry 0 is the
+/// automatic color. RTF has no alpha channel, so it is dropped.
+struct ColorTable {
+    colors: Vec<[u8; 3]>,
+}
+
+impl ColorTable {
+    fn collect(doc: &RichDocument) -> Self {
+        let mut colors: Vec<[u8; 3]> = Vec::new();
+        for_each_format(doc, |format| {
+            for color in [
+                &format.foreground_color,
+                &format.background_color,
+                &format.underline_color,
+            ] {
+                if let Some([r, g, b, _]) = color.as_deref().and_then(parse_css_color)
+                    && !colors.contains(&[r, g, b])
+                {
+                    colors.push([r, g, b]);
+                }
+            }
+        });
+        ColorTable { colors }
+    }
+
+    fn index_of(&self, color: &str) -> Option<usize> {
+        let [r, g, b, _] = parse_css_color(color)?;
+        self.colors
+            .iter()
+            .position(|c| *c == [r, g, b])
+            .map(|i| i + 1)
+    }
+}
+
+struct Writer<'a> {
+    out: String,
+    fonts: &'a FontTable,
+    colors: &'a ColorTable,
+    doc: &'a RichDocument,
+    /// Next number per list key, for ordered list labels.
+    counters: HashMap<(String, u32), usize>,
+}
+
+impl Writer<'_> {
+    fn block(&mut self, block: &RichBlock, in_table: bool) {
+        self.out.push_str("\\pard\\plain");
+        if in_table {
+            self.out.push_str("\\intbl");
+        }
+        match block.alignment {
+            Some(Alignment::Right) => self.out.push_str("\\qr"),
+            Some(Alignment::Center) => self.out.push_str("\\qc"),
+            Some(Alignment::Justify) => self.out.push_str("\\qj"),
+            Some(Alignment::Left) | None => {}
+        }
+
+        let mut left = block.left_margin.unwrap_or(0) * TWIPS_PER_PIXEL;
+        let mut first = block.text_indent.unwrap_or(0) * TWIPS_PER_PIXEL;
+        if let Some(item) = &block.list {
+            left += LIST_INDENT * item.indent.max(1) as i64;
+            first -= LIST_INDENT / 2;
+        }
+        if left != 0 {
+            let _ = write!(self.out, "\\li{}", left);
+        }
+        if first != 0 {
+            let _ = write!(self.out, "\\fi{}", first);
+        }
+        if let Some(right) = block.right_margin.filter(|r| *r != 0) {
+            let _ = write!(self.out, "\\ri{}", right * TWIPS_PER_PIXEL);
+        }
+        if let Some(level) = block.heading_level.filter(|l| (1..=6).contains(l)) {
+            let _ = write!(self.out, "\\outlinelevel{}", level - 1);
+        }
+        self.out.push(' ');
+
+        if let Some(item) = &block.list {
+            let label = self.list_label(&item.key, item.indent, &item.style);
+            let _ = write!(self.out, "{{\\pntext {}\\tab}}", escape(&label));
+        }
+        for inline in &block.inlines {
+            match inline {
+                RichInline::Text { text, format } => self.run(text, format),
+                RichInline::Image {
+                    name,
+                    width,
+                    height,
+                    ..
+                } => self.image(name, *width, *height),
+            }
+        }
+    }
+
+    fn list_label(&mut self, key: &str, indent: u32, style: &ListStyle) -> String {
+        let counter = self.counters.entry((key.to_string(), indent)).or_insert(0);
+        *counter += 1;
+        let n = *counter;
+        match style {
+            ListStyle::Disc => "\u{2022}".to_string(),
+            ListStyle::Circle => "\u{25E6}".to_string(),
+            ListStyle::Square => "\u{25AA}".to_string(),
+            ListStyle::Decimal => format!("{}.", n),
+            ListStyle::LowerAlpha => format!("{}.", alpha(n).to_lowercase()),
+            ListStyle::UpperAlpha => format!("{}.", alpha(n)),
+            ListStyle::LowerRoman => format!("{}.", roman(n).to_lowercase()),
+            ListStyle::UpperRoman => format!("{}.", roman(n)),
+        }
+    }
+
+    fn run(&mut self, text: &str, format: &CharacterFormat) {
+        if let Some(href) = &format.anchor_href {
+            let _ = write!(
+                self.out,
+                "{{\\field{{\\*\\fldinst{{HYPERLINK \"{}\"}}}}{{\\fldrslt",
+                escape(href)
+            );
+        }
+        self.out.push('{');
+        let start = self.out.len();
+        self.character_format(format);
+        if self.out.len() > start {
+            self.out.push(' ');
+        }
+        self.out.push_str(&escape(text));
+        self.out.push('}');
+        if format.anchor_href.is_some() {
+            self.out.push_str("}}");
+        }
+    }
+
+    fn character_format(&mut self, format: &CharacterFormat) {
+        if format.font_bold == Some(true) || format.font_weight.is_some_and(|w| w >= 600) {
+            self.out.push_str("\\b");
+        }
+        if format.font_italic == Some(true) {
+            self.out.push_str("\\i");
+        }
+        if format.font_underline == Some(true) {
+            self.out.push_str(match format.underline_style {
+                Some(UnderlineStyle::DashUnderline) => "\\uldash",
+                Some(UnderlineStyle::DotLine) => "\\uld",
+                Some(UnderlineStyle::DashDotLine) => "\\uldashd",
+                Some(UnderlineStyle::DashDotDotLine) => "\\uldashdd",
+                Some(UnderlineStyle::WaveUnderline) => "\\ulwave",
+                _ => "\\ul",
+            });
+        }
+        if format.font_strikeout == Some(true) {
+            self.out.push_str("\\strike");
+        }
+        match format.vertical_alignment {
+            Some(CharVerticalAlignment::SuperScript) => self.out.push_str("\\super"),
+            Some(CharVerticalAlignment::SubScript) => self.out.push_str("\\sub"),
+            _ => {}
+        }
+        if let Some(index) = format
+            .font_family
+            .as_deref()
+            .and_then(|f| self.fonts.index_of(f))
+        {
+            let _ = write!(self.out, "\\f{}", index);
+        }
+        if let Some(size) = format.font_point_size.filter(|s| *s > 0) {
+            let _ = write!(self.out, "\\fs{}", size * 2);
+        }
+        let color_words = [
+            ("\\cf", &format.foreground_color),
+            ("\\chcbpat", &format.background_color),
+            ("\\ulc", &format.underline_color),
+        ];
+        for (word, color) in color_words {
+            if let Some(index) = color.as_deref().and_then(|c| self.colors.index_of(c)) {
+                let _ = write!(self.out, "{}{}", word, index);
+            }
+        }
+    }
+
+    fn image(&mut self, name: &str, width: i64, height: i64) {
+        let Some(resource) = self.doc.resources.iter().find(|r| r.name == name) else {
+            return;
+        };
+        let blip = match resource.mime_type.as_str() {
+            "image/png" => "\\pngblip",
+            "image/jpeg" | "image/jpg" => "\\jpegblip",
+            // Other formats have no RTF blip type readers agree on.
+            _ => return,
+        };
+        let _ = writeln!(
+            self.out,
+            "{{\\pict{}\\picw{}\\pich{}\\picwgoal{}\\pichgoal{}",
+            blip,
+            width,
+            height,
+            width * TWIPS_PER_PIXEL,
+            height * TWIPS_PER_PIXEL
+        );
+        for line in resource.data.chunks(64) {
+            for byte in line {
+                let _ = write!(self.out, "{:02x}", byte);
+            }
+            self.out.push('\n');
+        }
+        self.out.push('}');
+    }
+
+    fn table(&mut self, table: &RichTable) {
+        for row in 0..table.rows {
+            self.out.push_str("\\trowd\\trgaph108");
+            // One definition per visible cell; vertically covered
+            // positions get a `\clvmrg` continuation cell.
+            let mut columns: Vec<(usize, usize, Option<usize>)> = Vec::new();
+            let mut column = 0;
+            while column < table.columns {
+                if let Some(index) = table
+                    .cells
+                    .iter()
+                    .position(|c| c.row == row && c.column == column)
+                {
+                    let cell = &table.cells[index];
+                    let span = cell.column_span.max(1);
+                    if cell.row_span > 1 {
+                        self.out.push_str("\\clvmgf");
+                    }
+                    columns.push((column, span, Some(index)));
+                    column += span;
+                } else if let Some(above) = table
+                    .cells
+                    .iter()
+                    .find(|c| c.column == column && c.row < row && row < c.row + c.row_span.max(1))
+                {
+                    self.out.push_str("\\clvmrg");
+                    let span = above.column_span.max(1);
+                    columns.push((column, span, None));
+                    column += span;
+                } else {
+                    columns.push((column, 1, None));
+                    column += 1;
+                }
+                let (start, span, _) = columns[columns.len() - 1];
+                let _ = write!(self.out, "\\cellx{}", (start + span) as i64 * COLUMN_WIDTH);
+            }
+            self.out.push('\n');
+
+            for (_, _, index) in columns {
+                let blocks = index
+                    .map(|i| table.cells[i].blocks.as_slice())
+                    .unwrap_or_default();
+                if blocks.is_empty() {
+                    self.out.push_str("\\pard\\plain\\intbl ");
+                }
+                for (i, block) in blocks.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push_str("\\par\n");
+                    }
+                    self.block(block, true);
+                }
+                self.out.push_str("\\cell\n");
+            }
+            self.out.push_str("\\row\n");
+        }
+        self.out.push_str("\\pard\n");
+    }
+}
+
+fn alpha(mut n: usize) -> String {
+    let mut label = Vec::new();
+    while n > 0 {
+        n -= 1;
+        label.push((b'A' + (n % 26) as u8) as char);
+        n /= 26;
+    }
+    label.iter().rev().collect()
+}
+
+fn roman(mut n: usize) -> String {
+    const NUMERALS: [(usize, &str); 13] = [
+        (1000, "M"),
+        (900, "CM"),
+        (500, "D"),
+        (400, "CD"),
+        (100, "C"),
+        (90, "XC"),
+        (50, "L"),
+        (40, "XL"),
+        (10, "X"),
+        (9, "IX"),
+        (5, "V"),
+        (4, "IV"),
+        (1, "I"),
+    ];
+    let mut label = String::new();
+    for (value, numeral) in NUMERALS {
+        while n >= value {
+            label.push_str(numeral);
+            n -= value;
+        }
+    }
+    label
+}
+
+/// Escape RTF specials; characters outside ASCII become `\uN?`.
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '{' => out.push_str("\\{"),
+            '}' => out.push_str("\\}"),
+            '\t' => out.push_str("\\tab "),
+            '\n' => out.push_str("\\line "),
+            c if c.is_ascii() => out.push(c),
+            c => {
+                let mut units = [0u16; 2];
+                for unit in c.encode_utf16(&mut units) {
+                    // RTF parameters are signed 16-bit values.
+                    let _ = write!(out, "\\u{}?", *unit as i16);
+                }
+            }
+        }
+    }
+    out
+}
@@ -11,6 +11,16 @@ pub struct EntityTreeSnapshot {
     pub store_snapshot: Option<StoreSnapshot>,
 }
 
+impl EntityTreeSnapshot {
+    /// Approximate number of bytes this snapshot keeps alive, for undo
+    /// memory budgets.
+    pub fn approximate_size(&self) -> usize {
+        self.store_snapshot
+            .as_ref()
+            .map_or(0, StoreSnapshot::approximate_size)
+    }
+}
+
 // ─────────────────────────────────────────────────────────────────────────────
 // Store-level snapshot (type-erased so snapshot.rs has no concrete store dependency)
 // ─────────────────────────────────────────────────────────────────────────────
@@ -19,6 +29,8 @@ pub struct EntityTreeSnapshot {
 pub trait StoreSnapshotTrait: std::fmt::Debug + Send + Sync {
     fn clone_box(&self) -> Box<dyn StoreSnapshotTrait>;
     fn as_any(&self) -> &dyn Any;
+    /// Approximate number of bytes this snapshot keeps alive.
+    fn approximate_size(&self) -> usize;
 }
 
 impl Clone for Box<dyn StoreSnapshotTrait> {
@@ -43,4 +55,8 @@ impl StoreSnapshot {
     pub fn downcast_ref<T: 'static>(&self) -> Option<&T> {
         self.inner.as_any().downcast_ref()
     }
+
+    pub fn approximate_size(&self) -> usize {
+        self.inner.approximate_size()
+    }
 }
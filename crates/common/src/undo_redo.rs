@@ -1,4 +1,5 @@
 // Generated by Qleany v1.7.3 from undo_redo.tera
+use crate::database::edit_record::EditRecord;
 use crate::event::{Event, EventHub, Origin, UndoRedoEvent};
 use crate::types::EntityId;
 use anyhow::{Result, anyhow};
@@ -7,6 +8,11 @@ use std::collections::HashMap;
 use std::fmt;
 use std::sync::Arc;
 
+mod history;
+
+pub use history::{CellRangeState, EditSelection, SelectionState, UndoLimit};
+use history::{StackEntry, union_affected_blocks};
+
 /// Trait for commands that can be undone and redone.
 ///
 /// Implementors can optionally support command merging by overriding the
@@ -61,6 +67,44 @@ pub trait UndoRedoCommand: Send {
         false
     }
 
+    /// Returns a short human-readable label for this command, such as
+    /// "Typing" or "Insert table", for undo history views.
+    fn description(&self) -> String {
+        "Edit".to_string()
+    }
+
+    /// Returns the blocks whose content or format undoing or redoing this
+    /// command rewrites, as a contiguous run in document order.
+    ///
+    /// Callers that report what changed after an undo can then look at
+    /// those blocks only instead of diffing the whole document. `None`
+    /// (the default) means the command may touch anything.
+    fn affected_blocks(&self) -> Option<Vec<EntityId>> {
+        None
+    }
+
+    /// Returns roughly how many bytes this command keeps alive, for
+    /// undo memory budgets (see [`UndoLimit::max_bytes`]).
+    ///
+    /// The default counts only the command itself. Commands holding
+    /// snapshots or captured text should add those.
+    fn approximate_size(&self) -> usize {
+        std::mem::size_of_val(self)
+    }
+
+    /// Returns what this command changed, recorded when it last ran,
+    /// in the order it made the changes. Undoing the records in
+    /// reverse order, or redoing them in order, moves a copy of the
+    /// store across the command the way [`undo`](Self::undo) and
+    /// [`redo`](Self::redo) move the live one. Used to save the undo
+    /// history without touching the document.
+    ///
+    /// `None` (the default) means the command can't describe its
+    /// changes, and a history containing it can't be saved.
+    fn edit_records(&self) -> Option<Vec<EditRecord>> {
+        None
+    }
+
     /// Returns the type ID of this command for type checking.
     ///
     /// This is used for downcasting in the `can_merge` and `merge` methods.
@@ -90,6 +134,7 @@ pub trait UndoRedoCommand: Send {
 /// ```
 pub struct CompositeCommand {
     commands: Vec<Box<dyn UndoRedoCommand>>,
+    description: Option<String>,
     pub stack_id: u64,
 }
 
@@ -107,6 +152,7 @@ impl CompositeCommand {
     pub fn new(stack_id: Option<u64>) -> Self {
         CompositeCommand {
             commands: Vec::new(),
+            description: None,
             stack_id: stack_id.unwrap_or(0),
         }
     }
@@ -122,6 +168,12 @@ impl CompositeCommand {
     pub fn is_empty(&self) -> bool {
         self.commands.is_empty()
     }
+
+    /// Sets the label reported by `description`. Without one, the
+    /// composite reports its last command's description.
+    pub fn set_description(&mut self, description: impl Into<String>) {
+        self.description = Some(description.into());
+    }
 }
 
 impl UndoRedoCommand for CompositeCommand {
@@ -141,6 +193,35 @@ impl UndoRedoCommand for CompositeCommand {
         Ok(())
     }
 
+    fn description(&self) -> String {
+        match (&self.description, self.commands.last()) {
+            (Some(description), _) => description.clone(),
+            (None, Some(last)) => last.description(),
+            (None, None) => "Edit".to_string(),
+        }
+    }
+
+    fn affected_blocks(&self) -> Option<Vec<EntityId>> {
+        union_affected_blocks(self.commands.iter())
+    }
+
+    fn approximate_size(&self) -> usize {
+        std::mem::size_of_val(self)
+            + self
+                .commands
+                .iter()
+                .map(|c| c.approximate_size())
+                .sum::<usize>()
+    }
+
+    fn edit_records(&self) -> Option<Vec<EditRecord>> {
+        let mut records = Vec::new();
+        for command in &self.commands {
+            records.extend(command.edit_records()?);
+        }
+        Some(records)
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
@@ -174,8 +255,11 @@ pub trait AsyncUndoRedoCommand: UndoRedoCommand {
 
 #[derive(Default)]
 struct StackData {
-    undo_stack: Vec<Box<dyn UndoRedoCommand>>,
-    redo_stack: Vec<Box<dyn UndoRedoCommand>>,
+    undo_stack: Vec<StackEntry>,
+    redo_stack: Vec<StackEntry>,
+    limit: UndoLimit,
+    /// Made with [`UndoRedoManager::create_guarded_stack`].
+    guarded: bool,
 }
 
 impl fmt::Debug for StackData {
@@ -202,9 +286,14 @@ pub struct UndoRedoManager {
     stacks: HashMap<u64, StackData>,
     next_stack_id: u64,
     in_progress_composite: Option<CompositeCommand>,
+    in_progress_selection: Option<EditSelection>,
     composite_nesting_level: usize,
+    /// Stack whose newest entry (or the in-progress composite) received
+    /// the last added command and has not had a selection recorded yet.
+    selection_target: Option<u64>,
     composite_stack_id: Option<u64>,
     event_hub: Option<Arc<EventHub>>,
+    next_sequence: u64,
 }
 
 impl Default for UndoRedoManager {
@@ -222,9 +311,12 @@ impl UndoRedoManager {
             stacks,
             next_stack_id: 1,
             in_progress_composite: None,
+            in_progress_selection: None,
             composite_nesting_level: 0,
+            selection_target: None,
             composite_stack_id: None,
             event_hub: None,
+            next_sequence: 0,
         }
     }
 
@@ -240,18 +332,22 @@ impl UndoRedoManager {
     /// Returns Ok(()) if successful or if there are no commands to undo.
     pub fn undo(&mut self, stack_id: Option<u64>) -> Result<()> {
         let target_stack_id = stack_id.unwrap_or(0);
+        self.check_undo_is_independent(target_stack_id)?;
+        let sequence = self.next_sequence();
         let stack = self
             .stacks
             .get_mut(&target_stack_id)
             .ok_or_else(|| anyhow!("Stack with ID {} not found", target_stack_id))?;
 
-        if let Some(mut command) = stack.undo_stack.pop() {
-            if let Err(e) = command.undo() {
+        self.selection_target = None;
+        if let Some(mut entry) = stack.undo_stack.pop() {
+            if let Err(e) = entry.command.undo() {
                 log::error!("Undo failed, re-pushing command to undo stack: {e}");
-                stack.undo_stack.push(command);
+                stack.undo_stack.push(entry);
                 return Err(e);
             }
-            stack.redo_stack.push(command);
+            entry.sequence = sequence;
+            stack.redo_stack.push(entry);
             if let Some(event_hub) = &self.event_hub {
                 event_hub.send_event(Event {
                     origin: Origin::UndoRedo(UndoRedoEvent::Undone),
@@ -270,18 +366,22 @@ impl UndoRedoManager {
     /// Returns Ok(()) if successful or if there are no commands to redo.
     pub fn redo(&mut self, stack_id: Option<u64>) -> Result<()> {
         let target_stack_id = stack_id.unwrap_or(0);
+        self.check_redo_is_independent(target_stack_id)?;
+        let sequence = self.next_sequence();
         let stack = self
             .stacks
             .get_mut(&target_stack_id)
             .ok_or_else(|| anyhow!("Stack with ID {} not found", target_stack_id))?;
 
-        if let Some(mut command) = stack.redo_stack.pop() {
-            if let Err(e) = command.redo() {
+        self.selection_target = None;
+        if let Some(mut entry) = stack.redo_stack.pop() {
+            if let Err(e) = entry.command.redo() {
                 log::error!("Redo failed, re-pushing command to redo stack: {e}");
-                stack.redo_stack.push(command);
+                stack.redo_stack.push(entry);
                 return Err(e);
             }
-            stack.undo_stack.push(command);
+            entry.sequence = sequence;
+            stack.undo_stack.push(entry);
             if let Some(event_hub) = &self.event_hub {
                 event_hub.send_event(Event {
                     origin: Origin::UndoRedo(UndoRedoEvent::Redone),
@@ -349,17 +449,28 @@ impl UndoRedoManager {
 
         // Only end the composite if we're at the outermost level
         if self.composite_nesting_level == 0 {
+            let selection = self.in_progress_selection.take();
             if let Some(composite) = self.in_progress_composite.take()
                 && !composite.is_empty()
             {
                 let target_stack_id = self.composite_stack_id.unwrap_or(0);
+                let sequence = self.next_sequence();
                 let stack = self
                     .stacks
                     .get_mut(&target_stack_id)
                     .expect("Stack must exist");
-                stack.undo_stack.push(Box::new(composite));
+                stack
+                    .undo_stack
+                    .push(StackEntry::new(Box::new(composite), selection, sequence));
                 stack.redo_stack.clear();
+                stack.trim();
+                if stack.undo_stack.is_empty() {
+                    self.selection_target = None;
+                }
+            } else {
+                self.selection_target = None;
             }
+            self.composite_stack_id = None;
             // not sure if we want to send events for composites
             if let Some(event_hub) = &self.event_hub {
                 event_hub.send_event(Event {
@@ -383,6 +494,8 @@ impl UndoRedoManager {
         }
 
         self.in_progress_composite = None;
+        self.in_progress_selection = None;
+        self.selection_target = None;
         self.composite_stack_id = None;
 
         // not sure if we want to send events for composites
@@ -423,28 +536,39 @@ impl UndoRedoManager {
                 ));
             }
             composite.add_command(command);
+            self.selection_target = stack_id.or(Some(0));
             return Ok(());
         }
 
         let target_stack_id = stack_id.unwrap_or(0);
+        let sequence = self.next_sequence();
         let stack = self
             .stacks
             .get_mut(&target_stack_id)
             .ok_or_else(|| anyhow!("Stack with ID {} does not exist", target_stack_id))?;
 
+        self.selection_target = Some(target_stack_id);
+
         // Try to merge with the last command if possible
-        if let Some(last_command) = stack.undo_stack.last_mut()
-            && last_command.can_merge(&*command)
-            && last_command.merge(&*command)
+        if let Some(last) = stack.undo_stack.last_mut()
+            && last.command.can_merge(&*command)
+            && last.command.merge(&*command)
         {
             // Successfully merged, no need to add the new command
-            stack.redo_stack.clear();
-            return Ok(());
+            last.size = last.command.approximate_size();
+            last.sequence = sequence;
+        } else {
+            // If we couldn't merge, just add the command normally
+            stack
+                .undo_stack
+                .push(StackEntry::new(command, None, sequence));
         }
-
-        // If we couldn't merge, just add the command normally
-        stack.undo_stack.push(command);
         stack.redo_stack.clear();
+        stack.trim();
+        if stack.undo_stack.is_empty() {
+            // The new entry itself was over budget.
+            self.selection_target = None;
+        }
         Ok(())
     }
 
@@ -485,6 +609,8 @@ impl UndoRedoManager {
             stack.redo_stack.clear();
         }
         self.in_progress_composite = None;
+        self.in_progress_selection = None;
+        self.selection_target = None;
         self.composite_nesting_level = 0;
     }
 
@@ -0,0 +1,493 @@
+//! Undo history bookkeeping that is maintained by hand rather than
+//! generated with the rest of `undo_redo.rs`: the cursor selection
+//! recorded with each entry, per-stack size limits, the ordering of
+//! entries across stacks that guarded stacks check before undoing,
+//! and the queries history views and saving use.
+
+use super::{StackData, UndoRedoCommand, UndoRedoManager};
+use crate::database::edit_record::EditRecord;
+use crate::types::EntityId;
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+
+/// A cursor's selection, as recorded with an undo entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelectionState {
+    pub position: usize,
+    pub anchor: usize,
+    /// Forced rectangular cell selection, if the cursor had one.
+    pub cell_range: Option<CellRangeState>,
+}
+
+/// A rectangular cell selection within one table (inclusive bounds).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CellRangeState {
+    pub table_id: EntityId,
+    pub start_row: usize,
+    pub start_col: usize,
+    pub end_row: usize,
+    pub end_col: usize,
+}
+
+/// Selection of the cursor that made an undo entry, before its first
+/// edit and after its last one, so undo and redo can put it back.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EditSelection {
+    pub cursor_id: u64,
+    pub before: SelectionState,
+    pub after: SelectionState,
+}
+
+impl EditSelection {
+    /// Fold `selection` into `slot`: the first edit fixes the cursor and
+    /// its `before`, later edits by the same cursor move `after`, and
+    /// edits by other cursors are ignored.
+    fn record_into(slot: &mut Option<EditSelection>, selection: EditSelection) {
+        match slot {
+            Some(existing) if existing.cursor_id == selection.cursor_id => {
+                existing.after = selection.after;
+            }
+            Some(_) => {}
+            None => *slot = Some(selection),
+        }
+    }
+}
+
+/// How much history one stack keeps. Once an added command takes the
+/// stack over either limit, the oldest undo entries are dropped first,
+/// then the redo entries furthest from the current state. `None` means
+/// unbounded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UndoLimit {
+    /// Maximum number of undo plus redo entries.
+    pub max_entries: Option<usize>,
+    /// Maximum total of the entries' [`UndoRedoCommand::approximate_size`].
+    /// A single entry larger than this is not kept at all.
+    pub max_bytes: Option<usize>,
+}
+
+pub(super) struct StackEntry {
+    pub(super) command: Box<dyn UndoRedoCommand>,
+    pub(super) selection: Option<EditSelection>,
+    /// `command.approximate_size()`, cached when the entry is pushed
+    /// or merged into.
+    pub(super) size: usize,
+    /// When the entry last changed the document, from the manager-wide
+    /// [`UndoRedoManager::next_sequence`] counter: when it was added,
+    /// merged into or redone for undo entries, when it was undone for
+    /// redo entries. Orders entries across stacks.
+    pub(super) sequence: u64,
+}
+
+impl StackEntry {
+    pub(super) fn new(
+        command: Box<dyn UndoRedoCommand>,
+        selection: Option<EditSelection>,
+        sequence: u64,
+    ) -> Self {
+        let size = command.approximate_size();
+        StackEntry {
+            command,
+            selection,
+            size,
+            sequence,
+        }
+    }
+}
+
+impl StackData {
+    fn memory_usage(&self) -> usize {
+        self.undo_stack
+            .iter()
+            .chain(&self.redo_stack)
+            .map(|e| e.size)
+            .sum()
+    }
+
+    /// Drop entries until the stack is within its limit, oldest undo
+    /// entries first, then the redo entries furthest from the current
+    /// state. Returns the number of entries dropped.
+    pub(super) fn trim(&mut self) -> usize {
+        let mut dropped = 0;
+        if let Some(max) = self.limit.max_entries {
+            let excess = (self.undo_stack.len() + self.redo_stack.len()).saturating_sub(max);
+            dropped += self.drop_oldest(excess);
+        }
+        if let Some(max) = self.limit.max_bytes {
+            let mut total = self.memory_usage();
+            while total > max {
+                let size = self
+                    .undo_stack
+                    .first()
+                    .or(self.redo_stack.first())
+                    .map_or(0, |e| e.size);
+                if self.drop_oldest(1) == 0 {
+                    break;
+                }
+                total -= size;
+                dropped += 1;
+            }
+        }
+        dropped
+    }
+
+    fn drop_oldest(&mut self, count: usize) -> usize {
+        let from_undo = count.min(self.undo_stack.len());
+        self.undo_stack.drain(..from_undo);
+        let from_redo = (count - from_undo).min(self.redo_stack.len());
+        self.redo_stack.drain(..from_redo);
+        from_undo + from_redo
+    }
+}
+
+impl UndoRedoManager {
+    pub(super) fn next_sequence(&mut self) -> u64 {
+        self.next_sequence += 1;
+        self.next_sequence
+    }
+
+    /// Entries of guarded stacks other than `stack_id` that changed the
+    /// document after `sequence`. With `include_redo`, entries that were
+    /// undone since then count too, as their undo also changed the
+    /// document.
+    fn later_entries_elsewhere(
+        &self,
+        stack_id: u64,
+        sequence: u64,
+        include_redo: bool,
+    ) -> impl Iterator<Item = &StackEntry> {
+        self.stacks
+            .iter()
+            .filter(move |(id, stack)| **id != stack_id && stack.guarded)
+            .flat_map(move |(_, stack)| {
+                let redo: &[StackEntry] = if include_redo { &stack.redo_stack } else { &[] };
+                stack.undo_stack.iter().chain(redo)
+            })
+            .filter(move |entry| entry.sequence > sequence)
+    }
+
+    /// Stacks share one document, and commands undo by restoring what
+    /// they captured. Undoing an entry of a guarded stack is therefore
+    /// refused when a later edit on another guarded stack may have
+    /// touched the same blocks: restoring them would silently discard
+    /// that edit. Edits another stack has since undone are no obstacle,
+    /// their blocks are back as they were.
+    pub(super) fn check_undo_is_independent(&self, stack_id: u64) -> Result<()> {
+        let Some(entry) = self
+            .stacks
+            .get(&stack_id)
+            .filter(|s| s.guarded)
+            .and_then(|s| s.undo_stack.last())
+        else {
+            return Ok(());
+        };
+        let blocks = entry.command.affected_blocks();
+        let conflict = self
+            .later_entries_elsewhere(stack_id, entry.sequence, false)
+            .any(|later| match (&blocks, later.command.affected_blocks()) {
+                (Some(ours), Some(theirs)) => ours.iter().any(|b| theirs.contains(b)),
+                _ => true,
+            });
+        if conflict {
+            return Err(anyhow!(
+                "Cannot undo \"{}\": a later edit in another undo stack may have changed the same content",
+                entry.command.description()
+            ));
+        }
+        Ok(())
+    }
+
+    /// Redo replays a command at the positions it first ran at, which is
+    /// only right if no other guarded stack changed the document since
+    /// it was undone.
+    pub(super) fn check_redo_is_independent(&self, stack_id: u64) -> Result<()> {
+        let Some(entry) = self
+            .stacks
+            .get(&stack_id)
+            .filter(|s| s.guarded)
+            .and_then(|s| s.redo_stack.last())
+        else {
+            return Ok(());
+        };
+        if self
+            .later_entries_elsewhere(stack_id, entry.sequence, true)
+            .next()
+            .is_some()
+        {
+            return Err(anyhow!(
+                "Cannot redo \"{}\": another undo stack changed the document since it was undone",
+                entry.command.description()
+            ));
+        }
+        Ok(())
+    }
+
+    /// Returns the number of commands on the undo stack of the specified stack.
+    /// If `stack_id` is None, the global stack (ID 0) is used.
+    pub fn undo_count(&self, stack_id: Option<u64>) -> usize {
+        let target_stack_id = stack_id.unwrap_or(0);
+        self.stacks
+            .get(&target_stack_id)
+            .map_or(0, |s| s.undo_stack.len())
+    }
+
+    /// Returns the number of commands on the redo stack of the specified stack.
+    /// If `stack_id` is None, the global stack (ID 0) is used.
+    pub fn redo_count(&self, stack_id: Option<u64>) -> usize {
+        let target_stack_id = stack_id.unwrap_or(0);
+        self.stacks
+            .get(&target_stack_id)
+            .map_or(0, |s| s.redo_stack.len())
+    }
+
+    /// Returns the blocks the next `steps` undos on the specified stack
+    /// would rewrite, or `None` when any of them is unknown. See
+    /// [`UndoRedoCommand::affected_blocks`].
+    pub fn undo_affected_blocks(
+        &self,
+        stack_id: Option<u64>,
+        steps: usize,
+    ) -> Option<Vec<EntityId>> {
+        let stack = &self.stacks.get(&stack_id.unwrap_or(0))?.undo_stack;
+        union_affected_blocks(stack.iter().rev().take(steps).map(|e| &e.command))
+    }
+
+    /// Returns the blocks the next `steps` redos on the specified stack
+    /// would rewrite, or `None` when any of them is unknown. See
+    /// [`UndoRedoCommand::affected_blocks`].
+    pub fn redo_affected_blocks(
+        &self,
+        stack_id: Option<u64>,
+        steps: usize,
+    ) -> Option<Vec<EntityId>> {
+        let stack = &self.stacks.get(&stack_id.unwrap_or(0))?.redo_stack;
+        union_affected_blocks(stack.iter().rev().take(steps).map(|e| &e.command))
+    }
+
+    /// Descriptions of the commands on the undo stack, oldest first.
+    pub fn undo_descriptions(&self, stack_id: Option<u64>) -> Vec<String> {
+        self.stacks
+            .get(&stack_id.unwrap_or(0))
+            .map(|s| {
+                s.undo_stack
+                    .iter()
+                    .map(|e| e.command.description())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Descriptions of the commands on the redo stack, next to redo first.
+    pub fn redo_descriptions(&self, stack_id: Option<u64>) -> Vec<String> {
+        self.stacks
+            .get(&stack_id.unwrap_or(0))
+            .map(|s| {
+                s.redo_stack
+                    .iter()
+                    .rev()
+                    .map(|e| e.command.description())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Descriptions and [`UndoRedoCommand::edit_records`] of the
+    /// entries on the undo stack, oldest first.
+    pub fn undo_records(&self, stack_id: Option<u64>) -> Vec<(String, Option<Vec<EditRecord>>)> {
+        self.stacks
+            .get(&stack_id.unwrap_or(0))
+            .map(|s| {
+                s.undo_stack
+                    .iter()
+                    .map(|e| (e.command.description(), e.command.edit_records()))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Descriptions and [`UndoRedoCommand::edit_records`] of the
+    /// entries on the redo stack, next to redo first.
+    pub fn redo_records(&self, stack_id: Option<u64>) -> Vec<(String, Option<Vec<EditRecord>>)> {
+        self.stacks
+            .get(&stack_id.unwrap_or(0))
+            .map(|s| {
+                s.redo_stack
+                    .iter()
+                    .rev()
+                    .map(|e| (e.command.description(), e.command.edit_records()))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Labels the composite currently being built. Ignored when no
+    /// composite is in progress.
+    pub fn set_composite_description(&mut self, description: &str) {
+        if let Some(composite) = &mut self.in_progress_composite {
+            composite.set_description(description);
+        }
+    }
+
+    /// Records the editing cursor's selection with the entry that received
+    /// the most recently added command (its merge target, or the
+    /// composite in progress). Ignored when nothing was added since the
+    /// last call, undo or redo, so a no-op edit cannot relabel an older
+    /// entry.
+    pub fn record_selection(&mut self, selection: EditSelection) {
+        let Some(stack_id) = self.selection_target.take() else {
+            return;
+        };
+        if self.in_progress_composite.is_some() {
+            // Keep targeting the composite until it is pushed.
+            self.selection_target = Some(stack_id);
+            EditSelection::record_into(&mut self.in_progress_selection, selection);
+        } else if let Some(entry) = self
+            .stacks
+            .get_mut(&stack_id)
+            .and_then(|s| s.undo_stack.last_mut())
+        {
+            EditSelection::record_into(&mut entry.selection, selection);
+        }
+    }
+
+    /// Selection recorded with the entry the next undo would revert.
+    /// Right after a redo, its `after` is where that redo left the cursor.
+    pub fn next_undo_selection(&self, stack_id: Option<u64>) -> Option<EditSelection> {
+        self.stacks
+            .get(&stack_id.unwrap_or(0))?
+            .undo_stack
+            .last()?
+            .selection
+            .clone()
+    }
+
+    /// Selection recorded with the entry the next redo would reapply.
+    /// Right after an undo, its `before` is where that undo puts the cursor.
+    pub fn next_redo_selection(&self, stack_id: Option<u64>) -> Option<EditSelection> {
+        self.stacks
+            .get(&stack_id.unwrap_or(0))?
+            .redo_stack
+            .last()?
+            .selection
+            .clone()
+    }
+
+    /// Creates a stack like [`create_new_stack`](Self::create_new_stack)
+    /// whose steps are checked against the other guarded stacks sharing
+    /// the document: undo is refused while a later entry of another
+    /// guarded stack touched the same blocks, and redo once another
+    /// guarded stack has changed the document since the entry was
+    /// undone.
+    pub fn create_guarded_stack(&mut self) -> u64 {
+        let id = self.create_new_stack();
+        self.stacks.get_mut(&id).expect("just created").guarded = true;
+        id
+    }
+
+    /// Sets the history limit of a stack and trims it to fit. Returns the
+    /// number of entries dropped.
+    pub fn set_stack_limit(&mut self, stack_id: u64, limit: UndoLimit) -> Result<usize> {
+        let stack = self
+            .stacks
+            .get_mut(&stack_id)
+            .ok_or_else(|| anyhow!("Stack with ID {} does not exist", stack_id))?;
+        stack.limit = limit;
+        let dropped = stack.trim();
+        if dropped > 0 && self.selection_target == Some(stack_id) {
+            self.selection_target = None;
+        }
+        Ok(dropped)
+    }
+
+    /// Replaces a stack's history, e.g. with edits restored from a saved
+    /// document. `undo` is oldest first and `redo` next-to-redo first,
+    /// matching [`undo_descriptions`](Self::undo_descriptions) and
+    /// [`redo_descriptions`](Self::redo_descriptions). The stack is then
+    /// trimmed to its limit.
+    pub fn set_stack_history(
+        &mut self,
+        stack_id: u64,
+        undo: Vec<Box<dyn UndoRedoCommand>>,
+        redo: Vec<Box<dyn UndoRedoCommand>>,
+    ) -> Result<()> {
+        if !self.stacks.contains_key(&stack_id) {
+            return Err(anyhow!("Stack with ID {} does not exist", stack_id));
+        }
+        let undo: Vec<StackEntry> = undo
+            .into_iter()
+            .map(|command| StackEntry::new(command, None, self.next_sequence()))
+            .collect();
+        let redo: Vec<StackEntry> = redo
+            .into_iter()
+            .rev()
+            .map(|command| StackEntry::new(command, None, self.next_sequence()))
+            .collect();
+        let stack = self.stacks.get_mut(&stack_id).expect("checked above");
+        stack.undo_stack = undo;
+        stack.redo_stack = redo;
+        stack.trim();
+        if self.selection_target == Some(stack_id) {
+            self.selection_target = None;
+        }
+        Ok(())
+    }
+
+    /// Gets the history limit of a stack.
+    pub fn get_stack_limit(&self, stack_id: u64) -> UndoLimit {
+        self.stacks
+            .get(&stack_id)
+            .map(|s| s.limit)
+            .unwrap_or_default()
+    }
+
+    /// Approximate bytes held by a stack's undo and redo entries.
+    pub fn get_stack_memory_usage(&self, stack_id: u64) -> usize {
+        self.stacks
+            .get(&stack_id)
+            .map(StackData::memory_usage)
+            .unwrap_or(0)
+    }
+}
+
+pub(super) fn union_affected_blocks<'a>(
+    commands: impl Iterator<Item = &'a Box<dyn UndoRedoCommand>>,
+) -> Option<Vec<EntityId>> {
+    let mut blocks: Vec<EntityId> = Vec::new();
+    for command in commands {
+        for id in command.affected_blocks()? {
+            if !blocks.contains(&id) {
+                blocks.push(id);
+            }
+        }
+    }
+    Some(blocks)
+}
+
+/// Expands, inside an `impl UndoRedoCommand` block, to the
+/// [`affected_blocks`](UndoRedoCommand::affected_blocks),
+/// [`approximate_size`](UndoRedoCommand::approximate_size) and
+/// [`edit_records`](UndoRedoCommand::edit_records) of a command whose
+/// undo state is the `Option<StoreDelta>` field named `$delta`.
+#[macro_export]
+macro_rules! store_delta_undo_methods {
+    ($delta:ident) => {
+        fn affected_blocks(&self) -> Option<Vec<$crate::types::EntityId>> {
+            self.$delta.as_ref()?.changed_blocks.clone()
+        }
+
+        fn approximate_size(&self) -> usize {
+            std::mem::size_of_val(self)
+                + self.$delta.as_ref().map_or(
+                    0,
+                    $crate::database::store_delta::StoreDelta::approximate_size,
+                )
+        }
+
+        fn edit_records(&self) -> Option<Vec<$crate::database::edit_record::EditRecord>> {
+            let delta = self.$delta.clone()?;
+            Some(vec![$crate::database::edit_record::EditRecord::Store(
+                delta,
+            )])
+        }
+    };
+}
@@ -0,0 +1,397 @@
+//! `Replica` convergence tests.
+//!
+//! Several replicas edit concurrently and exchange operations in random
+//! order, duplicates included. At the end every replica must hold the
+//! same sequence, and a plain mirror that only follows the returned
+//! effects must match its replica at every step.
+
+use common::collab::{BlockProps, CellProps, Effect, Operation, Replica, UnitContent};
+use common::entities::Alignment;
+use common::format_runs::CharacterFormat;
+use proptest::prelude::*;
+
+fn text(s: &str) -> Vec<UnitContent> {
+    s.chars()
+        .map(|ch| {
+            if ch == '\n' {
+                UnitContent::Break {
+                    props: BlockProps::default(),
+                }
+            } else {
+                UnitContent::Char {
+                    ch,
+                    format: CharacterFormat::default(),
+                }
+            }
+        })
+        .collect()
+}
+
+fn plain(replica: &Replica) -> String {
+    replica
+        .content()
+        .map(|u| match u {
+            UnitContent::Char { ch, .. } => *ch,
+            UnitContent::Image { .. } => '#',
+            UnitContent::Break { .. } => '\n',
+        })
+        .collect()
+}
+
+/// Follows a replica through its effects only.
+struct Mirror {
+    first_block: BlockProps,
+    units: Vec<UnitContent>,
+}
+
+impl Mirror {
+    fn of(replica: &Replica) -> Self {
+        Mirror {
+            first_block: replica.first_block().clone(),
+            units: replica.content().cloned().collect(),
+        }
+    }
+
+    fn follow(&mut self, effects: Vec<Effect>) {
+        for effect in effects {
+            match effect {
+                Effect::Insert { position, units } => {
+                    self.units.splice(position..position, units);
+                }
+                Effect::Delete { position, length } => {
+                    self.units.drain(position..position + length);
+                }
+                Effect::Format {
+                    position,
+                    length,
+                    format,
+                } => {
+                    for unit in &mut self.units[position..position + length] {
+                        match unit {
+                            UnitContent::Char { format: f, .. }
+                            | UnitContent::Image { format: f, .. } => *f = format.clone(),
+                            UnitContent::Break { .. } => panic!("format effect on a break"),
+                        }
+                    }
+                }
+                Effect::BlockProps { position, props } => {
+                    if position == 0 {
+                        self.first_block = props;
+                    } else {
+                        match &mut self.units[position - 1] {
+                            UnitContent::Break { props: p } => *p = props,
+                            _ => panic!("block effect not at a block start"),
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn assert_matches(&self, replica: &Replica) {
+        assert_eq!(&self.first_block, replica.first_block());
+        let units: Vec<UnitContent> = replica.content().cloned().collect();
+        assert_eq!(self.units, units);
+    }
+}
+
+fn bold() -> CharacterFormat {
+    CharacterFormat {
+        font_bold: Some(true),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn concurrent_inserts_at_the_same_place_converge() {
+    let mut a = Replica::new(1, BlockProps::default(), text("ac")).unwrap();
+    let mut b = Replica::new(2, BlockProps::default(), text("ac")).unwrap();
+
+    let from_a = a.insert(1, text("x")).unwrap();
+    let from_b = b.insert(1, text("y")).unwrap();
+    a.apply(from_b);
+    b.apply(from_a);
+
+    assert_eq!(plain(&a), plain(&b));
+    assert_eq!(plain(&a).len(), 4);
+}
+
+#[test]
+fn out_of_order_operations_wait_for_their_dependencies() {
+    let mut a = Replica::new(1, BlockProps::default(), text("")).unwrap();
+    let mut b = Replica::new(2, BlockProps::default(), text("")).unwrap();
+
+    let first = a.insert(0, text("ab")).unwrap();
+    let second = a.insert(1, text("X")).unwrap();
+    let third = a.delete(0, 1).unwrap();
+
+    assert!(b.apply(third.clone()).is_empty());
+    assert!(b.apply(second.clone()).is_empty());
+    assert_eq!(b.pending_count(), 2);
+    b.apply(first.clone());
+    assert_eq!(b.pending_count(), 0);
+    assert_eq!(plain(&b), "Xb");
+
+    // Duplicates change nothing.
+    assert!(b.apply(second).is_empty());
+    assert_eq!(plain(&b), plain(&a));
+}
+
+#[test]
+fn last_format_write_wins() {
+    let mut a = Replica::new(1, BlockProps::default(), text("abc")).unwrap();
+    let mut b = Replica::new(2, BlockProps::default(), text("abc")).unwrap();
+
+    let from_a = a.format(0, 3, &bold()).unwrap();
+    b.apply(from_a);
+    let from_b = b.format(1, 1, &CharacterFormat::default()).unwrap();
+    a.apply(from_b);
+
+    let formats: Vec<_> = a.content().map(|u| u.format().cloned()).collect();
+    assert_eq!(
+        formats,
+        b.content().map(|u| u.format().cloned()).collect::<Vec<_>>()
+    );
+    assert_eq!(formats[1], Some(CharacterFormat::default()));
+    assert_eq!(formats[0], Some(bold()));
+}
+
+#[test]
+fn concurrent_changes_to_different_fields_both_survive() {
+    let mut a = Replica::new(1, BlockProps::default(), text("abc")).unwrap();
+    let mut b = Replica::new(2, BlockProps::default(), text("abc")).unwrap();
+
+    let from_a = a.format(0, 2, &bold()).unwrap();
+    let italic = CharacterFormat {
+        font_italic: Some(true),
+        ..Default::default()
+    };
+    let from_b = b.format(1, 2, &italic).unwrap();
+    a.apply(from_b);
+    b.apply(from_a);
+
+    let both = CharacterFormat {
+        font_bold: Some(true),
+        font_italic: Some(true),
+        ..Default::default()
+    };
+    for replica in [&a, &b] {
+        let formats: Vec<_> = replica.content().map(|u| u.format().cloned()).collect();
+        assert_eq!(
+            formats,
+            vec![Some(bold()), Some(both.clone()), Some(italic.clone())]
+        );
+    }
+}
+
+#[test]
+fn only_the_first_block_changes_cell() {
+    let mut a = Replica::new(1, BlockProps::default(), text("a\nb")).unwrap();
+    let mut b = Replica::new(2, BlockProps::default(), text("a\nb")).unwrap();
+    let table = a.new_key();
+    let cell = BlockProps {
+        cell: Some(CellProps {
+            table,
+            row: 0,
+            column: 0,
+        }),
+        ..Default::default()
+    };
+
+    // A table now starts the document: the root opens its first cell.
+    b.apply(a.set_block_props(0, &cell).unwrap());
+    b.apply(a.set_block_props(2, &cell).unwrap());
+    for replica in [&a, &b] {
+        assert_eq!(replica.first_block(), &cell);
+        assert_eq!(
+            replica.content().nth(1),
+            Some(&UnitContent::Break {
+                props: BlockProps::default()
+            })
+        );
+    }
+}
+
+#[test]
+fn revert_keeps_later_edits_from_other_sites() {
+    let mut a = Replica::new(1, BlockProps::default(), text("abc")).unwrap();
+    let mut b = Replica::new(2, BlockProps::default(), text("abc")).unwrap();
+
+    let typed = a.insert(1, text("XY")).unwrap();
+    let removed = a.delete(3, 2).unwrap();
+    let bolded = a.format(0, 3, &bold()).unwrap();
+    for op in [&typed, &removed, &bolded] {
+        b.apply(op.clone());
+    }
+    // Site b types around and inside the inserted text, and
+    // italicizes all of it.
+    a.apply(b.insert(0, text("_")).unwrap());
+    let italic = CharacterFormat {
+        font_italic: Some(true),
+        ..Default::default()
+    };
+    a.apply(b.insert(3, text("z")).unwrap());
+    a.apply(b.format(0, 5, &italic).unwrap());
+    assert_eq!(plain(&a), "_aXzY");
+
+    let mut reverts = Vec::new();
+    for op in [&bolded, &removed, &typed] {
+        reverts = a.revert(op).unwrap();
+        for revert in &reverts {
+            b.apply(revert.clone());
+        }
+    }
+    assert_eq!(plain(&a), "_azbc");
+    assert_eq!(plain(&a), plain(&b));
+    let formats: Vec<_> = a.content().map(|u| u.format().cloned()).collect();
+    assert_eq!(formats[1], Some(italic.clone()));
+    assert_eq!(formats[2], Some(italic));
+
+    // Reverting a revert makes the edit again; only the site that made
+    // an operation can revert it.
+    for revert in &reverts {
+        for again in a.revert(revert).unwrap() {
+            b.apply(again);
+        }
+    }
+    assert_eq!(plain(&a), "_aXzYbc");
+    assert_eq!(plain(&a), plain(&b));
+    assert!(b.revert(&typed).is_err());
+}
+
+#[test]
+fn operations_roundtrip_through_json() {
+    let mut a = Replica::new(1, BlockProps::default(), text("ab")).unwrap();
+    let ops = vec![
+        a.insert(2, text("\ncd")).unwrap(),
+        a.format(0, 2, &bold()).unwrap(),
+        a.set_block_props(
+            3,
+            &BlockProps {
+                alignment: Some(Alignment::Center),
+                ..Default::default()
+            },
+        )
+        .unwrap(),
+        a.delete(1, 1).unwrap(),
+    ];
+    let json = serde_json::to_string(&ops).unwrap();
+    let back: Vec<Operation> = serde_json::from_str(&json).unwrap();
+    assert_eq!(ops, back);
+}
+
+#[derive(Debug, Clone)]
+enum Action {
+    Insert {
+        at: usize,
+        text: String,
+    },
+    Delete {
+        at: usize,
+        len: usize,
+    },
+    Format {
+        at: usize,
+        len: usize,
+        bold: bool,
+    },
+    Align {
+        at: usize,
+        center: bool,
+    },
+    /// Deliver one queued operation to the site, picked by index.
+    Deliver {
+        pick: usize,
+    },
+}
+
+fn action() -> impl Strategy<Value = Action> {
+    prop_oneof![
+        3 => (any::<usize>(), "[a-c\n]{1,3}").prop_map(|(at, text)| Action::Insert { at, text }),
+        2 => (any::<usize>(), 1..4usize).prop_map(|(at, len)| Action::Delete { at, len }),
+        1 => (any::<usize>(), 1..4usize, any::<bool>())
+            .prop_map(|(at, len, bold)| Action::Format { at, len, bold }),
+        1 => (any::<usize>(), any::<bool>()).prop_map(|(at, center)| Action::Align { at, center }),
+        4 => any::<usize>().prop_map(|pick| Action::Deliver { pick }),
+    ]
+}
+
+proptest! {
+    #[test]
+    fn replicas_converge_under_random_interleavings(
+        steps in proptest::collection::vec((0..3usize, action()), 1..60)
+    ) {
+        let start = text("hello\nworld");
+        let mut replicas: Vec<Replica> = (1..=3)
+            .map(|site| Replica::new(site, BlockProps::default(), start.clone()).unwrap())
+            .collect();
+        let mut mirrors: Vec<Mirror> = replicas.iter().map(Mirror::of).collect();
+        let mut inboxes: Vec<Vec<Operation>> = vec![Vec::new(); 3];
+
+        for (site, action) in steps {
+            let replica = &mut replicas[site];
+            let len = replica.len();
+            let op = match action {
+                Action::Insert { at, text: s } => Some(replica.insert(at % (len + 1), text(&s)).unwrap()),
+                Action::Delete { at, len: n } if len > 0 => {
+                    let at = at % len;
+                    Some(replica.delete(at, n.min(len - at)).unwrap())
+                }
+                Action::Format { at, len: n, bold: b } if len > 0 => {
+                    let at = at % len;
+                    let format = if b { bold() } else { CharacterFormat::default() };
+                    Some(replica.format(at, n.min(len - at), &format).unwrap())
+                }
+                Action::Align { at, center } => {
+                    let starts: Vec<usize> = std::iter::once(0)
+                        .chain(replica.content().enumerate().filter_map(|(i, u)| {
+                            matches!(u, UnitContent::Break { .. }).then_some(i + 1)
+                        }))
+                        .collect();
+                    let alignment = if center { Alignment::Center } else { Alignment::Right };
+                    let props = BlockProps { alignment: Some(alignment), ..Default::default() };
+                    Some(replica.set_block_props(starts[at % starts.len()], &props).unwrap())
+                }
+                Action::Deliver { pick } => {
+                    let inbox = &mut inboxes[site];
+                    if !inbox.is_empty() {
+                        let op = inbox.remove(pick % inbox.len());
+                        // Deliver some operations twice.
+                        if pick % 5 == 0 {
+                            inbox.push(op.clone());
+                        }
+                        let effects = replicas[site].apply(op);
+                        mirrors[site].follow(effects);
+                        mirrors[site].assert_matches(&replicas[site]);
+                    }
+                    None
+                }
+                _ => None,
+            };
+            if let Some(op) = op {
+                mirrors[site] = Mirror::of(&replicas[site]);
+                for (other, inbox) in inboxes.iter_mut().enumerate() {
+                    if other != site {
+                        inbox.push(op.clone());
+                    }
+                }
+            }
+        }
+
+        for site in 0..3 {
+            for op in std::mem::take(&mut inboxes[site]) {
+                let effects = replicas[site].apply(op);
+                mirrors[site].follow(effects);
+            }
+            mirrors[site].assert_matches(&replicas[site]);
+            prop_assert_eq!(replicas[site].pending_count(), 0);
+        }
+        for site in 1..3 {
+            prop_assert_eq!(plain(&replicas[0]), plain(&replicas[site]));
+            let a: Vec<_> = replicas[0].content().cloned().collect();
+            let b: Vec<_> = replicas[site].content().cloned().collect();
+            prop_assert_eq!(a, b);
+            prop_assert_eq!(replicas[0].first_block(), replicas[site].first_block());
+        }
+    }
+}
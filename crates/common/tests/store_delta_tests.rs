@@ -0,0 +1,274 @@
+//! `StoreDelta` tests: a delta computed between two store states moves
+//! either state to the other, survives serialization, refuses to apply
+//! to a state it was not recorded against, and still applies after
+//! edits elsewhere in the document. A delta recorded from the store's
+//! write journal holds the same rows as the one found by comparing the
+//! states in full, with a splice for each place the text changed.
+
+use common::database::rope_store::{RopeStore, RopeStoreSnapshot};
+use common::database::store_delta::{StoreDelta, TextSplice};
+use common::entities::{Block, Document};
+use common::format_runs::{CharacterFormat, FormatRun};
+use common::snapshot::{EntityTreeSnapshot, StoreSnapshot};
+
+fn block(id: u64) -> Block {
+    Block {
+        id,
+        ..Block::default()
+    }
+}
+
+fn bold(byte_start: u32, byte_end: u32) -> FormatRun {
+    FormatRun {
+        byte_start,
+        byte_end,
+        format: CharacterFormat {
+            font_bold: Some(true),
+            ..CharacterFormat::default()
+        },
+    }
+}
+
+/// Two blocks, "first" and "second", the first one partly bold.
+fn two_blocks() -> RopeStore {
+    let store = RopeStore::new();
+    store.rope.write().unwrap().insert(0, "first\nsecond");
+    store.blocks.write().unwrap().insert(1, block(1));
+    store.blocks.write().unwrap().insert(2, block(2));
+    store
+        .format_runs
+        .write()
+        .unwrap()
+        .insert(1, vec![bold(0, 2)]);
+    let mut offsets = store.block_offsets.write().unwrap();
+    offsets.push_block(1, 0);
+    offsets.push_block(2, 6);
+    offsets.set_total_bytes(12);
+    drop(offsets);
+    store
+}
+
+/// Insert text into the first block and append a third block.
+fn edit(store: &RopeStore) {
+    store.rope.write().unwrap().insert(5, " one");
+    store.rope.write().unwrap().insert(16, "\nthird");
+    store.blocks.write().unwrap().insert(3, block(3));
+    store
+        .format_runs
+        .write()
+        .unwrap()
+        .insert(1, vec![bold(0, 9)]);
+    let mut offsets = store.block_offsets.write().unwrap();
+    offsets.shift_after(5, 4);
+    offsets.push_block(3, 17);
+    offsets.set_total_bytes(22);
+}
+
+fn text_and_offsets(snap: &RopeStoreSnapshot) -> (String, Vec<(u64, u32)>) {
+    let store = RopeStore::new();
+    store.restore(snap);
+    let text = store.rope.read().unwrap().to_string();
+    let offsets = store
+        .block_offsets
+        .read()
+        .unwrap()
+        .entries
+        .iter()
+        .map(|(marker, start)| (marker.as_block().unwrap(), *start))
+        .collect();
+    (text, offsets)
+}
+
+#[test]
+fn delta_moves_between_states() {
+    let store = two_blocks();
+    let before = store.snapshot();
+    edit(&store);
+    let after = store.snapshot();
+
+    let delta = StoreDelta::between(&before, &after);
+    // Only the changed rows are recorded.
+    assert_eq!(delta.blocks.len(), 1);
+    assert_eq!(delta.format_runs.len(), 1);
+
+    let applied = delta.apply(&before).unwrap();
+    assert_eq!(text_and_offsets(&applied), text_and_offsets(&after));
+    assert_eq!(text_and_offsets(&applied).1, vec![(1, 0), (2, 10), (3, 17)]);
+
+    let reverted = delta.revert(&after).unwrap();
+    assert_eq!(text_and_offsets(&reverted), text_and_offsets(&before));
+}
+
+#[test]
+fn delta_survives_serialization() {
+    let store = two_blocks();
+    let before = store.snapshot();
+    edit(&store);
+    let after = store.snapshot();
+
+    let delta = StoreDelta::between(&before, &after);
+    let json = serde_json::to_string(&delta).unwrap();
+    let decoded: StoreDelta = serde_json::from_str(&json).unwrap();
+    assert_eq!(decoded, delta);
+    assert_eq!(
+        text_and_offsets(&decoded.revert(&after).unwrap()),
+        text_and_offsets(&before)
+    );
+}
+
+#[test]
+fn delta_rejects_mismatched_state() {
+    let store = two_blocks();
+    let before = store.snapshot();
+    edit(&store);
+    let after = store.snapshot();
+    let delta = StoreDelta::between(&before, &after);
+
+    // Already in the `before` state: reverting again must fail.
+    assert!(delta.revert(&before).is_err());
+    // Already in the `after` state: applying again must fail.
+    assert!(delta.apply(&after).is_err());
+}
+
+#[test]
+fn delta_reverts_after_an_edit_before_it() {
+    let store = two_blocks();
+    store.documents.write().unwrap().insert(
+        1,
+        Document {
+            id: 1,
+            character_count: 11,
+            block_count: 2,
+            ..Document::default()
+        },
+    );
+    store
+        .blocks
+        .write()
+        .unwrap()
+        .get_mut(&2)
+        .unwrap()
+        .document_position = 6;
+    let before = store.snapshot();
+
+    // Make the second block a heading and append to it.
+    store.rope.write().unwrap().insert(12, " two");
+    store.block_offsets.write().unwrap().set_total_bytes(16);
+    store
+        .blocks
+        .write()
+        .unwrap()
+        .get_mut(&2)
+        .unwrap()
+        .fmt_heading_level = Some(1);
+    store
+        .documents
+        .write()
+        .unwrap()
+        .get_mut(&1)
+        .unwrap()
+        .character_count = 15;
+    let delta = StoreDelta::between(&before, &store.snapshot());
+    assert_eq!(delta.changed_blocks, Some(vec![2]));
+
+    // An edit in the first block moves the second one along and
+    // changes the document's character count.
+    store.rope.write().unwrap().insert(0, "the ");
+    store.block_offsets.write().unwrap().shift_after(1, 4);
+    store
+        .blocks
+        .write()
+        .unwrap()
+        .get_mut(&2)
+        .unwrap()
+        .document_position = 10;
+    store
+        .documents
+        .write()
+        .unwrap()
+        .get_mut(&1)
+        .unwrap()
+        .character_count = 19;
+
+    let reverted = delta.revert(&store.snapshot()).unwrap();
+    assert_eq!(
+        text_and_offsets(&reverted),
+        ("the first\nsecond".to_string(), vec![(1, 0), (2, 10)])
+    );
+    let restored = RopeStore::new();
+    restored.restore(&reverted);
+    let second = restored.blocks.read().unwrap()[&2].clone();
+    assert_eq!(
+        (second.fmt_heading_level, second.document_position),
+        (None, 10)
+    );
+    assert_eq!(restored.documents.read().unwrap()[&1].character_count, 15);
+
+    // Applied again, it lands after the first block's edit as well.
+    let reapplied = delta.apply(&reverted).unwrap();
+    assert_eq!(text_and_offsets(&reapplied).0, "the first\nsecond two");
+}
+
+#[test]
+fn text_splice_keeps_chars_whole_across_chunks() {
+    // Long enough for the rope to hold several chunks, and the edit
+    // swaps one multi-byte char for another sharing its leading byte.
+    let long = "é".repeat(4000);
+    let store = RopeStore::new();
+    store
+        .rope
+        .write()
+        .unwrap()
+        .insert(0, &format!("{long}ü{long}"));
+    let before = store.snapshot();
+    store.rope.write().unwrap().remove(4000..4001);
+    store.rope.write().unwrap().insert(4000, "ö");
+    let after = store.snapshot();
+
+    let delta = StoreDelta::between(&before, &after);
+    assert_eq!(delta.text[0].char_start, 4000);
+    assert_eq!(delta.text[0].removed, "ü");
+    assert_eq!(delta.text[0].inserted, "ö");
+    assert_eq!(
+        text_and_offsets(&delta.revert(&after).unwrap()).0,
+        text_and_offsets(&before).0
+    );
+}
+
+#[test]
+fn delta_since_journal_splices_each_edit() {
+    let store = two_blocks();
+    store.start_journal();
+    let before = store.snapshot();
+    edit(&store);
+
+    let snapshot = EntityTreeSnapshot {
+        store_snapshot: Some(StoreSnapshot::new(before.clone())),
+    };
+    let delta = StoreDelta::since(&snapshot, &store).unwrap();
+    let full = StoreDelta::between(&before, &store.snapshot());
+    assert_eq!(delta.blocks, full.blocks);
+    assert_eq!(delta.format_runs, full.format_runs);
+    assert_eq!(
+        delta.text,
+        [
+            TextSplice {
+                char_start: 5,
+                removed: String::new(),
+                inserted: " one".into(),
+            },
+            TextSplice {
+                char_start: 12,
+                removed: String::new(),
+                inserted: "\nthird".into(),
+            },
+        ]
+    );
+    let after = store.snapshot();
+    let reverted = delta.revert(&after).unwrap();
+    assert_eq!(text_and_offsets(&reverted), text_and_offsets(&before));
+    assert_eq!(
+        text_and_offsets(&delta.apply(&reverted).unwrap()),
+        text_and_offsets(&after)
+    );
+}
@@ -4,7 +4,11 @@
 #![allow(unused_imports)]
 
 use anyhow::Result;
-use common::undo_redo::{CompositeCommand, UndoRedoCommand, UndoRedoManager};
+use common::database::edit_record::EditRecord;
+use common::database::store_delta::StoreDelta;
+use common::undo_redo::{
+    CompositeCommand, EditSelection, SelectionState, UndoLimit, UndoRedoCommand, UndoRedoManager,
+};
 use std::any::Any;
 use std::sync::{Arc, Mutex};
 
@@ -437,3 +441,502 @@ fn test_clear_with_in_progress_composite() {
     assert!(!manager.can_undo(None));
     assert!(manager.can_redo(None));
 }
+
+// A command with a fixed description, for history inspection
+struct LabelledCommand {
+    label: &'static str,
+}
+
+impl UndoRedoCommand for LabelledCommand {
+    fn undo(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn redo(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn description(&self) -> String {
+        self.label.to_string()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[test]
+fn test_descriptions() {
+    let counter = Arc::new(Mutex::new(0));
+    let mut manager = UndoRedoManager::new();
+
+    manager.add_command(Box::new(LabelledCommand { label: "Typing" }));
+    manager.add_command(Box::new(TestCommand::new(counter.clone(), 1)));
+    manager.add_command(Box::new(LabelledCommand { label: "Delete" }));
+
+    assert_eq!(
+        manager.undo_descriptions(None),
+        ["Typing", "Edit", "Delete"]
+    );
+    assert!(manager.redo_descriptions(None).is_empty());
+    assert_eq!(manager.undo_count(None), 3);
+    assert_eq!(manager.redo_count(None), 0);
+
+    manager.undo(None).unwrap();
+    manager.undo(None).unwrap();
+
+    assert_eq!(manager.undo_descriptions(None), ["Typing"]);
+    // Next to redo comes first
+    assert_eq!(manager.redo_descriptions(None), ["Edit", "Delete"]);
+    assert_eq!(manager.undo_count(None), 1);
+    assert_eq!(manager.redo_count(None), 2);
+    assert_eq!(manager.undo_count(Some(42)), 0);
+}
+
+#[test]
+fn test_composite_description() {
+    let mut manager = UndoRedoManager::new();
+
+    // Without a label, a composite is described by its last child
+    manager.begin_composite(None).unwrap();
+    manager.add_command(Box::new(LabelledCommand { label: "Delete" }));
+    manager.add_command(Box::new(LabelledCommand { label: "Typing" }));
+    manager.end_composite();
+
+    // An explicit label wins
+    manager.begin_composite(None).unwrap();
+    manager.set_composite_description("Replace formatting");
+    manager.add_command(Box::new(LabelledCommand {
+        label: "Format text",
+    }));
+    manager.end_composite();
+
+    assert_eq!(
+        manager.undo_descriptions(None),
+        ["Typing", "Replace formatting"]
+    );
+
+    let mut composite = CompositeCommand::new(None);
+    assert_eq!(composite.description(), "Edit");
+    composite.set_description("Batch");
+    assert_eq!(composite.description(), "Batch");
+}
+
+fn selection(cursor_id: u64, before: usize, after: usize) -> EditSelection {
+    let at = |position| SelectionState {
+        position,
+        anchor: position,
+        cell_range: None,
+    };
+    EditSelection {
+        cursor_id,
+        before: at(before),
+        after: at(after),
+    }
+}
+
+#[test]
+fn test_record_selection() {
+    let counter = Arc::new(Mutex::new(0));
+    let mut manager = UndoRedoManager::new();
+
+    // Nothing added yet: ignored
+    manager.record_selection(selection(1, 0, 1));
+    assert_eq!(manager.next_undo_selection(None), None);
+
+    manager.add_command(Box::new(TestCommand::new(counter.clone(), 1)));
+    manager.record_selection(selection(1, 0, 1));
+    assert_eq!(manager.next_undo_selection(None), Some(selection(1, 0, 1)));
+
+    // A second record without a new command does not relabel the entry
+    manager.record_selection(selection(1, 5, 6));
+    assert_eq!(manager.next_undo_selection(None), Some(selection(1, 0, 1)));
+
+    manager.undo(None).unwrap();
+    assert_eq!(manager.next_undo_selection(None), None);
+    assert_eq!(manager.next_redo_selection(None), Some(selection(1, 0, 1)));
+}
+
+#[test]
+fn test_record_selection_on_merged_command() {
+    let counter = Arc::new(Mutex::new(0));
+    let mut manager = UndoRedoManager::new();
+
+    manager.add_command(Box::new(MergeableCommand::new(counter.clone(), 1)));
+    manager.record_selection(selection(1, 0, 1));
+    manager.add_command(Box::new(MergeableCommand::new(counter.clone(), 1)));
+    manager.record_selection(selection(1, 1, 2));
+    // Another cursor merging into the same entry keeps the first cursor
+    manager.add_command(Box::new(MergeableCommand::new(counter.clone(), 1)));
+    manager.record_selection(selection(2, 7, 8));
+
+    // Keeps the first edit's `before` and the last edit's `after`
+    assert_eq!(manager.next_undo_selection(None), Some(selection(1, 0, 2)));
+}
+
+#[test]
+fn test_record_selection_in_composite() {
+    let counter = Arc::new(Mutex::new(0));
+    let mut manager = UndoRedoManager::new();
+
+    manager.begin_composite(None).unwrap();
+    manager.add_command(Box::new(TestCommand::new(counter.clone(), 1)));
+    manager.record_selection(selection(1, 3, 4));
+    manager.add_command(Box::new(TestCommand::new(counter.clone(), 1)));
+    manager.record_selection(selection(1, 4, 9));
+    manager.end_composite();
+
+    assert_eq!(manager.next_undo_selection(None), Some(selection(1, 3, 9)));
+
+    // Cancelled composites leave no selection behind
+    manager.begin_composite(None).unwrap();
+    manager.add_command(Box::new(TestCommand::new(counter.clone(), 1)));
+    manager.record_selection(selection(1, 9, 10));
+    manager.cancel_composite();
+    manager.begin_composite(None).unwrap();
+    manager.add_command(Box::new(TestCommand::new(counter.clone(), 1)));
+    manager.end_composite();
+    assert_eq!(manager.next_undo_selection(None), None);
+}
+
+// A command reporting a fixed size, for memory budgets
+struct SizedCommand {
+    counter: Arc<Mutex<i32>>,
+    size: usize,
+}
+
+impl UndoRedoCommand for SizedCommand {
+    fn undo(&mut self) -> Result<()> {
+        *self.counter.lock().unwrap() -= 1;
+        Ok(())
+    }
+
+    fn redo(&mut self) -> Result<()> {
+        *self.counter.lock().unwrap() += 1;
+        Ok(())
+    }
+
+    fn approximate_size(&self) -> usize {
+        self.size
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[test]
+fn test_max_entries_limit() {
+    let counter = Arc::new(Mutex::new(0));
+    let mut manager = UndoRedoManager::new();
+    manager
+        .set_stack_limit(
+            0,
+            UndoLimit {
+                max_entries: Some(2),
+                max_bytes: None,
+            },
+        )
+        .unwrap();
+
+    for _ in 0..5 {
+        let mut cmd = TestCommand::new(counter.clone(), 1);
+        cmd.redo().unwrap();
+        manager.add_command(Box::new(cmd));
+    }
+    assert_eq!(manager.get_stack_size(0), 2);
+
+    // Only the two newest entries can be undone
+    manager.undo(None).unwrap();
+    manager.undo(None).unwrap();
+    assert!(!manager.can_undo(None));
+    assert_eq!(*counter.lock().unwrap(), 3);
+
+    // Lowering the limit trims the redo entries furthest from the current state
+    let dropped = manager
+        .set_stack_limit(
+            0,
+            UndoLimit {
+                max_entries: Some(1),
+                max_bytes: None,
+            },
+        )
+        .unwrap();
+    assert_eq!(dropped, 1);
+    manager.redo(None).unwrap();
+    assert!(!manager.can_redo(None));
+    assert_eq!(*counter.lock().unwrap(), 4);
+}
+
+#[test]
+fn test_max_bytes_limit() {
+    let counter = Arc::new(Mutex::new(0));
+    let mut manager = UndoRedoManager::new();
+    manager
+        .set_stack_limit(
+            0,
+            UndoLimit {
+                max_entries: None,
+                max_bytes: Some(250),
+            },
+        )
+        .unwrap();
+
+    for _ in 0..4 {
+        manager.add_command(Box::new(SizedCommand {
+            counter: counter.clone(),
+            size: 100,
+        }));
+    }
+    assert_eq!(manager.get_stack_size(0), 2);
+    assert_eq!(manager.get_stack_memory_usage(0), 200);
+
+    // An entry larger than the whole budget is not kept
+    manager.add_command(Box::new(SizedCommand {
+        counter: counter.clone(),
+        size: 1000,
+    }));
+    assert!(!manager.can_undo(None));
+    assert_eq!(manager.get_stack_memory_usage(0), 0);
+
+    // Composites are measured as a whole
+    manager.begin_composite(None).unwrap();
+    for _ in 0..3 {
+        manager.add_command(Box::new(SizedCommand {
+            counter: counter.clone(),
+            size: 100,
+        }));
+    }
+    manager.end_composite();
+    assert!(!manager.can_undo(None));
+}
+
+#[test]
+fn test_limit_defaults_to_unbounded() {
+    let counter = Arc::new(Mutex::new(0));
+    let mut manager = UndoRedoManager::new();
+    assert_eq!(manager.get_stack_limit(0), UndoLimit::default());
+    for _ in 0..100 {
+        manager.add_command(Box::new(TestCommand::new(counter.clone(), 1)));
+    }
+    assert_eq!(manager.get_stack_size(0), 100);
+    assert!(manager.set_stack_limit(42, UndoLimit::default()).is_err());
+}
+
+#[test]
+fn test_set_stack_history() {
+    let mut manager = UndoRedoManager::new();
+    manager.add_command(Box::new(LabelledCommand { label: "Old" }));
+    let labelled = |label| Box::new(LabelledCommand { label }) as Box<dyn UndoRedoCommand>;
+
+    manager
+        .set_stack_history(
+            0,
+            vec![labelled("First"), labelled("Second")],
+            vec![labelled("Third"), labelled("Fourth")],
+        )
+        .unwrap();
+    assert_eq!(manager.undo_descriptions(None), vec!["First", "Second"]);
+    assert_eq!(manager.redo_descriptions(None), vec!["Third", "Fourth"]);
+
+    manager.redo(None).unwrap();
+    assert_eq!(
+        manager.undo_descriptions(None),
+        vec!["First", "Second", "Third"]
+    );
+
+    manager
+        .set_stack_limit(
+            0,
+            UndoLimit {
+                max_entries: Some(2),
+                ..UndoLimit::default()
+            },
+        )
+        .unwrap();
+    manager
+        .set_stack_history(0, vec![labelled("A"), labelled("B"), labelled("C")], vec![])
+        .unwrap();
+    assert_eq!(manager.undo_descriptions(None), vec!["B", "C"]);
+    assert!(manager.set_stack_history(42, vec![], vec![]).is_err());
+}
+
+// A command that reports its changes as empty store deltas
+struct RecordedCommand {
+    label: &'static str,
+    records: usize,
+}
+
+impl UndoRedoCommand for RecordedCommand {
+    fn undo(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn redo(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn description(&self) -> String {
+        self.label.to_string()
+    }
+
+    fn edit_records(&self) -> Option<Vec<EditRecord>> {
+        Some(vec![EditRecord::Store(StoreDelta::default()); self.records])
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[test]
+fn test_edit_records() {
+    let mut manager = UndoRedoManager::new();
+    let recorded = |label, records| Box::new(RecordedCommand { label, records });
+    manager.add_command(recorded("First", 1));
+    manager.begin_composite(None).unwrap();
+    manager.add_command(recorded("Second", 2));
+    manager.add_command(recorded("Third", 1));
+    manager.end_composite();
+    manager.add_command(Box::new(LabelledCommand { label: "Fourth" }));
+    manager.undo(None).unwrap();
+
+    let counts = |entries: Vec<(String, Option<Vec<EditRecord>>)>| {
+        entries
+            .into_iter()
+            .map(|(label, records)| (label, records.map(|r| r.len())))
+            .collect::<Vec<_>>()
+    };
+    // A composite reports its children's records in order.
+    assert_eq!(
+        counts(manager.undo_records(None)),
+        vec![
+            ("First".to_string(), Some(1)),
+            ("Third".to_string(), Some(3))
+        ]
+    );
+    // Commands that can't describe their changes report none.
+    assert_eq!(
+        counts(manager.redo_records(None)),
+        vec![("Fourth".to_string(), None)]
+    );
+
+    manager.begin_composite(None).unwrap();
+    manager.add_command(recorded("Fifth", 1));
+    manager.add_command(Box::new(LabelledCommand { label: "Sixth" }));
+    manager.end_composite();
+    assert_eq!(
+        counts(manager.undo_records(None)).last(),
+        Some(&("Sixth".to_string(), None))
+    );
+    assert!(manager.undo_records(Some(42)).is_empty());
+}
+
+// A command that reports which blocks it touches
+struct BlockCommand {
+    blocks: Vec<u64>,
+}
+
+impl UndoRedoCommand for BlockCommand {
+    fn undo(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn redo(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn affected_blocks(&self) -> Option<Vec<u64>> {
+        Some(self.blocks.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[test]
+fn test_independent_stacks() {
+    let mut manager = UndoRedoManager::new();
+    let main = Some(manager.create_guarded_stack());
+    let region = manager.create_guarded_stack();
+    let touching = |blocks: &[u64]| {
+        Box::new(BlockCommand {
+            blocks: blocks.to_vec(),
+        }) as Box<dyn UndoRedoCommand>
+    };
+
+    manager
+        .add_command_to_stack(touching(&[1]), Some(region))
+        .unwrap();
+    manager.add_command_to_stack(touching(&[2]), main).unwrap();
+    // Disjoint blocks: the region undoes without touching the main stack.
+    manager.undo(Some(region)).unwrap();
+    assert!(manager.can_undo(main));
+
+    // The main stack edited since the region's undo: redo is refused.
+    manager.add_command_to_stack(touching(&[2]), main).unwrap();
+    assert!(manager.redo(Some(region)).is_err());
+    assert!(manager.can_redo(Some(region)));
+
+    manager.clear_stack(region);
+    manager
+        .add_command_to_stack(touching(&[3]), Some(region))
+        .unwrap();
+    manager.add_command_to_stack(touching(&[3]), main).unwrap();
+    // A later edit elsewhere touched the same block.
+    assert!(manager.undo(Some(region)).is_err());
+    manager.undo(main).unwrap();
+    manager.undo(Some(region)).unwrap();
+}
+
+#[test]
+fn test_composite_on_another_stack_after_one_ends() {
+    let counter = Arc::new(Mutex::new(0));
+    let mut manager = UndoRedoManager::new();
+    let region = manager.create_new_stack();
+
+    manager.begin_composite(Some(region)).unwrap();
+    // Another stack cannot start a composite while this one is open.
+    assert!(manager.begin_composite(None).is_err());
+    manager
+        .add_command_to_stack(Box::new(TestCommand::new(counter.clone(), 1)), Some(region))
+        .unwrap();
+    manager.end_composite();
+
+    manager.begin_composite(None).unwrap();
+    manager.add_command(Box::new(TestCommand::new(counter.clone(), 2)));
+    manager.end_composite();
+    assert!(manager.can_undo(None));
+    assert!(manager.can_undo(Some(region)));
+}
+
+#[test]
+fn test_plain_stacks_are_not_checked() {
+    let mut manager = UndoRedoManager::new();
+    let plain = manager.create_new_stack();
+    let guarded = manager.create_guarded_stack();
+    let touching = |blocks: &[u64]| {
+        Box::new(BlockCommand {
+            blocks: blocks.to_vec(),
+        }) as Box<dyn UndoRedoCommand>
+    };
+
+    // Stacks from `create_new_stack` step freely, as they always have.
+    manager
+        .add_command_to_stack(touching(&[1]), Some(plain))
+        .unwrap();
+    manager.add_command_to_stack(touching(&[1]), None).unwrap();
+    manager.undo(Some(plain)).unwrap();
+    manager.add_command_to_stack(touching(&[1]), None).unwrap();
+    manager.redo(Some(plain)).unwrap();
+
+    // Nor do their edits hold back a guarded stack.
+    manager
+        .add_command_to_stack(touching(&[2]), Some(guarded))
+        .unwrap();
+    manager
+        .add_command_to_stack(touching(&[2]), Some(plain))
+        .unwrap();
+    manager.undo(Some(guarded)).unwrap();
+}
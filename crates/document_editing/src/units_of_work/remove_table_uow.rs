@@ -101,6 +101,7 @@ impl CommandUnitOfWork for RemoveTableUnitOfWork {
 #[macros::uow_action(entity = "Frame", action = "GetRelationship")]
 #[macros::uow_action(entity = "Block", action = "GetMulti")]
 #[macros::uow_action(entity = "Block", action = "UpdateMulti")]
+#[macros::uow_action(entity = "Block", action = "Create")]
 #[macros::uow_action(entity = "Table", action = "Get")]
 #[macros::uow_action(entity = "Table", action = "Remove")]
 #[macros::uow_action(entity = "Table", action = "GetRelationship")]
@@ -1,6 +1,7 @@
 use crate::AddBlockToListDto;
 use anyhow::{Result, anyhow};
 use common::database::CommandUnitOfWork;
+use common::database::store_delta::StoreDelta;
 use common::direct_access::block::block_repository::BlockRelationshipField;
 use common::direct_access::root::root_repository::RootRelationshipField;
 use common::entities::{Block, Document, List, Root};
@@ -62,7 +63,7 @@ fn execute_add_block_to_list(
 
 pub struct AddBlockToListUseCase {
     uow_factory: Box<dyn AddBlockToListUnitOfWorkFactoryTrait>,
-    undo_snapshot: Option<EntityTreeSnapshot>,
+    undo_delta: Option<StoreDelta>,
     last_dto: Option<AddBlockToListDto>,
 }
 
@@ -70,7 +71,7 @@ impl AddBlockToListUseCase {
     pub fn new(uow_factory: Box<dyn AddBlockToListUnitOfWorkFactoryTrait>) -> Self {
         AddBlockToListUseCase {
             uow_factory,
-            undo_snapshot: None,
+            undo_delta: None,
             last_dto: None,
         }
     }
@@ -80,7 +81,7 @@ impl AddBlockToListUseCase {
         uow.begin_transaction()?;
 
         let snapshot = execute_add_block_to_list(&mut uow, dto)?;
-        self.undo_snapshot = Some(snapshot);
+        self.undo_delta = Some(StoreDelta::since(&snapshot, &uow.store())?);
         self.last_dto = Some(dto.clone());
 
         uow.commit()?;
@@ -90,14 +91,14 @@ impl AddBlockToListUseCase {
 
 impl UndoRedoCommand for AddBlockToListUseCase {
     fn undo(&mut self) -> Result<()> {
-        let snapshot = self
-            .undo_snapshot
+        let delta = self
+            .undo_delta
             .as_ref()
-            .ok_or_else(|| anyhow!("No snapshot available for undo"))?
-            .clone();
+            .ok_or_else(|| anyhow!("No delta available for undo"))?;
 
         let mut uow = self.uow_factory.create();
         uow.begin_transaction()?;
+        let snapshot = delta.reverted(&uow.store())?;
         uow.restore_document(&snapshot)?;
         uow.commit()?;
         Ok(())
@@ -113,11 +114,17 @@ impl UndoRedoCommand for AddBlockToListUseCase {
         let mut uow = self.uow_factory.create();
         uow.begin_transaction()?;
         let snapshot = execute_add_block_to_list(&mut uow, &dto)?;
-        self.undo_snapshot = Some(snapshot);
+        self.undo_delta = Some(StoreDelta::since(&snapshot, &uow.store())?);
         uow.commit()?;
         Ok(())
     }
 
+    fn description(&self) -> String {
+        "Add to list".to_string()
+    }
+
+    common::store_delta_undo_methods!(undo_delta);
+
     fn as_any(&self) -> &dyn Any {
         self
     }
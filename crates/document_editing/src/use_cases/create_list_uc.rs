@@ -2,7 +2,10 @@ use crate::CreateListDto;
 use crate::CreateListResultDto;
 use anyhow::{Result, anyhow};
 use common::database::CommandUnitOfWork;
-use common::database::rope_helpers::block_char_length;
+use common::database::rope_helpers::{
+    block_char_length, block_document_position, rope_positions_match_flow,
+};
+use common::database::store_delta::StoreDelta;
 use common::direct_access::block::block_repository::BlockRelationshipField;
 use common::direct_access::document::document_repository::DocumentRelationshipField;
 use common::direct_access::frame::frame_repository::FrameRelationshipField;
@@ -35,7 +38,7 @@ pub trait CreateListUnitOfWorkTrait: CommandUnitOfWork {}
 
 pub struct CreateListUseCase {
     uow_factory: Box<dyn CreateListUnitOfWorkFactoryTrait>,
-    undo_snapshot: Option<EntityTreeSnapshot>,
+    undo_delta: Option<StoreDelta>,
     last_dto: Option<CreateListDto>,
 }
 
@@ -88,6 +91,15 @@ fn execute_create_list(
     // Get all blocks
     let blocks_opt = uow.get_block_multi(&block_ids)?;
     let mut blocks: Vec<Block> = blocks_opt.into_iter().flatten().collect();
+    // When the rope mirrors the flow it is the source of truth for
+    // positions; the stored fields can be stale (see
+    // `find_block_at_position`).
+    let store = uow.store();
+    if rope_positions_match_flow(&store) {
+        for block in &mut blocks {
+            block.document_position = block_document_position(block, &store);
+        }
+    }
     blocks.sort_by_key(|b| b.document_position);
 
     // Create the List entity
@@ -105,7 +117,6 @@ fn execute_create_list(
     let created_list = uow.create_list(&list, doc_id, -1)?;
 
     // Find all blocks in range [sel_start, sel_end] and assign the list relationship
-    let store = uow.store();
     for block in &blocks {
         let block_start = block.document_position;
         let block_end = block_start + block_char_length(block, &store);
@@ -131,7 +142,7 @@ impl CreateListUseCase {
     pub fn new(uow_factory: Box<dyn CreateListUnitOfWorkFactoryTrait>) -> Self {
         CreateListUseCase {
             uow_factory,
-            undo_snapshot: None,
+            undo_delta: None,
             last_dto: None,
         }
     }
@@ -141,7 +152,7 @@ impl CreateListUseCase {
         uow.begin_transaction()?;
 
         let (result, snapshot) = execute_create_list(&mut uow, dto)?;
-        self.undo_snapshot = Some(snapshot);
+        self.undo_delta = Some(StoreDelta::since(&snapshot, &uow.store())?);
         self.last_dto = Some(dto.clone());
 
         uow.commit()?;
@@ -151,14 +162,14 @@ impl CreateListUseCase {
 
 impl UndoRedoCommand for CreateListUseCase {
     fn undo(&mut self) -> Result<()> {
-        let snapshot = self
-            .undo_snapshot
+        let delta = self
+            .undo_delta
             .as_ref()
-            .ok_or_else(|| anyhow!("No snapshot available for undo"))?
-            .clone();
+            .ok_or_else(|| anyhow!("No delta available for undo"))?;
 
         let mut uow = self.uow_factory.create();
         uow.begin_transaction()?;
+        let snapshot = delta.reverted(&uow.store())?;
         uow.restore_document(&snapshot)?;
         uow.commit()?;
         Ok(())
@@ -174,11 +185,17 @@ impl UndoRedoCommand for CreateListUseCase {
         let mut uow = self.uow_factory.create();
         uow.begin_transaction()?;
         let (_, snapshot) = execute_create_list(&mut uow, &dto)?;
-        self.undo_snapshot = Some(snapshot);
+        self.undo_delta = Some(StoreDelta::since(&snapshot, &uow.store())?);
         uow.commit()?;
         Ok(())
     }
 
+    fn description(&self) -> String {
+        "Create list".to_string()
+    }
+
+    common::store_delta_undo_methods!(undo_delta);
+
     fn as_any(&self) -> &dyn Any {
         self
     }
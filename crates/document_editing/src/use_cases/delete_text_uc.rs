@@ -5,7 +5,13 @@ use crate::DeleteTextDto;
 use crate::DeleteTextResultDto;
 use anyhow::{Result, anyhow};
 use common::database::CommandUnitOfWork;
-use common::database::rope_helpers::{block_char_length, block_content_via_store};
+use common::database::block_delta::BlockDelta;
+use common::database::edit_record::EditRecord;
+use common::database::rope_helpers::{
+    block_char_length, block_content_via_store, find_block_at_char_position,
+    refresh_block_positions, rope_positions_match_flow,
+};
+use common::database::store_delta::StoreDelta;
 use common::direct_access::document::document_repository::DocumentRelationshipField;
 use common::direct_access::frame::frame_repository::FrameRelationshipField;
 use common::direct_access::root::root_repository::RootRelationshipField;
@@ -53,13 +59,55 @@ pub trait DeleteTextUnitOfWorkTrait: CommandUnitOfWork {}
 
 pub struct DeleteTextUseCase {
     uow_factory: Box<dyn DeleteTextUnitOfWorkFactoryTrait>,
-    undo_snapshot: Option<EntityTreeSnapshot>,
+    undo_data: Option<DeleteTextUndo>,
+    /// For deletes inside one block, the block as it was just before
+    /// the last undo, for [`UndoRedoCommand::edit_records`] while the
+    /// command is undone.
+    redo_delta: Option<BlockDelta>,
     last_dto: Option<DeleteTextDto>,
     last_result: Option<DeleteTextResultDto>,
     last_merge_time: Option<Instant>,
     is_single_char_origin: bool,
 }
 
+enum DeleteTextUndo {
+    /// Collapsed selection: nothing was deleted.
+    Unchanged,
+    /// Deletion inside one block of a flat document.
+    Block(Box<BlockDelta>),
+    /// Anything touching block structure, tables or frames, as the
+    /// deltas of this delete and of any deletes merged into it, in order.
+    Structural(Vec<StoreDelta>),
+}
+
+impl DeleteTextUndo {
+    fn approximate_size(&self) -> usize {
+        match self {
+            DeleteTextUndo::Unchanged => 0,
+            DeleteTextUndo::Block(delta) => delta.approximate_size(),
+            DeleteTextUndo::Structural(deltas) => {
+                deltas.iter().map(StoreDelta::approximate_size).sum()
+            }
+        }
+    }
+}
+
+/// True when `[start..end)` lies inside a single block of a document
+/// whose rope positions match flow order, so the edit can be undone
+/// from that block's prior state alone.
+fn is_single_block_delete(store: &common::database::Store, start: i64, end: i64) -> bool {
+    if !rope_positions_match_flow(store) {
+        return false;
+    }
+    match (
+        find_block_at_char_position(store, start),
+        find_block_at_char_position(store, end),
+    ) {
+        (Some((start_block, ..)), Some((end_block, ..))) => start_block == end_block,
+        _ => false,
+    }
+}
+
 /// Read the per-block format_runs + block_images vectors. Used by callers
 /// that want to manipulate the new run/image tables directly.
 fn read_block_runs_and_images(
@@ -121,22 +169,14 @@ fn drop_block_runs_and_images(uow: &dyn DeleteTextUnitOfWorkTrait, block_id: Ent
 fn execute_delete(
     uow: &mut Box<dyn DeleteTextUnitOfWorkTrait>,
     dto: &DeleteTextDto,
-) -> Result<(DeleteTextResultDto, EntityTreeSnapshot)> {
+) -> Result<(DeleteTextResultDto, DeleteTextUndo)> {
     if dto.position == dto.anchor {
-        let root = uow
-            .get_root(&ROOT_ENTITY_ID)?
-            .ok_or_else(|| anyhow!("Root entity not found"))?;
-        let doc_ids = uow.get_root_relationship(&root.id, &RootRelationshipField::Document)?;
-        let doc_id = *doc_ids
-            .first()
-            .ok_or_else(|| anyhow!("Root has no document"))?;
-        let snapshot = uow.snapshot_document(&[doc_id])?;
         return Ok((
             DeleteTextResultDto {
                 new_position: dto.position,
                 deleted_text: String::new(),
             },
-            snapshot,
+            DeleteTextUndo::Unchanged,
         ));
     }
 
@@ -157,7 +197,20 @@ fn execute_delete(
         .get_document(&doc_id)?
         .ok_or_else(|| anyhow!("Document not found"))?;
 
-    let snapshot = uow.snapshot_document(&[doc_id])?;
+    let snapshot = if is_single_block_delete(&store, start, end) {
+        None
+    } else {
+        Some(uow.snapshot_document(&[doc_id])?)
+    };
+    // Called once the edit is done: keeps only what changed since the
+    // snapshot rather than the snapshot itself.
+    let take_delta = |snapshot: Option<EntityTreeSnapshot>| -> Result<DeleteTextUndo> {
+        let snapshot =
+            snapshot.ok_or_else(|| anyhow!("Multi-block delete without a document snapshot"))?;
+        Ok(DeleteTextUndo::Structural(vec![StoreDelta::since(
+            &snapshot, &store,
+        )?]))
+    };
 
     let frame_ids = uow.get_document_relationship(&doc_id, &DocumentRelationshipField::Frames)?;
     let frame_id = *frame_ids
@@ -181,27 +234,18 @@ fn execute_delete(
     let blocks_opt = uow.get_block_multi(&all_block_ids)?;
     let mut blocks: Vec<Block> = blocks_opt.into_iter().flatten().collect();
 
-    // Refresh stored block positions from child_order + text_length, since
-    // insert_text's fast path leaves them stale. Cell-frame blocks remain
-    // in their cell-local position space.
-    let root_frame = uow
-        .get_frame(&frame_id)?
-        .ok_or_else(|| anyhow!("Root frame not found"))?;
-    let mut running: i64 = 0;
-    let mut blocks_to_refresh: Vec<Block> = Vec::new();
-    for &entry in &root_frame.child_order {
-        if entry <= 0 {
-            continue;
-        }
-        let id = entry as EntityId;
-        if let Some(b) = blocks.iter_mut().find(|b| b.id == id) {
-            if b.document_position != running {
-                b.document_position = running;
-                blocks_to_refresh.push(b.clone());
-            }
-            running += block_char_length(b, &store) + 1;
-        }
-    }
+    // Refresh stored block positions from the rope, since insert_text's
+    // fast path leaves them stale. In documents with tables the stored
+    // positions are the maintained ones and stay as they are: a walk of
+    // the root frame's blocks alone would drop the cells' positions.
+    let stored: Vec<i64> = blocks.iter().map(|b| b.document_position).collect();
+    refresh_block_positions(&mut blocks, &store);
+    let blocks_to_refresh: Vec<Block> = blocks
+        .iter()
+        .zip(stored)
+        .filter(|(b, old)| b.document_position != *old)
+        .map(|(b, _)| b.clone())
+        .collect();
     if !blocks_to_refresh.is_empty() {
         uow.update_block_multi(&blocks_to_refresh)?;
     }
@@ -291,6 +335,7 @@ fn execute_delete(
             let extra_block_ids: Vec<EntityId> = cell_blocks[1..].iter().map(|b| b.id).collect();
             for &eid in &extra_block_ids {
                 drop_block_runs_and_images(uow.as_ref(), eid);
+                common::database::rope_helpers::rope_remove_block(&uow.store(), eid);
                 uow.remove_block(&eid)?;
             }
 
@@ -329,6 +374,20 @@ fn execute_delete(
             }
 
             if start < table_min_pos || end > table_max_pos {
+                // Removing the frames cascades to the cell blocks without
+                // touching the rope, so take them and the table's anchor
+                // out of it first (the helpers look them up by id).
+                for c in &cells {
+                    if let Some(cf_id) = c.cell_frame {
+                        let blk_ids =
+                            uow.get_frame_relationship(&cf_id, &FrameRelationshipField::Blocks)?;
+                        for bid in blk_ids {
+                            drop_block_runs_and_images(uow.as_ref(), bid);
+                            common::database::rope_helpers::rope_remove_block(&uow.store(), bid);
+                        }
+                    }
+                }
+                common::database::rope_helpers::rope_remove_table_anchor(&uow.store(), tid);
                 for c in &cells {
                     if let Some(cf_id) = c.cell_frame {
                         uow.remove_frame(&cf_id)?;
@@ -431,6 +490,7 @@ fn execute_delete(
             } else {
                 total_chars_removed += block_char_length(block, &store);
                 drop_block_runs_and_images(uow.as_ref(), block.id);
+                common::database::rope_helpers::rope_remove_block(&uow.store(), block.id);
                 uow.remove_block(&block.id)?;
                 non_cell_blocks_to_remove.push(block.id);
             }
@@ -535,8 +595,29 @@ fn execute_delete(
                 &get_tcf,
                 &frame_id,
             )?;
-            let opts = uow.get_block_multi(&candidate_ids)?;
-            opts.into_iter().flatten().count()
+            // Cells were cleared and tables or blocks removed without
+            // moving what follows, so reassign every position in flow
+            // order.
+            let remaining: Vec<Block> = uow
+                .get_block_multi(&candidate_ids)?
+                .into_iter()
+                .flatten()
+                .collect();
+            let mut running: i64 = 0;
+            let mut moved: Vec<Block> = Vec::new();
+            for block in &remaining {
+                if block.document_position != running {
+                    let mut ub = block.clone();
+                    ub.document_position = running;
+                    ub.updated_at = now;
+                    moved.push(ub);
+                }
+                running += block_char_length(block, &uow.store()) + 1;
+            }
+            if !moved.is_empty() {
+                uow.update_block_multi(&moved)?;
+            }
+            remaining.len()
         };
         if remaining_block_count == 0 {
             let empty_block = Block {
@@ -552,13 +633,11 @@ fn execute_delete(
             uf.updated_at = now;
             uow.update_frame(&uf)?;
 
-            // Cross-block delete can leave stale rope-offset entries (e.g.
-            // table-cell blocks that were cascade-removed via frame
-            // deletion never went through `rope_remove_block`, and the
-            // table-anchor sentinel can survive too). Now that every
-            // entity-store block is gone, drop everything in the rope and
-            // re-register a single empty block matching the entity we just
-            // created. No-op under default backend.
+            // `rope_remove_block` leaves a sole remaining entry in place,
+            // so the rope can still hold the last removed block. Now that
+            // every entity-store block is gone, drop everything in the
+            // rope and re-register a single empty block matching the
+            // entity we just created. No-op under default backend.
             common::database::rope_helpers::rope_reset(&uow.store());
             common::database::rope_helpers::rope_append_empty_block(&uow.store(), created.id);
         }
@@ -587,7 +666,7 @@ fn execute_delete(
                 new_position: start,
                 deleted_text: String::new(),
             },
-            snapshot,
+            take_delta(snapshot)?,
         ));
     }
     // ── End cell selection safety ──────────────────────────────────
@@ -603,6 +682,16 @@ fn execute_delete(
         let start_block_text = block_content_via_store(&start_block, &store);
         let byte_so = logical_offset_to_byte(&start_block_text, &images, start_offset);
         let byte_eo = logical_offset_to_byte(&start_block_text, &images, end_offset);
+        let block_undo = snapshot.is_none().then(|| {
+            Box::new(BlockDelta::capture(
+                &store,
+                &blocks[start_block_idx],
+                doc_id,
+                document.character_count,
+                byte_so..byte_eo,
+                0,
+            ))
+        });
 
         let deleted_text: String = start_block_text[byte_so as usize..byte_eo as usize].to_string();
 
@@ -662,7 +751,10 @@ fn execute_delete(
                 new_position: start,
                 deleted_text,
             },
-            snapshot,
+            match block_undo {
+                Some(delta) => DeleteTextUndo::Block(delta),
+                None => take_delta(snapshot)?,
+            },
         ))
     } else {
         // Cross-block delete: merge end_block's tail into start_block.
@@ -852,7 +944,7 @@ fn execute_delete(
                 new_position: start,
                 deleted_text,
             },
-            snapshot,
+            take_delta(snapshot)?,
         ))
     }
 }
@@ -915,7 +1007,8 @@ impl DeleteTextUseCase {
     pub fn new(uow_factory: Box<dyn DeleteTextUnitOfWorkFactoryTrait>) -> Self {
         DeleteTextUseCase {
             uow_factory,
-            undo_snapshot: None,
+            undo_data: None,
+            redo_delta: None,
             last_dto: None,
             last_result: None,
             last_merge_time: None,
@@ -927,8 +1020,9 @@ impl DeleteTextUseCase {
         let mut uow = self.uow_factory.create();
         uow.begin_transaction()?;
 
-        let (result, snapshot) = execute_delete(&mut uow, dto)?;
-        self.undo_snapshot = Some(snapshot);
+        let (result, undo) = execute_delete(&mut uow, dto)?;
+        self.undo_data = Some(undo);
+        self.redo_delta = None;
         self.last_dto = Some(dto.clone());
         self.last_result = Some(result.clone());
         self.last_merge_time = Some(Instant::now());
@@ -941,15 +1035,53 @@ impl DeleteTextUseCase {
 
 impl UndoRedoCommand for DeleteTextUseCase {
     fn undo(&mut self) -> Result<()> {
-        let snapshot = self
-            .undo_snapshot
+        let undo = self
+            .undo_data
             .as_ref()
-            .ok_or_else(|| anyhow!("No snapshot available for undo"))?
-            .clone();
+            .ok_or_else(|| anyhow!("No undo data available"))?;
 
         let mut uow = self.uow_factory.create();
         uow.begin_transaction()?;
-        uow.restore_document(&snapshot)?;
+        match undo {
+            DeleteTextUndo::Unchanged => {}
+            DeleteTextUndo::Block(delta) => {
+                self.redo_delta = Some(delta.recapture(&uow.store()));
+                let added = delta.chars_added(&uow.store());
+                delta.restore_content(&uow.store());
+                // Move later blocks and the character count back by what
+                // the edit changed rather than resetting them, and keep
+                // the block's current position: edits made since on
+                // other undo stacks may have moved them.
+                let mut block = delta.block.clone();
+                if let Some(current) = uow.get_block(&block.id)? {
+                    block.document_position = current.document_position;
+                }
+                uow.update_block(&block)?;
+                let ids = delta.blocks_after(&uow.store());
+                if !ids.is_empty() {
+                    let shifted: Vec<Block> = uow
+                        .get_block_multi(&ids)?
+                        .into_iter()
+                        .flatten()
+                        .map(|mut b| {
+                            b.document_position -= added;
+                            b
+                        })
+                        .collect();
+                    uow.update_block_multi(&shifted)?;
+                }
+                let mut doc = uow
+                    .get_document(&delta.doc_id)?
+                    .ok_or_else(|| anyhow!("Document not found"))?;
+                doc.character_count -= added;
+                doc.updated_at = chrono::Utc::now();
+                uow.update_document(&doc)?;
+            }
+            DeleteTextUndo::Structural(deltas) => {
+                let snapshot = StoreDelta::reverted_all(deltas, &uow.store())?;
+                uow.restore_document(&snapshot)?;
+            }
+        }
         uow.commit()?;
         Ok(())
     }
@@ -963,8 +1095,9 @@ impl UndoRedoCommand for DeleteTextUseCase {
 
         let mut uow = self.uow_factory.create();
         uow.begin_transaction()?;
-        let (_, snapshot) = execute_delete(&mut uow, &dto)?;
-        self.undo_snapshot = Some(snapshot);
+        let (_, undo) = execute_delete(&mut uow, &dto)?;
+        self.undo_data = Some(undo);
+        self.redo_delta = None;
         uow.commit()?;
         Ok(())
     }
@@ -991,6 +1124,16 @@ impl UndoRedoCommand for DeleteTextUseCase {
             return false;
         }
 
+        // A block delta can absorb later deletes in the same block;
+        // structural deltas take on the later delete's deltas.
+        match (&self.undo_data, &other_cmd.undo_data) {
+            (Some(DeleteTextUndo::Block(self_delta)), Some(DeleteTextUndo::Block(other_delta)))
+                if other_delta.block.id == self_delta.block.id => {}
+            (Some(DeleteTextUndo::Structural(_)), Some(DeleteTextUndo::Structural(_))) => {}
+            (Some(DeleteTextUndo::Block(_) | DeleteTextUndo::Structural(_)), _) => return false,
+            _ => {}
+        }
+
         if !self.is_single_char_origin {
             return false;
         }
@@ -1041,6 +1184,15 @@ impl UndoRedoCommand for DeleteTextUseCase {
             return false;
         };
 
+        // Widen the block delta over the other delete, so a single undo
+        // puts back every merged character.
+        if let (Some(DeleteTextUndo::Block(delta)), Some(DeleteTextUndo::Block(other_delta))) =
+            (&mut self.undo_data, &other_cmd.undo_data)
+            && !delta.absorb(other_delta)
+        {
+            return false;
+        }
+
         let self_is_backspace = self_dto.position > self_dto.anchor;
 
         let combined_dto = if self_is_backspace {
@@ -1055,6 +1207,14 @@ impl UndoRedoCommand for DeleteTextUseCase {
             }
         };
 
+        if let (
+            Some(DeleteTextUndo::Structural(deltas)),
+            Some(DeleteTextUndo::Structural(other_deltas)),
+        ) = (&mut self.undo_data, &other_cmd.undo_data)
+        {
+            deltas.extend(other_deltas.iter().cloned());
+        }
+
         self.last_dto = Some(combined_dto);
         self.last_result = Some(other_result.clone());
         self.last_merge_time = Some(*other_time);
@@ -1062,6 +1222,51 @@ impl UndoRedoCommand for DeleteTextUseCase {
         true
     }
 
+    fn affected_blocks(&self) -> Option<Vec<EntityId>> {
+        match self.undo_data.as_ref()? {
+            DeleteTextUndo::Unchanged => Some(Vec::new()),
+            DeleteTextUndo::Block(delta) => Some(vec![delta.block.id]),
+            DeleteTextUndo::Structural(deltas) => {
+                let mut blocks = Vec::new();
+                for delta in deltas {
+                    blocks.extend(delta.changed_blocks.as_ref()?);
+                }
+                blocks.sort_unstable();
+                blocks.dedup();
+                Some(blocks)
+            }
+        }
+    }
+
+    fn description(&self) -> String {
+        "Delete".to_string()
+    }
+
+    fn approximate_size(&self) -> usize {
+        std::mem::size_of_val(self)
+            + self
+                .undo_data
+                .as_ref()
+                .map_or(0, DeleteTextUndo::approximate_size)
+            + self
+                .redo_delta
+                .as_ref()
+                .map_or(0, BlockDelta::approximate_size)
+    }
+
+    fn edit_records(&self) -> Option<Vec<EditRecord>> {
+        match (self.undo_data.as_ref()?, &self.redo_delta) {
+            (DeleteTextUndo::Unchanged, _) => Some(Vec::new()),
+            (DeleteTextUndo::Block(_), Some(delta)) => Some(vec![EditRecord::Block(delta.clone())]),
+            (DeleteTextUndo::Block(delta), None) => {
+                Some(vec![EditRecord::Block((**delta).clone())])
+            }
+            (DeleteTextUndo::Structural(deltas), _) => {
+                Some(deltas.iter().cloned().map(EditRecord::Store).collect())
+            }
+        }
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
@@ -49,12 +49,19 @@ pub(crate) use impl_cell_frame_creator;
 /// inline_elements bridge sees the conventional single-Empty element
 /// for the cell; the new format_runs / block_images representation is
 /// the source of truth (both empty for a brand-new cell).
+///
+/// `anchor_frame` is the table's anchor frame, the cell frame's parent,
+/// when it already exists.
 pub fn create_cell_frame(
     uow: &mut dyn CellFrameCreator,
     doc_id: EntityId,
+    anchor_frame: Option<EntityId>,
     now: chrono::DateTime<chrono::Utc>,
 ) -> Result<(EntityId, Block)> {
-    let cell_frame = Frame::default();
+    let cell_frame = Frame {
+        parent_frame: anchor_frame,
+        ..Frame::default()
+    };
     let created_frame = uow.cfc_create_frame(&cell_frame, doc_id, -1)?;
 
     let block = Block {
@@ -101,14 +108,14 @@ macro_rules! impl_cell_block_reader {
 
 pub(crate) use impl_cell_block_reader;
 
-/// Compute the minimum document_position across all blocks in the given cell frames.
 /// Reassign document_position for all blocks across table cells in row-major order.
-/// Returns the blocks that need updating and the total block count.
+/// Returns the blocks that need updating and the positions they span.
 pub fn reassign_cell_block_positions(
     uow: &dyn CellBlockReader,
     cells: &[common::entities::TableCell],
     base_pos: i64,
     now: chrono::DateTime<chrono::Utc>,
+    store: &Store,
 ) -> Result<(Vec<Block>, i64)> {
     let mut blocks_to_update: Vec<Block> = Vec::new();
     let mut running_pos: i64 = 0;
@@ -122,8 +129,8 @@ pub fn reassign_cell_block_positions(
             for mut block in blocks {
                 block.document_position = base_pos + running_pos;
                 block.updated_at = now;
+                running_pos += block_char_length(&block, store) + 1;
                 blocks_to_update.push(block);
-                running_pos += 1;
             }
         }
     }
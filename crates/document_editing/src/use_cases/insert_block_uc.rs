@@ -13,6 +13,7 @@ use common::format_runs::{
     debug_assert_well_formed, logical_offset_to_byte, split_images_at, split_runs_at,
 };
 
+use common::database::store_delta::StoreDelta;
 use common::snapshot::EntityTreeSnapshot;
 use common::types::{EntityId, ROOT_ENTITY_ID};
 use common::undo_redo::UndoRedoCommand;
@@ -44,7 +45,7 @@ pub trait InsertBlockUnitOfWorkTrait: CommandUnitOfWork {}
 
 pub struct InsertBlockUseCase {
     uow_factory: Box<dyn InsertBlockUnitOfWorkFactoryTrait>,
-    undo_snapshot: Option<EntityTreeSnapshot>,
+    undo_delta: Option<StoreDelta>,
     last_dto: Option<InsertBlockDto>,
 }
 
@@ -195,9 +196,29 @@ fn execute_insert_block(
         }
         Ok(None)
     }
-    let owner_frame_id = find_owner_frame(&**uow, &frame_id, current_block.id)?.unwrap_or(frame_id);
+    let mut owner_frame_id = find_owner_frame(&**uow, &frame_id, current_block.id)?;
+    // Cell frames hang off their table rather than a frame's child order.
+    if owner_frame_id.is_none() {
+        for fid in &frame_ids {
+            if uow
+                .get_frame_relationship(fid, &FrameRelationshipField::Blocks)?
+                .contains(&current_block.id)
+            {
+                owner_frame_id = Some(*fid);
+                break;
+            }
+        }
+    }
+    let owner_frame_id = owner_frame_id.unwrap_or(frame_id);
 
-    let created_block = uow.create_block(&new_block, owner_frame_id, -1)?;
+    // Right after the split block in its frame's blocks, which cell
+    // frames are read in the order of.
+    let insert_index = uow
+        .get_frame_relationship(&owner_frame_id, &FrameRelationshipField::Blocks)?
+        .iter()
+        .position(|&id| id == current_block.id)
+        .map_or(-1, |i| i as i32 + 1);
+    let created_block = uow.create_block(&new_block, owner_frame_id, insert_index)?;
 
     // Place the split format_runs / block_images on the new block.
     debug_assert_well_formed(&right_runs, text_after_byte_len);
@@ -266,7 +287,7 @@ impl InsertBlockUseCase {
     pub fn new(uow_factory: Box<dyn InsertBlockUnitOfWorkFactoryTrait>) -> Self {
         InsertBlockUseCase {
             uow_factory,
-            undo_snapshot: None,
+            undo_delta: None,
             last_dto: None,
         }
     }
@@ -276,7 +297,7 @@ impl InsertBlockUseCase {
         uow.begin_transaction()?;
 
         let (result, snapshot) = execute_insert_block(&mut uow, dto)?;
-        self.undo_snapshot = Some(snapshot);
+        self.undo_delta = Some(StoreDelta::since(&snapshot, &uow.store())?);
         self.last_dto = Some(dto.clone());
 
         uow.commit()?;
@@ -286,14 +307,14 @@ impl InsertBlockUseCase {
 
 impl UndoRedoCommand for InsertBlockUseCase {
     fn undo(&mut self) -> Result<()> {
-        let snapshot = self
-            .undo_snapshot
+        let delta = self
+            .undo_delta
             .as_ref()
-            .ok_or_else(|| anyhow!("No snapshot available for undo"))?
-            .clone();
+            .ok_or_else(|| anyhow!("No delta available for undo"))?;
 
         let mut uow = self.uow_factory.create();
         uow.begin_transaction()?;
+        let snapshot = delta.reverted(&uow.store())?;
         uow.restore_document(&snapshot)?;
         uow.commit()?;
         Ok(())
@@ -309,11 +330,17 @@ impl UndoRedoCommand for InsertBlockUseCase {
         let mut uow = self.uow_factory.create();
         uow.begin_transaction()?;
         let (_, snapshot) = execute_insert_block(&mut uow, &dto)?;
-        self.undo_snapshot = Some(snapshot);
+        self.undo_delta = Some(StoreDelta::since(&snapshot, &uow.store())?);
         uow.commit()?;
         Ok(())
     }
 
+    fn description(&self) -> String {
+        "Insert paragraph".to_string()
+    }
+
+    common::store_delta_undo_methods!(undo_delta);
+
     fn as_any(&self) -> &dyn Any {
         self
     }
@@ -3,15 +3,18 @@ use crate::InsertFormattedTextDto;
 use crate::InsertFormattedTextResultDto;
 use anyhow::{Result, anyhow};
 use common::database::CommandUnitOfWork;
+use common::database::block_delta::BlockDelta;
+use common::database::edit_record::EditRecord;
 use common::database::rope_helpers::{
     block_char_length, block_content_via_store, rope_delete_in_block, rope_insert_in_block,
 };
+use common::database::store_delta::StoreDelta;
 use common::direct_access::document::document_repository::DocumentRelationshipField;
 use common::direct_access::root::root_repository::RootRelationshipField;
 use common::direct_access::table::TableRelationshipField;
 use common::entities::{Block, Document, Frame, Root, TableCell};
 use common::format_runs::{
-    CharacterFormat, FormatRun, ImageAnchor, debug_assert_well_formed, logical_offset_to_byte,
+    CharacterFormat, FormatRun, debug_assert_well_formed, logical_offset_to_byte,
     shift_images_for_delete, shift_images_for_insert, shift_runs_for_delete, shift_runs_for_insert,
     splice_range,
 };
@@ -43,28 +46,29 @@ pub trait InsertFormattedTextUnitOfWorkFactoryTrait: Send + Sync {
 #[macros::uow_action(entity = "TableCell", action = "GetMulti")]
 pub trait InsertFormattedTextUnitOfWorkTrait: CommandUnitOfWork {}
 
-/// Lightweight undo data for the simple (no-selection) path.
-struct SimpleUndoData {
-    block_id: EntityId,
-    original_block: Block,
-    original_format_runs: Vec<FormatRun>,
-    original_block_images: Vec<ImageAnchor>,
-    doc_id: EntityId,
-    original_character_count: i64,
-    /// Byte offset inside the block where the new text was inserted.
-    inserted_byte_offset: u32,
-    /// Number of bytes inserted into the rope by this command.
-    inserted_byte_len: u32,
+enum InsertFormattedTextUndo {
+    /// The block before a no-selection insert. Undo restores its row,
+    /// runs and images and deletes the inserted bytes.
+    Simple(Box<BlockDelta>),
+    SelectionReplacement(Box<StoreDelta>),
 }
 
-enum InsertFormattedTextUndo {
-    Simple(Box<SimpleUndoData>),
-    SelectionReplacement(common::snapshot::EntityTreeSnapshot),
+impl InsertFormattedTextUndo {
+    fn approximate_size(&self) -> usize {
+        match self {
+            InsertFormattedTextUndo::Simple(original) => original.approximate_size(),
+            InsertFormattedTextUndo::SelectionReplacement(delta) => delta.approximate_size(),
+        }
+    }
 }
 
 pub struct InsertFormattedTextUseCase {
     uow_factory: Box<dyn InsertFormattedTextUnitOfWorkFactoryTrait>,
     undo_data: Option<InsertFormattedTextUndo>,
+    /// For the simple path, the edited block as it was just before the
+    /// last undo, for [`UndoRedoCommand::edit_records`] while the
+    /// command is undone.
+    redo_delta: Option<BlockDelta>,
     last_dto: Option<InsertFormattedTextDto>,
 }
 
@@ -86,6 +90,9 @@ fn dto_to_character_format(dto: &InsertFormattedTextDto) -> CharacterFormat {
         tooltip: None,
         underline_style: None,
         vertical_alignment: None,
+        foreground_color: None,
+        background_color: None,
+        underline_color: None,
     }
 }
 
@@ -287,7 +294,10 @@ fn execute_with_selection(
         InsertFormattedTextResultDto {
             new_position: sel_start + text_len,
         },
-        InsertFormattedTextUndo::SelectionReplacement(snapshot),
+        InsertFormattedTextUndo::SelectionReplacement(Box::new(StoreDelta::since(
+            &snapshot,
+            &uow.store(),
+        )?)),
     ))
 }
 
@@ -337,23 +347,14 @@ fn execute_insert_simple(
     let store = uow.store();
     let offset = (position - block_pos).clamp(0, block_char_length(&block, &store));
 
-    let original_block = block.clone();
-    let original_format_runs = store
-        .format_runs
-        .read()
-        .unwrap()
-        .get(&block.id)
-        .cloned()
-        .unwrap_or_default();
-    let original_block_images = store
-        .block_images
-        .read()
-        .unwrap()
-        .get(&block.id)
-        .cloned()
-        .unwrap_or_default();
+    let mut original =
+        BlockDelta::capture(&store, &block, doc_id, document.character_count, 0..0, 0);
 
+    // Nothing is removed, so the splice can be placed once the insert
+    // has worked out where its bytes went.
     let (inserted_byte_offset, inserted_byte_len) = insert_formatted_at(uow, &block, offset, dto)?;
+    original.at = inserted_byte_offset;
+    original.inserted_len = inserted_byte_len;
 
     let text_len = dto.text.chars().count() as i64;
     let mut updated_doc = document.clone();
@@ -361,22 +362,11 @@ fn execute_insert_simple(
     updated_doc.updated_at = chrono::Utc::now();
     uow.update_document(&updated_doc)?;
 
-    let undo_data = SimpleUndoData {
-        block_id: block.id,
-        original_block,
-        original_format_runs,
-        original_block_images,
-        doc_id,
-        original_character_count: document.character_count,
-        inserted_byte_offset,
-        inserted_byte_len,
-    };
-
     Ok((
         InsertFormattedTextResultDto {
             new_position: position + text_len,
         },
-        InsertFormattedTextUndo::Simple(Box::new(undo_data)),
+        InsertFormattedTextUndo::Simple(Box::new(original)),
     ))
 }
 
@@ -421,6 +411,7 @@ impl InsertFormattedTextUseCase {
         InsertFormattedTextUseCase {
             uow_factory,
             undo_data: None,
+            redo_delta: None,
             last_dto: None,
         }
     }
@@ -439,6 +430,7 @@ impl InsertFormattedTextUseCase {
             execute_insert_simple(&mut uow, dto)?
         };
         self.undo_data = Some(undo_data);
+        self.redo_delta = None;
         self.last_dto = Some(dto.clone());
 
         uow.commit()?;
@@ -457,43 +449,23 @@ impl UndoRedoCommand for InsertFormattedTextUseCase {
         uow.begin_transaction()?;
 
         match &undo_data {
-            InsertFormattedTextUndo::Simple(data) => {
-                uow.update_block(&data.original_block)?;
-                let store = uow.store();
-
-                // Revert the rope mutation done by the forward path. Must
-                // happen BEFORE format_runs/block_images restore so any
-                // debug_assert_well_formed inside the rope helper sees a
-                // consistent runs-vs-rope state.
-                if data.inserted_byte_len > 0 {
-                    rope_delete_in_block(
-                        &store,
-                        data.block_id,
-                        data.inserted_byte_offset,
-                        data.inserted_byte_offset + data.inserted_byte_len,
-                    );
-                }
-
-                store
-                    .format_runs
-                    .write()
-                    .unwrap()
-                    .insert(data.block_id, data.original_format_runs.clone());
-                store
-                    .block_images
-                    .write()
-                    .unwrap()
-                    .insert(data.block_id, data.original_block_images.clone());
+            InsertFormattedTextUndo::Simple(original) => {
+                self.redo_delta = Some(original.recapture(&uow.store()));
+                uow.update_block(&original.block)?;
+                // Deletes the inserted bytes from the rope before putting
+                // the runs and images back.
+                original.restore_content(&uow.store());
 
                 let mut doc = uow
-                    .get_document(&data.doc_id)?
+                    .get_document(&original.doc_id)?
                     .ok_or_else(|| anyhow!("Document not found"))?;
-                doc.character_count = data.original_character_count;
+                doc.character_count = original.character_count;
                 doc.updated_at = chrono::Utc::now();
                 uow.update_document(&doc)?;
             }
-            InsertFormattedTextUndo::SelectionReplacement(snapshot) => {
-                uow.restore_document(snapshot)?;
+            InsertFormattedTextUndo::SelectionReplacement(delta) => {
+                let snapshot = delta.reverted(&uow.store())?;
+                uow.restore_document(&snapshot)?;
             }
         }
 
@@ -519,11 +491,48 @@ impl UndoRedoCommand for InsertFormattedTextUseCase {
             execute_insert_simple(&mut uow, &dto)?
         };
         self.undo_data = Some(undo_data);
+        self.redo_delta = None;
 
         uow.commit()?;
         Ok(())
     }
 
+    fn affected_blocks(&self) -> Option<Vec<EntityId>> {
+        match self.undo_data.as_ref()? {
+            InsertFormattedTextUndo::Simple(original) => Some(vec![original.block.id]),
+            InsertFormattedTextUndo::SelectionReplacement(delta) => delta.changed_blocks.clone(),
+        }
+    }
+
+    fn description(&self) -> String {
+        "Insert formatted text".to_string()
+    }
+
+    fn approximate_size(&self) -> usize {
+        std::mem::size_of_val(self)
+            + self
+                .undo_data
+                .as_ref()
+                .map_or(0, InsertFormattedTextUndo::approximate_size)
+            + self
+                .redo_delta
+                .as_ref()
+                .map_or(0, BlockDelta::approximate_size)
+    }
+
+    fn edit_records(&self) -> Option<Vec<EditRecord>> {
+        let record = match (self.undo_data.as_ref()?, &self.redo_delta) {
+            (InsertFormattedTextUndo::Simple(_), Some(delta)) => EditRecord::Block(delta.clone()),
+            (InsertFormattedTextUndo::Simple(original), None) => {
+                EditRecord::Block((**original).clone())
+            }
+            (InsertFormattedTextUndo::SelectionReplacement(delta), _) => {
+                EditRecord::Store((**delta).clone())
+            }
+        };
+        Some(vec![record])
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
@@ -17,10 +17,11 @@ use common::direct_access::root::root_repository::RootRelationshipField;
 use common::direct_access::table::TableRelationshipField;
 use common::entities::{Block, Document, Frame, List, Root, Table, TableCell};
 use common::format_runs::{
-    FormatRun, ImageAnchor, InlineSegment, character_format_from_segment, coalesce_in_place,
-    logical_offset_to_byte, split_images_at, split_runs_at,
+    FormatRun, ImageAnchor, coalesce_in_place, logical_offset_to_byte, split_images_at,
+    split_runs_at,
 };
 
+use common::database::store_delta::StoreDelta;
 use common::parser_tools::fragment_schema::{FragmentBlock, FragmentData, FragmentTable};
 use common::parser_tools::list_grouper::ListGrouper;
 use common::snapshot::EntityTreeSnapshot;
@@ -78,25 +79,7 @@ fn frag_block_state(fb: &FragmentBlock) -> (Vec<FormatRun>, Vec<ImageAnchor>) {
     let mut byte_offset: u32 = 0;
 
     for elem in &fb.elements {
-        let fmt = character_format_from_segment(&InlineSegment {
-            content: elem.content.clone(),
-            fmt_font_family: elem.fmt_font_family.clone(),
-            fmt_font_point_size: elem.fmt_font_point_size,
-            fmt_font_weight: elem.fmt_font_weight,
-            fmt_font_bold: elem.fmt_font_bold,
-            fmt_font_italic: elem.fmt_font_italic,
-            fmt_font_underline: elem.fmt_font_underline,
-            fmt_font_overline: elem.fmt_font_overline,
-            fmt_font_strikeout: elem.fmt_font_strikeout,
-            fmt_letter_spacing: elem.fmt_letter_spacing,
-            fmt_word_spacing: elem.fmt_word_spacing,
-            fmt_anchor_href: elem.fmt_anchor_href.clone(),
-            fmt_anchor_names: elem.fmt_anchor_names.clone(),
-            fmt_is_anchor: elem.fmt_is_anchor,
-            fmt_tooltip: elem.fmt_tooltip.clone(),
-            fmt_underline_style: elem.fmt_underline_style.clone(),
-            fmt_vertical_alignment: elem.fmt_vertical_alignment.clone(),
-        });
+        let fmt = elem.to_character_format();
 
         match &elem.content {
             InlineContent::Empty => {}
@@ -170,6 +153,17 @@ fn clear_block_state(uow: &mut Box<dyn InsertFragmentUnitOfWorkTrait>, block_id:
     store.block_images.write().unwrap().remove(&block_id);
 }
 
+/// Index in `frame.child_order` right after `block_id`, or the end if
+/// the block is not listed. `block_idx` counts blocks across the whole
+/// document and can be off inside sub-frames or past stale positions.
+fn child_order_index_after(frame: &Frame, block_id: EntityId) -> usize {
+    frame
+        .child_order
+        .iter()
+        .position(|&entry| entry > 0 && entry as EntityId == block_id)
+        .map_or(frame.child_order.len(), |i| i + 1)
+}
+
 /// Collect all blocks from a frame tree and map each block to its owning frame.
 /// Traverses blockquote sub-frames and table cell frames recursively.
 fn collect_all_blocks_with_frame(
@@ -489,10 +483,33 @@ fn insert_table_fragment(
         };
         let created_table = uow.create_table(&table, doc_id, -1)?;
 
+        let anchor_frame = Frame {
+            id: 0,
+            created_at: now,
+            updated_at: now,
+            parent_frame: Some(frame_id),
+            blocks: vec![],
+            child_order: vec![],
+            fmt_height: None,
+            fmt_width: None,
+            fmt_top_margin: None,
+            fmt_bottom_margin: None,
+            fmt_left_margin: None,
+            fmt_right_margin: None,
+            fmt_padding: None,
+            fmt_border: None,
+            fmt_position: None,
+            fmt_is_blockquote: None,
+            table: Some(created_table.id),
+            byte_range: (0, 0),
+        };
+        let created_anchor = uow.create_frame(&anchor_frame, doc_id, -1)?;
+
         let mut cell_blocks_to_update: Vec<Block> = Vec::new();
 
         for frag_cell in &frag_table.cells {
-            let (cell_frame_id, created_block) = create_cell_frame(uow, doc_id, now)?;
+            let (cell_frame_id, created_block) =
+                create_cell_frame(uow, doc_id, Some(created_anchor.id), now)?;
             let mut this_cell_blocks: CellPayload = Vec::new();
 
             if !frag_cell.blocks.is_empty() {
@@ -563,28 +580,6 @@ fn insert_table_fragment(
             uow.update_block_multi(&cell_blocks_to_update)?;
         }
 
-        let anchor_frame = Frame {
-            id: 0,
-            created_at: now,
-            updated_at: now,
-            parent_frame: Some(frame_id),
-            blocks: vec![],
-            child_order: vec![],
-            fmt_height: None,
-            fmt_width: None,
-            fmt_top_margin: None,
-            fmt_bottom_margin: None,
-            fmt_left_margin: None,
-            fmt_right_margin: None,
-            fmt_padding: None,
-            fmt_border: None,
-            fmt_position: None,
-            fmt_is_blockquote: None,
-            table: Some(created_table.id),
-            byte_range: (0, 0),
-        };
-        let created_anchor = uow.create_frame(&anchor_frame, doc_id, -1)?;
-
         let parent_frame = uow
             .get_frame(&frame_id)?
             .ok_or_else(|| anyhow!("Parent frame not found"))?;
@@ -1130,13 +1125,36 @@ fn insert_mixed_fragment(
                 };
                 let created_table = uow.create_table(&table, doc_id, -1)?;
 
+                let anchor_frame = Frame {
+                    id: 0,
+                    created_at: now,
+                    updated_at: now,
+                    parent_frame: Some(frame_id),
+                    blocks: vec![],
+                    child_order: vec![],
+                    fmt_height: None,
+                    fmt_width: None,
+                    fmt_top_margin: None,
+                    fmt_bottom_margin: None,
+                    fmt_left_margin: None,
+                    fmt_right_margin: None,
+                    fmt_padding: None,
+                    fmt_border: None,
+                    fmt_position: None,
+                    fmt_is_blockquote: None,
+                    table: Some(created_table.id),
+                    byte_range: (0, 0),
+                };
+                let created_anchor = uow.create_frame(&anchor_frame, doc_id, -1)?;
+
                 let mut cell_blocks_to_update: Vec<Block> = Vec::new();
                 // (block_id, content) tuples in cell order, for the
                 // rope mirror below.
                 let mut this_table_cell_blocks: Vec<Vec<(EntityId, String)>> = Vec::new();
 
                 for frag_cell in &frag_table.cells {
-                    let (cell_frame_id, created_block) = create_cell_frame(uow, doc_id, now)?;
+                    let (cell_frame_id, created_block) =
+                        create_cell_frame(uow, doc_id, Some(created_anchor.id), now)?;
                     let mut this_cell_blocks: Vec<(EntityId, String)> = Vec::new();
 
                     if !frag_cell.blocks.is_empty() {
@@ -1209,27 +1227,6 @@ fn insert_mixed_fragment(
                     uow.update_block_multi(&cell_blocks_to_update)?;
                 }
 
-                let anchor_frame = Frame {
-                    id: 0,
-                    created_at: now,
-                    updated_at: now,
-                    parent_frame: Some(frame_id),
-                    blocks: vec![],
-                    child_order: vec![],
-                    fmt_height: None,
-                    fmt_width: None,
-                    fmt_top_margin: None,
-                    fmt_bottom_margin: None,
-                    fmt_left_margin: None,
-                    fmt_right_margin: None,
-                    fmt_padding: None,
-                    fmt_border: None,
-                    fmt_position: None,
-                    fmt_is_blockquote: None,
-                    table: Some(created_table.id),
-                    byte_range: (0, 0),
-                };
-                let created_anchor = uow.create_frame(&anchor_frame, doc_id, -1)?;
                 new_child_order_entries.push(-(created_anchor.id as i64));
 
                 // Splice the anchor into the parent frame's child_order
@@ -1448,7 +1445,7 @@ fn insert_mixed_fragment(
     }
 
     let mut updated_frame = frame.clone();
-    let child_order_insert_pos = (block_idx + 1).min(updated_frame.child_order.len());
+    let child_order_insert_pos = child_order_index_after(&updated_frame, current_block.id);
     for (i, entry) in new_child_order_entries.iter().enumerate() {
         updated_frame
             .child_order
@@ -1809,12 +1806,10 @@ fn execute_insert_fragment(
         let mut new_block_ids: Vec<EntityId> = Vec::new();
         // Track (created_block_id, plain_text) for the rope mirror.
         let mut middle_block_payload: Vec<(EntityId, String)> = Vec::new();
-        let head_delta = updated_current_char_length - original_current_char_length;
-        let mut total_new_chars: i64 = if merge_first || overwrite_head {
-            head_delta
-        } else {
-            0
-        };
+        // The head keeps only `text_before` (plus the first fragment
+        // block when merged); what followed the cursor moves to the tail
+        // block and is counted back in there.
+        let mut total_new_chars: i64 = updated_current_char_length - original_current_char_length;
         let mut running_position =
             current_block.document_position + updated_current_char_length + 1;
 
@@ -1891,9 +1886,6 @@ fn execute_insert_fragment(
             &right_images,
             if merge_last { Some(last_frag) } else { None },
         );
-        if merge_last {
-            total_new_chars += last_chars;
-        }
 
         let tail_chars = tail_plain.chars().count() as i64;
         let tail_image_count = tail_images.len() as i64;
@@ -2004,7 +1996,7 @@ fn execute_insert_fragment(
         }
 
         let mut updated_frame = frame.clone();
-        let child_order_insert_pos = (block_idx + 1).min(updated_frame.child_order.len());
+        let child_order_insert_pos = child_order_index_after(&updated_frame, current_block.id);
         let mut new_child_ids: Vec<i64> = new_block_ids.iter().map(|id| *id as i64).collect();
         if let Some(tid) = created_tail_id {
             new_child_ids.push(tid as i64);
@@ -2076,6 +2068,9 @@ fn execute_insert_fragment(
             let _ = rope_insert_block_boundary;
         }
 
+        // The tail holds `text_after`, after the last fragment block
+        // when merged.
+        total_new_chars += tail_text_len;
         let standalone_count = (middle_end - middle_start) as i64;
         let tail_count: i64 = if skip_tail { 0 } else { 1 };
         let blocks_added = standalone_count + tail_count;
@@ -2205,7 +2200,8 @@ fn execute_insert_fragment(
                 );
 
                 let mut updated_frame = frame.clone();
-                let child_order_insert_pos = (block_idx + 1).min(updated_frame.child_order.len());
+                let child_order_insert_pos =
+                    child_order_index_after(&updated_frame, current_block.id);
                 updated_frame
                     .child_order
                     .insert(child_order_insert_pos, created_tail.id as i64);
@@ -2368,7 +2364,7 @@ fn execute_insert_fragment(
             );
 
             let mut updated_frame = frame.clone();
-            let child_order_insert_pos = (block_idx + 1).min(updated_frame.child_order.len());
+            let child_order_insert_pos = child_order_index_after(&updated_frame, current_block.id);
             let new_child_ids = [created_block.id as i64, created_tail.id as i64];
             for (i, id) in new_child_ids.iter().enumerate() {
                 updated_frame
@@ -2457,7 +2453,7 @@ fn execute_insert_fragment(
 
 pub struct InsertFragmentUseCase {
     uow_factory: Box<dyn InsertFragmentUnitOfWorkFactoryTrait>,
-    undo_snapshot: Option<EntityTreeSnapshot>,
+    undo_delta: Option<StoreDelta>,
     last_dto: Option<InsertFragmentDto>,
 }
 
@@ -2465,7 +2461,7 @@ impl InsertFragmentUseCase {
     pub fn new(uow_factory: Box<dyn InsertFragmentUnitOfWorkFactoryTrait>) -> Self {
         InsertFragmentUseCase {
             uow_factory,
-            undo_snapshot: None,
+            undo_delta: None,
             last_dto: None,
         }
     }
@@ -2475,7 +2471,7 @@ impl InsertFragmentUseCase {
         uow.begin_transaction()?;
 
         let (result, snapshot) = execute_insert_fragment(&mut uow, dto)?;
-        self.undo_snapshot = Some(snapshot);
+        self.undo_delta = Some(StoreDelta::since(&snapshot, &uow.store())?);
         self.last_dto = Some(dto.clone());
 
         uow.commit()?;
@@ -2485,14 +2481,14 @@ impl InsertFragmentUseCase {
 
 impl UndoRedoCommand for InsertFragmentUseCase {
     fn undo(&mut self) -> Result<()> {
-        let snapshot = self
-            .undo_snapshot
+        let delta = self
+            .undo_delta
             .as_ref()
-            .ok_or_else(|| anyhow!("No snapshot available for undo"))?
-            .clone();
+            .ok_or_else(|| anyhow!("No delta available for undo"))?;
 
         let mut uow = self.uow_factory.create();
         uow.begin_transaction()?;
+        let snapshot = delta.reverted(&uow.store())?;
         uow.restore_document(&snapshot)?;
         uow.commit()?;
         Ok(())
@@ -2508,11 +2504,17 @@ impl UndoRedoCommand for InsertFragmentUseCase {
         let mut uow = self.uow_factory.create();
         uow.begin_transaction()?;
         let (_, snapshot) = execute_insert_fragment(&mut uow, &dto)?;
-        self.undo_snapshot = Some(snapshot);
+        self.undo_delta = Some(StoreDelta::since(&snapshot, &uow.store())?);
         uow.commit()?;
         Ok(())
     }
 
+    fn description(&self) -> String {
+        "Insert fragment".to_string()
+    }
+
+    common::store_delta_undo_methods!(undo_delta);
+
     fn as_any(&self) -> &dyn Any {
         self
     }
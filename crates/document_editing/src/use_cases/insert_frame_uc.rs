@@ -4,6 +4,7 @@ use anyhow::{Result, anyhow};
 use common::database::CommandUnitOfWork;
 use common::database::rope_helpers::block_char_length;
 use common::database::rope_helpers::rope_append_empty_block;
+use common::database::store_delta::StoreDelta;
 use common::direct_access::document::document_repository::DocumentRelationshipField;
 use common::direct_access::frame::frame_repository::FrameRelationshipField;
 use common::direct_access::root::root_repository::RootRelationshipField;
@@ -34,7 +35,7 @@ pub trait InsertFrameUnitOfWorkTrait: CommandUnitOfWork {}
 
 pub struct InsertFrameUseCase {
     uow_factory: Box<dyn InsertFrameUnitOfWorkFactoryTrait>,
-    undo_snapshot: Option<EntityTreeSnapshot>,
+    undo_delta: Option<StoreDelta>,
     last_dto: Option<InsertFrameDto>,
 }
 
@@ -212,7 +213,7 @@ impl InsertFrameUseCase {
     pub fn new(uow_factory: Box<dyn InsertFrameUnitOfWorkFactoryTrait>) -> Self {
         InsertFrameUseCase {
             uow_factory,
-            undo_snapshot: None,
+            undo_delta: None,
             last_dto: None,
         }
     }
@@ -222,7 +223,7 @@ impl InsertFrameUseCase {
         uow.begin_transaction()?;
 
         let (result, snapshot) = execute_insert_frame(&mut uow, dto)?;
-        self.undo_snapshot = Some(snapshot);
+        self.undo_delta = Some(StoreDelta::since(&snapshot, &uow.store())?);
         self.last_dto = Some(dto.clone());
 
         uow.commit()?;
@@ -232,14 +233,14 @@ impl InsertFrameUseCase {
 
 impl UndoRedoCommand for InsertFrameUseCase {
     fn undo(&mut self) -> Result<()> {
-        let snapshot = self
-            .undo_snapshot
+        let delta = self
+            .undo_delta
             .as_ref()
-            .ok_or_else(|| anyhow!("No snapshot available for undo"))?
-            .clone();
+            .ok_or_else(|| anyhow!("No delta available for undo"))?;
 
         let mut uow = self.uow_factory.create();
         uow.begin_transaction()?;
+        let snapshot = delta.reverted(&uow.store())?;
         uow.restore_document(&snapshot)?;
         uow.commit()?;
         Ok(())
@@ -255,11 +256,17 @@ impl UndoRedoCommand for InsertFrameUseCase {
         let mut uow = self.uow_factory.create();
         uow.begin_transaction()?;
         let (_, snapshot) = execute_insert_frame(&mut uow, &dto)?;
-        self.undo_snapshot = Some(snapshot);
+        self.undo_delta = Some(StoreDelta::since(&snapshot, &uow.store())?);
         uow.commit()?;
         Ok(())
     }
 
+    fn description(&self) -> String {
+        "Insert frame".to_string()
+    }
+
+    common::store_delta_undo_methods!(undo_delta);
+
     fn as_any(&self) -> &dyn Any {
         self
     }
@@ -16,6 +16,7 @@ use common::format_runs::{
     shift_runs_for_insert, splice_range, split_images_at, split_runs_at,
 };
 
+use common::database::store_delta::StoreDelta;
 use common::parser_tools::content_parser::{self, ParsedBlock, format_runs_from_spans};
 use common::parser_tools::list_grouper::ListGrouper;
 use common::snapshot::EntityTreeSnapshot;
@@ -939,7 +940,7 @@ fn execute_insert_html(
 
 pub struct InsertHtmlAtPositionUseCase {
     uow_factory: Box<dyn InsertHtmlAtPositionUnitOfWorkFactoryTrait>,
-    undo_snapshot: Option<EntityTreeSnapshot>,
+    undo_delta: Option<StoreDelta>,
     last_dto: Option<InsertHtmlAtPositionDto>,
 }
 
@@ -947,7 +948,7 @@ impl InsertHtmlAtPositionUseCase {
     pub fn new(uow_factory: Box<dyn InsertHtmlAtPositionUnitOfWorkFactoryTrait>) -> Self {
         InsertHtmlAtPositionUseCase {
             uow_factory,
-            undo_snapshot: None,
+            undo_delta: None,
             last_dto: None,
         }
     }
@@ -960,7 +961,7 @@ impl InsertHtmlAtPositionUseCase {
         uow.begin_transaction()?;
 
         let (result, snapshot) = execute_insert_html(&mut uow, dto)?;
-        self.undo_snapshot = Some(snapshot);
+        self.undo_delta = Some(StoreDelta::since(&snapshot, &uow.store())?);
         self.last_dto = Some(dto.clone());
 
         uow.commit()?;
@@ -970,14 +971,14 @@ impl InsertHtmlAtPositionUseCase {
 
 impl UndoRedoCommand for InsertHtmlAtPositionUseCase {
     fn undo(&mut self) -> Result<()> {
-        let snapshot = self
-            .undo_snapshot
+        let delta = self
+            .undo_delta
             .as_ref()
-            .ok_or_else(|| anyhow!("No snapshot available for undo"))?
-            .clone();
+            .ok_or_else(|| anyhow!("No delta available for undo"))?;
 
         let mut uow = self.uow_factory.create();
         uow.begin_transaction()?;
+        let snapshot = delta.reverted(&uow.store())?;
         uow.restore_document(&snapshot)?;
         uow.commit()?;
         Ok(())
@@ -993,11 +994,17 @@ impl UndoRedoCommand for InsertHtmlAtPositionUseCase {
         let mut uow = self.uow_factory.create();
         uow.begin_transaction()?;
         let (_, snapshot) = execute_insert_html(&mut uow, &dto)?;
-        self.undo_snapshot = Some(snapshot);
+        self.undo_delta = Some(StoreDelta::since(&snapshot, &uow.store())?);
         uow.commit()?;
         Ok(())
     }
 
+    fn description(&self) -> String {
+        "Insert HTML".to_string()
+    }
+
+    common::store_delta_undo_methods!(undo_delta);
+
     fn as_any(&self) -> &dyn Any {
         self
     }
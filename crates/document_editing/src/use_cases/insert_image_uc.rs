@@ -4,11 +4,14 @@ use crate::InsertImageResultDto;
 use anyhow::{Result, anyhow};
 use common::database::CommandUnitOfWork;
 use common::database::rope_helpers::{block_content_via_store, rope_insert_in_block};
+use common::database::store_delta::StoreDelta;
 use common::direct_access::document::document_repository::DocumentRelationshipField;
 use common::direct_access::frame::frame_repository::FrameRelationshipField;
 use common::direct_access::root::root_repository::RootRelationshipField;
 use common::entities::{Block, Document, Frame, Root};
-use common::format_runs::{ImageAnchor, InlineContent, synth_element_id};
+use common::format_runs::{
+    ImageAnchor, InlineContent, shift_images_for_insert, shift_runs_for_insert, synth_element_id,
+};
 use common::format_runs_query::inline_segments_for_block;
 use common::snapshot::EntityTreeSnapshot;
 use common::types::{EntityId, ROOT_ENTITY_ID};
@@ -37,7 +40,7 @@ pub trait InsertImageUnitOfWorkTrait: CommandUnitOfWork {}
 
 pub struct InsertImageUseCase {
     uow_factory: Box<dyn InsertImageUnitOfWorkFactoryTrait>,
-    undo_snapshot: Option<EntityTreeSnapshot>,
+    undo_delta: Option<StoreDelta>,
     last_dto: Option<InsertImageDto>,
 }
 
@@ -129,13 +132,26 @@ fn execute_insert_image(
 
     let now = chrono::Utc::now();
 
+    // The sentinel inserted below occupies bytes in the block's content:
+    // runs and anchors at or past the offset move with it, and a run
+    // straddling the offset keeps covering the text on both sides.
+    let sentinel_len = '\u{FFFC}'.len_utf8() as u32;
+    {
+        let store = uow.store();
+        let mut runs_map = store.format_runs.write().unwrap();
+        if let Some(runs) = runs_map.get_mut(&block.id) {
+            shift_runs_for_insert(runs, byte_offset, sentinel_len);
+        }
+    }
+
     // Insert ImageAnchor directly into block_images, maintaining sort order
-    // (ascending by byte_offset; equal byte_offsets keep insertion order, so
-    // the new image goes AFTER any existing anchors at the same byte position).
+    // (ascending by byte_offset). Existing anchors at the same byte position
+    // were shifted past the new sentinel, so the new image goes before them.
     {
         let store = uow.store();
         let mut images_map = store.block_images.write().unwrap();
         let images = images_map.entry(block.id).or_default();
+        shift_images_for_insert(images, byte_offset, sentinel_len);
         let insert_idx = images
             .iter()
             .position(|a| a.byte_offset > byte_offset)
@@ -199,7 +215,7 @@ impl InsertImageUseCase {
     pub fn new(uow_factory: Box<dyn InsertImageUnitOfWorkFactoryTrait>) -> Self {
         InsertImageUseCase {
             uow_factory,
-            undo_snapshot: None,
+            undo_delta: None,
             last_dto: None,
         }
     }
@@ -209,7 +225,7 @@ impl InsertImageUseCase {
         uow.begin_transaction()?;
 
         let (result, snapshot) = execute_insert_image(&mut uow, dto)?;
-        self.undo_snapshot = Some(snapshot);
+        self.undo_delta = Some(StoreDelta::since(&snapshot, &uow.store())?);
         self.last_dto = Some(dto.clone());
 
         uow.commit()?;
@@ -219,14 +235,14 @@ impl InsertImageUseCase {
 
 impl UndoRedoCommand for InsertImageUseCase {
     fn undo(&mut self) -> Result<()> {
-        let snapshot = self
-            .undo_snapshot
+        let delta = self
+            .undo_delta
             .as_ref()
-            .ok_or_else(|| anyhow!("No snapshot available for undo"))?
-            .clone();
+            .ok_or_else(|| anyhow!("No delta available for undo"))?;
 
         let mut uow = self.uow_factory.create();
         uow.begin_transaction()?;
+        let snapshot = delta.reverted(&uow.store())?;
         uow.restore_document(&snapshot)?;
         uow.commit()?;
         Ok(())
@@ -242,11 +258,17 @@ impl UndoRedoCommand for InsertImageUseCase {
         let mut uow = self.uow_factory.create();
         uow.begin_transaction()?;
         let (_, snapshot) = execute_insert_image(&mut uow, &dto)?;
-        self.undo_snapshot = Some(snapshot);
+        self.undo_delta = Some(StoreDelta::since(&snapshot, &uow.store())?);
         uow.commit()?;
         Ok(())
     }
 
+    fn description(&self) -> String {
+        "Insert image".to_string()
+    }
+
+    common::store_delta_undo_methods!(undo_delta);
+
     fn as_any(&self) -> &dyn Any {
         self
     }
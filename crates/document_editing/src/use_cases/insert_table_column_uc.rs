@@ -7,6 +7,7 @@ use crate::InsertTableColumnResultDto;
 use anyhow::{Result, anyhow};
 use common::database::CommandUnitOfWork;
 use common::database::rope_helpers::{rope_insert_block_at, top_level_frame_end_byte};
+use common::database::store_delta::StoreDelta;
 use common::direct_access::document::document_repository::DocumentRelationshipField;
 use common::direct_access::frame::frame_repository::FrameRelationshipField;
 use common::direct_access::root::root_repository::RootRelationshipField;
@@ -47,7 +48,7 @@ impl_cell_block_reader!(dyn InsertTableColumnUnitOfWorkTrait);
 
 pub struct InsertTableColumnUseCase {
     uow_factory: Box<dyn InsertTableColumnUnitOfWorkFactoryTrait>,
-    undo_snapshot: Option<EntityTreeSnapshot>,
+    undo_delta: Option<StoreDelta>,
     last_dto: Option<InsertTableColumnDto>,
 }
 
@@ -112,12 +113,22 @@ fn execute_insert_table_column(
         uow.update_table_cell_multi(&cells_to_update)?;
     }
 
+    // The table's anchor frame, parent of the new cell frames.
+    let anchor_frame = {
+        let store = uow.store();
+        let frames = store.frames.read().unwrap();
+        frames
+            .values()
+            .find(|f| f.table == Some(table_id))
+            .map(|f| f.id)
+    };
     // Create new cells for the inserted column (one per row), collecting
     // their blocks so we can mirror them into the rope after the entity
     // graph is in place.
     let mut new_cell_blocks: Vec<Block> = Vec::with_capacity(table.rows as usize);
     for r in 0..table.rows {
-        let (cell_frame_id, created_block) = create_cell_frame(&mut *uow, doc_id, now)?;
+        let (cell_frame_id, created_block) =
+            create_cell_frame(&mut *uow, doc_id, anchor_frame, now)?;
         new_cell_blocks.push(created_block);
 
         let cell = TableCell {
@@ -175,7 +186,7 @@ fn execute_insert_table_column(
 
     // Assign positions in row-major order (handles multi-block cells)
     let (cell_blocks_to_update, _) =
-        reassign_cell_block_positions(&*uow, &all_cells, base_pos, now)?;
+        reassign_cell_block_positions(&*uow, &all_cells, base_pos, now, &uow.store())?;
     if !cell_blocks_to_update.is_empty() {
         uow.update_block_multi(&cell_blocks_to_update)?;
     }
@@ -225,7 +236,7 @@ impl InsertTableColumnUseCase {
     pub fn new(uow_factory: Box<dyn InsertTableColumnUnitOfWorkFactoryTrait>) -> Self {
         InsertTableColumnUseCase {
             uow_factory,
-            undo_snapshot: None,
+            undo_delta: None,
             last_dto: None,
         }
     }
@@ -234,7 +245,7 @@ impl InsertTableColumnUseCase {
         let mut uow = self.uow_factory.create();
         uow.begin_transaction()?;
         let (result, snapshot) = execute_insert_table_column(&mut uow, dto)?;
-        self.undo_snapshot = Some(snapshot);
+        self.undo_delta = Some(StoreDelta::since(&snapshot, &uow.store())?);
         self.last_dto = Some(dto.clone());
         uow.commit()?;
         Ok(result)
@@ -243,13 +254,13 @@ impl InsertTableColumnUseCase {
 
 impl UndoRedoCommand for InsertTableColumnUseCase {
     fn undo(&mut self) -> Result<()> {
-        let snapshot = self
-            .undo_snapshot
+        let delta = self
+            .undo_delta
             .as_ref()
-            .ok_or_else(|| anyhow!("No snapshot for undo"))?
-            .clone();
+            .ok_or_else(|| anyhow!("No delta for undo"))?;
         let mut uow = self.uow_factory.create();
         uow.begin_transaction()?;
+        let snapshot = delta.reverted(&uow.store())?;
         uow.restore_document(&snapshot)?;
         uow.commit()?;
         Ok(())
@@ -264,11 +275,17 @@ impl UndoRedoCommand for InsertTableColumnUseCase {
         let mut uow = self.uow_factory.create();
         uow.begin_transaction()?;
         let (_, snapshot) = execute_insert_table_column(&mut uow, &dto)?;
-        self.undo_snapshot = Some(snapshot);
+        self.undo_delta = Some(StoreDelta::since(&snapshot, &uow.store())?);
         uow.commit()?;
         Ok(())
     }
 
+    fn description(&self) -> String {
+        "Insert table column".to_string()
+    }
+
+    common::store_delta_undo_methods!(undo_delta);
+
     fn as_any(&self) -> &dyn Any {
         self
     }
@@ -2,9 +2,11 @@ use crate::InsertTableDto;
 use crate::InsertTableResultDto;
 use anyhow::{Result, anyhow};
 use common::database::CommandUnitOfWork;
+use common::database::block_offset_index::OffsetMarker;
 use common::database::rope_helpers::{
-    block_char_length, rope_insert_block_at, rope_insert_table_anchor, top_level_frame_end_byte,
+    block_char_length, rope_insert_block_at, rope_insert_table_anchor_at, top_level_frame_end_byte,
 };
+use common::database::store_delta::StoreDelta;
 use common::direct_access::document::document_repository::DocumentRelationshipField;
 use common::direct_access::frame::frame_repository::FrameRelationshipField;
 use common::direct_access::root::root_repository::RootRelationshipField;
@@ -46,7 +48,7 @@ impl_cell_frame_creator!(dyn InsertTableUnitOfWorkTrait);
 
 pub struct InsertTableUseCase {
     uow_factory: Box<dyn InsertTableUnitOfWorkFactoryTrait>,
-    undo_snapshot: Option<EntityTreeSnapshot>,
+    undo_delta: Option<StoreDelta>,
     last_dto: Option<InsertTableDto>,
 }
 
@@ -155,7 +157,7 @@ fn execute_insert_table(
     let (parent_frame_id, child_order_insert_idx, rope_anchor, cell_start_pos): (
         EntityId,
         usize,
-        Option<(EntityId, bool)>,
+        Option<(OffsetMarker, bool)>,
         i64,
     ) = if all_blocks.is_empty() {
         // Empty document — use the first frame. No host block, so the
@@ -227,19 +229,19 @@ fn execute_insert_table(
         let owning_frame = uow
             .get_frame(&found_frame_id)?
             .ok_or_else(|| anyhow!("Owning frame {found_frame_id} not found"))?;
-        let mut cell_anchor: Option<(EntityId, EntityId)> = None;
+        let mut cell_anchor: Option<(EntityId, EntityId, EntityId)> = None;
         if let Some(parent_id) = owning_frame.parent_frame {
             let parent = uow
                 .get_frame(&parent_id)?
                 .ok_or_else(|| anyhow!("Parent frame {parent_id} not found"))?;
-            if parent.table.is_some()
+            if let Some(table_id) = parent.table
                 && let Some(grandparent_id) = parent.parent_frame
             {
-                cell_anchor = Some((parent_id, grandparent_id));
+                cell_anchor = Some((parent_id, grandparent_id, table_id));
             }
         }
 
-        if let Some((anchor_frame_id, grandparent_id)) = cell_anchor {
+        if let Some((anchor_frame_id, grandparent_id, containing_table_id)) = cell_anchor {
             let grandparent = uow
                 .get_frame(&grandparent_id)?
                 .ok_or_else(|| anyhow!("Grandparent frame {grandparent_id} not found"))?;
@@ -284,16 +286,17 @@ fn execute_insert_table(
             (
                 grandparent_id,
                 anchor_idx + 1,
-                // Rope mirror: anchor after target_block (a cell of the
-                // existing table). No-op under the default backend.
-                Some((target_block.id, true)),
+                // Rope mirror: right after the existing table's anchor,
+                // in the flow, not after target_block, whose text lives
+                // with the other cells at the end of the rope.
+                Some((OffsetMarker::TableAnchor(containing_table_id), true)),
                 hoisted_cell_start,
             )
         } else {
             (
                 found_frame_id,
                 found_child_idx + after_idx,
-                Some((target_block.id, after)),
+                Some((OffsetMarker::Block(target_block.id), after)),
                 cell_start,
             )
         }
@@ -324,7 +327,7 @@ fn execute_insert_table(
     for r in 0..dto.rows {
         for c in 0..dto.columns {
             // Create a cell frame with an empty block
-            let (cell_frame_id, created_block) = create_cell_frame(uow, doc_id, now)?;
+            let (cell_frame_id, created_block) = create_cell_frame(uow, doc_id, None, now)?;
 
             cell_blocks.push(created_block);
             cell_frame_ids.push(cell_frame_id);
@@ -409,8 +412,8 @@ fn execute_insert_table(
     // offset index. Cell-internal content is not yet tracked in
     // BlockOffsetIndex — plan §1.6's Frame.byte_range model is a
     // follow-up commit.
-    if let Some((target_block_id, after)) = rope_anchor {
-        rope_insert_table_anchor(&uow.store(), created_table.id, target_block_id, after);
+    if let Some((target, after)) = rope_anchor {
+        rope_insert_table_anchor_at(&uow.store(), created_table.id, target, after);
     }
 
     // 4. Assign document_position to all cell blocks in row-major
@@ -535,7 +538,7 @@ impl InsertTableUseCase {
     pub fn new(uow_factory: Box<dyn InsertTableUnitOfWorkFactoryTrait>) -> Self {
         InsertTableUseCase {
             uow_factory,
-            undo_snapshot: None,
+            undo_delta: None,
             last_dto: None,
         }
     }
@@ -545,7 +548,7 @@ impl InsertTableUseCase {
         uow.begin_transaction()?;
 
         let (result, snapshot) = execute_insert_table(&mut uow, dto)?;
-        self.undo_snapshot = Some(snapshot);
+        self.undo_delta = Some(StoreDelta::since(&snapshot, &uow.store())?);
         self.last_dto = Some(dto.clone());
 
         uow.commit()?;
@@ -555,14 +558,14 @@ impl InsertTableUseCase {
 
 impl UndoRedoCommand for InsertTableUseCase {
     fn undo(&mut self) -> Result<()> {
-        let snapshot = self
-            .undo_snapshot
+        let delta = self
+            .undo_delta
             .as_ref()
-            .ok_or_else(|| anyhow!("No snapshot available for undo"))?
-            .clone();
+            .ok_or_else(|| anyhow!("No delta available for undo"))?;
 
         let mut uow = self.uow_factory.create();
         uow.begin_transaction()?;
+        let snapshot = delta.reverted(&uow.store())?;
         uow.restore_document(&snapshot)?;
         uow.commit()?;
         Ok(())
@@ -578,11 +581,17 @@ impl UndoRedoCommand for InsertTableUseCase {
         let mut uow = self.uow_factory.create();
         uow.begin_transaction()?;
         let (_, snapshot) = execute_insert_table(&mut uow, &dto)?;
-        self.undo_snapshot = Some(snapshot);
+        self.undo_delta = Some(StoreDelta::since(&snapshot, &uow.store())?);
         uow.commit()?;
         Ok(())
     }
 
+    fn description(&self) -> String {
+        "Insert table".to_string()
+    }
+
+    common::store_delta_undo_methods!(undo_delta);
+
     fn as_any(&self) -> &dyn Any {
         self
     }
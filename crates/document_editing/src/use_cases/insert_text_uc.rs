@@ -3,6 +3,8 @@ use crate::InsertTextDto;
 use crate::InsertTextResultDto;
 use anyhow::{Result, anyhow};
 use common::database::CommandUnitOfWork;
+use common::database::block_delta::BlockDelta;
+use common::database::edit_record::EditRecord;
 use common::database::rope_helpers::{
     block_char_length, block_content_via_store, find_block_at_char_position, rope_delete_in_block,
     rope_insert_in_block,
@@ -13,8 +15,8 @@ use common::direct_access::root::root_repository::RootRelationshipField;
 use common::direct_access::table::TableRelationshipField;
 use common::entities::{Block, Document, Frame, Root, TableCell};
 use common::format_runs::{
-    FormatRun, ImageAnchor, debug_assert_well_formed, logical_offset_to_byte,
-    shift_images_for_delete, shift_images_for_insert, shift_runs_for_delete, shift_runs_for_insert,
+    debug_assert_well_formed, logical_offset_to_byte, shift_images_for_delete,
+    shift_images_for_insert, shift_runs_for_delete, shift_runs_for_insert,
 };
 
 use common::types::{EntityId, ROOT_ENTITY_ID};
@@ -31,8 +33,6 @@ pub trait InsertTextUnitOfWorkFactoryTrait: Send + Sync {
 #[macros::uow_action(entity = "Document", action = "Get")]
 #[macros::uow_action(entity = "Document", action = "Update")]
 #[macros::uow_action(entity = "Document", action = "GetRelationship")]
-#[macros::uow_action(entity = "Document", action = "Snapshot")]
-#[macros::uow_action(entity = "Document", action = "Restore")]
 #[macros::uow_action(entity = "Frame", action = "Get")]
 #[macros::uow_action(entity = "Frame", action = "GetRelationship")]
 #[macros::uow_action(entity = "Block", action = "Get")]
@@ -44,25 +44,24 @@ pub trait InsertTextUnitOfWorkFactoryTrait: Send + Sync {
 #[macros::uow_action(entity = "TableCell", action = "GetMulti")]
 pub trait InsertTextUnitOfWorkTrait: CommandUnitOfWork {}
 
-/// Lightweight undo data for the no-selection insert path. The cloned
-/// format_runs / block_images vectors serve as a per-block backup so
-/// undo can restore the run table verbatim.
-struct UndoData {
-    block_id: EntityId,
-    original_block: Block,
-    original_format_runs: Vec<FormatRun>,
-    original_block_images: Vec<ImageAnchor>,
-    doc_id: EntityId,
-    original_character_count: i64,
-    /// Byte range in the block where the text was inserted. Used by
-    /// undo to delete those bytes from the rope.
-    inserted_byte_offset: u32,
-    inserted_byte_len: u32,
-}
-
-enum InsertTextUndo {
-    Simple(Box<UndoData>),
-    SelectionReplacement(common::snapshot::EntityTreeSnapshot),
+/// Byte range in `block` of the logical character range
+/// `[start_offset..end_offset)`.
+fn block_byte_range(
+    store: &common::database::Store,
+    block: &Block,
+    start_offset: i64,
+    end_offset: i64,
+) -> std::ops::Range<u32> {
+    let images = store
+        .block_images
+        .read()
+        .unwrap()
+        .get(&block.id)
+        .cloned()
+        .unwrap_or_default();
+    let block_text = block_content_via_store(block, store);
+    logical_offset_to_byte(&block_text, &images, start_offset)
+        ..logical_offset_to_byte(&block_text, &images, end_offset)
 }
 
 /// Delete a logical character range `[start_offset..end_offset)` inside a
@@ -80,17 +79,11 @@ fn delete_range_in_block(
     }
 
     let store = uow.store();
-    let images_before = store
-        .block_images
-        .read()
-        .unwrap()
-        .get(&block.id)
-        .cloned()
-        .unwrap_or_default();
-
     let block_text = block_content_via_store(block, &store);
-    let byte_start = logical_offset_to_byte(&block_text, &images_before, start_offset);
-    let byte_end = logical_offset_to_byte(&block_text, &images_before, end_offset);
+    let std::ops::Range {
+        start: byte_start,
+        end: byte_end,
+    } = block_byte_range(&store, block, start_offset, end_offset);
 
     let removed_text_chars = block_text[byte_start as usize..byte_end as usize]
         .chars()
@@ -129,7 +122,7 @@ fn delete_range_in_block(
 fn execute_insert_with_selection(
     uow: &mut Box<dyn InsertTextUnitOfWorkTrait>,
     dto: &InsertTextDto,
-) -> Result<(InsertTextResultDto, InsertTextUndo)> {
+) -> Result<(InsertTextResultDto, BlockDelta)> {
     let root = uow
         .get_root(&ROOT_ENTITY_ID)?
         .ok_or_else(|| anyhow!("Root entity not found"))?;
@@ -142,7 +135,46 @@ fn execute_insert_with_selection(
         .get_document(&doc_id)?
         .ok_or_else(|| anyhow!("Document not found"))?;
 
-    let snapshot = uow.snapshot_document(&[doc_id])?;
+    let sel_start = std::cmp::min(dto.position, dto.anchor);
+    let sel_end = std::cmp::max(dto.position, dto.anchor);
+    let position = sel_start;
+
+    // Fast path: in a flat document the rope index locates both ends in
+    // O(log n). Delete the selection, then reuse the plain insert path.
+    let store = uow.store();
+    if common::database::rope_helpers::rope_positions_match_flow(&store)
+        && let (Some((block_id, start_offset, _)), Some((end_block_id, end_offset, _))) = (
+            find_block_at_char_position(&store, sel_start),
+            find_block_at_char_position(&store, sel_end),
+        )
+        && block_id == end_block_id
+    {
+        let block = uow
+            .get_block(&block_id)?
+            .ok_or_else(|| anyhow!("Block not found"))?;
+        let mut delta = BlockDelta::capture(
+            &store,
+            &block,
+            doc_id,
+            document.character_count,
+            block_byte_range(&store, &block, start_offset, end_offset),
+            0,
+        );
+        let chars_removed = delete_range_in_block(uow, &block, start_offset, end_offset)?;
+        document.character_count -= chars_removed;
+        document.updated_at = chrono::Utc::now();
+        uow.update_document(&document)?;
+
+        let insert_dto = InsertTextDto {
+            position,
+            anchor: position,
+            text: dto.text.clone(),
+        };
+        let (result, inserted) = execute_insert_simple(uow, &insert_dto)?;
+        delta.absorb(&inserted);
+        return Ok((result, delta));
+    }
+    drop(store);
 
     let frame_ids = uow.get_document_relationship(&doc_id, &DocumentRelationshipField::Frames)?;
     let frame_id = *frame_ids
@@ -166,10 +198,6 @@ fn execute_insert_with_selection(
     let mut blocks: Vec<Block> = blocks_opt.into_iter().flatten().collect();
     blocks.sort_by_key(|b| b.document_position);
 
-    let sel_start = std::cmp::min(dto.position, dto.anchor);
-    let sel_end = std::cmp::max(dto.position, dto.anchor);
-    let position = sel_start;
-
     let (sel_block, sel_block_idx, sel_start_offset) =
         super::editing_helpers::find_block_at_position(&blocks, sel_start, &uow.store())?;
     let (_, sel_end_block_idx, sel_end_offset) =
@@ -182,32 +210,57 @@ fn execute_insert_with_selection(
         ));
     }
 
+    let store = uow.store();
+    let delta = BlockDelta::capture(
+        &store,
+        &blocks[sel_block_idx],
+        doc_id,
+        document.character_count,
+        block_byte_range(&store, &sel_block, sel_start_offset, sel_end_offset),
+        dto.text.len() as u32,
+    );
+    drop(store);
+    // Stored positions of later blocks only need maintaining when the
+    // rope can't be the source of truth (see `execute_insert_simple`).
+    let later_blocks: Vec<&Block> =
+        if common::database::rope_helpers::rope_positions_match_flow(&uow.store()) {
+            Vec::new()
+        } else {
+            blocks
+                .iter()
+                .filter(|b| {
+                    b.id != sel_block.id && b.document_position > sel_block.document_position
+                })
+                .collect()
+        };
+
     let chars_removed = delete_range_in_block(uow, &sel_block, sel_start_offset, sel_end_offset)?;
 
     document.character_count -= chars_removed;
     document.updated_at = chrono::Utc::now();
     uow.update_document(&document)?;
 
-    let mut to_update = Vec::new();
-    for b in &blocks[(sel_block_idx + 1)..] {
-        let mut ub = b.clone();
-        ub.document_position -= chars_removed;
-        ub.updated_at = chrono::Utc::now();
-        to_update.push(ub);
-    }
-    if !to_update.is_empty() {
+    let net_chars = dto.text.chars().count() as i64 - chars_removed;
+    if net_chars != 0 && !later_blocks.is_empty() {
+        let to_update: Vec<Block> = later_blocks
+            .iter()
+            .map(|b| {
+                let mut ub = (*b).clone();
+                ub.document_position += net_chars;
+                ub.updated_at = chrono::Utc::now();
+                ub
+            })
+            .collect();
         uow.update_block_multi(&to_update)?;
     }
 
-    let blocks_opt = uow.get_block_multi(&all_block_ids)?;
-    blocks = blocks_opt.into_iter().flatten().collect();
-    blocks.sort_by_key(|b| b.document_position);
-    document = uow
-        .get_document(&doc_id)?
-        .ok_or_else(|| anyhow!("Document not found"))?;
-
-    let (block, block_idx, offset) =
-        super::editing_helpers::find_block_at_position(&blocks, position, &uow.store())?;
+    // Insert where the selection started. Looking the position up again
+    // could land in another block sharing it (an empty table cell), which
+    // the undo delta doesn't cover.
+    let block = uow
+        .get_block(&sel_block.id)?
+        .ok_or_else(|| anyhow!("Block not found"))?;
+    let offset = sel_start_offset;
 
     let store = uow.store();
     let images = store
@@ -244,17 +297,6 @@ fn execute_insert_with_selection(
 
     rope_insert_in_block(&store, block.id, byte_offset, &dto.text);
 
-    let mut blocks_to_update: Vec<Block> = Vec::new();
-    for b in &blocks[(block_idx + 1)..] {
-        let mut ub = b.clone();
-        ub.document_position += inserted_char_len;
-        ub.updated_at = chrono::Utc::now();
-        blocks_to_update.push(ub);
-    }
-    if !blocks_to_update.is_empty() {
-        uow.update_block_multi(&blocks_to_update)?;
-    }
-
     let mut updated_doc = document.clone();
     updated_doc.character_count += inserted_char_len;
     updated_doc.updated_at = chrono::Utc::now();
@@ -265,14 +307,14 @@ fn execute_insert_with_selection(
             new_position: block.document_position + offset + inserted_char_len,
             blocks_affected: 1,
         },
-        InsertTextUndo::SelectionReplacement(snapshot),
+        delta,
     ))
 }
 
 fn execute_insert_simple(
     uow: &mut Box<dyn InsertTextUnitOfWorkTrait>,
     dto: &InsertTextDto,
-) -> Result<(InsertTextResultDto, InsertTextUndo)> {
+) -> Result<(InsertTextResultDto, BlockDelta)> {
     let position = dto.position;
 
     let root = uow
@@ -332,26 +374,18 @@ fn execute_insert_simple(
         }
     };
 
-    let original_block = block.clone();
-    let original_format_runs = store
-        .format_runs
-        .read()
-        .unwrap()
-        .get(&block.id)
-        .cloned()
-        .unwrap_or_default();
-    let original_block_images = store
-        .block_images
-        .read()
-        .unwrap()
-        .get(&block.id)
-        .cloned()
-        .unwrap_or_default();
-
-    let block_text = block_content_via_store(&block, &store);
-    let byte_offset = logical_offset_to_byte(&block_text, &original_block_images, offset);
+    let byte_offset = block_byte_range(&store, &block, offset, offset).start;
     let inserted_byte_len = dto.text.len() as u32;
     let inserted_char_len = dto.text.chars().count() as i64;
+    let original = BlockDelta::capture(
+        &store,
+        &block,
+        doc_id,
+        document.character_count,
+        byte_offset..byte_offset,
+        inserted_byte_len,
+    );
+    let block_text = block_content_via_store(&block, &store);
 
     let mut new_plain = block_text.clone();
     new_plain.insert_str(byte_offset as usize, &dto.text);
@@ -413,23 +447,12 @@ fn execute_insert_simple(
     updated_doc.updated_at = chrono::Utc::now();
     uow.update_document(&updated_doc)?;
 
-    let undo_data = UndoData {
-        block_id: block.id,
-        original_block,
-        original_format_runs,
-        original_block_images,
-        doc_id,
-        original_character_count: document.character_count,
-        inserted_byte_offset: byte_offset,
-        inserted_byte_len,
-    };
-
     Ok((
         InsertTextResultDto {
             new_position: block_pos + offset + inserted_char_len,
             blocks_affected: 1,
         },
-        InsertTextUndo::Simple(Box::new(undo_data)),
+        original,
     ))
 }
 
@@ -476,7 +499,12 @@ fn find_block_at_position_sequential(
 
 pub struct InsertTextUseCase {
     uow_factory: Box<dyn InsertTextUnitOfWorkFactoryTrait>,
-    undo_data: Option<InsertTextUndo>,
+    /// The edited block's prior text, runs and images. Keystrokes
+    /// merged into this command widen its splice.
+    undo_data: Option<BlockDelta>,
+    /// The edited block as it was just before the last undo, for
+    /// [`UndoRedoCommand::edit_records`] while the command is undone.
+    redo_delta: Option<BlockDelta>,
     last_dto: Option<InsertTextDto>,
     last_result: Option<InsertTextResultDto>,
     last_merge_time: Option<Instant>,
@@ -488,6 +516,7 @@ impl InsertTextUseCase {
         InsertTextUseCase {
             uow_factory,
             undo_data: None,
+            redo_delta: None,
             last_dto: None,
             last_result: None,
             last_merge_time: None,
@@ -507,6 +536,7 @@ impl InsertTextUseCase {
         };
 
         self.undo_data = Some(undo);
+        self.redo_delta = None;
         self.last_dto = Some(dto.clone());
         self.last_result = Some(result.clone());
         self.last_merge_time = Some(Instant::now());
@@ -526,46 +556,41 @@ impl UndoRedoCommand for InsertTextUseCase {
 
         let mut uow = self.uow_factory.create();
         uow.begin_transaction()?;
-
-        match undo {
-            InsertTextUndo::SelectionReplacement(snapshot) => {
-                uow.restore_document(&snapshot.clone())?;
-            }
-            InsertTextUndo::Simple(data) => {
-                uow.update_block(&data.original_block)?;
-
-                let store = uow.store();
-                store
-                    .format_runs
-                    .write()
-                    .unwrap()
-                    .insert(data.block_id, data.original_format_runs.clone());
-                store
-                    .block_images
-                    .write()
-                    .unwrap()
-                    .insert(data.block_id, data.original_block_images.clone());
-
-                // Revert the rope mutation done by the forward path.
-                if data.inserted_byte_len > 0 {
-                    rope_delete_in_block(
-                        &store,
-                        data.block_id,
-                        data.inserted_byte_offset,
-                        data.inserted_byte_offset + data.inserted_byte_len,
-                    );
-                }
-
-                let mut doc = uow
-                    .get_document(&data.doc_id)?
-                    .ok_or_else(|| anyhow!("Document not found"))?;
-                doc.character_count = data.original_character_count;
-                doc.updated_at = chrono::Utc::now();
-                uow.update_document(&doc)?;
-            }
+        let redo_delta = undo.recapture(&uow.store());
+        let added = undo.chars_added(&uow.store());
+        let later = undo.blocks_after(&uow.store());
+        undo.restore_content(&uow.store());
+
+        // Move later blocks and the character count back by what the
+        // edit changed rather than resetting them, and keep the block's
+        // current position: edits made since on other undo stacks may
+        // have moved them.
+        let mut block = undo.block.clone();
+        if let Some(current) = uow.get_block(&block.id)? {
+            block.document_position = current.document_position;
+        }
+        uow.update_block(&block)?;
+        if !later.is_empty() {
+            let shifted: Vec<Block> = uow
+                .get_block_multi(&later)?
+                .into_iter()
+                .flatten()
+                .map(|mut b| {
+                    b.document_position -= added;
+                    b
+                })
+                .collect();
+            uow.update_block_multi(&shifted)?;
         }
+        let mut doc = uow
+            .get_document(&undo.doc_id)?
+            .ok_or_else(|| anyhow!("Document not found"))?;
+        doc.character_count -= added;
+        doc.updated_at = chrono::Utc::now();
+        uow.update_document(&doc)?;
 
         uow.commit()?;
+        self.redo_delta = Some(redo_delta);
         Ok(())
     }
 
@@ -585,6 +610,7 @@ impl UndoRedoCommand for InsertTextUseCase {
             execute_insert_simple(&mut uow, &dto)?
         };
         self.undo_data = Some(undo);
+        self.redo_delta = None;
         uow.commit()?;
         Ok(())
     }
@@ -612,6 +638,12 @@ impl UndoRedoCommand for InsertTextUseCase {
             return false;
         }
 
+        // The merged undo only restores this command's block.
+        match (&self.undo_data, &other_cmd.undo_data) {
+            (Some(self_undo), Some(other_undo)) if self_undo.block.id == other_undo.block.id => {}
+            _ => return false,
+        }
+
         if other_dto.position != self_result.new_position {
             return false;
         }
@@ -641,6 +673,17 @@ impl UndoRedoCommand for InsertTextUseCase {
             return false;
         };
 
+        // Widen this command's splice over the other's so a single undo
+        // reverts all merged keystrokes. can_merge() only lets through
+        // inserts that continue this one in the same block.
+        let (Some(self_undo), Some(other_undo)) = (&mut self.undo_data, &other_cmd.undo_data)
+        else {
+            return false;
+        };
+        if !self_undo.absorb(other_undo) {
+            return false;
+        }
+
         if let (Some(self_dto), Some(other_dto)) = (&mut self.last_dto, &other_cmd.last_dto) {
             self_dto.text.push_str(&other_dto.text);
             self_dto.anchor = self_dto.position;
@@ -650,21 +693,37 @@ impl UndoRedoCommand for InsertTextUseCase {
         }
         self.last_merge_time = other_cmd.last_merge_time;
 
-        // Extend the combined insertion length so a single undo reverts
-        // all merged keystrokes (not just the first one). The merge
-        // criteria in can_merge() guarantee both inserts are contiguous
-        // and in the same block, so widening the existing
-        // (inserted_byte_offset, inserted_byte_len) span by the other's
-        // length captures the combined effect.
-        if let (Some(InsertTextUndo::Simple(self_undo)), Some(InsertTextUndo::Simple(other_undo))) =
-            (&mut self.undo_data, &other_cmd.undo_data)
-        {
-            self_undo.inserted_byte_len += other_undo.inserted_byte_len;
-        }
-
         true
     }
 
+    fn affected_blocks(&self) -> Option<Vec<EntityId>> {
+        Some(vec![self.undo_data.as_ref()?.block.id])
+    }
+
+    fn description(&self) -> String {
+        "Typing".to_string()
+    }
+
+    fn approximate_size(&self) -> usize {
+        std::mem::size_of_val(self)
+            + self
+                .undo_data
+                .as_ref()
+                .map_or(0, BlockDelta::approximate_size)
+            + self
+                .redo_delta
+                .as_ref()
+                .map_or(0, BlockDelta::approximate_size)
+    }
+
+    fn edit_records(&self) -> Option<Vec<EditRecord>> {
+        let delta = match &self.redo_delta {
+            Some(delta) => delta,
+            None => self.undo_data.as_ref()?,
+        };
+        Some(vec![EditRecord::Block(delta.clone())])
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
@@ -1,6 +1,7 @@
 use crate::RemoveBlockFromListDto;
 use anyhow::{Result, anyhow};
 use common::database::CommandUnitOfWork;
+use common::database::store_delta::StoreDelta;
 use common::direct_access::block::block_repository::BlockRelationshipField;
 use common::direct_access::root::root_repository::RootRelationshipField;
 use common::entities::{Block, Document, List, Root};
@@ -71,7 +72,7 @@ fn execute_remove_block_from_list(
 
 pub struct RemoveBlockFromListUseCase {
     uow_factory: Box<dyn RemoveBlockFromListUnitOfWorkFactoryTrait>,
-    undo_snapshot: Option<EntityTreeSnapshot>,
+    undo_delta: Option<StoreDelta>,
     last_dto: Option<RemoveBlockFromListDto>,
 }
 
@@ -79,7 +80,7 @@ impl RemoveBlockFromListUseCase {
     pub fn new(uow_factory: Box<dyn RemoveBlockFromListUnitOfWorkFactoryTrait>) -> Self {
         RemoveBlockFromListUseCase {
             uow_factory,
-            undo_snapshot: None,
+            undo_delta: None,
             last_dto: None,
         }
     }
@@ -89,7 +90,7 @@ impl RemoveBlockFromListUseCase {
         uow.begin_transaction()?;
 
         let snapshot = execute_remove_block_from_list(&mut uow, dto)?;
-        self.undo_snapshot = Some(snapshot);
+        self.undo_delta = Some(StoreDelta::since(&snapshot, &uow.store())?);
         self.last_dto = Some(dto.clone());
 
         uow.commit()?;
@@ -99,14 +100,14 @@ impl RemoveBlockFromListUseCase {
 
 impl UndoRedoCommand for RemoveBlockFromListUseCase {
     fn undo(&mut self) -> Result<()> {
-        let snapshot = self
-            .undo_snapshot
+        let delta = self
+            .undo_delta
             .as_ref()
-            .ok_or_else(|| anyhow!("No snapshot available for undo"))?
-            .clone();
+            .ok_or_else(|| anyhow!("No delta available for undo"))?;
 
         let mut uow = self.uow_factory.create();
         uow.begin_transaction()?;
+        let snapshot = delta.reverted(&uow.store())?;
         uow.restore_document(&snapshot)?;
         uow.commit()?;
         Ok(())
@@ -122,11 +123,17 @@ impl UndoRedoCommand for RemoveBlockFromListUseCase {
         let mut uow = self.uow_factory.create();
         uow.begin_transaction()?;
         let snapshot = execute_remove_block_from_list(&mut uow, &dto)?;
-        self.undo_snapshot = Some(snapshot);
+        self.undo_delta = Some(StoreDelta::since(&snapshot, &uow.store())?);
         uow.commit()?;
         Ok(())
     }
 
+    fn description(&self) -> String {
+        "Remove from list".to_string()
+    }
+
+    common::store_delta_undo_methods!(undo_delta);
+
     fn as_any(&self) -> &dyn Any {
         self
     }
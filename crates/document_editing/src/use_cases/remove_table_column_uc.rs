@@ -5,7 +5,8 @@ use crate::RemoveTableColumnDto;
 use crate::RemoveTableColumnResultDto;
 use anyhow::{Result, anyhow};
 use common::database::CommandUnitOfWork;
-use common::database::rope_helpers::rope_remove_block;
+use common::database::rope_helpers::{block_char_length, rope_remove_block};
+use common::database::store_delta::StoreDelta;
 use common::direct_access::document::document_repository::DocumentRelationshipField;
 use common::direct_access::frame::frame_repository::FrameRelationshipField;
 use common::direct_access::root::root_repository::RootRelationshipField;
@@ -45,7 +46,7 @@ impl_cell_block_reader!(dyn RemoveTableColumnUnitOfWorkTrait);
 
 pub struct RemoveTableColumnUseCase {
     uow_factory: Box<dyn RemoveTableColumnUnitOfWorkFactoryTrait>,
-    undo_snapshot: Option<EntityTreeSnapshot>,
+    undo_delta: Option<StoreDelta>,
     last_dto: Option<RemoveTableColumnDto>,
 }
 
@@ -144,6 +145,13 @@ fn execute_remove_table_column(
         let bids = uow.get_frame_relationship(fid, &FrameRelationshipField::Blocks)?;
         removed_cell_block_ids.extend(bids);
     }
+    // Positions the removed blocks take, read before the rope forgets them.
+    let removed_positions: i64 = uow
+        .get_block_multi(&removed_cell_block_ids)?
+        .iter()
+        .flatten()
+        .map(|block| block_char_length(block, &uow.store()) + 1)
+        .sum();
     for fid in &column_cell_frame_ids {
         uow.remove_frame(fid)?;
     }
@@ -191,13 +199,12 @@ fn execute_remove_table_column(
 
     // Assign positions in row-major order (handles multi-block cells)
     let (cell_blocks_to_update, _) =
-        reassign_cell_block_positions(&*uow, &remaining_cells, base_pos, now)?;
+        reassign_cell_block_positions(&*uow, &remaining_cells, base_pos, now, &uow.store())?;
     if !cell_blocks_to_update.is_empty() {
         uow.update_block_multi(&cell_blocks_to_update)?;
     }
 
     // Shift non-table blocks after the table
-    let removed_cells = cells_to_remove.len() as i64;
     let frame_ids = uow.get_document_relationship(&doc_id, &DocumentRelationshipField::Frames)?;
     let cell_frame_set: std::collections::HashSet<EntityId> =
         remaining_cell_frame_ids.into_iter().collect();
@@ -214,7 +221,7 @@ fn execute_remove_table_column(
         for block in blocks_opt.into_iter().flatten() {
             if block.document_position >= base_pos {
                 let mut shifted = block;
-                shifted.document_position -= removed_cells;
+                shifted.document_position -= removed_positions;
                 shifted.updated_at = now;
                 shifted_blocks.push(shifted);
             }
@@ -226,7 +233,7 @@ fn execute_remove_table_column(
 
     // Update Document.block_count
     let mut updated_doc = document.clone();
-    updated_doc.block_count -= removed_cells;
+    updated_doc.block_count -= removed_cell_block_ids.len() as i64;
     updated_doc.updated_at = now;
     uow.update_document(&updated_doc)?;
 
@@ -242,7 +249,7 @@ impl RemoveTableColumnUseCase {
     pub fn new(uow_factory: Box<dyn RemoveTableColumnUnitOfWorkFactoryTrait>) -> Self {
         RemoveTableColumnUseCase {
             uow_factory,
-            undo_snapshot: None,
+            undo_delta: None,
             last_dto: None,
         }
     }
@@ -251,7 +258,7 @@ impl RemoveTableColumnUseCase {
         let mut uow = self.uow_factory.create();
         uow.begin_transaction()?;
         let (result, snapshot) = execute_remove_table_column(&mut uow, dto)?;
-        self.undo_snapshot = Some(snapshot);
+        self.undo_delta = Some(StoreDelta::since(&snapshot, &uow.store())?);
         self.last_dto = Some(dto.clone());
         uow.commit()?;
         Ok(result)
@@ -260,14 +267,14 @@ impl RemoveTableColumnUseCase {
 
 impl UndoRedoCommand for RemoveTableColumnUseCase {
     fn undo(&mut self) -> Result<()> {
-        let snapshot = self
-            .undo_snapshot
+        let delta = self
+            .undo_delta
             .as_ref()
-            .ok_or_else(|| anyhow!("No snapshot available for undo"))?
-            .clone();
+            .ok_or_else(|| anyhow!("No delta available for undo"))?;
 
         let mut uow = self.uow_factory.create();
         uow.begin_transaction()?;
+        let snapshot = delta.reverted(&uow.store())?;
         uow.restore_document(&snapshot)?;
         uow.commit()?;
         Ok(())
@@ -283,11 +290,17 @@ impl UndoRedoCommand for RemoveTableColumnUseCase {
         let mut uow = self.uow_factory.create();
         uow.begin_transaction()?;
         let (_, snapshot) = execute_remove_table_column(&mut uow, &dto)?;
-        self.undo_snapshot = Some(snapshot);
+        self.undo_delta = Some(StoreDelta::since(&snapshot, &uow.store())?);
         uow.commit()?;
         Ok(())
     }
 
+    fn description(&self) -> String {
+        "Remove table column".to_string()
+    }
+
+    common::store_delta_undo_methods!(undo_delta);
+
     fn as_any(&self) -> &dyn Any {
         self
     }
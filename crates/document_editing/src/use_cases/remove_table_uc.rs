@@ -1,6 +1,8 @@
 use crate::RemoveTableDto;
 use anyhow::{Result, anyhow};
 use common::database::CommandUnitOfWork;
+use common::database::rope_helpers::block_char_length;
+use common::database::store_delta::StoreDelta;
 use common::direct_access::document::document_repository::DocumentRelationshipField;
 use common::direct_access::frame::frame_repository::FrameRelationshipField;
 use common::direct_access::root::root_repository::RootRelationshipField;
@@ -29,6 +31,7 @@ pub trait RemoveTableUnitOfWorkFactoryTrait: Send + Sync {
 #[macros::uow_action(entity = "Frame", action = "GetRelationship")]
 #[macros::uow_action(entity = "Block", action = "GetMulti")]
 #[macros::uow_action(entity = "Block", action = "UpdateMulti")]
+#[macros::uow_action(entity = "Block", action = "Create")]
 #[macros::uow_action(entity = "Table", action = "Get")]
 #[macros::uow_action(entity = "Table", action = "Remove")]
 #[macros::uow_action(entity = "Table", action = "GetRelationship")]
@@ -37,7 +40,7 @@ pub trait RemoveTableUnitOfWorkTrait: CommandUnitOfWork {}
 
 pub struct RemoveTableUseCase {
     uow_factory: Box<dyn RemoveTableUnitOfWorkFactoryTrait>,
-    undo_snapshot: Option<EntityTreeSnapshot>,
+    undo_delta: Option<StoreDelta>,
     last_dto: Option<RemoveTableDto>,
 }
 
@@ -78,10 +81,11 @@ fn execute_remove_table(
     // Collect cell frame IDs
     let cell_frame_ids: Vec<EntityId> = cells.iter().filter_map(|c| c.cell_frame).collect();
 
-    // Count how many cell blocks exist (for position shifting), and
-    // remember each cell-block id so we can detach them from the
-    // global rope before the entity cascade below.
+    // Count how many cell blocks exist and the positions they take (for
+    // position shifting), and remember each cell-block id so we can
+    // detach them from the global rope before the entity cascade below.
     let mut total_cell_blocks: i64 = 0;
+    let mut total_cell_positions: i64 = 0;
     let mut min_cell_position: Option<i64> = None;
     let mut cell_block_ids: Vec<EntityId> = Vec::new();
     for fid in &cell_frame_ids {
@@ -90,6 +94,7 @@ fn execute_remove_table(
             let blocks_opt = uow.get_block_multi(&block_ids)?;
             for block in blocks_opt.into_iter().flatten() {
                 total_cell_blocks += 1;
+                total_cell_positions += block_char_length(&block, &uow.store()) + 1;
                 cell_block_ids.push(block.id);
                 match min_cell_position {
                     None => min_cell_position = Some(block.document_position),
@@ -130,6 +135,7 @@ fn execute_remove_table(
     }
 
     // Remove anchor frame
+    let mut parent_frame_id: Option<EntityId> = None;
     if let Some(anchor_id) = anchor_frame_id {
         // First, remove the anchor from its parent frame's child_order
         let frames_opt = uow.get_frame_multi(&frame_ids)?;
@@ -140,6 +146,7 @@ fn execute_remove_table(
                 updated.child_order.retain(|&x| x != neg_anchor);
                 updated.updated_at = now;
                 uow.update_frame(&updated)?;
+                parent_frame_id = Some(frame.id);
                 break;
             }
         }
@@ -167,7 +174,7 @@ fn execute_remove_table(
                 for block in blocks_opt.into_iter().flatten() {
                     if block.document_position >= table_start_pos {
                         let mut shifted = block;
-                        shifted.document_position -= total_cell_blocks;
+                        shifted.document_position -= total_cell_positions;
                         shifted.updated_at = now;
                         blocks_to_shift.push(shifted);
                     }
@@ -179,9 +186,33 @@ fn execute_remove_table(
         }
     }
 
+    // A document holding nothing but the table keeps one empty block,
+    // as when all of its text is deleted.
+    let mut created_blocks = 0;
+    if document.block_count == total_cell_blocks
+        && let Some(frame_id) = parent_frame_id
+    {
+        let empty_block = Block {
+            document_position: 0,
+            ..Block::default()
+        };
+        let created = uow.create_block(&empty_block, frame_id, -1)?;
+        let frame = uow
+            .get_frame(&frame_id)?
+            .ok_or_else(|| anyhow!("Frame not found"))?;
+        let mut updated = frame.clone();
+        updated.child_order.push(created.id as i64);
+        updated.updated_at = now;
+        uow.update_frame(&updated)?;
+
+        common::database::rope_helpers::rope_reset(&uow.store());
+        common::database::rope_helpers::rope_append_empty_block(&uow.store(), created.id);
+        created_blocks = 1;
+    }
+
     // Update Document stats
     let mut updated_doc = document.clone();
-    updated_doc.block_count -= total_cell_blocks;
+    updated_doc.block_count += created_blocks - total_cell_blocks;
     updated_doc.updated_at = now;
     uow.update_document(&updated_doc)?;
 
@@ -192,7 +223,7 @@ impl RemoveTableUseCase {
     pub fn new(uow_factory: Box<dyn RemoveTableUnitOfWorkFactoryTrait>) -> Self {
         RemoveTableUseCase {
             uow_factory,
-            undo_snapshot: None,
+            undo_delta: None,
             last_dto: None,
         }
     }
@@ -202,7 +233,7 @@ impl RemoveTableUseCase {
         uow.begin_transaction()?;
 
         let snapshot = execute_remove_table(&mut uow, dto)?;
-        self.undo_snapshot = Some(snapshot);
+        self.undo_delta = Some(StoreDelta::since(&snapshot, &uow.store())?);
         self.last_dto = Some(dto.clone());
 
         uow.commit()?;
@@ -212,14 +243,14 @@ impl RemoveTableUseCase {
 
 impl UndoRedoCommand for RemoveTableUseCase {
     fn undo(&mut self) -> Result<()> {
-        let snapshot = self
-            .undo_snapshot
+        let delta = self
+            .undo_delta
             .as_ref()
-            .ok_or_else(|| anyhow!("No snapshot available for undo"))?
-            .clone();
+            .ok_or_else(|| anyhow!("No delta available for undo"))?;
 
         let mut uow = self.uow_factory.create();
         uow.begin_transaction()?;
+        let snapshot = delta.reverted(&uow.store())?;
         uow.restore_document(&snapshot)?;
         uow.commit()?;
         Ok(())
@@ -235,11 +266,17 @@ impl UndoRedoCommand for RemoveTableUseCase {
         let mut uow = self.uow_factory.create();
         uow.begin_transaction()?;
         let snapshot = execute_remove_table(&mut uow, &dto)?;
-        self.undo_snapshot = Some(snapshot);
+        self.undo_delta = Some(StoreDelta::since(&snapshot, &uow.store())?);
         uow.commit()?;
         Ok(())
     }
 
+    fn description(&self) -> String {
+        "Remove table".to_string()
+    }
+
+    common::store_delta_undo_methods!(undo_delta);
+
     fn as_any(&self) -> &dyn Any {
         self
     }
@@ -7,6 +7,7 @@ use crate::SplitTableCellResultDto;
 use anyhow::{Result, anyhow};
 use common::database::CommandUnitOfWork;
 use common::database::rope_helpers::{rope_insert_block_at, top_level_frame_end_byte};
+use common::database::store_delta::StoreDelta;
 use common::direct_access::document::document_repository::DocumentRelationshipField;
 use common::direct_access::frame::frame_repository::FrameRelationshipField;
 use common::direct_access::root::root_repository::RootRelationshipField;
@@ -47,7 +48,7 @@ impl_cell_block_reader!(dyn SplitTableCellUnitOfWorkTrait);
 
 pub struct SplitTableCellUseCase {
     uow_factory: Box<dyn SplitTableCellUnitOfWorkFactoryTrait>,
-    undo_snapshot: Option<EntityTreeSnapshot>,
+    undo_delta: Option<StoreDelta>,
     last_dto: Option<SplitTableCellDto>,
 }
 
@@ -161,12 +162,22 @@ fn execute_split_table_cell(
     uow.update_table_cell(&updated_cell)?;
     all_cell_ids_result.push(cell.id as i64);
 
+    // The table's anchor frame, parent of the new cell frames.
+    let anchor_frame = {
+        let store = uow.store();
+        let frames = store.frames.read().unwrap();
+        frames
+            .values()
+            .find(|f| f.table == Some(table_id))
+            .map(|f| f.id)
+    };
     // Create new cells for remaining sub-cell positions, collecting
     // their blocks so we can mirror them into the rope after the
     // entity graph is in place.
     let mut new_cell_blocks: Vec<Block> = Vec::with_capacity(sub_cells.len().saturating_sub(1));
     for &(r, c, rs, cs) in &sub_cells[1..] {
-        let (cell_frame_id, created_block) = create_cell_frame(&mut *uow, doc_id, now)?;
+        let (cell_frame_id, created_block) =
+            create_cell_frame(&mut *uow, doc_id, anchor_frame, now)?;
         new_cell_blocks.push(created_block);
 
         let new_cell = TableCell {
@@ -216,7 +227,7 @@ fn execute_split_table_cell(
     let cell_frame_ids: Vec<EntityId> = all_cells.iter().filter_map(|c| c.cell_frame).collect();
 
     let (cell_blocks_to_update, _) =
-        reassign_cell_block_positions(&*uow, &all_cells, base_pos, now)?;
+        reassign_cell_block_positions(&*uow, &all_cells, base_pos, now, &uow.store())?;
     if !cell_blocks_to_update.is_empty() {
         uow.update_block_multi(&cell_blocks_to_update)?;
     }
@@ -270,7 +281,7 @@ impl SplitTableCellUseCase {
     pub fn new(uow_factory: Box<dyn SplitTableCellUnitOfWorkFactoryTrait>) -> Self {
         SplitTableCellUseCase {
             uow_factory,
-            undo_snapshot: None,
+            undo_delta: None,
             last_dto: None,
         }
     }
@@ -279,7 +290,7 @@ impl SplitTableCellUseCase {
         let mut uow = self.uow_factory.create();
         uow.begin_transaction()?;
         let (result, snapshot) = execute_split_table_cell(&mut uow, dto)?;
-        self.undo_snapshot = Some(snapshot);
+        self.undo_delta = Some(StoreDelta::since(&snapshot, &uow.store())?);
         self.last_dto = Some(dto.clone());
         uow.commit()?;
         Ok(result)
@@ -288,13 +299,13 @@ impl SplitTableCellUseCase {
 
 impl UndoRedoCommand for SplitTableCellUseCase {
     fn undo(&mut self) -> Result<()> {
-        let snapshot = self
-            .undo_snapshot
+        let delta = self
+            .undo_delta
             .as_ref()
-            .ok_or_else(|| anyhow!("No snapshot for undo"))?
-            .clone();
+            .ok_or_else(|| anyhow!("No delta for undo"))?;
         let mut uow = self.uow_factory.create();
         uow.begin_transaction()?;
+        let snapshot = delta.reverted(&uow.store())?;
         uow.restore_document(&snapshot)?;
         uow.commit()?;
         Ok(())
@@ -309,11 +320,17 @@ impl UndoRedoCommand for SplitTableCellUseCase {
         let mut uow = self.uow_factory.create();
         uow.begin_transaction()?;
         let (_, snapshot) = execute_split_table_cell(&mut uow, &dto)?;
-        self.undo_snapshot = Some(snapshot);
+        self.undo_delta = Some(StoreDelta::since(&snapshot, &uow.store())?);
         uow.commit()?;
         Ok(())
     }
 
+    fn description(&self) -> String {
+        "Split table cell".to_string()
+    }
+
+    common::store_delta_undo_methods!(undo_delta);
+
     fn as_any(&self) -> &dyn Any {
         self
     }
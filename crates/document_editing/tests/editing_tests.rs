@@ -251,6 +251,56 @@ fn test_insert_text_replaces_selection() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_in_block_undo_touches_one_block() -> Result<()> {
+    let (db_context, event_hub, mut undo_redo_manager) = setup_with_text("Hello World\nSecond")?;
+
+    document_editing_controller::insert_text(
+        &db_context,
+        &event_hub,
+        &mut undo_redo_manager,
+        None,
+        &InsertTextDto {
+            position: 6,
+            anchor: 11,
+            text: "Rust".to_string(),
+        },
+    )?;
+    assert_eq!(
+        undo_redo_manager
+            .undo_affected_blocks(None, 1)
+            .map(|b| b.len()),
+        Some(1)
+    );
+    undo_redo_manager.undo(None)?;
+    assert_eq!(export_text(&db_context, &event_hub)?, "Hello World\nSecond");
+    undo_redo_manager.redo(None)?;
+    assert_eq!(export_text(&db_context, &event_hub)?, "Hello Rust\nSecond");
+
+    // Deleting across the block boundary touches both blocks it merges.
+    document_editing_controller::delete_text(
+        &db_context,
+        &event_hub,
+        &mut undo_redo_manager,
+        None,
+        &DeleteTextDto {
+            position: 8,
+            anchor: 13,
+        },
+    )?;
+    assert_eq!(export_text(&db_context, &event_hub)?, "Hello Rucond");
+    assert_eq!(
+        undo_redo_manager
+            .undo_affected_blocks(None, 1)
+            .map(|b| b.len()),
+        Some(2)
+    );
+    undo_redo_manager.undo(None)?;
+    assert_eq!(export_text(&db_context, &event_hub)?, "Hello Rust\nSecond");
+
+    Ok(())
+}
+
 // --- DeleteText: reversed anchor/position ---
 
 #[test]
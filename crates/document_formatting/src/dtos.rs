@@ -19,6 +19,9 @@ pub struct SetTextFormatDto {
     pub word_spacing: Option<i64>,
     pub underline_style: Option<UnderlineStyle>,
     pub vertical_alignment: Option<CharVerticalAlignment>,
+    pub foreground_color: Option<String>,
+    pub background_color: Option<String>,
+    pub underline_color: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq)]
@@ -54,6 +57,9 @@ pub struct MergeTextFormatDto {
     pub font_italic: Option<bool>,
     pub font_underline: Option<bool>,
     pub font_strikeout: Option<bool>,
+    pub foreground_color: Option<String>,
+    pub background_color: Option<String>,
+    pub underline_color: Option<String>,
 }
 #[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
 pub struct SetBlockFormatDto {
@@ -1,7 +1,8 @@
 use crate::SetBlockFormatDto;
 use anyhow::{Result, anyhow};
 use common::database::CommandUnitOfWork;
-use common::database::rope_helpers::block_char_length;
+use common::database::rope_helpers::{block_char_length, refresh_block_positions};
+use common::database::store_delta::StoreDelta;
 use common::direct_access::document::document_repository::DocumentRelationshipField;
 use common::direct_access::frame::frame_repository::FrameRelationshipField;
 use common::direct_access::root::root_repository::RootRelationshipField;
@@ -79,6 +80,8 @@ fn execute_set_block_format(
     // Get all blocks
     let blocks_opt = uow.get_block_multi(&all_block_ids)?;
     let mut blocks: Vec<Block> = blocks_opt.into_iter().flatten().collect();
+    let store = uow.store();
+    refresh_block_positions(&mut blocks, &store);
     blocks.sort_by_key(|b| b.document_position);
 
     // Determine the range
@@ -87,7 +90,6 @@ fn execute_set_block_format(
 
     // Find blocks that overlap the range
     let mut blocks_to_update: Vec<Block> = Vec::new();
-    let store = uow.store();
     for block in &blocks {
         let block_start = block.document_position;
         let block_end = block_start + block_char_length(block, &store);
@@ -161,7 +163,7 @@ fn execute_set_block_format(
 
 pub struct SetBlockFormatUseCase {
     uow_factory: Box<dyn SetBlockFormatUnitOfWorkFactoryTrait>,
-    undo_snapshot: Option<EntityTreeSnapshot>,
+    undo_delta: Option<StoreDelta>,
     last_dto: Option<SetBlockFormatDto>,
 }
 
@@ -169,7 +171,7 @@ impl SetBlockFormatUseCase {
     pub fn new(uow_factory: Box<dyn SetBlockFormatUnitOfWorkFactoryTrait>) -> Self {
         SetBlockFormatUseCase {
             uow_factory,
-            undo_snapshot: None,
+            undo_delta: None,
             last_dto: None,
         }
     }
@@ -179,7 +181,7 @@ impl SetBlockFormatUseCase {
         uow.begin_transaction()?;
 
         let snapshot = execute_set_block_format(&mut uow, dto)?;
-        self.undo_snapshot = Some(snapshot);
+        self.undo_delta = Some(StoreDelta::since(&snapshot, &uow.store())?);
         self.last_dto = Some(dto.clone());
 
         uow.commit()?;
@@ -189,14 +191,14 @@ impl SetBlockFormatUseCase {
 
 impl UndoRedoCommand for SetBlockFormatUseCase {
     fn undo(&mut self) -> Result<()> {
-        let snapshot = self
-            .undo_snapshot
+        let delta = self
+            .undo_delta
             .as_ref()
-            .ok_or_else(|| anyhow!("No snapshot available for undo"))?
-            .clone();
+            .ok_or_else(|| anyhow!("No delta available for undo"))?;
 
         let mut uow = self.uow_factory.create();
         uow.begin_transaction()?;
+        let snapshot = delta.reverted(&uow.store())?;
         uow.restore_document(&snapshot)?;
         uow.commit()?;
         Ok(())
@@ -212,11 +214,17 @@ impl UndoRedoCommand for SetBlockFormatUseCase {
         let mut uow = self.uow_factory.create();
         uow.begin_transaction()?;
         let snapshot = execute_set_block_format(&mut uow, &dto)?;
-        self.undo_snapshot = Some(snapshot);
+        self.undo_delta = Some(StoreDelta::since(&snapshot, &uow.store())?);
         uow.commit()?;
         Ok(())
     }
 
+    fn description(&self) -> String {
+        "Format paragraph".to_string()
+    }
+
+    common::store_delta_undo_methods!(undo_delta);
+
     fn as_any(&self) -> &dyn Any {
         self
     }
@@ -1,6 +1,7 @@
 use crate::SetFrameFormatDto;
 use anyhow::{Result, anyhow};
 use common::database::CommandUnitOfWork;
+use common::database::store_delta::StoreDelta;
 use common::direct_access::root::root_repository::RootRelationshipField;
 use common::entities::{Document, Frame, Root};
 use common::snapshot::EntityTreeSnapshot;
@@ -84,7 +85,7 @@ fn execute_set_frame_format(
 
 pub struct SetFrameFormatUseCase {
     uow_factory: Box<dyn SetFrameFormatUnitOfWorkFactoryTrait>,
-    undo_snapshot: Option<EntityTreeSnapshot>,
+    undo_delta: Option<StoreDelta>,
     last_dto: Option<SetFrameFormatDto>,
 }
 
@@ -92,7 +93,7 @@ impl SetFrameFormatUseCase {
     pub fn new(uow_factory: Box<dyn SetFrameFormatUnitOfWorkFactoryTrait>) -> Self {
         SetFrameFormatUseCase {
             uow_factory,
-            undo_snapshot: None,
+            undo_delta: None,
             last_dto: None,
         }
     }
@@ -102,7 +103,7 @@ impl SetFrameFormatUseCase {
         uow.begin_transaction()?;
 
         let snapshot = execute_set_frame_format(&mut uow, dto)?;
-        self.undo_snapshot = Some(snapshot);
+        self.undo_delta = Some(StoreDelta::since(&snapshot, &uow.store())?);
         self.last_dto = Some(dto.clone());
 
         uow.commit()?;
@@ -112,14 +113,14 @@ impl SetFrameFormatUseCase {
 
 impl UndoRedoCommand for SetFrameFormatUseCase {
     fn undo(&mut self) -> Result<()> {
-        let snapshot = self
-            .undo_snapshot
+        let delta = self
+            .undo_delta
             .as_ref()
-            .ok_or_else(|| anyhow!("No snapshot available for undo"))?
-            .clone();
+            .ok_or_else(|| anyhow!("No delta available for undo"))?;
 
         let mut uow = self.uow_factory.create();
         uow.begin_transaction()?;
+        let snapshot = delta.reverted(&uow.store())?;
         uow.restore_document(&snapshot)?;
         uow.commit()?;
         Ok(())
@@ -135,11 +136,17 @@ impl UndoRedoCommand for SetFrameFormatUseCase {
         let mut uow = self.uow_factory.create();
         uow.begin_transaction()?;
         let snapshot = execute_set_frame_format(&mut uow, &dto)?;
-        self.undo_snapshot = Some(snapshot);
+        self.undo_delta = Some(StoreDelta::since(&snapshot, &uow.store())?);
         uow.commit()?;
         Ok(())
     }
 
+    fn description(&self) -> String {
+        "Format frame".to_string()
+    }
+
+    common::store_delta_undo_methods!(undo_delta);
+
     fn as_any(&self) -> &dyn Any {
         self
     }
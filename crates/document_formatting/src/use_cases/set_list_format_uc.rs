@@ -1,6 +1,7 @@
 use crate::SetListFormatDto;
 use anyhow::{Result, anyhow};
 use common::database::CommandUnitOfWork;
+use common::database::store_delta::StoreDelta;
 use common::direct_access::root::root_repository::RootRelationshipField;
 use common::entities::{Document, List, Root};
 use common::snapshot::EntityTreeSnapshot;
@@ -69,7 +70,7 @@ fn execute_set_list_format(
 
 pub struct SetListFormatUseCase {
     uow_factory: Box<dyn SetListFormatUnitOfWorkFactoryTrait>,
-    undo_snapshot: Option<EntityTreeSnapshot>,
+    undo_delta: Option<StoreDelta>,
     last_dto: Option<SetListFormatDto>,
 }
 
@@ -77,7 +78,7 @@ impl SetListFormatUseCase {
     pub fn new(uow_factory: Box<dyn SetListFormatUnitOfWorkFactoryTrait>) -> Self {
         SetListFormatUseCase {
             uow_factory,
-            undo_snapshot: None,
+            undo_delta: None,
             last_dto: None,
         }
     }
@@ -87,7 +88,7 @@ impl SetListFormatUseCase {
         uow.begin_transaction()?;
 
         let snapshot = execute_set_list_format(&mut uow, dto)?;
-        self.undo_snapshot = Some(snapshot);
+        self.undo_delta = Some(StoreDelta::since(&snapshot, &uow.store())?);
         self.last_dto = Some(dto.clone());
 
         uow.commit()?;
@@ -97,14 +98,14 @@ impl SetListFormatUseCase {
 
 impl UndoRedoCommand for SetListFormatUseCase {
     fn undo(&mut self) -> Result<()> {
-        let snapshot = self
-            .undo_snapshot
+        let delta = self
+            .undo_delta
             .as_ref()
-            .ok_or_else(|| anyhow!("No snapshot available for undo"))?
-            .clone();
+            .ok_or_else(|| anyhow!("No delta available for undo"))?;
 
         let mut uow = self.uow_factory.create();
         uow.begin_transaction()?;
+        let snapshot = delta.reverted(&uow.store())?;
         uow.restore_document(&snapshot)?;
         uow.commit()?;
         Ok(())
@@ -120,11 +121,17 @@ impl UndoRedoCommand for SetListFormatUseCase {
         let mut uow = self.uow_factory.create();
         uow.begin_transaction()?;
         let snapshot = execute_set_list_format(&mut uow, &dto)?;
-        self.undo_snapshot = Some(snapshot);
+        self.undo_delta = Some(StoreDelta::since(&snapshot, &uow.store())?);
         uow.commit()?;
         Ok(())
     }
 
+    fn description(&self) -> String {
+        "Format list".to_string()
+    }
+
+    common::store_delta_undo_methods!(undo_delta);
+
     fn as_any(&self) -> &dyn Any {
         self
     }
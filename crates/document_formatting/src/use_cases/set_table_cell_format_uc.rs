@@ -1,6 +1,7 @@
 use crate::SetTableCellFormatDto;
 use anyhow::{Result, anyhow};
 use common::database::CommandUnitOfWork;
+use common::database::store_delta::StoreDelta;
 use common::direct_access::root::root_repository::RootRelationshipField;
 use common::entities::{Document, Root, TableCell};
 use common::snapshot::EntityTreeSnapshot;
@@ -83,7 +84,7 @@ fn execute_set_table_cell_format(
 
 pub struct SetTableCellFormatUseCase {
     uow_factory: Box<dyn SetTableCellFormatUnitOfWorkFactoryTrait>,
-    undo_snapshot: Option<EntityTreeSnapshot>,
+    undo_delta: Option<StoreDelta>,
     last_dto: Option<SetTableCellFormatDto>,
 }
 
@@ -91,7 +92,7 @@ impl SetTableCellFormatUseCase {
     pub fn new(uow_factory: Box<dyn SetTableCellFormatUnitOfWorkFactoryTrait>) -> Self {
         SetTableCellFormatUseCase {
             uow_factory,
-            undo_snapshot: None,
+            undo_delta: None,
             last_dto: None,
         }
     }
@@ -101,7 +102,7 @@ impl SetTableCellFormatUseCase {
         uow.begin_transaction()?;
 
         let snapshot = execute_set_table_cell_format(&mut uow, dto)?;
-        self.undo_snapshot = Some(snapshot);
+        self.undo_delta = Some(StoreDelta::since(&snapshot, &uow.store())?);
         self.last_dto = Some(dto.clone());
 
         uow.commit()?;
@@ -111,14 +112,14 @@ impl SetTableCellFormatUseCase {
 
 impl UndoRedoCommand for SetTableCellFormatUseCase {
     fn undo(&mut self) -> Result<()> {
-        let snapshot = self
-            .undo_snapshot
+        let delta = self
+            .undo_delta
             .as_ref()
-            .ok_or_else(|| anyhow!("No snapshot available for undo"))?
-            .clone();
+            .ok_or_else(|| anyhow!("No delta available for undo"))?;
 
         let mut uow = self.uow_factory.create();
         uow.begin_transaction()?;
+        let snapshot = delta.reverted(&uow.store())?;
         uow.restore_document(&snapshot)?;
         uow.commit()?;
         Ok(())
@@ -134,11 +135,17 @@ impl UndoRedoCommand for SetTableCellFormatUseCase {
         let mut uow = self.uow_factory.create();
         uow.begin_transaction()?;
         let snapshot = execute_set_table_cell_format(&mut uow, &dto)?;
-        self.undo_snapshot = Some(snapshot);
+        self.undo_delta = Some(StoreDelta::since(&snapshot, &uow.store())?);
         uow.commit()?;
         Ok(())
     }
 
+    fn description(&self) -> String {
+        "Format table cell".to_string()
+    }
+
+    common::store_delta_undo_methods!(undo_delta);
+
     fn as_any(&self) -> &dyn Any {
         self
     }
@@ -1,6 +1,7 @@
 use crate::SetTableFormatDto;
 use anyhow::{Result, anyhow};
 use common::database::CommandUnitOfWork;
+use common::database::store_delta::StoreDelta;
 use common::direct_access::root::root_repository::RootRelationshipField;
 use common::entities::{Document, Root, Table};
 use common::snapshot::EntityTreeSnapshot;
@@ -81,7 +82,7 @@ fn execute_set_table_format(
 
 pub struct SetTableFormatUseCase {
     uow_factory: Box<dyn SetTableFormatUnitOfWorkFactoryTrait>,
-    undo_snapshot: Option<EntityTreeSnapshot>,
+    undo_delta: Option<StoreDelta>,
     last_dto: Option<SetTableFormatDto>,
 }
 
@@ -89,7 +90,7 @@ impl SetTableFormatUseCase {
     pub fn new(uow_factory: Box<dyn SetTableFormatUnitOfWorkFactoryTrait>) -> Self {
         SetTableFormatUseCase {
             uow_factory,
-            undo_snapshot: None,
+            undo_delta: None,
             last_dto: None,
         }
     }
@@ -99,7 +100,7 @@ impl SetTableFormatUseCase {
         uow.begin_transaction()?;
 
         let snapshot = execute_set_table_format(&mut uow, dto)?;
-        self.undo_snapshot = Some(snapshot);
+        self.undo_delta = Some(StoreDelta::since(&snapshot, &uow.store())?);
         self.last_dto = Some(dto.clone());
 
         uow.commit()?;
@@ -109,14 +110,14 @@ impl SetTableFormatUseCase {
 
 impl UndoRedoCommand for SetTableFormatUseCase {
     fn undo(&mut self) -> Result<()> {
-        let snapshot = self
-            .undo_snapshot
+        let delta = self
+            .undo_delta
             .as_ref()
-            .ok_or_else(|| anyhow!("No snapshot available for undo"))?
-            .clone();
+            .ok_or_else(|| anyhow!("No delta available for undo"))?;
 
         let mut uow = self.uow_factory.create();
         uow.begin_transaction()?;
+        let snapshot = delta.reverted(&uow.store())?;
         uow.restore_document(&snapshot)?;
         uow.commit()?;
         Ok(())
@@ -132,11 +133,17 @@ impl UndoRedoCommand for SetTableFormatUseCase {
         let mut uow = self.uow_factory.create();
         uow.begin_transaction()?;
         let snapshot = execute_set_table_format(&mut uow, &dto)?;
-        self.undo_snapshot = Some(snapshot);
+        self.undo_delta = Some(StoreDelta::since(&snapshot, &uow.store())?);
         uow.commit()?;
         Ok(())
     }
 
+    fn description(&self) -> String {
+        "Format table".to_string()
+    }
+
+    common::store_delta_undo_methods!(undo_delta);
+
     fn as_any(&self) -> &dyn Any {
         self
     }
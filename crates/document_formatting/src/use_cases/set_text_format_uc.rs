@@ -1,15 +1,15 @@
 use crate::SetTextFormatDto;
 use anyhow::{Result, anyhow};
 use common::database::CommandUnitOfWork;
-use common::database::rope_helpers::{block_char_length, block_char_to_byte_in_block};
+use common::database::edit_record::{EditRecord, RangeFormats};
+use common::database::rope_helpers::{
+    block_char_length, block_char_to_byte_in_block, refresh_block_positions,
+};
 use common::direct_access::document::document_repository::DocumentRelationshipField;
 use common::direct_access::frame::frame_repository::FrameRelationshipField;
 use common::direct_access::root::root_repository::RootRelationshipField;
 use common::entities::{Block, Document, Frame, Root};
-use common::format_runs::{
-    CharacterFormat, FormatRun, capture_image_formats_in_range, capture_runs_in_range,
-    debug_assert_well_formed, splice_range,
-};
+use common::format_runs::{CharacterFormat, FormatRun, debug_assert_well_formed, splice_range};
 use common::types::{EntityId, ROOT_ENTITY_ID};
 use common::undo_redo::UndoRedoCommand;
 use std::any::Any;
@@ -29,17 +29,6 @@ pub trait SetTextFormatUnitOfWorkFactoryTrait: Send + Sync {
 #[macros::uow_action(entity = "Block", action = "GetRelationship")]
 pub trait SetTextFormatUnitOfWorkTrait: CommandUnitOfWork {}
 
-/// Per-block captured state for hand-rolled undo. Built during the
-/// mutation pass; consumed by `undo()` to restore the prior state
-/// without paying the cost of a full RopeStoreSnapshot.
-#[derive(Clone, Debug)]
-struct BlockFormatInverse {
-    block_id: EntityId,
-    byte_range: (u32, u32),
-    prior_runs: Vec<FormatRun>,
-    prior_image_formats: Vec<(u32, CharacterFormat)>,
-}
-
 fn underline_style_to_entity(s: &crate::dtos::UnderlineStyle) -> common::entities::UnderlineStyle {
     match s {
         crate::dtos::UnderlineStyle::NoUnderline => common::entities::UnderlineStyle::NoUnderline,
@@ -129,6 +118,15 @@ fn merge_dto(base: &CharacterFormat, dto: &SetTextFormatDto) -> CharacterFormat
     if let Some(ref v) = dto.vertical_alignment {
         out.vertical_alignment = Some(vertical_alignment_to_entity(v));
     }
+    if let Some(ref v) = dto.foreground_color {
+        out.foreground_color = Some(v.clone());
+    }
+    if let Some(ref v) = dto.background_color {
+        out.background_color = Some(v.clone());
+    }
+    if let Some(ref v) = dto.underline_color {
+        out.underline_color = Some(v.clone());
+    }
     out
 }
 
@@ -176,7 +174,7 @@ fn build_replacement_runs(
 fn execute_set_text_format(
     uow: &mut Box<dyn SetTextFormatUnitOfWorkTrait>,
     dto: &SetTextFormatDto,
-) -> Result<Vec<BlockFormatInverse>> {
+) -> Result<Vec<RangeFormats>> {
     // Get Root -> Document
     let root = uow
         .get_root(&ROOT_ENTITY_ID)?
@@ -200,18 +198,19 @@ fn execute_set_text_format(
 
     let blocks_opt = uow.get_block_multi(&all_block_ids)?;
     let mut blocks: Vec<Block> = blocks_opt.into_iter().flatten().collect();
+    let store = uow.store();
+    refresh_block_positions(&mut blocks, &store);
     blocks.sort_by_key(|b| b.document_position);
 
     let range_start = std::cmp::min(dto.position, dto.anchor);
     let range_end = std::cmp::max(dto.position, dto.anchor);
 
-    let mut inverse: Vec<BlockFormatInverse> = Vec::new();
+    let mut inverse: Vec<RangeFormats> = Vec::new();
 
     if range_start == range_end {
         return Ok(inverse);
     }
 
-    let store = uow.store();
     for block in &blocks {
         let block_start = block.document_position;
         let block_end = block_start + block_char_length(block, &store);
@@ -237,20 +236,7 @@ fn execute_set_text_format(
         }
 
         // Capture prior state before mutation.
-        let prior_runs = {
-            let runs_map = store.format_runs.read().unwrap();
-            runs_map
-                .get(&block.id)
-                .map(|runs| capture_runs_in_range(runs, byte_start, byte_end))
-                .unwrap_or_default()
-        };
-        let prior_image_formats = {
-            let images_map = store.block_images.read().unwrap();
-            images_map
-                .get(&block.id)
-                .map(|images| capture_image_formats_in_range(images, byte_start, byte_end))
-                .unwrap_or_default()
-        };
+        let prior = RangeFormats::capture(&store, block.id, (byte_start, byte_end));
 
         // Update format runs over the byte range.
         {
@@ -274,52 +260,19 @@ fn execute_set_text_format(
             }
         }
 
-        inverse.push(BlockFormatInverse {
-            block_id: block.id,
-            byte_range: (byte_start, byte_end),
-            prior_runs,
-            prior_image_formats,
-        });
+        inverse.push(prior);
     }
 
     Ok(inverse)
 }
 
-/// Restore the prior format-run and image-format state captured during
-/// the forward mutation. Splices the captured runs back into each
-/// affected block's byte range and restores per-image formats.
-fn apply_inverse(
-    uow: &mut Box<dyn SetTextFormatUnitOfWorkTrait>,
-    inverse: &[BlockFormatInverse],
-) -> Result<()> {
-    let store = uow.store();
-    for entry in inverse {
-        {
-            let mut runs_map = store.format_runs.write().unwrap();
-            let runs = runs_map.entry(entry.block_id).or_default();
-            splice_range(
-                runs,
-                entry.byte_range.0..entry.byte_range.1,
-                entry.prior_runs.clone(),
-            );
-        }
-        {
-            let mut images_map = store.block_images.write().unwrap();
-            if let Some(images) = images_map.get_mut(&entry.block_id) {
-                for (byte_offset, format) in &entry.prior_image_formats {
-                    if let Some(img) = images.iter_mut().find(|i| i.byte_offset == *byte_offset) {
-                        img.format = format.clone();
-                    }
-                }
-            }
-        }
-    }
-    Ok(())
-}
-
 pub struct SetTextFormatUseCase {
     uow_factory: Box<dyn SetTextFormatUnitOfWorkFactoryTrait>,
-    inverse: Option<Vec<BlockFormatInverse>>,
+    inverse: Option<Vec<RangeFormats>>,
+    /// The formatted ranges as they were just before the last undo,
+    /// for [`UndoRedoCommand::edit_records`] while the command is
+    /// undone.
+    redo_formats: Option<Vec<RangeFormats>>,
     last_dto: Option<SetTextFormatDto>,
 }
 
@@ -328,6 +281,7 @@ impl SetTextFormatUseCase {
         SetTextFormatUseCase {
             uow_factory,
             inverse: None,
+            redo_formats: None,
             last_dto: None,
         }
     }
@@ -338,6 +292,7 @@ impl SetTextFormatUseCase {
 
         let inverse = execute_set_text_format(&mut uow, dto)?;
         self.inverse = Some(inverse);
+        self.redo_formats = None;
         self.last_dto = Some(dto.clone());
 
         uow.commit()?;
@@ -355,8 +310,16 @@ impl UndoRedoCommand for SetTextFormatUseCase {
 
         let mut uow = self.uow_factory.create();
         uow.begin_transaction()?;
-        apply_inverse(&mut uow, &inverse)?;
+        let store = uow.store();
+        let redo_formats = inverse
+            .iter()
+            .map(|prior| RangeFormats::capture(&store, prior.block_id, prior.byte_range))
+            .collect();
+        for prior in &inverse {
+            prior.restore(&store);
+        }
         uow.commit()?;
+        self.redo_formats = Some(redo_formats);
         Ok(())
     }
 
@@ -371,10 +334,39 @@ impl UndoRedoCommand for SetTextFormatUseCase {
         uow.begin_transaction()?;
         let inverse = execute_set_text_format(&mut uow, &dto)?;
         self.inverse = Some(inverse);
+        self.redo_formats = None;
         uow.commit()?;
         Ok(())
     }
 
+    fn affected_blocks(&self) -> Option<Vec<EntityId>> {
+        let formats = self.inverse.as_ref()?;
+        let mut blocks: Vec<EntityId> = formats.iter().map(|prior| prior.block_id).collect();
+        blocks.sort_unstable();
+        blocks.dedup();
+        Some(blocks)
+    }
+
+    fn description(&self) -> String {
+        "Set text format".to_string()
+    }
+
+    fn approximate_size(&self) -> usize {
+        std::mem::size_of_val(self)
+            + self
+                .inverse
+                .iter()
+                .chain(&self.redo_formats)
+                .flatten()
+                .map(RangeFormats::approximate_size)
+                .sum::<usize>()
+    }
+
+    fn edit_records(&self) -> Option<Vec<EditRecord>> {
+        let formats = self.redo_formats.as_ref().or(self.inverse.as_ref())?;
+        Some(formats.iter().cloned().map(EditRecord::Formats).collect())
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
@@ -267,6 +267,9 @@ fn test_set_text_format_whole_element() -> Result<()> {
             word_spacing: Some(4),
             underline_style: Some(UnderlineStyle::SingleUnderline),
             vertical_alignment: Some(CharVerticalAlignment::Normal),
+            foreground_color: None,
+            background_color: None,
+            underline_color: None,
         },
     )?;
 
@@ -316,6 +319,9 @@ fn test_set_text_format_partial() -> Result<()> {
             word_spacing: Some(0),
             underline_style: Some(UnderlineStyle::NoUnderline),
             vertical_alignment: Some(CharVerticalAlignment::Normal),
+            foreground_color: None,
+            background_color: None,
+            underline_color: None,
         },
     )?;
 
@@ -373,6 +379,9 @@ fn test_merge_text_format_preserves_other_fields() -> Result<()> {
             word_spacing: Some(10),
             underline_style: Some(UnderlineStyle::WaveUnderline),
             vertical_alignment: Some(CharVerticalAlignment::SuperScript),
+            foreground_color: None,
+            background_color: None,
+            underline_color: None,
         },
     )?;
 
@@ -390,6 +399,9 @@ fn test_merge_text_format_preserves_other_fields() -> Result<()> {
             font_italic: Some(true),
             font_underline: Some(true),
             font_strikeout: None,
+            foreground_color: None,
+            background_color: None,
+            underline_color: None,
         },
     )?;
 
@@ -450,6 +462,9 @@ fn test_merge_text_format_undo() -> Result<()> {
             word_spacing: Some(0),
             underline_style: Some(UnderlineStyle::NoUnderline),
             vertical_alignment: Some(CharVerticalAlignment::Normal),
+            foreground_color: None,
+            background_color: None,
+            underline_color: None,
         },
     )?;
 
@@ -467,6 +482,9 @@ fn test_merge_text_format_undo() -> Result<()> {
             font_italic: Some(true),
             font_underline: Some(false),
             font_strikeout: None,
+            foreground_color: None,
+            background_color: None,
+            underline_color: None,
         },
     )?;
 
@@ -520,6 +538,9 @@ fn test_set_text_format_undo() -> Result<()> {
             word_spacing: Some(0),
             underline_style: Some(UnderlineStyle::NoUnderline),
             vertical_alignment: Some(CharVerticalAlignment::Normal),
+            foreground_color: None,
+            background_color: None,
+            underline_color: None,
         },
     )?;
 
@@ -561,6 +582,9 @@ fn test_set_text_format_cross_block() -> Result<()> {
             word_spacing: Some(0),
             underline_style: Some(UnderlineStyle::NoUnderline),
             vertical_alignment: Some(CharVerticalAlignment::Normal),
+            foreground_color: None,
+            background_color: None,
+            underline_color: None,
         },
     )?;
 
@@ -105,6 +105,9 @@ fn test_merge_text_format_on_list_blocks() -> Result<()> {
             font_italic: None,
             font_underline: None,
             font_strikeout: None,
+            foreground_color: None,
+            background_color: None,
+            underline_color: None,
         },
     )?;
 
@@ -149,6 +152,9 @@ fn test_merge_text_format_partial_split_preserves_fields() -> Result<()> {
             word_spacing: Some(4),
             underline_style: Some(UnderlineStyle::NoUnderline),
             vertical_alignment: Some(CharVerticalAlignment::Normal),
+            foreground_color: None,
+            background_color: None,
+            underline_color: None,
         },
     )?;
 
@@ -166,6 +172,9 @@ fn test_merge_text_format_partial_split_preserves_fields() -> Result<()> {
             font_italic: Some(true),
             font_underline: None,
             font_strikeout: None,
+            foreground_color: None,
+            background_color: None,
+            underline_color: None,
         },
     )?;
 
@@ -224,6 +233,9 @@ fn test_merge_text_format_undo_redo() -> Result<()> {
             font_italic: None,
             font_underline: None,
             font_strikeout: None,
+            foreground_color: None,
+            background_color: None,
+            underline_color: None,
         },
     )?;
 
@@ -335,6 +335,9 @@ fn test_set_text_format_all_fields() -> Result<()> {
             word_spacing: Some(6),
             underline_style: Some(UnderlineStyle::WaveUnderline),
             vertical_alignment: Some(CharVerticalAlignment::SuperScript),
+            foreground_color: Some("#ff0000".into()),
+            background_color: Some("#00ff0080".into()),
+            underline_color: Some("#0000ff".into()),
         },
     )?;
 
@@ -360,5 +363,8 @@ fn test_set_text_format_all_fields() -> Result<()> {
         elem.fmt_vertical_alignment,
         Some(common::entities::CharVerticalAlignment::SuperScript)
     );
+    assert_eq!(elem.fmt_foreground_color.as_deref(), Some("#ff0000"));
+    assert_eq!(elem.fmt_background_color.as_deref(), Some("#00ff0080"));
+    assert_eq!(elem.fmt_underline_color.as_deref(), Some("#0000ff"));
     Ok(())
 }
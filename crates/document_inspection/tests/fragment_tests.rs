@@ -524,6 +524,9 @@ fn test_extract_fragment_multiple_formats() -> Result<()> {
             word_spacing: Some(0),
             underline_style: Some(UnderlineStyle::NoUnderline),
             vertical_alignment: Some(CharVerticalAlignment::Normal),
+            foreground_color: None,
+            background_color: None,
+            underline_color: None,
         },
     )?;
 
@@ -547,6 +550,9 @@ fn test_extract_fragment_multiple_formats() -> Result<()> {
             word_spacing: Some(0),
             underline_style: Some(UnderlineStyle::NoUnderline),
             vertical_alignment: Some(CharVerticalAlignment::Normal),
+            foreground_color: None,
+            background_color: None,
+            underline_color: None,
         },
     )?;
 
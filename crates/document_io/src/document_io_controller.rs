@@ -2,42 +2,77 @@
 
 use crate::ExportDocxDto;
 use crate::ExportDocxResultDto;
+use crate::ExportEpubDto;
+use crate::ExportEpubResultDto;
 use crate::ExportHtmlDto;
 use crate::ExportLatexDto;
 use crate::ExportLatexResultDto;
 use crate::ExportMarkdownDto;
+use crate::ExportNativeDto;
+use crate::ExportOdtDto;
+use crate::ExportOdtResultDto;
 use crate::ExportPlainTextDto;
+use crate::ExportRtfDto;
+use crate::ImportDocxDto;
+use crate::ImportDocxResultDto;
 use crate::ImportHtmlDto;
 use crate::ImportHtmlResultDto;
 use crate::ImportMarkdownDto;
 use crate::ImportMarkdownResultDto;
+use crate::ImportNativeDto;
+use crate::ImportNativeResultDto;
+use crate::ImportOdtDto;
+use crate::ImportOdtResultDto;
 use crate::ImportPlainTextDto;
+use crate::ImportRtfDto;
+use crate::ImportRtfResultDto;
 use crate::units_of_work::export_docx_uow::ExportDocxUnitOfWorkFactory;
+use crate::units_of_work::export_epub_uow::ExportEpubUnitOfWorkFactory;
 use crate::units_of_work::export_html_uow::ExportHtmlUnitOfWorkFactory;
 use crate::units_of_work::export_latex_uow::ExportLatexUnitOfWorkFactory;
 use crate::units_of_work::export_markdown_uow::ExportMarkdownUnitOfWorkFactory;
+use crate::units_of_work::export_native_uow::ExportNativeUnitOfWorkFactory;
+use crate::units_of_work::export_odt_uow::ExportOdtUnitOfWorkFactory;
 use crate::units_of_work::export_plain_text_uow::ExportPlainTextUnitOfWorkFactory;
+use crate::units_of_work::export_rtf_uow::ExportRtfUnitOfWorkFactory;
+use crate::units_of_work::import_docx_uow::ImportDocxUnitOfWorkFactory;
 use crate::units_of_work::import_html_uow::ImportHtmlUnitOfWorkFactory;
 use crate::units_of_work::import_markdown_uow::ImportMarkdownUnitOfWorkFactory;
+use crate::units_of_work::import_native_uow::ImportNativeUnitOfWorkFactory;
+use crate::units_of_work::import_odt_uow::ImportOdtUnitOfWorkFactory;
 use crate::units_of_work::import_plain_text_uow::ImportPlainTextUnitOfWorkFactory;
+use crate::units_of_work::import_rtf_uow::ImportRtfUnitOfWorkFactory;
 use crate::use_cases::export_docx_uc::ExportDocxUseCase;
+use crate::use_cases::export_epub_uc::ExportEpubUseCase;
 use crate::use_cases::export_html_uc::ExportHtmlUseCase;
 use crate::use_cases::export_latex_uc::ExportLatexUseCase;
 use crate::use_cases::export_markdown_uc::ExportMarkdownUseCase;
+use crate::use_cases::export_native_uc::ExportNativeUseCase;
+use crate::use_cases::export_odt_uc::ExportOdtUseCase;
 use crate::use_cases::export_plain_text_uc::ExportPlainTextUseCase;
+use crate::use_cases::export_rtf_uc::ExportRtfUseCase;
+use crate::use_cases::import_docx_uc::ImportDocxUseCase;
 use crate::use_cases::import_html_uc::ImportHtmlUseCase;
 use crate::use_cases::import_markdown_uc::ImportMarkdownUseCase;
+use crate::use_cases::import_native_uc::{ImportNativeUseCase, RestoredEditUseCase};
+use crate::use_cases::import_odt_uc::ImportOdtUseCase;
 use crate::use_cases::import_plain_text_uc::ImportPlainTextUseCase;
+use crate::use_cases::import_rtf_uc::ImportRtfUseCase;
 use anyhow::Result;
 use common::event::{Event, Origin};
 
 use common::event::DocumentIoEvent::ExportHtml;
 use common::event::DocumentIoEvent::ExportLatex;
 use common::event::DocumentIoEvent::ExportMarkdown;
+use common::event::DocumentIoEvent::ExportNative;
 use common::event::DocumentIoEvent::ExportPlainText;
+use common::event::DocumentIoEvent::ExportRtf;
+use common::event::DocumentIoEvent::ImportNative;
 use common::event::DocumentIoEvent::ImportPlainText;
 
+use common::database::native_format::NativeUndoEntry;
 use common::long_operation::{LongOperationManager, OperationProgress};
+use common::undo_redo::{UndoRedoCommand, UndoRedoManager};
 use common::{database::db_context::DbContext, event::EventHub};
 use std::sync::Arc;
 
@@ -162,6 +197,42 @@ pub fn get_import_html_result(
     Ok(Some(result_dto))
 }
 
+pub fn import_docx(
+    db_context: &DbContext,
+    event_hub: &Arc<EventHub>,
+    long_operation_manager: &mut LongOperationManager,
+    dto: &ImportDocxDto,
+) -> Result<String> {
+    let uow_context = ImportDocxUnitOfWorkFactory::new(db_context, event_hub);
+    let uc = ImportDocxUseCase::new(Box::new(uow_context), dto);
+    let operation_id = long_operation_manager.start_operation(uc);
+    Ok(operation_id)
+}
+
+pub fn get_import_docx_progress(
+    long_operation_manager: &LongOperationManager,
+    operation_id: &str,
+) -> Option<OperationProgress> {
+    long_operation_manager.get_operation_progress(operation_id)
+}
+
+pub fn get_import_docx_result(
+    long_operation_manager: &LongOperationManager,
+    operation_id: &str,
+) -> Result<Option<ImportDocxResultDto>> {
+    // Get the operation result as a JSON string
+    let result_json = long_operation_manager.get_operation_result(operation_id);
+
+    // If there's no result, return None
+    if result_json.is_none() {
+        return Ok(None);
+    }
+    // Parse the JSON string into a ImportDocxResultDto
+    let result_dto: ImportDocxResultDto = serde_json::from_str(&result_json.unwrap())?;
+
+    Ok(Some(result_dto))
+}
+
 pub fn export_html(db_context: &DbContext, event_hub: &Arc<EventHub>) -> Result<ExportHtmlDto> {
     let uow_context = ExportHtmlUnitOfWorkFactory::new(db_context);
     let mut uc = ExportHtmlUseCase::new(Box::new(uow_context));
@@ -227,3 +298,255 @@ pub fn get_export_docx_result(
 
     Ok(Some(result_dto))
 }
+
+pub fn import_native(
+    db_context: &DbContext,
+    event_hub: &Arc<EventHub>,
+    dto: &ImportNativeDto,
+) -> Result<ImportNativeResultDto> {
+    let uow_context = ImportNativeUnitOfWorkFactory::new(db_context, event_hub);
+    let mut uc = ImportNativeUseCase::new(Box::new(uow_context));
+    let return_dto = uc.execute(dto)?;
+    // Notify that the handling manifest has been loaded
+    event_hub.send_event(Event {
+        origin: Origin::DocumentIo(ImportNative),
+        ids: vec![],
+        data: None,
+    });
+    Ok(return_dto)
+}
+
+/// Import a native document and replace `stack_id`'s history with the
+/// undo history saved in it. Returns whether a history was restored; when
+/// the file has no usable history the stack is simply cleared.
+pub fn import_native_with_history(
+    db_context: &DbContext,
+    event_hub: &Arc<EventHub>,
+    undo_redo_manager: &mut UndoRedoManager,
+    stack_id: u64,
+    dto: &ImportNativeDto,
+) -> Result<(ImportNativeResultDto, bool)> {
+    let uow_context = ImportNativeUnitOfWorkFactory::new(db_context, event_hub);
+    let mut uc = ImportNativeUseCase::new(Box::new(uow_context));
+    let (return_dto, history) = uc.execute_with_history(dto)?;
+    let restored = history.is_some();
+    let history = history.unwrap_or_default();
+    undo_redo_manager.set_stack_history(
+        stack_id,
+        restored_commands(db_context, event_hub, history.undo),
+        restored_commands(db_context, event_hub, history.redo),
+    )?;
+    // Notify that the handling manifest has been loaded
+    event_hub.send_event(Event {
+        origin: Origin::DocumentIo(ImportNative),
+        ids: vec![],
+        data: None,
+    });
+    Ok((return_dto, restored))
+}
+
+/// Undo commands replaying saved history entries.
+fn restored_commands(
+    db_context: &DbContext,
+    event_hub: &Arc<EventHub>,
+    entries: Vec<NativeUndoEntry>,
+) -> Vec<Box<dyn UndoRedoCommand>> {
+    entries
+        .into_iter()
+        .map(|entry| {
+            let uow_context = ImportNativeUnitOfWorkFactory::new(db_context, event_hub);
+            Box::new(RestoredEditUseCase::new(Box::new(uow_context), entry))
+                as Box<dyn UndoRedoCommand>
+        })
+        .collect()
+}
+
+pub fn export_native(db_context: &DbContext, event_hub: &Arc<EventHub>) -> Result<ExportNativeDto> {
+    let uow_context = ExportNativeUnitOfWorkFactory::new(db_context);
+    let mut uc = ExportNativeUseCase::new(Box::new(uow_context));
+    let return_dto = uc.execute()?;
+    // Notify that the handling manifest has been loaded
+    event_hub.send_event(Event {
+        origin: Origin::DocumentIo(ExportNative),
+        ids: vec![],
+        data: None,
+    });
+    Ok(return_dto)
+}
+
+/// Export a native document that also carries `stack_id`'s undo history.
+pub fn export_native_with_history(
+    db_context: &DbContext,
+    event_hub: &Arc<EventHub>,
+    undo_redo_manager: &UndoRedoManager,
+    stack_id: Option<u64>,
+) -> Result<ExportNativeDto> {
+    let uow_context = ExportNativeUnitOfWorkFactory::new(db_context);
+    let mut uc = ExportNativeUseCase::new(Box::new(uow_context));
+    let return_dto = uc.execute_with_history(undo_redo_manager, stack_id)?;
+    // Notify that the handling manifest has been loaded
+    event_hub.send_event(Event {
+        origin: Origin::DocumentIo(ExportNative),
+        ids: vec![],
+        data: None,
+    });
+    Ok(return_dto)
+}
+
+pub fn import_odt(
+    db_context: &DbContext,
+    event_hub: &Arc<EventHub>,
+    long_operation_manager: &mut LongOperationManager,
+    dto: &ImportOdtDto,
+) -> Result<String> {
+    let uow_context = ImportOdtUnitOfWorkFactory::new(db_context, event_hub);
+    let uc = ImportOdtUseCase::new(Box::new(uow_context), dto);
+    let operation_id = long_operation_manager.start_operation(uc);
+    Ok(operation_id)
+}
+
+pub fn get_import_odt_progress(
+    long_operation_manager: &LongOperationManager,
+    operation_id: &str,
+) -> Option<OperationProgress> {
+    long_operation_manager.get_operation_progress(operation_id)
+}
+
+pub fn get_import_odt_result(
+    long_operation_manager: &LongOperationManager,
+    operation_id: &str,
+) -> Result<Option<ImportOdtResultDto>> {
+    // Get the operation result as a JSON string
+    let result_json = long_operation_manager.get_operation_result(operation_id);
+
+    // If there's no result, return None
+    if result_json.is_none() {
+        return Ok(None);
+    }
+    // Parse the JSON string into a ImportOdtResultDto
+    let result_dto: ImportOdtResultDto = serde_json::from_str(&result_json.unwrap())?;
+
+    Ok(Some(result_dto))
+}
+
+pub fn export_odt(
+    db_context: &DbContext,
+    _event_hub: &Arc<EventHub>,
+    long_operation_manager: &mut LongOperationManager,
+    dto: &ExportOdtDto,
+) -> Result<String> {
+    let uow_context = ExportOdtUnitOfWorkFactory::new(db_context);
+    let uc = ExportOdtUseCase::new(Box::new(uow_context), dto);
+    let operation_id = long_operation_manager.start_operation(uc);
+    Ok(operation_id)
+}
+
+pub fn get_export_odt_progress(
+    long_operation_manager: &LongOperationManager,
+    operation_id: &str,
+) -> Option<OperationProgress> {
+    long_operation_manager.get_operation_progress(operation_id)
+}
+
+pub fn get_export_odt_result(
+    long_operation_manager: &LongOperationManager,
+    operation_id: &str,
+) -> Result<Option<ExportOdtResultDto>> {
+    // Get the operation result as a JSON string
+    let result_json = long_operation_manager.get_operation_result(operation_id);
+
+    // If there's no result, return None
+    if result_json.is_none() {
+        return Ok(None);
+    }
+    // Parse the JSON string into a ExportOdtResultDto
+    let result_dto: ExportOdtResultDto = serde_json::from_str(&result_json.unwrap())?;
+
+    Ok(Some(result_dto))
+}
+
+pub fn import_rtf(
+    db_context: &DbContext,
+    event_hub: &Arc<EventHub>,
+    long_operation_manager: &mut LongOperationManager,
+    dto: &ImportRtfDto,
+) -> Result<String> {
+    let uow_context = ImportRtfUnitOfWorkFactory::new(db_context, event_hub);
+    let uc = ImportRtfUseCase::new(Box::new(uow_context), dto);
+    let operation_id = long_operation_manager.start_operation(uc);
+    Ok(operation_id)
+}
+
+pub fn get_import_rtf_progress(
+    long_operation_manager: &LongOperationManager,
+    operation_id: &str,
+) -> Option<OperationProgress> {
+    long_operation_manager.get_operation_progress(operation_id)
+}
+
+pub fn get_import_rtf_result(
+    long_operation_manager: &LongOperationManager,
+    operation_id: &str,
+) -> Result<Option<ImportRtfResultDto>> {
+    // Get the operation result as a JSON string
+    let result_json = long_operation_manager.get_operation_result(operation_id);
+
+    // If there's no result, return None
+    if result_json.is_none() {
+        return Ok(None);
+    }
+    // Parse the JSON string into a ImportRtfResultDto
+    let result_dto: ImportRtfResultDto = serde_json::from_str(&result_json.unwrap())?;
+
+    Ok(Some(result_dto))
+}
+
+pub fn export_rtf(db_context: &DbContext, event_hub: &Arc<EventHub>) -> Result<ExportRtfDto> {
+    let uow_context = ExportRtfUnitOfWorkFactory::new(db_context);
+    let mut uc = ExportRtfUseCase::new(Box::new(uow_context));
+    let return_dto = uc.execute()?;
+    // Notify that the handling manifest has been loaded
+    event_hub.send_event(Event {
+        origin: Origin::DocumentIo(ExportRtf),
+        ids: vec![],
+        data: None,
+    });
+    Ok(return_dto)
+}
+
+pub fn export_epub(
+    db_context: &DbContext,
+    _event_hub: &Arc<EventHub>,
+    long_operation_manager: &mut LongOperationManager,
+    dto: &ExportEpubDto,
+) -> Result<String> {
+    let uow_context = ExportEpubUnitOfWorkFactory::new(db_context);
+    let html = ExportHtmlUseCase::new(Box::new(ExportHtmlUnitOfWorkFactory::new(db_context)));
+    let uc = ExportEpubUseCase::new(Box::new(uow_context), html, dto);
+    let operation_id = long_operation_manager.start_operation(uc);
+    Ok(operation_id)
+}
+
+pub fn get_export_epub_progress(
+    long_operation_manager: &LongOperationManager,
+    operation_id: &str,
+) -> Option<OperationProgress> {
+    long_operation_manager.get_operation_progress(operation_id)
+}
+
+pub fn get_export_epub_result(
+    long_operation_manager: &LongOperationManager,
+    operation_id: &str,
+) -> Result<Option<ExportEpubResultDto>> {
+    // Get the operation result as a JSON string
+    let result_json = long_operation_manager.get_operation_result(operation_id);
+
+    // If there's no result, return None
+    if result_json.is_none() {
+        return Ok(None);
+    }
+    // Parse the JSON string into a ExportEpubResultDto
+    let result_dto: ExportEpubResultDto = serde_json::from_str(&result_json.unwrap())?;
+
+    Ok(Some(result_dto))
+}
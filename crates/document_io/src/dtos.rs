@@ -31,6 +31,14 @@ pub struct ImportHtmlResultDto {
     pub block_count: i64,
 }
 #[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct ImportDocxDto {
+    pub docx_data: Vec<u8>,
+}
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct ImportDocxResultDto {
+    pub block_count: i64,
+}
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
 pub struct ExportHtmlDto {
     pub html_text: String,
 }
@@ -52,3 +60,57 @@ pub struct ExportDocxResultDto {
     pub file_path: String,
     pub paragraph_count: i64,
 }
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct ImportNativeDto {
+    pub native_data: String,
+}
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct ImportNativeResultDto {
+    pub block_count: i64,
+}
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct ExportNativeDto {
+    pub native_data: String,
+}
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct ImportOdtDto {
+    pub odt_data: Vec<u8>,
+}
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct ImportOdtResultDto {
+    pub block_count: i64,
+}
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct ExportOdtDto {
+    pub output_path: String,
+}
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct ExportOdtResultDto {
+    pub file_path: String,
+    pub paragraph_count: i64,
+}
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct ImportRtfDto {
+    pub rtf_text: String,
+}
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct ImportRtfResultDto {
+    pub block_count: i64,
+}
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct ExportRtfDto {
+    pub rtf_text: String,
+}
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct ExportEpubDto {
+    pub output_path: String,
+    pub heading_level: i64,
+    pub author: String,
+    pub language: String,
+    pub identifier: String,
+}
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct ExportEpubResultDto {
+    pub file_path: String,
+    pub chapter_count: i64,
+}
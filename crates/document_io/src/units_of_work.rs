@@ -1,10 +1,18 @@
 // Generated by Qleany v1.4.8 from feature_units_of_work_mod.tera
 
 pub(crate) mod export_docx_uow;
+pub(crate) mod export_epub_uow;
 pub(crate) mod export_html_uow;
 pub(crate) mod export_latex_uow;
 pub(crate) mod export_markdown_uow;
+pub(crate) mod export_native_uow;
+pub(crate) mod export_odt_uow;
 pub(crate) mod export_plain_text_uow;
+pub(crate) mod export_rtf_uow;
+pub(crate) mod import_docx_uow;
 pub(crate) mod import_html_uow;
 pub(crate) mod import_markdown_uow;
+pub(crate) mod import_native_uow;
+pub(crate) mod import_odt_uow;
 pub(crate) mod import_plain_text_uow;
+pub(crate) mod import_rtf_uow;
@@ -0,0 +1,69 @@
+// Generated by Qleany v1.4.8 from feature_use_case_uow.tera
+
+use crate::use_cases::export_epub_uc::{
+    ExportEpubUnitOfWorkFactoryTrait, ExportEpubUnitOfWorkTrait,
+};
+use anyhow::{Ok, Result};
+use common::database::QueryUnitOfWork;
+use common::database::{db_context::DbContext, transactions::Transaction};
+#[allow(unused_imports)]
+use common::entities::{Document, Resource, Root};
+#[allow(unused_imports)]
+use common::types;
+#[allow(unused_imports)]
+use common::types::EntityId;
+use std::sync::Mutex;
+
+pub struct ExportEpubUnitOfWork {
+    context: DbContext,
+    transaction: Mutex<Option<Transaction>>,
+}
+
+impl ExportEpubUnitOfWork {
+    pub fn new(db_context: &DbContext) -> Self {
+        ExportEpubUnitOfWork {
+            context: db_context.clone(),
+            transaction: Mutex::new(None),
+        }
+    }
+}
+
+impl QueryUnitOfWork for ExportEpubUnitOfWork {
+    fn begin_transaction(&self) -> Result<()> {
+        let mut transaction = self.transaction.lock().unwrap();
+        *transaction = Some(Transaction::begin_read_transaction(&self.context)?);
+        Ok(())
+    }
+
+    fn end_transaction(&self) -> Result<()> {
+        let mut transaction = self.transaction.lock().unwrap();
+        transaction.take().unwrap().end_read_transaction()?;
+        Ok(())
+    }
+
+    fn store(&self) -> std::sync::Arc<common::database::Store> {
+        self.context.get_store().clone()
+    }
+}
+#[macros::uow_action(entity = "Root", action = "GetRO", thread_safe = true)]
+#[macros::uow_action(entity = "Document", action = "GetRO", thread_safe = true)]
+#[macros::uow_action(entity = "Resource", action = "GetMultiRO", thread_safe = true)]
+impl ExportEpubUnitOfWorkTrait for ExportEpubUnitOfWork {}
+
+pub struct ExportEpubUnitOfWorkFactory {
+    context: DbContext,
+}
+
+impl ExportEpubUnitOfWorkFactory {
+    pub fn new(db_context: &DbContext) -> Self {
+        ExportEpubUnitOfWorkFactory {
+            context: db_context.clone(),
+        }
+    }
+}
+
+impl ExportEpubUnitOfWorkFactoryTrait for ExportEpubUnitOfWorkFactory {
+    fn create(&self) -> Box<dyn ExportEpubUnitOfWorkTrait> {
+        Box::new(ExportEpubUnitOfWork::new(&self.context))
+    }
+}
@@ -0,0 +1,70 @@
+// Generated by Qleany v1.4.8 from feature_use_case_uow.tera
+
+use crate::use_cases::export_native_uc::{
+    ExportNativeUnitOfWorkFactoryTrait, ExportNativeUnitOfWorkTrait,
+};
+use anyhow::{Ok, Result};
+use common::database::QueryUnitOfWork;
+use common::database::{db_context::DbContext, transactions::Transaction};
+#[allow(unused_imports)]
+use common::entities::{Document, Root};
+#[allow(unused_imports)]
+use common::types;
+#[allow(unused_imports)]
+use common::types::EntityId;
+use std::cell::RefCell;
+
+// Unit of work for ExportNative
+
+pub struct ExportNativeUnitOfWork {
+    context: DbContext,
+    transaction: RefCell<Option<Transaction>>,
+}
+
+impl ExportNativeUnitOfWork {
+    pub fn new(db_context: &DbContext) -> Self {
+        ExportNativeUnitOfWork {
+            context: db_context.clone(),
+            transaction: RefCell::new(None),
+        }
+    }
+}
+
+impl QueryUnitOfWork for ExportNativeUnitOfWork {
+    fn begin_transaction(&self) -> Result<()> {
+        self.transaction
+            .replace(Some(Transaction::begin_read_transaction(&self.context)?));
+        Ok(())
+    }
+
+    fn end_transaction(&self) -> Result<()> {
+        self.transaction.take().unwrap().end_read_transaction()?;
+        Ok(())
+    }
+
+    fn store(&self) -> std::sync::Arc<common::database::Store> {
+        self.context.get_store().clone()
+    }
+}
+
+#[macros::uow_action(entity = "Root", action = "GetRO")]
+#[macros::uow_action(entity = "Document", action = "GetRO")]
+impl ExportNativeUnitOfWorkTrait for ExportNativeUnitOfWork {}
+
+pub struct ExportNativeUnitOfWorkFactory {
+    context: DbContext,
+}
+
+impl ExportNativeUnitOfWorkFactory {
+    pub fn new(db_context: &DbContext) -> Self {
+        ExportNativeUnitOfWorkFactory {
+            context: db_context.clone(),
+        }
+    }
+}
+
+impl ExportNativeUnitOfWorkFactoryTrait for ExportNativeUnitOfWorkFactory {
+    fn create(&self) -> Box<dyn ExportNativeUnitOfWorkTrait> {
+        Box::new(ExportNativeUnitOfWork::new(&self.context))
+    }
+}
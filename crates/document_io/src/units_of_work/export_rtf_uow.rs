@@ -0,0 +1,74 @@
+// Generated by Qleany v1.4.8 from feature_use_case_uow.tera
+
+use crate::use_cases::export_rtf_uc::{ExportRtfUnitOfWorkFactoryTrait, ExportRtfUnitOfWorkTrait};
+use anyhow::{Ok, Result};
+use common::database::QueryUnitOfWork;
+use common::database::{db_context::DbContext, transactions::Transaction};
+#[allow(unused_imports)]
+use common::entities::{Block, Document, Frame, List, Resource, Root, Table, TableCell};
+#[allow(unused_imports)]
+use common::types;
+#[allow(unused_imports)]
+use common::types::EntityId;
+use std::cell::RefCell;
+
+// Unit of work for ExportRtf
+
+pub struct ExportRtfUnitOfWork {
+    context: DbContext,
+    transaction: RefCell<Option<Transaction>>,
+}
+
+impl ExportRtfUnitOfWork {
+    pub fn new(db_context: &DbContext) -> Self {
+        ExportRtfUnitOfWork {
+            context: db_context.clone(),
+            transaction: RefCell::new(None),
+        }
+    }
+}
+
+impl QueryUnitOfWork for ExportRtfUnitOfWork {
+    fn begin_transaction(&self) -> Result<()> {
+        self.transaction
+            .replace(Some(Transaction::begin_read_transaction(&self.context)?));
+        Ok(())
+    }
+
+    fn end_transaction(&self) -> Result<()> {
+        self.transaction.take().unwrap().end_read_transaction()?;
+        Ok(())
+    }
+
+    fn store(&self) -> std::sync::Arc<common::database::Store> {
+        self.context.get_store().clone()
+    }
+}
+
+#[macros::uow_action(entity = "Root", action = "GetRO")]
+#[macros::uow_action(entity = "Document", action = "GetRO")]
+#[macros::uow_action(entity = "Frame", action = "GetRO")]
+#[macros::uow_action(entity = "Block", action = "GetRO")]
+#[macros::uow_action(entity = "List", action = "GetRO")]
+#[macros::uow_action(entity = "Resource", action = "GetMultiRO")]
+#[macros::uow_action(entity = "Table", action = "GetRO")]
+#[macros::uow_action(entity = "TableCell", action = "GetMultiRO")]
+impl ExportRtfUnitOfWorkTrait for ExportRtfUnitOfWork {}
+
+pub struct ExportRtfUnitOfWorkFactory {
+    context: DbContext,
+}
+
+impl ExportRtfUnitOfWorkFactory {
+    pub fn new(db_context: &DbContext) -> Self {
+        ExportRtfUnitOfWorkFactory {
+            context: db_context.clone(),
+        }
+    }
+}
+
+impl ExportRtfUnitOfWorkFactoryTrait for ExportRtfUnitOfWorkFactory {
+    fn create(&self) -> Box<dyn ExportRtfUnitOfWorkTrait> {
+        Box::new(ExportRtfUnitOfWork::new(&self.context))
+    }
+}
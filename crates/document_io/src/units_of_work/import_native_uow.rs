@@ -0,0 +1,111 @@
+// Generated by Qleany v1.4.8 from feature_use_case_uow.tera
+
+use crate::use_cases::import_native_uc::{
+    ImportNativeUnitOfWorkFactoryTrait, ImportNativeUnitOfWorkTrait,
+};
+use anyhow::{Ok, Result};
+use common::database::CommandUnitOfWork;
+use common::database::{db_context::DbContext, transactions::Transaction};
+#[allow(unused_imports)]
+use common::entities::{Document, Root};
+use common::event::{AllEvent, DirectAccessEntity, Event, EventBuffer, EventHub, Origin};
+#[allow(unused_imports)]
+use common::types;
+#[allow(unused_imports)]
+use common::types::EntityId;
+use std::cell::RefCell;
+use std::sync::Arc;
+
+// Unit of work for ImportNative
+
+pub struct ImportNativeUnitOfWork {
+    context: DbContext,
+    transaction: Option<Transaction>,
+    event_hub: Arc<EventHub>,
+    event_buffer: RefCell<EventBuffer>,
+}
+
+impl ImportNativeUnitOfWork {
+    pub fn new(db_context: &DbContext, event_hub: &Arc<EventHub>) -> Self {
+        ImportNativeUnitOfWork {
+            context: db_context.clone(),
+            transaction: None,
+            event_hub: event_hub.clone(),
+            event_buffer: RefCell::new(EventBuffer::new()),
+        }
+    }
+}
+
+impl CommandUnitOfWork for ImportNativeUnitOfWork {
+    fn begin_transaction(&mut self) -> Result<()> {
+        self.transaction = Some(Transaction::begin_write_transaction(&self.context)?);
+        self.event_buffer.get_mut().begin_buffering();
+        Ok(())
+    }
+
+    fn commit(&mut self) -> Result<()> {
+        self.transaction.take().unwrap().commit()?;
+        for event in self.event_buffer.get_mut().flush() {
+            self.event_hub.send_event(event);
+        }
+        Ok(())
+    }
+
+    fn rollback(&mut self) -> Result<()> {
+        self.transaction.take().unwrap().rollback()?;
+        self.event_buffer.get_mut().discard();
+        Ok(())
+    }
+
+    fn create_savepoint(&self) -> Result<types::Savepoint> {
+        self.transaction.as_ref().unwrap().create_savepoint()
+    }
+
+    fn restore_to_savepoint(&mut self, savepoint: types::Savepoint) -> Result<()> {
+        let mut transaction = self.transaction.take().unwrap();
+        transaction.restore_to_savepoint(savepoint)?;
+
+        // Discard buffered events — savepoint restore invalidated them
+        self.event_buffer.get_mut().discard();
+
+        // Send Reset immediately (not buffered — UI must refresh now)
+        self.event_hub.send_event(Event {
+            origin: Origin::DirectAccess(DirectAccessEntity::All(AllEvent::Reset)),
+            ids: vec![],
+            data: None,
+        });
+
+        // Recreate the transaction after restoring to savepoint
+        self.transaction = Some(transaction);
+
+        Ok(())
+    }
+
+    fn store(&self) -> std::sync::Arc<common::database::Store> {
+        self.context.get_store().clone()
+    }
+}
+
+#[macros::uow_action(entity = "Root", action = "Get")]
+#[macros::uow_action(entity = "Document", action = "Get")]
+impl ImportNativeUnitOfWorkTrait for ImportNativeUnitOfWork {}
+
+pub struct ImportNativeUnitOfWorkFactory {
+    context: DbContext,
+    event_hub: Arc<EventHub>,
+}
+
+impl ImportNativeUnitOfWorkFactory {
+    pub fn new(db_context: &DbContext, event_hub: &Arc<EventHub>) -> Self {
+        ImportNativeUnitOfWorkFactory {
+            context: db_context.clone(),
+            event_hub: event_hub.clone(),
+        }
+    }
+}
+
+impl ImportNativeUnitOfWorkFactoryTrait for ImportNativeUnitOfWorkFactory {
+    fn create(&self) -> Box<dyn ImportNativeUnitOfWorkTrait> {
+        Box::new(ImportNativeUnitOfWork::new(&self.context, &self.event_hub))
+    }
+}
@@ -1,10 +1,26 @@
 // Generated by Qleany v1.4.8 from feature_use_cases_mod.tera
 
 pub(crate) mod export_docx_uc;
+pub(crate) mod export_epub_uc;
 pub(crate) mod export_html_uc;
 pub(crate) mod export_latex_uc;
 pub(crate) mod export_markdown_uc;
+pub(crate) mod export_native_uc;
+pub(crate) mod export_odt_uc;
 pub(crate) mod export_plain_text_uc;
+pub(crate) mod export_rtf_uc;
+pub(crate) mod import_docx_uc;
 pub(crate) mod import_html_uc;
 pub(crate) mod import_markdown_uc;
+pub(crate) mod import_native_uc;
+pub(crate) mod import_odt_uc;
 pub(crate) mod import_plain_text_uc;
+pub(crate) mod import_rtf_uc;
+
+pub(crate) mod docx_reader;
+pub(crate) mod epub_writer;
+pub(crate) mod export_helpers;
+pub(crate) mod import_helpers;
+pub(crate) mod odt_reader;
+pub(crate) mod odt_writer;
+pub(crate) mod package_helpers;
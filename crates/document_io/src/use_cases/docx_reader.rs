@@ -0,0 +1,522 @@
+//! WordprocessingML (DOCX) reader producing a [`RichDocument`].
+//!
+//! Reads `word/document.xml` plus the parts it refers to: relationships
+//! (hyperlink targets, image parts), `numbering.xml` (list styles) and
+//! `styles.xml` (heading styles). Elements are matched by local name so
+//! files using non-default namespace prefixes still parse.
+
+use crate::use_cases::package_helpers::{
+    attr, child, file_name, is, mime_type_for, read_binary_part, read_part,
+};
+use anyhow::{Result, anyhow};
+use common::entities::{Alignment, CharVerticalAlignment, ListStyle, UnderlineStyle};
+use common::format_runs::CharacterFormat;
+use common::parser_tools::css_color::normalize_css_color;
+use common::parser_tools::rich_document::{
+    RichBlock, RichCell, RichDocument, RichElement, RichInline, RichListItem, RichResource,
+    RichTable,
+};
+use roxmltree::Node;
+use std::collections::HashMap;
+use std::io::Cursor;
+
+/// English Metric Units per pixel at 96 DPI.
+const EMU_PER_PIXEL: i64 = 9525;
+
+struct DocxParts {
+    /// Relationship id → target, from `word/_rels/document.xml.rels`.
+    relationships: HashMap<String, String>,
+    /// `numId` → per-level list styles, from `numbering.xml`.
+    numbering: HashMap<String, HashMap<u32, ListStyle>>,
+    /// Paragraph style id → heading level, from `styles.xml`.
+    heading_styles: HashMap<String, i64>,
+}
+
+/// Parse DOCX bytes. Fails when the input is not a zip archive or has
+/// no `word/document.xml`.
+pub(crate) fn read_docx(bytes: &[u8]) -> Result<RichDocument> {
+    let mut archive =
+        zip::ZipArchive::new(Cursor::new(bytes)).map_err(|e| anyhow!("Not a DOCX file: {}", e))?;
+
+    let document_xml = read_part(&mut archive, "word/document.xml")?
+        .ok_or_else(|| anyhow!("Not a DOCX file: missing word/document.xml"))?;
+    let rels_xml = read_part(&mut archive, "word/_rels/document.xml.rels")?;
+    let numbering_xml = read_part(&mut archive, "word/numbering.xml")?;
+    let styles_xml = read_part(&mut archive, "word/styles.xml")?;
+
+    let parts = DocxParts {
+        relationships: rels_xml
+            .as_deref()
+            .map(parse_relationships)
+            .transpose()?
+            .unwrap_or_default(),
+        numbering: numbering_xml
+            .as_deref()
+            .map(parse_numbering)
+            .transpose()?
+            .unwrap_or_default(),
+        heading_styles: styles_xml
+            .as_deref()
+            .map(parse_heading_styles)
+            .transpose()?
+            .unwrap_or_default(),
+    };
+
+    let xml = roxmltree::Document::parse(&document_xml)
+        .map_err(|e| anyhow!("Invalid word/document.xml: {}", e))?;
+    let body = xml
+        .descendants()
+        .find(|n| is(n, "body"))
+        .ok_or_else(|| anyhow!("word/document.xml has no body"))?;
+
+    let mut reader = BodyReader {
+        parts: &parts,
+        images: Vec::new(),
+    };
+    let mut elements = Vec::new();
+    reader.read_container(body, &mut elements);
+
+    let mut resources = Vec::new();
+    for target in reader.images {
+        let path = part_path(&target);
+        if let Some(data) = read_binary_part(&mut archive, &path)? {
+            resources.push(RichResource {
+                name: file_name(&target).to_string(),
+                mime_type: mime_type_for(&target).to_string(),
+                data,
+            });
+        }
+    }
+
+    Ok(RichDocument {
+        elements,
+        resources,
+    })
+}
+
+/// Resolve a relationship target (relative to `word/`) to a zip path.
+fn part_path(target: &str) -> String {
+    match target.strip_prefix('/') {
+        Some(absolute) => absolute.to_string(),
+        None => format!("word/{}", target),
+    }
+}
+
+fn child_val<'a>(node: Node<'a, '_>, local_name: &str) -> Option<&'a str> {
+    child(node, local_name).and_then(|n| attr(n, "val"))
+}
+
+/// OOXML on/off property: present without `val`, or with a true value.
+fn toggle(props: Node, local_name: &str) -> Option<bool> {
+    let node = child(props, local_name)?;
+    Some(!matches!(
+        attr(node, "val"),
+        Some("0" | "false" | "off" | "none")
+    ))
+}
+
+fn parse_relationships(xml: &str) -> Result<HashMap<String, String>> {
+    let doc = roxmltree::Document::parse(xml)
+        .map_err(|e| anyhow!("Invalid document relationships: {}", e))?;
+    Ok(doc
+        .descendants()
+        .filter(|n| is(n, "Relationship"))
+        .filter_map(|n| Some((attr(n, "Id")?.to_string(), attr(n, "Target")?.to_string())))
+        .collect())
+}
+
+fn list_style_for(num_fmt: &str, level: u32) -> ListStyle {
+    match num_fmt {
+        "bullet" => match level % 3 {
+            0 => ListStyle::Disc,
+            1 => ListStyle::Circle,
+            _ => ListStyle::Square,
+        },
+        "lowerLetter" => ListStyle::LowerAlpha,
+        "upperLetter" => ListStyle::UpperAlpha,
+        "lowerRoman" => ListStyle::LowerRoman,
+        "upperRoman" => ListStyle::UpperRoman,
+        _ => ListStyle::Decimal,
+    }
+}
+
+fn parse_numbering(xml: &str) -> Result<HashMap<String, HashMap<u32, ListStyle>>> {
+    let doc = roxmltree::Document::parse(xml)
+        .map_err(|e| anyhow!("Invalid word/numbering.xml: {}", e))?;
+    let root = doc.root_element();
+
+    let mut abstract_levels: HashMap<&str, HashMap<u32, ListStyle>> = HashMap::new();
+    for abstract_num in root.children().filter(|n| is(n, "abstractNum")) {
+        let Some(id) = attr(abstract_num, "abstractNumId") else {
+            continue;
+        };
+        let levels = abstract_num
+            .children()
+            .filter(|n| is(n, "lvl"))
+            .filter_map(|lvl| {
+                let level: u32 = attr(lvl, "ilvl")?.parse().ok()?;
+                let fmt = child_val(lvl, "numFmt").unwrap_or("decimal");
+                Some((level, list_style_for(fmt, level)))
+            })
+            .collect();
+        abstract_levels.insert(id, levels);
+    }
+
+    Ok(root
+        .children()
+        .filter(|n| is(n, "num"))
+        .filter_map(|num| {
+            let num_id = attr(num, "numId")?;
+            let abstract_id = child_val(num, "abstractNumId")?;
+            let levels = abstract_levels
+                .get(abstract_id)
+                .cloned()
+                .unwrap_or_default();
+            Some((num_id.to_string(), levels))
+        })
+        .collect())
+}
+
+/// Heading level of a paragraph style, from its name ("heading 2") or
+/// its outline level.
+fn parse_heading_styles(xml: &str) -> Result<HashMap<String, i64>> {
+    let doc =
+        roxmltree::Document::parse(xml).map_err(|e| anyhow!("Invalid word/styles.xml: {}", e))?;
+    Ok(doc
+        .root_element()
+        .children()
+        .filter(|n| is(n, "style") && attr(*n, "type") == Some("paragraph"))
+        .filter_map(|style| {
+            let id = attr(style, "styleId")?;
+            let from_name = child_val(style, "name").and_then(heading_level_from_name);
+            let from_outline = child(style, "pPr")
+                .and_then(|ppr| child_val(ppr, "outlineLvl"))
+                .and_then(|v| v.parse::<i64>().ok())
+                .filter(|l| *l < 9)
+                .map(|l| l + 1);
+            Some((id.to_string(), from_name.or(from_outline)?))
+        })
+        .collect())
+}
+
+/// "heading 1", "Heading1", "Title" → level.
+fn heading_level_from_name(name: &str) -> Option<i64> {
+    let lower = name.to_ascii_lowercase();
+    if lower == "title" {
+        return Some(1);
+    }
+    let level: i64 = lower.strip_prefix("heading")?.trim().parse().ok()?;
+    (1..=6).contains(&level).then_some(level)
+}
+
+struct BodyReader<'p> {
+    parts: &'p DocxParts,
+    /// Relationship targets of embedded images, in first-use order.
+    images: Vec<String>,
+}
+
+impl BodyReader<'_> {
+    /// Read block-level content (`w:p`, `w:tbl`, content controls).
+    fn read_container(&mut self, node: Node, out: &mut Vec<RichElement>) {
+        for n in node.children().filter(|n| n.is_element()) {
+            match n.tag_name().name() {
+                "p" => out.extend(self.read_paragraph(n).into_iter().map(RichElement::Block)),
+                "tbl" => {
+                    if let Some(table) = self.read_table(n) {
+                        out.push(RichElement::Table(table));
+                    }
+                }
+                "sdt" => {
+                    if let Some(content) = child(n, "sdtContent") {
+                        self.read_container(content, out);
+                    }
+                }
+                "customXml" | "ins" => self.read_container(n, out),
+                _ => {}
+            }
+        }
+    }
+
+    /// Block-level content flattened to blocks, for table cells.
+    fn read_blocks(&mut self, node: Node) -> Vec<RichBlock> {
+        let mut elements = Vec::new();
+        self.read_container(node, &mut elements);
+        let mut blocks = Vec::new();
+        for element in elements {
+            match element {
+                RichElement::Block(b) => blocks.push(b),
+                RichElement::Table(t) => {
+                    blocks.extend(t.cells.into_iter().flat_map(|c| c.blocks));
+                }
+            }
+        }
+        blocks
+    }
+
+    /// A paragraph yields one block per line: `w:br` starts a new block
+    /// with the same paragraph properties, as `<br>` does in HTML import.
+    fn read_paragraph(&mut self, p: Node) -> Vec<RichBlock> {
+        let mut template = RichBlock::default();
+        if let Some(ppr) = child(p, "pPr") {
+            if let Some(style) = child_val(ppr, "pStyle") {
+                template.heading_level = self
+                    .parts
+                    .heading_styles
+                    .get(style)
+                    .copied()
+                    .or_else(|| heading_level_from_name(style));
+            }
+            if let Some(level) = child_val(ppr, "outlineLvl").and_then(|v| v.parse::<i64>().ok())
+                && level < 6
+            {
+                template.heading_level = Some(level + 1);
+            }
+            template.alignment = match child_val(ppr, "jc") {
+                Some("center") => Some(Alignment::Center),
+                Some("right" | "end") => Some(Alignment::Right),
+                Some("both" | "distribute") => Some(Alignment::Justify),
+                Some("left" | "start") => Some(Alignment::Left),
+                _ => None,
+            };
+            template.list = child(ppr, "numPr").and_then(|num_pr| self.list_item(num_pr));
+        }
+
+        let mut lines = vec![template.clone()];
+        self.read_inline_container(p, &CharacterFormat::default(), &template, &mut lines);
+        lines
+    }
+
+    fn list_item(&self, num_pr: Node) -> Option<RichListItem> {
+        let num_id = child_val(num_pr, "numId")?;
+        // numId 0 explicitly removes numbering.
+        if num_id == "0" {
+            return None;
+        }
+        let level: u32 = child_val(num_pr, "ilvl")
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(0);
+        let style = self
+            .parts
+            .numbering
+            .get(num_id)
+            .and_then(|levels| levels.get(&level).cloned())
+            .unwrap_or(ListStyle::Decimal);
+        Some(RichListItem {
+            key: num_id.to_string(),
+            style,
+            indent: level,
+        })
+    }
+
+    /// Read runs and run containers (hyperlinks, insertions, fields)
+    /// appending to the last block of `lines`.
+    fn read_inline_container(
+        &mut self,
+        node: Node,
+        base: &CharacterFormat,
+        template: &RichBlock,
+        lines: &mut Vec<RichBlock>,
+    ) {
+        for n in node.children().filter(|n| n.is_element()) {
+            match n.tag_name().name() {
+                "r" => self.read_run(n, base, template, lines),
+                "hyperlink" => {
+                    let href = attr(n, "id")
+                        .and_then(|id| self.parts.relationships.get(id).cloned())
+                        .or_else(|| attr(n, "anchor").map(|a| format!("#{}", a)));
+                    let mut format = base.clone();
+                    if let Some(href) = href {
+                        format.anchor_href = Some(href);
+                        format.is_anchor = Some(true);
+                    }
+                    self.read_inline_container(n, &format, template, lines);
+                }
+                "ins" | "smartTag" | "fldSimple" | "customXml" => {
+                    self.read_inline_container(n, base, template, lines)
+                }
+                "sdt" => {
+                    if let Some(content) = child(n, "sdtContent") {
+                        self.read_inline_container(content, base, template, lines);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn read_run(
+        &mut self,
+        r: Node,
+        base: &CharacterFormat,
+        template: &RichBlock,
+        lines: &mut Vec<RichBlock>,
+    ) {
+        let mut format = base.clone();
+        if let Some(rpr) = child(r, "rPr") {
+            apply_run_properties(rpr, &mut format);
+        }
+        for n in r.children().filter(|n| n.is_element()) {
+            let line = lines.last_mut().expect("paragraph has at least one line");
+            match n.tag_name().name() {
+                "t" => line.push_text(n.text().unwrap_or(""), &format),
+                "tab" => line.push_text("\t", &format),
+                "noBreakHyphen" => line.push_text("-", &format),
+                "br" | "cr" if !matches!(attr(n, "type"), Some("page" | "column")) => {
+                    lines.push(template.clone());
+                }
+                "drawing" => {
+                    if let Some(image) = self.read_drawing(n, &format) {
+                        line.inlines.push(image);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn read_drawing(&mut self, drawing: Node, format: &CharacterFormat) -> Option<RichInline> {
+        let blip = drawing.descendants().find(|n| is(n, "blip"))?;
+        let target = self.parts.relationships.get(attr(blip, "embed")?)?.clone();
+        let (width, height) = drawing
+            .descendants()
+            .find(|n| is(n, "extent"))
+            .map(|extent| {
+                let emu = |name| {
+                    attr(extent, name)
+                        .and_then(|v| v.parse::<i64>().ok())
+                        .unwrap_or(0)
+                };
+                (emu("cx") / EMU_PER_PIXEL, emu("cy") / EMU_PER_PIXEL)
+            })
+            .unwrap_or((0, 0));
+        if !self.images.contains(&target) {
+            self.images.push(target.clone());
+        }
+        Some(RichInline::Image {
+            name: file_name(&target).to_string(),
+            width,
+            height,
+            format: format.clone(),
+        })
+    }
+
+    fn read_table(&mut self, tbl: Node) -> Option<RichTable> {
+        let grid_columns = child(tbl, "tblGrid")
+            .map(|g| g.children().filter(|n| is(n, "gridCol")).count())
+            .unwrap_or(0);
+
+        let mut cells: Vec<RichCell> = Vec::new();
+        let mut columns = grid_columns;
+        let rows: Vec<Node> = tbl.children().filter(|n| is(n, "tr")).collect();
+
+        for (row, tr) in rows.iter().enumerate() {
+            let mut column: usize = child(*tr, "trPr")
+                .and_then(|p| child_val(p, "gridBefore"))
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(0);
+            for tc in tr.children().filter(|n| is(n, "tc")) {
+                let tc_pr = child(tc, "tcPr");
+                let column_span: usize = tc_pr
+                    .and_then(|p| child_val(p, "gridSpan"))
+                    .and_then(|v| v.parse().ok())
+                    .filter(|s| *s > 0)
+                    .unwrap_or(1);
+                let v_merge = tc_pr.and_then(|p| child(p, "vMerge"));
+                let continues = v_merge.is_some_and(|m| attr(m, "val") != Some("restart"));
+
+                // A continued vertical merge extends the cell above.
+                let above = continues
+                    .then(|| {
+                        cells
+                            .iter_mut()
+                            .rev()
+                            .find(|c| c.column == column && c.row + c.row_span == row && row > 0)
+                    })
+                    .flatten();
+                match above {
+                    Some(origin) => origin.row_span += 1,
+                    None => {
+                        let blocks = self.read_blocks(tc);
+                        cells.push(RichCell {
+                            row,
+                            column,
+                            row_span: 1,
+                            column_span,
+                            blocks,
+                        });
+                    }
+                }
+                column += column_span;
+            }
+            columns = columns.max(column);
+        }
+
+        if rows.is_empty() || columns == 0 {
+            return None;
+        }
+        Some(RichTable {
+            rows: rows.len(),
+            columns,
+            cells,
+        })
+    }
+}
+
+/// `w:color`/`w:fill` values are bare `RRGGBB` digits or `auto`.
+fn docx_color(value: &str) -> Option<String> {
+    if value.eq_ignore_ascii_case("auto") {
+        return None;
+    }
+    normalize_css_color(&format!("#{}", value))
+}
+
+fn apply_run_properties(rpr: Node, format: &mut CharacterFormat) {
+    // Explicit "off" values clear inherited formatting rather than
+    // producing `Some(false)` runs.
+    if let Some(bold) = toggle(rpr, "b") {
+        format.font_bold = bold.then_some(true);
+    }
+    if let Some(italic) = toggle(rpr, "i") {
+        format.font_italic = italic.then_some(true);
+    }
+    if let Some(strike) = toggle(rpr, "strike").or_else(|| toggle(rpr, "dstrike")) {
+        format.font_strikeout = strike.then_some(true);
+    }
+    if let Some(u) = child(rpr, "u") {
+        let style = match attr(u, "val").unwrap_or("single") {
+            "none" => None,
+            "dash" | "dashLong" | "dashedHeavy" | "dashLongHeavy" => {
+                Some(UnderlineStyle::DashUnderline)
+            }
+            "dotted" | "dottedHeavy" => Some(UnderlineStyle::DotLine),
+            "dotDash" | "dashDotHeavy" => Some(UnderlineStyle::DashDotLine),
+            "dotDotDash" | "dashDotDotHeavy" => Some(UnderlineStyle::DashDotDotLine),
+            "wave" | "wavyHeavy" | "wavyDouble" => Some(UnderlineStyle::WaveUnderline),
+            _ => Some(UnderlineStyle::SingleUnderline),
+        };
+        format.font_underline = style.is_some().then_some(true);
+        format.underline_style = style.filter(|s| *s != UnderlineStyle::SingleUnderline);
+        if let Some(color) = attr(u, "color") {
+            format.underline_color = docx_color(color);
+        }
+    }
+    if let Some(color) = child_val(rpr, "color") {
+        format.foreground_color = docx_color(color);
+    }
+    if let Some(fill) = child(rpr, "shd").and_then(|shd| attr(shd, "fill")) {
+        format.background_color = docx_color(fill);
+    }
+    if let Some(fonts) = child(rpr, "rFonts")
+        && let Some(family) = attr(fonts, "ascii").or_else(|| attr(fonts, "hAnsi"))
+    {
+        format.font_family = Some(family.to_string());
+    }
+    if let Some(half_points) = child_val(rpr, "sz").and_then(|v| v.parse::<i64>().ok()) {
+        format.font_point_size = Some(half_points / 2);
+    }
+    match child_val(rpr, "vertAlign") {
+        Some("superscript") => format.vertical_alignment = Some(CharVerticalAlignment::SuperScript),
+        Some("subscript") => format.vertical_alignment = Some(CharVerticalAlignment::SubScript),
+        _ => {}
+    }
+}
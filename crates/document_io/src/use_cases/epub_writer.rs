@@ -0,0 +1,333 @@
+//! EPUB 3 writer packaging XHTML body parts into chapters.
+//!
+//! The body parts come from the HTML exporter, one top-level element
+//! each. [`split_chapters`] starts a new chapter at every heading at or
+//! above the configured level and collects a table of contents from all
+//! headings; [`write_epub`] writes the OCF container with the package
+//! document, the navigation document and images under `OEBPS/images/`.
+
+use anyhow::Result;
+use std::fmt::Write as _;
+use std::io::{Cursor, Write};
+use zip::write::FileOptions;
+
+const MIMETYPE: &str = "application/epub+zip";
+
+const CONTAINER_XML: &str = concat!(
+    r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+    "\n",
+    r#"<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">"#,
+    r#"<rootfiles><rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/></rootfiles>"#,
+    "</container>"
+);
+
+pub(crate) struct EpubMetadata {
+    pub title: String,
+    pub author: String,
+    pub language: String,
+    pub identifier: String,
+    /// `dcterms:modified`, formatted as `CCYY-MM-DDThh:mm:ssZ`.
+    pub modified: String,
+}
+
+/// Chapter and table of contents texts are already XHTML-escaped.
+pub(crate) struct EpubChapter {
+    pub title: String,
+    pub body: String,
+}
+
+/// A navigation entry pointing at a heading (`anchor`) or at the start
+/// of a chapter.
+pub(crate) struct EpubTocEntry {
+    pub level: i64,
+    pub chapter: usize,
+    pub anchor: Option<String>,
+    pub text: String,
+}
+
+pub(crate) struct EpubImage {
+    pub name: String,
+    pub mime_type: String,
+    pub data: Vec<u8>,
+}
+
+/// Group body parts into chapters. A heading of level `1..=heading_level`
+/// opens a new chapter; content before the first one becomes a chapter
+/// titled `default_title`. Every heading gets an `id` so the navigation
+/// document can link to it, and image sources are rewritten to point
+/// into `images/`. Always returns at least one chapter.
+pub(crate) fn split_chapters(
+    parts: Vec<String>,
+    heading_level: i64,
+    default_title: &str,
+) -> (Vec<EpubChapter>, Vec<EpubTocEntry>) {
+    let default_title = escape_xml(default_title);
+    let default_title = default_title.as_str();
+    let mut chapters: Vec<EpubChapter> = Vec::new();
+    let mut toc = Vec::new();
+    let mut headings = 0;
+
+    for part in parts {
+        let mut part = part.replace("<img src=\"", "<img src=\"images/");
+        let level = heading_tag_level(&part);
+        let mut anchor = None;
+        if let Some(level) = level {
+            headings += 1;
+            let id = format!("h-{}", headings);
+            part.insert_str(3, &format!(" id=\"{}\"", id));
+            anchor = Some((level, id));
+        }
+
+        match anchor {
+            Some((level, id)) => {
+                let text = heading_text(&part);
+                if level <= heading_level || chapters.is_empty() {
+                    chapters.push(EpubChapter {
+                        title: if text.is_empty() {
+                            default_title.to_string()
+                        } else {
+                            text.clone()
+                        },
+                        body: String::new(),
+                    });
+                }
+                toc.push(EpubTocEntry {
+                    level,
+                    chapter: chapters.len() - 1,
+                    anchor: Some(id),
+                    text,
+                });
+            }
+            None if chapters.is_empty() => {
+                chapters.push(EpubChapter {
+                    title: default_title.to_string(),
+                    body: String::new(),
+                });
+                toc.push(EpubTocEntry {
+                    level: 1,
+                    chapter: 0,
+                    anchor: None,
+                    text: default_title.to_string(),
+                });
+            }
+            None => {}
+        }
+        chapters.last_mut().unwrap().body.push_str(&part);
+    }
+
+    if chapters.is_empty() {
+        chapters.push(EpubChapter {
+            title: default_title.to_string(),
+            body: String::new(),
+        });
+        toc.push(EpubTocEntry {
+            level: 1,
+            chapter: 0,
+            anchor: None,
+            text: default_title.to_string(),
+        });
+    }
+    for entry in &mut toc {
+        if entry.text.is_empty() {
+            entry.text = chapters[entry.chapter].title.clone();
+        }
+    }
+    (chapters, toc)
+}
+
+/// Level of a part that is a heading element (`<h1`..`<h6`).
+fn heading_tag_level(part: &str) -> Option<i64> {
+    let bytes = part.as_bytes();
+    if bytes.len() > 3
+        && bytes[0] == b'<'
+        && bytes[1] == b'h'
+        && (b'1'..=b'6').contains(&bytes[2])
+        && matches!(bytes[3], b'>' | b' ')
+    {
+        Some((bytes[2] - b'0') as i64)
+    } else {
+        None
+    }
+}
+
+/// Text content of a rendered heading, with markup removed. Entities are
+/// kept as they are, which is valid in the XHTML documents we write.
+fn heading_text(part: &str) -> String {
+    let mut text = String::new();
+    let mut in_tag = false;
+    for c in part.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' => in_tag = false,
+            _ if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    text.trim().to_string()
+}
+
+/// Serialize the book as an EPUB 3 container.
+pub(crate) fn write_epub(
+    meta: &EpubMetadata,
+    chapters: &[EpubChapter],
+    toc: &[EpubTocEntry],
+    images: &[EpubImage],
+) -> Result<Vec<u8>> {
+    let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    let stored = FileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    let deflated = FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+
+    // The mimetype must be the first entry and uncompressed so readers
+    // can identify the file by its leading bytes.
+    zip.start_file("mimetype", stored)?;
+    zip.write_all(MIMETYPE.as_bytes())?;
+    zip.start_file("META-INF/container.xml", deflated)?;
+    zip.write_all(CONTAINER_XML.as_bytes())?;
+    zip.start_file("OEBPS/content.opf", deflated)?;
+    zip.write_all(package_xml(meta, chapters.len(), images).as_bytes())?;
+    zip.start_file("OEBPS/nav.xhtml", deflated)?;
+    zip.write_all(nav_xhtml(meta, toc).as_bytes())?;
+    for (index, chapter) in chapters.iter().enumerate() {
+        zip.start_file(format!("OEBPS/{}", chapter_file(index)), deflated)?;
+        zip.write_all(xhtml_document(&meta.language, &chapter.title, &chapter.body).as_bytes())?;
+    }
+    for image in images {
+        zip.start_file(format!("OEBPS/images/{}", image.name), stored)?;
+        zip.write_all(&image.data)?;
+    }
+
+    let cursor = zip.finish()?;
+    Ok(cursor.into_inner())
+}
+
+fn chapter_file(index: usize) -> String {
+    format!("chapter-{}.xhtml", index + 1)
+}
+
+fn package_xml(meta: &EpubMetadata, chapter_count: usize, images: &[EpubImage]) -> String {
+    let mut metadata = format!(
+        concat!(
+            r#"<dc:identifier id="pub-id">{}</dc:identifier>"#,
+            "<dc:title>{}</dc:title>",
+            "<dc:language>{}</dc:language>",
+        ),
+        escape_xml(&meta.identifier),
+        escape_xml(&meta.title),
+        escape_xml(&meta.language),
+    );
+    if !meta.author.is_empty() {
+        let _ = write!(
+            metadata,
+            "<dc:creator>{}</dc:creator>",
+            escape_xml(&meta.author)
+        );
+    }
+    let _ = write!(
+        metadata,
+        r#"<meta property="dcterms:modified">{}</meta>"#,
+        meta.modified
+    );
+
+    let mut manifest = String::from(
+        r#"<item id="nav" href="nav.xhtml" media-type="application/xhtml+xml" properties="nav"/>"#,
+    );
+    let mut spine = String::new();
+    for index in 0..chapter_count {
+        let _ = write!(
+            manifest,
+            r#"<item id="chapter-{}" href="{}" media-type="application/xhtml+xml"/>"#,
+            index + 1,
+            chapter_file(index)
+        );
+        let _ = write!(spine, r#"<itemref idref="chapter-{}"/>"#, index + 1);
+    }
+    for (index, image) in images.iter().enumerate() {
+        let _ = write!(
+            manifest,
+            r#"<item id="image-{}" href="images/{}" media-type="{}"/>"#,
+            index + 1,
+            escape_xml(&image.name),
+            escape_xml(&image.mime_type)
+        );
+    }
+
+    format!(
+        concat!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+            "\n",
+            r#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="pub-id">"#,
+            r#"<metadata xmlns:dc="http://purl.org/dc/elements/1.1/">{}</metadata>"#,
+            "<manifest>{}</manifest>",
+            "<spine>{}</spine>",
+            "</package>"
+        ),
+        metadata, manifest, spine
+    )
+}
+
+/// Navigation document with the table of contents as nested `<ol>`
+/// lists. Skipped heading levels nest one step deeper, not several.
+fn nav_xhtml(meta: &EpubMetadata, toc: &[EpubTocEntry]) -> String {
+    let mut list = String::new();
+    let mut open_levels: Vec<i64> = Vec::new();
+    let mut depth = 0;
+    for entry in toc {
+        while open_levels.last().is_some_and(|&top| top >= entry.level) {
+            open_levels.pop();
+        }
+        open_levels.push(entry.level);
+        let new_depth = open_levels.len();
+        if new_depth > depth {
+            list.push_str("<ol>");
+        } else {
+            list.push_str("</li>");
+            for _ in new_depth..depth {
+                list.push_str("</ol></li>");
+            }
+        }
+        depth = new_depth;
+
+        let mut href = chapter_file(entry.chapter);
+        if let Some(anchor) = &entry.anchor {
+            href.push('#');
+            href.push_str(anchor);
+        }
+        let _ = write!(list, r#"<li><a href="{}">{}</a>"#, href, entry.text);
+    }
+    if depth > 0 {
+        list.push_str("</li>");
+        for _ in 1..depth {
+            list.push_str("</ol></li>");
+        }
+        list.push_str("</ol>");
+    }
+
+    let body = format!(
+        r#"<nav epub:type="toc" id="toc"><h1>{}</h1>{}</nav>"#,
+        escape_xml(&meta.title),
+        list
+    );
+    xhtml_document(&meta.language, &escape_xml(&meta.title), &body)
+}
+
+fn xhtml_document(language: &str, title: &str, body: &str) -> String {
+    format!(
+        concat!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+            "\n<!DOCTYPE html>\n",
+            r#"<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops" xml:lang="{0}" lang="{0}">"#,
+            r#"<head><meta charset="utf-8"/><title>{1}</title></head>"#,
+            "<body>{2}</body></html>"
+        ),
+        escape_xml(language),
+        title,
+        body
+    )
+}
+
+fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
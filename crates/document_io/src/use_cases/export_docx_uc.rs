@@ -7,6 +7,7 @@ use common::database::rope_helpers::block_content_via_store;
 use common::entities::{Block, Document, Frame, List, Root, Table, TableCell};
 use common::format_runs::InlineContent;
 use common::long_operation::LongOperation;
+use common::parser_tools::css_color::rgb_hex_digits;
 use common::types::{EntityId, ROOT_ENTITY_ID};
 use std::collections::HashSet;
 use std::sync::Arc;
@@ -224,6 +225,7 @@ impl LongOperation for ExportDocxUseCase {
                     if elem.fmt_font_family.as_deref() == Some("monospace") {
                         run = run.fonts(RunFonts::new().ascii("Courier New"));
                     }
+                    run = apply_run_colors(run, elem);
 
                     paragraph = paragraph.add_run(run);
                 }
@@ -393,6 +395,7 @@ impl ExportDocxUseCase {
                                 if elem.fmt_font_family.as_deref() == Some("monospace") {
                                     run = run.fonts(RunFonts::new().ascii("Courier New"));
                                 }
+                                run = apply_run_colors(run, elem);
 
                                 paragraph = paragraph.add_run(run);
                             }
@@ -431,3 +434,26 @@ impl ExportDocxUseCase {
         Ok(docx_table)
     }
 }
+
+/// Foreground color as `w:color` and background color as run shading.
+/// Underline colors have no builder in docx-rs and are dropped.
+fn apply_run_colors(
+    mut run: docx_rs::Run,
+    elem: &common::format_runs::InlineSegment,
+) -> docx_rs::Run {
+    if let Some(hex) = elem
+        .fmt_foreground_color
+        .as_deref()
+        .and_then(rgb_hex_digits)
+    {
+        run = run.color(hex);
+    }
+    if let Some(hex) = elem
+        .fmt_background_color
+        .as_deref()
+        .and_then(rgb_hex_digits)
+    {
+        run = run.shading(docx_rs::Shading::new().fill(hex));
+    }
+    run
+}
@@ -0,0 +1,177 @@
+// Generated by Qleany v1.5.1 from feature_use_case.tera
+use crate::ExportEpubDto;
+use crate::ExportEpubResultDto;
+use crate::use_cases::epub_writer::{EpubImage, EpubMetadata, split_chapters, write_epub};
+use crate::use_cases::export_html_uc::ExportHtmlUseCase;
+use anyhow::{Result, anyhow};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use common::database::QueryUnitOfWork;
+use common::entities::{Document, Resource, ResourceType, Root};
+use common::long_operation::LongOperation;
+use common::types::{EntityId, ROOT_ENTITY_ID};
+use std::sync::Arc;
+
+pub trait ExportEpubUnitOfWorkFactoryTrait: Send + Sync {
+    fn create(&self) -> Box<dyn ExportEpubUnitOfWorkTrait>;
+}
+
+#[macros::uow_action(entity = "Root", action = "GetRO", thread_safe = true)]
+#[macros::uow_action(entity = "Document", action = "GetRO", thread_safe = true)]
+#[macros::uow_action(entity = "Resource", action = "GetMultiRO", thread_safe = true)]
+pub trait ExportEpubUnitOfWorkTrait: QueryUnitOfWork + Send + Sync {}
+
+pub struct ExportEpubUseCase {
+    uow_factory: Box<dyn ExportEpubUnitOfWorkFactoryTrait>,
+    /// Renders the chapter bodies; EPUB content documents are XHTML.
+    html: ExportHtmlUseCase,
+    dto: ExportEpubDto,
+}
+
+impl ExportEpubUseCase {
+    pub fn new(
+        uow_factory: Box<dyn ExportEpubUnitOfWorkFactoryTrait>,
+        html: ExportHtmlUseCase,
+        dto: &ExportEpubDto,
+    ) -> Self {
+        ExportEpubUseCase {
+            uow_factory,
+            html,
+            dto: dto.clone(),
+        }
+    }
+}
+
+impl LongOperation for ExportEpubUseCase {
+    type Output = ExportEpubResultDto;
+
+    fn execute(
+        &self,
+        progress_callback: Box<dyn Fn(common::long_operation::OperationProgress) + Send>,
+        cancel_flag: Arc<std::sync::atomic::AtomicBool>,
+    ) -> Result<Self::Output> {
+        use std::sync::atomic::Ordering;
+
+        // Validate output path
+        let output_path = std::path::Path::new(&self.dto.output_path);
+        if let Some(parent) = output_path.parent()
+            && !parent.as_os_str().is_empty()
+            && !parent.exists()
+        {
+            return Err(anyhow!(
+                "Output directory does not exist: '{}'",
+                parent.display()
+            ));
+        }
+
+        progress_callback(common::long_operation::OperationProgress::new(
+            0.0,
+            Some("Starting EPUB export...".to_string()),
+        ));
+
+        // Step 1: Title and image resources
+        let uow = self.uow_factory.create();
+        uow.begin_transaction()?;
+        let collected = collect_title_and_images(&*uow);
+        uow.end_transaction()?;
+        let (title, images) = collected?;
+
+        if cancel_flag.load(Ordering::Relaxed) {
+            return Err(anyhow!("Operation was cancelled"));
+        }
+
+        progress_callback(common::long_operation::OperationProgress::new(
+            20.0,
+            Some("Rendering chapters...".to_string()),
+        ));
+
+        // Step 2: Render the body and split it into chapters
+        let parts = self.html.render_body_parts()?;
+        let heading_level = if self.dto.heading_level > 0 {
+            self.dto.heading_level.min(6)
+        } else {
+            1
+        };
+        let title = if title.trim().is_empty() {
+            "Untitled".to_string()
+        } else {
+            title
+        };
+        let (chapters, toc) = split_chapters(parts, heading_level, &title);
+
+        if cancel_flag.load(Ordering::Relaxed) {
+            return Err(anyhow!("Operation was cancelled"));
+        }
+
+        progress_callback(common::long_operation::OperationProgress::new(
+            60.0,
+            Some("Writing EPUB file...".to_string()),
+        ));
+
+        // Step 3: Package
+        let metadata = EpubMetadata {
+            title,
+            author: self.dto.author.clone(),
+            language: if self.dto.language.is_empty() {
+                "en".to_string()
+            } else {
+                self.dto.language.clone()
+            },
+            identifier: if self.dto.identifier.is_empty() {
+                format!("urn:uuid:{}", uuid::Uuid::new_v4())
+            } else {
+                self.dto.identifier.clone()
+            },
+            modified: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+        };
+        let bytes = write_epub(&metadata, &chapters, &toc, &images)?;
+        std::fs::write(&self.dto.output_path, bytes).map_err(|e| {
+            anyhow!(
+                "Failed to create output file '{}': {}",
+                self.dto.output_path,
+                e
+            )
+        })?;
+
+        progress_callback(common::long_operation::OperationProgress::new(
+            100.0,
+            Some("completed".to_string()),
+        ));
+
+        Ok(ExportEpubResultDto {
+            file_path: self.dto.output_path.clone(),
+            chapter_count: chapters.len() as i64,
+        })
+    }
+}
+
+/// Document title and the image resources to package, in document order.
+fn collect_title_and_images(
+    uow: &dyn ExportEpubUnitOfWorkTrait,
+) -> Result<(String, Vec<EpubImage>)> {
+    let root = uow
+        .get_root(&ROOT_ENTITY_ID)?
+        .ok_or_else(|| anyhow!("Root entity not found"))?;
+    let document = uow
+        .get_document(&root.document)?
+        .ok_or_else(|| anyhow!("Root has no associated Document"))?;
+
+    let mut images: Vec<EpubImage> = Vec::new();
+    for resource in uow
+        .get_resource_multi(&document.resources)?
+        .into_iter()
+        .flatten()
+    {
+        if resource.resource_type != ResourceType::Image
+            || images.iter().any(|i| i.name == resource.name)
+        {
+            continue;
+        }
+        images.push(EpubImage {
+            data: BASE64.decode(&resource.data_base64)?,
+            name: resource.name,
+            mime_type: resource.mime_type,
+        });
+    }
+    Ok((document.title, images))
+}
@@ -0,0 +1,249 @@
+//! Shared document reader for the rich format exporters (ODT, RTF).
+//!
+//! [`collect_document`] walks the main frame's `child_order` — blocks,
+//! table anchors and nested frames in flow order — and returns a
+//! [`RichDocument`] that format writers serialize without touching the
+//! store again.
+
+use anyhow::{Result, anyhow};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use common::database::Store;
+use common::database::rope_helpers::block_content_via_store;
+use common::entities::{Block, Document, Frame, List, Resource, Root, Table, TableCell};
+use common::format_runs::{InlineContent, character_format_from_segment};
+use common::format_runs_query::inline_segments_for_block;
+use common::parser_tools::rich_document::{
+    RichBlock, RichCell, RichDocument, RichElement, RichInline, RichListItem, RichResource,
+    RichTable,
+};
+use common::types::{EntityId, ROOT_ENTITY_ID};
+use std::sync::Arc;
+
+/// Read-only UoW operations needed by [`collect_document`]. Implemented
+/// for each exporter's boxed UoW trait with [`impl_rich_export_source!`].
+pub(crate) trait RichExportSource {
+    fn rxs_store(&self) -> Arc<Store>;
+    fn rxs_get_root(&self, id: EntityId) -> Result<Option<Root>>;
+    fn rxs_get_document(&self, id: EntityId) -> Result<Option<Document>>;
+    fn rxs_get_frame(&self, id: EntityId) -> Result<Option<Frame>>;
+    fn rxs_get_block(&self, id: EntityId) -> Result<Option<Block>>;
+    fn rxs_get_list(&self, id: EntityId) -> Result<Option<List>>;
+    fn rxs_get_table(&self, id: EntityId) -> Result<Option<Table>>;
+    fn rxs_get_table_cells(&self, ids: &[EntityId]) -> Result<Vec<Option<TableCell>>>;
+    fn rxs_get_resources(&self, ids: &[EntityId]) -> Result<Vec<Option<Resource>>>;
+}
+
+macro_rules! impl_rich_export_source {
+    ($trait_type:ty) => {
+        impl $crate::use_cases::export_helpers::RichExportSource for Box<$trait_type> {
+            fn rxs_store(&self) -> std::sync::Arc<common::database::Store> {
+                (**self).store()
+            }
+            fn rxs_get_root(&self, id: EntityId) -> Result<Option<Root>> {
+                (**self).get_root(&id)
+            }
+            fn rxs_get_document(&self, id: EntityId) -> Result<Option<Document>> {
+                (**self).get_document(&id)
+            }
+            fn rxs_get_frame(&self, id: EntityId) -> Result<Option<Frame>> {
+                (**self).get_frame(&id)
+            }
+            fn rxs_get_block(&self, id: EntityId) -> Result<Option<Block>> {
+                (**self).get_block(&id)
+            }
+            fn rxs_get_list(&self, id: EntityId) -> Result<Option<List>> {
+                (**self).get_list(&id)
+            }
+            fn rxs_get_table(&self, id: EntityId) -> Result<Option<Table>> {
+                (**self).get_table(&id)
+            }
+            fn rxs_get_table_cells(&self, ids: &[EntityId]) -> Result<Vec<Option<TableCell>>> {
+                (**self).get_table_cell_multi(ids)
+            }
+            fn rxs_get_resources(&self, ids: &[EntityId]) -> Result<Vec<Option<Resource>>> {
+                (**self).get_resource_multi(ids)
+            }
+        }
+    };
+}
+
+pub(crate) use impl_rich_export_source;
+
+/// Read the whole document in flow order. Returns the model and the
+/// document title. Resources are only included when an image refers to
+/// them.
+pub(crate) fn collect_document<U: RichExportSource + ?Sized>(
+    uow: &U,
+) -> Result<(RichDocument, String)> {
+    let root = uow
+        .rxs_get_root(ROOT_ENTITY_ID)?
+        .ok_or_else(|| anyhow!("Root entity not found"))?;
+    let document = uow
+        .rxs_get_document(root.document)?
+        .ok_or_else(|| anyhow!("Root has no associated Document"))?;
+    let main_frame_id = *document
+        .frames
+        .first()
+        .ok_or_else(|| anyhow!("Document has no frames"))?;
+
+    let mut collector = Collector {
+        uow,
+        store: uow.rxs_store(),
+        image_names: Vec::new(),
+    };
+    let mut elements = Vec::new();
+    collector.collect_frame(main_frame_id, &mut elements)?;
+
+    let mut resources = Vec::new();
+    for resource in uow
+        .rxs_get_resources(&document.resources)?
+        .into_iter()
+        .flatten()
+    {
+        if collector.image_names.contains(&resource.name)
+            && !resources
+                .iter()
+                .any(|r: &RichResource| r.name == resource.name)
+        {
+            resources.push(RichResource {
+                name: resource.name,
+                mime_type: resource.mime_type,
+                data: BASE64.decode(&resource.data_base64)?,
+            });
+        }
+    }
+
+    Ok((
+        RichDocument {
+            elements,
+            resources,
+        },
+        document.title,
+    ))
+}
+
+struct Collector<'a, U: RichExportSource + ?Sized> {
+    uow: &'a U,
+    store: Arc<Store>,
+    image_names: Vec<String>,
+}
+
+impl<U: RichExportSource + ?Sized> Collector<'_, U> {
+    /// Append a frame's content. Nested non-table frames (blockquotes,
+    /// floats) are flattened into the surrounding flow.
+    fn collect_frame(&mut self, frame_id: EntityId, out: &mut Vec<RichElement>) -> Result<()> {
+        let frame = self
+            .uow
+            .rxs_get_frame(frame_id)?
+            .ok_or_else(|| anyhow!("Frame {} not found", frame_id))?;
+        if let Some(table_id) = frame.table {
+            if let Some(table) = self.collect_table(table_id)? {
+                out.push(RichElement::Table(table));
+            }
+            return Ok(());
+        }
+
+        let entries: Vec<i64> = if frame.child_order.is_empty() {
+            frame.blocks.iter().map(|id| *id as i64).collect()
+        } else {
+            frame.child_order.clone()
+        };
+        for entry in entries {
+            if entry > 0 {
+                if let Some(block) = self.uow.rxs_get_block(entry as EntityId)? {
+                    out.push(RichElement::Block(self.collect_block(&block)?));
+                }
+            } else {
+                self.collect_frame((-entry) as EntityId, out)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn collect_block(&mut self, block: &Block) -> Result<RichBlock> {
+        let text = block_content_via_store(block, &self.store);
+        let mut rich = RichBlock {
+            heading_level: block.fmt_heading_level,
+            alignment: block.fmt_alignment.clone(),
+            left_margin: block.fmt_left_margin,
+            right_margin: block.fmt_right_margin,
+            text_indent: block.fmt_text_indent,
+            is_code_block: block.fmt_is_code_block == Some(true),
+            ..RichBlock::default()
+        };
+        for segment in inline_segments_for_block(&self.store, block.id, &text) {
+            let format = character_format_from_segment(&segment);
+            match segment.content {
+                InlineContent::Text(t) => rich.push_text(&t, &format),
+                InlineContent::Image {
+                    name,
+                    width,
+                    height,
+                    ..
+                } => {
+                    if !self.image_names.contains(&name) {
+                        self.image_names.push(name.clone());
+                    }
+                    rich.inlines.push(RichInline::Image {
+                        name,
+                        width,
+                        height,
+                        format,
+                    });
+                }
+                InlineContent::Empty => {}
+            }
+        }
+        if let Some(list_id) = block.list
+            && let Some(list) = self.uow.rxs_get_list(list_id)?
+        {
+            rich.list = Some(RichListItem {
+                key: list.id.to_string(),
+                style: list.style,
+                indent: list.indent.max(0) as u32,
+            });
+        }
+        Ok(rich)
+    }
+
+    fn collect_table(&mut self, table_id: EntityId) -> Result<Option<RichTable>> {
+        let Some(table) = self.uow.rxs_get_table(table_id)? else {
+            return Ok(None);
+        };
+        let mut cells = Vec::new();
+        for cell in self
+            .uow
+            .rxs_get_table_cells(&table.cells)?
+            .into_iter()
+            .flatten()
+        {
+            let mut blocks = Vec::new();
+            if let Some(cell_frame) = cell.cell_frame {
+                let mut elements = Vec::new();
+                self.collect_frame(cell_frame, &mut elements)?;
+                for element in elements {
+                    match element {
+                        RichElement::Block(b) => blocks.push(b),
+                        RichElement::Table(t) => {
+                            blocks.extend(t.cells.into_iter().flat_map(|c| c.blocks))
+                        }
+                    }
+                }
+            }
+            cells.push(RichCell {
+                row: cell.row.max(0) as usize,
+                column: cell.column.max(0) as usize,
+                row_span: cell.row_span.max(1) as usize,
+                column_span: cell.column_span.max(1) as usize,
+                blocks,
+            });
+        }
+        cells.sort_by_key(|c| (c.row, c.column));
+        Ok(Some(RichTable {
+            rows: table.rows.max(0) as usize,
+            columns: table.columns.max(0) as usize,
+            cells,
+        }))
+    }
+}
@@ -39,6 +39,20 @@ impl ExportHtmlUseCase {
     }
 
     pub fn execute(&mut self) -> Result<ExportHtmlDto> {
+        let body_parts = self.render_body_parts()?;
+
+        let html_text = format!(
+            "<html><head><meta charset=\"utf-8\"></head><body>{}</body></html>",
+            body_parts.join("")
+        );
+
+        Ok(ExportHtmlDto { html_text })
+    }
+
+    /// Render the document body as a list of top-level XHTML elements
+    /// (paragraphs, headings, lists, tables, blockquotes) in flow order.
+    /// Used by the EPUB exporter to split the body into chapters.
+    pub(crate) fn render_body_parts(&self) -> Result<Vec<String>> {
         let uow = self.uow_factory.create();
         uow.begin_transaction()?;
 
@@ -79,46 +93,53 @@ impl ExportHtmlUseCase {
             }
         }
 
+        // Frames listed in another frame's child_order (table anchors,
+        // blockquotes) are rendered in place there.
+        let mut nested_frame_ids: HashSet<EntityId> = HashSet::new();
+        for frame_id in &frame_ids {
+            if let Some(frame) = uow.get_frame(frame_id)? {
+                nested_frame_ids.extend(
+                    frame
+                        .child_order
+                        .iter()
+                        .filter(|&&entry| entry < 0)
+                        .map(|&entry| (-entry) as EntityId),
+                );
+            }
+        }
+
         let mut body_parts: Vec<String> = Vec::new();
 
         for frame_id in &frame_ids {
             // Skip cell frames — they're rendered as part of their table
-            if cell_frame_ids.contains(frame_id) {
+            if cell_frame_ids.contains(frame_id) || nested_frame_ids.contains(frame_id) {
                 continue;
             }
 
-            let frame_html = self.render_frame_html(&*uow, frame_id, &cell_frame_ids)?;
-            if !frame_html.is_empty() {
-                body_parts.push(frame_html);
-            }
+            body_parts.extend(self.render_frame_parts(&*uow, frame_id, &cell_frame_ids)?);
         }
 
         uow.end_transaction()?;
 
-        let html_text = format!(
-            "<html><head><meta charset=\"utf-8\"></head><body>{}</body></html>",
-            body_parts.join("")
-        );
-
-        Ok(ExportHtmlDto { html_text })
+        Ok(body_parts)
     }
 
-    /// Render a frame's content as HTML, walking its `child_order` to interleave
-    /// blocks and sub-frames (blockquotes). Falls back to sorted blocks when
-    /// `child_order` is empty.
-    fn render_frame_html(
+    /// Render a frame's content as top-level HTML elements, walking its
+    /// `child_order` to interleave blocks and sub-frames (blockquotes). Falls
+    /// back to sorted blocks when `child_order` is empty.
+    fn render_frame_parts(
         &self,
         uow: &dyn ExportHtmlUnitOfWorkTrait,
         frame_id: &EntityId,
         cell_frame_ids: &HashSet<EntityId>,
-    ) -> Result<String> {
+    ) -> Result<Vec<String>> {
         let frame = uow
             .get_frame(frame_id)?
             .ok_or_else(|| anyhow!("Frame not found"))?;
 
         // Table anchor frame — render the table instead of blocks
         if let Some(table_id) = frame.table {
-            return self.render_table_html(uow, &table_id);
+            return Ok(vec![self.render_table_html(uow, &table_id)?]);
         }
 
         // If child_order is populated, use it to interleave blocks and sub-frames
@@ -133,14 +154,14 @@ impl ExportHtmlUseCase {
         )?;
 
         if block_ids.is_empty() {
-            return Ok(String::new());
+            return Ok(Vec::new());
         }
 
         let blocks_opt = uow.get_block_multi(&block_ids)?;
         let mut blocks: Vec<Block> = blocks_opt.into_iter().flatten().collect();
         blocks.sort_by_key(|b| b.document_position);
 
-        self.render_blocks_html(uow, &blocks)
+        self.render_blocks_parts(uow, &blocks)
     }
 
     /// Walk `child_order` entries: positive values are block IDs, negative values
@@ -150,7 +171,7 @@ impl ExportHtmlUseCase {
         uow: &dyn ExportHtmlUnitOfWorkTrait,
         frame: &Frame,
         cell_frame_ids: &HashSet<EntityId>,
-    ) -> Result<String> {
+    ) -> Result<Vec<String>> {
         let mut parts: Vec<String> = Vec::new();
         // Accumulate consecutive blocks so we can group list items
         let mut pending_blocks: Vec<Block> = Vec::new();
@@ -166,10 +187,7 @@ impl ExportHtmlUseCase {
                 // Negative: negated sub-frame ID
                 // First, flush any accumulated blocks
                 if !pending_blocks.is_empty() {
-                    let html = self.render_blocks_html(uow, &pending_blocks)?;
-                    if !html.is_empty() {
-                        parts.push(html);
-                    }
+                    parts.extend(self.render_blocks_parts(uow, &pending_blocks)?);
                     pending_blocks.clear();
                 }
 
@@ -184,16 +202,17 @@ impl ExportHtmlUseCase {
                 if let Some(ref sf) = sub_frame {
                     if sf.fmt_is_blockquote == Some(true) {
                         // Recursively render the blockquote frame content
-                        let inner = self.render_frame_html(uow, &sub_frame_id, cell_frame_ids)?;
+                        let inner = self.render_frame_parts(uow, &sub_frame_id, cell_frame_ids)?;
                         if !inner.is_empty() {
-                            parts.push(format!("<blockquote>{}</blockquote>", inner));
+                            parts.push(format!("<blockquote>{}</blockquote>", inner.join("")));
                         }
                     } else {
                         // Non-blockquote sub-frame: render normally
-                        let inner = self.render_frame_html(uow, &sub_frame_id, cell_frame_ids)?;
-                        if !inner.is_empty() {
-                            parts.push(inner);
-                        }
+                        parts.extend(self.render_frame_parts(
+                            uow,
+                            &sub_frame_id,
+                            cell_frame_ids,
+                        )?);
                     }
                 }
             }
@@ -201,22 +220,19 @@ impl ExportHtmlUseCase {
 
         // Flush remaining blocks
         if !pending_blocks.is_empty() {
-            let html = self.render_blocks_html(uow, &pending_blocks)?;
-            if !html.is_empty() {
-                parts.push(html);
-            }
+            parts.extend(self.render_blocks_parts(uow, &pending_blocks)?);
         }
 
-        Ok(parts.join(""))
+        Ok(parts)
     }
 
-    /// Render a slice of blocks as HTML, grouping consecutive list items and
-    /// handling code blocks, headings, and paragraphs.
-    fn render_blocks_html(
+    /// Render a slice of blocks as HTML elements, grouping consecutive list
+    /// items and handling code blocks, headings, and paragraphs.
+    fn render_blocks_parts(
         &self,
         uow: &dyn ExportHtmlUnitOfWorkTrait,
         blocks: &[Block],
-    ) -> Result<String> {
+    ) -> Result<Vec<String>> {
         let mut parts: Vec<String> = Vec::new();
         let mut i = 0;
 
@@ -351,7 +367,7 @@ impl ExportHtmlUseCase {
             }
         }
 
-        Ok(parts.join(""))
+        Ok(parts)
     }
 
     fn render_table_html(
@@ -508,6 +524,19 @@ impl ExportHtmlUseCase {
             if elem.fmt_font_strikeout == Some(true) {
                 formatted = format!("<s>{}</s>", formatted);
             }
+            let mut styles: Vec<String> = Vec::new();
+            if let Some(ref c) = elem.fmt_foreground_color {
+                styles.push(format!("color: {}", escape_html(c)));
+            }
+            if let Some(ref c) = elem.fmt_background_color {
+                styles.push(format!("background-color: {}", escape_html(c)));
+            }
+            if let Some(ref c) = elem.fmt_underline_color {
+                styles.push(format!("text-decoration-color: {}", escape_html(c)));
+            }
+            if !styles.is_empty() {
+                formatted = format!("<span style=\"{}\">{}</span>", styles.join("; "), formatted);
+            }
             if let Some(ref href) = elem.fmt_anchor_href {
                 formatted = format!("<a href=\"{}\">{}</a>", escape_html(href), formatted);
             }
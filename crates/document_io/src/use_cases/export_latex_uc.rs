@@ -8,6 +8,7 @@ use common::entities::{
     Block, Document, Frame, List, ListStyle, Root, Table, TableCell, TextDirection,
 };
 use common::format_runs::InlineContent;
+use common::parser_tools::css_color::rgb_hex_digits;
 use common::types::{EntityId, ROOT_ENTITY_ID};
 use std::collections::HashMap;
 use std::collections::HashSet;
@@ -394,6 +395,20 @@ impl ExportLatexUseCase {
             if elem.fmt_font_strikeout == Some(true) {
                 formatted = format!("\\sout{{{}}}", formatted);
             }
+            if let Some(hex) = elem
+                .fmt_foreground_color
+                .as_deref()
+                .and_then(rgb_hex_digits)
+            {
+                formatted = format!("\\textcolor[HTML]{{{}}}{{{}}}", hex, formatted);
+            }
+            if let Some(hex) = elem
+                .fmt_background_color
+                .as_deref()
+                .and_then(rgb_hex_digits)
+            {
+                formatted = format!("\\colorbox[HTML]{{{}}}{{{}}}", hex, formatted);
+            }
             if let Some(ref href) = elem.fmt_anchor_href {
                 formatted = format!("\\href{{{}}}{{{}}}", escape_latex(href), formatted);
             }
@@ -78,11 +78,26 @@ impl ExportMarkdownUseCase {
             }
         }
 
+        // Frames listed in another frame's child_order (table anchors,
+        // blockquotes) are rendered in place there.
+        let mut nested_frame_ids: HashSet<EntityId> = HashSet::new();
+        for frame_id in &frame_ids {
+            if let Some(frame) = uow.get_frame(frame_id)? {
+                nested_frame_ids.extend(
+                    frame
+                        .child_order
+                        .iter()
+                        .filter(|&&entry| entry < 0)
+                        .map(|&entry| (-entry) as EntityId),
+                );
+            }
+        }
+
         let mut output_parts: Vec<String> = Vec::new();
 
         for frame_id in &frame_ids {
             // Skip cell frames — they're rendered as part of their table
-            if cell_frame_ids.contains(frame_id) {
+            if cell_frame_ids.contains(frame_id) || nested_frame_ids.contains(frame_id) {
                 continue;
             }
 
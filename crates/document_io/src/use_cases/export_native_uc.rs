@@ -0,0 +1,122 @@
+use crate::ExportNativeDto;
+use anyhow::{Context, Result, anyhow};
+use common::database::QueryUnitOfWork;
+use common::database::native_format::{self, NativeUndoEntry, NativeUndoHistory};
+use common::database::rope_store::RopeStoreSnapshot;
+use common::entities::{Document, Root};
+use common::types::{EntityId, ROOT_ENTITY_ID};
+use common::undo_redo::UndoRedoManager;
+
+pub trait ExportNativeUnitOfWorkFactoryTrait: Send + Sync {
+    fn create(&self) -> Box<dyn ExportNativeUnitOfWorkTrait>;
+}
+
+#[macros::uow_action(entity = "Root", action = "GetRO")]
+#[macros::uow_action(entity = "Document", action = "GetRO")]
+pub trait ExportNativeUnitOfWorkTrait: QueryUnitOfWork {}
+
+pub struct ExportNativeUseCase {
+    uow_factory: Box<dyn ExportNativeUnitOfWorkFactoryTrait>,
+}
+
+impl ExportNativeUseCase {
+    pub fn new(uow_factory: Box<dyn ExportNativeUnitOfWorkFactoryTrait>) -> Self {
+        ExportNativeUseCase { uow_factory }
+    }
+
+    pub fn execute(&mut self) -> Result<ExportNativeDto> {
+        self.export(None)
+    }
+
+    /// Like [`execute`](Self::execute), also saving the undo and redo
+    /// history of `stack_id`.
+    ///
+    /// The history is rebuilt from the edit records each entry's command
+    /// kept, replayed on a copy of the store, so neither the document
+    /// nor the stack is touched. When an entry can't be replayed,
+    /// because its command keeps no records or the document no longer
+    /// matches them, nothing is saved and the error is returned.
+    pub fn execute_with_history(
+        &mut self,
+        undo_redo_manager: &UndoRedoManager,
+        stack_id: Option<u64>,
+    ) -> Result<ExportNativeDto> {
+        let current = self.uow_factory.create().store().snapshot();
+        let history = saved_history(&current, undo_redo_manager, stack_id)
+            .context("cannot save the undo history")?;
+        // Saved with the current counters, so a loaded copy hands out
+        // the same IDs as this document from here on.
+        self.export(Some((&current, &history)))
+    }
+
+    fn export(
+        &mut self,
+        history: Option<(&RopeStoreSnapshot, &NativeUndoHistory)>,
+    ) -> Result<ExportNativeDto> {
+        let uow = self.uow_factory.create();
+        uow.begin_transaction()?;
+
+        let root = uow
+            .get_root(&ROOT_ENTITY_ID)?
+            .ok_or_else(|| anyhow!("Root entity not found"))?;
+        uow.get_document(&root.document)?
+            .ok_or_else(|| anyhow!("Root has no associated Document"))?;
+
+        // The whole store is serialized, not just the entities reachable
+        // from the root: format runs, image anchors and the block offset
+        // index live beside the entity tables.
+        let native_data = match &history {
+            Some((snapshot, history)) => native_format::encode_with_history(snapshot, history)?,
+            None => native_format::encode(&uow.store())?,
+        };
+
+        uow.end_transaction()?;
+
+        Ok(ExportNativeDto { native_data })
+    }
+}
+
+/// The saved form of `stack_id`'s history: every entry as the deltas of
+/// its records, found by replaying them from `current`. Each record is
+/// saved on its own, so an entry costs what its edits changed rather
+/// than everything between its first and last edit.
+fn saved_history(
+    current: &RopeStoreSnapshot,
+    undo_redo_manager: &UndoRedoManager,
+    stack_id: Option<u64>,
+) -> Result<NativeUndoHistory> {
+    let mut undo = Vec::new();
+    let mut state = current.clone();
+    for (description, records) in undo_redo_manager.undo_records(stack_id).into_iter().rev() {
+        let records = records.ok_or_else(|| anyhow!("\"{description}\" can't be saved"))?;
+        let mut deltas = Vec::new();
+        for record in records.iter().rev() {
+            let before = record.undo(&state)?;
+            deltas.push(record.to_delta(&before, &state));
+            state = before;
+        }
+        deltas.reverse();
+        undo.push(NativeUndoEntry {
+            description,
+            deltas,
+        });
+    }
+    undo.reverse();
+
+    let mut redo = Vec::new();
+    let mut state = current.clone();
+    for (description, records) in undo_redo_manager.redo_records(stack_id) {
+        let records = records.ok_or_else(|| anyhow!("\"{description}\" can't be saved"))?;
+        let mut deltas = Vec::new();
+        for record in &records {
+            let after = record.redo(&state)?;
+            deltas.push(record.to_delta(&state, &after));
+            state = after;
+        }
+        redo.push(NativeUndoEntry {
+            description,
+            deltas,
+        });
+    }
+    Ok(NativeUndoHistory { undo, redo })
+}
@@ -0,0 +1,104 @@
+// Generated by Qleany v1.5.1 from feature_use_case.tera
+use crate::ExportOdtDto;
+use crate::ExportOdtResultDto;
+use crate::use_cases::export_helpers::{collect_document, impl_rich_export_source};
+use crate::use_cases::odt_writer::write_odt;
+use anyhow::{Result, anyhow};
+use common::database::QueryUnitOfWork;
+use common::entities::{Block, Document, Frame, List, Resource, Root, Table, TableCell};
+use common::long_operation::LongOperation;
+use common::types::EntityId;
+use std::sync::Arc;
+
+pub trait ExportOdtUnitOfWorkFactoryTrait: Send + Sync {
+    fn create(&self) -> Box<dyn ExportOdtUnitOfWorkTrait>;
+}
+
+#[macros::uow_action(entity = "Root", action = "GetRO", thread_safe = true)]
+#[macros::uow_action(entity = "Document", action = "GetRO", thread_safe = true)]
+#[macros::uow_action(entity = "Frame", action = "GetRO", thread_safe = true)]
+#[macros::uow_action(entity = "Block", action = "GetRO", thread_safe = true)]
+#[macros::uow_action(entity = "List", action = "GetRO", thread_safe = true)]
+#[macros::uow_action(entity = "Resource", action = "GetMultiRO", thread_safe = true)]
+#[macros::uow_action(entity = "Table", action = "GetRO", thread_safe = true)]
+#[macros::uow_action(entity = "TableCell", action = "GetMultiRO", thread_safe = true)]
+pub trait ExportOdtUnitOfWorkTrait: QueryUnitOfWork + Send + Sync {}
+
+impl_rich_export_source!(dyn ExportOdtUnitOfWorkTrait);
+
+pub struct ExportOdtUseCase {
+    uow_factory: Box<dyn ExportOdtUnitOfWorkFactoryTrait>,
+    dto: ExportOdtDto,
+}
+
+impl ExportOdtUseCase {
+    pub fn new(uow_factory: Box<dyn ExportOdtUnitOfWorkFactoryTrait>, dto: &ExportOdtDto) -> Self {
+        ExportOdtUseCase {
+            uow_factory,
+            dto: dto.clone(),
+        }
+    }
+}
+
+impl LongOperation for ExportOdtUseCase {
+    type Output = ExportOdtResultDto;
+
+    fn execute(
+        &self,
+        progress_callback: Box<dyn Fn(common::long_operation::OperationProgress) + Send>,
+        cancel_flag: Arc<std::sync::atomic::AtomicBool>,
+    ) -> Result<Self::Output> {
+        use std::sync::atomic::Ordering;
+
+        // Validate output path
+        let output_path = std::path::Path::new(&self.dto.output_path);
+        if let Some(parent) = output_path.parent()
+            && !parent.as_os_str().is_empty()
+            && !parent.exists()
+        {
+            return Err(anyhow!(
+                "Output directory does not exist: '{}'",
+                parent.display()
+            ));
+        }
+
+        progress_callback(common::long_operation::OperationProgress::new(
+            0.0,
+            Some("Starting ODT export...".to_string()),
+        ));
+
+        let uow = self.uow_factory.create();
+        uow.begin_transaction()?;
+        let collected = collect_document(&uow);
+        uow.end_transaction()?;
+        let (rich, title) = collected?;
+
+        if cancel_flag.load(Ordering::Relaxed) {
+            return Err(anyhow!("Operation was cancelled"));
+        }
+
+        progress_callback(common::long_operation::OperationProgress::new(
+            50.0,
+            Some("Writing ODT file...".to_string()),
+        ));
+
+        let (bytes, paragraph_count) = write_odt(&rich, &title)?;
+        std::fs::write(&self.dto.output_path, bytes).map_err(|e| {
+            anyhow!(
+                "Failed to create output file '{}': {}",
+                self.dto.output_path,
+                e
+            )
+        })?;
+
+        progress_callback(common::long_operation::OperationProgress::new(
+            100.0,
+            Some("completed".to_string()),
+        ));
+
+        Ok(ExportOdtResultDto {
+            file_path: self.dto.output_path.clone(),
+            paragraph_count,
+        })
+    }
+}
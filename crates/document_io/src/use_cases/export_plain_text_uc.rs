@@ -2,9 +2,11 @@
 use crate::ExportPlainTextDto;
 use anyhow::{Result, anyhow};
 use common::database::QueryUnitOfWork;
+use common::database::Store;
 use common::database::rope_helpers::{block_content_via_store, rope_flat_text_if_simple};
-use common::entities::{Block, Document, Frame, Root};
+use common::entities::{Block, Document, Frame, Root, TableCell};
 use common::types::{EntityId, ROOT_ENTITY_ID};
+use std::collections::HashSet;
 
 pub trait ExportPlainTextUnitOfWorkFactoryTrait: Send + Sync {
     fn create(&self) -> Box<dyn ExportPlainTextUnitOfWorkTrait>;
@@ -17,6 +19,8 @@ pub trait ExportPlainTextUnitOfWorkFactoryTrait: Send + Sync {
 #[macros::uow_action(entity = "Frame", action = "GetRO")]
 #[macros::uow_action(entity = "Frame", action = "GetRelationshipRO")]
 #[macros::uow_action(entity = "Block", action = "GetMultiRO")]
+#[macros::uow_action(entity = "Table", action = "GetRelationshipRO")]
+#[macros::uow_action(entity = "TableCell", action = "GetMultiRO")]
 pub trait ExportPlainTextUnitOfWorkTrait: QueryUnitOfWork {}
 
 pub struct ExportPlainTextUseCase {
@@ -66,25 +70,43 @@ impl ExportPlainTextUseCase {
         // separate byte ranges later in the rope (plan §1.6).
         let mut all_plain_texts: Vec<String> = Vec::new();
 
-        for frame_id in &frame_ids {
-            // Get Block IDs from the Frame.Blocks relationship
-            let block_ids = uow.get_frame_relationship(
-                frame_id,
-                &common::direct_access::frame::FrameRelationshipField::Blocks,
+        // Cell frames are exported with their table, and frames listed in
+        // another frame's child_order (table anchors, blockquotes) in place
+        // there.
+        let table_ids = uow.get_document_relationship(
+            &doc_id,
+            &common::direct_access::document::DocumentRelationshipField::Tables,
+        )?;
+        let mut nested_frame_ids: HashSet<EntityId> = HashSet::new();
+        for tid in &table_ids {
+            let cell_ids = uow.get_table_relationship(
+                tid,
+                &common::direct_access::table::TableRelationshipField::Cells,
             )?;
-
-            if block_ids.is_empty() {
-                continue;
+            nested_frame_ids.extend(
+                uow.get_table_cell_multi(&cell_ids)?
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|cell| cell.cell_frame),
+            );
+        }
+        for frame_id in &frame_ids {
+            if let Some(frame) = uow.get_frame(frame_id)? {
+                nested_frame_ids.extend(
+                    frame
+                        .child_order
+                        .iter()
+                        .filter(|&&entry| entry < 0)
+                        .map(|&entry| (-entry) as EntityId),
+                );
             }
+        }
 
-            // Get all blocks in batch and sort by document_position
-            let blocks_opt = uow.get_block_multi(&block_ids)?;
-            let mut blocks: Vec<Block> = blocks_opt.into_iter().flatten().collect();
-            blocks.sort_by_key(|b| b.document_position);
-
-            for block in &blocks {
-                all_plain_texts.push(block_content_via_store(block, &store));
+        for frame_id in &frame_ids {
+            if nested_frame_ids.contains(frame_id) {
+                continue;
             }
+            Self::push_frame_texts(&*uow, &store, frame_id, &mut all_plain_texts)?;
         }
 
         let plain_text = all_plain_texts.join("\n");
@@ -93,4 +115,67 @@ impl ExportPlainTextUseCase {
 
         Ok(ExportPlainTextDto { plain_text })
     }
+
+    /// Append the text of every block in a frame, in flow order: a table
+    /// anchor contributes its cells row by row, and `child_order` places
+    /// sub-frames among the blocks. Falls back to sorted blocks when
+    /// `child_order` is empty.
+    fn push_frame_texts(
+        uow: &dyn ExportPlainTextUnitOfWorkTrait,
+        store: &Store,
+        frame_id: &EntityId,
+        texts: &mut Vec<String>,
+    ) -> Result<()> {
+        let Some(frame) = uow.get_frame(frame_id)? else {
+            return Ok(());
+        };
+
+        if let Some(table_id) = frame.table {
+            let cell_ids = uow.get_table_relationship(
+                &table_id,
+                &common::direct_access::table::TableRelationshipField::Cells,
+            )?;
+            let mut cells: Vec<TableCell> = uow
+                .get_table_cell_multi(&cell_ids)?
+                .into_iter()
+                .flatten()
+                .collect();
+            cells.sort_by_key(|cell| (cell.row, cell.column));
+            for cell in &cells {
+                if let Some(cf_id) = cell.cell_frame {
+                    Self::push_frame_texts(uow, store, &cf_id, texts)?;
+                }
+            }
+            return Ok(());
+        }
+
+        if !frame.child_order.is_empty() {
+            for &entry in &frame.child_order {
+                if entry > 0 {
+                    let blocks = uow.get_block_multi(&[entry as EntityId])?;
+                    for block in blocks.iter().flatten() {
+                        texts.push(block_content_via_store(block, store));
+                    }
+                } else {
+                    Self::push_frame_texts(uow, store, &((-entry) as EntityId), texts)?;
+                }
+            }
+            return Ok(());
+        }
+
+        let block_ids = uow.get_frame_relationship(
+            frame_id,
+            &common::direct_access::frame::FrameRelationshipField::Blocks,
+        )?;
+        let mut blocks: Vec<Block> = uow
+            .get_block_multi(&block_ids)?
+            .into_iter()
+            .flatten()
+            .collect();
+        blocks.sort_by_key(|b| b.document_position);
+        for block in &blocks {
+            texts.push(block_content_via_store(block, store));
+        }
+        Ok(())
+    }
 }
@@ -0,0 +1,46 @@
+// Generated by Qleany v1.5.1 from feature_use_case.tera
+use crate::ExportRtfDto;
+use crate::use_cases::export_helpers::{collect_document, impl_rich_export_source};
+use anyhow::Result;
+use common::database::QueryUnitOfWork;
+use common::entities::{Block, Document, Frame, List, Resource, Root, Table, TableCell};
+use common::parser_tools::rtf_writer::write_rtf;
+use common::types::EntityId;
+
+pub trait ExportRtfUnitOfWorkFactoryTrait: Send + Sync {
+    fn create(&self) -> Box<dyn ExportRtfUnitOfWorkTrait>;
+}
+
+#[macros::uow_action(entity = "Root", action = "GetRO")]
+#[macros::uow_action(entity = "Document", action = "GetRO")]
+#[macros::uow_action(entity = "Frame", action = "GetRO")]
+#[macros::uow_action(entity = "Block", action = "GetRO")]
+#[macros::uow_action(entity = "List", action = "GetRO")]
+#[macros::uow_action(entity = "Resource", action = "GetMultiRO")]
+#[macros::uow_action(entity = "Table", action = "GetRO")]
+#[macros::uow_action(entity = "TableCell", action = "GetMultiRO")]
+pub trait ExportRtfUnitOfWorkTrait: QueryUnitOfWork {}
+
+impl_rich_export_source!(dyn ExportRtfUnitOfWorkTrait);
+
+pub struct ExportRtfUseCase {
+    uow_factory: Box<dyn ExportRtfUnitOfWorkFactoryTrait>,
+}
+
+impl ExportRtfUseCase {
+    pub fn new(uow_factory: Box<dyn ExportRtfUnitOfWorkFactoryTrait>) -> Self {
+        ExportRtfUseCase { uow_factory }
+    }
+
+    pub fn execute(&mut self) -> Result<ExportRtfDto> {
+        let uow = self.uow_factory.create();
+        uow.begin_transaction()?;
+        let collected = collect_document(&uow);
+        uow.end_transaction()?;
+        let (rich, _title) = collected?;
+
+        Ok(ExportRtfDto {
+            rtf_text: write_rtf(&rich),
+        })
+    }
+}
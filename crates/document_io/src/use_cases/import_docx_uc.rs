@@ -0,0 +1,125 @@
+// Generated by Qleany v1.5.1 from feature_use_case.tera
+use crate::ImportDocxDto;
+use crate::ImportDocxResultDto;
+use crate::use_cases::docx_reader::read_docx;
+use crate::use_cases::import_helpers::{build_document, impl_rich_import_target};
+use anyhow::{Result, anyhow};
+use common::database::CommandUnitOfWork;
+use common::entities::{Block, Document, Frame, List, Resource, Root, Table, TableCell};
+use common::long_operation::LongOperation;
+use common::types::{EntityId, ROOT_ENTITY_ID};
+use std::sync::Arc;
+
+pub trait ImportDocxUnitOfWorkFactoryTrait: Send + Sync {
+    fn create(&self) -> Box<dyn ImportDocxUnitOfWorkTrait>;
+}
+
+#[macros::uow_action(entity = "Root", action = "Get", thread_safe = true)]
+#[macros::uow_action(entity = "Root", action = "GetRelationship", thread_safe = true)]
+#[macros::uow_action(entity = "Document", action = "Get", thread_safe = true)]
+#[macros::uow_action(entity = "Document", action = "Update", thread_safe = true)]
+#[macros::uow_action(entity = "Document", action = "GetRelationship", thread_safe = true)]
+#[macros::uow_action(entity = "Frame", action = "Get", thread_safe = true)]
+#[macros::uow_action(entity = "Frame", action = "Create", thread_safe = true)]
+#[macros::uow_action(entity = "Frame", action = "Update", thread_safe = true)]
+#[macros::uow_action(entity = "Frame", action = "Remove", thread_safe = true)]
+#[macros::uow_action(entity = "Frame", action = "GetRelationship", thread_safe = true)]
+#[macros::uow_action(entity = "Block", action = "Create", thread_safe = true)]
+#[macros::uow_action(entity = "Block", action = "SetRelationship", thread_safe = true)]
+#[macros::uow_action(entity = "List", action = "Create", thread_safe = true)]
+#[macros::uow_action(entity = "Resource", action = "Create", thread_safe = true)]
+#[macros::uow_action(entity = "Table", action = "Create", thread_safe = true)]
+#[macros::uow_action(entity = "TableCell", action = "Create", thread_safe = true)]
+pub trait ImportDocxUnitOfWorkTrait: CommandUnitOfWork + Send + Sync {}
+
+impl_rich_import_target!(dyn ImportDocxUnitOfWorkTrait);
+
+pub struct ImportDocxUseCase {
+    uow_factory: Box<dyn ImportDocxUnitOfWorkFactoryTrait>,
+    dto: ImportDocxDto,
+}
+
+impl ImportDocxUseCase {
+    pub fn new(
+        uow_factory: Box<dyn ImportDocxUnitOfWorkFactoryTrait>,
+        dto: &ImportDocxDto,
+    ) -> Self {
+        ImportDocxUseCase {
+            uow_factory,
+            dto: dto.clone(),
+        }
+    }
+}
+
+impl LongOperation for ImportDocxUseCase {
+    type Output = ImportDocxResultDto;
+
+    fn execute(
+        &self,
+        progress_callback: Box<dyn Fn(common::long_operation::OperationProgress) + Send>,
+        cancel_flag: Arc<std::sync::atomic::AtomicBool>,
+    ) -> Result<Self::Output> {
+        use std::sync::atomic::Ordering;
+
+        progress_callback(common::long_operation::OperationProgress::new(
+            0.0,
+            Some("Starting DOCX import...".to_string()),
+        ));
+
+        // Parse the package before touching the document so a malformed
+        // file leaves it unchanged.
+        let rich = read_docx(&self.dto.docx_data)?;
+
+        progress_callback(common::long_operation::OperationProgress::new(
+            10.0,
+            Some("Parsed DOCX, building document...".to_string()),
+        ));
+
+        if cancel_flag.load(Ordering::Relaxed) {
+            return Err(anyhow!("Operation was cancelled"));
+        }
+
+        let mut uow = self.uow_factory.create();
+        uow.begin_transaction()?;
+
+        let root = uow
+            .get_root(&ROOT_ENTITY_ID)?
+            .ok_or_else(|| anyhow!("Root entity not found"))?;
+        let doc_ids = uow.get_root_relationship(
+            &root.id,
+            &common::direct_access::root::RootRelationshipField::Document,
+        )?;
+        let doc_id = *doc_ids
+            .first()
+            .ok_or_else(|| anyhow!("Root has no associated Document"))?;
+
+        let block_count = match build_document(
+            &mut uow,
+            doc_id,
+            &rich,
+            &*progress_callback,
+            &cancel_flag,
+            (20.0, 90.0),
+        ) {
+            Ok(count) => count,
+            Err(e) => {
+                uow.rollback()?;
+                return Err(e);
+            }
+        };
+
+        if cancel_flag.load(Ordering::Relaxed) {
+            uow.rollback()?;
+            return Err(anyhow!("Operation was cancelled"));
+        }
+
+        uow.commit()?;
+
+        progress_callback(common::long_operation::OperationProgress::new(
+            100.0,
+            Some("completed".to_string()),
+        ));
+
+        Ok(ImportDocxResultDto { block_count })
+    }
+}
@@ -0,0 +1,386 @@
+//! Shared document builder for the binary-format importers (DOCX, ODT).
+//!
+//! Format readers parse their input into a [`RichDocument`] and
+//! [`build_document`] turns it into entities, format runs and the rope,
+//! the same way `import_html_uc` does for the simpler `ParsedElement`
+//! model.
+
+use anyhow::{Result, anyhow};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use common::database::Store;
+use common::database::rope_helpers::{
+    rope_append_block, rope_append_table_anchor, rope_insert_block_boundary, rope_reset,
+};
+use common::entities::{Block, Document, Frame, List, Resource, ResourceType, Table, TableCell};
+use common::format_runs::{CharacterFormat, FormatRun, ImageAnchor, coalesce_in_place};
+use common::long_operation::OperationProgress;
+use common::parser_tools::rich_document::{
+    RichBlock, RichCell, RichDocument, RichElement, RichInline, RichTable,
+};
+use common::types::EntityId;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// UoW operations needed by [`build_document`]. Implemented for each
+/// importer's boxed UoW trait with [`impl_rich_import_target!`].
+pub(crate) trait RichImportTarget {
+    fn rit_store(&self) -> Arc<Store>;
+    fn rit_get_frames(&self, doc_id: EntityId) -> Result<Vec<EntityId>>;
+    fn rit_remove_frame(&mut self, id: EntityId) -> Result<()>;
+    fn rit_get_frame(&self, id: EntityId) -> Result<Option<Frame>>;
+    fn rit_create_frame(&mut self, frame: &Frame, owner_id: EntityId) -> Result<Frame>;
+    fn rit_update_frame(&mut self, frame: &Frame) -> Result<Frame>;
+    fn rit_create_block(&mut self, block: &Block, owner_id: EntityId) -> Result<Block>;
+    fn rit_set_block_list(&mut self, block_id: EntityId, list_id: EntityId) -> Result<()>;
+    fn rit_create_list(&mut self, list: &List, owner_id: EntityId) -> Result<List>;
+    fn rit_create_resource(&mut self, resource: &Resource, owner_id: EntityId) -> Result<Resource>;
+    fn rit_create_table(&mut self, table: &Table, owner_id: EntityId) -> Result<Table>;
+    fn rit_create_table_cell(&mut self, cell: &TableCell, owner_id: EntityId) -> Result<TableCell>;
+    fn rit_get_document(&self, id: EntityId) -> Result<Option<Document>>;
+    fn rit_update_document(&mut self, document: &Document) -> Result<Document>;
+}
+
+macro_rules! impl_rich_import_target {
+    ($trait_type:ty) => {
+        impl $crate::use_cases::import_helpers::RichImportTarget for Box<$trait_type> {
+            fn rit_store(&self) -> std::sync::Arc<common::database::Store> {
+                (**self).store()
+            }
+            fn rit_get_frames(&self, doc_id: EntityId) -> Result<Vec<EntityId>> {
+                (**self).get_document_relationship(
+                    &doc_id,
+                    &common::direct_access::document::DocumentRelationshipField::Frames,
+                )
+            }
+            fn rit_remove_frame(&mut self, id: EntityId) -> Result<()> {
+                (**self).remove_frame(&id)
+            }
+            fn rit_get_frame(&self, id: EntityId) -> Result<Option<Frame>> {
+                (**self).get_frame(&id)
+            }
+            fn rit_create_frame(&mut self, frame: &Frame, owner_id: EntityId) -> Result<Frame> {
+                (**self).create_frame(frame, owner_id, -1)
+            }
+            fn rit_update_frame(&mut self, frame: &Frame) -> Result<Frame> {
+                (**self).update_frame(frame)
+            }
+            fn rit_create_block(&mut self, block: &Block, owner_id: EntityId) -> Result<Block> {
+                (**self).create_block(block, owner_id, -1)
+            }
+            fn rit_set_block_list(&mut self, block_id: EntityId, list_id: EntityId) -> Result<()> {
+                (**self).set_block_relationship(
+                    &block_id,
+                    &common::direct_access::block::BlockRelationshipField::List,
+                    &[list_id],
+                )
+            }
+            fn rit_create_list(&mut self, list: &List, owner_id: EntityId) -> Result<List> {
+                (**self).create_list(list, owner_id, -1)
+            }
+            fn rit_create_resource(
+                &mut self,
+                resource: &Resource,
+                owner_id: EntityId,
+            ) -> Result<Resource> {
+                (**self).create_resource(resource, owner_id, -1)
+            }
+            fn rit_create_table(&mut self, table: &Table, owner_id: EntityId) -> Result<Table> {
+                (**self).create_table(table, owner_id, -1)
+            }
+            fn rit_create_table_cell(
+                &mut self,
+                cell: &TableCell,
+                owner_id: EntityId,
+            ) -> Result<TableCell> {
+                (**self).create_table_cell(cell, owner_id, -1)
+            }
+            fn rit_get_document(&self, id: EntityId) -> Result<Option<Document>> {
+                (**self).get_document(&id)
+            }
+            fn rit_update_document(&mut self, document: &Document) -> Result<Document> {
+                (**self).update_document(document)
+            }
+        }
+    };
+}
+
+pub(crate) use impl_rich_import_target;
+
+/// Plain text (with one U+FFFC sentinel per image), format runs and
+/// image anchors for one block. Sentinels are not covered by runs.
+fn block_content(block: &RichBlock) -> (String, Vec<FormatRun>, Vec<ImageAnchor>) {
+    let mut text = String::new();
+    let mut runs: Vec<FormatRun> = Vec::new();
+    let mut images: Vec<ImageAnchor> = Vec::new();
+    for inline in &block.inlines {
+        match inline {
+            RichInline::Text {
+                text: chunk,
+                format,
+            } => {
+                // Readers split blocks on line breaks; a stray newline
+                // here would break the rope's block boundaries.
+                let chunk = chunk.replace(['\n', '\r'], " ");
+                if chunk.is_empty() {
+                    continue;
+                }
+                let start = text.len() as u32;
+                text.push_str(&chunk);
+                if *format != CharacterFormat::default() {
+                    runs.push(FormatRun {
+                        byte_start: start,
+                        byte_end: text.len() as u32,
+                        format: format.clone(),
+                    });
+                }
+            }
+            RichInline::Image {
+                name,
+                width,
+                height,
+                format,
+            } => {
+                images.push(ImageAnchor {
+                    byte_offset: text.len() as u32,
+                    name: name.clone(),
+                    width: *width,
+                    height: *height,
+                    quality: 100,
+                    format: format.clone(),
+                });
+                text.push('\u{FFFC}');
+            }
+        }
+    }
+    coalesce_in_place(&mut runs);
+    (text, runs, images)
+}
+
+fn write_block_state(
+    store: &Store,
+    block_id: EntityId,
+    runs: Vec<FormatRun>,
+    images: Vec<ImageAnchor>,
+) {
+    {
+        let mut runs_map = store.format_runs.write().unwrap();
+        if runs.is_empty() {
+            runs_map.remove(&block_id);
+        } else {
+            runs_map.insert(block_id, runs);
+        }
+    }
+    let mut images_map = store.block_images.write().unwrap();
+    if images.is_empty() {
+        images_map.remove(&block_id);
+    } else {
+        images_map.insert(block_id, images);
+    }
+}
+
+/// Accumulates entity ids and counters while the document is rebuilt.
+struct Builder<'a, U: RichImportTarget + ?Sized> {
+    uow: &'a mut U,
+    doc_id: EntityId,
+    lists: HashMap<(String, u32), EntityId>,
+    document_position: i64,
+    total_chars: i64,
+    total_blocks: i64,
+    emitted_any_main_block: bool,
+}
+
+impl<U: RichImportTarget + ?Sized> Builder<'_, U> {
+    /// Create a block in `frame_id` and mirror it into the rope.
+    fn add_block(&mut self, block: &RichBlock, frame_id: EntityId) -> Result<Block> {
+        let (text, runs, images) = block_content(block);
+        let entity = Block {
+            document_position: self.document_position,
+            fmt_heading_level: block.heading_level,
+            fmt_alignment: block.alignment.clone(),
+            fmt_left_margin: block.left_margin,
+            fmt_right_margin: block.right_margin,
+            fmt_text_indent: block.text_indent,
+            fmt_is_code_block: block.is_code_block.then_some(true),
+            ..Block::default()
+        };
+        let created = self.uow.rit_create_block(&entity, frame_id)?;
+
+        let store = self.uow.rit_store();
+        if self.emitted_any_main_block {
+            rope_insert_block_boundary(&store);
+        }
+        rope_append_block(&store, created.id, &text);
+        self.emitted_any_main_block = true;
+        write_block_state(&store, created.id, runs, images);
+
+        if let Some(item) = &block.list {
+            let list_id = match self.lists.get(&(item.key.clone(), item.indent)) {
+                Some(id) => *id,
+                None => {
+                    let list = List {
+                        style: item.style.clone(),
+                        indent: item.indent as i64,
+                        ..List::default()
+                    };
+                    let created_list = self.uow.rit_create_list(&list, self.doc_id)?;
+                    self.lists
+                        .insert((item.key.clone(), item.indent), created_list.id);
+                    created_list.id
+                }
+            };
+            self.uow.rit_set_block_list(created.id, list_id)?;
+        }
+
+        let len = text.chars().count() as i64;
+        self.total_chars += len;
+        self.total_blocks += 1;
+        self.document_position += len + 1;
+        Ok(created)
+    }
+
+    /// Create the table, its cell frames and the anchor frame. Returns
+    /// the anchor frame id for the parent's `child_order`.
+    fn add_table(&mut self, table: &RichTable, parent_frame_id: EntityId) -> Result<EntityId> {
+        let entity = Table {
+            rows: table.rows as i64,
+            columns: table.columns as i64,
+            ..Table::default()
+        };
+        let created_table = self.uow.rit_create_table(&entity, self.doc_id)?;
+        rope_append_table_anchor(&self.uow.rit_store(), created_table.id);
+        // The anchor sentinel is followed by the cell blocks, each
+        // preceded by its own boundary.
+        self.emitted_any_main_block = true;
+
+        let mut cells: Vec<&RichCell> = table.cells.iter().collect();
+        cells.sort_by_key(|c| (c.row, c.column));
+        for cell in cells {
+            let cell_frame = self.uow.rit_create_frame(&Frame::default(), self.doc_id)?;
+            let mut child_order = Vec::new();
+            let default_block = RichBlock::default();
+            let blocks: Vec<&RichBlock> = if cell.blocks.is_empty() {
+                vec![&default_block]
+            } else {
+                cell.blocks.iter().collect()
+            };
+            for block in blocks {
+                let created = self.add_block(block, cell_frame.id)?;
+                child_order.push(created.id as i64);
+            }
+            let mut updated_frame = cell_frame.clone();
+            updated_frame.child_order = child_order;
+            self.uow.rit_update_frame(&updated_frame)?;
+
+            let table_cell = TableCell {
+                row: cell.row as i64,
+                column: cell.column as i64,
+                row_span: cell.row_span.max(1) as i64,
+                column_span: cell.column_span.max(1) as i64,
+                cell_frame: Some(cell_frame.id),
+                ..TableCell::default()
+            };
+            self.uow
+                .rit_create_table_cell(&table_cell, created_table.id)?;
+        }
+
+        let anchor_frame = Frame {
+            parent_frame: Some(parent_frame_id),
+            table: Some(created_table.id),
+            ..Frame::default()
+        };
+        let created_anchor = self.uow.rit_create_frame(&anchor_frame, self.doc_id)?;
+        Ok(created_anchor.id)
+    }
+}
+
+/// Replace the content of document `doc_id` with `rich`. Progress is
+/// reported between `progress_from` and `progress_to` percent. Returns
+/// the number of blocks created. The caller owns the transaction and
+/// rolls it back on error.
+pub(crate) fn build_document<U: RichImportTarget + ?Sized>(
+    uow: &mut U,
+    doc_id: EntityId,
+    rich: &RichDocument,
+    progress_callback: &dyn Fn(OperationProgress),
+    cancel_flag: &AtomicBool,
+    (progress_from, progress_to): (f32, f32),
+) -> Result<i64> {
+    for frame_id in uow.rit_get_frames(doc_id)? {
+        uow.rit_remove_frame(frame_id)?;
+    }
+    let root_frame = uow.rit_create_frame(&Frame::default(), doc_id)?;
+    // Importers replace the entire document — reset the rope and
+    // block_offsets.
+    rope_reset(&uow.rit_store());
+
+    for resource in &rich.resources {
+        let entity = Resource {
+            resource_type: ResourceType::Image,
+            name: resource.name.clone(),
+            mime_type: resource.mime_type.clone(),
+            data_base64: BASE64.encode(&resource.data),
+            ..Resource::default()
+        };
+        uow.rit_create_resource(&entity, doc_id)?;
+    }
+
+    let mut builder = Builder {
+        uow,
+        doc_id,
+        lists: HashMap::new(),
+        document_position: 0,
+        total_chars: 0,
+        total_blocks: 0,
+        emitted_any_main_block: false,
+    };
+    let mut child_order: Vec<i64> = Vec::new();
+    let total_elements = rich.elements.len();
+
+    for (i, element) in rich.elements.iter().enumerate() {
+        if cancel_flag.load(Ordering::Relaxed) {
+            return Err(anyhow!("Operation was cancelled"));
+        }
+        match element {
+            RichElement::Block(block) => {
+                let created = builder.add_block(block, root_frame.id)?;
+                child_order.push(created.id as i64);
+            }
+            RichElement::Table(table) => {
+                if table.cells.is_empty() {
+                    continue;
+                }
+                let anchor_id = builder.add_table(table, root_frame.id)?;
+                child_order.push(-(anchor_id as i64));
+            }
+        }
+
+        if i % 10 == 0 {
+            let pct =
+                progress_from + (i as f32 / total_elements as f32) * (progress_to - progress_from);
+            progress_callback(OperationProgress::new(
+                pct,
+                Some(format!("Processing element {}/{}", i + 1, total_elements)),
+            ));
+        }
+    }
+
+    let total_chars = builder.total_chars;
+    let total_blocks = builder.total_blocks;
+
+    let mut updated_frame = uow
+        .rit_get_frame(root_frame.id)?
+        .ok_or_else(|| anyhow!("Created frame not found"))?;
+    updated_frame.child_order = child_order;
+    uow.rit_update_frame(&updated_frame)?;
+
+    let mut updated_doc = uow
+        .rit_get_document(doc_id)?
+        .ok_or_else(|| anyhow!("Document not found after import"))?;
+    updated_doc.character_count = total_chars;
+    updated_doc.block_count = total_blocks;
+    uow.rit_update_document(&updated_doc)?;
+
+    Ok(total_blocks)
+}
@@ -0,0 +1,159 @@
+use crate::{ImportNativeDto, ImportNativeResultDto};
+use anyhow::{Result, anyhow};
+use common::database::CommandUnitOfWork;
+use common::database::edit_record::EditRecord;
+use common::database::native_format::{self, NativeUndoEntry, NativeUndoHistory};
+use common::database::rope_store::RopeStoreSnapshot;
+use common::database::store_delta::StoreDelta;
+use common::entities::{Document, Root};
+use common::types::{EntityId, ROOT_ENTITY_ID};
+use common::undo_redo::UndoRedoCommand;
+use std::any::Any;
+
+pub trait ImportNativeUnitOfWorkFactoryTrait: Send + Sync {
+    fn create(&self) -> Box<dyn ImportNativeUnitOfWorkTrait>;
+}
+
+#[macros::uow_action(entity = "Root", action = "Get")]
+#[macros::uow_action(entity = "Document", action = "Get")]
+pub trait ImportNativeUnitOfWorkTrait: CommandUnitOfWork {}
+
+pub struct ImportNativeUseCase {
+    uow_factory: Box<dyn ImportNativeUnitOfWorkFactoryTrait>,
+}
+
+impl ImportNativeUseCase {
+    pub fn new(uow_factory: Box<dyn ImportNativeUnitOfWorkFactoryTrait>) -> Self {
+        ImportNativeUseCase { uow_factory }
+    }
+
+    pub fn execute(&mut self, dto: &ImportNativeDto) -> Result<ImportNativeResultDto> {
+        // Decode before opening the transaction: a malformed or too-new
+        // file must leave the current document untouched.
+        let snapshot = native_format::decode(&dto.native_data)?;
+        self.load(snapshot)
+    }
+
+    /// Like [`execute`](Self::execute), also returning the undo history
+    /// saved in the file. The history is `None` when the file has none,
+    /// or has one that is incompatible with this version or with the
+    /// document it was saved with.
+    pub fn execute_with_history(
+        &mut self,
+        dto: &ImportNativeDto,
+    ) -> Result<(ImportNativeResultDto, Option<NativeUndoHistory>)> {
+        let (snapshot, history) = native_format::decode_with_history(&dto.native_data)?;
+        Ok((self.load(snapshot)?, history))
+    }
+
+    fn load(&mut self, snapshot: RopeStoreSnapshot) -> Result<ImportNativeResultDto> {
+        let mut uow = self.uow_factory.create();
+        uow.begin_transaction()?;
+
+        native_format::load_into(&uow.store(), snapshot);
+
+        let root = uow
+            .get_root(&ROOT_ENTITY_ID)?
+            .ok_or_else(|| anyhow!("Root entity not found after import"))?;
+        let document = uow
+            .get_document(&root.document)?
+            .ok_or_else(|| anyhow!("Document not found after import"))?;
+
+        uow.commit()?;
+        Ok(ImportNativeResultDto {
+            block_count: document.block_count,
+        })
+    }
+}
+
+/// An undo entry restored from a saved history. It replays the recorded
+/// [`StoreDelta`]s instead of re-running the original operation, and
+/// refuses to touch the document if it no longer matches them.
+pub struct RestoredEditUseCase {
+    uow_factory: Box<dyn ImportNativeUnitOfWorkFactoryTrait>,
+    description: String,
+    deltas: Vec<StoreDelta>,
+}
+
+impl RestoredEditUseCase {
+    pub fn new(
+        uow_factory: Box<dyn ImportNativeUnitOfWorkFactoryTrait>,
+        entry: NativeUndoEntry,
+    ) -> Self {
+        RestoredEditUseCase {
+            uow_factory,
+            description: entry.description,
+            deltas: entry.deltas,
+        }
+    }
+
+    fn step(
+        &self,
+        transform: impl Fn(&RopeStoreSnapshot) -> Result<RopeStoreSnapshot>,
+    ) -> Result<()> {
+        let mut uow = self.uow_factory.create();
+        uow.begin_transaction()?;
+        let store = uow.store();
+        match transform(&store.snapshot()) {
+            Ok(next) => {
+                store.restore_without_counters(&next);
+                uow.commit()
+            }
+            Err(e) => {
+                uow.rollback()?;
+                Err(e)
+            }
+        }
+    }
+}
+
+impl UndoRedoCommand for RestoredEditUseCase {
+    fn undo(&mut self) -> Result<()> {
+        self.step(|snap| {
+            self.deltas
+                .iter()
+                .rev()
+                .try_fold(snap.clone(), |state, delta| delta.revert(&state))
+        })
+    }
+
+    fn redo(&mut self) -> Result<()> {
+        self.step(|snap| {
+            self.deltas
+                .iter()
+                .try_fold(snap.clone(), |state, delta| delta.apply(&state))
+        })
+    }
+
+    fn affected_blocks(&self) -> Option<Vec<EntityId>> {
+        let mut blocks = Vec::new();
+        for delta in &self.deltas {
+            blocks.extend(delta.changed_blocks.as_ref()?);
+        }
+        blocks.sort_unstable();
+        blocks.dedup();
+        Some(blocks)
+    }
+
+    fn description(&self) -> String {
+        self.description.clone()
+    }
+
+    fn approximate_size(&self) -> usize {
+        std::mem::size_of_val(self)
+            + self.description.len()
+            + self
+                .deltas
+                .iter()
+                .map(StoreDelta::approximate_size)
+                .sum::<usize>()
+    }
+
+    fn edit_records(&self) -> Option<Vec<EditRecord>> {
+        Some(self.deltas.iter().cloned().map(EditRecord::Store).collect())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
@@ -0,0 +1,680 @@
+//! OpenDocument Text (ODT) reader producing a [`RichDocument`].
+//!
+//! Reads `content.xml` and the styles it refers to, from its own
+//! automatic styles and from `styles.xml`. Character formatting comes
+//! from `text` family styles (resolved through `style:parent-style-name`)
+//! and from the automatic paragraph style of each paragraph; common
+//! paragraph styles only contribute headings and code blocks.
+
+use crate::use_cases::package_helpers::{
+    attr, child, file_name, is, mime_type_for, read_binary_part, read_part,
+};
+use anyhow::{Result, anyhow};
+use common::entities::{Alignment, CharVerticalAlignment, ListStyle, UnderlineStyle};
+use common::format_runs::CharacterFormat;
+use common::parser_tools::css_color::normalize_css_color;
+use common::parser_tools::rich_document::{
+    RichBlock, RichCell, RichDocument, RichElement, RichInline, RichListItem, RichResource,
+    RichTable,
+};
+use roxmltree::Node;
+use std::collections::HashMap;
+use std::io::Cursor;
+
+/// Paragraph style the ODT writer uses for code blocks.
+pub(crate) const CODE_STYLE: &str = "Preformatted_20_Text";
+
+#[derive(Debug, Default, Clone)]
+struct StyleDef {
+    parent: Option<String>,
+    automatic: bool,
+    /// `style:text-properties` attributes, by local name.
+    text_properties: Vec<(String, String)>,
+    alignment: Option<Alignment>,
+}
+
+/// List style name → style per level (1-based, as in `text:level`).
+type ListStyles = HashMap<String, HashMap<u32, ListStyle>>;
+
+/// Parse ODT bytes. Fails when the input is not a zip archive or has no
+/// `content.xml`.
+pub(crate) fn read_odt(bytes: &[u8]) -> Result<RichDocument> {
+    let mut archive =
+        zip::ZipArchive::new(Cursor::new(bytes)).map_err(|e| anyhow!("Not an ODT file: {}", e))?;
+
+    let content_xml = read_part(&mut archive, "content.xml")?
+        .ok_or_else(|| anyhow!("Not an ODT file: missing content.xml"))?;
+    let styles_xml = read_part(&mut archive, "styles.xml")?;
+
+    let mut styles = HashMap::new();
+    let mut list_styles = ListStyles::new();
+    if let Some(styles_xml) = styles_xml.as_deref() {
+        let xml = roxmltree::Document::parse(styles_xml)
+            .map_err(|e| anyhow!("Invalid styles.xml: {}", e))?;
+        collect_styles(xml.root_element(), &mut styles, &mut list_styles);
+    }
+
+    let xml = roxmltree::Document::parse(&content_xml)
+        .map_err(|e| anyhow!("Invalid content.xml: {}", e))?;
+    collect_styles(xml.root_element(), &mut styles, &mut list_styles);
+    let text = xml
+        .descendants()
+        .find(|n| is(n, "text") && n.parent().is_some_and(|p| is(&p, "body")))
+        .ok_or_else(|| anyhow!("content.xml has no office:text body"))?;
+
+    let mut reader = ContentReader {
+        styles: &styles,
+        list_styles: &list_styles,
+        images: Vec::new(),
+        lists: 0,
+        last_list_key: HashMap::new(),
+    };
+    let mut elements = Vec::new();
+    reader.read_container(text, &mut elements);
+
+    let mut resources = Vec::new();
+    for path in reader.images {
+        if let Some(data) = read_binary_part(&mut archive, &path)? {
+            resources.push(RichResource {
+                name: file_name(&path).to_string(),
+                mime_type: mime_type_for(&path).to_string(),
+                data,
+            });
+        }
+    }
+
+    Ok(RichDocument {
+        elements,
+        resources,
+    })
+}
+
+/// Gather `style:style` and `text:list-style` definitions from the
+/// `office:styles` and `office:automatic-styles` sections of a part.
+fn collect_styles(
+    root: Node,
+    styles: &mut HashMap<String, StyleDef>,
+    list_styles: &mut ListStyles,
+) {
+    for section in root
+        .children()
+        .filter(|n| is(n, "styles") || is(n, "automatic-styles"))
+    {
+        let automatic = is(&section, "automatic-styles");
+        for n in section.children().filter(|n| n.is_element()) {
+            let Some(name) = attr(n, "name") else {
+                continue;
+            };
+            if is(&n, "style") {
+                let mut def = StyleDef {
+                    parent: attr(n, "parent-style-name").map(str::to_string),
+                    automatic,
+                    ..StyleDef::default()
+                };
+                if let Some(props) = child(n, "text-properties") {
+                    def.text_properties = props
+                        .attributes()
+                        .map(|a| (a.name().to_string(), a.value().to_string()))
+                        .collect();
+                }
+                if let Some(props) = child(n, "paragraph-properties") {
+                    def.alignment = match attr(props, "text-align") {
+                        Some("center") => Some(Alignment::Center),
+                        Some("end" | "right") => Some(Alignment::Right),
+                        Some("justify") => Some(Alignment::Justify),
+                        Some("start" | "left") => Some(Alignment::Left),
+                        _ => None,
+                    };
+                }
+                styles.insert(name.to_string(), def);
+            } else if is(&n, "list-style") {
+                list_styles.insert(name.to_string(), parse_list_style(n));
+            }
+        }
+    }
+}
+
+fn parse_list_style(list_style: Node) -> HashMap<u32, ListStyle> {
+    let mut levels = HashMap::new();
+    for level in list_style.children().filter(|n| n.is_element()) {
+        let Some(number) = attr(level, "level").and_then(|v| v.parse::<u32>().ok()) else {
+            continue;
+        };
+        let style = if is(&level, "list-level-style-number") {
+            match attr(level, "num-format") {
+                Some("a") => ListStyle::LowerAlpha,
+                Some("A") => ListStyle::UpperAlpha,
+                Some("i") => ListStyle::LowerRoman,
+                Some("I") => ListStyle::UpperRoman,
+                _ => ListStyle::Decimal,
+            }
+        } else {
+            match attr(level, "bullet-char") {
+                Some("◦" | "○") => ListStyle::Circle,
+                Some("▪" | "■") => ListStyle::Square,
+                _ => ListStyle::Disc,
+            }
+        };
+        levels.insert(number, style);
+    }
+    levels
+}
+
+/// Apply `style:text-properties` attributes to `format`. Explicit
+/// "none"/"normal" values clear inherited formatting.
+fn apply_text_properties(properties: &[(String, String)], format: &mut CharacterFormat) {
+    for (name, value) in properties {
+        let value = value.as_str();
+        match name.as_str() {
+            "font-weight" => match value {
+                "bold" => format.font_bold = Some(true),
+                "normal" => {
+                    format.font_bold = None;
+                    format.font_weight = None;
+                }
+                weight => {
+                    if let Ok(weight) = weight.parse::<i64>() {
+                        format.font_weight = Some(weight);
+                    }
+                }
+            },
+            "font-style" => format.font_italic = (value != "normal").then_some(true),
+            "text-underline-style" => {
+                let style = match value {
+                    "none" => None,
+                    "dash" | "long-dash" => Some(UnderlineStyle::DashUnderline),
+                    "dotted" => Some(UnderlineStyle::DotLine),
+                    "dot-dash" => Some(UnderlineStyle::DashDotLine),
+                    "dot-dot-dash" => Some(UnderlineStyle::DashDotDotLine),
+                    "wave" => Some(UnderlineStyle::WaveUnderline),
+                    _ => Some(UnderlineStyle::SingleUnderline),
+                };
+                format.font_underline = style.is_some().then_some(true);
+                format.underline_style = style.filter(|s| *s != UnderlineStyle::SingleUnderline);
+            }
+            "color" => format.foreground_color = normalize_css_color(value),
+            "background-color" => {
+                format.background_color =
+                    normalize_css_color(value).filter(|_| value != "transparent")
+            }
+            "text-underline-color" => format.underline_color = normalize_css_color(value),
+            "text-overline-style" => format.font_overline = (value != "none").then_some(true),
+            "text-line-through-style" => format.font_strikeout = (value != "none").then_some(true),
+            "font-family" | "font-name" => {
+                let family = value.trim().trim_matches(|c| c == '\'' || c == '"');
+                if !family.is_empty() {
+                    format.font_family = Some(family.to_string());
+                }
+            }
+            "font-size" => {
+                if let Some(size) = value
+                    .strip_suffix("pt")
+                    .and_then(|v| v.trim().parse::<f64>().ok())
+                {
+                    format.font_point_size = Some(size.round() as i64);
+                }
+            }
+            "text-position" => {
+                let position = value.split_whitespace().next().unwrap_or("0%");
+                let offset = position.trim_end_matches('%').parse::<f64>().unwrap_or(0.0);
+                format.vertical_alignment = if position == "super" || offset > 0.0 {
+                    Some(CharVerticalAlignment::SuperScript)
+                } else if position == "sub" || offset < 0.0 {
+                    Some(CharVerticalAlignment::SubScript)
+                } else {
+                    None
+                };
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Convert an ODF length ("2.5cm", "1in", "96px", ...) to pixels at
+/// 96 DPI.
+fn length_to_pixels(value: &str) -> i64 {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| c.is_ascii_alphabetic())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let Ok(number) = number.parse::<f64>() else {
+        return 0;
+    };
+    let pixels = match unit {
+        "in" => number * 96.0,
+        "cm" => number * 96.0 / 2.54,
+        "mm" => number * 96.0 / 25.4,
+        "pt" => number * 96.0 / 72.0,
+        "pc" => number * 16.0,
+        _ => number,
+    };
+    pixels.round() as i64
+}
+
+struct ContentReader<'s> {
+    styles: &'s HashMap<String, StyleDef>,
+    list_styles: &'s ListStyles,
+    /// Package paths of embedded images, in first-use order.
+    images: Vec<String>,
+    /// Number of top-level `text:list` elements seen so far.
+    lists: usize,
+    /// List style name → key of the last top-level list using it, for
+    /// `text:continue-numbering`.
+    last_list_key: HashMap<String, String>,
+}
+
+/// List context of the `text:list-item` being read.
+struct ListContext {
+    key: String,
+    style_name: Option<String>,
+    depth: u32,
+}
+
+impl ContentReader<'_> {
+    /// Walk `name`'s style and its ancestors, root first.
+    fn style_chain<'a>(&'a self, name: &'a str) -> Vec<(&'a str, &'a StyleDef)> {
+        let mut chain = Vec::new();
+        let mut current = Some(name);
+        while let Some(name) = current {
+            let Some(def) = self.styles.get(name) else {
+                break;
+            };
+            if chain.iter().any(|(n, _)| *n == name) {
+                break;
+            }
+            chain.push((name, def));
+            current = def.parent.as_deref();
+        }
+        chain.reverse();
+        chain
+    }
+
+    fn apply_text_style(&self, name: &str, format: &mut CharacterFormat) {
+        for (_, def) in self.style_chain(name) {
+            apply_text_properties(&def.text_properties, format);
+        }
+    }
+
+    /// Read block-level content of `office:text`, sections, list items
+    /// and table cells.
+    fn read_container(&mut self, node: Node, out: &mut Vec<RichElement>) {
+        self.read_container_in_list(node, None, out);
+    }
+
+    fn read_container_in_list(
+        &mut self,
+        node: Node,
+        list: Option<&ListContext>,
+        out: &mut Vec<RichElement>,
+    ) {
+        for n in node.children().filter(|n| n.is_element()) {
+            match n.tag_name().name() {
+                "p" | "h" => {
+                    let item = list.map(|l| self.list_item(l));
+                    out.extend(
+                        self.read_paragraph(n, item)
+                            .into_iter()
+                            .map(RichElement::Block),
+                    );
+                }
+                "list" => self.read_list(n, list, out),
+                "table" => {
+                    if let Some(table) = self.read_table(n) {
+                        out.push(RichElement::Table(table));
+                    }
+                }
+                "section" | "index-body" | "list-header" => {
+                    self.read_container_in_list(n, list, out)
+                }
+                "table-of-content" | "illustration-index" | "alphabetical-index" | "user-index"
+                | "bibliography" | "object-index" | "table-index" => {
+                    if let Some(body) = child(n, "index-body") {
+                        self.read_container_in_list(body, list, out);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn read_list(&mut self, node: Node, parent: Option<&ListContext>, out: &mut Vec<RichElement>) {
+        let style_name = attr(node, "style-name")
+            .map(str::to_string)
+            .or_else(|| parent.and_then(|p| p.style_name.clone()));
+        let context = match parent {
+            Some(p) => ListContext {
+                key: p.key.clone(),
+                style_name,
+                depth: p.depth + 1,
+            },
+            None => {
+                let continues = attr(node, "continue-numbering") == Some("true")
+                    || attr(node, "continue-list").is_some();
+                let previous = style_name
+                    .as_ref()
+                    .and_then(|s| self.last_list_key.get(s))
+                    .filter(|_| continues)
+                    .cloned();
+                let key = previous.unwrap_or_else(|| {
+                    self.lists += 1;
+                    format!("{}#{}", style_name.as_deref().unwrap_or(""), self.lists)
+                });
+                if let Some(style_name) = &style_name {
+                    self.last_list_key.insert(style_name.clone(), key.clone());
+                }
+                ListContext {
+                    key,
+                    style_name,
+                    depth: 0,
+                }
+            }
+        };
+        for item in node
+            .children()
+            .filter(|n| is(n, "list-item") || is(n, "list-header"))
+        {
+            self.read_container_in_list(item, Some(&context), out);
+        }
+    }
+
+    fn list_item(&self, list: &ListContext) -> RichListItem {
+        let style = list
+            .style_name
+            .as_deref()
+            .and_then(|s| self.list_styles.get(s))
+            .and_then(|levels| levels.get(&(list.depth + 1)).cloned())
+            .unwrap_or_default();
+        RichListItem {
+            key: list.key.clone(),
+            style,
+            indent: list.depth,
+        }
+    }
+
+    /// A paragraph yields one block per line: `text:line-break` starts a
+    /// new block with the same paragraph properties.
+    fn read_paragraph(&mut self, p: Node, list: Option<RichListItem>) -> Vec<RichBlock> {
+        let mut template = RichBlock {
+            list,
+            ..RichBlock::default()
+        };
+        if is(&p, "h") {
+            let level = attr(p, "outline-level")
+                .and_then(|v| v.parse::<i64>().ok())
+                .unwrap_or(1);
+            template.heading_level = Some(level.clamp(1, 6));
+        }
+
+        let mut base = CharacterFormat::default();
+        if let Some(style_name) = attr(p, "style-name") {
+            for (name, def) in self.style_chain(style_name) {
+                if def.alignment.is_some() {
+                    template.alignment = def.alignment.clone();
+                }
+                if name == CODE_STYLE {
+                    template.is_code_block = true;
+                }
+                if def.automatic {
+                    apply_text_properties(&def.text_properties, &mut base);
+                }
+            }
+        }
+
+        let mut state = ParagraphState {
+            lines: vec![template.clone()],
+            template,
+            pending_space: false,
+            anchor_names: Vec::new(),
+        };
+        self.read_inline_container(p, &base, &mut state);
+        state.lines
+    }
+
+    fn read_inline_container(
+        &mut self,
+        node: Node,
+        base: &CharacterFormat,
+        state: &mut ParagraphState,
+    ) {
+        for n in node.children() {
+            if n.is_text() {
+                state.push_collapsed(n.text().unwrap_or(""), base);
+                continue;
+            }
+            if !n.is_element() {
+                continue;
+            }
+            match n.tag_name().name() {
+                "span" => {
+                    let mut format = base.clone();
+                    if let Some(style_name) = attr(n, "style-name") {
+                        self.apply_text_style(style_name, &mut format);
+                    }
+                    self.read_inline_container(n, &format, state);
+                }
+                "a" => {
+                    let mut format = base.clone();
+                    if let Some(href) = attr(n, "href") {
+                        format.anchor_href = Some(href.to_string());
+                        format.is_anchor = Some(true);
+                    }
+                    if let Some(title) = attr(n, "title") {
+                        format.tooltip = Some(title.to_string());
+                    }
+                    if let Some(style_name) = attr(n, "style-name") {
+                        self.apply_text_style(style_name, &mut format);
+                    }
+                    self.read_inline_container(n, &format, state);
+                }
+                "s" => {
+                    let count = attr(n, "c")
+                        .and_then(|v| v.parse::<usize>().ok())
+                        .unwrap_or(1);
+                    state.push(&" ".repeat(count), base);
+                }
+                "tab" => state.push("\t", base),
+                "line-break" => {
+                    state.pending_space = false;
+                    let template = state.template.clone();
+                    state.lines.push(template);
+                }
+                "bookmark" | "bookmark-start" | "reference-mark" | "reference-mark-start" => {
+                    if let Some(name) = attr(n, "name") {
+                        state.anchor_names.push(name.to_string());
+                    }
+                }
+                "frame" => {
+                    if let Some(image) = self.read_frame(n, base) {
+                        state.pending_space = false;
+                        state.line().inlines.push(image);
+                    }
+                }
+                // Notes, annotations and change-tracking markup hold text
+                // that is not part of the paragraph flow.
+                "note" | "annotation" | "annotation-end" | "change" | "change-start"
+                | "change-end" | "bookmark-end" | "reference-mark-end" | "soft-page-break" => {}
+                _ => self.read_inline_container(n, base, state),
+            }
+        }
+    }
+
+    fn read_frame(&mut self, frame: Node, format: &CharacterFormat) -> Option<RichInline> {
+        let image = child(frame, "image")?;
+        let href = attr(image, "href")?;
+        let width = attr(frame, "width").map(length_to_pixels).unwrap_or(0);
+        let height = attr(frame, "height").map(length_to_pixels).unwrap_or(0);
+        let external = href.contains("://") || href.starts_with("../");
+        let name = if external {
+            href.to_string()
+        } else {
+            let path = href.trim_start_matches("./").to_string();
+            let name = file_name(&path).to_string();
+            if !self.images.contains(&path) {
+                self.images.push(path);
+            }
+            name
+        };
+        Some(RichInline::Image {
+            name,
+            width,
+            height,
+            format: format.clone(),
+        })
+    }
+
+    fn read_table(&mut self, table: Node) -> Option<RichTable> {
+        let mut declared_columns = 0;
+        let mut rows = Vec::new();
+        collect_table_rows(table, &mut declared_columns, &mut rows);
+
+        let mut cells: Vec<RichCell> = Vec::new();
+        let mut columns = declared_columns;
+        for (row, tr) in rows.iter().enumerate() {
+            let mut column = 0;
+            for tc in tr.children().filter(|n| n.is_element()) {
+                let repeated = attr(tc, "number-columns-repeated")
+                    .and_then(|v| v.parse::<usize>().ok())
+                    .filter(|r| *r > 0)
+                    .unwrap_or(1);
+                if is(&tc, "covered-table-cell") {
+                    column += repeated;
+                    continue;
+                }
+                if !is(&tc, "table-cell") {
+                    continue;
+                }
+                let span = |name| {
+                    attr(tc, name)
+                        .and_then(|v| v.parse::<usize>().ok())
+                        .filter(|s| *s > 0)
+                        .unwrap_or(1)
+                };
+                let column_span = span("number-columns-spanned");
+                let row_span = span("number-rows-spanned");
+                let blocks = self.read_blocks(tc);
+                for _ in 0..repeated {
+                    cells.push(RichCell {
+                        row,
+                        column,
+                        row_span,
+                        column_span,
+                        blocks: blocks.clone(),
+                    });
+                    column += 1;
+                }
+            }
+            columns = columns.max(column);
+        }
+
+        if rows.is_empty() || columns == 0 {
+            return None;
+        }
+        Some(RichTable {
+            rows: rows.len(),
+            columns,
+            cells,
+        })
+    }
+
+    /// Block-level content flattened to blocks, for table cells.
+    fn read_blocks(&mut self, node: Node) -> Vec<RichBlock> {
+        let mut elements = Vec::new();
+        self.read_container(node, &mut elements);
+        let mut blocks = Vec::new();
+        for element in elements {
+            match element {
+                RichElement::Block(b) => blocks.push(b),
+                RichElement::Table(t) => {
+                    blocks.extend(t.cells.into_iter().flat_map(|c| c.blocks));
+                }
+            }
+        }
+        blocks
+    }
+}
+
+/// Collect `table:table-row`s (also inside header-rows and row groups)
+/// and count declared columns.
+fn collect_table_rows<'a, 'i>(
+    node: Node<'a, 'i>,
+    columns: &mut usize,
+    rows: &mut Vec<Node<'a, 'i>>,
+) {
+    for n in node.children().filter(|n| n.is_element()) {
+        match n.tag_name().name() {
+            "table-column" => {
+                *columns += attr(n, "number-columns-repeated")
+                    .and_then(|v| v.parse::<usize>().ok())
+                    .unwrap_or(1);
+            }
+            "table-row" => {
+                let repeated = attr(n, "number-rows-repeated")
+                    .and_then(|v| v.parse::<usize>().ok())
+                    .filter(|r| *r > 0)
+                    .unwrap_or(1);
+                rows.extend(std::iter::repeat_n(n, repeated));
+            }
+            "table-columns"
+            | "table-header-columns"
+            | "table-column-group"
+            | "table-rows"
+            | "table-header-rows"
+            | "table-row-group" => collect_table_rows(n, columns, rows),
+            _ => {}
+        }
+    }
+}
+
+/// Lines being built for one paragraph, with ODF whitespace handling:
+/// runs of literal whitespace collapse to one space and leading
+/// whitespace is dropped; `text:s` and `text:tab` are kept verbatim.
+struct ParagraphState {
+    lines: Vec<RichBlock>,
+    template: RichBlock,
+    pending_space: bool,
+    /// Bookmark names waiting for the next text run.
+    anchor_names: Vec<String>,
+}
+
+impl ParagraphState {
+    fn line(&mut self) -> &mut RichBlock {
+        self.lines
+            .last_mut()
+            .expect("paragraph has at least one line")
+    }
+
+    fn push_collapsed(&mut self, text: &str, format: &CharacterFormat) {
+        let mut collapsed = String::with_capacity(text.len());
+        let mut at_start = self.line().inlines.is_empty();
+        for c in text.chars() {
+            if c.is_whitespace() {
+                if !at_start && !self.pending_space {
+                    collapsed.push(' ');
+                    self.pending_space = true;
+                }
+            } else {
+                collapsed.push(c);
+                self.pending_space = false;
+                at_start = false;
+            }
+        }
+        let pending = self.pending_space;
+        self.push(&collapsed, format);
+        self.pending_space = pending;
+    }
+
+    fn push(&mut self, text: &str, format: &CharacterFormat) {
+        if text.is_empty() {
+            return;
+        }
+        self.pending_space = false;
+        if self.anchor_names.is_empty() {
+            self.line().push_text(text, format);
+        } else {
+            let mut format = format.clone();
+            format.anchor_names = std::mem::take(&mut self.anchor_names);
+            self.line().push_text(text, &format);
+        }
+    }
+}
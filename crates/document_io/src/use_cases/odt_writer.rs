@@ -0,0 +1,741 @@
+//! OpenDocument Text (ODT) writer serializing a [`RichDocument`].
+//!
+//! Character and paragraph formatting become automatic styles in
+//! `content.xml`; `styles.xml` only carries the common heading and code
+//! block styles the paragraphs refer to. Images are stored under
+//! `Pictures/`.
+
+use crate::use_cases::odt_reader::CODE_STYLE;
+use crate::use_cases::package_helpers::file_name;
+use anyhow::Result;
+use common::entities::{Alignment, CharVerticalAlignment, ListStyle, UnderlineStyle};
+use common::format_runs::CharacterFormat;
+use common::parser_tools::css_color::rgb_hex_digits;
+use common::parser_tools::rich_document::{
+    RichBlock, RichDocument, RichElement, RichInline, RichTable,
+};
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write as _;
+use std::io::{Cursor, Write};
+use zip::write::FileOptions;
+
+const MIMETYPE: &str = "application/vnd.oasis.opendocument.text";
+
+/// Levels defined for every list style, as LibreOffice does.
+const LIST_LEVELS: u32 = 10;
+
+const NAMESPACES: &str = concat!(
+    r#"xmlns:office="urn:oasis:names:tc:opendocument:xmlns:office:1.0" "#,
+    r#"xmlns:style="urn:oasis:names:tc:opendocument:xmlns:style:1.0" "#,
+    r#"xmlns:text="urn:oasis:names:tc:opendocument:xmlns:text:1.0" "#,
+    r#"xmlns:table="urn:oasis:names:tc:opendocument:xmlns:table:1.0" "#,
+    r#"xmlns:draw="urn:oasis:names:tc:opendocument:xmlns:drawing:1.0" "#,
+    r#"xmlns:fo="urn:oasis:names:tc:opendocument:xmlns:xsl-fo-compatible:1.0" "#,
+    r#"xmlns:xlink="http://www.w3.org/1999/xlink" "#,
+    r#"xmlns:dc="http://purl.org/dc/elements/1.1/" "#,
+    r#"xmlns:meta="urn:oasis:names:tc:opendocument:xmlns:meta:1.0" "#,
+    r#"xmlns:svg="urn:oasis:names:tc:opendocument:xmlns:svg-compatible:1.0" "#,
+    r#"office:version="1.3""#,
+);
+
+/// Serialize `doc` as an ODT package. Returns the package bytes and the
+/// number of paragraphs written.
+pub(crate) fn write_odt(doc: &RichDocument, title: &str) -> Result<(Vec<u8>, i64)> {
+    let mut writer = ContentWriter::new(doc);
+    let items: Vec<FlowItem> = doc
+        .elements
+        .iter()
+        .map(|e| match e {
+            RichElement::Block(b) => FlowItem::Block(b),
+            RichElement::Table(t) => FlowItem::Table(t),
+        })
+        .collect();
+    writer.write_flow(&items);
+
+    let content = format!(
+        concat!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+            "\n<office:document-content {}>",
+            "<office:automatic-styles>{}</office:automatic-styles>",
+            "<office:body><office:text>{}</office:text></office:body>",
+            "</office:document-content>"
+        ),
+        NAMESPACES,
+        writer.automatic_styles(),
+        writer.body
+    );
+
+    let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    let stored = FileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    let deflated = FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+
+    // The mimetype entry must come first and be stored uncompressed so
+    // the package type can be sniffed from a fixed offset.
+    zip.start_file("mimetype", stored)?;
+    zip.write_all(MIMETYPE.as_bytes())?;
+    zip.start_file("content.xml", deflated)?;
+    zip.write_all(content.as_bytes())?;
+    zip.start_file("styles.xml", deflated)?;
+    zip.write_all(styles_xml().as_bytes())?;
+    zip.start_file("meta.xml", deflated)?;
+    zip.write_all(meta_xml(title).as_bytes())?;
+
+    let mut manifest_entries = String::new();
+    for resource in &doc.resources {
+        let Some(path) = writer.image_paths.get(&resource.name) else {
+            continue;
+        };
+        zip.start_file(path.as_str(), stored)?;
+        zip.write_all(&resource.data)?;
+        let _ = write!(
+            manifest_entries,
+            r#"<manifest:file-entry manifest:full-path="{}" manifest:media-type="{}"/>"#,
+            escape_xml(path),
+            escape_xml(&resource.mime_type)
+        );
+    }
+    zip.start_file("META-INF/manifest.xml", deflated)?;
+    zip.write_all(manifest_xml(&manifest_entries).as_bytes())?;
+
+    let cursor = zip.finish()?;
+    Ok((cursor.into_inner(), writer.paragraph_count))
+}
+
+#[derive(Clone, Copy)]
+enum FlowItem<'a> {
+    Block(&'a RichBlock),
+    Table(&'a RichTable),
+}
+
+#[derive(Clone, PartialEq)]
+struct ParagraphStyle {
+    parent: String,
+    alignment: Alignment,
+}
+
+struct ContentWriter {
+    body: String,
+    paragraph_count: i64,
+    text_styles: Vec<CharacterFormat>,
+    paragraph_styles: Vec<ParagraphStyle>,
+    /// List style definitions, `L{index + 1}`: style per level.
+    list_styles: Vec<Vec<ListStyle>>,
+    /// List key of the first item of a list → its list style name.
+    list_style_names: HashMap<String, String>,
+    /// Resource name → package path.
+    image_paths: HashMap<String, String>,
+    tables: usize,
+    images: usize,
+}
+
+impl ContentWriter {
+    fn new(doc: &RichDocument) -> Self {
+        let mut image_paths = HashMap::new();
+        let mut used = HashSet::new();
+        for (index, resource) in doc.resources.iter().enumerate() {
+            let mut stem: String = file_name(&resource.name)
+                .chars()
+                .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'))
+                .collect();
+            if stem.is_empty() || stem.starts_with('.') || used.contains(&stem) {
+                stem = format!("image{}{}", index + 1, extension_for(&resource.mime_type));
+            } else if !stem.contains('.') {
+                stem.push_str(extension_for(&resource.mime_type));
+            }
+            used.insert(stem.clone());
+            image_paths.insert(resource.name.clone(), format!("Pictures/{}", stem));
+        }
+        ContentWriter {
+            body: String::new(),
+            paragraph_count: 0,
+            text_styles: Vec::new(),
+            paragraph_styles: Vec::new(),
+            list_styles: Vec::new(),
+            list_style_names: HashMap::new(),
+            image_paths,
+            tables: 0,
+            images: 0,
+        }
+    }
+
+    /// Write blocks and tables, grouping consecutive list items into one
+    /// `text:list`.
+    fn write_flow(&mut self, items: &[FlowItem]) {
+        let mut i = 0;
+        while i < items.len() {
+            match items[i] {
+                FlowItem::Block(block) if block.list.is_some() => {
+                    let run_end = items[i..]
+                        .iter()
+                        .position(|item| !matches!(item, FlowItem::Block(b) if b.list.is_some()))
+                        .map_or(items.len(), |p| i + p);
+                    let run: Vec<&RichBlock> = items[i..run_end]
+                        .iter()
+                        .filter_map(|item| match item {
+                            FlowItem::Block(b) => Some(*b),
+                            FlowItem::Table(_) => None,
+                        })
+                        .collect();
+                    self.write_list(&run);
+                    i = run_end;
+                }
+                FlowItem::Block(block) => {
+                    self.write_paragraph(block);
+                    i += 1;
+                }
+                FlowItem::Table(table) => {
+                    self.write_table(table);
+                    i += 1;
+                }
+            }
+        }
+    }
+
+    fn write_list(&mut self, run: &[&RichBlock]) {
+        let first_key = run[0]
+            .list
+            .as_ref()
+            .map(|l| l.key.clone())
+            .unwrap_or_default();
+        let (style_name, continues) = match self.list_style_names.get(&first_key) {
+            Some(name) => (name.clone(), true),
+            None => {
+                let mut levels = vec![None; LIST_LEVELS as usize];
+                for item in run.iter().filter_map(|b| b.list.as_ref()) {
+                    let level = (item.indent as usize).min(LIST_LEVELS as usize - 1);
+                    if levels[level].is_none() {
+                        levels[level] = Some(item.style.clone());
+                    }
+                }
+                self.list_styles
+                    .push(levels.into_iter().map(Option::unwrap_or_default).collect());
+                let name = format!("L{}", self.list_styles.len());
+                self.list_style_names.insert(first_key, name.clone());
+                (name, false)
+            }
+        };
+
+        let _ = write!(self.body, r#"<text:list text:style-name="{}""#, style_name);
+        if continues {
+            self.body.push_str(r#" text:continue-numbering="true""#);
+        }
+        self.body.push('>');
+        for block in run {
+            let depth = block
+                .list
+                .as_ref()
+                .map_or(0, |l| l.indent.min(LIST_LEVELS - 1));
+            // Deeper items nest inside label-less items of the levels
+            // above, as LibreOffice writes them.
+            self.body.push_str("<text:list-item>");
+            for _ in 0..depth {
+                self.body.push_str("<text:list><text:list-item>");
+            }
+            self.write_paragraph(block);
+            for _ in 0..depth {
+                self.body.push_str("</text:list-item></text:list>");
+            }
+            self.body.push_str("</text:list-item>");
+        }
+        self.body.push_str("</text:list>");
+    }
+
+    fn write_paragraph(&mut self, block: &RichBlock) {
+        self.paragraph_count += 1;
+        let parent = match block.heading_level {
+            Some(level) => format!("Heading_20_{}", level.clamp(1, 6)),
+            None if block.is_code_block => CODE_STYLE.to_string(),
+            None => "Standard".to_string(),
+        };
+        let style_name = match &block.alignment {
+            Some(alignment) => {
+                let style = ParagraphStyle {
+                    parent,
+                    alignment: alignment.clone(),
+                };
+                let index = match self.paragraph_styles.iter().position(|s| *s == style) {
+                    Some(index) => index,
+                    None => {
+                        self.paragraph_styles.push(style);
+                        self.paragraph_styles.len() - 1
+                    }
+                };
+                format!("P{}", index + 1)
+            }
+            None => parent,
+        };
+
+        match block.heading_level {
+            Some(level) => {
+                let _ = write!(
+                    self.body,
+                    r#"<text:h text:style-name="{}" text:outline-level="{}">"#,
+                    style_name,
+                    level.clamp(1, 6)
+                );
+            }
+            None => {
+                let _ = write!(self.body, r#"<text:p text:style-name="{}">"#, style_name);
+            }
+        }
+
+        // Leading spaces would be dropped by whitespace collapsing.
+        let mut after_space = true;
+        for inline in &block.inlines {
+            let format = match inline {
+                RichInline::Text { format, .. } | RichInline::Image { format, .. } => format,
+            };
+            for name in &format.anchor_names {
+                let _ = write!(
+                    self.body,
+                    r#"<text:bookmark text:name="{}"/>"#,
+                    escape_xml(name)
+                );
+            }
+            if let Some(href) = &format.anchor_href {
+                let _ = write!(
+                    self.body,
+                    r#"<text:a xlink:type="simple" xlink:href="{}""#,
+                    escape_xml(href)
+                );
+                if let Some(tooltip) = &format.tooltip {
+                    let _ = write!(self.body, r#" office:title="{}""#, escape_xml(tooltip));
+                }
+                self.body.push('>');
+            }
+            match inline {
+                RichInline::Text { text, format } => {
+                    let style = self.text_style_name(format);
+                    if let Some(style) = &style {
+                        let _ = write!(self.body, r#"<text:span text:style-name="{}">"#, style);
+                    }
+                    write_text(&mut self.body, text, &mut after_space);
+                    if style.is_some() {
+                        self.body.push_str("</text:span>");
+                    }
+                }
+                RichInline::Image {
+                    name,
+                    width,
+                    height,
+                    ..
+                } => {
+                    self.write_image(name, *width, *height);
+                    after_space = false;
+                }
+            }
+            if format.anchor_href.is_some() {
+                self.body.push_str("</text:a>");
+            }
+        }
+
+        self.body.push_str(if block.heading_level.is_some() {
+            "</text:h>"
+        } else {
+            "</text:p>"
+        });
+    }
+
+    fn write_image(&mut self, name: &str, width: i64, height: i64) {
+        self.images += 1;
+        let href = self
+            .image_paths
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| name.to_string());
+        let _ = write!(
+            self.body,
+            r#"<draw:frame draw:name="Image{}" text:anchor-type="as-char""#,
+            self.images
+        );
+        if width > 0 {
+            let _ = write!(self.body, r#" svg:width="{:.4}in""#, width as f64 / 96.0);
+        }
+        if height > 0 {
+            let _ = write!(self.body, r#" svg:height="{:.4}in""#, height as f64 / 96.0);
+        }
+        let _ = write!(
+            self.body,
+            concat!(
+                r#"><draw:image xlink:href="{}" xlink:type="simple" xlink:show="embed" "#,
+                r#"xlink:actuate="onLoad"/></draw:frame>"#
+            ),
+            escape_xml(&href)
+        );
+    }
+
+    fn write_table(&mut self, table: &RichTable) {
+        self.tables += 1;
+        let _ = write!(
+            self.body,
+            r#"<table:table table:name="Table{}"><table:table-column table:number-columns-repeated="{}"/>"#,
+            self.tables,
+            table.columns.max(1)
+        );
+
+        let mut covered = HashSet::new();
+        for cell in &table.cells {
+            for r in cell.row..cell.row + cell.row_span.max(1) {
+                for c in cell.column..cell.column + cell.column_span.max(1) {
+                    if (r, c) != (cell.row, cell.column) {
+                        covered.insert((r, c));
+                    }
+                }
+            }
+        }
+
+        for row in 0..table.rows {
+            self.body.push_str("<table:table-row>");
+            for column in 0..table.columns {
+                let cell = table
+                    .cells
+                    .iter()
+                    .find(|c| c.row == row && c.column == column);
+                match cell {
+                    Some(cell) => {
+                        self.body.push_str("<table:table-cell");
+                        if cell.column_span > 1 {
+                            let _ = write!(
+                                self.body,
+                                r#" table:number-columns-spanned="{}""#,
+                                cell.column_span
+                            );
+                        }
+                        if cell.row_span > 1 {
+                            let _ = write!(
+                                self.body,
+                                r#" table:number-rows-spanned="{}""#,
+                                cell.row_span
+                            );
+                        }
+                        self.body.push_str(r#" office:value-type="string">"#);
+                        if cell.blocks.is_empty() {
+                            self.body
+                                .push_str(r#"<text:p text:style-name="Standard"/>"#);
+                        } else {
+                            let items: Vec<FlowItem> =
+                                cell.blocks.iter().map(FlowItem::Block).collect();
+                            self.write_flow(&items);
+                        }
+                        self.body.push_str("</table:table-cell>");
+                    }
+                    None if covered.contains(&(row, column)) => {
+                        self.body.push_str("<table:covered-table-cell/>");
+                    }
+                    None => self.body.push_str(concat!(
+                        r#"<table:table-cell office:value-type="string">"#,
+                        r#"<text:p text:style-name="Standard"/></table:table-cell>"#
+                    )),
+                }
+            }
+            self.body.push_str("</table:table-row>");
+        }
+        self.body.push_str("</table:table>");
+    }
+
+    /// Automatic text style for `format`, without its link and bookmark
+    /// properties (those become `text:a` and `text:bookmark`). `None`
+    /// when nothing remains.
+    fn text_style_name(&mut self, format: &CharacterFormat) -> Option<String> {
+        let style = CharacterFormat {
+            anchor_href: None,
+            anchor_names: Vec::new(),
+            is_anchor: None,
+            tooltip: None,
+            ..format.clone()
+        };
+        if text_properties(&style).is_empty() {
+            return None;
+        }
+        let index = match self.text_styles.iter().position(|s| *s == style) {
+            Some(index) => index,
+            None => {
+                self.text_styles.push(style);
+                self.text_styles.len() - 1
+            }
+        };
+        Some(format!("T{}", index + 1))
+    }
+
+    fn automatic_styles(&self) -> String {
+        let mut out = String::new();
+        for (index, style) in self.paragraph_styles.iter().enumerate() {
+            let align = match style.alignment {
+                Alignment::Left => "start",
+                Alignment::Right => "end",
+                Alignment::Center => "center",
+                Alignment::Justify => "justify",
+            };
+            let _ = write!(
+                out,
+                concat!(
+                    r#"<style:style style:name="P{}" style:family="paragraph" "#,
+                    r#"style:parent-style-name="{}">"#,
+                    r#"<style:paragraph-properties fo:text-align="{}"/></style:style>"#
+                ),
+                index + 1,
+                style.parent,
+                align
+            );
+        }
+        for (index, format) in self.text_styles.iter().enumerate() {
+            let _ = write!(
+                out,
+                concat!(
+                    r#"<style:style style:name="T{}" style:family="text">"#,
+                    r#"<style:text-properties{}/></style:style>"#
+                ),
+                index + 1,
+                text_properties(format)
+            );
+        }
+        for (index, levels) in self.list_styles.iter().enumerate() {
+            let _ = write!(out, r#"<text:list-style style:name="L{}">"#, index + 1);
+            for (level, style) in levels.iter().enumerate() {
+                write_list_level(&mut out, level as u32 + 1, style);
+            }
+            out.push_str("</text:list-style>");
+        }
+        out
+    }
+}
+
+fn write_list_level(out: &mut String, level: u32, style: &ListStyle) {
+    let num_format = match style {
+        ListStyle::Decimal => Some("1"),
+        ListStyle::LowerAlpha => Some("a"),
+        ListStyle::UpperAlpha => Some("A"),
+        ListStyle::LowerRoman => Some("i"),
+        ListStyle::UpperRoman => Some("I"),
+        ListStyle::Disc | ListStyle::Circle | ListStyle::Square => None,
+    };
+    let (element, label) = match num_format {
+        Some(format) => (
+            "text:list-level-style-number",
+            format!(r#"style:num-suffix="." style:num-format="{}""#, format),
+        ),
+        None => {
+            let bullet = match style {
+                ListStyle::Circle => "◦",
+                ListStyle::Square => "▪",
+                _ => "•",
+            };
+            (
+                "text:list-level-style-bullet",
+                format!(r#"text:bullet-char="{}""#, bullet),
+            )
+        }
+    };
+    let _ = write!(
+        out,
+        concat!(
+            r#"<{} text:level="{}" {}>"#,
+            r#"<style:list-level-properties text:list-level-position-and-space-mode="label-alignment">"#,
+            r#"<style:list-level-label-alignment text:label-followed-by="listtab" "#,
+            r#"text:list-tab-stop-position="{:.2}in" fo:text-indent="-0.25in" fo:margin-left="{:.2}in"/>"#,
+            r#"</style:list-level-properties></{}>"#
+        ),
+        element,
+        level,
+        label,
+        level as f64 * 0.5,
+        level as f64 * 0.5,
+        element
+    );
+}
+
+/// `style:text-properties` attributes for `format`, each with a leading
+/// space.
+fn text_properties(format: &CharacterFormat) -> String {
+    let mut out = String::new();
+    if format.font_bold == Some(true) {
+        out.push_str(r#" fo:font-weight="bold""#);
+    } else if let Some(weight) = format.font_weight {
+        let _ = write!(out, r#" fo:font-weight="{}""#, weight);
+    }
+    if format.font_italic == Some(true) {
+        out.push_str(r#" fo:font-style="italic""#);
+    }
+    if format.font_underline == Some(true) {
+        let style = match format.underline_style {
+            Some(UnderlineStyle::DashUnderline) => "dash",
+            Some(UnderlineStyle::DotLine) => "dotted",
+            Some(UnderlineStyle::DashDotLine) => "dot-dash",
+            Some(UnderlineStyle::DashDotDotLine) => "dot-dot-dash",
+            Some(UnderlineStyle::WaveUnderline | UnderlineStyle::SpellCheckUnderline) => "wave",
+            _ => "solid",
+        };
+        let color = format
+            .underline_color
+            .as_deref()
+            .and_then(rgb_hex_digits)
+            .map(|hex| format!("#{}", hex))
+            .unwrap_or_else(|| "font-color".to_string());
+        let _ = write!(
+            out,
+            r#" style:text-underline-style="{}" style:text-underline-width="auto" style:text-underline-color="{}""#,
+            style, color
+        );
+    }
+    if format.font_overline == Some(true) {
+        out.push_str(r#" style:text-overline-style="solid""#);
+    }
+    if format.font_strikeout == Some(true) {
+        out.push_str(r#" style:text-line-through-style="solid""#);
+    }
+    if let Some(family) = &format.font_family {
+        let _ = write!(out, r#" fo:font-family="'{}'""#, escape_xml(family));
+    }
+    if let Some(size) = format.font_point_size {
+        let _ = write!(out, r#" fo:font-size="{}pt""#, size);
+    }
+    if let Some(hex) = format.foreground_color.as_deref().and_then(rgb_hex_digits) {
+        let _ = write!(out, r##" fo:color="#{}""##, hex);
+    }
+    if let Some(hex) = format.background_color.as_deref().and_then(rgb_hex_digits) {
+        let _ = write!(out, r##" fo:background-color="#{}""##, hex);
+    }
+    match format.vertical_alignment {
+        Some(CharVerticalAlignment::SuperScript) => {
+            out.push_str(r#" style:text-position="super 58%""#)
+        }
+        Some(CharVerticalAlignment::SubScript) => out.push_str(r#" style:text-position="sub 58%""#),
+        _ => {}
+    }
+    out
+}
+
+/// Append `text`, encoding tabs and runs of spaces so they survive ODF
+/// whitespace collapsing. `after_space` tracks whether the previous
+/// character written was a space (or the paragraph start).
+fn write_text(out: &mut String, text: &str, after_space: &mut bool) {
+    let mut spaces = 0usize;
+    let flush = |out: &mut String, spaces: &mut usize, after_space: &mut bool| {
+        if *spaces == 0 {
+            return;
+        }
+        let mut literal = 0;
+        if !*after_space {
+            out.push(' ');
+            literal = 1;
+        }
+        match *spaces - literal {
+            0 => {}
+            1 => out.push_str("<text:s/>"),
+            n => {
+                let _ = write!(out, r#"<text:s text:c="{}"/>"#, n);
+            }
+        }
+        *spaces = 0;
+        *after_space = true;
+    };
+    for c in text.chars() {
+        match c {
+            ' ' => spaces += 1,
+            '\t' => {
+                flush(out, &mut spaces, after_space);
+                out.push_str("<text:tab/>");
+                *after_space = true;
+            }
+            // Characters XML 1.0 cannot represent.
+            c if c.is_control() => {}
+            c => {
+                flush(out, &mut spaces, after_space);
+                match c {
+                    '&' => out.push_str("&amp;"),
+                    '<' => out.push_str("&lt;"),
+                    '>' => out.push_str("&gt;"),
+                    c => out.push(c),
+                }
+                *after_space = false;
+            }
+        }
+    }
+    flush(out, &mut spaces, after_space);
+}
+
+fn extension_for(mime_type: &str) -> &'static str {
+    match mime_type {
+        "image/png" => ".png",
+        "image/jpeg" => ".jpg",
+        "image/gif" => ".gif",
+        "image/bmp" => ".bmp",
+        "image/svg+xml" => ".svg",
+        "image/tiff" => ".tif",
+        "image/webp" => ".webp",
+        _ => "",
+    }
+}
+
+fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn styles_xml() -> String {
+    let mut headings = String::new();
+    for level in 1..=6 {
+        let size = [24, 18, 14, 12, 11, 10][level - 1];
+        let _ = write!(
+            headings,
+            concat!(
+                r#"<style:style style:name="Heading_20_{0}" style:display-name="Heading {0}" "#,
+                r#"style:family="paragraph" style:parent-style-name="Heading" "#,
+                r#"style:next-style-name="Standard" style:default-outline-level="{0}" "#,
+                r#"style:class="text"><style:text-properties fo:font-size="{1}pt" "#,
+                r#"fo:font-weight="bold"/></style:style>"#
+            ),
+            level, size
+        );
+    }
+    format!(
+        concat!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+            "\n<office:document-styles {}><office:styles>",
+            r#"<style:style style:name="Standard" style:family="paragraph" style:class="text"/>"#,
+            r#"<style:style style:name="Heading" style:family="paragraph" "#,
+            r#"style:parent-style-name="Standard" style:class="text">"#,
+            r#"<style:paragraph-properties fo:margin-top="0.1665in" fo:margin-bottom="0.0835in" "#,
+            r#"fo:keep-with-next="always"/></style:style>"#,
+            "{}",
+            r#"<style:style style:name="{}" style:display-name="Preformatted Text" "#,
+            r#"style:family="paragraph" style:parent-style-name="Standard" style:class="html">"#,
+            r#"<style:text-properties fo:font-family="'Liberation Mono'" "#,
+            r#"style:font-family-generic="modern" style:font-pitch="fixed"/></style:style>"#,
+            "</office:styles></office:document-styles>"
+        ),
+        NAMESPACES, headings, CODE_STYLE
+    )
+}
+
+fn meta_xml(title: &str) -> String {
+    let mut meta = String::from("<meta:generator>text-document</meta:generator>");
+    if !title.is_empty() {
+        let _ = write!(meta, "<dc:title>{}</dc:title>", escape_xml(title));
+    }
+    format!(
+        concat!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+            "\n<office:document-meta {}><office:meta>{}</office:meta></office:document-meta>"
+        ),
+        NAMESPACES, meta
+    )
+}
+
+fn manifest_xml(extra_entries: &str) -> String {
+    format!(
+        concat!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+            "\n",
+            r#"<manifest:manifest xmlns:manifest="urn:oasis:names:tc:opendocument:xmlns:manifest:1.0" manifest:version="1.3">"#,
+            r#"<manifest:file-entry manifest:full-path="/" manifest:version="1.3" manifest:media-type="{}"/>"#,
+            r#"<manifest:file-entry manifest:full-path="content.xml" manifest:media-type="text/xml"/>"#,
+            r#"<manifest:file-entry manifest:full-path="styles.xml" manifest:media-type="text/xml"/>"#,
+            r#"<manifest:file-entry manifest:full-path="meta.xml" manifest:media-type="text/xml"/>"#,
+            "{}</manifest:manifest>"
+        ),
+        MIMETYPE, extra_entries
+    )
+}
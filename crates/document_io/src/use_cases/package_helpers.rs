@@ -0,0 +1,67 @@
+//! Zip and XML helpers shared by the office package readers (DOCX, ODT).
+//!
+//! XML elements and attributes are matched by local name so files using
+//! non-default namespace prefixes still parse.
+
+use anyhow::{Result, anyhow};
+use roxmltree::Node;
+use std::io::{Read, Seek};
+
+/// Read a text part, decoding invalid UTF-8 lossily. `Ok(None)` when the
+/// archive has no such entry.
+pub(crate) fn read_part<R: Read + Seek>(
+    archive: &mut zip::ZipArchive<R>,
+    name: &str,
+) -> Result<Option<String>> {
+    Ok(read_binary_part(archive, name)?.map(|b| String::from_utf8_lossy(&b).into_owned()))
+}
+
+pub(crate) fn read_binary_part<R: Read + Seek>(
+    archive: &mut zip::ZipArchive<R>,
+    name: &str,
+) -> Result<Option<Vec<u8>>> {
+    let mut file = match archive.by_name(name) {
+        Ok(f) => f,
+        Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+        Err(e) => return Err(anyhow!("Failed to read '{}' from package: {}", name, e)),
+    };
+    let mut data = Vec::new();
+    file.read_to_end(&mut data)?;
+    Ok(Some(data))
+}
+
+/// Last path segment of a part name or URL.
+pub(crate) fn file_name(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+pub(crate) fn mime_type_for(path: &str) -> &'static str {
+    let ext = path.rsplit('.').next().unwrap_or("").to_ascii_lowercase();
+    match ext.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "bmp" => "image/bmp",
+        "svg" => "image/svg+xml",
+        "tif" | "tiff" => "image/tiff",
+        "webp" => "image/webp",
+        "emf" => "image/emf",
+        "wmf" => "image/wmf",
+        _ => "application/octet-stream",
+    }
+}
+
+pub(crate) fn is(node: &Node, local_name: &str) -> bool {
+    node.is_element() && node.tag_name().name() == local_name
+}
+
+pub(crate) fn child<'a, 'i>(node: Node<'a, 'i>, local_name: &str) -> Option<Node<'a, 'i>> {
+    node.children().find(|n| is(n, local_name))
+}
+
+/// Attribute by local name, whatever its namespace prefix.
+pub(crate) fn attr<'a>(node: Node<'a, '_>, local_name: &str) -> Option<&'a str> {
+    node.attributes()
+        .find(|a| a.name() == local_name)
+        .map(|a| a.value())
+}
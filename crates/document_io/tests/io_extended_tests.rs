@@ -123,6 +123,102 @@ fn test_import_html_with_list() -> Result<()> {
     Ok(())
 }
 
+// ─── Import DOCX Tests ─────────────────────────────────────────────
+
+#[test]
+fn test_import_docx_invalid_data_fails() -> Result<()> {
+    let (db_context, event_hub, _) = setup_with_text("unchanged")?;
+    let mut long_op_manager = LongOperationManager::new();
+
+    let op_id = document_io_controller::import_docx(
+        &db_context,
+        &event_hub,
+        &mut long_op_manager,
+        &ImportDocxDto {
+            docx_data: b"plain bytes".to_vec(),
+        },
+    )?;
+
+    wait_for_long_operation(&long_op_manager, &op_id);
+
+    let status = long_op_manager.get_operation_status(&op_id);
+    assert!(matches!(status, Some(OperationStatus::Failed(_))));
+    assert!(document_io_controller::get_import_docx_result(&long_op_manager, &op_id)?.is_none());
+
+    let exported = document_io_controller::export_plain_text(&db_context, &event_hub)?;
+    assert_eq!(exported.plain_text, "unchanged");
+
+    Ok(())
+}
+
+#[test]
+fn test_export_odt_then_import_odt() -> Result<()> {
+    let (db_context, event_hub, _) = setup_with_text("First line\nSecond line")?;
+    let mut long_op_manager = LongOperationManager::new();
+    let path = std::env::temp_dir().join("test_export_odt_then_import_odt.odt");
+
+    let op_id = document_io_controller::export_odt(
+        &db_context,
+        &event_hub,
+        &mut long_op_manager,
+        &ExportOdtDto {
+            output_path: path.to_string_lossy().into_owned(),
+        },
+    )?;
+    wait_for_long_operation(&long_op_manager, &op_id);
+    let exported = document_io_controller::get_export_odt_result(&long_op_manager, &op_id)?
+        .expect("export result");
+    assert_eq!(exported.paragraph_count, 2);
+    let odt_data = std::fs::read(&path)?;
+    let _ = std::fs::remove_file(&path);
+
+    let (db_context, event_hub, _) = setup()?;
+    let op_id = document_io_controller::import_odt(
+        &db_context,
+        &event_hub,
+        &mut long_op_manager,
+        &ImportOdtDto { odt_data },
+    )?;
+    wait_for_long_operation(&long_op_manager, &op_id);
+    let imported = document_io_controller::get_import_odt_result(&long_op_manager, &op_id)?
+        .expect("import result");
+    assert_eq!(imported.block_count, 2);
+
+    let text = document_io_controller::export_plain_text(&db_context, &event_hub)?;
+    assert_eq!(text.plain_text, "First line\nSecond line");
+
+    Ok(())
+}
+
+#[test]
+fn test_export_rtf_then_import_rtf() -> Result<()> {
+    let (db_context, event_hub, _) = setup_with_text("First {line}\nSecond line")?;
+    let mut long_op_manager = LongOperationManager::new();
+
+    let exported = document_io_controller::export_rtf(&db_context, &event_hub)?;
+    assert!(exported.rtf_text.starts_with("{\\rtf1"));
+    assert!(exported.rtf_text.contains("First \\{line\\}"));
+
+    let (db_context, event_hub, _) = setup()?;
+    let op_id = document_io_controller::import_rtf(
+        &db_context,
+        &event_hub,
+        &mut long_op_manager,
+        &ImportRtfDto {
+            rtf_text: exported.rtf_text,
+        },
+    )?;
+    wait_for_long_operation(&long_op_manager, &op_id);
+    let imported = document_io_controller::get_import_rtf_result(&long_op_manager, &op_id)?
+        .expect("import result");
+    assert_eq!(imported.block_count, 2);
+
+    let text = document_io_controller::export_plain_text(&db_context, &event_hub)?;
+    assert_eq!(text.plain_text, "First {line}\nSecond line");
+
+    Ok(())
+}
+
 // ─── Export Markdown Tests ──────────────────────────────────────────
 
 #[test]
@@ -2,20 +2,24 @@
 
 use crate::FindAllDto;
 use crate::FindAllResultDto;
+use crate::FindByFormatDto;
 use crate::FindResultDto;
 use crate::FindTextDto;
 use crate::ReplaceResultDto;
 use crate::ReplaceTextDto;
 use crate::units_of_work::find_all_uow::FindAllUnitOfWorkFactory;
+use crate::units_of_work::find_by_format_uow::FindByFormatUnitOfWorkFactory;
 use crate::units_of_work::find_text_uow::FindTextUnitOfWorkFactory;
 use crate::units_of_work::replace_text_uow::ReplaceTextUnitOfWorkFactory;
-use crate::use_cases::find_all_uc::FindAllUseCase;
+use crate::use_cases::find_all_uc::{FindAllSession, FindAllUseCase};
+use crate::use_cases::find_by_format_uc::FindByFormatUseCase;
 use crate::use_cases::find_text_uc::FindTextUseCase;
 use crate::use_cases::replace_text_uc::ReplaceTextUseCase;
 use anyhow::Result;
 use common::event::{Event, Origin};
 
 use common::event::DocumentSearchEvent::FindAll;
+use common::event::DocumentSearchEvent::FindByFormat;
 use common::event::DocumentSearchEvent::FindText;
 use common::event::DocumentSearchEvent::ReplaceText;
 
@@ -57,6 +61,41 @@ pub fn find_all(
     Ok(return_dto)
 }
 
+/// Start a find-all search whose pages are fetched one at a time from
+/// the returned session.
+pub fn start_find_all(
+    db_context: &DbContext,
+    event_hub: &Arc<EventHub>,
+    dto: &FindAllDto,
+) -> Result<FindAllSession> {
+    let uow_context = FindAllUnitOfWorkFactory::new(db_context);
+    let mut uc = FindAllUseCase::new(Box::new(uow_context));
+    let session = uc.start(dto)?;
+    event_hub.send_event(Event {
+        origin: Origin::DocumentSearch(FindAll),
+        ids: vec![],
+        data: None,
+    });
+    Ok(session)
+}
+
+pub fn find_by_format(
+    db_context: &DbContext,
+    event_hub: &Arc<EventHub>,
+    dto: &FindByFormatDto,
+) -> Result<FindAllResultDto> {
+    let uow_context = FindByFormatUnitOfWorkFactory::new(db_context);
+    let mut uc = FindByFormatUseCase::new(Box::new(uow_context));
+    let return_dto = uc.execute(dto)?;
+    // Notify that the handling manifest has been loaded
+    event_hub.send_event(Event {
+        origin: Origin::DocumentSearch(FindByFormat),
+        ids: vec![],
+        data: None,
+    });
+    Ok(return_dto)
+}
+
 pub fn replace_text(
     db_context: &DbContext,
     event_hub: &Arc<EventHub>,
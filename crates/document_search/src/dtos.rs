@@ -10,12 +10,33 @@ pub struct FindTextDto {
     pub use_regex: bool,
     pub search_backward: bool,
     pub start_position: i64,
+    pub scope_start: Option<i64>,
+    pub scope_end: Option<i64>,
+    pub scope_frame_id: Option<i64>,
+    pub scope_table_id: Option<i64>,
+    pub normalization: SearchNormalization,
+    pub fold_accents: bool,
+    pub fold_width: bool,
+    pub max_edits: Option<i64>,
+    pub with_details: bool,
+    pub context_chars: i64,
 }
 #[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
 pub struct FindResultDto {
     pub found: bool,
     pub position: i64,
     pub length: i64,
+    pub distance: i64,
+    pub block_id: i64,
+    pub block_position: i64,
+    pub frame_id: i64,
+    pub table_id: i64,
+    pub cell_row: i64,
+    pub cell_column: i64,
+    pub context_before: String,
+    pub context_after: String,
+    pub capture_positions: Vec<i64>,
+    pub capture_lengths: Vec<i64>,
 }
 #[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
 pub struct FindAllDto {
@@ -23,12 +44,55 @@ pub struct FindAllDto {
     pub case_sensitive: bool,
     pub whole_word: bool,
     pub use_regex: bool,
+    pub scope_start: Option<i64>,
+    pub scope_end: Option<i64>,
+    pub scope_frame_id: Option<i64>,
+    pub scope_table_id: Option<i64>,
+    pub normalization: SearchNormalization,
+    pub fold_accents: bool,
+    pub fold_width: bool,
+    pub max_edits: Option<i64>,
+    pub start_position: Option<i64>,
+    pub max_results: Option<i64>,
+    pub with_details: bool,
+    pub context_chars: i64,
 }
 #[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
 pub struct FindAllResultDto {
     pub positions: Vec<i64>,
     pub lengths: Vec<i64>,
     pub count: i64,
+    pub has_more: bool,
+    pub block_ids: Vec<i64>,
+    pub block_positions: Vec<i64>,
+    pub frame_ids: Vec<i64>,
+    pub table_ids: Vec<i64>,
+    pub cell_rows: Vec<i64>,
+    pub cell_columns: Vec<i64>,
+    pub contexts_before: Vec<String>,
+    pub contexts_after: Vec<String>,
+    pub capture_counts: Vec<i64>,
+    pub capture_positions: Vec<i64>,
+    pub capture_lengths: Vec<i64>,
+    pub distances: Vec<i64>,
+}
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct FindByFormatDto {
+    pub font_family: Option<String>,
+    pub font_bold: Option<bool>,
+    pub font_italic: Option<bool>,
+    pub font_underline: Option<bool>,
+    pub font_strikeout: Option<bool>,
+    pub foreground_color: Option<String>,
+    pub background_color: Option<String>,
+    pub anchor_href: Option<String>,
+    pub heading_level: Option<i64>,
+    pub is_code_block: Option<bool>,
+    pub code_language: Option<String>,
+    pub scope_start: Option<i64>,
+    pub scope_end: Option<i64>,
+    pub scope_frame_id: Option<i64>,
+    pub scope_table_id: Option<i64>,
 }
 #[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
 pub struct ReplaceTextDto {
@@ -38,9 +102,26 @@ pub struct ReplaceTextDto {
     pub whole_word: bool,
     pub use_regex: bool,
     pub replace_all: bool,
+    pub preserve_case: bool,
+    pub cross_block: bool,
+    pub scope_start: Option<i64>,
+    pub scope_end: Option<i64>,
+    pub scope_frame_id: Option<i64>,
+    pub scope_table_id: Option<i64>,
+    pub normalization: SearchNormalization,
+    pub fold_accents: bool,
+    pub fold_width: bool,
 }
 #[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
 pub struct ReplaceResultDto {
     pub replacements_count: i64,
     pub skipped_cross_block: i64,
 }
+
+#[derive(Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq)]
+pub enum SearchNormalization {
+    #[default]
+    NoNormalization,
+    Nfc,
+    Nfkc,
+}
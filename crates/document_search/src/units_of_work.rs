@@ -1,5 +1,6 @@
 // Generated by Qleany v1.4.8 from feature_units_of_work_mod.tera
 
 pub(crate) mod find_all_uow;
+pub(crate) mod find_by_format_uow;
 pub(crate) mod find_text_uow;
 pub(crate) mod replace_text_uow;
@@ -0,0 +1,76 @@
+// Generated by Qleany v1.5.1 from feature_use_case_uow.tera
+
+use crate::use_cases::find_by_format_uc::{
+    FindByFormatUnitOfWorkFactoryTrait, FindByFormatUnitOfWorkTrait,
+};
+use anyhow::{Ok, Result};
+use common::database::QueryUnitOfWork;
+use common::database::{db_context::DbContext, transactions::Transaction};
+#[allow(unused_imports)]
+use common::entities::{Block, Document, Frame, Root, Table, TableCell};
+#[allow(unused_imports)]
+use common::types;
+#[allow(unused_imports)]
+use common::types::EntityId;
+use std::cell::RefCell;
+
+// Unit of work for FindByFormat
+
+pub struct FindByFormatUnitOfWork {
+    context: DbContext,
+    transaction: RefCell<Option<Transaction>>,
+}
+
+impl FindByFormatUnitOfWork {
+    pub fn new(db_context: &DbContext) -> Self {
+        FindByFormatUnitOfWork {
+            context: db_context.clone(),
+            transaction: RefCell::new(None),
+        }
+    }
+}
+
+impl QueryUnitOfWork for FindByFormatUnitOfWork {
+    fn begin_transaction(&self) -> Result<()> {
+        self.transaction
+            .replace(Some(Transaction::begin_read_transaction(&self.context)?));
+        Ok(())
+    }
+
+    fn end_transaction(&self) -> Result<()> {
+        self.transaction.take().unwrap().end_read_transaction()?;
+        Ok(())
+    }
+
+    fn store(&self) -> std::sync::Arc<common::database::Store> {
+        self.context.get_store().clone()
+    }
+}
+
+#[macros::uow_action(entity = "Root", action = "GetRO")]
+#[macros::uow_action(entity = "Root", action = "GetRelationshipRO")]
+#[macros::uow_action(entity = "Document", action = "GetRelationshipRO")]
+#[macros::uow_action(entity = "Frame", action = "GetRO")]
+#[macros::uow_action(entity = "Frame", action = "GetRelationshipRO")]
+#[macros::uow_action(entity = "Block", action = "GetMultiRO")]
+#[macros::uow_action(entity = "Table", action = "GetRO")]
+#[macros::uow_action(entity = "TableCell", action = "GetMultiRO")]
+impl FindByFormatUnitOfWorkTrait for FindByFormatUnitOfWork {}
+
+pub struct FindByFormatUnitOfWorkFactory {
+    context: DbContext,
+}
+
+impl FindByFormatUnitOfWorkFactory {
+    pub fn new(db_context: &DbContext) -> Self {
+        FindByFormatUnitOfWorkFactory {
+            context: db_context.clone(),
+        }
+    }
+}
+
+impl FindByFormatUnitOfWorkFactoryTrait for FindByFormatUnitOfWorkFactory {
+    fn create(&self) -> Box<dyn FindByFormatUnitOfWorkTrait> {
+        Box::new(FindByFormatUnitOfWork::new(&self.context))
+    }
+}
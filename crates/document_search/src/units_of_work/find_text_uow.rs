@@ -5,7 +5,7 @@ use anyhow::{Ok, Result};
 use common::database::QueryUnitOfWork;
 use common::database::{db_context::DbContext, transactions::Transaction};
 #[allow(unused_imports)]
-use common::entities::{Block, Document, Frame, Root};
+use common::entities::{Block, Document, Frame, Root, Table, TableCell};
 #[allow(unused_imports)]
 use common::types;
 #[allow(unused_imports)]
@@ -48,8 +48,11 @@ impl QueryUnitOfWork for FindTextUnitOfWork {
 #[macros::uow_action(entity = "Root", action = "GetRO")]
 #[macros::uow_action(entity = "Root", action = "GetRelationshipRO")]
 #[macros::uow_action(entity = "Document", action = "GetRelationshipRO")]
+#[macros::uow_action(entity = "Frame", action = "GetRO")]
 #[macros::uow_action(entity = "Frame", action = "GetRelationshipRO")]
 #[macros::uow_action(entity = "Block", action = "GetMultiRO")]
+#[macros::uow_action(entity = "Table", action = "GetRO")]
+#[macros::uow_action(entity = "TableCell", action = "GetMultiRO")]
 impl FindTextUnitOfWorkTrait for FindTextUnitOfWork {}
 
 pub struct FindTextUnitOfWorkFactory {
@@ -1,6 +1,7 @@
 // Generated by Qleany v1.5.1 from feature_use_cases_mod.tera
 
 pub(crate) mod find_all_uc;
+pub(crate) mod find_by_format_uc;
 pub(crate) mod find_text_uc;
 pub(crate) mod replace_text_uc;
 pub(crate) mod search_helpers;
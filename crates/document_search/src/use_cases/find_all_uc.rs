@@ -1,5 +1,8 @@
 // Generated by Qleany v1.4.8 from feature_use_case.tera
-use super::search_helpers::{build_full_text_via_store, find_all_matches};
+use super::search_helpers::{
+    MatchDescriber, MatchOptions, MatchScanner, ScopeFilter, ScopeLookup, SearchScope, TextFolding,
+    build_full_text_via_store,
+};
 use crate::FindAllDto;
 use crate::FindAllResultDto;
 use anyhow::{Result, anyhow};
@@ -9,8 +12,9 @@ use common::direct_access::document::document_repository::DocumentRelationshipFi
 use common::direct_access::frame::frame_repository::FrameRelationshipField;
 use common::direct_access::root::root_repository::RootRelationshipField;
 #[allow(unused_imports)]
-use common::entities::{Block, Document, Frame, Root};
+use common::entities::{Block, Document, Frame, Root, Table, TableCell};
 use common::types::{EntityId, ROOT_ENTITY_ID};
+use std::borrow::Cow;
 
 pub trait FindAllUnitOfWorkFactoryTrait: Send + Sync {
     fn create(&self) -> Box<dyn FindAllUnitOfWorkTrait>;
@@ -19,12 +23,34 @@ pub trait FindAllUnitOfWorkFactoryTrait: Send + Sync {
 #[macros::uow_action(entity = "Root", action = "GetRO")]
 #[macros::uow_action(entity = "Root", action = "GetRelationshipRO")]
 #[macros::uow_action(entity = "Document", action = "GetRelationshipRO")]
+#[macros::uow_action(entity = "Frame", action = "GetRO")]
 #[macros::uow_action(entity = "Frame", action = "GetRelationshipRO")]
 #[macros::uow_action(entity = "Block", action = "GetMultiRO")]
+#[macros::uow_action(entity = "Table", action = "GetRO")]
+#[macros::uow_action(entity = "TableCell", action = "GetMultiRO")]
 pub trait FindAllUnitOfWorkTrait: QueryUnitOfWork {}
 
+impl ScopeLookup for dyn FindAllUnitOfWorkTrait + '_ {
+    fn frame(&self, id: &EntityId) -> Result<Option<Frame>> {
+        self.get_frame(id)
+    }
+
+    fn table(&self, id: &EntityId) -> Result<Option<Table>> {
+        self.get_table(id)
+    }
+
+    fn table_cells(&self, ids: &[EntityId]) -> Result<Vec<Option<TableCell>>> {
+        self.get_table_cell_multi(ids)
+    }
+}
+
 /// Fetch all blocks from the document via the UoW, sort them, and build the full text.
-fn build_full_text(uow: &dyn FindAllUnitOfWorkTrait) -> Result<String> {
+/// The sorted blocks are returned only when `with_blocks` is set, which
+/// also rules out the flat-document fast path.
+fn build_full_text(
+    uow: &dyn FindAllUnitOfWorkTrait,
+    with_blocks: bool,
+) -> Result<(String, Vec<Block>)> {
     let root = uow
         .get_root(&ROOT_ENTITY_ID)?
         .ok_or_else(|| anyhow!("Root entity not found"))?;
@@ -38,8 +64,8 @@ fn build_full_text(uow: &dyn FindAllUnitOfWorkTrait) -> Result<String> {
 
     // Fast path: flat single-frame document — entire searchable text
     // is already laid out contiguously in the rope.
-    if let Some(text) = rope_flat_text_if_simple(&uow.store(), frame_ids.len()) {
-        return Ok(text);
+    if !with_blocks && let Some(text) = rope_flat_text_if_simple(&uow.store(), frame_ids.len()) {
+        return Ok((text, Vec::new()));
     }
 
     let mut all_block_ids: Vec<EntityId> = Vec::new();
@@ -52,7 +78,131 @@ fn build_full_text(uow: &dyn FindAllUnitOfWorkTrait) -> Result<String> {
     let mut blocks: Vec<Block> = blocks_opt.into_iter().flatten().collect();
     blocks.sort_by_key(|b| b.document_position);
 
-    Ok(build_full_text_via_store(&blocks, &uow.store()))
+    let text = build_full_text_via_store(&blocks, &uow.store());
+    Ok((text, blocks))
+}
+
+/// Describer for the matches in `text`, which must have been built
+/// with its blocks.
+fn match_describer<'a>(
+    uow: &dyn FindAllUnitOfWorkTrait,
+    text: impl Into<Cow<'a, str>>,
+    blocks: &[Block],
+    options: &MatchOptions,
+    context_chars: i64,
+) -> Result<MatchDescriber<'a>> {
+    let root = uow
+        .get_root(&ROOT_ENTITY_ID)?
+        .ok_or_else(|| anyhow!("Root entity not found"))?;
+    let doc_ids = uow.get_root_relationship(&root.id, &RootRelationshipField::Document)?;
+    let doc_id = *doc_ids
+        .first()
+        .ok_or_else(|| anyhow!("Root has no document"))?;
+    let frame_ids = uow.get_document_relationship(&doc_id, &DocumentRelationshipField::Frames)?;
+    let table_ids = uow.get_document_relationship(&doc_id, &DocumentRelationshipField::Tables)?;
+    MatchDescriber::new(
+        uow,
+        text,
+        blocks,
+        &frame_ids,
+        &table_ids,
+        &uow.store(),
+        options,
+        context_chars.max(0) as usize,
+    )
+}
+
+fn match_options(dto: &FindAllDto) -> MatchOptions<'_> {
+    MatchOptions {
+        query: &dto.query,
+        case_sensitive: dto.case_sensitive,
+        whole_word: dto.whole_word,
+        use_regex: dto.use_regex,
+        folding: TextFolding::new(dto.normalization.clone(), dto.fold_accents, dto.fold_width),
+        max_edits: dto.max_edits.map(|k| k.max(0) as usize),
+        multi_line: false,
+    }
+}
+
+/// A search kept open between pages, started by
+/// [`FindAllUseCase::start`]. The searched text, the scope and the
+/// place the scan reached are kept, so each [`next_page`] carries on
+/// where the previous one stopped instead of searching the document
+/// again. It does not see later edits; start a new one after any.
+///
+/// [`next_page`]: FindAllSession::next_page
+pub struct FindAllSession {
+    scanner: MatchScanner,
+    filter: ScopeFilter,
+    describer: Option<MatchDescriber<'static>>,
+    fuzzy: bool,
+    limit: Option<usize>,
+    /// The match found past the end of the previous page.
+    peeked: Option<(usize, usize, usize)>,
+    /// Fuzzy matches not returned yet, best first. All of them are
+    /// found and ranked when the first page is asked for.
+    ranked: Option<std::vec::IntoIter<(usize, usize, usize)>>,
+}
+
+impl FindAllSession {
+    /// The next page of matches, as [`FindAllUseCase::execute`] returns
+    /// them; `has_more` is false on the last one.
+    pub fn next_page(&mut self) -> FindAllResultDto {
+        let mut result = FindAllResultDto::default();
+        let filter = &self.filter;
+        let accept = |pos, len| filter.contains(pos, len);
+        let mut page = Vec::new();
+        if self.fuzzy {
+            let scanner = &mut self.scanner;
+            let ranked = self.ranked.get_or_insert_with(|| {
+                let mut all = scanner.next_page(None, &accept);
+                all.sort_by_key(|&(pos, _, distance)| (distance, pos));
+                all.into_iter()
+            });
+            page.extend(ranked.take(self.limit.unwrap_or(usize::MAX)));
+            result.has_more = ranked.len() > 0;
+            result.distances = page.iter().map(|&(_, _, d)| d as i64).collect();
+        } else {
+            if self.limit != Some(0) {
+                page.extend(self.peeked.take());
+            }
+            page.extend(
+                self.scanner
+                    .next_page(self.limit.map(|l| l.saturating_sub(page.len())), &accept),
+            );
+            // One extra match tells whether there are more.
+            if self.limit.is_some() && self.peeked.is_none() {
+                self.peeked = self.scanner.next_match(&accept);
+            }
+            result.has_more = self.peeked.is_some();
+        }
+
+        if let Some(describer) = &self.describer {
+            for &(pos, len, _) in &page {
+                let details = describer.describe(pos, len);
+                let (table_id, row, column) = details.cell.unwrap_or((0, -1, -1));
+                result.block_ids.push(details.block_id as i64);
+                result.block_positions.push(details.block_position as i64);
+                result.frame_ids.push(details.frame_id as i64);
+                result.table_ids.push(table_id as i64);
+                result.cell_rows.push(row);
+                result.cell_columns.push(column);
+                result.contexts_before.push(details.context_before);
+                result.contexts_after.push(details.context_after);
+                result.capture_counts.push(details.captures.len() as i64);
+                for group in details.captures {
+                    let (pos, len) = group.map_or((-1, 0), |(p, l)| (p as i64, l as i64));
+                    result.capture_positions.push(pos);
+                    result.capture_lengths.push(len);
+                }
+            }
+        }
+
+        result.positions = page.iter().map(|&(pos, _, _)| pos as i64).collect();
+        result.lengths = page.iter().map(|&(_, len, _)| len as i64).collect();
+        result.count = page.len() as i64;
+        result
+    }
 }
 
 pub struct FindAllUseCase {
@@ -64,30 +214,68 @@ impl FindAllUseCase {
         FindAllUseCase { uow_factory }
     }
 
+    /// Find the matches of `dto.query`. With `start_position` only the
+    /// matches starting there or later are returned, and with
+    /// `max_results` at most that many, `has_more` telling whether the
+    /// search stopped early; together they page through the matches of
+    /// a large document without collecting them all. With
+    /// `with_details` the per-match vectors are filled as well: table
+    /// ids are 0 and rows and columns -1 outside tables, and the
+    /// capture groups of all matches are laid out back to back,
+    /// `capture_counts` giving how many belong to each match, with
+    /// position -1 for a group that took no part in the match.
+    ///
+    /// With `max_edits` the search is fuzzy and the matches come best
+    /// first, ordered by their `distances` to the query and then by
+    /// position. They are ranked over the whole scope, so the first
+    /// page holds the best matches; `start_position` still skips the
+    /// matches before it.
     pub fn execute(&mut self, dto: &FindAllDto) -> Result<FindAllResultDto> {
+        Ok(self.start(dto)?.next_page())
+    }
+
+    /// Start a search whose pages of `dto.max_results` matches are
+    /// fetched one at a time with [`FindAllSession::next_page`], the
+    /// first one being what [`execute`](Self::execute) returns.
+    pub fn start(&mut self, dto: &FindAllDto) -> Result<FindAllSession> {
         let uow = self.uow_factory.create();
         uow.begin_transaction()?;
 
-        let full_text = build_full_text(uow.as_ref())?;
-
-        let all_matches = find_all_matches(
-            &full_text,
-            &dto.query,
-            dto.case_sensitive,
-            dto.whole_word,
-            dto.use_regex,
+        let scope = SearchScope::resolve(
+            uow.as_ref(),
+            dto.scope_start,
+            dto.scope_end,
+            dto.scope_frame_id,
+            dto.scope_table_id,
         )?;
+        let (full_text, blocks) =
+            build_full_text(uow.as_ref(), scope.needs_blocks() || dto.with_details)?;
+        let options = match_options(dto);
+        let filter = scope.filter(&blocks, &uow.store());
+        let from = dto.start_position.unwrap_or(0).max(0) as usize;
+        let scanner = MatchScanner::new(&full_text, &options, from)?;
+        let describer = if dto.with_details {
+            Some(match_describer(
+                uow.as_ref(),
+                full_text,
+                &blocks,
+                &options,
+                dto.context_chars,
+            )?)
+        } else {
+            None
+        };
 
         uow.end_transaction()?;
 
-        let positions: Vec<i64> = all_matches.iter().map(|(pos, _)| *pos as i64).collect();
-        let lengths: Vec<i64> = all_matches.iter().map(|(_, len)| *len as i64).collect();
-        let count = all_matches.len() as i64;
-
-        Ok(FindAllResultDto {
-            positions,
-            lengths,
-            count,
+        Ok(FindAllSession {
+            scanner,
+            filter,
+            describer,
+            fuzzy: options.max_edits.is_some(),
+            limit: dto.max_results.map(|m| m.max(0) as usize),
+            peeked: None,
+            ranked: None,
         })
     }
 }
@@ -0,0 +1,236 @@
+// Generated by Qleany v1.5.1 from feature_use_case.tera
+use super::search_helpers::{ScopeLookup, SearchScope, build_byte_to_char_map};
+use crate::FindAllResultDto;
+use crate::FindByFormatDto;
+use anyhow::{Result, anyhow, bail};
+use common::database::QueryUnitOfWork;
+use common::database::rope_helpers::block_content_via_store;
+use common::direct_access::document::document_repository::DocumentRelationshipField;
+use common::direct_access::frame::frame_repository::FrameRelationshipField;
+use common::direct_access::root::root_repository::RootRelationshipField;
+#[allow(unused_imports)]
+use common::entities::{Block, Document, Frame, Root, Table, TableCell};
+use common::format_runs::CharacterFormat;
+use common::format_runs_query::get_format_runs;
+use common::parser_tools::css_color::normalize_css_color;
+use common::types::{EntityId, ROOT_ENTITY_ID};
+
+pub trait FindByFormatUnitOfWorkFactoryTrait: Send + Sync {
+    fn create(&self) -> Box<dyn FindByFormatUnitOfWorkTrait>;
+}
+
+#[macros::uow_action(entity = "Root", action = "GetRO")]
+#[macros::uow_action(entity = "Root", action = "GetRelationshipRO")]
+#[macros::uow_action(entity = "Document", action = "GetRelationshipRO")]
+#[macros::uow_action(entity = "Frame", action = "GetRO")]
+#[macros::uow_action(entity = "Frame", action = "GetRelationshipRO")]
+#[macros::uow_action(entity = "Block", action = "GetMultiRO")]
+#[macros::uow_action(entity = "Table", action = "GetRO")]
+#[macros::uow_action(entity = "TableCell", action = "GetMultiRO")]
+pub trait FindByFormatUnitOfWorkTrait: QueryUnitOfWork {}
+
+impl ScopeLookup for dyn FindByFormatUnitOfWorkTrait {
+    fn frame(&self, id: &EntityId) -> Result<Option<Frame>> {
+        self.get_frame(id)
+    }
+
+    fn table(&self, id: &EntityId) -> Result<Option<Table>> {
+        self.get_table(id)
+    }
+
+    fn table_cells(&self, ids: &[EntityId]) -> Result<Vec<Option<TableCell>>> {
+        self.get_table_cell_multi(ids)
+    }
+}
+
+/// Fetch all blocks from the document via the UoW, sorted by position.
+fn fetch_sorted_blocks(uow: &dyn FindByFormatUnitOfWorkTrait) -> Result<Vec<Block>> {
+    let root = uow
+        .get_root(&ROOT_ENTITY_ID)?
+        .ok_or_else(|| anyhow!("Root entity not found"))?;
+
+    let doc_ids = uow.get_root_relationship(&root.id, &RootRelationshipField::Document)?;
+    let doc_id = *doc_ids
+        .first()
+        .ok_or_else(|| anyhow!("Root has no document"))?;
+
+    let frame_ids = uow.get_document_relationship(&doc_id, &DocumentRelationshipField::Frames)?;
+
+    let mut all_block_ids: Vec<EntityId> = Vec::new();
+    for frame_id in &frame_ids {
+        let block_ids = uow.get_frame_relationship(frame_id, &FrameRelationshipField::Blocks)?;
+        all_block_ids.extend(block_ids);
+    }
+
+    let blocks_opt = uow.get_block_multi(&all_block_ids)?;
+    let mut blocks: Vec<Block> = blocks_opt.into_iter().flatten().collect();
+    blocks.sort_by_key(|b| b.document_position);
+    Ok(blocks)
+}
+
+fn has_char_conditions(dto: &FindByFormatDto) -> bool {
+    dto.font_family.is_some()
+        || dto.font_bold.is_some()
+        || dto.font_italic.is_some()
+        || dto.font_underline.is_some()
+        || dto.font_strikeout.is_some()
+        || dto.foreground_color.is_some()
+        || dto.background_color.is_some()
+        || dto.anchor_href.is_some()
+}
+
+fn has_block_conditions(dto: &FindByFormatDto) -> bool {
+    dto.heading_level.is_some() || dto.is_code_block.is_some() || dto.code_language.is_some()
+}
+
+fn same_color(actual: &Option<String>, wanted: &str) -> bool {
+    actual.as_deref().and_then(normalize_css_color) == normalize_css_color(wanted)
+}
+
+/// Unset boolean attributes count as `false`; `anchor_href` matches any
+/// link whose target contains the wanted text.
+fn char_format_matches(dto: &FindByFormatDto, format: &CharacterFormat) -> bool {
+    let flag = |wanted: Option<bool>, actual: Option<bool>| {
+        wanted.is_none_or(|w| actual.unwrap_or(false) == w)
+    };
+    flag(dto.font_bold, format.font_bold)
+        && flag(dto.font_italic, format.font_italic)
+        && flag(dto.font_underline, format.font_underline)
+        && flag(dto.font_strikeout, format.font_strikeout)
+        && dto.font_family.as_deref().is_none_or(|wanted| {
+            format
+                .font_family
+                .as_deref()
+                .is_some_and(|family| family.eq_ignore_ascii_case(wanted))
+        })
+        && dto
+            .foreground_color
+            .as_deref()
+            .is_none_or(|wanted| same_color(&format.foreground_color, wanted))
+        && dto
+            .background_color
+            .as_deref()
+            .is_none_or(|wanted| same_color(&format.background_color, wanted))
+        && dto.anchor_href.as_deref().is_none_or(|wanted| {
+            format
+                .anchor_href
+                .as_deref()
+                .is_some_and(|href| href.contains(wanted))
+        })
+}
+
+/// An unset heading level is 0 (body text) and an unset code block flag
+/// is `false`.
+fn block_format_matches(dto: &FindByFormatDto, block: &Block) -> bool {
+    dto.heading_level
+        .is_none_or(|level| block.fmt_heading_level.unwrap_or(0) == level)
+        && dto
+            .is_code_block
+            .is_none_or(|code| block.fmt_is_code_block.unwrap_or(false) == code)
+        && dto.code_language.as_deref().is_none_or(|wanted| {
+            block
+                .fmt_code_language
+                .as_deref()
+                .is_some_and(|language| language.eq_ignore_ascii_case(wanted))
+        })
+}
+
+/// Char ranges `(start, end)` inside `text` whose character format
+/// matches. Text outside every run has the default format.
+fn matching_char_ranges(
+    dto: &FindByFormatDto,
+    text: &str,
+    runs: &[common::format_runs::FormatRun],
+) -> Vec<(usize, usize)> {
+    let byte_to_char = build_byte_to_char_map(text);
+    let text_len = text.len() as u32;
+    let default_format = CharacterFormat::default();
+
+    let mut segments: Vec<(u32, u32, &CharacterFormat)> = Vec::new();
+    let mut cursor = 0;
+    for run in runs {
+        let start = run.byte_start.min(text_len);
+        let end = run.byte_end.min(text_len);
+        if start > cursor {
+            segments.push((cursor, start, &default_format));
+        }
+        segments.push((start, end, &run.format));
+        cursor = end;
+    }
+    if cursor < text_len {
+        segments.push((cursor, text_len, &default_format));
+    }
+
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for (start, end, format) in segments {
+        if start >= end || !char_format_matches(dto, format) {
+            continue;
+        }
+        let start = byte_to_char[start as usize];
+        let end = byte_to_char[end as usize];
+        match ranges.last_mut() {
+            Some(last) if last.1 == start => last.1 = end,
+            _ => ranges.push((start, end)),
+        }
+    }
+    ranges
+}
+
+pub struct FindByFormatUseCase {
+    uow_factory: Box<dyn FindByFormatUnitOfWorkFactoryTrait>,
+}
+
+impl FindByFormatUseCase {
+    pub fn new(uow_factory: Box<dyn FindByFormatUnitOfWorkFactoryTrait>) -> Self {
+        FindByFormatUseCase { uow_factory }
+    }
+
+    /// Ranges of text whose character format and block format satisfy
+    /// every condition set in the DTO. Ranges never span blocks.
+    pub fn execute(&mut self, dto: &FindByFormatDto) -> Result<FindAllResultDto> {
+        if !has_char_conditions(dto) && !has_block_conditions(dto) {
+            bail!("Format search needs at least one condition");
+        }
+
+        let uow = self.uow_factory.create();
+        uow.begin_transaction()?;
+
+        let scope = SearchScope::resolve(
+            uow.as_ref(),
+            dto.scope_start,
+            dto.scope_end,
+            dto.scope_frame_id,
+            dto.scope_table_id,
+        )?;
+        let blocks = fetch_sorted_blocks(uow.as_ref())?;
+        let store = uow.store();
+
+        let mut matches: Vec<(usize, usize)> = Vec::new();
+        let mut block_start = 0;
+        for block in &blocks {
+            let text = block_content_via_store(block, &store);
+            let text_chars = text.chars().count();
+            if block_format_matches(dto, block) {
+                if has_char_conditions(dto) {
+                    let runs = get_format_runs(&store, block.id);
+                    for (start, end) in matching_char_ranges(dto, &text, &runs) {
+                        matches.push((block_start + start, end - start));
+                    }
+                } else if text_chars > 0 {
+                    matches.push((block_start, text_chars));
+                }
+            }
+            block_start += text_chars + 1;
+        }
+        scope.retain(&mut matches, &blocks, &store);
+
+        uow.end_transaction()?;
+
+        Ok(FindAllResultDto {
+            positions: matches.iter().map(|(pos, _)| *pos as i64).collect(),
+            lengths: matches.iter().map(|(_, len)| *len as i64).collect(),
+            count: matches.len() as i64,
+            ..Default::default()
+        })
+    }
+}
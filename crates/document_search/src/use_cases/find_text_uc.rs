@@ -1,5 +1,8 @@
 // Generated by Qleany v1.4.8 from feature_use_case.tera
-use super::search_helpers::{build_full_text_via_store, find_all_matches};
+use super::search_helpers::{
+    MatchDescriber, MatchOptions, ScopeLookup, SearchScope, TextFolding, build_full_text_via_store,
+    find_fuzzy_matches, find_matches_paged,
+};
 use crate::FindResultDto;
 use crate::FindTextDto;
 use anyhow::{Result, anyhow};
@@ -9,7 +12,7 @@ use common::direct_access::document::document_repository::DocumentRelationshipFi
 use common::direct_access::frame::frame_repository::FrameRelationshipField;
 use common::direct_access::root::root_repository::RootRelationshipField;
 #[allow(unused_imports)]
-use common::entities::{Block, Document, Frame, Root};
+use common::entities::{Block, Document, Frame, Root, Table, TableCell};
 use common::types::{EntityId, ROOT_ENTITY_ID};
 
 pub trait FindTextUnitOfWorkFactoryTrait: Send + Sync {
@@ -19,12 +22,34 @@ pub trait FindTextUnitOfWorkFactoryTrait: Send + Sync {
 #[macros::uow_action(entity = "Root", action = "GetRO")]
 #[macros::uow_action(entity = "Root", action = "GetRelationshipRO")]
 #[macros::uow_action(entity = "Document", action = "GetRelationshipRO")]
+#[macros::uow_action(entity = "Frame", action = "GetRO")]
 #[macros::uow_action(entity = "Frame", action = "GetRelationshipRO")]
 #[macros::uow_action(entity = "Block", action = "GetMultiRO")]
+#[macros::uow_action(entity = "Table", action = "GetRO")]
+#[macros::uow_action(entity = "TableCell", action = "GetMultiRO")]
 pub trait FindTextUnitOfWorkTrait: QueryUnitOfWork {}
 
+impl ScopeLookup for dyn FindTextUnitOfWorkTrait + '_ {
+    fn frame(&self, id: &EntityId) -> Result<Option<Frame>> {
+        self.get_frame(id)
+    }
+
+    fn table(&self, id: &EntityId) -> Result<Option<Table>> {
+        self.get_table(id)
+    }
+
+    fn table_cells(&self, ids: &[EntityId]) -> Result<Vec<Option<TableCell>>> {
+        self.get_table_cell_multi(ids)
+    }
+}
+
 /// Fetch all blocks from the document via the UoW, sort them, and build the full text.
-fn build_full_text(uow: &dyn FindTextUnitOfWorkTrait) -> Result<String> {
+/// The sorted blocks are returned only when `with_blocks` is set, which
+/// also rules out the flat-document fast path.
+fn build_full_text(
+    uow: &dyn FindTextUnitOfWorkTrait,
+    with_blocks: bool,
+) -> Result<(String, Vec<Block>)> {
     let root = uow
         .get_root(&ROOT_ENTITY_ID)?
         .ok_or_else(|| anyhow!("Root entity not found"))?;
@@ -38,8 +63,8 @@ fn build_full_text(uow: &dyn FindTextUnitOfWorkTrait) -> Result<String> {
 
     // Fast path: flat single-frame document — entire searchable text
     // is already laid out contiguously in the rope.
-    if let Some(text) = rope_flat_text_if_simple(&uow.store(), frame_ids.len()) {
-        return Ok(text);
+    if !with_blocks && let Some(text) = rope_flat_text_if_simple(&uow.store(), frame_ids.len()) {
+        return Ok((text, Vec::new()));
     }
 
     let mut all_block_ids: Vec<EntityId> = Vec::new();
@@ -52,7 +77,50 @@ fn build_full_text(uow: &dyn FindTextUnitOfWorkTrait) -> Result<String> {
     let mut blocks: Vec<Block> = blocks_opt.into_iter().flatten().collect();
     blocks.sort_by_key(|b| b.document_position);
 
-    Ok(build_full_text_via_store(&blocks, &uow.store()))
+    let text = build_full_text_via_store(&blocks, &uow.store());
+    Ok((text, blocks))
+}
+
+/// Describer for the matches in `text`, which must have been built
+/// with its blocks.
+fn match_describer<'a>(
+    uow: &dyn FindTextUnitOfWorkTrait,
+    text: &'a str,
+    blocks: &[Block],
+    options: &MatchOptions,
+    context_chars: i64,
+) -> Result<MatchDescriber<'a>> {
+    let root = uow
+        .get_root(&ROOT_ENTITY_ID)?
+        .ok_or_else(|| anyhow!("Root entity not found"))?;
+    let doc_ids = uow.get_root_relationship(&root.id, &RootRelationshipField::Document)?;
+    let doc_id = *doc_ids
+        .first()
+        .ok_or_else(|| anyhow!("Root has no document"))?;
+    let frame_ids = uow.get_document_relationship(&doc_id, &DocumentRelationshipField::Frames)?;
+    let table_ids = uow.get_document_relationship(&doc_id, &DocumentRelationshipField::Tables)?;
+    MatchDescriber::new(
+        uow,
+        text,
+        blocks,
+        &frame_ids,
+        &table_ids,
+        &uow.store(),
+        options,
+        context_chars.max(0) as usize,
+    )
+}
+
+fn match_options(dto: &FindTextDto) -> MatchOptions<'_> {
+    MatchOptions {
+        query: &dto.query,
+        case_sensitive: dto.case_sensitive,
+        whole_word: dto.whole_word,
+        use_regex: dto.use_regex,
+        folding: TextFolding::new(dto.normalization.clone(), dto.fold_accents, dto.fold_width),
+        max_edits: dto.max_edits.map(|k| k.max(0) as usize),
+        multi_line: false,
+    }
 }
 
 pub struct FindTextUseCase {
@@ -64,48 +132,87 @@ impl FindTextUseCase {
         FindTextUseCase { uow_factory }
     }
 
+    /// Find the next match at or after `start_position`, or the last
+    /// one before it when searching backward. With `with_details` the
+    /// block, frame, table cell, context and capture group fields are
+    /// filled as for `find_all`.
     pub fn execute(&mut self, dto: &FindTextDto) -> Result<FindResultDto> {
         let uow = self.uow_factory.create();
         uow.begin_transaction()?;
 
-        let full_text = build_full_text(uow.as_ref())?;
-
-        let all_matches = find_all_matches(
-            &full_text,
-            &dto.query,
-            dto.case_sensitive,
-            dto.whole_word,
-            dto.use_regex,
+        let scope = SearchScope::resolve(
+            uow.as_ref(),
+            dto.scope_start,
+            dto.scope_end,
+            dto.scope_frame_id,
+            dto.scope_table_id,
         )?;
+        let (full_text, blocks) =
+            build_full_text(uow.as_ref(), scope.needs_blocks() || dto.with_details)?;
+        let options = match_options(dto);
 
         let start_pos = dto.start_position.max(0) as usize;
 
+        let filter = scope.filter(&blocks, &uow.store());
+        let search = |from: usize, limit: Option<usize>| -> Result<Vec<(usize, usize, usize)>> {
+            let accept = |pos, len| filter.contains(pos, len);
+            if options.max_edits.is_some() {
+                find_fuzzy_matches(&full_text, &options, from, limit, accept)
+            } else {
+                Ok(
+                    find_matches_paged(&full_text, &options, from, limit, accept)?
+                        .into_iter()
+                        .map(|(pos, len)| (pos, len, 0))
+                        .collect(),
+                )
+            }
+        };
         let result = if dto.search_backward {
-            all_matches
-                .iter()
+            search(0, None)?
+                .into_iter()
                 .rev()
-                .find(|(pos, _)| *pos < start_pos)
-                .copied()
+                .find(|(pos, _, _)| *pos < start_pos)
         } else {
-            all_matches
-                .iter()
-                .find(|(pos, _)| *pos >= start_pos)
-                .copied()
+            search(start_pos, Some(1))?.first().copied()
         };
 
-        uow.end_transaction()?;
-
-        match result {
-            Some((pos, len)) => Ok(FindResultDto {
-                found: true,
-                position: pos as i64,
-                length: len as i64,
-            }),
-            None => Ok(FindResultDto {
-                found: false,
-                position: 0,
-                length: 0,
-            }),
+        let Some((pos, len, distance)) = result else {
+            uow.end_transaction()?;
+            return Ok(FindResultDto::default());
+        };
+        let mut found = FindResultDto {
+            found: true,
+            position: pos as i64,
+            length: len as i64,
+            distance: distance as i64,
+            ..Default::default()
+        };
+        if dto.with_details {
+            let details = match_describer(
+                uow.as_ref(),
+                &full_text,
+                &blocks,
+                &options,
+                dto.context_chars,
+            )?
+            .describe(pos, len);
+            let (table_id, row, column) = details.cell.unwrap_or((0, -1, -1));
+            found.block_id = details.block_id as i64;
+            found.block_position = details.block_position as i64;
+            found.frame_id = details.frame_id as i64;
+            found.table_id = table_id as i64;
+            found.cell_row = row;
+            found.cell_column = column;
+            found.context_before = details.context_before;
+            found.context_after = details.context_after;
+            for group in details.captures {
+                let (pos, len) = group.map_or((-1, 0), |(p, l)| (p as i64, l as i64));
+                found.capture_positions.push(pos);
+                found.capture_lengths.push(len);
+            }
         }
+
+        uow.end_transaction()?;
+        Ok(found)
     }
 }
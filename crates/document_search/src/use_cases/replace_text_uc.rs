@@ -1,26 +1,34 @@
-use super::search_helpers::{build_full_text_via_store, find_all_matches};
+use super::search_helpers::{
+    FoldedText, MatchOptions, ScopeLookup, SearchScope, TextFolding, build_char_to_byte_map,
+    build_full_text_via_store, expand_captures, expand_groups, find_all_matches, preserve_case,
+};
 use crate::ReplaceResultDto;
 use crate::ReplaceTextDto;
 use anyhow::{Result, anyhow};
 use common::database::CommandUnitOfWork;
+use common::database::Store;
 use common::database::rope_helpers::block_char_length;
 use common::database::rope_helpers::rope_flat_text_if_simple;
 use common::database::rope_helpers::{
-    block_content_via_store, rope_delete_in_block, rope_insert_in_block,
+    block_content_via_store, rope_delete_in_block, rope_insert_in_block, rope_merge_block_range,
+    rope_remove_block, rope_split_block,
 };
 use common::direct_access::document::document_repository::DocumentRelationshipField;
 use common::direct_access::frame::frame_repository::FrameRelationshipField;
 use common::direct_access::root::root_repository::RootRelationshipField;
-use common::entities::{Block, Document, Frame, Root};
+use common::entities::{Block, Document, Frame, Root, Table, TableCell};
 use common::format_runs::{
-    debug_assert_well_formed, logical_offset_to_byte, shift_images_for_delete,
-    shift_images_for_insert, shift_runs_for_delete, shift_runs_for_insert,
+    FormatRun, ImageAnchor, coalesce_in_place, debug_assert_well_formed, logical_offset_to_byte,
+    shift_images_for_delete, shift_images_for_insert, shift_runs_for_delete, shift_runs_for_insert,
+    split_images_at, split_runs_at,
 };
 
+use common::database::store_delta::StoreDelta;
 use common::snapshot::EntityTreeSnapshot;
 use common::types::{EntityId, ROOT_ENTITY_ID};
 use common::undo_redo::UndoRedoCommand;
 use std::any::Any;
+use std::collections::HashMap;
 
 pub trait ReplaceTextUnitOfWorkFactoryTrait: Send + Sync {
     fn create(&self) -> Box<dyn ReplaceTextUnitOfWorkTrait>;
@@ -34,14 +42,33 @@ pub trait ReplaceTextUnitOfWorkFactoryTrait: Send + Sync {
 #[macros::uow_action(entity = "Document", action = "Snapshot")]
 #[macros::uow_action(entity = "Document", action = "Restore")]
 #[macros::uow_action(entity = "Frame", action = "Get")]
+#[macros::uow_action(entity = "Frame", action = "Update")]
 #[macros::uow_action(entity = "Frame", action = "GetRelationship")]
 #[macros::uow_action(entity = "Block", action = "Get")]
 #[macros::uow_action(entity = "Block", action = "GetMulti")]
 #[macros::uow_action(entity = "Block", action = "Update")]
 #[macros::uow_action(entity = "Block", action = "UpdateMulti")]
+#[macros::uow_action(entity = "Block", action = "Create")]
+#[macros::uow_action(entity = "Block", action = "Remove")]
 #[macros::uow_action(entity = "Block", action = "GetRelationship")]
+#[macros::uow_action(entity = "Table", action = "Get")]
+#[macros::uow_action(entity = "TableCell", action = "GetMulti")]
 pub trait ReplaceTextUnitOfWorkTrait: CommandUnitOfWork {}
 
+impl ScopeLookup for dyn ReplaceTextUnitOfWorkTrait {
+    fn frame(&self, id: &EntityId) -> Result<Option<Frame>> {
+        self.get_frame(id)
+    }
+
+    fn table(&self, id: &EntityId) -> Result<Option<Table>> {
+        self.get_table(id)
+    }
+
+    fn table_cells(&self, ids: &[EntityId]) -> Result<Vec<Option<TableCell>>> {
+        self.get_table_cell_multi(ids)
+    }
+}
+
 fn fetch_blocks_and_build_text(
     uow: &dyn ReplaceTextUnitOfWorkTrait,
 ) -> Result<(String, Vec<Block>)> {
@@ -178,6 +205,60 @@ fn replace_in_block(
     Ok(())
 }
 
+/// The text each match is replaced with: the replacement with capture
+/// groups expanded when searching by regex, then adapted to the matched
+/// text's capitalization when `preserve_case` is set.
+fn resolve_replacements(
+    full_text: &str,
+    dto: &ReplaceTextDto,
+    matches: &[(usize, usize)],
+) -> Result<Vec<String>> {
+    let options = match_options(dto);
+    let re = if dto.use_regex {
+        Some(options.regex()?)
+    } else {
+        None
+    };
+    // A folding regex matched the folded text, but captured groups are
+    // copied from the document's own text.
+    let folded = (dto.use_regex && !options.folding.is_identity())
+        .then(|| FoldedText::new(full_text, &options.folding));
+    let char_to_byte = build_char_to_byte_map(full_text);
+
+    let mut replacements = Vec::with_capacity(matches.len());
+    for &(match_pos, match_len) in matches {
+        let byte_start = char_to_byte[match_pos];
+        let byte_end = char_to_byte[match_pos + match_len];
+        let mut text = match (&re, &folded) {
+            (Some(re), Some(folded)) => match folded.capture_groups(re, match_pos) {
+                Some(groups) => {
+                    expand_groups(re, full_text, &char_to_byte, &groups, &dto.replacement)
+                }
+                None => dto.replacement.clone(),
+            },
+            (Some(re), None) => expand_captures(re, full_text, byte_start, &dto.replacement),
+            (None, _) => dto.replacement.clone(),
+        };
+        if dto.preserve_case {
+            text = preserve_case(&full_text[byte_start..byte_end], &text);
+        }
+        replacements.push(text);
+    }
+    Ok(replacements)
+}
+
+fn match_options(dto: &ReplaceTextDto) -> MatchOptions<'_> {
+    MatchOptions {
+        query: &dto.query,
+        case_sensitive: dto.case_sensitive,
+        whole_word: dto.whole_word,
+        use_regex: dto.use_regex,
+        folding: TextFolding::new(dto.normalization.clone(), dto.fold_accents, dto.fold_width),
+        max_edits: None,
+        multi_line: dto.cross_block,
+    }
+}
+
 fn execute_replace(
     uow: &mut Box<dyn ReplaceTextUnitOfWorkTrait>,
     dto: &ReplaceTextDto,
@@ -194,13 +275,15 @@ fn execute_replace(
 
     let (full_text, blocks) = fetch_blocks_and_build_text(uow.as_ref())?;
 
-    let all_matches = find_all_matches(
-        &full_text,
-        &dto.query,
-        dto.case_sensitive,
-        dto.whole_word,
-        dto.use_regex,
+    let mut all_matches = find_all_matches(&full_text, &match_options(dto))?;
+    let scope = SearchScope::resolve(
+        uow.as_ref(),
+        dto.scope_start,
+        dto.scope_end,
+        dto.scope_frame_id,
+        dto.scope_table_id,
     )?;
+    scope.retain(&mut all_matches, &blocks, &uow.store());
 
     if all_matches.is_empty() {
         return Ok((
@@ -212,6 +295,12 @@ fn execute_replace(
         ));
     }
 
+    if dto.cross_block {
+        let result =
+            execute_cross_block_replace(uow, dto, doc_id, &full_text, &blocks, &all_matches)?;
+        return Ok((result, snapshot));
+    }
+
     let mut valid_matches: Vec<(usize, usize, usize, usize)> = Vec::new();
     let mut skipped_cross_block: i64 = 0;
     let store = uow.store();
@@ -239,15 +328,17 @@ fn execute_replace(
         ));
     }
 
-    let replacement = &dto.replacement;
-    let replacement_char_len = replacement.chars().count() as i64;
+    let positions: Vec<(usize, usize)> = valid_matches.iter().map(|m| (m.0, m.1)).collect();
+    let replacements = resolve_replacements(&full_text, dto, &positions)?;
     let replacements_count = valid_matches.len() as i64;
 
     let mut cumulative_delta: i64 = 0;
 
-    for &(_match_pos, match_len, block_idx, block_offset) in valid_matches.iter().rev() {
+    for (&(_match_pos, match_len, block_idx, block_offset), replacement) in
+        valid_matches.iter().zip(&replacements).rev()
+    {
         let match_char_len = match_len as i64;
-        let delta = replacement_char_len - match_char_len;
+        let delta = replacement.chars().count() as i64 - match_char_len;
 
         let block = uow
             .get_block(&blocks[block_idx].id)?
@@ -276,8 +367,10 @@ fn execute_replace(
 
     let mut delta_by_block: std::collections::HashMap<usize, i64> =
         std::collections::HashMap::new();
-    for &(_match_pos, match_len, block_idx, _block_offset) in &valid_matches {
-        let delta = replacement_char_len - match_len as i64;
+    for (&(_match_pos, match_len, block_idx, _block_offset), replacement) in
+        valid_matches.iter().zip(&replacements)
+    {
+        let delta = replacement.chars().count() as i64 - match_len as i64;
         *delta_by_block.entry(block_idx).or_insert(0) += delta;
     }
 
@@ -330,18 +423,404 @@ fn execute_replace(
     ))
 }
 
+// ── Cross-block replacement ─────────────────────────────────────
+
+/// A match in block coordinates: indices into the flow-ordered block
+/// list and char offsets inside the first and last block.
+struct BlockSpan {
+    position: usize,
+    length: usize,
+    start_idx: usize,
+    start_offset: usize,
+    end_idx: usize,
+    end_offset: usize,
+}
+
+/// Block index and in-block char offset of a full-text position. A
+/// position at a block's end stays in that block.
+fn locate(starts: &[usize], lengths: &[usize], position: usize) -> Option<(usize, usize)> {
+    let idx = starts.partition_point(|&s| s <= position).checked_sub(1)?;
+    let offset = position - starts[idx];
+    (offset <= lengths[idx]).then_some((idx, offset))
+}
+
+/// Owning frame and `child_order` index of every block placed directly
+/// in one of the document's frames.
+fn block_slots(
+    uow: &dyn ReplaceTextUnitOfWorkTrait,
+    doc_id: EntityId,
+) -> Result<HashMap<EntityId, (EntityId, usize)>> {
+    let mut slots = HashMap::new();
+    for frame_id in uow.get_document_relationship(&doc_id, &DocumentRelationshipField::Frames)? {
+        if let Some(frame) = uow.get_frame(&frame_id)? {
+            for (i, &entry) in frame.child_order.iter().enumerate() {
+                if entry > 0 {
+                    slots.insert(entry as EntityId, (frame_id, i));
+                }
+            }
+        }
+    }
+    Ok(slots)
+}
+
+fn read_runs_and_images(store: &Store, block_id: EntityId) -> (Vec<FormatRun>, Vec<ImageAnchor>) {
+    let runs = store
+        .format_runs
+        .read()
+        .unwrap()
+        .get(&block_id)
+        .cloned()
+        .unwrap_or_default();
+    let images = store
+        .block_images
+        .read()
+        .unwrap()
+        .get(&block_id)
+        .cloned()
+        .unwrap_or_default();
+    (runs, images)
+}
+
+fn write_runs_and_images(
+    store: &Store,
+    block_id: EntityId,
+    runs: Vec<FormatRun>,
+    images: Vec<ImageAnchor>,
+) {
+    store.format_runs.write().unwrap().insert(block_id, runs);
+    store.block_images.write().unwrap().insert(block_id, images);
+}
+
+/// Replace matches that may span paragraph breaks. The blocks a match
+/// covers are merged into its first block, keeping the format runs of
+/// the text around the match, and every `\n` in the replacement splits
+/// the block, the new blocks taking the block format of the first one.
+/// Matches whose blocks are not consecutive siblings of one frame
+/// (table cells, frame boundaries) are skipped.
+fn execute_cross_block_replace(
+    uow: &mut Box<dyn ReplaceTextUnitOfWorkTrait>,
+    dto: &ReplaceTextDto,
+    doc_id: EntityId,
+    full_text: &str,
+    blocks: &[Block],
+    all_matches: &[(usize, usize)],
+) -> Result<ReplaceResultDto> {
+    let store = uow.store();
+    let lengths: Vec<usize> = blocks
+        .iter()
+        .map(|b| block_char_length(b, &store) as usize)
+        .collect();
+    let mut starts = Vec::with_capacity(blocks.len());
+    let mut next = 0;
+    for len in &lengths {
+        starts.push(next);
+        next += len + 1;
+    }
+    let slots = block_slots(uow.as_ref(), doc_id)?;
+    let joinable = |a: &Block, b: &Block| match (slots.get(&a.id), slots.get(&b.id)) {
+        (Some(a), Some(b)) => a.0 == b.0 && b.1 == a.1 + 1,
+        _ => false,
+    };
+
+    let mut spans: Vec<BlockSpan> = Vec::new();
+    let mut skipped_cross_block: i64 = 0;
+    let mut last_end = 0;
+    for &(position, length) in all_matches {
+        // Literal search reports overlapping matches; replace the first.
+        if position < last_end && !spans.is_empty() {
+            continue;
+        }
+        let (Some((start_idx, start_offset)), Some((end_idx, end_offset))) = (
+            locate(&starts, &lengths, position),
+            locate(&starts, &lengths, position + length),
+        ) else {
+            skipped_cross_block += 1;
+            continue;
+        };
+        if !slots.contains_key(&blocks[start_idx].id)
+            || !(start_idx..end_idx).all(|i| joinable(&blocks[i], &blocks[i + 1]))
+        {
+            skipped_cross_block += 1;
+            continue;
+        }
+        spans.push(BlockSpan {
+            position,
+            length,
+            start_idx,
+            start_offset,
+            end_idx,
+            end_offset,
+        });
+        last_end = position + length;
+        if !dto.replace_all {
+            break;
+        }
+    }
+
+    if spans.is_empty() {
+        return Ok(ReplaceResultDto {
+            replacements_count: 0,
+            skipped_cross_block,
+        });
+    }
+
+    let positions: Vec<(usize, usize)> = spans.iter().map(|s| (s.position, s.length)).collect();
+    let replacements = resolve_replacements(full_text, dto, &positions)?;
+
+    // Surviving blocks in flow order, each with the position gap that
+    // follows it (non-zero around tables), kept in step with merges and
+    // splits so positions can be recomputed afterwards.
+    let mut order: Vec<(EntityId, i64)> = blocks
+        .iter()
+        .enumerate()
+        .map(|(k, b)| {
+            let gap = blocks.get(k + 1).map_or(0, |next| {
+                next.document_position - (b.document_position + lengths[k] as i64 + 1)
+            });
+            (b.id, gap)
+        })
+        .collect();
+
+    let now = chrono::Utc::now();
+    let mut chars_delta: i64 = 0;
+    let mut blocks_delta: i64 = 0;
+    // Back to front, so earlier spans keep their block indices and offsets.
+    for (span, replacement) in spans.iter().zip(&replacements).rev() {
+        let frame_id = slots[&blocks[span.start_idx].id].0;
+        replace_span(uow, blocks, span, replacement, frame_id, &mut order, now)?;
+        let merged = (span.end_idx - span.start_idx) as i64;
+        let split = replacement.matches('\n').count() as i64;
+        chars_delta += (replacement.chars().count() as i64 - split) - (span.length as i64 - merged);
+        blocks_delta += split - merged;
+    }
+
+    // Recompute stored positions from the first edited block onwards.
+    let first = &blocks[spans[0].start_idx];
+    let first_order_idx = order
+        .iter()
+        .position(|(id, _)| *id == first.id)
+        .ok_or_else(|| anyhow!("Block not found"))?;
+    let ids: Vec<EntityId> = order[first_order_idx..].iter().map(|(id, _)| *id).collect();
+    let current: Vec<Block> = uow.get_block_multi(&ids)?.into_iter().flatten().collect();
+    let mut position = first.document_position;
+    let mut blocks_to_update: Vec<Block> = Vec::new();
+    for (block, &(_, gap)) in current.iter().zip(&order[first_order_idx..]) {
+        if block.document_position != position {
+            let mut ub = block.clone();
+            ub.document_position = position;
+            ub.updated_at = now;
+            blocks_to_update.push(ub);
+        }
+        position += block_char_length(block, &store) + 1 + gap;
+    }
+    if !blocks_to_update.is_empty() {
+        uow.update_block_multi(&blocks_to_update)?;
+    }
+
+    let mut document = uow
+        .get_document(&doc_id)?
+        .ok_or_else(|| anyhow!("Document not found"))?;
+    document.character_count += chars_delta;
+    document.block_count += blocks_delta;
+    document.updated_at = now;
+    uow.update_document(&document)?;
+
+    Ok(ReplaceResultDto {
+        replacements_count: spans.len() as i64,
+        skipped_cross_block,
+    })
+}
+
+/// Replace one span: merge its blocks into the first, then insert the
+/// replacement line by line, splitting the block at each `\n`.
+fn replace_span(
+    uow: &mut Box<dyn ReplaceTextUnitOfWorkTrait>,
+    blocks: &[Block],
+    span: &BlockSpan,
+    replacement: &str,
+    frame_id: EntityId,
+    order: &mut Vec<(EntityId, i64)>,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<()> {
+    let store = uow.store();
+    let first = uow
+        .get_block(&blocks[span.start_idx].id)?
+        .ok_or_else(|| anyhow!("Block not found"))?;
+    let last_id = blocks[span.end_idx].id;
+
+    let first_text = block_content_via_store(&first, &store);
+    let (first_runs, first_images) = read_runs_and_images(&store, first.id);
+    let byte_so = logical_offset_to_byte(&first_text, &first_images, span.start_offset as i64);
+    let (last_text, last_runs, last_images) = if span.end_idx == span.start_idx {
+        (first_text.clone(), first_runs.clone(), first_images.clone())
+    } else {
+        let last = uow
+            .get_block(&last_id)?
+            .ok_or_else(|| anyhow!("Block not found"))?;
+        let (runs, images) = read_runs_and_images(&store, last_id);
+        (block_content_via_store(&last, &store), runs, images)
+    };
+    let byte_eo = logical_offset_to_byte(&last_text, &last_images, span.end_offset as i64);
+
+    // Keep the first block's content before the match and the last
+    // block's content after it.
+    let (mut runs, _) = split_runs_at(&first_runs, byte_so);
+    let (_, tail_runs) = split_runs_at(&last_runs, byte_eo);
+    runs.extend(tail_runs.into_iter().map(|run| FormatRun {
+        byte_start: run.byte_start + byte_so,
+        byte_end: run.byte_end + byte_so,
+        format: run.format,
+    }));
+    coalesce_in_place(&mut runs);
+    let (mut images, _) = split_images_at(&first_images, byte_so);
+    let (_, tail_images) = split_images_at(&last_images, byte_eo);
+    images.extend(tail_images.into_iter().map(|mut image| {
+        image.byte_offset += byte_so;
+        image
+    }));
+
+    if span.end_idx == span.start_idx {
+        rope_delete_in_block(&store, first.id, byte_so, byte_eo);
+    } else {
+        rope_merge_block_range(&store, first.id, byte_so, last_id, byte_eo);
+        let removed: Vec<EntityId> = blocks[span.start_idx + 1..=span.end_idx]
+            .iter()
+            .map(|b| b.id)
+            .collect();
+        for id in &removed {
+            store.format_runs.write().unwrap().remove(id);
+            store.block_images.write().unwrap().remove(id);
+            rope_remove_block(&store, *id);
+            uow.remove_block(id)?;
+        }
+        let mut frame = uow
+            .get_frame(&frame_id)?
+            .ok_or_else(|| anyhow!("Frame not found"))?;
+        frame
+            .child_order
+            .retain(|entry| !removed.contains(&(*entry as EntityId)));
+        frame.updated_at = now;
+        uow.update_frame(&frame)?;
+
+        let trailing_gap = order
+            .iter()
+            .find(|(id, _)| *id == last_id)
+            .map_or(0, |(_, gap)| *gap);
+        order.retain(|(id, _)| !removed.contains(id));
+        if let Some(entry) = order.iter_mut().find(|(id, _)| *id == first.id) {
+            entry.1 = trailing_gap;
+        }
+    }
+
+    // The replacement takes the format of the text before the match
+    // (Qt convention), on every line.
+    let inherited = runs
+        .iter()
+        .find(|run| run.byte_start < byte_so && run.byte_end >= byte_so)
+        .map(|run| run.format.clone());
+
+    let mut lines = replacement.split('\n');
+    let first_line = lines.next().unwrap_or_default();
+    let first_line_len = first_line.len() as u32;
+    shift_runs_for_insert(&mut runs, byte_so, first_line_len);
+    shift_images_for_insert(&mut images, byte_so, first_line_len);
+    debug_assert_well_formed(
+        &runs,
+        byte_so as usize + first_line.len() + last_text.len() - byte_eo as usize,
+    );
+    write_runs_and_images(&store, first.id, runs, images);
+    rope_insert_in_block(&store, first.id, byte_so, first_line);
+    let mut updated_first = first.clone();
+    updated_first.updated_at = now;
+    uow.update_block(&updated_first)?;
+
+    let mut current = first;
+    let mut cursor = byte_so + first_line_len;
+    for line in lines {
+        current = split_block(uow, &current, cursor, frame_id, order, now)?;
+        let line_len = line.len() as u32;
+        let (mut runs, mut images) = read_runs_and_images(&store, current.id);
+        shift_runs_for_insert(&mut runs, 0, line_len);
+        shift_images_for_insert(&mut images, 0, line_len);
+        if let Some(format) = inherited.clone().filter(|_| line_len > 0) {
+            runs.insert(
+                0,
+                FormatRun {
+                    byte_start: 0,
+                    byte_end: line_len,
+                    format,
+                },
+            );
+            coalesce_in_place(&mut runs);
+        }
+        write_runs_and_images(&store, current.id, runs, images);
+        rope_insert_in_block(&store, current.id, 0, line);
+        cursor = line_len;
+    }
+    Ok(())
+}
+
+/// Split `block` at `byte_offset`, moving the content after it into a
+/// new block with the same block format, placed right after `block` in
+/// `frame_id`. Returns the new block.
+fn split_block(
+    uow: &mut Box<dyn ReplaceTextUnitOfWorkTrait>,
+    block: &Block,
+    byte_offset: u32,
+    frame_id: EntityId,
+    order: &mut Vec<(EntityId, i64)>,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<Block> {
+    let store = uow.store();
+    let (runs, images) = read_runs_and_images(&store, block.id);
+    let (left_runs, right_runs) = split_runs_at(&runs, byte_offset);
+    let (left_images, right_images) = split_images_at(&images, byte_offset);
+    write_runs_and_images(&store, block.id, left_runs, left_images);
+
+    let new_block = Block {
+        id: 0,
+        created_at: now,
+        updated_at: now,
+        ..block.clone()
+    };
+    let created = uow.create_block(&new_block, frame_id, -1)?;
+    write_runs_and_images(&store, created.id, right_runs, right_images);
+    rope_split_block(&store, block.id, byte_offset, created.id);
+
+    let mut frame = uow
+        .get_frame(&frame_id)?
+        .ok_or_else(|| anyhow!("Frame not found"))?;
+    let index = frame
+        .child_order
+        .iter()
+        .position(|&e| e > 0 && e as EntityId == block.id)
+        .map_or(frame.child_order.len(), |i| i + 1);
+    frame.child_order.insert(index, created.id as i64);
+    frame.blocks = uow.get_frame_relationship(&frame_id, &FrameRelationshipField::Blocks)?;
+    frame.updated_at = now;
+    uow.update_frame(&frame)?;
+
+    if let Some(pos) = order.iter().position(|(id, _)| *id == block.id) {
+        let gap = std::mem::replace(&mut order[pos].1, 0);
+        order.insert(pos + 1, (created.id, gap));
+    }
+    Ok(created)
+}
+
 pub struct ReplaceTextUseCase {
     uow_factory: Box<dyn ReplaceTextUnitOfWorkFactoryTrait>,
-    undo_snapshot: Option<EntityTreeSnapshot>,
+    undo_delta: Option<StoreDelta>,
     last_dto: Option<ReplaceTextDto>,
+    replacements_count: i64,
 }
 
 impl ReplaceTextUseCase {
     pub fn new(uow_factory: Box<dyn ReplaceTextUnitOfWorkFactoryTrait>) -> Self {
         ReplaceTextUseCase {
             uow_factory,
-            undo_snapshot: None,
+            undo_delta: None,
             last_dto: None,
+            replacements_count: 0,
         }
     }
 
@@ -350,8 +829,9 @@ impl ReplaceTextUseCase {
         uow.begin_transaction()?;
 
         let (result, snapshot) = execute_replace(&mut uow, dto)?;
-        self.undo_snapshot = Some(snapshot);
+        self.undo_delta = Some(StoreDelta::since(&snapshot, &uow.store())?);
         self.last_dto = Some(dto.clone());
+        self.replacements_count = result.replacements_count;
 
         uow.commit()?;
         Ok(result)
@@ -360,14 +840,14 @@ impl ReplaceTextUseCase {
 
 impl UndoRedoCommand for ReplaceTextUseCase {
     fn undo(&mut self) -> Result<()> {
-        let snapshot = self
-            .undo_snapshot
+        let delta = self
+            .undo_delta
             .as_ref()
-            .ok_or_else(|| anyhow!("No snapshot available for undo"))?
-            .clone();
+            .ok_or_else(|| anyhow!("No delta available for undo"))?;
 
         let mut uow = self.uow_factory.create();
         uow.begin_transaction()?;
+        let snapshot = delta.reverted(&uow.store())?;
         uow.restore_document(&snapshot)?;
         uow.commit()?;
         Ok(())
@@ -382,12 +862,22 @@ impl UndoRedoCommand for ReplaceTextUseCase {
 
         let mut uow = self.uow_factory.create();
         uow.begin_transaction()?;
-        let (_, snapshot) = execute_replace(&mut uow, &dto)?;
-        self.undo_snapshot = Some(snapshot);
+        let (result, snapshot) = execute_replace(&mut uow, &dto)?;
+        self.undo_delta = Some(StoreDelta::since(&snapshot, &uow.store())?);
+        self.replacements_count = result.replacements_count;
         uow.commit()?;
         Ok(())
     }
 
+    fn description(&self) -> String {
+        match self.replacements_count {
+            1 => "Replace 1 occurrence".to_string(),
+            count => format!("Replace {count} occurrences"),
+        }
+    }
+
+    common::store_delta_undo_methods!(undo_delta);
+
     fn as_any(&self) -> &dyn Any {
         self
     }
@@ -1,12 +1,18 @@
-use std::collections::HashSet;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
 
-use anyhow::{Result, anyhow};
+use anyhow::{Result, anyhow, bail};
 use common::database::Store;
-use common::database::rope_helpers::block_content_via_store;
-use common::entities::Block;
-use regex::RegexBuilder;
+use common::database::rope_helpers::{block_char_length, block_content_via_store};
+use common::entities::{Block, Frame, Table, TableCell};
+use common::types::EntityId;
+use regex::{Regex, RegexBuilder};
+use unicode_normalization::UnicodeNormalization;
+use unicode_normalization::char::{canonical_combining_class, decompose_compatible};
 use unicode_segmentation::UnicodeSegmentation;
 
+use crate::SearchNormalization;
+
 /// Build the full document text from main-flow blocks by reading
 /// content from the global rope via `block_offsets`. Blocks whose
 /// content isn't in the rope (e.g. table-cell blocks not yet covered
@@ -30,6 +36,306 @@ pub fn build_full_text_via_store(blocks: &[Block], store: &Store) -> String {
     out
 }
 
+/// Entity lookups needed to resolve a frame or table search scope.
+/// Implemented by the search units of work.
+pub trait ScopeLookup {
+    fn frame(&self, id: &EntityId) -> Result<Option<Frame>>;
+    fn table(&self, id: &EntityId) -> Result<Option<Table>>;
+    fn table_cells(&self, ids: &[EntityId]) -> Result<Vec<Option<TableCell>>>;
+}
+
+/// The part of the document a search is restricted to. A match must
+/// lie entirely inside the character range and, for a frame or table
+/// scope, inside the blocks of that frame (sub-frames and tables
+/// included) or of that table's cells. Unset parts don't restrict.
+#[derive(Debug, Default)]
+pub struct SearchScope {
+    range: Option<(usize, usize)>,
+    blocks: Option<HashSet<EntityId>>,
+}
+
+impl SearchScope {
+    pub fn resolve<L: ScopeLookup + ?Sized>(
+        lookup: &L,
+        start: Option<i64>,
+        end: Option<i64>,
+        frame_id: Option<i64>,
+        table_id: Option<i64>,
+    ) -> Result<Self> {
+        let range = (start.is_some() || end.is_some()).then(|| {
+            (
+                start.unwrap_or(0).max(0) as usize,
+                end.map_or(usize::MAX, |e| e.max(0) as usize),
+            )
+        });
+
+        let mut blocks: Option<HashSet<EntityId>> = None;
+        if let Some(frame_id) = frame_id {
+            let mut ids = HashSet::new();
+            collect_frame_blocks(lookup, frame_id as EntityId, &mut ids)?;
+            blocks = Some(ids);
+        }
+        if let Some(table_id) = table_id {
+            let mut ids = HashSet::new();
+            collect_table_blocks(lookup, table_id as EntityId, &mut ids)?;
+            blocks = Some(match blocks {
+                Some(frame_ids) => frame_ids.intersection(&ids).copied().collect(),
+                None => ids,
+            });
+        }
+        Ok(SearchScope { range, blocks })
+    }
+
+    /// Whether [`retain`](Self::retain) needs the document's blocks.
+    pub fn needs_blocks(&self) -> bool {
+        self.blocks.is_some()
+    }
+
+    /// Drop the matches that fall outside the scope. `blocks` are the
+    /// position-sorted blocks the searched text was built from; they
+    /// are only read for a frame or table scope.
+    pub fn retain(&self, matches: &mut Vec<(usize, usize)>, blocks: &[Block], store: &Store) {
+        let filter = self.filter(blocks, store);
+        matches.retain(|&(pos, len)| filter.contains(pos, len));
+    }
+
+    /// Precompute the text ranges of the scope, to test matches one at a
+    /// time while scanning.
+    pub fn filter(&self, blocks: &[Block], store: &Store) -> ScopeFilter {
+        let Some(ids) = &self.blocks else {
+            return ScopeFilter {
+                range: self.range,
+                block_ranges: None,
+            };
+        };
+        // Text ranges covered by scope blocks; neighbouring scope blocks
+        // share one range so a match may span their paragraph break.
+        let mut ranges: Vec<(usize, usize)> = Vec::new();
+        let mut block_start = 0;
+        let mut previous_inside = false;
+        for block in blocks {
+            let block_end = block_start + block_char_length(block, store) as usize;
+            let inside = ids.contains(&block.id);
+            if inside {
+                match ranges.last_mut() {
+                    Some(range) if previous_inside => range.1 = block_end,
+                    _ => ranges.push((block_start, block_end)),
+                }
+            }
+            previous_inside = inside;
+            block_start = block_end + 1;
+        }
+        ScopeFilter {
+            range: self.range,
+            block_ranges: Some(ranges),
+        }
+    }
+}
+
+/// A [`SearchScope`] resolved against the searched text.
+pub struct ScopeFilter {
+    range: Option<(usize, usize)>,
+    block_ranges: Option<Vec<(usize, usize)>>,
+}
+
+impl ScopeFilter {
+    pub fn contains(&self, pos: usize, len: usize) -> bool {
+        let inside = |&(start, end): &(usize, usize)| pos >= start && pos + len <= end;
+        self.range.as_ref().is_none_or(inside)
+            && self
+                .block_ranges
+                .as_ref()
+                .is_none_or(|ranges| ranges.iter().any(inside))
+    }
+}
+
+fn collect_frame_blocks<L: ScopeLookup + ?Sized>(
+    lookup: &L,
+    frame_id: EntityId,
+    out: &mut HashSet<EntityId>,
+) -> Result<()> {
+    let frame = lookup
+        .frame(&frame_id)?
+        .ok_or_else(|| anyhow!("Frame {} not found", frame_id))?;
+    out.extend(frame.blocks.iter().copied());
+    if let Some(table_id) = frame.table {
+        collect_table_blocks(lookup, table_id, out)?;
+    }
+    for &entry in &frame.child_order {
+        if entry < 0 {
+            collect_frame_blocks(lookup, (-entry) as EntityId, out)?;
+        }
+    }
+    Ok(())
+}
+
+fn collect_table_blocks<L: ScopeLookup + ?Sized>(
+    lookup: &L,
+    table_id: EntityId,
+    out: &mut HashSet<EntityId>,
+) -> Result<()> {
+    let table = lookup
+        .table(&table_id)?
+        .ok_or_else(|| anyhow!("Table {} not found", table_id))?;
+    for cell in lookup.table_cells(&table.cells)?.into_iter().flatten() {
+        if let Some(frame_id) = cell.cell_frame {
+            collect_frame_blocks(lookup, frame_id, out)?;
+        }
+    }
+    Ok(())
+}
+
+/// Where a match sits in the document and what surrounds it.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MatchDetails {
+    pub block_id: EntityId,
+    /// Char offset of the match within its block.
+    pub block_position: usize,
+    pub frame_id: EntityId,
+    /// `(table_id, row, column)` when the block is in a table cell.
+    pub cell: Option<(EntityId, i64, i64)>,
+    pub context_before: String,
+    pub context_after: String,
+    /// `(char_position, char_length)` of each regex capture group, group
+    /// 1 first; `None` for a group that took no part in the match.
+    pub captures: Vec<Option<(usize, usize)>>,
+}
+
+/// Describes matches in a searched text: their block, frame and table
+/// cell, up to `context_chars` characters of surrounding text (never
+/// past the match's block) and, for a regex search, the capture groups.
+pub struct MatchDescriber<'a> {
+    text: Cow<'a, str>,
+    /// Byte offset of every char of `text`, plus `text.len()`.
+    char_bytes: Vec<usize>,
+    block_starts: Vec<usize>,
+    block_ids: Vec<EntityId>,
+    frame_of_block: HashMap<EntityId, EntityId>,
+    cell_of_frame: HashMap<EntityId, (EntityId, i64, i64)>,
+    regex: Option<Regex>,
+    /// The searched text when the regex ran over a folded one.
+    folded: Option<FoldedText>,
+    context_chars: usize,
+}
+
+impl<'a> MatchDescriber<'a> {
+    /// `blocks` are the position-sorted blocks `text` was built from,
+    /// `frame_ids` and `table_ids` the document's frames and tables.
+    /// The text may be borrowed or handed over, for a describer kept
+    /// longer than the text's builder.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new<L: ScopeLookup + ?Sized>(
+        lookup: &L,
+        text: impl Into<Cow<'a, str>>,
+        blocks: &[Block],
+        frame_ids: &[EntityId],
+        table_ids: &[EntityId],
+        store: &Store,
+        options: &MatchOptions,
+        context_chars: usize,
+    ) -> Result<Self> {
+        let text = text.into();
+        let char_bytes = build_char_to_byte_map(&text);
+        let regex = if options.use_regex {
+            Some(options.regex()?)
+        } else {
+            None
+        };
+        let folded = (options.use_regex && !options.folding.is_identity())
+            .then(|| FoldedText::new(&text, &options.folding));
+
+        let mut block_starts = Vec::with_capacity(blocks.len());
+        let mut start = 0;
+        for block in blocks {
+            block_starts.push(start);
+            start += block_char_length(block, store) as usize + 1;
+        }
+
+        let mut frames = HashMap::new();
+        for frame_id in frame_ids {
+            if let Some(frame) = lookup.frame(frame_id)? {
+                frames.insert(*frame_id, frame);
+            }
+        }
+        let mut cell_frames = HashMap::new();
+        for table_id in table_ids {
+            let Some(table) = lookup.table(table_id)? else {
+                continue;
+            };
+            for cell in lookup.table_cells(&table.cells)?.into_iter().flatten() {
+                if let Some(frame_id) = cell.cell_frame {
+                    cell_frames.insert(frame_id, (*table_id, cell.row, cell.column));
+                }
+            }
+        }
+        // A frame nested inside a cell still belongs to that cell.
+        let mut cell_of_frame = HashMap::new();
+        for &frame_id in frames.keys() {
+            let mut current = Some(frame_id);
+            while let Some(id) = current {
+                if let Some(&cell) = cell_frames.get(&id) {
+                    cell_of_frame.insert(frame_id, cell);
+                    break;
+                }
+                current = frames.get(&id).and_then(|f| f.parent_frame);
+            }
+        }
+        let frame_of_block = frames
+            .values()
+            .flat_map(|frame| frame.blocks.iter().map(move |&block| (block, frame.id)))
+            .collect();
+
+        Ok(MatchDescriber {
+            text,
+            char_bytes,
+            block_starts,
+            block_ids: blocks.iter().map(|b| b.id).collect(),
+            frame_of_block,
+            cell_of_frame,
+            regex,
+            folded,
+            context_chars,
+        })
+    }
+
+    pub fn describe(&self, pos: usize, len: usize) -> MatchDetails {
+        let chars = self.char_bytes.len() - 1;
+        let pos = pos.min(chars);
+        let end = (pos + len).min(chars);
+        let byte = |char_pos: usize| self.char_bytes[char_pos];
+
+        let mut details = MatchDetails::default();
+        let index = self.block_starts.partition_point(|&s| s <= pos);
+        if index > 0 {
+            details.block_id = self.block_ids[index - 1];
+            details.block_position = pos - self.block_starts[index - 1];
+            details.frame_id = self
+                .frame_of_block
+                .get(&details.block_id)
+                .copied()
+                .unwrap_or_default();
+            details.cell = self.cell_of_frame.get(&details.frame_id).copied();
+        }
+
+        let before = &self.text[byte(pos.saturating_sub(self.context_chars))..byte(pos)];
+        details.context_before = match before.rfind('\n') {
+            Some(i) => before[i + 1..].to_string(),
+            None => before.to_string(),
+        };
+        let after = &self.text[byte(end)..byte((end + self.context_chars).min(chars))];
+        details.context_after = after.split('\n').next().unwrap_or_default().to_string();
+
+        if let Some(re) = &self.regex {
+            let groups = match &self.folded {
+                Some(folded) => folded.capture_groups(re, pos),
+                None => capture_groups(re, &self.text, &self.char_bytes, pos),
+            };
+            details.captures = groups.unwrap_or_default().into_iter().skip(1).collect();
+        }
+        details
+    }
+}
+
 /// Build a mapping from byte offset to char index for a string.
 /// `byte_to_char[byte_offset] = char_index`
 /// The vec has len = `text.len() + 1` (inclusive of the end position).
@@ -44,6 +350,13 @@ pub fn build_byte_to_char_map(text: &str) -> Vec<usize> {
     map
 }
 
+/// Byte offset of every char of `text`, plus `text.len()`.
+pub fn build_char_to_byte_map(text: &str) -> Vec<usize> {
+    let mut map: Vec<usize> = text.char_indices().map(|(i, _)| i).collect();
+    map.push(text.len());
+    map
+}
+
 /// Pre-compute the set of char indices that are Unicode word boundaries.
 ///
 /// A word boundary is a char index where a word starts or ends according
@@ -54,98 +367,809 @@ pub fn build_word_boundary_set(text: &str) -> HashSet<usize> {
     let mut set = HashSet::new();
     set.insert(0);
     set.insert(chars_len);
+    // Count chars from the previous word's end, not from the start of
+    // the text, to stay linear.
+    let (mut byte_end, mut char_end) = (0, 0);
     for (byte_start, word) in text.unicode_word_indices() {
-        let word_char_start = text[..byte_start].chars().count();
-        let word_char_end = word_char_start + word.chars().count();
+        let word_char_start = char_end + text[byte_end..byte_start].chars().count();
+        char_end = word_char_start + word.chars().count();
+        byte_end = byte_start + word.len();
         set.insert(word_char_start);
-        set.insert(word_char_end);
+        set.insert(char_end);
     }
     set
 }
 
-/// Find all occurrences of the query in the text, respecting search options.
-/// All positions are in char indices (not byte offsets).
-/// Returns a vec of `(char_position, char_length)` for each match.
-pub fn find_all_matches(
-    full_text: &str,
-    query: &str,
-    case_sensitive: bool,
-    whole_word: bool,
-    use_regex: bool,
-) -> Result<Vec<(usize, usize)>> {
-    if query.is_empty() {
-        return Ok(Vec::new());
+/// Compile a search pattern with the size limits used by every search.
+/// With `multi_line`, `^` and `$` also match at block boundaries, since
+/// the searched text has one line per block.
+pub fn build_regex(query: &str, case_sensitive: bool, multi_line: bool) -> Result<Regex> {
+    RegexBuilder::new(query)
+        .case_insensitive(!case_sensitive)
+        .multi_line(multi_line)
+        .size_limit(1 << 20) // 1 MB compiled size limit
+        .dfa_size_limit(1 << 20)
+        .build()
+        .map_err(|e| anyhow!("Invalid regex pattern: {}", e))
+}
+
+/// Expand `$1`, `${name}` and `$$` in `template` for the regex match
+/// starting at `byte_start` in `text`. Falls back to the literal
+/// template when no match starts there.
+pub fn expand_captures(re: &Regex, text: &str, byte_start: usize, template: &str) -> String {
+    match re.captures_at(text, byte_start) {
+        Some(caps) if caps.get(0).is_some_and(|m| m.start() == byte_start) => {
+            let mut out = String::new();
+            caps.expand(template, &mut out);
+            out
+        }
+        _ => template.to_string(),
     }
+}
 
-    // Pre-compute word boundaries once if needed, instead of O(n) per match.
-    let word_boundaries = if whole_word {
-        Some(build_word_boundary_set(full_text))
-    } else {
-        None
+/// Capitalization of one word of matched text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum WordCase {
+    Upper,
+    Lower,
+    /// A leading capital ("Foo").
+    Capitalized,
+    /// Anything else ("iPhone"): the replacement word is left alone.
+    Mixed,
+}
+
+impl WordCase {
+    fn of(word: &str) -> Option<WordCase> {
+        let cased: Vec<char> = word
+            .chars()
+            .filter(|c| c.is_uppercase() || c.is_lowercase())
+            .collect();
+        let first = cased.first()?;
+        Some(
+            if cased.len() > 1 && cased.iter().all(|c| c.is_uppercase()) {
+                WordCase::Upper
+            } else if cased.iter().all(|c| c.is_lowercase()) {
+                WordCase::Lower
+            } else if first.is_uppercase() && cased[1..].iter().all(|c| c.is_lowercase()) {
+                WordCase::Capitalized
+            } else {
+                WordCase::Mixed
+            },
+        )
+    }
+
+    fn apply(self, word: &str) -> String {
+        match self {
+            WordCase::Upper => word.to_uppercase(),
+            WordCase::Lower => word.to_lowercase(),
+            WordCase::Capitalized => {
+                let mut chars = word.chars();
+                match chars.next() {
+                    Some(c) => c.to_uppercase().chain(chars).collect(),
+                    None => String::new(),
+                }
+            }
+            WordCase::Mixed => word.to_string(),
+        }
+    }
+}
+
+/// Splits `text` into alternating runs of word and non-word characters,
+/// flagging the word runs. Hyphens and apostrophes between letters
+/// ("e-mail", "don't") belong to the word.
+fn word_runs(text: &str) -> Vec<(&str, bool)> {
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    let in_word = |i: usize| {
+        let (_, c) = chars[i];
+        c.is_alphanumeric()
+            || (matches!(c, '-' | '\'' | '\u{2019}')
+                && i > 0
+                && chars[i - 1].1.is_alphanumeric()
+                && chars.get(i + 1).is_some_and(|(_, n)| n.is_alphanumeric()))
+    };
+    let mut runs = Vec::new();
+    let mut start = 0;
+    for i in 1..=chars.len() {
+        if i == chars.len() || in_word(i) != in_word(start) {
+            let end = chars.get(i).map_or(text.len(), |(b, _)| *b);
+            runs.push((&text[chars[start].0..end], in_word(start)));
+            start = i;
+        }
+    }
+    runs
+}
+
+/// Adapt `replacement` to the capitalization of `matched`, word by word:
+/// each replacement word takes the case of the matched word at the same
+/// index, and words past the last matched one reuse its case. An all
+/// upper-case word ("FOO") gives an upper-case word, an all lower-case
+/// one a lower-case word, and a leading capital ("Foo") capitalizes the
+/// first letter. Mixed case leaves the word untouched.
+///
+/// So "HELLO world" replaced by "goodbye moon" gives "GOODBYE moon", and
+/// "Hello" replaced by "good night" gives "Good Night".
+pub fn preserve_case(matched: &str, replacement: &str) -> String {
+    let cases: Vec<WordCase> = word_runs(matched)
+        .into_iter()
+        .filter(|(_, is_word)| *is_word)
+        .filter_map(|(word, _)| WordCase::of(word))
+        .collect();
+    let Some(&last) = cases.last() else {
+        return replacement.to_string();
     };
+    let mut index = 0;
+    word_runs(replacement)
+        .into_iter()
+        .map(|(run, is_word)| {
+            if !is_word {
+                return run.to_string();
+            }
+            let case = cases.get(index).copied().unwrap_or(last);
+            index += 1;
+            case.apply(run)
+        })
+        .collect()
+}
 
-    let mut results = Vec::new();
+/// How text and query are folded before they are compared, so that
+/// e.g. "resume" finds "résumé" or NFD text matches an NFC query.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TextFolding {
+    pub normalization: SearchNormalization,
+    /// Drop diacritics: combining accents, and the strokes of letters
+    /// such as "đ" and "ø".
+    pub fold_accents: bool,
+    /// Map full-width forms to ASCII and half-width katakana to their
+    /// usual full-width form.
+    pub fold_width: bool,
+}
 
-    if use_regex {
-        let re = RegexBuilder::new(query)
-            .case_insensitive(!case_sensitive)
-            .size_limit(1 << 20) // 1 MB compiled size limit
-            .dfa_size_limit(1 << 20)
-            .build()
-            .map_err(|e| anyhow!("Invalid regex pattern: {}", e))?;
+/// Letters whose stroke is not a combining mark, so decomposition
+/// alone doesn't remove it.
+const STROKED_LETTERS: &[(char, char)] = &[
+    ('đ', 'd'),
+    ('Đ', 'D'),
+    ('ø', 'o'),
+    ('Ø', 'O'),
+    ('ł', 'l'),
+    ('Ł', 'L'),
+    ('ħ', 'h'),
+    ('Ħ', 'H'),
+];
 
-        let char_offsets = build_byte_to_char_map(full_text);
+impl TextFolding {
+    pub fn new(normalization: SearchNormalization, fold_accents: bool, fold_width: bool) -> Self {
+        TextFolding {
+            normalization,
+            fold_accents,
+            fold_width,
+        }
+    }
+
+    pub fn is_identity(&self) -> bool {
+        self.normalization == SearchNormalization::NoNormalization
+            && !self.fold_accents
+            && !self.fold_width
+    }
 
-        for mat in re.find_iter(full_text) {
-            let char_start = char_offsets[mat.start()];
-            let char_end = char_offsets[mat.end()];
-            let char_len = char_end - char_start;
+    /// Fold a whole string, e.g. the query.
+    pub fn fold(&self, text: &str) -> String {
+        let mut out = String::with_capacity(text.len());
+        for grapheme in text.graphemes(true) {
+            self.fold_grapheme(grapheme, &mut out);
+        }
+        out
+    }
 
-            if let Some(ref wb) = word_boundaries {
-                if wb.contains(&char_start) && wb.contains(&char_end) {
-                    results.push((char_start, char_len));
+    /// Graphemes are folded one at a time, since composition never
+    /// crosses their boundaries.
+    fn fold_grapheme(&self, grapheme: &str, out: &mut String) {
+        if grapheme.is_ascii() {
+            out.push_str(grapheme);
+            return;
+        }
+        let mut text = grapheme.to_string();
+        if self.fold_width {
+            let mut widened = String::with_capacity(text.len());
+            for c in text.chars() {
+                match c {
+                    '\u{FF01}'..='\u{FF5E}' => {
+                        widened.extend(char::from_u32(c as u32 - 0xFEE0));
+                    }
+                    '\u{3000}' => widened.push(' '),
+                    '\u{FF5F}'..='\u{FFEE}' => decompose_compatible(c, |d| widened.push(d)),
+                    _ => widened.push(c),
                 }
-            } else {
-                results.push((char_start, char_len));
             }
+            // Recompose half-width katakana with their voicing marks.
+            text = widened.nfc().collect();
+        }
+        if self.fold_accents {
+            text = text
+                .nfd()
+                .filter(|&c| !is_diacritic(c))
+                .map(|c| {
+                    STROKED_LETTERS
+                        .iter()
+                        .find(|(stroked, _)| *stroked == c)
+                        .map_or(c, |(_, plain)| *plain)
+                })
+                .nfc()
+                .collect();
         }
-    } else {
-        // Literal search using lowercased Strings instead of Vec<char>.
-        let (search_text, search_query) = if case_sensitive {
-            (full_text.to_string(), query.to_string())
+        match self.normalization {
+            SearchNormalization::NoNormalization => out.push_str(&text),
+            SearchNormalization::Nfc => out.extend(text.nfc()),
+            SearchNormalization::Nfkc => out.extend(text.nfkc()),
+        }
+    }
+}
+
+/// Combining marks from the diacritical mark blocks. Other marks, such
+/// as Indic vowel signs or the kana voicing marks, are part of the
+/// letter rather than an accent on it.
+fn is_diacritic(c: char) -> bool {
+    canonical_combining_class(c) != 0
+        && matches!(
+            c,
+            '\u{0300}'..='\u{036F}'
+                | '\u{1AB0}'..='\u{1AFF}'
+                | '\u{1DC0}'..='\u{1DFF}'
+                | '\u{20D0}'..='\u{20FF}'
+                | '\u{FE20}'..='\u{FE2F}'
+        )
+}
+
+/// A text folded for matching, with the way back to the original: each
+/// folded char maps to the original char range of the grapheme it came
+/// from.
+pub struct FoldedText {
+    text: String,
+    char_bytes: Vec<usize>,
+    starts: Vec<usize>,
+    ends: Vec<usize>,
+    original_len: usize,
+}
+
+impl FoldedText {
+    pub fn new(original: &str, folding: &TextFolding) -> Self {
+        let mut folded = FoldedText {
+            text: String::with_capacity(original.len()),
+            char_bytes: Vec::new(),
+            starts: Vec::new(),
+            ends: Vec::new(),
+            original_len: 0,
+        };
+        let mut position = 0;
+        for grapheme in original.graphemes(true) {
+            let end = position + grapheme.chars().count();
+            let before = folded.text.len();
+            folding.fold_grapheme(grapheme, &mut folded.text);
+            for (offset, _) in folded.text[before..].char_indices() {
+                folded.char_bytes.push(before + offset);
+                folded.starts.push(position);
+                folded.ends.push(end);
+            }
+            position = end;
+        }
+        folded.char_bytes.push(folded.text.len());
+        folded.original_len = position;
+        folded
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    /// The first folded char at or after original char `position`.
+    pub fn folded_position(&self, position: usize) -> usize {
+        self.starts.partition_point(|&s| s < position)
+    }
+
+    /// Map a `(position, length)` char range of the folded text to the
+    /// whole graphemes it covers in the original.
+    pub fn to_original(&self, position: usize, length: usize) -> (usize, usize) {
+        let start = self
+            .starts
+            .get(position)
+            .copied()
+            .unwrap_or(self.original_len);
+        let end = match (position + length).checked_sub(1) {
+            Some(last) if length > 0 => self.ends.get(last).copied().unwrap_or(self.original_len),
+            _ => start,
+        };
+        (start, end - start)
+    }
+
+    /// [`capture_groups`] of the match starting at original char
+    /// `position`, for a regex built by [`MatchOptions::regex`], mapped
+    /// back to the original text.
+    pub fn capture_groups(
+        &self,
+        re: &Regex,
+        position: usize,
+    ) -> Option<Vec<Option<(usize, usize)>>> {
+        let groups = capture_groups(
+            re,
+            &self.text,
+            &self.char_bytes,
+            self.folded_position(position),
+        )?;
+        Some(
+            groups
+                .into_iter()
+                .map(|group| group.map(|(p, l)| self.to_original(p, l)))
+                .collect(),
+        )
+    }
+}
+
+/// What to search for and how to compare it.
+#[derive(Debug, Clone, Default)]
+pub struct MatchOptions<'a> {
+    pub query: &'a str,
+    pub case_sensitive: bool,
+    pub whole_word: bool,
+    pub use_regex: bool,
+    pub folding: TextFolding,
+    /// Fuzzy search: match runs of as many whole words as the query has
+    /// that are at most this many edits (Levenshtein distance) away.
+    pub max_edits: Option<usize>,
+    /// Let regex `^` and `$` match at block boundaries, for matches
+    /// that may span blocks.
+    pub multi_line: bool,
+}
+
+impl MatchOptions<'_> {
+    /// The regex to search with. When folding, it is compiled from the
+    /// folded pattern and matches against the [`FoldedText`].
+    pub fn regex(&self) -> Result<Regex> {
+        if self.folding.is_identity() {
+            build_regex(self.query, self.case_sensitive, self.multi_line)
         } else {
-            (full_text.to_lowercase(), query.to_lowercase())
+            build_regex(
+                &self.folding.fold(self.query),
+                self.case_sensitive,
+                self.multi_line,
+            )
+        }
+    }
+}
+
+/// `(char_position, char_length)` of each capture group of the regex
+/// match starting at char `position` of `text`, group 0 first, `None`
+/// for a group that took no part in the match. `char_bytes` is the
+/// [`build_char_to_byte_map`] of `text`. `None` when no match starts
+/// there.
+pub fn capture_groups(
+    re: &Regex,
+    text: &str,
+    char_bytes: &[usize],
+    position: usize,
+) -> Option<Vec<Option<(usize, usize)>>> {
+    let byte_start = *char_bytes.get(position)?;
+    let caps = re.captures_at(text, byte_start)?;
+    if caps.get(0)?.start() != byte_start {
+        return None;
+    }
+    let to_char = |b: usize| char_bytes.partition_point(|&c| c < b);
+    Some(
+        caps.iter()
+            .map(|group| {
+                group.map(|m| {
+                    let start = to_char(m.start());
+                    (start, to_char(m.end()) - start)
+                })
+            })
+            .collect(),
+    )
+}
+
+/// Expand `$1`, `${name}` and `$$` in `template` like
+/// [`regex::Captures::expand`], taking each group's text from `text`
+/// at the char ranges of `groups` (as returned by [`capture_groups`]).
+/// Used when the regex ran over a [`FoldedText`], so the replacement
+/// gets the document's own spelling of the captured text.
+pub fn expand_groups(
+    re: &Regex,
+    text: &str,
+    char_bytes: &[usize],
+    groups: &[Option<(usize, usize)>],
+    template: &str,
+) -> String {
+    let group_text = |index: Option<usize>| -> &str {
+        match index.and_then(|i| groups.get(i).copied().flatten()) {
+            Some((start, length)) => &text[char_bytes[start]..char_bytes[start + length]],
+            None => "",
+        }
+    };
+    let mut out = String::new();
+    let mut rest = template;
+    while let Some(dollar) = rest.find('$') {
+        out.push_str(&rest[..dollar]);
+        rest = &rest[dollar + 1..];
+        if let Some(after) = rest.strip_prefix('$') {
+            out.push('$');
+            rest = after;
+            continue;
+        }
+        let (name, after) = match rest.strip_prefix('{') {
+            Some(braced) => match braced.find('}') {
+                Some(close) => (&braced[..close], &braced[close + 1..]),
+                None => {
+                    out.push('$');
+                    continue;
+                }
+            },
+            None => {
+                let end = rest
+                    .find(|c: char| !(c == '_' || c.is_ascii_alphanumeric()))
+                    .unwrap_or(rest.len());
+                (&rest[..end], &rest[end..])
+            }
+        };
+        if name.is_empty() {
+            out.push('$');
+            continue;
+        }
+        let index = match name.parse::<usize>() {
+            Ok(number) => Some(number),
+            Err(_) => re.capture_names().position(|n| n == Some(name)),
         };
+        out.push_str(group_text(index));
+        rest = after;
+    }
+    out.push_str(rest);
+    out
+}
 
-        // Build a char-index → byte-offset mapping for the search text
-        let char_indices: Vec<usize> = search_text.char_indices().map(|(i, _)| i).collect();
-        let query_char_len = search_query.chars().count();
+/// Find all occurrences of the query in the text, respecting search options.
+/// All positions are in char indices (not byte offsets).
+/// Returns a vec of `(char_position, char_length)` for each match.
+pub fn find_all_matches(full_text: &str, options: &MatchOptions) -> Result<Vec<(usize, usize)>> {
+    find_matches_paged(full_text, options, 0, None, |_, _| true)
+}
 
-        if query_char_len == 0 || char_indices.len() < query_char_len {
-            return Ok(results);
+/// Like [`find_all_matches`], but only keeps the matches starting at or
+/// after char position `from` that `accept` lets through, and stops
+/// scanning once `limit` of them are found. A regex is still matched
+/// from the start of the text, so a page holds the same matches as the
+/// corresponding slice of the full result.
+///
+/// With [`TextFolding`] the search runs over the folded text and query;
+/// matches are widened to whole graphemes of `full_text`.
+pub fn find_matches_paged(
+    full_text: &str,
+    options: &MatchOptions,
+    from: usize,
+    limit: Option<usize>,
+    accept: impl Fn(usize, usize) -> bool,
+) -> Result<Vec<(usize, usize)>> {
+    let mut scanner = MatchScanner::new(full_text, options, from)?;
+    Ok(scanner
+        .next_page(limit, &accept)
+        .into_iter()
+        .map(|(pos, len, _)| (pos, len))
+        .collect())
+}
+
+/// Fuzzy counterpart of [`find_matches_paged`] for `options.max_edits`,
+/// returning `(char_position, char_length, distance)` in document
+/// order. A candidate is a run of consecutive words within one block,
+/// compared to the query with the text between the words included, so
+/// "helo, wrld" is two edits from "hello world".
+pub fn find_fuzzy_matches(
+    full_text: &str,
+    options: &MatchOptions,
+    from: usize,
+    limit: Option<usize>,
+    accept: impl Fn(usize, usize) -> bool,
+) -> Result<Vec<(usize, usize, usize)>> {
+    let mut scanner = MatchScanner::new(full_text, options, from)?;
+    Ok(scanner.next_page(limit, &accept))
+}
+
+/// A search through one text that hands out its matches as they are
+/// asked for. The text it scans (folded, lowercased), its char maps and
+/// word boundaries are built once, and each call carries on where the
+/// previous one stopped, so a caller paging through a large document
+/// keeps one scanner instead of searching again for every page.
+pub struct MatchScanner {
+    /// Set when the scan runs over a folded text, to map its matches
+    /// back to the original one.
+    folded: Option<FoldedText>,
+    /// Matches starting before this char of the scanned text are skipped.
+    from: usize,
+    scan: Scan,
+}
+
+enum Scan {
+    Finished,
+    Literal {
+        text: String,
+        query: String,
+        query_chars: usize,
+        /// Byte offset of every char of `text`.
+        char_bytes: Vec<usize>,
+        word_boundaries: Option<HashSet<usize>>,
+        next: usize,
+    },
+    Regex {
+        re: Regex,
+        text: String,
+        byte_to_char: Vec<usize>,
+        word_boundaries: Option<HashSet<usize>>,
+        next_byte: usize,
+        /// End of the previous match, where no empty match is taken
+        /// again (as `Regex::find_iter` does).
+        last_end: Option<usize>,
+    },
+    Fuzzy {
+        query: Vec<char>,
+        case_sensitive: bool,
+        max_edits: usize,
+        word_count: usize,
+        text: String,
+        byte_to_char: Vec<usize>,
+        /// Byte ranges of the words of `text`.
+        words: Vec<(usize, usize)>,
+        next: usize,
+    },
+}
+
+impl MatchScanner {
+    /// Prepare a search for `options` over `full_text`, returning only
+    /// the matches starting at or after char position `from`.
+    pub fn new(full_text: &str, options: &MatchOptions, from: usize) -> Result<Self> {
+        if options.max_edits.is_some() && options.use_regex {
+            bail!("Fuzzy search does not support regular expressions");
         }
+        let (folded, query) = if options.folding.is_identity() {
+            (None, options.query.to_string())
+        } else {
+            (
+                Some(FoldedText::new(full_text, &options.folding)),
+                options.folding.fold(options.query),
+            )
+        };
+        let (text, from) = match &folded {
+            Some(folded) => (folded.as_str(), folded.folded_position(from)),
+            None => (full_text, from),
+        };
+        let word_boundaries = || options.whole_word.then(|| build_word_boundary_set(text));
 
-        let mut char_pos = 0;
-        while char_pos + query_char_len <= char_indices.len() {
-            let byte_start = char_indices[char_pos];
-            let byte_end = if char_pos + query_char_len < char_indices.len() {
-                char_indices[char_pos + query_char_len]
+        let scan = if let Some(max_edits) = options.max_edits {
+            let comparable = comparable_chars(options.case_sensitive);
+            let word_count = query.unicode_words().count();
+            if word_count == 0 {
+                Scan::Finished
             } else {
-                search_text.len()
+                Scan::Fuzzy {
+                    query: comparable(&query),
+                    case_sensitive: options.case_sensitive,
+                    max_edits,
+                    word_count,
+                    text: text.to_string(),
+                    byte_to_char: build_byte_to_char_map(text),
+                    words: text
+                        .unicode_word_indices()
+                        .map(|(start, word)| (start, start + word.len()))
+                        .collect(),
+                    next: 0,
+                }
+            }
+        } else if query.is_empty() {
+            Scan::Finished
+        } else if options.use_regex {
+            Scan::Regex {
+                re: build_regex(&query, options.case_sensitive, options.multi_line)?,
+                text: text.to_string(),
+                byte_to_char: build_byte_to_char_map(text),
+                word_boundaries: word_boundaries(),
+                next_byte: 0,
+                last_end: None,
+            }
+        } else {
+            // Literal search using lowercased Strings instead of Vec<char>.
+            let (search_text, search_query) = if options.case_sensitive {
+                (text.to_string(), query)
+            } else {
+                (text.to_lowercase(), query.to_lowercase())
             };
+            let mut char_bytes: Vec<usize> = search_text.char_indices().map(|(i, _)| i).collect();
+            let query_chars = search_query.chars().count();
+            if query_chars == 0 || char_bytes.len() < query_chars {
+                Scan::Finished
+            } else {
+                char_bytes.push(search_text.len());
+                Scan::Literal {
+                    text: search_text,
+                    query: search_query,
+                    query_chars,
+                    char_bytes,
+                    word_boundaries: word_boundaries(),
+                    next: from,
+                }
+            }
+        };
+        Ok(MatchScanner { folded, from, scan })
+    }
 
-            if search_text[byte_start..byte_end] == search_query[..] {
-                if let Some(ref wb) = word_boundaries {
-                    if wb.contains(&char_pos) && wb.contains(&(char_pos + query_char_len)) {
-                        results.push((char_pos, query_char_len));
+    /// The next match `accept` lets through, as `(char_position,
+    /// char_length, distance)` in the original text; the distance is 0
+    /// outside a fuzzy search. `None` once the text is exhausted.
+    pub fn next_match(
+        &mut self,
+        accept: &dyn Fn(usize, usize) -> bool,
+    ) -> Option<(usize, usize, usize)> {
+        while let Some((pos, len, distance)) = self.scan.next_candidate() {
+            if pos < self.from {
+                continue;
+            }
+            let (pos, len) = match &self.folded {
+                Some(folded) => folded.to_original(pos, len),
+                None => (pos, len),
+            };
+            if accept(pos, len) {
+                return Some((pos, len, distance));
+            }
+        }
+        None
+    }
+
+    /// Up to `limit` more matches, in document order.
+    pub fn next_page(
+        &mut self,
+        limit: Option<usize>,
+        accept: &dyn Fn(usize, usize) -> bool,
+    ) -> Vec<(usize, usize, usize)> {
+        let mut results = Vec::new();
+        while limit.is_none_or(|l| results.len() < l) {
+            let Some(found) = self.next_match(accept) else {
+                break;
+            };
+            results.push(found);
+        }
+        results
+    }
+}
+
+impl Scan {
+    /// The next match in scanned-text chars, before scope and `from`
+    /// are applied.
+    fn next_candidate(&mut self) -> Option<(usize, usize, usize)> {
+        let is_whole_word = |wb: &Option<HashSet<usize>>, start: usize, end: usize| {
+            wb.as_ref()
+                .is_none_or(|wb| wb.contains(&start) && wb.contains(&end))
+        };
+        let found = match self {
+            Scan::Finished => None,
+            Scan::Literal {
+                text,
+                query,
+                query_chars,
+                char_bytes,
+                word_boundaries,
+                next,
+            } => {
+                let mut found = None;
+                let last_start = char_bytes.len() - 1;
+                while *next + *query_chars <= last_start {
+                    let char_pos = *next;
+                    *next += 1;
+                    let candidate =
+                        &text[char_bytes[char_pos]..char_bytes[char_pos + *query_chars]];
+                    if candidate == query.as_str()
+                        && is_whole_word(word_boundaries, char_pos, char_pos + *query_chars)
+                    {
+                        found = Some((char_pos, *query_chars, 0));
+                        break;
+                    }
+                }
+                found
+            }
+            Scan::Regex {
+                re,
+                text,
+                byte_to_char,
+                word_boundaries,
+                next_byte,
+                last_end,
+            } => {
+                let mut found = None;
+                while *next_byte <= text.len() {
+                    let Some(mat) = re.find_at(text, *next_byte) else {
+                        break;
+                    };
+                    if mat.is_empty() && *last_end == Some(mat.end()) {
+                        // Step over the char an empty match sits before.
+                        *next_byte = text[mat.end()..]
+                            .chars()
+                            .next()
+                            .map_or(text.len() + 1, |c| mat.end() + c.len_utf8());
+                        continue;
+                    }
+                    *next_byte = mat.end();
+                    *last_end = Some(mat.end());
+                    let char_start = byte_to_char[mat.start()];
+                    let char_end = byte_to_char[mat.end()];
+                    if is_whole_word(word_boundaries, char_start, char_end) {
+                        found = Some((char_start, char_end - char_start, 0));
+                        break;
+                    }
+                }
+                found
+            }
+            Scan::Fuzzy {
+                query,
+                case_sensitive,
+                max_edits,
+                word_count,
+                text,
+                byte_to_char,
+                words,
+                next,
+            } => {
+                let comparable = comparable_chars(*case_sensitive);
+                let mut found = None;
+                while *next + *word_count <= words.len() {
+                    let window = &words[*next..*next + *word_count];
+                    *next += 1;
+                    let (byte_start, byte_end) = (window[0].0, window[*word_count - 1].1);
+                    let candidate = &text[byte_start..byte_end];
+                    if candidate.contains('\n') {
+                        continue;
                     }
-                } else {
-                    results.push((char_pos, query_char_len));
+                    let Some(distance) =
+                        bounded_levenshtein(&comparable(candidate), query, *max_edits)
+                    else {
+                        continue;
+                    };
+                    let pos = byte_to_char[byte_start];
+                    found = Some((pos, byte_to_char[byte_end] - pos, distance));
+                    break;
                 }
+                found
             }
-            char_pos += 1;
+        };
+        if found.is_none() {
+            *self = Scan::Finished;
         }
+        found
     }
+}
 
-    Ok(results)
+/// The chars fuzzy matching compares, lowercased unless the search is
+/// case sensitive.
+fn comparable_chars(case_sensitive: bool) -> impl Fn(&str) -> Vec<char> {
+    move |s: &str| {
+        if case_sensitive {
+            s.chars().collect()
+        } else {
+            s.to_lowercase().chars().collect()
+        }
+    }
+}
+
+/// Levenshtein distance between `a` and `b`, or `None` when it exceeds
+/// `max`. Stops as soon as a whole row of the table is over the bound.
+fn bounded_levenshtein(a: &[char], b: &[char], max: usize) -> Option<usize> {
+    if a.len().abs_diff(b.len()) > max {
+        return None;
+    }
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for (i, &ca) in a.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        if current.iter().all(|&d| d > max) {
+            return None;
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    let distance = previous[b.len()];
+    (distance <= max).then_some(distance)
 }
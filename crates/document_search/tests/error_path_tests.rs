@@ -4,7 +4,7 @@ use anyhow::Result;
 use test_harness::setup_with_text;
 
 use document_search::document_search_controller;
-use document_search::{FindAllDto, FindTextDto, ReplaceTextDto};
+use document_search::{FindAllDto, FindTextDto, ReplaceTextDto, SearchNormalization};
 
 // ═══════════════════════════════════════════════════════════════════
 // Find on empty document
@@ -24,6 +24,16 @@ fn test_find_text_in_empty_document() -> Result<()> {
             use_regex: false,
             search_backward: false,
             start_position: 0,
+            scope_start: None,
+            scope_end: None,
+            scope_frame_id: None,
+            scope_table_id: None,
+            normalization: SearchNormalization::NoNormalization,
+            fold_accents: false,
+            fold_width: false,
+            max_edits: None,
+            with_details: false,
+            context_chars: 0,
         },
     )?;
 
@@ -44,6 +54,18 @@ fn test_find_all_in_empty_document() -> Result<()> {
             case_sensitive: true,
             whole_word: false,
             use_regex: false,
+            scope_start: None,
+            scope_end: None,
+            scope_frame_id: None,
+            scope_table_id: None,
+            normalization: SearchNormalization::NoNormalization,
+            fold_accents: false,
+            fold_width: false,
+            max_edits: None,
+            start_position: None,
+            max_results: None,
+            with_details: false,
+            context_chars: 0,
         },
     )?;
 
@@ -71,6 +93,16 @@ fn test_find_empty_query() -> Result<()> {
             use_regex: false,
             search_backward: false,
             start_position: 0,
+            scope_start: None,
+            scope_end: None,
+            scope_frame_id: None,
+            scope_table_id: None,
+            normalization: SearchNormalization::NoNormalization,
+            fold_accents: false,
+            fold_width: false,
+            max_edits: None,
+            with_details: false,
+            context_chars: 0,
         },
     )?;
 
@@ -99,6 +131,16 @@ fn test_find_invalid_regex() -> Result<()> {
             use_regex: true,
             search_backward: false,
             start_position: 0,
+            scope_start: None,
+            scope_end: None,
+            scope_frame_id: None,
+            scope_table_id: None,
+            normalization: SearchNormalization::NoNormalization,
+            fold_accents: false,
+            fold_width: false,
+            max_edits: None,
+            with_details: false,
+            context_chars: 0,
         },
     );
 
@@ -129,6 +171,16 @@ fn test_find_start_position_beyond_document() -> Result<()> {
             use_regex: false,
             search_backward: false,
             start_position: 999,
+            scope_start: None,
+            scope_end: None,
+            scope_frame_id: None,
+            scope_table_id: None,
+            normalization: SearchNormalization::NoNormalization,
+            fold_accents: false,
+            fold_width: false,
+            max_edits: None,
+            with_details: false,
+            context_chars: 0,
         },
     )?;
 
@@ -158,6 +210,15 @@ fn test_replace_no_matches() -> Result<()> {
             whole_word: false,
             use_regex: false,
             replace_all: true,
+            preserve_case: false,
+            cross_block: false,
+            scope_start: None,
+            scope_end: None,
+            scope_frame_id: None,
+            scope_table_id: None,
+            normalization: SearchNormalization::NoNormalization,
+            fold_accents: false,
+            fold_width: false,
         },
     )?;
 
@@ -182,6 +243,15 @@ fn test_replace_with_empty_string() -> Result<()> {
             whole_word: false,
             use_regex: false,
             replace_all: false,
+            preserve_case: false,
+            cross_block: false,
+            scope_start: None,
+            scope_end: None,
+            scope_frame_id: None,
+            scope_table_id: None,
+            normalization: SearchNormalization::NoNormalization,
+            fold_accents: false,
+            fold_width: false,
         },
     )?;
 
@@ -206,6 +276,15 @@ fn test_replace_in_empty_document() -> Result<()> {
             whole_word: false,
             use_regex: false,
             replace_all: true,
+            preserve_case: false,
+            cross_block: false,
+            scope_start: None,
+            scope_end: None,
+            scope_frame_id: None,
+            scope_table_id: None,
+            normalization: SearchNormalization::NoNormalization,
+            fold_accents: false,
+            fold_width: false,
         },
     )?;
 
@@ -0,0 +1,183 @@
+extern crate text_document_search as document_search;
+use anyhow::Result;
+
+use common::format_runs::{CharacterFormat, FormatRun};
+use test_harness::{block_controller, get_block_ids, setup_with_text};
+
+use document_search::FindByFormatDto;
+use document_search::document_search_controller;
+
+fn set_runs(
+    db_context: &common::database::db_context::DbContext,
+    block_id: u64,
+    runs: Vec<FormatRun>,
+) {
+    db_context
+        .get_store()
+        .format_runs
+        .write()
+        .unwrap()
+        .insert(block_id, runs);
+}
+
+fn run(byte_start: u32, byte_end: u32, format: CharacterFormat) -> FormatRun {
+    FormatRun {
+        byte_start,
+        byte_end,
+        format,
+    }
+}
+
+#[test]
+fn test_find_by_format_bold_runs() -> Result<()> {
+    let (db_context, event_hub, _undo_redo_manager) = setup_with_text("plain bold both\nnone")?;
+    let block_ids = get_block_ids(&db_context)?;
+    let bold = CharacterFormat {
+        font_bold: Some(true),
+        ..Default::default()
+    };
+    let bold_italic = CharacterFormat {
+        font_italic: Some(true),
+        ..bold.clone()
+    };
+    set_runs(
+        &db_context,
+        block_ids[0],
+        vec![run(6, 11, bold), run(11, 15, bold_italic)],
+    );
+
+    let find = |dto: &FindByFormatDto| {
+        document_search_controller::find_by_format(&db_context, &event_hub, dto)
+    };
+
+    // Adjacent runs that both match form one range.
+    let result = find(&FindByFormatDto {
+        font_bold: Some(true),
+        ..Default::default()
+    })?;
+    assert_eq!(result.positions, vec![6]);
+    assert_eq!(result.lengths, vec![9]);
+
+    let result = find(&FindByFormatDto {
+        font_bold: Some(true),
+        font_italic: Some(false),
+        ..Default::default()
+    })?;
+    assert_eq!((result.positions, result.lengths), (vec![6], vec![5]));
+
+    // Unformatted text counts as not bold.
+    let result = find(&FindByFormatDto {
+        font_bold: Some(false),
+        ..Default::default()
+    })?;
+    assert_eq!(result.positions, vec![0, 16]);
+    assert_eq!(result.lengths, vec![6, 4]);
+
+    Ok(())
+}
+
+#[test]
+fn test_find_by_format_links_and_colors() -> Result<()> {
+    let (db_context, event_hub, _undo_redo_manager) = setup_with_text("one two three")?;
+    let block_ids = get_block_ids(&db_context)?;
+    set_runs(
+        &db_context,
+        block_ids[0],
+        vec![
+            run(
+                0,
+                3,
+                CharacterFormat {
+                    anchor_href: Some("https://example.com/a".into()),
+                    ..Default::default()
+                },
+            ),
+            run(
+                4,
+                7,
+                CharacterFormat {
+                    anchor_href: Some("https://other.org".into()),
+                    foreground_color: Some("#ff0000".into()),
+                    ..Default::default()
+                },
+            ),
+        ],
+    );
+
+    let result = document_search_controller::find_by_format(
+        &db_context,
+        &event_hub,
+        &FindByFormatDto {
+            anchor_href: Some("example.com".into()),
+            ..Default::default()
+        },
+    )?;
+    assert_eq!((result.positions, result.lengths), (vec![0], vec![3]));
+
+    let result = document_search_controller::find_by_format(
+        &db_context,
+        &event_hub,
+        &FindByFormatDto {
+            foreground_color: Some("red".into()),
+            ..Default::default()
+        },
+    )?;
+    assert_eq!((result.positions, result.lengths), (vec![4], vec![3]));
+
+    Ok(())
+}
+
+#[test]
+fn test_find_by_format_heading_blocks() -> Result<()> {
+    let (db_context, event_hub, mut undo_redo_manager) = setup_with_text("Title\nbody\nSection\n")?;
+    let block_ids = get_block_ids(&db_context)?;
+    for (id, level) in [(block_ids[0], 1), (block_ids[2], 2), (block_ids[3], 2)] {
+        let mut block = block_controller::get(&db_context, &id)?.unwrap();
+        block.fmt_heading_level = Some(level);
+        block_controller::update(
+            &db_context,
+            &event_hub,
+            &mut undo_redo_manager,
+            None,
+            &block.into(),
+        )?;
+    }
+
+    // Empty blocks have nothing to match.
+    let result = document_search_controller::find_by_format(
+        &db_context,
+        &event_hub,
+        &FindByFormatDto {
+            heading_level: Some(2),
+            ..Default::default()
+        },
+    )?;
+    assert_eq!((result.positions, result.lengths), (vec![11], vec![7]));
+
+    let result = document_search_controller::find_by_format(
+        &db_context,
+        &event_hub,
+        &FindByFormatDto {
+            heading_level: Some(0),
+            scope_start: Some(6),
+            ..Default::default()
+        },
+    )?;
+    assert_eq!((result.positions, result.lengths), (vec![6], vec![4]));
+
+    Ok(())
+}
+
+#[test]
+fn test_find_by_format_without_conditions_fails() -> Result<()> {
+    let (db_context, event_hub, _undo_redo_manager) = setup_with_text("text")?;
+
+    let result = document_search_controller::find_by_format(
+        &db_context,
+        &event_hub,
+        &FindByFormatDto::default(),
+    );
+    assert!(result.is_err());
+
+    Ok(())
+}
@@ -1,10 +1,10 @@
 extern crate text_document_search as document_search;
 use anyhow::Result;
 
-use test_harness::{export_text, setup_with_text};
+use test_harness::{export_text, get_block_ids, get_document_stats, setup_with_text};
 
-use document_search::ReplaceTextDto;
 use document_search::document_search_controller;
+use document_search::{ReplaceTextDto, SearchNormalization};
 
 #[test]
 fn test_replace_single() -> Result<()> {
@@ -22,6 +22,15 @@ fn test_replace_single() -> Result<()> {
             whole_word: false,
             use_regex: false,
             replace_all: false,
+            preserve_case: false,
+            cross_block: false,
+            scope_start: None,
+            scope_end: None,
+            scope_frame_id: None,
+            scope_table_id: None,
+            normalization: SearchNormalization::NoNormalization,
+            fold_accents: false,
+            fold_width: false,
         },
     )?;
 
@@ -48,6 +57,15 @@ fn test_replace_all() -> Result<()> {
             whole_word: false,
             use_regex: false,
             replace_all: true,
+            preserve_case: false,
+            cross_block: false,
+            scope_start: None,
+            scope_end: None,
+            scope_frame_id: None,
+            scope_table_id: None,
+            normalization: SearchNormalization::NoNormalization,
+            fold_accents: false,
+            fold_width: false,
         },
     )?;
 
@@ -74,6 +92,15 @@ fn test_replace_case_insensitive() -> Result<()> {
             whole_word: false,
             use_regex: false,
             replace_all: true,
+            preserve_case: false,
+            cross_block: false,
+            scope_start: None,
+            scope_end: None,
+            scope_frame_id: None,
+            scope_table_id: None,
+            normalization: SearchNormalization::NoNormalization,
+            fold_accents: false,
+            fold_width: false,
         },
     )?;
 
@@ -100,6 +127,15 @@ fn test_replace_regex() -> Result<()> {
             whole_word: false,
             use_regex: true,
             replace_all: true,
+            preserve_case: false,
+            cross_block: false,
+            scope_start: None,
+            scope_end: None,
+            scope_frame_id: None,
+            scope_table_id: None,
+            normalization: SearchNormalization::NoNormalization,
+            fold_accents: false,
+            fold_width: false,
         },
     )?;
 
@@ -126,6 +162,15 @@ fn test_replace_not_found() -> Result<()> {
             whole_word: false,
             use_regex: false,
             replace_all: true,
+            preserve_case: false,
+            cross_block: false,
+            scope_start: None,
+            scope_end: None,
+            scope_frame_id: None,
+            scope_table_id: None,
+            normalization: SearchNormalization::NoNormalization,
+            fold_accents: false,
+            fold_width: false,
         },
     )?;
 
@@ -152,6 +197,15 @@ fn test_replace_empty_query() -> Result<()> {
             whole_word: false,
             use_regex: false,
             replace_all: true,
+            preserve_case: false,
+            cross_block: false,
+            scope_start: None,
+            scope_end: None,
+            scope_frame_id: None,
+            scope_table_id: None,
+            normalization: SearchNormalization::NoNormalization,
+            fold_accents: false,
+            fold_width: false,
         },
     )?;
 
@@ -178,6 +232,15 @@ fn test_replace_undo() -> Result<()> {
             whole_word: false,
             use_regex: false,
             replace_all: true,
+            preserve_case: false,
+            cross_block: false,
+            scope_start: None,
+            scope_end: None,
+            scope_frame_id: None,
+            scope_table_id: None,
+            normalization: SearchNormalization::NoNormalization,
+            fold_accents: false,
+            fold_width: false,
         },
     )?;
 
@@ -210,6 +273,15 @@ fn test_replace_all_across_blocks() -> Result<()> {
             whole_word: false,
             use_regex: false,
             replace_all: true,
+            preserve_case: false,
+            cross_block: false,
+            scope_start: None,
+            scope_end: None,
+            scope_frame_id: None,
+            scope_table_id: None,
+            normalization: SearchNormalization::NoNormalization,
+            fold_accents: false,
+            fold_width: false,
         },
     )?;
 
@@ -236,6 +308,15 @@ fn test_replace_redo() -> Result<()> {
             whole_word: false,
             use_regex: false,
             replace_all: true,
+            preserve_case: false,
+            cross_block: false,
+            scope_start: None,
+            scope_end: None,
+            scope_frame_id: None,
+            scope_table_id: None,
+            normalization: SearchNormalization::NoNormalization,
+            fold_accents: false,
+            fold_width: false,
         },
     )?;
 
@@ -266,6 +347,15 @@ fn test_replace_with_empty_deletes() -> Result<()> {
             whole_word: false,
             use_regex: false,
             replace_all: true,
+            preserve_case: false,
+            cross_block: false,
+            scope_start: None,
+            scope_end: None,
+            scope_frame_id: None,
+            scope_table_id: None,
+            normalization: SearchNormalization::NoNormalization,
+            fold_accents: false,
+            fold_width: false,
         },
     )?;
 
@@ -291,6 +381,15 @@ fn test_replace_with_longer_text() -> Result<()> {
             whole_word: false,
             use_regex: false,
             replace_all: true,
+            preserve_case: false,
+            cross_block: false,
+            scope_start: None,
+            scope_end: None,
+            scope_frame_id: None,
+            scope_table_id: None,
+            normalization: SearchNormalization::NoNormalization,
+            fold_accents: false,
+            fold_width: false,
         },
     )?;
 
@@ -299,3 +398,371 @@ fn test_replace_with_longer_text() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_replace_regex_expands_capture_groups() -> Result<()> {
+    let (db_context, event_hub, mut undo_redo_manager) =
+        setup_with_text("Doe, John\nRoe, Jane\ncost $5")?;
+
+    let result = document_search_controller::replace_text(
+        &db_context,
+        &event_hub,
+        &mut undo_redo_manager,
+        None,
+        &ReplaceTextDto {
+            query: r"(\w+), (?<first>\w+)".to_string(),
+            replacement: "${first} $1".to_string(),
+            case_sensitive: true,
+            whole_word: false,
+            use_regex: true,
+            replace_all: true,
+            preserve_case: false,
+            cross_block: false,
+            scope_start: None,
+            scope_end: None,
+            scope_frame_id: None,
+            scope_table_id: None,
+            normalization: SearchNormalization::NoNormalization,
+            fold_accents: false,
+            fold_width: false,
+        },
+    )?;
+    assert_eq!(result.replacements_count, 2);
+
+    document_search_controller::replace_text(
+        &db_context,
+        &event_hub,
+        &mut undo_redo_manager,
+        None,
+        &ReplaceTextDto {
+            query: r"\$(\d)".to_string(),
+            replacement: "$$${1}0".to_string(),
+            case_sensitive: true,
+            whole_word: false,
+            use_regex: true,
+            replace_all: true,
+            preserve_case: false,
+            cross_block: false,
+            scope_start: None,
+            scope_end: None,
+            scope_frame_id: None,
+            scope_table_id: None,
+            normalization: SearchNormalization::NoNormalization,
+            fold_accents: false,
+            fold_width: false,
+        },
+    )?;
+    assert_eq!(
+        export_text(&db_context, &event_hub)?,
+        "John Doe\nJane Roe\ncost $50"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_replace_literal_keeps_dollar_signs() -> Result<()> {
+    let (db_context, event_hub, mut undo_redo_manager) = setup_with_text("price")?;
+
+    document_search_controller::replace_text(
+        &db_context,
+        &event_hub,
+        &mut undo_redo_manager,
+        None,
+        &ReplaceTextDto {
+            query: "price".to_string(),
+            replacement: "$1".to_string(),
+            case_sensitive: true,
+            whole_word: false,
+            use_regex: false,
+            replace_all: true,
+            preserve_case: false,
+            cross_block: false,
+            scope_start: None,
+            scope_end: None,
+            scope_frame_id: None,
+            scope_table_id: None,
+            normalization: SearchNormalization::NoNormalization,
+            fold_accents: false,
+            fold_width: false,
+        },
+    )?;
+    assert_eq!(export_text(&db_context, &event_hub)?, "$1");
+
+    Ok(())
+}
+
+#[test]
+fn test_replace_preserve_case() -> Result<()> {
+    let (db_context, event_hub, mut undo_redo_manager) = setup_with_text("foo Foo FOO fOO 42")?;
+
+    let result = document_search_controller::replace_text(
+        &db_context,
+        &event_hub,
+        &mut undo_redo_manager,
+        None,
+        &ReplaceTextDto {
+            query: "foo".to_string(),
+            replacement: "bar".to_string(),
+            case_sensitive: false,
+            whole_word: false,
+            use_regex: false,
+            replace_all: true,
+            preserve_case: true,
+            cross_block: false,
+            scope_start: None,
+            scope_end: None,
+            scope_frame_id: None,
+            scope_table_id: None,
+            normalization: SearchNormalization::NoNormalization,
+            fold_accents: false,
+            fold_width: false,
+        },
+    )?;
+    assert_eq!(result.replacements_count, 4);
+    assert_eq!(export_text(&db_context, &event_hub)?, "bar Bar BAR bar 42");
+
+    Ok(())
+}
+
+#[test]
+fn test_replace_preserve_case_word_by_word() -> Result<()> {
+    let (db_context, event_hub, mut undo_redo_manager) =
+        setup_with_text("Hello World. HELLO world. hello WORLD.")?;
+
+    let replace = |undo_redo_manager: &mut _, query: &str, replacement: &str| {
+        document_search_controller::replace_text(
+            &db_context,
+            &event_hub,
+            undo_redo_manager,
+            None,
+            &ReplaceTextDto {
+                query: query.to_string(),
+                replacement: replacement.to_string(),
+                case_sensitive: false,
+                whole_word: false,
+                use_regex: false,
+                replace_all: true,
+                preserve_case: true,
+                cross_block: false,
+                scope_start: None,
+                scope_end: None,
+                scope_frame_id: None,
+                scope_table_id: None,
+                normalization: SearchNormalization::NoNormalization,
+                fold_accents: false,
+                fold_width: false,
+            },
+        )
+    };
+
+    let result = replace(&mut undo_redo_manager, "hello world", "goodbye moon")?;
+    assert_eq!(result.replacements_count, 3);
+    assert_eq!(
+        export_text(&db_context, &event_hub)?,
+        "Goodbye Moon. GOODBYE moon. goodbye MOON."
+    );
+
+    // Extra replacement words take the case of the last matched word
+    replace(&mut undo_redo_manager, "moon", "new moon rising")?;
+    assert_eq!(
+        export_text(&db_context, &event_hub)?,
+        "Goodbye New Moon Rising. GOODBYE new moon rising. goodbye NEW MOON RISING."
+    );
+
+    Ok(())
+}
+
+fn cross_block_dto(query: &str, replacement: &str, use_regex: bool) -> ReplaceTextDto {
+    ReplaceTextDto {
+        query: query.to_string(),
+        replacement: replacement.to_string(),
+        case_sensitive: true,
+        whole_word: false,
+        use_regex,
+        replace_all: true,
+        preserve_case: false,
+        cross_block: true,
+        scope_start: None,
+        scope_end: None,
+        scope_frame_id: None,
+        scope_table_id: None,
+        normalization: SearchNormalization::NoNormalization,
+        fold_accents: false,
+        fold_width: false,
+    }
+}
+
+#[test]
+fn test_replace_cross_block_skipped_by_default() -> Result<()> {
+    let (db_context, event_hub, mut undo_redo_manager) = setup_with_text("foo\n\nbar")?;
+
+    let result = document_search_controller::replace_text(
+        &db_context,
+        &event_hub,
+        &mut undo_redo_manager,
+        None,
+        &ReplaceTextDto {
+            cross_block: false,
+            ..cross_block_dto("foo\\n\\nbar", "x", true)
+        },
+    )?;
+    assert_eq!(result.replacements_count, 0);
+    assert_eq!(result.skipped_cross_block, 1);
+    assert_eq!(export_text(&db_context, &event_hub)?, "foo\n\nbar");
+
+    Ok(())
+}
+
+#[test]
+fn test_replace_cross_block_merges_blocks() -> Result<()> {
+    let (db_context, event_hub, mut undo_redo_manager) =
+        setup_with_text("one foo\n\nbar two\nfoo\n\nbar")?;
+
+    let result = document_search_controller::replace_text(
+        &db_context,
+        &event_hub,
+        &mut undo_redo_manager,
+        None,
+        &cross_block_dto("foo\\n\\nbar", "X", true),
+    )?;
+    assert_eq!(result.replacements_count, 2);
+    assert_eq!(result.skipped_cross_block, 0);
+    assert_eq!(export_text(&db_context, &event_hub)?, "one X two\nX");
+    assert_eq!(get_block_ids(&db_context)?.len(), 2);
+    let stats = get_document_stats(&db_context)?;
+    assert_eq!(stats.block_count, 2);
+    assert_eq!(stats.character_count, 10);
+
+    undo_redo_manager.undo(None)?;
+    assert_eq!(
+        export_text(&db_context, &event_hub)?,
+        "one foo\n\nbar two\nfoo\n\nbar"
+    );
+    assert_eq!(get_block_ids(&db_context)?.len(), 6);
+
+    undo_redo_manager.redo(None)?;
+    assert_eq!(export_text(&db_context, &event_hub)?, "one X two\nX");
+
+    Ok(())
+}
+
+#[test]
+fn test_replace_cross_block_splits_blocks() -> Result<()> {
+    let (db_context, event_hub, mut undo_redo_manager) = setup_with_text("a, b\nc, d")?;
+
+    let result = document_search_controller::replace_text(
+        &db_context,
+        &event_hub,
+        &mut undo_redo_manager,
+        None,
+        &cross_block_dto(", ", "\n", false),
+    )?;
+    assert_eq!(result.replacements_count, 2);
+    assert_eq!(export_text(&db_context, &event_hub)?, "a\nb\nc\nd");
+    let stats = get_document_stats(&db_context)?;
+    assert_eq!(stats.block_count, 4);
+    assert_eq!(stats.character_count, 4);
+
+    // A multi-line literal joined back into one paragraph.
+    let result = document_search_controller::replace_text(
+        &db_context,
+        &event_hub,
+        &mut undo_redo_manager,
+        None,
+        &cross_block_dto("b\nc", "b + c", false),
+    )?;
+    assert_eq!(result.replacements_count, 1);
+    assert_eq!(export_text(&db_context, &event_hub)?, "a\nb + c\nd");
+    assert_eq!(get_document_stats(&db_context)?.block_count, 3);
+
+    undo_redo_manager.undo(None)?;
+    undo_redo_manager.undo(None)?;
+    assert_eq!(export_text(&db_context, &event_hub)?, "a, b\nc, d");
+    assert_eq!(get_document_stats(&db_context)?.block_count, 2);
+
+    Ok(())
+}
+
+#[test]
+fn test_replace_regex_anchors_match_blocks_only_across_blocks() -> Result<()> {
+    let (db_context, event_hub, mut undo_redo_manager) = setup_with_text("foo\nfoo\nbar foo")?;
+
+    // Without cross-block matching `^` only matches at the document start.
+    let result = document_search_controller::replace_text(
+        &db_context,
+        &event_hub,
+        &mut undo_redo_manager,
+        None,
+        &ReplaceTextDto {
+            cross_block: false,
+            ..cross_block_dto("^foo", "x", true)
+        },
+    )?;
+    assert_eq!(result.replacements_count, 1);
+    assert_eq!(export_text(&db_context, &event_hub)?, "x\nfoo\nbar foo");
+
+    undo_redo_manager.undo(None)?;
+    let result = document_search_controller::replace_text(
+        &db_context,
+        &event_hub,
+        &mut undo_redo_manager,
+        None,
+        &cross_block_dto("^foo", "x", true),
+    )?;
+    assert_eq!(result.replacements_count, 2);
+    assert_eq!(export_text(&db_context, &event_hub)?, "x\nx\nbar foo");
+
+    Ok(())
+}
+
+#[test]
+fn test_replace_in_range_scope() -> Result<()> {
+    let (db_context, event_hub, mut undo_redo_manager) = setup_with_text("a a\na a\na a")?;
+
+    let result = document_search_controller::replace_text(
+        &db_context,
+        &event_hub,
+        &mut undo_redo_manager,
+        None,
+        &ReplaceTextDto {
+            cross_block: false,
+            scope_start: Some(2),
+            scope_end: Some(7),
+            ..cross_block_dto("a", "b", false)
+        },
+    )?;
+    assert_eq!(result.replacements_count, 3);
+    assert_eq!(export_text(&db_context, &event_hub)?, "a b\nb b\na a");
+
+    undo_redo_manager.undo(None)?;
+    assert_eq!(export_text(&db_context, &event_hub)?, "a a\na a\na a");
+
+    Ok(())
+}
+
+#[test]
+fn test_replace_folded_regex_keeps_document_spelling_of_groups() -> Result<()> {
+    let (db_context, event_hub, mut undo_redo_manager) = setup_with_text("un résumé, a resume")?;
+
+    let result = document_search_controller::replace_text(
+        &db_context,
+        &event_hub,
+        &mut undo_redo_manager,
+        None,
+        &ReplaceTextDto {
+            query: r"r(e)sum(?<last>e)".to_string(),
+            replacement: "[$1${last}$$]".to_string(),
+            use_regex: true,
+            replace_all: true,
+            fold_accents: true,
+            ..Default::default()
+        },
+    )?;
+
+    assert_eq!(result.replacements_count, 2);
+    let text = export_text(&db_context, &event_hub)?;
+    assert_eq!(text, "un [éé$], a [ee$]");
+
+    Ok(())
+}
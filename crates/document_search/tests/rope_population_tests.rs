@@ -4,8 +4,8 @@
 extern crate text_document_search as document_search;
 
 use anyhow::Result;
-use document_search::ReplaceTextDto;
 use document_search::document_search_controller;
+use document_search::{ReplaceTextDto, SearchNormalization};
 use test_harness::setup_with_imported_text;
 
 /// `replace_text` mutates `block.plain_text` and must also mirror
@@ -29,6 +29,15 @@ fn replace_text_mirrors_to_rope() -> Result<()> {
             whole_word: false,
             use_regex: false,
             replace_all: true,
+            preserve_case: false,
+            cross_block: false,
+            scope_start: None,
+            scope_end: None,
+            scope_frame_id: None,
+            scope_table_id: None,
+            normalization: SearchNormalization::NoNormalization,
+            fold_accents: false,
+            fold_width: false,
         },
     )?;
 
@@ -62,6 +71,15 @@ fn replace_text_in_one_block_of_many_preserves_other_blocks() -> Result<()> {
             whole_word: false,
             use_regex: false,
             replace_all: true,
+            preserve_case: false,
+            cross_block: false,
+            scope_start: None,
+            scope_end: None,
+            scope_frame_id: None,
+            scope_table_id: None,
+            normalization: SearchNormalization::NoNormalization,
+            fold_accents: false,
+            fold_width: false,
         },
     )?;
 
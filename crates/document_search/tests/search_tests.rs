@@ -4,7 +4,7 @@ use anyhow::Result;
 use test_harness::setup_with_text;
 
 use document_search::document_search_controller;
-use document_search::{FindAllDto, FindTextDto};
+use document_search::{FindAllDto, FindTextDto, SearchNormalization};
 
 #[test]
 fn test_find_text_simple() -> Result<()> {
@@ -20,6 +20,16 @@ fn test_find_text_simple() -> Result<()> {
             use_regex: false,
             search_backward: false,
             start_position: 0,
+            scope_start: None,
+            scope_end: None,
+            scope_frame_id: None,
+            scope_table_id: None,
+            normalization: SearchNormalization::NoNormalization,
+            fold_accents: false,
+            fold_width: false,
+            max_edits: None,
+            with_details: false,
+            context_chars: 0,
         },
     )?;
 
@@ -44,6 +54,16 @@ fn test_find_text_not_found() -> Result<()> {
             use_regex: false,
             search_backward: false,
             start_position: 0,
+            scope_start: None,
+            scope_end: None,
+            scope_frame_id: None,
+            scope_table_id: None,
+            normalization: SearchNormalization::NoNormalization,
+            fold_accents: false,
+            fold_width: false,
+            max_edits: None,
+            with_details: false,
+            context_chars: 0,
         },
     )?;
 
@@ -67,6 +87,16 @@ fn test_find_text_case_insensitive() -> Result<()> {
             use_regex: false,
             search_backward: false,
             start_position: 0,
+            scope_start: None,
+            scope_end: None,
+            scope_frame_id: None,
+            scope_table_id: None,
+            normalization: SearchNormalization::NoNormalization,
+            fold_accents: false,
+            fold_width: false,
+            max_edits: None,
+            with_details: false,
+            context_chars: 0,
         },
     )?;
     assert!(!result_sensitive.found);
@@ -82,6 +112,16 @@ fn test_find_text_case_insensitive() -> Result<()> {
             use_regex: false,
             search_backward: false,
             start_position: 0,
+            scope_start: None,
+            scope_end: None,
+            scope_frame_id: None,
+            scope_table_id: None,
+            normalization: SearchNormalization::NoNormalization,
+            fold_accents: false,
+            fold_width: false,
+            max_edits: None,
+            with_details: false,
+            context_chars: 0,
         },
     )?;
     assert!(result_insensitive.found);
@@ -106,6 +146,16 @@ fn test_find_text_backward() -> Result<()> {
             use_regex: false,
             search_backward: true,
             start_position: 8,
+            scope_start: None,
+            scope_end: None,
+            scope_frame_id: None,
+            scope_table_id: None,
+            normalization: SearchNormalization::NoNormalization,
+            fold_accents: false,
+            fold_width: false,
+            max_edits: None,
+            with_details: false,
+            context_chars: 0,
         },
     )?;
 
@@ -128,6 +178,18 @@ fn test_find_all_multiple_matches() -> Result<()> {
             case_sensitive: true,
             whole_word: false,
             use_regex: false,
+            scope_start: None,
+            scope_end: None,
+            scope_frame_id: None,
+            scope_table_id: None,
+            normalization: SearchNormalization::NoNormalization,
+            fold_accents: false,
+            fold_width: false,
+            max_edits: None,
+            start_position: None,
+            max_results: None,
+            with_details: false,
+            context_chars: 0,
         },
     )?;
 
@@ -150,6 +212,18 @@ fn test_find_all_no_matches() -> Result<()> {
             case_sensitive: true,
             whole_word: false,
             use_regex: false,
+            scope_start: None,
+            scope_end: None,
+            scope_frame_id: None,
+            scope_table_id: None,
+            normalization: SearchNormalization::NoNormalization,
+            fold_accents: false,
+            fold_width: false,
+            max_edits: None,
+            start_position: None,
+            max_results: None,
+            with_details: false,
+            context_chars: 0,
         },
     )?;
 
@@ -171,6 +245,18 @@ fn test_find_all_across_blocks() -> Result<()> {
             case_sensitive: true,
             whole_word: false,
             use_regex: false,
+            scope_start: None,
+            scope_end: None,
+            scope_frame_id: None,
+            scope_table_id: None,
+            normalization: SearchNormalization::NoNormalization,
+            fold_accents: false,
+            fold_width: false,
+            max_edits: None,
+            start_position: None,
+            max_results: None,
+            with_details: false,
+            context_chars: 0,
         },
     )?;
 
@@ -196,6 +282,16 @@ fn test_find_text_regex() -> Result<()> {
             use_regex: true,
             search_backward: false,
             start_position: 0,
+            scope_start: None,
+            scope_end: None,
+            scope_frame_id: None,
+            scope_table_id: None,
+            normalization: SearchNormalization::NoNormalization,
+            fold_accents: false,
+            fold_width: false,
+            max_edits: None,
+            with_details: false,
+            context_chars: 0,
         },
     )?;
 
@@ -219,6 +315,18 @@ fn test_find_all_regex() -> Result<()> {
             case_sensitive: true,
             whole_word: false,
             use_regex: true,
+            scope_start: None,
+            scope_end: None,
+            scope_frame_id: None,
+            scope_table_id: None,
+            normalization: SearchNormalization::NoNormalization,
+            fold_accents: false,
+            fold_width: false,
+            max_edits: None,
+            start_position: None,
+            max_results: None,
+            with_details: false,
+            context_chars: 0,
         },
     )?;
 
@@ -229,6 +337,27 @@ fn test_find_all_regex() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_find_all_regex_anchors_match_document_start_only() -> Result<()> {
+    let (db_context, event_hub, _) = setup_with_text("foo\nfoo\nbar foo")?;
+
+    let result = document_search_controller::find_all(
+        &db_context,
+        &event_hub,
+        &FindAllDto {
+            query: r"^foo".to_string(),
+            case_sensitive: true,
+            use_regex: true,
+            ..Default::default()
+        },
+    )?;
+
+    assert_eq!(result.count, 1);
+    assert_eq!(result.positions, vec![0]);
+
+    Ok(())
+}
+
 #[test]
 fn test_find_text_unicode() -> Result<()> {
     let (db_context, event_hub, _) = setup_with_text("café résumé naïve")?;
@@ -244,6 +373,16 @@ fn test_find_text_unicode() -> Result<()> {
             use_regex: false,
             search_backward: false,
             start_position: 0,
+            scope_start: None,
+            scope_end: None,
+            scope_frame_id: None,
+            scope_table_id: None,
+            normalization: SearchNormalization::NoNormalization,
+            fold_accents: false,
+            fold_width: false,
+            max_edits: None,
+            with_details: false,
+            context_chars: 0,
         },
     )?;
 
@@ -267,6 +406,18 @@ fn test_find_text_whole_word_unicode() -> Result<()> {
             case_sensitive: true,
             whole_word: true,
             use_regex: false,
+            scope_start: None,
+            scope_end: None,
+            scope_frame_id: None,
+            scope_table_id: None,
+            normalization: SearchNormalization::NoNormalization,
+            fold_accents: false,
+            fold_width: false,
+            max_edits: None,
+            start_position: None,
+            max_results: None,
+            with_details: false,
+            context_chars: 0,
         },
     )?;
 
@@ -291,6 +442,16 @@ fn test_find_text_start_position_skips() -> Result<()> {
             use_regex: false,
             search_backward: false,
             start_position: 1,
+            scope_start: None,
+            scope_end: None,
+            scope_frame_id: None,
+            scope_table_id: None,
+            normalization: SearchNormalization::NoNormalization,
+            fold_accents: false,
+            fold_width: false,
+            max_edits: None,
+            with_details: false,
+            context_chars: 0,
         },
     )?;
 
@@ -312,6 +473,18 @@ fn test_find_text_whole_word_ascii() -> Result<()> {
             case_sensitive: true,
             whole_word: true,
             use_regex: false,
+            scope_start: None,
+            scope_end: None,
+            scope_frame_id: None,
+            scope_table_id: None,
+            normalization: SearchNormalization::NoNormalization,
+            fold_accents: false,
+            fold_width: false,
+            max_edits: None,
+            start_position: None,
+            max_results: None,
+            with_details: false,
+            context_chars: 0,
         },
     )?;
 
@@ -333,6 +506,18 @@ fn test_find_text_regex_case_insensitive() -> Result<()> {
             case_sensitive: false,
             whole_word: false,
             use_regex: true,
+            scope_start: None,
+            scope_end: None,
+            scope_frame_id: None,
+            scope_table_id: None,
+            normalization: SearchNormalization::NoNormalization,
+            fold_accents: false,
+            fold_width: false,
+            max_edits: None,
+            start_position: None,
+            max_results: None,
+            with_details: false,
+            context_chars: 0,
         },
     )?;
 
@@ -355,6 +540,16 @@ fn test_find_text_empty_query() -> Result<()> {
             use_regex: false,
             search_backward: false,
             start_position: 0,
+            scope_start: None,
+            scope_end: None,
+            scope_frame_id: None,
+            scope_table_id: None,
+            normalization: SearchNormalization::NoNormalization,
+            fold_accents: false,
+            fold_width: false,
+            max_edits: None,
+            with_details: false,
+            context_chars: 0,
         },
     )?;
 
@@ -375,6 +570,18 @@ fn test_find_all_empty_query() -> Result<()> {
             case_sensitive: true,
             whole_word: false,
             use_regex: false,
+            scope_start: None,
+            scope_end: None,
+            scope_frame_id: None,
+            scope_table_id: None,
+            normalization: SearchNormalization::NoNormalization,
+            fold_accents: false,
+            fold_width: false,
+            max_edits: None,
+            start_position: None,
+            max_results: None,
+            with_details: false,
+            context_chars: 0,
         },
     )?;
 
@@ -382,3 +589,383 @@ fn test_find_all_empty_query() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_find_all_in_range_scope() -> Result<()> {
+    let (db_context, event_hub, _undo_redo_manager) = setup_with_text("ab ab\nab ab")?;
+
+    let result = document_search_controller::find_all(
+        &db_context,
+        &event_hub,
+        &FindAllDto {
+            query: "ab".to_string(),
+            case_sensitive: true,
+            whole_word: false,
+            use_regex: false,
+            scope_start: Some(2),
+            scope_end: Some(8),
+            scope_frame_id: None,
+            scope_table_id: None,
+            normalization: SearchNormalization::NoNormalization,
+            fold_accents: false,
+            fold_width: false,
+            max_edits: None,
+            start_position: None,
+            max_results: None,
+            with_details: false,
+            context_chars: 0,
+        },
+    )?;
+
+    // "ab" at 0 starts before the scope and "ab" at 9 ends after it.
+    assert_eq!(result.count, 2);
+    assert_eq!(result.positions, vec![3, 6]);
+
+    Ok(())
+}
+
+#[test]
+fn test_find_text_in_range_scope() -> Result<()> {
+    let (db_context, event_hub, _undo_redo_manager) = setup_with_text("ab ab ab")?;
+
+    let find = |search_backward: bool, start_position: i64| {
+        document_search_controller::find_text(
+            &db_context,
+            &event_hub,
+            &FindTextDto {
+                query: "ab".to_string(),
+                case_sensitive: true,
+                whole_word: false,
+                use_regex: false,
+                search_backward,
+                start_position,
+                scope_start: Some(1),
+                scope_end: Some(6),
+                scope_frame_id: None,
+                scope_table_id: None,
+                normalization: SearchNormalization::NoNormalization,
+                fold_accents: false,
+                fold_width: false,
+                max_edits: None,
+                with_details: false,
+                context_chars: 0,
+            },
+        )
+    };
+
+    let result = find(false, 0)?;
+    assert!(result.found);
+    assert_eq!(result.position, 3);
+    assert!(!find(false, 4)?.found);
+    assert!(!find(true, 3)?.found);
+
+    Ok(())
+}
+
+#[test]
+fn test_find_all_unknown_frame_scope_fails() -> Result<()> {
+    let (db_context, event_hub, _undo_redo_manager) = setup_with_text("ab")?;
+
+    let result = document_search_controller::find_all(
+        &db_context,
+        &event_hub,
+        &FindAllDto {
+            query: "ab".to_string(),
+            case_sensitive: true,
+            whole_word: false,
+            use_regex: false,
+            scope_start: None,
+            scope_end: None,
+            scope_frame_id: Some(9999),
+            scope_table_id: None,
+            normalization: SearchNormalization::NoNormalization,
+            fold_accents: false,
+            fold_width: false,
+            max_edits: None,
+            start_position: None,
+            max_results: None,
+            with_details: false,
+            context_chars: 0,
+        },
+    );
+    assert!(result.is_err());
+
+    Ok(())
+}
+
+fn find_all_dto(query: &str) -> FindAllDto {
+    FindAllDto {
+        query: query.to_string(),
+        case_sensitive: true,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_find_all_pages_through_matches() -> Result<()> {
+    let (db_context, event_hub, _undo_redo_manager) = setup_with_text("ab ab ab ab")?;
+
+    let page = |start_position: i64| {
+        document_search_controller::find_all(
+            &db_context,
+            &event_hub,
+            &FindAllDto {
+                start_position: Some(start_position),
+                max_results: Some(2),
+                ..find_all_dto("ab")
+            },
+        )
+    };
+
+    let first = page(0)?;
+    assert_eq!(first.positions, vec![0, 3]);
+    assert!(first.has_more);
+    let second = page(4)?;
+    assert_eq!(second.positions, vec![6, 9]);
+    assert!(!second.has_more);
+    assert_eq!(page(10)?.count, 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_find_all_session_pages_match_find_all() -> Result<()> {
+    let (db_context, event_hub, _undo_redo_manager) =
+        setup_with_text("Ab ab résumé\nabab resume b\n\nab")?;
+
+    let dtos = [
+        find_all_dto("ab"),
+        FindAllDto {
+            case_sensitive: false,
+            ..find_all_dto("ab")
+        },
+        FindAllDto {
+            use_regex: true,
+            ..find_all_dto(r"\b|b")
+        },
+        FindAllDto {
+            use_regex: true,
+            ..find_all_dto("x*")
+        },
+        FindAllDto {
+            whole_word: true,
+            ..find_all_dto("ab")
+        },
+        FindAllDto {
+            fold_accents: true,
+            ..find_all_dto("resume")
+        },
+        FindAllDto {
+            max_edits: Some(1),
+            ..find_all_dto("abc")
+        },
+    ];
+    for dto in dtos {
+        let all = document_search_controller::find_all(&db_context, &event_hub, &dto)?;
+        for page_size in [1, 2, 3] {
+            let mut session = document_search_controller::start_find_all(
+                &db_context,
+                &event_hub,
+                &FindAllDto {
+                    max_results: Some(page_size),
+                    ..dto.clone()
+                },
+            )?;
+            let mut matches = Vec::new();
+            loop {
+                let page = session.next_page();
+                assert!(page.count <= page_size);
+                matches.extend(page.positions.into_iter().zip(page.lengths));
+                if !page.has_more {
+                    break;
+                }
+            }
+            let mut expected: Vec<(i64, i64)> = all
+                .positions
+                .iter()
+                .copied()
+                .zip(all.lengths.iter().copied())
+                .collect();
+            // Fuzzy pages are each ranked on their own.
+            if dto.max_edits.is_some() {
+                matches.sort();
+                expected.sort();
+            }
+            assert_eq!(matches, expected, "{:?} in pages of {page_size}", dto.query);
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_find_all_with_details() -> Result<()> {
+    let (db_context, event_hub, _undo_redo_manager) = setup_with_text("Hello World\nOther words")?;
+
+    let result = document_search_controller::find_all(
+        &db_context,
+        &event_hub,
+        &FindAllDto {
+            use_regex: true,
+            with_details: true,
+            context_chars: 3,
+            ..find_all_dto("([Ww])or(x)?")
+        },
+    )?;
+
+    assert_eq!(result.positions, vec![6, 18]);
+    assert_eq!(result.block_positions, vec![6, 6]);
+    assert_ne!(result.block_ids[0], result.block_ids[1]);
+    assert_eq!(result.frame_ids[0], result.frame_ids[1]);
+    assert_eq!(result.table_ids, vec![0, 0]);
+    assert_eq!(result.contexts_before, vec!["lo ", "er "]);
+    assert_eq!(result.contexts_after, vec!["ld", "ds"]);
+    assert_eq!(result.capture_counts, vec![2, 2]);
+    assert_eq!(result.capture_positions, vec![6, -1, 18, -1]);
+    assert_eq!(result.capture_lengths, vec![1, 0, 1, 0]);
+
+    Ok(())
+}
+
+#[test]
+fn test_find_text_with_details() -> Result<()> {
+    let (db_context, event_hub, _undo_redo_manager) = setup_with_text("one two\nthree two")?;
+
+    let result = document_search_controller::find_text(
+        &db_context,
+        &event_hub,
+        &FindTextDto {
+            query: "two".to_string(),
+            case_sensitive: true,
+            start_position: 5,
+            with_details: true,
+            context_chars: 10,
+            ..Default::default()
+        },
+    )?;
+
+    assert!(result.found);
+    assert_eq!(result.position, 14);
+    assert_eq!(result.block_position, 6);
+    assert_eq!(result.context_before, "three ");
+    assert_eq!(result.context_after, "");
+    assert!(result.capture_positions.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_find_all_folds_accents_normalization_and_width() -> Result<()> {
+    let (db_context, event_hub, _undo_redo_manager) =
+        setup_with_text("Résumé or resume\nCafe\u{301} ＡＢＣ \u{FB01}le")?;
+
+    let find = |query: &str, folding: fn(&mut FindAllDto)| -> Result<Vec<(i64, i64)>> {
+        let mut dto = FindAllDto {
+            case_sensitive: false,
+            ..find_all_dto(query)
+        };
+        folding(&mut dto);
+        let result = document_search_controller::find_all(&db_context, &event_hub, &dto)?;
+        Ok(result.positions.into_iter().zip(result.lengths).collect())
+    };
+
+    assert_eq!(find("resume", |_| {})?, vec![(10, 6)]);
+    assert_eq!(
+        find("resume", |dto| dto.fold_accents = true)?,
+        vec![(0, 6), (10, 6)]
+    );
+    // The decomposed "é" in the document is matched whole.
+    assert_eq!(find("café", |_| {})?, vec![]);
+    assert_eq!(
+        find("café", |dto| dto.normalization = SearchNormalization::Nfc)?,
+        vec![(17, 5)]
+    );
+    assert_eq!(find("cafe", |dto| dto.fold_accents = true)?, vec![(17, 5)]);
+    assert_eq!(find("abc", |dto| dto.fold_width = true)?, vec![(23, 3)]);
+    assert_eq!(
+        find("file", |dto| dto.normalization = SearchNormalization::Nfkc)?,
+        vec![(27, 3)]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_find_text_folded_regex_captures_map_to_document() -> Result<()> {
+    let (db_context, event_hub, _undo_redo_manager) = setup_with_text("Tiếng Việt")?;
+
+    let result = document_search_controller::find_text(
+        &db_context,
+        &event_hub,
+        &FindTextDto {
+            query: r"(vi)(e)t".to_string(),
+            use_regex: true,
+            fold_accents: true,
+            with_details: true,
+            ..Default::default()
+        },
+    )?;
+
+    assert!(result.found);
+    assert_eq!((result.position, result.length), (6, 4));
+    assert_eq!(result.capture_positions, vec![6, 8]);
+    assert_eq!(result.capture_lengths, vec![2, 1]);
+
+    Ok(())
+}
+
+#[test]
+fn test_find_all_fuzzy_ranks_by_distance() -> Result<()> {
+    let (db_context, event_hub, _undo_redo_manager) =
+        setup_with_text("hello world, helo wrld\nhallo world")?;
+
+    let result = document_search_controller::find_all(
+        &db_context,
+        &event_hub,
+        &FindAllDto {
+            max_edits: Some(1),
+            ..find_all_dto("Hello World")
+        },
+    )?;
+    // Case differences count as edits in a case-sensitive search.
+    assert!(result.positions.is_empty());
+
+    let result = document_search_controller::find_all(
+        &db_context,
+        &event_hub,
+        &FindAllDto {
+            case_sensitive: false,
+            max_edits: Some(2),
+            ..find_all_dto("Hello World")
+        },
+    )?;
+    assert_eq!(result.positions, vec![0, 23, 13]);
+    assert_eq!(result.lengths, vec![11, 11, 9]);
+    assert_eq!(result.distances, vec![0, 1, 2]);
+
+    let next = document_search_controller::find_text(
+        &db_context,
+        &event_hub,
+        &FindTextDto {
+            query: "hello world".to_string(),
+            start_position: 1,
+            max_edits: Some(2),
+            ..Default::default()
+        },
+    )?;
+    assert!(next.found);
+    assert_eq!((next.position, next.distance), (13, 2));
+
+    let regex = document_search_controller::find_all(
+        &db_context,
+        &event_hub,
+        &FindAllDto {
+            use_regex: true,
+            max_edits: Some(1),
+            ..find_all_dto("hel+o")
+        },
+    );
+    assert!(regex.is_err());
+
+    Ok(())
+}
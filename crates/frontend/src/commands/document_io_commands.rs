@@ -5,9 +5,12 @@
 use crate::app_context::AppContext;
 use anyhow::{Context, Result};
 use document_io::{
-    ExportDocxDto, ExportDocxResultDto, ExportHtmlDto, ExportLatexDto, ExportLatexResultDto,
-    ExportMarkdownDto, ExportPlainTextDto, ImportHtmlDto, ImportHtmlResultDto, ImportMarkdownDto,
-    ImportMarkdownResultDto, ImportPlainTextDto, document_io_controller,
+    ExportDocxDto, ExportDocxResultDto, ExportEpubDto, ExportEpubResultDto, ExportHtmlDto,
+    ExportLatexDto, ExportLatexResultDto, ExportMarkdownDto, ExportNativeDto, ExportOdtDto,
+    ExportOdtResultDto, ExportPlainTextDto, ExportRtfDto, ImportDocxDto, ImportDocxResultDto,
+    ImportHtmlDto, ImportHtmlResultDto, ImportMarkdownDto, ImportMarkdownResultDto,
+    ImportNativeDto, ImportNativeResultDto, ImportOdtDto, ImportOdtResultDto, ImportPlainTextDto,
+    ImportRtfDto, ImportRtfResultDto, document_io_controller,
 };
 
 use common::long_operation::OperationProgress;
@@ -92,6 +95,37 @@ pub fn get_import_html_result(
     .context("getting import_html result")
 }
 
+/// import_docx (long operation)
+pub fn import_docx(ctx: &AppContext, dto: &ImportDocxDto) -> Result<String> {
+    document_io_controller::import_docx(
+        &ctx.db_context,
+        &ctx.event_hub,
+        &mut ctx.long_operation_manager.lock().unwrap(),
+        dto,
+    )
+    .context("import_docx")
+}
+
+/// Get the progress of a import_docx operation
+pub fn get_import_docx_progress(ctx: &AppContext, operation_id: &str) -> Option<OperationProgress> {
+    document_io_controller::get_import_docx_progress(
+        &ctx.long_operation_manager.lock().unwrap(),
+        operation_id,
+    )
+}
+
+/// Get the result of a import_docx operation
+pub fn get_import_docx_result(
+    ctx: &AppContext,
+    operation_id: &str,
+) -> Result<Option<ImportDocxResultDto>> {
+    document_io_controller::get_import_docx_result(
+        &ctx.long_operation_manager.lock().unwrap(),
+        operation_id,
+    )
+    .context("getting import_docx result")
+}
+
 pub fn export_html(ctx: &AppContext) -> Result<ExportHtmlDto> {
     document_io_controller::export_html(&ctx.db_context, &ctx.event_hub).context("export_html")
 }
@@ -131,3 +165,173 @@ pub fn get_export_docx_result(
     )
     .context("getting export_docx result")
 }
+
+pub fn import_native(ctx: &AppContext, dto: &ImportNativeDto) -> Result<ImportNativeResultDto> {
+    document_io_controller::import_native(&ctx.db_context, &ctx.event_hub, dto)
+        .context("import_native")
+}
+
+pub fn export_native(ctx: &AppContext) -> Result<ExportNativeDto> {
+    document_io_controller::export_native(&ctx.db_context, &ctx.event_hub).context("export_native")
+}
+
+/// Import a native document, restoring the undo history saved in it onto
+/// `stack_id`. Returns whether a history was restored.
+pub fn import_native_with_history(
+    ctx: &AppContext,
+    stack_id: u64,
+    dto: &ImportNativeDto,
+) -> Result<(ImportNativeResultDto, bool)> {
+    let mut undo_redo_manager = ctx.undo_redo_manager.lock().unwrap();
+    document_io_controller::import_native_with_history(
+        &ctx.db_context,
+        &ctx.event_hub,
+        &mut undo_redo_manager,
+        stack_id,
+        dto,
+    )
+    .context("import_native_with_history")
+}
+
+/// Export a native document carrying `stack_id`'s undo history.
+pub fn export_native_with_history(
+    ctx: &AppContext,
+    stack_id: Option<u64>,
+) -> Result<ExportNativeDto> {
+    let undo_redo_manager = ctx.undo_redo_manager.lock().unwrap();
+    document_io_controller::export_native_with_history(
+        &ctx.db_context,
+        &ctx.event_hub,
+        &undo_redo_manager,
+        stack_id,
+    )
+    .context("export_native_with_history")
+}
+
+/// import_odt (long operation)
+pub fn import_odt(ctx: &AppContext, dto: &ImportOdtDto) -> Result<String> {
+    document_io_controller::import_odt(
+        &ctx.db_context,
+        &ctx.event_hub,
+        &mut ctx.long_operation_manager.lock().unwrap(),
+        dto,
+    )
+    .context("import_odt")
+}
+
+/// Get the progress of a import_odt operation
+pub fn get_import_odt_progress(ctx: &AppContext, operation_id: &str) -> Option<OperationProgress> {
+    document_io_controller::get_import_odt_progress(
+        &ctx.long_operation_manager.lock().unwrap(),
+        operation_id,
+    )
+}
+
+/// Get the result of a import_odt operation
+pub fn get_import_odt_result(
+    ctx: &AppContext,
+    operation_id: &str,
+) -> Result<Option<ImportOdtResultDto>> {
+    document_io_controller::get_import_odt_result(
+        &ctx.long_operation_manager.lock().unwrap(),
+        operation_id,
+    )
+    .context("getting import_odt result")
+}
+
+/// export_odt (long operation)
+pub fn export_odt(ctx: &AppContext, dto: &ExportOdtDto) -> Result<String> {
+    document_io_controller::export_odt(
+        &ctx.db_context,
+        &ctx.event_hub,
+        &mut ctx.long_operation_manager.lock().unwrap(),
+        dto,
+    )
+    .context("export_odt")
+}
+
+/// Get the progress of a export_odt operation
+pub fn get_export_odt_progress(ctx: &AppContext, operation_id: &str) -> Option<OperationProgress> {
+    document_io_controller::get_export_odt_progress(
+        &ctx.long_operation_manager.lock().unwrap(),
+        operation_id,
+    )
+}
+
+/// Get the result of a export_odt operation
+pub fn get_export_odt_result(
+    ctx: &AppContext,
+    operation_id: &str,
+) -> Result<Option<ExportOdtResultDto>> {
+    document_io_controller::get_export_odt_result(
+        &ctx.long_operation_manager.lock().unwrap(),
+        operation_id,
+    )
+    .context("getting export_odt result")
+}
+
+/// import_rtf (long operation)
+pub fn import_rtf(ctx: &AppContext, dto: &ImportRtfDto) -> Result<String> {
+    document_io_controller::import_rtf(
+        &ctx.db_context,
+        &ctx.event_hub,
+        &mut ctx.long_operation_manager.lock().unwrap(),
+        dto,
+    )
+    .context("import_rtf")
+}
+
+/// Get the progress of a import_rtf operation
+pub fn get_import_rtf_progress(ctx: &AppContext, operation_id: &str) -> Option<OperationProgress> {
+    document_io_controller::get_import_rtf_progress(
+        &ctx.long_operation_manager.lock().unwrap(),
+        operation_id,
+    )
+}
+
+/// Get the result of a import_rtf operation
+pub fn get_import_rtf_result(
+    ctx: &AppContext,
+    operation_id: &str,
+) -> Result<Option<ImportRtfResultDto>> {
+    document_io_controller::get_import_rtf_result(
+        &ctx.long_operation_manager.lock().unwrap(),
+        operation_id,
+    )
+    .context("getting import_rtf result")
+}
+
+pub fn export_rtf(ctx: &AppContext) -> Result<ExportRtfDto> {
+    document_io_controller::export_rtf(&ctx.db_context, &ctx.event_hub).context("export_rtf")
+}
+
+/// export_epub (long operation)
+pub fn export_epub(ctx: &AppContext, dto: &ExportEpubDto) -> Result<String> {
+    document_io_controller::export_epub(
+        &ctx.db_context,
+        &ctx.event_hub,
+        &mut ctx.long_operation_manager.lock().unwrap(),
+        dto,
+    )
+    .context("export_epub")
+}
+
+/// Get the progress of a export_epub operation
+pub fn get_export_epub_progress(ctx: &AppContext, operation_id: &str) -> Option<OperationProgress> {
+    document_io_controller::get_export_epub_progress(
+        &ctx.long_operation_manager.lock().unwrap(),
+        operation_id,
+    )
+}
+
+/// Get the result of a export_epub operation
+pub fn get_export_epub_result(
+    ctx: &AppContext,
+    operation_id: &str,
+) -> Result<Option<ExportEpubResultDto>> {
+    document_io_controller::get_export_epub_result(
+        &ctx.long_operation_manager.lock().unwrap(),
+        operation_id,
+    )
+    .context("getting export_epub result")
+}
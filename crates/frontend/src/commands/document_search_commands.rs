@@ -5,8 +5,8 @@
 use crate::app_context::AppContext;
 use anyhow::{Context, Result};
 use document_search::{
-    FindAllDto, FindAllResultDto, FindResultDto, FindTextDto, ReplaceResultDto, ReplaceTextDto,
-    document_search_controller,
+    FindAllDto, FindAllResultDto, FindAllSession, FindByFormatDto, FindResultDto, FindTextDto,
+    ReplaceResultDto, ReplaceTextDto, document_search_controller,
 };
 
 pub fn find_text(ctx: &AppContext, dto: &FindTextDto) -> Result<FindResultDto> {
@@ -17,6 +17,16 @@ pub fn find_all(ctx: &AppContext, dto: &FindAllDto) -> Result<FindAllResultDto>
     document_search_controller::find_all(&ctx.db_context, &ctx.event_hub, dto).context("find_all")
 }
 
+pub fn start_find_all(ctx: &AppContext, dto: &FindAllDto) -> Result<FindAllSession> {
+    document_search_controller::start_find_all(&ctx.db_context, &ctx.event_hub, dto)
+        .context("start_find_all")
+}
+
+pub fn find_by_format(ctx: &AppContext, dto: &FindByFormatDto) -> Result<FindAllResultDto> {
+    document_search_controller::find_by_format(&ctx.db_context, &ctx.event_hub, dto)
+        .context("find_by_format")
+}
+
 pub fn replace_text(
     ctx: &AppContext,
     stack_id: Option<u64>,
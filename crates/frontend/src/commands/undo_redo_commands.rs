@@ -4,6 +4,7 @@
 
 use crate::app_context::AppContext;
 use anyhow::{Context, Result};
+use common::undo_redo::{EditSelection, UndoLimit};
 
 /// Undoes the most recent command on the specified stack.
 pub fn undo(ctx: &AppContext, stack_id: Option<u64>) -> Result<()> {
@@ -22,6 +23,68 @@ pub fn redo(ctx: &AppContext, stack_id: Option<u64>) -> Result<()> {
     undo_redo_manager.redo(stack_id).context("redo")
 }
 
+/// Blocks the next `steps` undos on the specified stack would rewrite, when known.
+pub fn undo_affected_blocks(
+    ctx: &AppContext,
+    stack_id: Option<u64>,
+    steps: usize,
+) -> Option<Vec<u64>> {
+    let undo_redo_manager = ctx.undo_redo_manager.lock().unwrap();
+    undo_redo_manager.undo_affected_blocks(stack_id, steps)
+}
+
+/// Blocks the next `steps` redos on the specified stack would rewrite, when known.
+pub fn redo_affected_blocks(
+    ctx: &AppContext,
+    stack_id: Option<u64>,
+    steps: usize,
+) -> Option<Vec<u64>> {
+    let undo_redo_manager = ctx.undo_redo_manager.lock().unwrap();
+    undo_redo_manager.redo_affected_blocks(stack_id, steps)
+}
+
+/// Descriptions of the undoable commands on the specified stack, oldest first.
+pub fn undo_descriptions(ctx: &AppContext, stack_id: Option<u64>) -> Vec<String> {
+    let undo_redo_manager = ctx.undo_redo_manager.lock().unwrap();
+    undo_redo_manager.undo_descriptions(stack_id)
+}
+
+/// Descriptions of the redoable commands on the specified stack, next to redo first.
+pub fn redo_descriptions(ctx: &AppContext, stack_id: Option<u64>) -> Vec<String> {
+    let undo_redo_manager = ctx.undo_redo_manager.lock().unwrap();
+    undo_redo_manager.redo_descriptions(stack_id)
+}
+
+/// Records the editing cursor's selection with the undo entry that received the last command.
+pub fn record_selection(ctx: &AppContext, selection: EditSelection) {
+    let mut undo_redo_manager = ctx.undo_redo_manager.lock().unwrap();
+    undo_redo_manager.record_selection(selection);
+}
+
+/// Selection recorded with the entry the next undo on the specified stack would revert.
+pub fn next_undo_selection(ctx: &AppContext, stack_id: Option<u64>) -> Option<EditSelection> {
+    let undo_redo_manager = ctx.undo_redo_manager.lock().unwrap();
+    undo_redo_manager.next_undo_selection(stack_id)
+}
+
+/// Selection recorded with the entry the next redo on the specified stack would reapply.
+pub fn next_redo_selection(ctx: &AppContext, stack_id: Option<u64>) -> Option<EditSelection> {
+    let undo_redo_manager = ctx.undo_redo_manager.lock().unwrap();
+    undo_redo_manager.next_redo_selection(stack_id)
+}
+
+/// Number of undoable commands on the specified stack.
+pub fn undo_count(ctx: &AppContext, stack_id: Option<u64>) -> usize {
+    let undo_redo_manager = ctx.undo_redo_manager.lock().unwrap();
+    undo_redo_manager.undo_count(stack_id)
+}
+
+/// Number of redoable commands on the specified stack.
+pub fn redo_count(ctx: &AppContext, stack_id: Option<u64>) -> usize {
+    let undo_redo_manager = ctx.undo_redo_manager.lock().unwrap();
+    undo_redo_manager.redo_count(stack_id)
+}
+
 /// Checks if there are commands that can be undone on the specified stack.
 pub fn can_undo(ctx: &AppContext, stack_id: Option<u64>) -> bool {
     let undo_redo_manager = ctx.undo_redo_manager.lock().unwrap();
@@ -41,6 +104,20 @@ pub fn begin_composite(ctx: &AppContext, stack_id: Option<u64>) {
     let _ = undo_redo_manager.begin_composite(stack_id);
 }
 
+/// Begins a composite command group, failing instead when one is in
+/// progress on another stack.
+pub fn try_begin_composite(ctx: &AppContext, stack_id: Option<u64>) -> Result<()> {
+    let mut undo_redo_manager = ctx.undo_redo_manager.lock().unwrap();
+    undo_redo_manager.set_event_hub(&ctx.event_hub);
+    undo_redo_manager.begin_composite(stack_id)
+}
+
+/// Labels the composite command group currently being built.
+pub fn set_composite_description(ctx: &AppContext, description: &str) {
+    let mut undo_redo_manager = ctx.undo_redo_manager.lock().unwrap();
+    undo_redo_manager.set_composite_description(description);
+}
+
 /// Ends the current composite command group and adds it to the specified stack.
 pub fn end_composite(ctx: &AppContext) {
     let mut undo_redo_manager = ctx.undo_redo_manager.lock().unwrap();
@@ -73,12 +150,39 @@ pub fn get_stack_size(ctx: &AppContext, stack_id: u64) -> usize {
     undo_redo_manager.get_stack_size(stack_id)
 }
 
+/// Sets the history limit of a stack, trimming it to fit. Returns the number of entries dropped.
+pub fn set_stack_limit(ctx: &AppContext, stack_id: u64, limit: UndoLimit) -> Result<usize> {
+    let mut undo_redo_manager = ctx.undo_redo_manager.lock().unwrap();
+    undo_redo_manager
+        .set_stack_limit(stack_id, limit)
+        .context("setting undo/redo stack limit")
+}
+
+/// Gets the history limit of a stack.
+pub fn get_stack_limit(ctx: &AppContext, stack_id: u64) -> UndoLimit {
+    let undo_redo_manager = ctx.undo_redo_manager.lock().unwrap();
+    undo_redo_manager.get_stack_limit(stack_id)
+}
+
+/// Gets the approximate bytes held by a stack's undo and redo entries.
+pub fn get_stack_memory_usage(ctx: &AppContext, stack_id: u64) -> usize {
+    let undo_redo_manager = ctx.undo_redo_manager.lock().unwrap();
+    undo_redo_manager.get_stack_memory_usage(stack_id)
+}
+
 /// Creates a new undo/redo stack and returns its ID.
 pub fn create_new_stack(ctx: &AppContext) -> u64 {
     let mut undo_redo_manager = ctx.undo_redo_manager.lock().unwrap();
     undo_redo_manager.create_new_stack()
 }
 
+/// Creates a new undo/redo stack checked against the other guarded
+/// stacks, and returns its ID.
+pub fn create_guarded_stack(ctx: &AppContext) -> u64 {
+    let mut undo_redo_manager = ctx.undo_redo_manager.lock().unwrap();
+    undo_redo_manager.create_guarded_stack()
+}
+
 /// Deletes an undo/redo stack by its ID.
 pub fn delete_stack(ctx: &AppContext, stack_id: u64) -> Result<()> {
     let mut undo_redo_manager = ctx.undo_redo_manager.lock().unwrap();
@@ -86,9 +86,18 @@ pub enum FlatEventKind {
     DocumentIoExportHtml,
     DocumentIoExportLatex,
     DocumentIoExportDocx,
+    DocumentIoImportNative,
+    DocumentIoExportNative,
+    DocumentIoImportDocx,
+    DocumentIoImportOdt,
+    DocumentIoExportOdt,
+    DocumentIoImportRtf,
+    DocumentIoExportRtf,
+    DocumentIoExportEpub,
 
     DocumentSearchFindText,
     DocumentSearchFindAll,
+    DocumentSearchFindByFormat,
     DocumentSearchReplaceText,
 
     DocumentInspectionGetDocumentStats,
@@ -251,10 +260,19 @@ impl From<Event> for FlatEvent {
                 DocumentIoEvent::ExportHtml => FlatEventKind::DocumentIoExportHtml,
                 DocumentIoEvent::ExportLatex => FlatEventKind::DocumentIoExportLatex,
                 DocumentIoEvent::ExportDocx => FlatEventKind::DocumentIoExportDocx,
+                DocumentIoEvent::ImportNative => FlatEventKind::DocumentIoImportNative,
+                DocumentIoEvent::ExportNative => FlatEventKind::DocumentIoExportNative,
+                DocumentIoEvent::ImportDocx => FlatEventKind::DocumentIoImportDocx,
+                DocumentIoEvent::ImportOdt => FlatEventKind::DocumentIoImportOdt,
+                DocumentIoEvent::ExportOdt => FlatEventKind::DocumentIoExportOdt,
+                DocumentIoEvent::ImportRtf => FlatEventKind::DocumentIoImportRtf,
+                DocumentIoEvent::ExportRtf => FlatEventKind::DocumentIoExportRtf,
+                DocumentIoEvent::ExportEpub => FlatEventKind::DocumentIoExportEpub,
             },
             Origin::DocumentSearch(fe) => match fe {
                 DocumentSearchEvent::FindText => FlatEventKind::DocumentSearchFindText,
                 DocumentSearchEvent::FindAll => FlatEventKind::DocumentSearchFindAll,
+                DocumentSearchEvent::FindByFormat => FlatEventKind::DocumentSearchFindByFormat,
                 DocumentSearchEvent::ReplaceText => FlatEventKind::DocumentSearchReplaceText,
             },
             Origin::DocumentInspection(fe) => match fe {
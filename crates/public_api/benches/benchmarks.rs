@@ -1,4 +1,5 @@
 use criterion::{BatchSize, BenchmarkId, Criterion, black_box, criterion_group, criterion_main};
+use std::hint;
 use std::time::Duration;
 use text_document::{
     Alignment, BlockFormat, FindOptions, ListStyle, MoveMode, MoveOperation, SelectionType,
@@ -334,6 +335,79 @@ fn bench_search(c: &mut Criterion) {
     group.finish();
 }
 
+// ── Search index ────────────────────────────────────────────────
+// Find-as-you-type on a large manuscript: each keystroke edits one
+// paragraph, then the search box re-runs the query. Compares the full
+// scan with the maintained trigram index.
+
+fn make_manuscript(paragraphs: usize) -> TextDocument {
+    let text: String = (0..paragraphs)
+        .map(|i| format!("{PARAGRAPH} Paragraph {i}."))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let doc = TextDocument::new();
+    doc.set_plain_text(&text).unwrap();
+    doc
+}
+
+fn bench_search_index(c: &mut Criterion) {
+    let mut group = c.benchmark_group("search_index");
+    group.sample_size(20);
+    group.measurement_time(Duration::from_secs(10));
+
+    for (indexed, mode) in [(false, "scan"), (true, "indexed")] {
+        let doc = make_manuscript(10_000);
+        doc.set_search_index_enabled(indexed);
+        let opts = FindOptions::default();
+        // Builds the index outside the measurement.
+        doc.find_all("warm up", &opts).unwrap();
+
+        group.bench_function(BenchmarkId::new("find_rare_word", mode), |b| {
+            b.iter(|| {
+                hint::black_box(
+                    doc.find_all(hint::black_box("Paragraph 9999."), &opts)
+                        .unwrap(),
+                )
+            });
+        });
+
+        let whole_word = FindOptions {
+            whole_word: true,
+            ..Default::default()
+        };
+        group.bench_function(BenchmarkId::new("find_whole_word", mode), |b| {
+            b.iter(|| {
+                hint::black_box(
+                    doc.find_all(hint::black_box("labore"), &whole_word)
+                        .unwrap(),
+                )
+            });
+        });
+
+        group.bench_function(BenchmarkId::new("find_next", mode), |b| {
+            b.iter(|| {
+                hint::black_box(
+                    doc.find(hint::black_box("Paragraph 5000"), 0, &opts)
+                        .unwrap(),
+                )
+            });
+        });
+
+        group.bench_function(BenchmarkId::new("type_then_find", mode), |b| {
+            let cursor = doc.cursor_at(500_000);
+            b.iter(|| {
+                cursor.insert_text("x").unwrap();
+                hint::black_box(
+                    doc.find_all(hint::black_box("Paragraph 42."), &opts)
+                        .unwrap(),
+                )
+            });
+        });
+    }
+
+    group.finish();
+}
+
 // ── Undo / Redo ─────────────────────────────────────────────────
 
 fn bench_undo_redo(c: &mut Criterion) {
@@ -385,6 +459,101 @@ fn bench_undo_redo(c: &mut Criterion) {
         );
     });
 
+    // In-block edits record only the touched block for undo, so these
+    // should stay roughly flat as the document grows. Setup rebuilds a
+    // large document per batch, hence the smaller sample.
+    group.sample_size(10);
+    let history_sizes = [
+        (100, "medium/100para"),
+        (1000, "large/1000para"),
+        (10000, "huge/10000para"),
+    ];
+    for (n, label) in history_sizes {
+        let select_in_middle = |doc: &TextDocument, len: usize| {
+            let start = len / 2 - (len / 2) % (PARAGRAPH.len() + 1) + 6;
+            let cursor = doc.cursor_at(start);
+            cursor.set_position(start + 20, MoveMode::KeepAnchor);
+            cursor
+        };
+
+        group.bench_with_input(
+            BenchmarkId::new("type_over_selection_undo", label),
+            &n,
+            |b, &n| {
+                b.iter_batched(
+                    || {
+                        let (doc, len) = make_doc(n);
+                        select_in_middle(&doc, len).insert_text("bench").unwrap();
+                        doc
+                    },
+                    // Hand the document back so its drop isn't timed.
+                    |doc| {
+                        doc.undo().unwrap();
+                        doc
+                    },
+                    BatchSize::SmallInput,
+                );
+            },
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new("delete_selection_undo", label),
+            &n,
+            |b, &n| {
+                b.iter_batched(
+                    || {
+                        let (doc, len) = make_doc(n);
+                        select_in_middle(&doc, len).remove_selected_text().unwrap();
+                        doc
+                    },
+                    |doc| {
+                        doc.undo().unwrap();
+                        doc
+                    },
+                    BatchSize::SmallInput,
+                );
+            },
+        );
+
+        // A selection spanning paragraphs merges blocks, so undo goes
+        // through a store delta rather than a single block's state.
+        group.bench_with_input(
+            BenchmarkId::new("type_over_cross_block_selection_undo", label),
+            &n,
+            |b, &n| {
+                b.iter_batched(
+                    || {
+                        let (doc, len) = make_doc(n);
+                        let start = len / 2 - (len / 2) % (PARAGRAPH.len() + 1) + 6;
+                        let cursor = doc.cursor_at(start);
+                        cursor.set_position(start + PARAGRAPH.len() + 20, MoveMode::KeepAnchor);
+                        cursor.insert_text("bench").unwrap();
+                        doc
+                    },
+                    |doc| {
+                        doc.undo().unwrap();
+                        doc
+                    },
+                    BatchSize::SmallInput,
+                );
+            },
+        );
+
+        // Undo then redo on one document, with no setup per iteration.
+        group.bench_with_input(
+            BenchmarkId::new("type_over_selection_undo_redo_cycle", label),
+            &n,
+            |b, &n| {
+                let (doc, len) = make_doc(n);
+                select_in_middle(&doc, len).insert_text("bench").unwrap();
+                b.iter(|| {
+                    doc.undo().unwrap();
+                    doc.redo().unwrap();
+                });
+            },
+        );
+    }
+
     group.finish();
 }
 
@@ -927,7 +1096,12 @@ fn bench_xl_workloads(c: &mut Criterion) {
 
 criterion_group!(creation, bench_document_creation);
 criterion_group!(editing, bench_insertion, bench_deletion);
-criterion_group!(navigation, bench_cursor_movement, bench_search);
+criterion_group!(
+    navigation,
+    bench_cursor_movement,
+    bench_search,
+    bench_search_index
+);
 criterion_group!(history, bench_undo_redo);
 criterion_group!(
     formatting_group,
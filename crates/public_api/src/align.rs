@@ -0,0 +1,139 @@
+//! Sequence alignment shared by the collaboration layer and document
+//! diffs: Myers' shortest edit script over any items with an equality.
+
+/// Edits past which two sequences are compared as one changed range
+/// rather than diffed item by item.
+const MAX_DIFF_EDITS: usize = 2048;
+
+/// `removed` items at `a` in the old sequence became the `added` items
+/// at `b` in the new one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Hunk {
+    pub(crate) a: usize,
+    pub(crate) removed: usize,
+    pub(crate) b: usize,
+    pub(crate) added: usize,
+}
+
+/// Hunks turning `a` into `b`, comparing items with `eq`, in order. Past
+/// [`MAX_DIFF_EDITS`] edits the differing middle is one hunk.
+pub(crate) fn diff_by<T>(a: &[T], b: &[T], eq: impl Fn(&T, &T) -> bool) -> Vec<Hunk> {
+    let prefix = a.iter().zip(b).take_while(|(x, y)| eq(x, y)).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| eq(x, y))
+        .count();
+    let (a, b) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+    let whole = Hunk {
+        a: prefix,
+        removed: a.len(),
+        b: prefix,
+        added: b.len(),
+    };
+    if a.is_empty() && b.is_empty() {
+        return Vec::new();
+    }
+    if a.is_empty() || b.is_empty() {
+        return vec![whole];
+    }
+    match shortest_edit(a, b, &eq) {
+        Some(hunks) => hunks
+            .into_iter()
+            .map(|hunk| Hunk {
+                a: hunk.a + prefix,
+                b: hunk.b + prefix,
+                ..hunk
+            })
+            .collect(),
+        None => vec![whole],
+    }
+}
+
+/// Myers' shortest edit script from `a` to `b` as hunks, or `None` when
+/// it takes more than [`MAX_DIFF_EDITS`] edits.
+fn shortest_edit<T>(a: &[T], b: &[T], eq: &impl Fn(&T, &T) -> bool) -> Option<Vec<Hunk>> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let max = (n + m).min(MAX_DIFF_EDITS as isize);
+    let at = |k: isize| (k + max + 1) as usize;
+    let mut v = vec![0isize; 2 * max as usize + 3];
+    // `trace[d]` holds the furthest x on diagonals -d..=d before step d.
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+    let mut end = None;
+    'search: for d in 0..=max {
+        trace.push(v[at(-d)..=at(d)].to_vec());
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && v[at(k - 1)] < v[at(k + 1)]) {
+                v[at(k + 1)]
+            } else {
+                v[at(k - 1)] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && eq(&a[x as usize], &b[y as usize]) {
+                x += 1;
+                y += 1;
+            }
+            v[at(k)] = x;
+            if x >= n && y >= m {
+                end = Some(d);
+                break 'search;
+            }
+        }
+    }
+    let end = end?;
+
+    // Walk back from the end, collecting edits last to first.
+    let mut edits = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for d in (1..=end).rev() {
+        let before = &trace[d as usize];
+        let get = |k: isize| before[(k + d) as usize];
+        let k = x - y;
+        let inserted = k == -d || (k != d && get(k - 1) < get(k + 1));
+        let prev_k = if inserted { k + 1 } else { k - 1 };
+        let prev_x = get(prev_k);
+        let prev_y = prev_x - prev_k;
+        edits.push((prev_x as usize, prev_y as usize, !inserted));
+        x = prev_x;
+        y = prev_y;
+    }
+
+    let mut hunks: Vec<Hunk> = Vec::new();
+    for (x, y, removal) in edits.into_iter().rev() {
+        match hunks.last_mut() {
+            Some(hunk) if hunk.a + hunk.removed == x && hunk.b + hunk.added == y => {
+                if removal {
+                    hunk.removed += 1;
+                } else {
+                    hunk.added += 1;
+                }
+            }
+            _ => hunks.push(Hunk {
+                a: x,
+                removed: usize::from(removal),
+                b: y,
+                added: usize::from(!removal),
+            }),
+        }
+    }
+    Some(hunks)
+}
+
+/// Pairs of positions, in `a` and in `b`, of the items `hunks` leave
+/// unchanged.
+pub(crate) fn unchanged(hunks: &[Hunk], a_len: usize) -> impl Iterator<Item = (usize, usize)> + '_ {
+    let mut from = (0, 0);
+    hunks
+        .iter()
+        .map(Some)
+        .chain(std::iter::once(None))
+        .flat_map(move |hunk| {
+            let (a_start, b_start) = from;
+            let a_end = hunk.map_or(a_len, |h| h.a);
+            if let Some(h) = hunk {
+                from = (h.a + h.removed, h.b + h.added);
+            }
+            (0..a_end - a_start).map(move |i| (a_start + i, b_start + i))
+        })
+}
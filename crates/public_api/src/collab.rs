@@ -0,0 +1,759 @@
+//! Collaborative editing: keep replicas of one document in sync.
+//!
+//! A [`CollabSession`] pairs a [`TextDocument`] with a
+//! [`Replica`](frontend::common::collab::Replica) of its content. Edits
+//! made to the document through any API are turned into
+//! [`CollabOperation`](crate::CollabOperation)s by [`local_operations`](CollabSession::local_operations);
+//! operations from other sites are merged by
+//! [`apply_remote`](CollabSession::apply_remote), which carries them
+//! out on the document through cursors bound to a separate undo stack,
+//! so existing cursors follow the edits and undo stays limited to local
+//! changes. [`undo`](CollabSession::undo) takes back local changes by
+//! reverting their operations on the replica, which works whatever the
+//! other sites changed since.
+//!
+//! What travels between sites: text and inline images with their
+//! character formats, paragraph breaks with their block formats, list
+//! membership and list formats outside tables, and tables with the
+//! text of their cells.
+//!
+//! Out of scope, by design of the replicated sequence, which only
+//! knows units and the block each break opens:
+//!
+//! - frames: their paragraphs are replicated, the frame around them
+//!   and its format are not, so other sites see plain paragraphs;
+//! - table and cell formats (borders, padding, widths, backgrounds)
+//!   stay on the site that set them;
+//! - merged cells: other sites keep the cells apart, and a site keeps
+//!   its own merge only while the cells it covers stay empty;
+//! - a change to a table's shape (a row or column inserted or
+//!   removed) is sent as the table being deleted and inserted again
+//!   under a new key, so text typed into the old table's cells at the
+//!   same time ends up beside the new table rather than in it.
+//!
+//! Concurrent edits can leave a table without some of its cells, say
+//! when one site removes it while another splits a paragraph in it.
+//! Every site then shows what is left of it as plain paragraphs. So
+//! does every table of content that would hold nothing but cells, as a
+//! document always keeps a paragraph beside its tables.
+
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::{Result, bail};
+use frontend::common::collab::{
+    BlockProps, CellProps, Effect, ListProps, Operation, Replica, SiteId, UnitContent, UnitId,
+};
+use frontend::common::entities::Block;
+use frontend::common::format_runs::CharacterFormat;
+use frontend::common::types::EntityId;
+use frontend::list::dtos::ListDto;
+
+use crate::align;
+use crate::diff::{Side, align_items, patch};
+use crate::doc_blocks::{DocBlock, DocCell, fragment_of, read_blocks};
+use crate::{MoveMode, TextCursor, TextDocument, UndoStackId};
+
+/// Keys of the list and the table a block is in.
+type BlockKeys = (Option<UnitId>, Option<UnitId>);
+
+/// Keeps one [`TextDocument`] in sync with the other sites editing it.
+///
+/// Every site must start from the same content, for example by loading
+/// the same file, and use its own site id. Call
+/// [`local_operations`](Self::local_operations) after local edits and
+/// send the result to the other sites; hand what they send to
+/// [`apply_remote`](Self::apply_remote). Operations can be delivered in
+/// any order and more than once; once every site has applied every
+/// operation, the documents are the same.
+///
+/// Use the session's [`undo`](Self::undo) and [`redo`](Self::redo)
+/// while it runs: the document's own [`TextDocument::undo`] refuses to
+/// take back an edit once a remote edit has touched the same blocks.
+///
+/// ```
+/// use text_document::{CollabSession, TextDocument};
+///
+/// let (a, b) = (TextDocument::new(), TextDocument::new());
+/// a.set_plain_text("Hello").unwrap();
+/// b.set_plain_text("Hello").unwrap();
+/// let mut site_a = CollabSession::new(&a, 1).unwrap();
+/// let mut site_b = CollabSession::new(&b, 2).unwrap();
+///
+/// a.cursor_at(5).insert_text(" world").unwrap();
+/// b.cursor_at(0).insert_text("Oh, ").unwrap();
+/// let from_a = site_a.local_operations().unwrap();
+/// let from_b = site_b.local_operations().unwrap();
+/// site_a.apply_remote(from_b).unwrap();
+/// site_b.apply_remote(from_a).unwrap();
+///
+/// assert_eq!(a.to_plain_text().unwrap(), "Oh, Hello world");
+/// assert_eq!(b.to_plain_text().unwrap(), "Oh, Hello world");
+/// ```
+pub struct CollabSession {
+    doc: TextDocument,
+    replica: Replica,
+    /// Undo stack remote edits are recorded on, apart from local ones.
+    remote_stack: UndoStackId,
+    /// Operations captured but not yet handed out.
+    outbox: Vec<Operation>,
+    /// Local changes that can be undone, each as the operations one
+    /// capture recorded, oldest first.
+    undo_steps: Vec<Vec<Operation>>,
+    /// Undone changes, as the operations that undid them.
+    redo_steps: Vec<Vec<Operation>>,
+    /// Document list → key naming it on every site.
+    list_keys: HashMap<EntityId, UnitId>,
+    /// Document table → key naming it, with the shape it had then.
+    table_keys: HashMap<EntityId, (UnitId, usize, usize)>,
+}
+
+impl CollabSession {
+    /// Start a session on `doc` as site `site`. The document's current
+    /// content is the shared starting point. Site ids must be unique
+    /// among the sites and not `0`.
+    pub fn new(doc: &TextDocument, site: SiteId) -> Result<Self> {
+        let blocks = read_blocks(&doc.inner.lock())?;
+        let mut list_keys = HashMap::new();
+        let mut table_keys = HashMap::new();
+        // Name the starting lists and tables after the position their
+        // first block starts at, which every site computes the same way.
+        let mut version = 0;
+        for block in &blocks {
+            let key = UnitId { version, site: 0 };
+            if let Some(list) = &block.list {
+                list_keys.entry(list.id).or_insert(key);
+            }
+            if let Some(cell) = &block.cell {
+                table_keys
+                    .entry(cell.table)
+                    .or_insert((key, cell.rows, cell.columns));
+            }
+            version += block.units.len() as u64 + 1;
+        }
+        let (first_block, content) = to_units(&blocks, &list_keys, &table_keys);
+        let replica = Replica::new(site, first_block, content)?;
+        let remote_stack = doc.create_undo_stack();
+        Ok(CollabSession {
+            doc: doc.clone(),
+            replica,
+            remote_stack,
+            outbox: Vec::new(),
+            undo_steps: Vec::new(),
+            redo_steps: Vec::new(),
+            list_keys,
+            table_keys,
+        })
+    }
+
+    pub fn site(&self) -> SiteId {
+        self.replica.site()
+    }
+
+    /// Highest operation version applied from each site, this one
+    /// included.
+    pub fn version_vector(&self) -> BTreeMap<SiteId, u64> {
+        self.replica.version_vector().clone()
+    }
+
+    /// The undo stack remote edits are recorded on. Local undo never
+    /// steps through it.
+    pub fn remote_undo_stack(&self) -> UndoStackId {
+        self.remote_stack
+    }
+
+    /// Remote operations held back until the operations they depend on
+    /// arrive.
+    pub fn pending_count(&self) -> usize {
+        self.replica.pending_count()
+    }
+
+    /// Operations for the document changes made since the last call,
+    /// to be sent to every other site. Serializable with serde.
+    pub fn local_operations(&mut self) -> Result<Vec<Operation>> {
+        self.capture()?;
+        Ok(std::mem::take(&mut self.outbox))
+    }
+
+    /// Merge operations from other sites into the document.
+    ///
+    /// Local changes not yet collected are captured first and stay
+    /// queued for [`local_operations`](Self::local_operations). Once
+    /// merged, the document shows exactly the merged content, so
+    /// merging adds nothing to send.
+    pub fn apply_remote(&mut self, operations: impl IntoIterator<Item = Operation>) -> Result<()> {
+        let blocks = self.capture()?;
+        // A merged cell leaves out the positions of the cells it
+        // covers, which the replica keeps: past one, its positions are
+        // not the document's.
+        let mut in_step = blocks
+            .iter()
+            .all(|b| b.cell.is_none_or(|c| (c.row_span, c.column_span) == (1, 1)));
+        for op in operations {
+            for effect in self.replica.apply(op) {
+                in_step = in_step && self.replay(&effect)?;
+            }
+        }
+        self.reconcile()
+    }
+
+    /// Take back the last local change still in the history, and queue
+    /// the operations doing so for [`local_operations`](Self::local_operations).
+    ///
+    /// A change is what one call to [`local_operations`](Self::local_operations)
+    /// or [`apply_remote`](Self::apply_remote) collected, so call the
+    /// former after each edit to undo edit by edit. Only what the
+    /// change did is taken back: text other sites have added since
+    /// stays, even in the same paragraph. Does nothing when there is
+    /// nothing to undo.
+    pub fn undo(&mut self) -> Result<()> {
+        self.capture()?;
+        let Some(step) = self.undo_steps.pop() else {
+            return Ok(());
+        };
+        let reverted = self.revert(&step)?;
+        self.redo_steps.push(reverted);
+        self.reconcile()
+    }
+
+    /// Make the last undone change again. New local changes clear what
+    /// can be redone.
+    pub fn redo(&mut self) -> Result<()> {
+        self.capture()?;
+        let Some(step) = self.redo_steps.pop() else {
+            return Ok(());
+        };
+        let reverted = self.revert(&step)?;
+        self.undo_steps.push(reverted);
+        self.reconcile()
+    }
+
+    /// Whether [`undo`](Self::undo) has a change to take back. Local
+    /// changes not collected yet are not counted.
+    pub fn can_undo(&self) -> bool {
+        !self.undo_steps.is_empty()
+    }
+
+    /// Whether [`redo`](Self::redo) has a change to make again.
+    pub fn can_redo(&self) -> bool {
+        !self.redo_steps.is_empty()
+    }
+
+    /// Revert the operations of `step`, last first, and queue the
+    /// operations doing so.
+    fn revert(&mut self, step: &[Operation]) -> Result<Vec<Operation>> {
+        let mut reverted = Vec::new();
+        for op in step.iter().rev() {
+            reverted.extend(self.replica.revert(op)?);
+        }
+        self.outbox.extend(reverted.iter().cloned());
+        Ok(reverted)
+    }
+
+    // ── Document → replica ───────────────────────────────────
+
+    /// Record the differences between the document and the replica as
+    /// local operations, making one undo step. Returns the blocks read.
+    fn capture(&mut self) -> Result<Vec<DocBlock>> {
+        let mut step = Vec::new();
+        let blocks = read_blocks(&self.doc.inner.lock())?;
+        self.assign_keys(&blocks);
+        let (first_block, units) = to_units(&blocks, &self.list_keys, &self.table_keys);
+        // Compared with what the document shows, not with the replica
+        // itself, so cells shown as plain paragraphs stay as they are.
+        let (shown_first, current) = shown(&self.replica);
+
+        let hunks = align::diff_by(&current, &units, UnitContent::same_content);
+        // Last hunk first, so the positions of the others still hold.
+        for hunk in hunks.iter().rev() {
+            if hunk.removed > 0 {
+                step.push(self.replica.delete(hunk.a, hunk.removed)?);
+            }
+            if hunk.added > 0 {
+                let added = units[hunk.b..hunk.b + hunk.added].to_vec();
+                step.push(self.replica.insert(hunk.a, added)?);
+            }
+        }
+
+        if shown_first != first_block {
+            step.push(self.replica.set_block_props(0, &first_block)?);
+        }
+        // Units the hunks leave alone keep the formats they had.
+        let mut run: Option<(usize, usize, CharacterFormat)> = None;
+        for (old, position) in align::unchanged(&hunks, current.len()) {
+            let had = &current[old];
+            let wanted = &units[position];
+            if let (UnitContent::Break { props }, UnitContent::Break { props: had }) = (wanted, had)
+                && props != had
+            {
+                step.push(self.replica.set_block_props(position + 1, props)?);
+            }
+            let differs = match (wanted.format(), had.format()) {
+                (Some(format), Some(had)) if format != had => Some(format),
+                _ => None,
+            };
+            run = match (run.take(), differs) {
+                (Some((start, length, format)), Some(next))
+                    if start + length == position && &format == next =>
+                {
+                    Some((start, length + 1, format))
+                }
+                (done, next) => {
+                    if let Some((start, length, format)) = done {
+                        step.push(self.replica.format(start, length, &format)?);
+                    }
+                    next.map(|format| (position, 1, format.clone()))
+                }
+            };
+        }
+        if let Some((start, length, format)) = run {
+            step.push(self.replica.format(start, length, &format)?);
+        }
+        if !step.is_empty() {
+            self.outbox.extend(step.iter().cloned());
+            self.undo_steps.push(step);
+            self.redo_steps.clear();
+        }
+        Ok(blocks)
+    }
+
+    /// Give the document's new lists and tables, and tables whose shape
+    /// changed, a key of their own.
+    fn assign_keys(&mut self, blocks: &[DocBlock]) {
+        for block in blocks {
+            if let Some(list) = &block.list
+                && !self.list_keys.contains_key(&list.id)
+            {
+                self.list_keys.insert(list.id, self.replica.new_key());
+            }
+            if let Some(cell) = &block.cell {
+                let known = self.table_keys.get(&cell.table);
+                if known
+                    .is_none_or(|(_, rows, columns)| (*rows, *columns) != (cell.rows, cell.columns))
+                {
+                    let key = self.replica.new_key();
+                    self.table_keys
+                        .insert(cell.table, (key, cell.rows, cell.columns));
+                }
+            }
+        }
+    }
+
+    // ── Replica → document ───────────────────────────────────
+
+    /// Carry out one remote change on the document if it only touches
+    /// text outside tables, and return whether it did. Tables, and
+    /// formats, are left to [`reconcile`](Self::reconcile), which sets
+    /// them exactly.
+    fn replay(&self, effect: &Effect) -> Result<bool> {
+        match effect {
+            Effect::Insert { position, units } => {
+                let cells = units.iter().any(
+                    |unit| matches!(unit, UnitContent::Break { props } if props.cell.is_some()),
+                );
+                if cells || !self.outside_tables(*position, *position)? {
+                    return Ok(false);
+                }
+                // A run of characters sharing one format is typed in:
+                // it takes the format of the text before it, but unlike
+                // a fragment it leaves local undo of other blocks
+                // possible.
+                let cursor = self.cursor_at(*position);
+                match plain_run(units) {
+                    Some(text) => cursor.insert_text(&text)?,
+                    None => cursor.insert_fragment(&fragment_of(units))?,
+                }
+                Ok(true)
+            }
+            Effect::Delete { position, length } => {
+                if !self.outside_tables(*position, position + length)? {
+                    return Ok(false);
+                }
+                let cursor = self.cursor_at(*position);
+                cursor.set_position(position + length, MoveMode::KeepAnchor);
+                cursor.remove_selected_text()?;
+                Ok(true)
+            }
+            Effect::Format { .. } | Effect::BlockProps { .. } => Ok(true),
+        }
+    }
+
+    /// Edit the document until it shows the replica.
+    fn reconcile(&mut self) -> Result<()> {
+        let (first_block, units) = shown(&self.replica);
+        let blocks = read_blocks(&self.doc.inner.lock())?;
+        if to_units(&blocks, &self.list_keys, &self.table_keys)
+            == (first_block.clone(), units.clone())
+        {
+            return Ok(());
+        }
+
+        let (wanted, keys) = document_of(&first_block, &units, &blocks, &self.table_keys);
+        let new = Side::of(wanted);
+        let target = Side::read(&self.doc)?;
+        let steps = align_items(&target, &target.items, &new, &new.items);
+        patch(&self.doc, &self.cursor_at(0), &target, &new, &steps)?;
+
+        // The document's lists and tables now stand for the replica's.
+        let now = read_blocks(&self.doc.inner.lock())?;
+        self.list_keys.clear();
+        self.table_keys.clear();
+        for (block, (list, table)) in now.iter().zip(keys) {
+            if let (Some(doc_list), Some(key)) = (&block.list, list) {
+                self.list_keys.insert(doc_list.id, key);
+            }
+            if let (Some(cell), Some(key)) = (block.cell, table) {
+                self.table_keys
+                    .insert(cell.table, (key, cell.rows, cell.columns));
+            }
+        }
+        if to_units(&now, &self.list_keys, &self.table_keys) != (first_block, units) {
+            bail!("the document does not show the merged content");
+        }
+        Ok(())
+    }
+
+    // ── Helpers ──────────────────────────────────────────────
+
+    /// A cursor at `position` whose edits go to the remote undo stack.
+    fn cursor_at(&self, position: usize) -> TextCursor {
+        let cursor = self.doc.cursor_at(position);
+        cursor.set_undo_stack(Some(self.remote_stack));
+        cursor
+    }
+
+    /// Whether `start..=end` lies in the document and every block it
+    /// touches is outside tables.
+    fn outside_tables(&self, start: usize, end: usize) -> Result<bool> {
+        let blocks = read_blocks(&self.doc.inner.lock())?;
+        let length = blocks
+            .last()
+            .map_or(0, |block| block.position + block.units.len());
+        Ok(end <= length
+            && blocks
+                .iter()
+                .filter(|block| {
+                    block.position <= end && start <= block.position + block.units.len()
+                })
+                .all(|block| block.cell.is_none()))
+    }
+}
+
+/// The text of `units` if they are all characters in one format.
+fn plain_run(units: &[UnitContent]) -> Option<String> {
+    let format = units.first()?.format();
+    units
+        .iter()
+        .map(|unit| match unit {
+            UnitContent::Char { ch, format: f } if Some(f) == format => Some(*ch),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The first block's properties and the units after it. Lists and
+/// tables without a key, and lists of cells, are left out. Each cell a merged cell covers
+/// counts as an empty block.
+fn to_units(
+    blocks: &[DocBlock],
+    list_keys: &HashMap<EntityId, UnitId>,
+    table_keys: &HashMap<EntityId, (UnitId, usize, usize)>,
+) -> (BlockProps, Vec<UnitContent>) {
+    let cell_props = |cell: &DocCell, n: usize| {
+        Some(CellProps {
+            table: table_keys.get(&cell.table)?.0,
+            row: n / cell.columns,
+            column: n % cell.columns,
+        })
+    };
+    let props = |block: &DocBlock| {
+        let list = block
+            .list
+            .as_ref()
+            .filter(|_| block.cell.is_none())
+            .and_then(|list| {
+                Some(ListProps {
+                    list: *list_keys.get(&list.id)?,
+                    style: list.style.clone(),
+                    indent: list.indent,
+                    prefix: list.prefix.clone(),
+                    suffix: list.suffix.clone(),
+                })
+            });
+        let cell = block
+            .cell
+            .and_then(|cell| cell_props(&cell, cell.row * cell.columns + cell.column));
+        BlockProps::from_block(&block.block, list, cell)
+    };
+    let covered = |cell: &DocCell, cells: std::ops::Range<usize>| {
+        cells
+            .map(|n| UnitContent::Break {
+                props: BlockProps {
+                    cell: cell_props(cell, n),
+                    ..BlockProps::default()
+                },
+            })
+            .collect::<Vec<_>>()
+    };
+    let index = |cell: &DocCell| cell.row * cell.columns + cell.column;
+
+    let mut first_block = BlockProps::default();
+    let mut units = Vec::new();
+    let mut previous: Option<DocCell> = None;
+    for (i, block) in blocks.iter().enumerate() {
+        let same_table = previous
+            .zip(block.cell)
+            .is_some_and(|(p, c)| p.table == c.table);
+        if let Some(p) = previous.filter(|_| !same_table) {
+            units.extend(covered(&p, index(&p) + 1..p.rows * p.columns));
+        }
+        if let Some(cell) = &block.cell {
+            let from = previous.filter(|_| same_table).map_or(0, |p| index(&p) + 1);
+            units.extend(covered(cell, from..index(cell)));
+        }
+        if i == 0 {
+            first_block = props(block);
+        } else {
+            units.push(UnitContent::Break {
+                props: props(block),
+            });
+        }
+        units.extend(block.units.iter().cloned());
+        previous = block.cell;
+    }
+    if let Some(p) = previous {
+        units.extend(covered(&p, index(&p) + 1..p.rows * p.columns));
+    }
+    (first_block, units)
+}
+
+/// The replica's content as the document shows it. A table runs from
+/// a block opening the first cell of a table key to the last block of
+/// that key, if its cells come row by row with none missing; blocks in
+/// between that name another cell, or none, join the cell before them.
+/// Cells that do not make up a table this way show as plain
+/// paragraphs, as do all tables when no paragraph is left beside them,
+/// and cells are never in a list.
+fn shown(replica: &Replica) -> (BlockProps, Vec<UnitContent>) {
+    let mut first_block = replica.first_block().clone();
+    let mut units: Vec<UnitContent> = replica.content().cloned().collect();
+    let mut cells = vec![first_block.cell.clone()];
+    cells.extend(units.iter().filter_map(|unit| match unit {
+        UnitContent::Break { props } => Some(props.cell.clone()),
+        _ => None,
+    }));
+    let mut last = HashMap::new();
+    for (n, cell) in cells.iter().enumerate() {
+        if let Some(cell) = cell {
+            last.insert(cell.table, n);
+        }
+    }
+
+    let mut shown = vec![None; cells.len()];
+    let mut n = 0;
+    while n < cells.len() {
+        let Some(first) = cells[n]
+            .as_ref()
+            .filter(|c| (c.row, c.column) == (0, 0) && whole_table(&cells[n..=last[&c.table]]))
+        else {
+            n += 1;
+            continue;
+        };
+        let end = last[&first.table];
+        let mut cell = first.clone();
+        for k in n..=end {
+            if let Some(next) = cells[k].as_ref().filter(|c| c.table == first.table) {
+                cell = next.clone();
+            }
+            shown[k] = Some(cell.clone());
+        }
+        n = end + 1;
+    }
+    if shown.iter().all(Option::is_some) {
+        shown.fill(None);
+    }
+
+    // Cells are never in lists.
+    let mut shown = shown.into_iter();
+    let mut show = |props: &mut BlockProps| {
+        props.cell = shown.next().flatten();
+        if props.cell.is_some() {
+            props.list = None;
+        }
+    };
+    show(&mut first_block);
+    for unit in &mut units {
+        if let UnitContent::Break { props } = unit {
+            show(props);
+        }
+    }
+    (first_block, units)
+}
+
+/// Whether the cells of the first one's table among `cells` come row
+/// by row, from the first to the last, with none missing.
+fn whole_table(cells: &[Option<CellProps>]) -> bool {
+    let Some(Some(first)) = cells.first() else {
+        return false;
+    };
+    let own: Vec<(usize, usize)> = cells
+        .iter()
+        .flatten()
+        .filter(|c| c.table == first.table)
+        .map(|c| (c.row, c.column))
+        .collect();
+    let (rows, columns) = shape(&own);
+    let index = |&(row, column): &(usize, usize)| row * columns + column;
+    own.first() == Some(&(0, 0))
+        && own
+            .windows(2)
+            .all(|pair| matches!(index(&pair[1]).checked_sub(index(&pair[0])), Some(0 | 1)))
+        && own.last().map(index) == Some(rows * columns - 1)
+}
+
+/// Rows and columns a table's `cells` take.
+fn shape(cells: &[(usize, usize)]) -> (usize, usize) {
+    let rows = cells.iter().map(|&(row, _)| row + 1).max().unwrap_or(0);
+    let columns = cells
+        .iter()
+        .map(|&(_, column)| column + 1)
+        .max()
+        .unwrap_or(0);
+    (rows, columns)
+}
+
+/// The blocks of the document `first_block` and `units` describe, with
+/// the keys of each block's list and table. Lists and tables get ids of
+/// their own. A merged cell of the current `blocks` stays merged while
+/// the cells it covers are empty.
+fn document_of(
+    first_block: &BlockProps,
+    units: &[UnitContent],
+    blocks: &[DocBlock],
+    table_keys: &HashMap<EntityId, (UnitId, usize, usize)>,
+) -> (Vec<DocBlock>, Vec<BlockKeys>) {
+    let mut split: Vec<(&BlockProps, Vec<UnitContent>)> = vec![(first_block, Vec::new())];
+    for unit in units {
+        match unit {
+            UnitContent::Break { props } => split.push((props, Vec::new())),
+            _ => split
+                .last_mut()
+                .expect("the first block")
+                .1
+                .push(unit.clone()),
+        }
+    }
+    let mut cells: HashMap<(UnitId, usize, usize), Vec<usize>> = HashMap::new();
+    for (n, (props, _)) in split.iter().enumerate() {
+        if let Some(cell) = &props.cell {
+            cells
+                .entry((cell.table, cell.row, cell.column))
+                .or_default()
+                .push(n);
+        }
+    }
+    let mut shapes: HashMap<UnitId, Vec<(usize, usize)>> = HashMap::new();
+    for &(table, row, column) in cells.keys() {
+        shapes.entry(table).or_default().push((row, column));
+    }
+    let shapes: HashMap<UnitId, (usize, usize)> = shapes
+        .into_iter()
+        .map(|(table, cells)| (table, shape(&cells)))
+        .collect();
+
+    // Merged cells the replica leaves alone: cells → spans, and the
+    // blocks of the cells they cover.
+    let mut spans = HashMap::new();
+    let mut dropped = vec![false; split.len()];
+    for cell in blocks.iter().filter_map(|block| block.cell) {
+        let Some(&(key, ..)) = table_keys.get(&cell.table) else {
+            continue;
+        };
+        if (cell.row_span, cell.column_span) == (1, 1)
+            || shapes.get(&key) != Some(&(cell.rows, cell.columns))
+        {
+            continue;
+        }
+        let covered: Vec<usize> = (cell.row..cell.row + cell.row_span)
+            .flat_map(|row| (cell.column..cell.column + cell.column_span).map(move |c| (row, c)))
+            .filter(|&at| at != (cell.row, cell.column))
+            .filter_map(
+                |(row, column)| match cells.get(&(key, row, column))?.as_slice() {
+                    &[n] if split[n].1.is_empty()
+                        && *split[n].0
+                            == (BlockProps {
+                                cell: split[n].0.cell.clone(),
+                                ..BlockProps::default()
+                            }) =>
+                    {
+                        Some(n)
+                    }
+                    _ => None,
+                },
+            )
+            .collect();
+        if covered.len() + 1 == cell.row_span * cell.column_span {
+            spans.insert(
+                (key, cell.row, cell.column),
+                (cell.row_span, cell.column_span),
+            );
+            for n in covered {
+                dropped[n] = true;
+            }
+        }
+    }
+
+    let mut ids: HashMap<UnitId, EntityId> = HashMap::new();
+    let mut id_of = |key: UnitId| {
+        let next = ids.len() as EntityId + 1;
+        *ids.entry(key).or_insert(next)
+    };
+    let mut wanted = Vec::new();
+    let mut keys = Vec::new();
+    let mut position = 0;
+    for (n, (props, units)) in split.into_iter().enumerate() {
+        if dropped[n] {
+            continue;
+        }
+        let mut block = Block::default();
+        props.apply_format_to(&mut block);
+        let list = props.list.as_ref().map(|list| ListDto {
+            id: id_of(list.list),
+            style: list.style.clone(),
+            indent: list.indent,
+            prefix: list.prefix.clone(),
+            suffix: list.suffix.clone(),
+            ..ListDto::default()
+        });
+        let cell = props.cell.as_ref().map(|cell| {
+            let (rows, columns) = shapes[&cell.table];
+            let (row_span, column_span) = spans
+                .get(&(cell.table, cell.row, cell.column))
+                .copied()
+                .unwrap_or((1, 1));
+            DocCell {
+                table: id_of(cell.table),
+                rows,
+                columns,
+                row: cell.row,
+                column: cell.column,
+                row_span,
+                column_span,
+            }
+        });
+        keys.push((
+            props.list.as_ref().map(|list| list.list),
+            props.cell.as_ref().map(|cell| cell.table),
+        ));
+        let length = units.len();
+        wanted.push(DocBlock {
+            id: n as EntityId,
+            block,
+            list,
+            cell,
+            position,
+            units,
+        });
+        position += length + 1;
+    }
+    (wanted, keys)
+}
@@ -4,9 +4,10 @@
 //! All Option mapping between public format structs and backend DTOs lives here.
 
 use crate::{
-    BlockFormat, BlockInfo, DocumentStats, FindMatch, FindOptions, FrameFormat, ListFormat,
-    TextFormat,
+    BlockFormat, BlockInfo, Color, DocumentStats, FindMatch, FindOptions, FormatQuery, FrameFormat,
+    ListFormat, SearchHit, SearchScope, TableCellContext, TextFormat,
 };
+use frontend::common::parser_tools::css_color::{format_hex_color, parse_css_color};
 
 // ── Position conversion ─────────────────────────────────────────
 
@@ -57,6 +58,7 @@ impl FindOptions {
         query: &str,
         start_position: usize,
     ) -> frontend::document_search::FindTextDto {
+        let (scope_start, scope_end, scope_frame_id, scope_table_id) = scope_fields(self.scope);
         frontend::document_search::FindTextDto {
             query: query.into(),
             case_sensitive: self.case_sensitive,
@@ -64,15 +66,38 @@ impl FindOptions {
             use_regex: self.use_regex,
             search_backward: self.search_backward,
             start_position: to_i64(start_position),
+            scope_start,
+            scope_end,
+            scope_frame_id,
+            scope_table_id,
+            normalization: self.normalization.clone(),
+            fold_accents: self.fold_accents,
+            fold_width: self.fold_width,
+            max_edits: self.fuzzy.map(to_i64),
+            with_details: false,
+            context_chars: to_i64(self.context_chars),
         }
     }
 
     pub(crate) fn to_find_all_dto(&self, query: &str) -> frontend::document_search::FindAllDto {
+        let (scope_start, scope_end, scope_frame_id, scope_table_id) = scope_fields(self.scope);
         frontend::document_search::FindAllDto {
             query: query.into(),
             case_sensitive: self.case_sensitive,
             whole_word: self.whole_word,
             use_regex: self.use_regex,
+            scope_start,
+            scope_end,
+            scope_frame_id,
+            scope_table_id,
+            normalization: self.normalization.clone(),
+            fold_accents: self.fold_accents,
+            fold_width: self.fold_width,
+            max_edits: self.fuzzy.map(to_i64),
+            start_position: None,
+            max_results: None,
+            with_details: false,
+            context_chars: to_i64(self.context_chars),
         }
     }
 
@@ -82,6 +107,7 @@ impl FindOptions {
         replacement: &str,
         replace_all: bool,
     ) -> frontend::document_search::ReplaceTextDto {
+        let (scope_start, scope_end, scope_frame_id, scope_table_id) = scope_fields(self.scope);
         frontend::document_search::ReplaceTextDto {
             query: query.into(),
             replacement: replacement.into(),
@@ -89,6 +115,53 @@ impl FindOptions {
             whole_word: self.whole_word,
             use_regex: self.use_regex,
             replace_all,
+            preserve_case: self.preserve_case,
+            cross_block: self.cross_block,
+            scope_start,
+            scope_end,
+            scope_frame_id,
+            scope_table_id,
+            normalization: self.normalization.clone(),
+            fold_accents: self.fold_accents,
+            fold_width: self.fold_width,
+        }
+    }
+}
+
+/// `(scope_start, scope_end, scope_frame_id, scope_table_id)` of the
+/// search DTOs.
+fn scope_fields(
+    scope: Option<SearchScope>,
+) -> (Option<i64>, Option<i64>, Option<i64>, Option<i64>) {
+    match scope {
+        None => (None, None, None, None),
+        Some(SearchScope::Range { start, end }) => {
+            (Some(to_i64(start)), Some(to_i64(end)), None, None)
+        }
+        Some(SearchScope::Frame(id)) => (None, None, Some(to_i64(id)), None),
+        Some(SearchScope::Table(id)) => (None, None, None, Some(to_i64(id))),
+    }
+}
+
+impl FormatQuery {
+    pub(crate) fn to_find_by_format_dto(&self) -> frontend::document_search::FindByFormatDto {
+        let (scope_start, scope_end, scope_frame_id, scope_table_id) = scope_fields(self.scope);
+        frontend::document_search::FindByFormatDto {
+            font_family: self.font_family.clone(),
+            font_bold: self.font_bold,
+            font_italic: self.font_italic,
+            font_underline: self.font_underline,
+            font_strikeout: self.font_strikeout,
+            foreground_color: self.foreground_color.as_ref().map(color_to_string),
+            background_color: self.background_color.as_ref().map(color_to_string),
+            anchor_href: self.anchor_href.clone(),
+            heading_level: self.heading_level.map(i64::from),
+            is_code_block: self.is_code_block,
+            code_language: self.code_language.clone(),
+            scope_start,
+            scope_end,
+            scope_frame_id,
+            scope_table_id,
         }
     }
 }
@@ -115,6 +188,68 @@ pub fn find_all_to_matches(dto: &frontend::document_search::FindAllResultDto) ->
         .collect()
 }
 
+fn table_cell_context(table_id: i64, row: i64, column: i64) -> Option<TableCellContext> {
+    (table_id != 0).then(|| TableCellContext {
+        table_id: to_usize(table_id),
+        row: to_usize(row),
+        column: to_usize(column),
+    })
+}
+
+fn capture_ranges(positions: &[i64], lengths: &[i64]) -> Vec<Option<(usize, usize)>> {
+    positions
+        .iter()
+        .zip(lengths)
+        .map(|(&pos, &len)| (pos >= 0).then(|| (to_usize(pos), to_usize(len))))
+        .collect()
+}
+
+pub fn find_result_to_hit(dto: &frontend::document_search::FindResultDto) -> Option<SearchHit> {
+    dto.found.then(|| SearchHit {
+        position: to_usize(dto.position),
+        length: to_usize(dto.length),
+        block_id: to_usize(dto.block_id),
+        block_position: to_usize(dto.block_position),
+        frame_id: to_usize(dto.frame_id),
+        table_cell: table_cell_context(dto.table_id, dto.cell_row, dto.cell_column),
+        context_before: dto.context_before.clone(),
+        context_after: dto.context_after.clone(),
+        captures: capture_ranges(&dto.capture_positions, &dto.capture_lengths),
+        distance: to_usize(dto.distance),
+    })
+}
+
+/// Hits of a `find_all` run with `with_details`.
+pub fn find_all_to_hits(dto: &frontend::document_search::FindAllResultDto) -> Vec<SearchHit> {
+    let mut capture_start = 0;
+    (0..dto.positions.len())
+        .map(|i| {
+            let capture_end = capture_start + to_usize(dto.capture_counts[i]);
+            let captures = capture_ranges(
+                &dto.capture_positions[capture_start..capture_end],
+                &dto.capture_lengths[capture_start..capture_end],
+            );
+            capture_start = capture_end;
+            SearchHit {
+                position: to_usize(dto.positions[i]),
+                length: to_usize(dto.lengths[i]),
+                block_id: to_usize(dto.block_ids[i]),
+                block_position: to_usize(dto.block_positions[i]),
+                frame_id: to_usize(dto.frame_ids[i]),
+                table_cell: table_cell_context(
+                    dto.table_ids[i],
+                    dto.cell_rows[i],
+                    dto.cell_columns[i],
+                ),
+                context_before: dto.contexts_before[i].clone(),
+                context_after: dto.contexts_after[i].clone(),
+                captures,
+                distance: dto.distances.get(i).copied().map_or(0, to_usize),
+            }
+        })
+        .collect()
+}
+
 // ── Domain ↔ DTO enum conversions ───────────────────────────────
 //
 // The DTO layer has its own enum types, separate from domain enums
@@ -196,6 +331,9 @@ impl TextFormat {
                 .vertical_alignment
                 .as_ref()
                 .map(vertical_alignment_to_dto),
+            foreground_color: self.foreground_color.as_ref().map(color_to_string),
+            background_color: self.background_color.as_ref().map(color_to_string),
+            underline_color: self.underline_color.as_ref().map(color_to_string),
         }
     }
 
@@ -212,6 +350,9 @@ impl TextFormat {
             font_italic: self.font_italic,
             font_underline: self.font_underline,
             font_strikeout: self.font_strikeout,
+            foreground_color: self.foreground_color.as_ref().map(color_to_string),
+            background_color: self.background_color.as_ref().map(color_to_string),
+            underline_color: self.underline_color.as_ref().map(color_to_string),
         }
     }
 }
@@ -237,13 +378,23 @@ impl From<&frontend::common::format_runs::CharacterFormat> for TextFormat {
             anchor_names: fmt.anchor_names.clone(),
             is_anchor: fmt.is_anchor,
             tooltip: fmt.tooltip.clone(),
-            foreground_color: None,
-            background_color: None,
-            underline_color: None,
+            foreground_color: fmt.foreground_color.as_deref().and_then(color_from_string),
+            background_color: fmt.background_color.as_deref().and_then(color_from_string),
+            underline_color: fmt.underline_color.as_deref().and_then(color_from_string),
         }
     }
 }
 
+// ── Color ↔ stored `#rrggbb[aa]` string ─────────────────────────
+
+fn color_to_string(c: &Color) -> String {
+    format_hex_color([c.red, c.green, c.blue, c.alpha])
+}
+
+fn color_from_string(s: &str) -> Option<Color> {
+    parse_css_color(s).map(|[r, g, b, a]| Color::rgba(r, g, b, a))
+}
+
 // ── BlockFormat ─────────────────────────────────────────────────
 
 impl BlockFormat {
@@ -9,18 +9,23 @@ use anyhow::Result;
 use crate::ListStyle;
 use frontend::commands::{
     document_editing_commands, document_formatting_commands, document_inspection_commands,
-    undo_redo_commands,
+    document_search_commands, undo_redo_commands,
 };
 
+use frontend::common::undo_redo::{EditSelection, SelectionState};
 use unicode_segmentation::UnicodeSegmentation;
 
-use crate::convert::{to_i64, to_usize};
+use crate::convert::{self, to_i64, to_usize};
+use crate::edit_log::{EditAction, LoggedCursor};
 use crate::events::DocumentEvent;
 use crate::flow::{CellRange, FlowElement, SelectionKind, TableCellRef};
 use crate::fragment::DocumentFragment;
 use crate::inner::{CursorData, QueuedEvents, TextDocumentInner};
 use crate::text_table::TextTable;
-use crate::{BlockFormat, FrameFormat, MoveMode, MoveOperation, SelectionType, TextFormat};
+use crate::{
+    BlockFormat, FindMatch, FindOptions, FrameFormat, MoveMode, MoveOperation, SearchScope,
+    SelectionType, TextFormat, UndoStackId,
+};
 
 use crate::document::get_main_frame_id;
 
@@ -29,7 +34,9 @@ use crate::document::get_main_frame_id;
 /// Cursor positions include block separators (one between each pair of adjacent
 /// blocks), but `character_count` does not. The max position is therefore
 /// `character_count + (block_count - 1)`.
-fn max_cursor_position(stats: &frontend::document_inspection::DocumentStatsDto) -> usize {
+pub(crate) fn max_cursor_position(
+    stats: &frontend::document_inspection::DocumentStatsDto,
+) -> usize {
     let chars = to_usize(stats.character_count);
     let blocks = to_usize(stats.block_count);
     if blocks > 1 {
@@ -39,6 +46,27 @@ fn max_cursor_position(stats: &frontend::document_inspection::DocumentStatsDto)
     }
 }
 
+/// Run `edit` as one composite undo step on `stack_id`. If it fails the
+/// composite is cancelled, undoing whatever part of it already ran, so
+/// no half-finished group is left open to swallow later edits.
+fn in_composite<T>(
+    ctx: &frontend::AppContext,
+    stack_id: u64,
+    edit: impl FnOnce() -> Result<T>,
+) -> Result<T> {
+    undo_redo_commands::begin_composite(ctx, Some(stack_id));
+    match edit() {
+        Ok(value) => {
+            undo_redo_commands::end_composite(ctx);
+            Ok(value)
+        }
+        Err(e) => {
+            undo_redo_commands::cancel_composite(ctx);
+            Err(e)
+        }
+    }
+}
+
 /// A cursor into a [`TextDocument`](crate::TextDocument).
 ///
 /// Multiple cursors can coexist on the same document (like Qt's `QTextCursor`).
@@ -59,12 +87,8 @@ impl Clone for TextCursor {
         };
         let data = {
             let mut inner = self.doc.lock();
-            let data = Arc::new(Mutex::new(CursorData {
-                position,
-                anchor,
-                cell_selection_override: None,
-            }));
-            inner.cursors.push(Arc::downgrade(&data));
+            let data = inner.register_cursor(position);
+            data.lock().anchor = anchor;
             data
         };
         TextCursor {
@@ -88,17 +112,28 @@ impl TextCursor {
     fn finish_edit(
         &self,
         inner: &mut TextDocumentInner,
+        stack_id: u64,
         edit_pos: usize,
         removed: usize,
         new_pos: usize,
         blocks_affected: usize,
     ) -> QueuedEvents {
-        self.finish_edit_ext(inner, edit_pos, removed, new_pos, blocks_affected, true)
+        self.finish_edit_ext(
+            inner,
+            stack_id,
+            edit_pos,
+            removed,
+            new_pos,
+            blocks_affected,
+            true,
+        )
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn finish_edit_ext(
         &self,
         inner: &mut TextDocumentInner,
+        stack_id: u64,
         edit_pos: usize,
         removed: usize,
         new_pos: usize,
@@ -111,6 +146,7 @@ impl TextCursor {
         // is no longer valid — fuzz finds this). Treat the edit as adding
         // 0 chars rather than overflowing; the cursor still moves to
         // `new_pos` below.
+        let before = self.selection_state();
         let added = new_pos.saturating_sub(edit_pos);
         inner.adjust_cursors(edit_pos, removed, added);
         {
@@ -131,7 +167,7 @@ impl TextCursor {
         if flow_may_change {
             inner.check_flow_changed();
         }
-        self.queue_undo_redo_event(inner)
+        self.queue_undo_redo_event(inner, stack_id, before)
     }
 
     // ── Position & selection ─────────────────────────────────
@@ -400,48 +436,48 @@ impl TextCursor {
 
         let queued = {
             let mut inner = self.doc.lock();
-            let result = match document_editing_commands::insert_text(
-                &inner.ctx,
-                Some(inner.stack_id),
-                &dto,
-            ) {
-                Ok(r) => r,
-                Err(_) if pos != anchor => {
-                    // Cross-block selection: compose delete + insert as a single undo unit
-                    undo_redo_commands::begin_composite(&inner.ctx, Some(inner.stack_id));
-
-                    let del_dto = frontend::document_editing::DeleteTextDto {
-                        position: to_i64(pos),
-                        anchor: to_i64(anchor),
-                    };
-                    let del_result = document_editing_commands::delete_text(
-                        &inner.ctx,
-                        Some(inner.stack_id),
-                        &del_dto,
-                    )?;
-                    let del_pos = to_usize(del_result.new_position);
-
-                    let ins_dto = frontend::document_editing::InsertTextDto {
-                        position: to_i64(del_pos),
-                        anchor: to_i64(del_pos),
-                        text: text.into(),
-                    };
-                    let ins_result = document_editing_commands::insert_text(
-                        &inner.ctx,
-                        Some(inner.stack_id),
-                        &ins_dto,
-                    )?;
-
-                    undo_redo_commands::end_composite(&inner.ctx);
-                    ins_result
-                }
-                Err(e) => return Err(e),
-            };
+            let stack_id = self.edit_stack(&mut inner);
+            let logged = self.logged_cursor(stack_id);
+            let result =
+                match document_editing_commands::insert_text(&inner.ctx, Some(stack_id), &dto) {
+                    Ok(r) => r,
+                    Err(_) if pos != anchor => {
+                        // Cross-block selection: compose delete + insert as a single undo unit
+                        in_composite(&inner.ctx, stack_id, || {
+                            let del_dto = frontend::document_editing::DeleteTextDto {
+                                position: to_i64(pos),
+                                anchor: to_i64(anchor),
+                            };
+                            let del_result = document_editing_commands::delete_text(
+                                &inner.ctx,
+                                Some(stack_id),
+                                &del_dto,
+                            )?;
+                            let del_pos = to_usize(del_result.new_position);
+
+                            let ins_dto = frontend::document_editing::InsertTextDto {
+                                position: to_i64(del_pos),
+                                anchor: to_i64(del_pos),
+                                text: text.into(),
+                            };
+                            document_editing_commands::insert_text(
+                                &inner.ctx,
+                                Some(stack_id),
+                                &ins_dto,
+                            )
+                        })?
+                    }
+                    Err(e) => return Err(e),
+                };
 
+            inner.log_edit(Some(logged), || EditAction::InsertText {
+                text: text.into(),
+            });
             let edit_pos = pos.min(anchor);
             let removed = pos.max(anchor) - edit_pos;
             self.finish_edit_ext(
                 &mut inner,
+                stack_id,
                 edit_pos,
                 removed,
                 to_usize(result.new_position),
@@ -471,43 +507,47 @@ impl TextCursor {
 
         let queued = {
             let mut inner = self.doc.lock();
+            let stack_id = self.edit_stack(&mut inner);
+            let logged = self.logged_cursor(stack_id);
             let result = match document_editing_commands::insert_formatted_text(
                 &inner.ctx,
-                Some(inner.stack_id),
+                Some(stack_id),
                 &make_dto(pos, anchor),
             ) {
                 Ok(r) => r,
                 Err(_) if pos != anchor => {
                     // Cross-block selection: compose delete + insert as a single undo unit
-                    undo_redo_commands::begin_composite(&inner.ctx, Some(inner.stack_id));
-
-                    let del_dto = frontend::document_editing::DeleteTextDto {
-                        position: to_i64(pos),
-                        anchor: to_i64(anchor),
-                    };
-                    let del_result = document_editing_commands::delete_text(
-                        &inner.ctx,
-                        Some(inner.stack_id),
-                        &del_dto,
-                    )?;
-                    let del_pos = to_usize(del_result.new_position);
-
-                    let ins_result = document_editing_commands::insert_formatted_text(
-                        &inner.ctx,
-                        Some(inner.stack_id),
-                        &make_dto(del_pos, del_pos),
-                    )?;
-
-                    undo_redo_commands::end_composite(&inner.ctx);
-                    ins_result
+                    in_composite(&inner.ctx, stack_id, || {
+                        let del_dto = frontend::document_editing::DeleteTextDto {
+                            position: to_i64(pos),
+                            anchor: to_i64(anchor),
+                        };
+                        let del_result = document_editing_commands::delete_text(
+                            &inner.ctx,
+                            Some(stack_id),
+                            &del_dto,
+                        )?;
+                        let del_pos = to_usize(del_result.new_position);
+
+                        document_editing_commands::insert_formatted_text(
+                            &inner.ctx,
+                            Some(stack_id),
+                            &make_dto(del_pos, del_pos),
+                        )
+                    })?
                 }
                 Err(e) => return Err(e),
             };
 
+            inner.log_edit(Some(logged), || EditAction::InsertFormattedText {
+                text: text.into(),
+                format: format.clone(),
+            });
             let edit_pos = pos.min(anchor);
             let removed = pos.max(anchor) - edit_pos;
             self.finish_edit_ext(
                 &mut inner,
+                stack_id,
                 edit_pos,
                 removed,
                 to_usize(result.new_position),
@@ -524,41 +564,40 @@ impl TextCursor {
         let (pos, anchor) = self.read_cursor();
         let queued = {
             let mut inner = self.doc.lock();
+            let stack_id = self.edit_stack(&mut inner);
+            let logged = self.logged_cursor(stack_id);
 
-            let (insert_pos, removed) = if pos != anchor {
-                // Selection active: delete first, then split (Word convention)
-                undo_redo_commands::begin_composite(&inner.ctx, Some(inner.stack_id));
-                let del_dto = frontend::document_editing::DeleteTextDto {
-                    position: to_i64(pos),
-                    anchor: to_i64(anchor),
+            let insert = |insert_pos: usize| {
+                let dto = frontend::document_editing::InsertBlockDto {
+                    position: to_i64(insert_pos),
+                    anchor: to_i64(insert_pos),
                 };
-                let del_result = document_editing_commands::delete_text(
-                    &inner.ctx,
-                    Some(inner.stack_id),
-                    &del_dto,
-                )?;
-                (
-                    to_usize(del_result.new_position),
-                    pos.max(anchor) - pos.min(anchor),
-                )
-            } else {
-                (pos, 0)
+                document_editing_commands::insert_block(&inner.ctx, Some(stack_id), &dto)
             };
-
-            let dto = frontend::document_editing::InsertBlockDto {
-                position: to_i64(insert_pos),
-                anchor: to_i64(insert_pos),
+            let (result, removed) = if pos != anchor {
+                // Selection active: delete first, then split (Word convention)
+                let result = in_composite(&inner.ctx, stack_id, || {
+                    let del_dto = frontend::document_editing::DeleteTextDto {
+                        position: to_i64(pos),
+                        anchor: to_i64(anchor),
+                    };
+                    let del_result = document_editing_commands::delete_text(
+                        &inner.ctx,
+                        Some(stack_id),
+                        &del_dto,
+                    )?;
+                    insert(to_usize(del_result.new_position))
+                })?;
+                (result, pos.max(anchor) - pos.min(anchor))
+            } else {
+                (insert(pos)?, 0)
             };
-            let result =
-                document_editing_commands::insert_block(&inner.ctx, Some(inner.stack_id), &dto)?;
-
-            if pos != anchor {
-                undo_redo_commands::end_composite(&inner.ctx);
-            }
 
+            inner.log_edit(Some(logged), || EditAction::InsertBlock);
             let edit_pos = pos.min(anchor);
             self.finish_edit(
                 &mut inner,
+                stack_id,
                 edit_pos,
                 removed,
                 to_usize(result.new_position),
@@ -573,55 +612,75 @@ impl TextCursor {
     pub fn insert_html(&self, html: &str) -> Result<()> {
         // Delegate to insert_fragment so table structure is preserved.
         let frag = DocumentFragment::from_html(html);
-        self.insert_fragment(&frag)
+        self.insert_fragment_as(&frag, || EditAction::InsertHtml { html: html.into() })
     }
 
     /// Insert a Markdown fragment at the cursor position. Replaces selection if any.
     pub fn insert_markdown(&self, markdown: &str) -> Result<()> {
         let frag = DocumentFragment::from_markdown(markdown);
-        self.insert_fragment(&frag)
+        self.insert_fragment_as(&frag, || EditAction::InsertMarkdown {
+            markdown: markdown.into(),
+        })
+    }
+
+    /// Insert an RTF fragment (e.g. from the clipboard) at the cursor
+    /// position. Replaces selection if any.
+    pub fn insert_rtf(&self, rtf: &str) -> Result<()> {
+        let frag = DocumentFragment::from_rtf(rtf)?;
+        self.insert_fragment_as(&frag, || EditAction::InsertRtf { rtf: rtf.into() })
     }
 
     /// Insert a document fragment at the cursor. Replaces selection if any.
     pub fn insert_fragment(&self, fragment: &DocumentFragment) -> Result<()> {
+        self.insert_fragment_as(fragment, || EditAction::InsertFragment {
+            data: fragment.raw_data().into(),
+            plain_text: fragment.to_plain_text().into(),
+        })
+    }
+
+    /// [`insert_fragment`](Self::insert_fragment), logged as `action`.
+    fn insert_fragment_as(
+        &self,
+        fragment: &DocumentFragment,
+        action: impl FnOnce() -> EditAction,
+    ) -> Result<()> {
         let (pos, anchor) = self.read_cursor();
         let queued = {
             let mut inner = self.doc.lock();
-
-            let (insert_pos, removed) = if pos != anchor {
-                undo_redo_commands::begin_composite(&inner.ctx, Some(inner.stack_id));
-                let del_dto = frontend::document_editing::DeleteTextDto {
-                    position: to_i64(pos),
-                    anchor: to_i64(anchor),
+            let stack_id = self.edit_stack(&mut inner);
+            let logged = self.logged_cursor(stack_id);
+
+            let insert = |insert_pos: usize| {
+                let dto = frontend::document_editing::InsertFragmentDto {
+                    position: to_i64(insert_pos),
+                    anchor: to_i64(insert_pos),
+                    fragment_data: fragment.raw_data().into(),
                 };
-                let del_result = document_editing_commands::delete_text(
-                    &inner.ctx,
-                    Some(inner.stack_id),
-                    &del_dto,
-                )?;
-                (
-                    to_usize(del_result.new_position),
-                    pos.max(anchor) - pos.min(anchor),
-                )
-            } else {
-                (pos, 0)
+                document_editing_commands::insert_fragment(&inner.ctx, Some(stack_id), &dto)
             };
-
-            let dto = frontend::document_editing::InsertFragmentDto {
-                position: to_i64(insert_pos),
-                anchor: to_i64(insert_pos),
-                fragment_data: fragment.raw_data().into(),
+            let (result, removed) = if pos != anchor {
+                let result = in_composite(&inner.ctx, stack_id, || {
+                    let del_dto = frontend::document_editing::DeleteTextDto {
+                        position: to_i64(pos),
+                        anchor: to_i64(anchor),
+                    };
+                    let del_result = document_editing_commands::delete_text(
+                        &inner.ctx,
+                        Some(stack_id),
+                        &del_dto,
+                    )?;
+                    insert(to_usize(del_result.new_position))
+                })?;
+                (result, pos.max(anchor) - pos.min(anchor))
+            } else {
+                (insert(pos)?, 0)
             };
-            let result =
-                document_editing_commands::insert_fragment(&inner.ctx, Some(inner.stack_id), &dto)?;
-
-            if pos != anchor {
-                undo_redo_commands::end_composite(&inner.ctx);
-            }
 
+            inner.log_edit(Some(logged), action);
             let edit_pos = pos.min(anchor);
             self.finish_edit(
                 &mut inner,
+                stack_id,
                 edit_pos,
                 removed,
                 to_usize(result.new_position),
@@ -688,43 +747,46 @@ impl TextCursor {
         let (pos, anchor) = self.read_cursor();
         let queued = {
             let mut inner = self.doc.lock();
-
-            let (insert_pos, removed) = if pos != anchor {
-                undo_redo_commands::begin_composite(&inner.ctx, Some(inner.stack_id));
-                let del_dto = frontend::document_editing::DeleteTextDto {
-                    position: to_i64(pos),
-                    anchor: to_i64(anchor),
+            let stack_id = self.edit_stack(&mut inner);
+            let logged = self.logged_cursor(stack_id);
+
+            let insert = |insert_pos: usize| {
+                let dto = frontend::document_editing::InsertImageDto {
+                    position: to_i64(insert_pos),
+                    anchor: to_i64(insert_pos),
+                    image_name: name.into(),
+                    width: width as i64,
+                    height: height as i64,
                 };
-                let del_result = document_editing_commands::delete_text(
-                    &inner.ctx,
-                    Some(inner.stack_id),
-                    &del_dto,
-                )?;
-                (
-                    to_usize(del_result.new_position),
-                    pos.max(anchor) - pos.min(anchor),
-                )
-            } else {
-                (pos, 0)
+                document_editing_commands::insert_image(&inner.ctx, Some(stack_id), &dto)
             };
-
-            let dto = frontend::document_editing::InsertImageDto {
-                position: to_i64(insert_pos),
-                anchor: to_i64(insert_pos),
-                image_name: name.into(),
-                width: width as i64,
-                height: height as i64,
+            let (result, removed) = if pos != anchor {
+                let result = in_composite(&inner.ctx, stack_id, || {
+                    let del_dto = frontend::document_editing::DeleteTextDto {
+                        position: to_i64(pos),
+                        anchor: to_i64(anchor),
+                    };
+                    let del_result = document_editing_commands::delete_text(
+                        &inner.ctx,
+                        Some(stack_id),
+                        &del_dto,
+                    )?;
+                    insert(to_usize(del_result.new_position))
+                })?;
+                (result, pos.max(anchor) - pos.min(anchor))
+            } else {
+                (insert(pos)?, 0)
             };
-            let result =
-                document_editing_commands::insert_image(&inner.ctx, Some(inner.stack_id), &dto)?;
-
-            if pos != anchor {
-                undo_redo_commands::end_composite(&inner.ctx);
-            }
 
+            inner.log_edit(Some(logged), || EditAction::InsertImage {
+                name: name.into(),
+                width,
+                height,
+            });
             let edit_pos = pos.min(anchor);
             self.finish_edit_ext(
                 &mut inner,
+                stack_id,
                 edit_pos,
                 removed,
                 to_usize(result.new_position),
@@ -741,11 +803,15 @@ impl TextCursor {
         let (pos, anchor) = self.read_cursor();
         let queued = {
             let mut inner = self.doc.lock();
+            let stack_id = self.edit_stack(&mut inner);
+            let logged = self.logged_cursor(stack_id);
+            let before = self.selection_state();
             let dto = frontend::document_editing::InsertFrameDto {
                 position: to_i64(pos),
                 anchor: to_i64(anchor),
             };
-            document_editing_commands::insert_frame(&inner.ctx, Some(inner.stack_id), &dto)?;
+            document_editing_commands::insert_frame(&inner.ctx, Some(stack_id), &dto)?;
+            inner.log_edit(Some(logged), || EditAction::InsertFrame);
             // Frame insertion adds structural content; adjust cursors and emit event.
             // The backend doesn't return a new_position, so the cursor stays put.
             inner.modified = true;
@@ -759,7 +825,7 @@ impl TextCursor {
             });
             inner.check_block_count_changed();
             inner.check_flow_changed();
-            self.queue_undo_redo_event(&mut inner)
+            self.queue_undo_redo_event(&mut inner, stack_id, before)
         };
         crate::inner::dispatch_queued_events(queued);
         Ok(())
@@ -774,14 +840,17 @@ impl TextCursor {
         let (pos, anchor) = self.read_cursor();
         let (table_id, queued) = {
             let mut inner = self.doc.lock();
+            let stack_id = self.edit_stack(&mut inner);
+            let logged = self.logged_cursor(stack_id);
+            let before = self.selection_state();
             let dto = frontend::document_editing::InsertTableDto {
                 position: to_i64(pos),
                 anchor: to_i64(anchor),
                 rows: to_i64(rows),
                 columns: to_i64(columns),
             };
-            let result =
-                document_editing_commands::insert_table(&inner.ctx, Some(inner.stack_id), &dto)?;
+            let result = document_editing_commands::insert_table(&inner.ctx, Some(stack_id), &dto)?;
+            inner.log_edit(Some(logged), || EditAction::InsertTable { rows, columns });
             let new_pos = to_usize(result.new_position);
             let table_id = to_usize(result.table_id);
             inner.adjust_cursors(pos.min(anchor), 0, new_pos - pos.min(anchor));
@@ -801,7 +870,10 @@ impl TextCursor {
             });
             inner.check_block_count_changed();
             inner.check_flow_changed();
-            (table_id, self.queue_undo_redo_event(&mut inner))
+            (
+                table_id,
+                self.queue_undo_redo_event(&mut inner, stack_id, before),
+            )
         };
         crate::inner::dispatch_queued_events(queued);
         Ok(TextTable {
@@ -861,16 +933,20 @@ impl TextCursor {
     pub fn remove_table(&self, table_id: usize) -> Result<()> {
         let queued = {
             let mut inner = self.doc.lock();
+            let stack_id = self.edit_stack(&mut inner);
+            let logged = self.logged_cursor(stack_id);
+            let before = self.selection_state();
             let dto = frontend::document_editing::RemoveTableDto {
                 table_id: to_i64(table_id),
             };
-            document_editing_commands::remove_table(&inner.ctx, Some(inner.stack_id), &dto)?;
+            document_editing_commands::remove_table(&inner.ctx, Some(stack_id), &dto)?;
+            inner.log_edit(Some(logged), || EditAction::RemoveTable { table_id });
             inner.modified = true;
             inner.invalidate_text_cache();
             inner.rehighlight_all();
             inner.check_block_count_changed();
             inner.check_flow_changed();
-            self.queue_undo_redo_event(&mut inner)
+            self.queue_undo_redo_event(&mut inner, stack_id, before)
         };
         crate::inner::dispatch_queued_events(queued);
         Ok(())
@@ -880,16 +956,23 @@ impl TextCursor {
     pub fn insert_table_row(&self, table_id: usize, row_index: usize) -> Result<()> {
         let queued = {
             let mut inner = self.doc.lock();
+            let stack_id = self.edit_stack(&mut inner);
+            let logged = self.logged_cursor(stack_id);
+            let before = self.selection_state();
             let dto = frontend::document_editing::InsertTableRowDto {
                 table_id: to_i64(table_id),
                 row_index: to_i64(row_index),
             };
-            document_editing_commands::insert_table_row(&inner.ctx, Some(inner.stack_id), &dto)?;
+            document_editing_commands::insert_table_row(&inner.ctx, Some(stack_id), &dto)?;
+            inner.log_edit(Some(logged), || EditAction::InsertTableRow {
+                table_id,
+                row_index,
+            });
             inner.modified = true;
             inner.invalidate_text_cache();
             inner.rehighlight_all();
             inner.check_block_count_changed();
-            self.queue_undo_redo_event(&mut inner)
+            self.queue_undo_redo_event(&mut inner, stack_id, before)
         };
         crate::inner::dispatch_queued_events(queued);
         Ok(())
@@ -899,16 +982,23 @@ impl TextCursor {
     pub fn insert_table_column(&self, table_id: usize, column_index: usize) -> Result<()> {
         let queued = {
             let mut inner = self.doc.lock();
+            let stack_id = self.edit_stack(&mut inner);
+            let logged = self.logged_cursor(stack_id);
+            let before = self.selection_state();
             let dto = frontend::document_editing::InsertTableColumnDto {
                 table_id: to_i64(table_id),
                 column_index: to_i64(column_index),
             };
-            document_editing_commands::insert_table_column(&inner.ctx, Some(inner.stack_id), &dto)?;
+            document_editing_commands::insert_table_column(&inner.ctx, Some(stack_id), &dto)?;
+            inner.log_edit(Some(logged), || EditAction::InsertTableColumn {
+                table_id,
+                column_index,
+            });
             inner.modified = true;
             inner.invalidate_text_cache();
             inner.rehighlight_all();
             inner.check_block_count_changed();
-            self.queue_undo_redo_event(&mut inner)
+            self.queue_undo_redo_event(&mut inner, stack_id, before)
         };
         crate::inner::dispatch_queued_events(queued);
         Ok(())
@@ -918,16 +1008,23 @@ impl TextCursor {
     pub fn remove_table_row(&self, table_id: usize, row_index: usize) -> Result<()> {
         let queued = {
             let mut inner = self.doc.lock();
+            let stack_id = self.edit_stack(&mut inner);
+            let logged = self.logged_cursor(stack_id);
+            let before = self.selection_state();
             let dto = frontend::document_editing::RemoveTableRowDto {
                 table_id: to_i64(table_id),
                 row_index: to_i64(row_index),
             };
-            document_editing_commands::remove_table_row(&inner.ctx, Some(inner.stack_id), &dto)?;
+            document_editing_commands::remove_table_row(&inner.ctx, Some(stack_id), &dto)?;
+            inner.log_edit(Some(logged), || EditAction::RemoveTableRow {
+                table_id,
+                row_index,
+            });
             inner.modified = true;
             inner.invalidate_text_cache();
             inner.rehighlight_all();
             inner.check_block_count_changed();
-            self.queue_undo_redo_event(&mut inner)
+            self.queue_undo_redo_event(&mut inner, stack_id, before)
         };
         crate::inner::dispatch_queued_events(queued);
         Ok(())
@@ -937,16 +1034,23 @@ impl TextCursor {
     pub fn remove_table_column(&self, table_id: usize, column_index: usize) -> Result<()> {
         let queued = {
             let mut inner = self.doc.lock();
+            let stack_id = self.edit_stack(&mut inner);
+            let logged = self.logged_cursor(stack_id);
+            let before = self.selection_state();
             let dto = frontend::document_editing::RemoveTableColumnDto {
                 table_id: to_i64(table_id),
                 column_index: to_i64(column_index),
             };
-            document_editing_commands::remove_table_column(&inner.ctx, Some(inner.stack_id), &dto)?;
+            document_editing_commands::remove_table_column(&inner.ctx, Some(stack_id), &dto)?;
+            inner.log_edit(Some(logged), || EditAction::RemoveTableColumn {
+                table_id,
+                column_index,
+            });
             inner.modified = true;
             inner.invalidate_text_cache();
             inner.rehighlight_all();
             inner.check_block_count_changed();
-            self.queue_undo_redo_event(&mut inner)
+            self.queue_undo_redo_event(&mut inner, stack_id, before)
         };
         crate::inner::dispatch_queued_events(queued);
         Ok(())
@@ -963,6 +1067,9 @@ impl TextCursor {
     ) -> Result<()> {
         let queued = {
             let mut inner = self.doc.lock();
+            let stack_id = self.edit_stack(&mut inner);
+            let logged = self.logged_cursor(stack_id);
+            let before = self.selection_state();
             let dto = frontend::document_editing::MergeTableCellsDto {
                 table_id: to_i64(table_id),
                 start_row: to_i64(start_row),
@@ -970,12 +1077,19 @@ impl TextCursor {
                 end_row: to_i64(end_row),
                 end_column: to_i64(end_column),
             };
-            document_editing_commands::merge_table_cells(&inner.ctx, Some(inner.stack_id), &dto)?;
+            document_editing_commands::merge_table_cells(&inner.ctx, Some(stack_id), &dto)?;
+            inner.log_edit(Some(logged), || EditAction::MergeTableCells {
+                table_id,
+                start_row,
+                start_column,
+                end_row,
+                end_column,
+            });
             inner.modified = true;
             inner.invalidate_text_cache();
             inner.rehighlight_all();
             inner.check_block_count_changed();
-            self.queue_undo_redo_event(&mut inner)
+            self.queue_undo_redo_event(&mut inner, stack_id, before)
         };
         crate::inner::dispatch_queued_events(queued);
         Ok(())
@@ -990,17 +1104,25 @@ impl TextCursor {
     ) -> Result<()> {
         let queued = {
             let mut inner = self.doc.lock();
+            let stack_id = self.edit_stack(&mut inner);
+            let logged = self.logged_cursor(stack_id);
+            let before = self.selection_state();
             let dto = frontend::document_editing::SplitTableCellDto {
                 cell_id: to_i64(cell_id),
                 split_rows: to_i64(split_rows),
                 split_columns: to_i64(split_columns),
             };
-            document_editing_commands::split_table_cell(&inner.ctx, Some(inner.stack_id), &dto)?;
+            document_editing_commands::split_table_cell(&inner.ctx, Some(stack_id), &dto)?;
+            inner.log_edit(Some(logged), || EditAction::SplitTableCell {
+                cell_id,
+                split_rows,
+                split_columns,
+            });
             inner.modified = true;
             inner.invalidate_text_cache();
             inner.rehighlight_all();
             inner.check_block_count_changed();
-            self.queue_undo_redo_event(&mut inner)
+            self.queue_undo_redo_event(&mut inner, stack_id, before)
         };
         crate::inner::dispatch_queued_events(queued);
         Ok(())
@@ -1016,15 +1138,22 @@ impl TextCursor {
     ) -> Result<()> {
         let queued = {
             let mut inner = self.doc.lock();
+            let stack_id = self.edit_stack(&mut inner);
+            let logged = self.logged_cursor(stack_id);
+            let before = self.selection_state();
             let dto = format.to_set_dto(table_id);
-            document_formatting_commands::set_table_format(&inner.ctx, Some(inner.stack_id), &dto)?;
+            document_formatting_commands::set_table_format(&inner.ctx, Some(stack_id), &dto)?;
+            inner.log_edit(Some(logged), || EditAction::SetTableFormat {
+                table_id,
+                format: format.clone(),
+            });
             inner.modified = true;
             inner.queue_event(DocumentEvent::FormatChanged {
                 position: 0,
                 length: 0,
                 kind: crate::flow::FormatChangeKind::Block,
             });
-            self.queue_undo_redo_event(&mut inner)
+            self.queue_undo_redo_event(&mut inner, stack_id, before)
         };
         crate::inner::dispatch_queued_events(queued);
         Ok(())
@@ -1038,19 +1167,22 @@ impl TextCursor {
     ) -> Result<()> {
         let queued = {
             let mut inner = self.doc.lock();
+            let stack_id = self.edit_stack(&mut inner);
+            let logged = self.logged_cursor(stack_id);
+            let before = self.selection_state();
             let dto = format.to_set_dto(cell_id);
-            document_formatting_commands::set_table_cell_format(
-                &inner.ctx,
-                Some(inner.stack_id),
-                &dto,
-            )?;
+            document_formatting_commands::set_table_cell_format(&inner.ctx, Some(stack_id), &dto)?;
+            inner.log_edit(Some(logged), || EditAction::SetTableCellFormat {
+                cell_id,
+                format: format.clone(),
+            });
             inner.modified = true;
             inner.queue_event(DocumentEvent::FormatChanged {
                 position: 0,
                 length: 0,
                 kind: crate::flow::FormatChangeKind::Block,
             });
-            self.queue_undo_redo_event(&mut inner)
+            self.queue_undo_redo_event(&mut inner, stack_id, before)
         };
         crate::inner::dispatch_queued_events(queued);
         Ok(())
@@ -1578,7 +1710,7 @@ impl TextCursor {
             }
             (pos, to)
         };
-        self.do_delete(del_pos, del_anchor)
+        self.do_delete(del_pos, del_anchor, EditAction::DeleteChar)
     }
 
     /// Delete the character before the cursor (Backspace key).
@@ -1595,7 +1727,7 @@ impl TextCursor {
         } else {
             return Ok(());
         };
-        self.do_delete(del_pos, del_anchor)
+        self.do_delete(del_pos, del_anchor, EditAction::DeletePreviousChar)
     }
 
     /// Delete the selected text. Returns the deleted text. No-op if no selection.
@@ -1606,12 +1738,15 @@ impl TextCursor {
         }
         let queued = {
             let mut inner = self.doc.lock();
+            let stack_id = self.edit_stack(&mut inner);
+            let logged = self.logged_cursor(stack_id);
+            let before = self.selection_state();
             let dto = frontend::document_editing::DeleteTextDto {
                 position: to_i64(pos),
                 anchor: to_i64(anchor),
             };
-            let result =
-                document_editing_commands::delete_text(&inner.ctx, Some(inner.stack_id), &dto)?;
+            let result = document_editing_commands::delete_text(&inner.ctx, Some(stack_id), &dto)?;
+            inner.log_edit(Some(logged), || EditAction::RemoveSelectedText);
             let edit_pos = pos.min(anchor);
             let removed = pos.max(anchor) - edit_pos;
             let new_pos = to_usize(result.new_position);
@@ -1633,7 +1768,10 @@ impl TextCursor {
             inner.check_block_count_changed();
             inner.check_flow_changed();
             // Return the deleted text alongside the queued events
-            (result.deleted_text, self.queue_undo_redo_event(&mut inner))
+            (
+                result.deleted_text,
+                self.queue_undo_redo_event(&mut inner, stack_id, before),
+            )
         };
         crate::inner::dispatch_queued_events(queued.1);
         Ok(queued.0)
@@ -1664,12 +1802,18 @@ impl TextCursor {
         let (pos, anchor) = self.read_cursor();
         let queued = {
             let mut inner = self.doc.lock();
+            let stack_id = self.edit_stack(&mut inner);
+            let logged = self.logged_cursor(stack_id);
+            let before = self.selection_state();
             let dto = frontend::document_editing::CreateListDto {
                 position: to_i64(pos),
                 anchor: to_i64(anchor),
                 style: style.clone(),
             };
-            document_editing_commands::create_list(&inner.ctx, Some(inner.stack_id), &dto)?;
+            document_editing_commands::create_list(&inner.ctx, Some(stack_id), &dto)?;
+            inner.log_edit(Some(logged), || EditAction::CreateList {
+                style: style.clone(),
+            });
             inner.modified = true;
             inner.rehighlight_affected(pos.min(anchor));
             inner.queue_event(DocumentEvent::ContentsChanged {
@@ -1678,7 +1822,7 @@ impl TextCursor {
                 chars_added: 0,
                 blocks_affected: 1,
             });
-            self.queue_undo_redo_event(&mut inner)
+            self.queue_undo_redo_event(&mut inner, stack_id, before)
         };
         crate::inner::dispatch_queued_events(queued);
         Ok(())
@@ -1689,17 +1833,22 @@ impl TextCursor {
         let (pos, anchor) = self.read_cursor();
         let queued = {
             let mut inner = self.doc.lock();
+            let stack_id = self.edit_stack(&mut inner);
+            let logged = self.logged_cursor(stack_id);
             let dto = frontend::document_editing::InsertListDto {
                 position: to_i64(pos),
                 anchor: to_i64(anchor),
                 style: style.clone(),
             };
-            let result =
-                document_editing_commands::insert_list(&inner.ctx, Some(inner.stack_id), &dto)?;
+            let result = document_editing_commands::insert_list(&inner.ctx, Some(stack_id), &dto)?;
+            inner.log_edit(Some(logged), || EditAction::InsertList {
+                style: style.clone(),
+            });
             let edit_pos = pos.min(anchor);
             let removed = pos.max(anchor) - edit_pos;
             self.finish_edit_ext(
                 &mut inner,
+                stack_id,
                 edit_pos,
                 removed,
                 to_usize(result.new_position),
@@ -1715,15 +1864,22 @@ impl TextCursor {
     pub fn set_list_format(&self, list_id: usize, format: &crate::ListFormat) -> Result<()> {
         let queued = {
             let mut inner = self.doc.lock();
+            let stack_id = self.edit_stack(&mut inner);
+            let logged = self.logged_cursor(stack_id);
+            let before = self.selection_state();
             let dto = format.to_set_dto(list_id);
-            document_formatting_commands::set_list_format(&inner.ctx, Some(inner.stack_id), &dto)?;
+            document_formatting_commands::set_list_format(&inner.ctx, Some(stack_id), &dto)?;
+            inner.log_edit(Some(logged), || EditAction::SetListFormat {
+                list_id,
+                format: format.clone(),
+            });
             inner.modified = true;
             inner.queue_event(DocumentEvent::FormatChanged {
                 position: 0,
                 length: 0,
                 kind: crate::flow::FormatChangeKind::List,
             });
-            self.queue_undo_redo_event(&mut inner)
+            self.queue_undo_redo_event(&mut inner, stack_id, before)
         };
         crate::inner::dispatch_queued_events(queued);
         Ok(())
@@ -1742,11 +1898,18 @@ impl TextCursor {
     pub fn add_block_to_list(&self, block_id: usize, list_id: usize) -> Result<()> {
         let queued = {
             let mut inner = self.doc.lock();
+            let stack_id = self.edit_stack(&mut inner);
+            let logged = self.logged_cursor(stack_id);
+            let before = self.selection_state();
             let dto = frontend::document_editing::AddBlockToListDto {
                 block_id: to_i64(block_id),
                 list_id: to_i64(list_id),
             };
-            document_editing_commands::add_block_to_list(&inner.ctx, Some(inner.stack_id), &dto)?;
+            document_editing_commands::add_block_to_list(&inner.ctx, Some(stack_id), &dto)?;
+            inner.log_edit(Some(logged), || EditAction::AddBlockToList {
+                block_id,
+                list_id,
+            });
             inner.modified = true;
             // List membership is a formatting/layout concern, not a text
             // change — fire FormatChanged so consumers re-layout (the
@@ -1759,7 +1922,7 @@ impl TextCursor {
                 length: 0,
                 kind: crate::flow::FormatChangeKind::List,
             });
-            self.queue_undo_redo_event(&mut inner)
+            self.queue_undo_redo_event(&mut inner, stack_id, before)
         };
         crate::inner::dispatch_queued_events(queued);
         Ok(())
@@ -1781,14 +1944,16 @@ impl TextCursor {
     pub fn remove_block_from_list(&self, block_id: usize) -> Result<()> {
         let queued = {
             let mut inner = self.doc.lock();
+            let stack_id = self.edit_stack(&mut inner);
+            let logged = self.logged_cursor(stack_id);
+            let before = self.selection_state();
             let dto = frontend::document_editing::RemoveBlockFromListDto {
                 block_id: to_i64(block_id),
             };
-            document_editing_commands::remove_block_from_list(
-                &inner.ctx,
-                Some(inner.stack_id),
-                &dto,
-            )?;
+            document_editing_commands::remove_block_from_list(&inner.ctx, Some(stack_id), &dto)?;
+            inner.log_edit(Some(logged), || EditAction::RemoveBlockFromList {
+                block_id,
+            });
             inner.modified = true;
             // See `add_block_to_list` — list-membership is a
             // formatting/layout change, not a text content change.
@@ -1797,7 +1962,7 @@ impl TextCursor {
                 length: 0,
                 kind: crate::flow::FormatChangeKind::List,
             });
-            self.queue_undo_redo_event(&mut inner)
+            self.queue_undo_redo_event(&mut inner, stack_id, before)
         };
         crate::inner::dispatch_queued_events(queued);
         Ok(())
@@ -1910,8 +2075,14 @@ impl TextCursor {
         let (pos, anchor) = self.read_cursor();
         let queued = {
             let mut inner = self.doc.lock();
+            let stack_id = self.edit_stack(&mut inner);
+            let logged = self.logged_cursor(stack_id);
+            let before = self.selection_state();
             let dto = format.to_set_dto(pos, anchor);
-            document_formatting_commands::set_text_format(&inner.ctx, Some(inner.stack_id), &dto)?;
+            document_formatting_commands::set_text_format(&inner.ctx, Some(stack_id), &dto)?;
+            inner.log_edit(Some(logged), || EditAction::SetCharFormat {
+                format: format.clone(),
+            });
             let start = pos.min(anchor);
             let length = pos.max(anchor) - start;
             inner.modified = true;
@@ -1920,7 +2091,7 @@ impl TextCursor {
                 length,
                 kind: crate::flow::FormatChangeKind::Character,
             });
-            self.queue_undo_redo_event(&mut inner)
+            self.queue_undo_redo_event(&mut inner, stack_id, before)
         };
         crate::inner::dispatch_queued_events(queued);
         Ok(())
@@ -1931,12 +2102,14 @@ impl TextCursor {
         let (pos, anchor) = self.read_cursor();
         let queued = {
             let mut inner = self.doc.lock();
+            let stack_id = self.edit_stack(&mut inner);
+            let logged = self.logged_cursor(stack_id);
+            let before = self.selection_state();
             let dto = format.to_merge_dto(pos, anchor);
-            document_formatting_commands::merge_text_format(
-                &inner.ctx,
-                Some(inner.stack_id),
-                &dto,
-            )?;
+            document_formatting_commands::merge_text_format(&inner.ctx, Some(stack_id), &dto)?;
+            inner.log_edit(Some(logged), || EditAction::MergeCharFormat {
+                format: format.clone(),
+            });
             let start = pos.min(anchor);
             let length = pos.max(anchor) - start;
             inner.modified = true;
@@ -1945,7 +2118,7 @@ impl TextCursor {
                 length,
                 kind: crate::flow::FormatChangeKind::Character,
             });
-            self.queue_undo_redo_event(&mut inner)
+            self.queue_undo_redo_event(&mut inner, stack_id, before)
         };
         crate::inner::dispatch_queued_events(queued);
         Ok(())
@@ -1956,8 +2129,14 @@ impl TextCursor {
         let (pos, anchor) = self.read_cursor();
         let queued = {
             let mut inner = self.doc.lock();
+            let stack_id = self.edit_stack(&mut inner);
+            let logged = self.logged_cursor(stack_id);
+            let before = self.selection_state();
             let dto = format.to_set_dto(pos, anchor);
-            document_formatting_commands::set_block_format(&inner.ctx, Some(inner.stack_id), &dto)?;
+            document_formatting_commands::set_block_format(&inner.ctx, Some(stack_id), &dto)?;
+            inner.log_edit(Some(logged), || EditAction::SetBlockFormat {
+                format: format.clone(),
+            });
             let start = pos.min(anchor);
             let length = pos.max(anchor) - start;
             inner.modified = true;
@@ -1966,7 +2145,7 @@ impl TextCursor {
                 length,
                 kind: crate::flow::FormatChangeKind::Block,
             });
-            self.queue_undo_redo_event(&mut inner)
+            self.queue_undo_redo_event(&mut inner, stack_id, before)
         };
         crate::inner::dispatch_queued_events(queued);
         Ok(())
@@ -1977,8 +2156,15 @@ impl TextCursor {
         let (pos, anchor) = self.read_cursor();
         let queued = {
             let mut inner = self.doc.lock();
+            let stack_id = self.edit_stack(&mut inner);
+            let logged = self.logged_cursor(stack_id);
+            let before = self.selection_state();
             let dto = format.to_set_dto(pos, anchor, frame_id);
-            document_formatting_commands::set_frame_format(&inner.ctx, Some(inner.stack_id), &dto)?;
+            document_formatting_commands::set_frame_format(&inner.ctx, Some(stack_id), &dto)?;
+            inner.log_edit(Some(logged), || EditAction::SetFrameFormat {
+                frame_id,
+                format: format.clone(),
+            });
             let start = pos.min(anchor);
             let length = pos.max(anchor) - start;
             inner.modified = true;
@@ -1987,24 +2173,150 @@ impl TextCursor {
                 length,
                 kind: crate::flow::FormatChangeKind::Block,
             });
-            self.queue_undo_redo_event(&mut inner)
+            self.queue_undo_redo_event(&mut inner, stack_id, before)
         };
         crate::inner::dispatch_queued_events(queued);
         Ok(())
     }
 
+    // ── Search in selection ──────────────────────────────────
+
+    /// Find all matches lying entirely inside the selection. The
+    /// selection takes the place of `options.scope`; without a selection
+    /// nothing is found.
+    pub fn find_all_in_selection(
+        &self,
+        query: &str,
+        options: &FindOptions,
+    ) -> Result<Vec<FindMatch>> {
+        let (pos, anchor) = self.read_cursor();
+        if pos == anchor {
+            return Ok(Vec::new());
+        }
+        let options = FindOptions {
+            scope: Some(SearchScope::Range {
+                start: pos.min(anchor),
+                end: pos.max(anchor),
+            }),
+            ..options.clone()
+        };
+        let inner = self.doc.lock();
+        let result =
+            document_search_commands::find_all(&inner.ctx, &options.to_find_all_dto(query))?;
+        Ok(convert::find_all_to_matches(&result))
+    }
+
+    /// Replace every match inside the selection as a single undo step and
+    /// return the number of replacements. The selection is resized to
+    /// cover the replaced text. Without a selection nothing is replaced.
+    pub fn replace_in_selection(
+        &self,
+        query: &str,
+        replacement: &str,
+        options: &FindOptions,
+    ) -> Result<usize> {
+        let (pos, anchor) = self.read_cursor();
+        if pos == anchor {
+            return Ok(0);
+        }
+        let start = pos.min(anchor);
+        let removed = pos.max(anchor) - start;
+        let options = FindOptions {
+            scope: Some(SearchScope::Range {
+                start,
+                end: start + removed,
+            }),
+            ..options.clone()
+        };
+        let (count, queued) = {
+            let mut inner = self.doc.lock();
+            let stack_id = self.edit_stack(&mut inner);
+            let logged = self.logged_cursor(stack_id);
+            let before = self.selection_state();
+            let stats_before = document_inspection_commands::get_document_stats(&inner.ctx)?;
+            let dto = options.to_replace_dto(query, replacement, true);
+            let result = document_search_commands::replace_text(&inner.ctx, Some(stack_id), &dto)?;
+            let count = to_usize(result.replacements_count);
+            if count == 0 {
+                return Ok(0);
+            }
+            inner.log_edit(Some(logged), || EditAction::ReplaceInSelection {
+                query: query.into(),
+                replacement: replacement.into(),
+                options: options.clone(),
+            });
+            // Every replacement lies in the selection, so the document's
+            // length change is the selection's.
+            let after = document_inspection_commands::get_document_stats(&inner.ctx)?;
+            let added = to_usize(
+                to_i64(removed) + to_i64(max_cursor_position(&after))
+                    - to_i64(max_cursor_position(&stats_before)),
+            );
+            inner.adjust_cursors(start, removed, added);
+            {
+                let mut d = self.data.lock();
+                if pos > anchor {
+                    d.anchor = start;
+                    d.position = start + added;
+                } else {
+                    d.position = start;
+                    d.anchor = start + added;
+                }
+            }
+            inner.modified = true;
+            inner.invalidate_text_cache();
+            inner.rehighlight_affected(start);
+            inner.queue_event(DocumentEvent::ContentsChanged {
+                position: start,
+                chars_removed: removed,
+                chars_added: added,
+                blocks_affected: count,
+            });
+            inner.check_block_count_changed();
+            inner.check_flow_changed();
+            (
+                count,
+                self.queue_undo_redo_event(&mut inner, stack_id, before),
+            )
+        };
+        crate::inner::dispatch_queued_events(queued);
+        Ok(count)
+    }
+
     // ── Edit blocks (composite undo) ─────────────────────────
 
     /// Begin a group of operations that will be undone as a single unit.
     pub fn begin_edit_block(&self) {
-        let inner = self.doc.lock();
-        undo_redo_commands::begin_composite(&inner.ctx, Some(inner.stack_id));
+        let mut inner = self.doc.lock();
+        let stack_id = self.edit_stack(&mut inner);
+        undo_redo_commands::begin_composite(&inner.ctx, Some(stack_id));
+        let logged = self.logged_cursor(stack_id);
+        inner.log_edit(Some(logged), || EditAction::BeginEditBlock);
     }
 
     /// End the current edit block.
     pub fn end_edit_block(&self) {
-        let inner = self.doc.lock();
+        let mut inner = self.doc.lock();
         undo_redo_commands::end_composite(&inner.ctx);
+        inner.log_edit(None, || EditAction::EndEditBlock);
+    }
+
+    /// Abandon the current edit block: the edits made in it are undone
+    /// and no undo step is recorded.
+    pub fn cancel_edit_block(&self) {
+        let queued = {
+            let mut inner = self.doc.lock();
+            let before = crate::document::capture_block_state(&inner, None);
+            undo_redo_commands::cancel_composite(&inner.ctx);
+            inner.invalidate_text_cache();
+            inner.rehighlight_all();
+            crate::document::emit_undo_redo_change_events(&mut inner, &before, None);
+            inner.check_block_count_changed();
+            inner.check_flow_changed();
+            inner.log_edit(None, || EditAction::CancelEditBlock);
+            inner.take_queued_events()
+        };
+        crate::inner::dispatch_queued_events(queued);
     }
 
     /// Alias for [`begin_edit_block`](Self::begin_edit_block).
@@ -2017,25 +2329,88 @@ impl TextCursor {
         self.begin_edit_block();
     }
 
+    // ── Undo stack ──────────────────────────────────────────
+
+    /// Send this cursor's edits to `stack`, whatever frame they land
+    /// in. `None` goes back to following the frame bindings made with
+    /// [`TextDocument::set_frame_undo_stack`](crate::TextDocument::set_frame_undo_stack).
+    pub fn set_undo_stack(&self, stack: Option<UndoStackId>) {
+        self.data.lock().undo_stack = stack.map(|s| s.0);
+    }
+
+    /// Undo stack an edit made through this cursor right now would go to.
+    pub fn undo_stack(&self) -> UndoStackId {
+        let mut inner = self.doc.lock();
+        UndoStackId(self.edit_stack(&mut inner))
+    }
+
     // ── Private helpers ─────────────────────────────────────
 
-    /// Queue an `UndoRedoChanged` event and return all queued events for dispatch.
-    fn queue_undo_redo_event(&self, inner: &mut TextDocumentInner) -> QueuedEvents {
-        let can_undo = undo_redo_commands::can_undo(&inner.ctx, Some(inner.stack_id));
-        let can_redo = undo_redo_commands::can_redo(&inner.ctx, Some(inner.stack_id));
-        inner.queue_event(DocumentEvent::UndoRedoChanged { can_undo, can_redo });
+    /// This cursor's selection and the undo stack its edit goes to, as
+    /// recorded in the edit log.
+    fn logged_cursor(&self, stack_id: u64) -> LoggedCursor {
+        let d = self.data.lock();
+        LoggedCursor {
+            position: d.position,
+            anchor: d.anchor,
+            undo_stack: stack_id,
+        }
+    }
+
+    /// This cursor's selection, in the form recorded with undo entries.
+    fn selection_state(&self) -> SelectionState {
+        self.data.lock().selection_state()
+    }
+
+    /// Undo stack this cursor's edits go to: the one bound with
+    /// [`set_undo_stack`](Self::set_undo_stack), else the stack of the
+    /// frame the selection starts in (see
+    /// [`TextDocument::set_frame_undo_stack`](crate::TextDocument::set_frame_undo_stack)).
+    fn edit_stack(&self, inner: &mut TextDocumentInner) -> u64 {
+        let (stack, start) = {
+            let d = self.data.lock();
+            (d.undo_stack, d.position.min(d.anchor))
+        };
+        stack.unwrap_or_else(|| inner.undo_stack_at(start))
+    }
+
+    /// Record this cursor's selection before and after the edit with the
+    /// undo entry it produced, queue the undo availability event for
+    /// `stack_id` and return all queued events for dispatch.
+    fn queue_undo_redo_event(
+        &self,
+        inner: &mut TextDocumentInner,
+        stack_id: u64,
+        before: SelectionState,
+    ) -> QueuedEvents {
+        let (cursor_id, after) = {
+            let d = self.data.lock();
+            (d.id, d.selection_state())
+        };
+        undo_redo_commands::record_selection(
+            &inner.ctx,
+            EditSelection {
+                cursor_id,
+                before,
+                after,
+            },
+        );
+        inner.queue_undo_state(stack_id);
         inner.take_queued_events()
     }
 
-    fn do_delete(&self, pos: usize, anchor: usize) -> Result<()> {
+    fn do_delete(&self, pos: usize, anchor: usize, action: EditAction) -> Result<()> {
         let queued = {
             let mut inner = self.doc.lock();
+            let stack_id = self.edit_stack(&mut inner);
+            let logged = self.logged_cursor(stack_id);
+            let before = self.selection_state();
             let dto = frontend::document_editing::DeleteTextDto {
                 position: to_i64(pos),
                 anchor: to_i64(anchor),
             };
-            let result =
-                document_editing_commands::delete_text(&inner.ctx, Some(inner.stack_id), &dto)?;
+            let result = document_editing_commands::delete_text(&inner.ctx, Some(stack_id), &dto)?;
+            inner.log_edit(Some(logged), || action);
             let edit_pos = pos.min(anchor);
             let removed = pos.max(anchor) - edit_pos;
             let new_pos = to_usize(result.new_position);
@@ -2056,7 +2431,7 @@ impl TextCursor {
             });
             inner.check_block_count_changed();
             inner.check_flow_changed();
-            self.queue_undo_redo_event(&mut inner)
+            self.queue_undo_redo_event(&mut inner, stack_id, before)
         };
         crate::inner::dispatch_queued_events(queued);
         Ok(())
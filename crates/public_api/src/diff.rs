@@ -0,0 +1,1822 @@
+//! Structural diffs between two documents.
+//!
+//! [`diff`] compares two [`TextDocument`]s the way a reader would. It
+//! lines up their blocks by text first, then pairs the blocks that were
+//! edited rather than replaced and lines up the characters within them.
+//! The result lists text edits, format-only changes to runs and blocks,
+//! list membership changes, tables changed row, column and cell at a
+//! time, and blocks that moved. [`DocumentDiff::to_html`] renders it
+//! for review and [`DocumentDiff::apply`] replays it on a document as a
+//! patch.
+//!
+//! Frames are compared as the blocks they contain, and table and cell
+//! formats are not compared, though cell spans are. A table whose rows
+//! and columns both changed is reported as removed and inserted again.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use anyhow::{Result, bail};
+use frontend::commands::list_commands;
+use frontend::common::types::EntityId;
+use frontend::list::dtos::ListDto;
+
+use crate::align::{self, Hunk};
+use crate::doc_blocks::{
+    BlockProps, DocBlock, UnitContent, adds_fields_only, fragment_of, read_blocks,
+    write_block_format,
+};
+use crate::fragment::escape_html;
+use crate::{BlockFormat, ListFormat, MoveMode, TextCursor, TextDocument, TextFormat};
+
+/// Hunk sizes (removed × added items) past which edited items are not
+/// paired up, and the whole hunk counts as removed and inserted.
+const MAX_PAIRING: usize = 10_000;
+
+/// Compare two documents. `old` is the version changes are reported
+/// against; positions in the result refer to `old` and `new` as they
+/// are now.
+///
+/// ```
+/// use text_document::{DiffChange, TextDocument};
+///
+/// let (old, new) = (TextDocument::new(), TextDocument::new());
+/// old.set_plain_text("Hello world\nBye").unwrap();
+/// new.set_plain_text("Hello there world\nBye").unwrap();
+///
+/// let diff = text_document::diff(&old, &new).unwrap();
+/// let DiffChange::BlockChanged(change) = &diff.changes()[0] else {
+///     panic!("expected an edited block");
+/// };
+/// assert_eq!(change.text_edits[0].inserted, "there ");
+/// assert_eq!(diff.to_html(), "<p>Hello <ins>there </ins>world</p>\n<p>Bye</p>\n");
+///
+/// diff.apply(&old).unwrap();
+/// assert_eq!(old.to_plain_text().unwrap(), "Hello there world\nBye");
+/// ```
+pub fn diff(old: &TextDocument, new: &TextDocument) -> Result<DocumentDiff> {
+    let old = Side::read(old)?;
+    let new = Side::read(new)?;
+    let steps = align_items(&old, &old.items, &new, &new.items);
+    let changes = changes(&old, &old.items, &new, &new.items, &steps);
+    Ok(DocumentDiff {
+        old,
+        new,
+        steps,
+        changes,
+    })
+}
+
+// ── Public types ─────────────────────────────────────────────
+
+/// The differences between two documents, from [`diff`].
+#[derive(Clone)]
+pub struct DocumentDiff {
+    old: Side,
+    new: Side,
+    steps: Vec<Step>,
+    changes: Vec<DiffChange>,
+}
+
+/// One change between the old and the new document. Blocks inside
+/// tables are reported in the [`CellChange`]s of their table.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DiffChange {
+    /// A block only the new document has.
+    BlockInserted { new_position: usize, text: String },
+    /// A block only the old document has.
+    BlockRemoved { old_position: usize, text: String },
+    /// A block whose text is found unchanged elsewhere in the new
+    /// document. Its formats are taken from the new document.
+    BlockMoved {
+        old_position: usize,
+        new_position: usize,
+        text: String,
+    },
+    /// A block edited in place: text, character formats, block format
+    /// or list membership.
+    BlockChanged(Box<BlockChange>),
+    /// A table only the new document has. `new_position` is the
+    /// position of its first cell.
+    TableInserted {
+        new_position: usize,
+        rows: usize,
+        columns: usize,
+    },
+    /// A table only the old document has.
+    TableRemoved {
+        old_position: usize,
+        rows: usize,
+        columns: usize,
+    },
+    /// A table with rows or columns added or removed, or cells edited.
+    TableChanged(TableChange),
+}
+
+/// How one block changed. Offsets count characters from the start of
+/// the block, with an image counting as one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockChange {
+    pub old_position: usize,
+    pub new_position: usize,
+    /// Text replaced, in order.
+    pub text_edits: Vec<TextEdit>,
+    /// Unchanged text whose character format changed.
+    pub format_changes: Vec<RunFormatChange>,
+    /// Old and new block format, if it changed.
+    pub block_format: Option<(BlockFormat, BlockFormat)>,
+    /// Old and new list format, `None` outside a list, if membership or
+    /// the list's format changed.
+    pub list: Option<(Option<ListFormat>, Option<ListFormat>)>,
+}
+
+/// `removed` at `old_offset` in the old block became `inserted` at
+/// `new_offset` in the new one. Images appear as U+FFFC.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    pub old_offset: usize,
+    pub removed: String,
+    pub new_offset: usize,
+    pub inserted: String,
+}
+
+/// `length` unchanged characters whose format went from `old` to `new`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunFormatChange {
+    pub old_offset: usize,
+    pub new_offset: usize,
+    pub length: usize,
+    pub old: TextFormat,
+    pub new: TextFormat,
+}
+
+/// How one table changed. Row and column indices of removals are in the
+/// old table, those of insertions in the new one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableChange {
+    pub old_position: usize,
+    pub new_position: usize,
+    pub rows_removed: Vec<usize>,
+    pub rows_inserted: Vec<usize>,
+    pub columns_removed: Vec<usize>,
+    pub columns_inserted: Vec<usize>,
+    /// Cells present in both tables that were merged or split.
+    pub spans: Vec<SpanChange>,
+    /// Cells present in both tables whose content changed. A merged
+    /// cell is compared with the old cells it covers.
+    pub cells: Vec<CellChange>,
+}
+
+/// A cell that spans other rows or columns than before. Spans are
+/// `(rows, columns)`; `old` is `None` where a merged cell covered it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpanChange {
+    pub old_row: usize,
+    pub old_column: usize,
+    pub new_row: usize,
+    pub new_column: usize,
+    pub old: Option<(usize, usize)>,
+    pub new: (usize, usize),
+}
+
+/// The changes to the blocks of one table cell.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CellChange {
+    pub old_row: usize,
+    pub old_column: usize,
+    pub new_row: usize,
+    pub new_column: usize,
+    pub changes: Vec<DiffChange>,
+}
+
+impl DocumentDiff {
+    /// The changes, in document order.
+    pub fn changes(&self) -> &[DiffChange] {
+        &self.changes
+    }
+
+    /// True when the documents have the same content and formats.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// The new document as HTML, with removed text in `<del>` and
+    /// inserted text in `<ins>`. Text and blocks whose format changed
+    /// carry `class="format-changed"`, blocks that moved
+    /// `class="moved"`, and table rows and cells that were removed or
+    /// inserted `class="removed"` or `class="inserted"`. Character
+    /// formats themselves are not rendered.
+    pub fn to_html(&self) -> String {
+        let mut html = String::new();
+        let render = Render {
+            old: &self.old,
+            new: &self.new,
+        };
+        render.items(&mut html, &self.old.items, &self.new.items, &self.steps);
+        html
+    }
+
+    /// Turn `doc`, which must have the content and formats of the old
+    /// document, into the new one. The edits form one undo step.
+    ///
+    /// Tables, merged cells included, are inserted where the new
+    /// document has them. A block cannot be inserted right after a
+    /// table, so a kept table may be removed and inserted again to make
+    /// room for one. A document without a paragraph outside its tables
+    /// cannot be patched. That fails, as does a patch whose result does
+    /// not match the new document, and leaves `doc` as it was.
+    pub fn apply(&self, doc: &TextDocument) -> Result<()> {
+        let target = Side::read(doc)?;
+        if !same_blocks(&target.blocks, &self.old.blocks, true) {
+            bail!("document does not match the old side of the diff");
+        }
+        patch(doc, &doc.cursor(), &target, &self.new, &self.steps)
+    }
+}
+
+impl fmt::Debug for DocumentDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DocumentDiff")
+            .field("changes", &self.changes)
+            .finish()
+    }
+}
+
+// ── Reading the documents ────────────────────────────────────
+
+/// One document's blocks, grouped into items.
+#[derive(Clone)]
+pub(crate) struct Side {
+    pub(crate) blocks: Vec<DocBlock>,
+    pub(crate) items: Vec<Item>,
+}
+
+/// A block or a table at the top level of a document, or a block in a
+/// table cell. Indices point into [`Side::blocks`].
+#[derive(Clone)]
+pub(crate) enum Item {
+    Block(usize),
+    Table(Table),
+}
+
+#[derive(Clone)]
+pub(crate) struct Table {
+    pub(crate) id: EntityId,
+    pub(crate) rows: usize,
+    pub(crate) columns: usize,
+    /// Blocks of each cell, row by row. Cells covered by a merged cell
+    /// have none.
+    pub(crate) cells: Vec<Vec<usize>>,
+    /// Rows and columns each cell spans, row by row.
+    pub(crate) spans: Vec<(usize, usize)>,
+}
+
+impl Side {
+    pub(crate) fn read(doc: &TextDocument) -> Result<Side> {
+        Ok(Side::of(read_blocks(&doc.inner.lock())?))
+    }
+
+    /// Group `blocks`, in document order, into items.
+    pub(crate) fn of(blocks: Vec<DocBlock>) -> Side {
+        let mut items = Vec::new();
+        let mut i = 0;
+        while i < blocks.len() {
+            let Some(first) = blocks[i].cell else {
+                items.push(Item::Block(i));
+                i += 1;
+                continue;
+            };
+            let mut cells = vec![Vec::new(); first.rows * first.columns];
+            let mut spans = vec![(1, 1); cells.len()];
+            while let Some(cell) = blocks.get(i).and_then(|b| b.cell)
+                && cell.table == first.table
+            {
+                let n = cell.row * first.columns + cell.column;
+                if let Some(blocks) = cells.get_mut(n) {
+                    blocks.push(i);
+                    spans[n] = (cell.row_span, cell.column_span);
+                }
+                i += 1;
+            }
+            items.push(Item::Table(Table {
+                id: first.table,
+                rows: first.rows,
+                columns: first.columns,
+                cells,
+                spans,
+            }));
+        }
+        Side { blocks, items }
+    }
+
+    pub(crate) fn block_items(&self, blocks: &[usize]) -> Vec<Item> {
+        blocks.iter().map(|&i| Item::Block(i)).collect()
+    }
+}
+
+impl Table {
+    pub(crate) fn cell(&self, row: usize, column: usize) -> &[usize] {
+        &self.cells[row * self.columns + column]
+    }
+
+    pub(crate) fn span(&self, row: usize, column: usize) -> (usize, usize) {
+        self.spans[row * self.columns + column]
+    }
+
+    /// Whether the cell at `row`, `column` is covered by a merged cell.
+    pub(crate) fn covered(&self, row: usize, column: usize) -> bool {
+        self.cell(row, column).is_empty()
+    }
+
+    /// The cell covering each cell, row by row: itself unless a merged
+    /// cell covers it.
+    fn owners(&self) -> Vec<(usize, usize)> {
+        let mut owners: Vec<_> = (0..self.cells.len())
+            .map(|n| (n / self.columns, n % self.columns))
+            .collect();
+        for (n, &(rows, columns)) in self.spans.iter().enumerate() {
+            let (row, column) = (n / self.columns, n % self.columns);
+            if self.covered(row, column) {
+                continue;
+            }
+            for r in row..(row + rows).min(self.rows) {
+                for c in column..(column + columns).min(self.columns) {
+                    owners[r * self.columns + c] = (row, column);
+                }
+            }
+        }
+        owners
+    }
+}
+
+fn position(block: &DocBlock) -> usize {
+    block.position
+}
+
+pub(crate) fn text_of(units: &[UnitContent]) -> String {
+    units
+        .iter()
+        .filter_map(|unit| match unit {
+            UnitContent::Char { ch, .. } => Some(*ch),
+            UnitContent::Image { .. } => Some('\u{FFFC}'),
+            UnitContent::Break { .. } => None,
+        })
+        .collect()
+}
+
+pub(crate) fn list_format(list: &ListDto) -> ListFormat {
+    ListFormat {
+        style: Some(list.style.clone()),
+        indent: u8::try_from(list.indent).ok(),
+        prefix: Some(list.prefix.clone()),
+        suffix: Some(list.suffix.clone()),
+    }
+}
+
+fn block_format(block: &DocBlock) -> BlockFormat {
+    BlockFormat::from(&block.block.clone().into())
+}
+
+pub(crate) fn block_props(block: &DocBlock) -> BlockProps {
+    BlockProps::from_block(&block.block, None, None)
+}
+
+/// Whether the blocks hold the same content, in the same table cells,
+/// and with `formats`, the same formats and list formats.
+pub(crate) fn same_blocks(a: &[DocBlock], b: &[DocBlock], formats: bool) -> bool {
+    a.len() == b.len()
+        && a.iter().zip(b).all(|(x, y)| {
+            let cell = |b: &DocBlock| {
+                b.cell.map(|c| {
+                    (
+                        c.rows,
+                        c.columns,
+                        c.row,
+                        c.column,
+                        c.row_span,
+                        c.column_span,
+                    )
+                })
+            };
+            let content = x.units.len() == y.units.len()
+                && x.units.iter().zip(&y.units).all(|(u, v)| u.same_content(v));
+            content
+                && cell(x) == cell(y)
+                && (!formats
+                    || (x.units == y.units
+                        && block_props(x) == block_props(y)
+                        && x.list.as_ref().map(list_format) == y.list.as_ref().map(list_format)))
+        })
+}
+
+// ── Alignment ────────────────────────────────────────────────
+
+/// One step of the walk through two item sequences that lines them up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Step {
+    /// The items correspond, changed or not.
+    Pair(usize, usize),
+    /// An old item with no counterpart, or one that moved to `moved_to`.
+    Removed { old: usize, moved_to: Option<usize> },
+    /// A new item with no counterpart, or one that moved from
+    /// `moved_from`.
+    Inserted {
+        new: usize,
+        moved_from: Option<usize>,
+    },
+}
+
+impl Step {
+    fn old(&self) -> Option<usize> {
+        match *self {
+            Step::Pair(old, _) | Step::Removed { old, .. } => Some(old),
+            Step::Inserted { .. } => None,
+        }
+    }
+}
+
+/// Line up items: Myers on content, then, within each changed stretch,
+/// pair the items that were edited rather than replaced, in order.
+/// Blocks removed in one place and inserted with the same text in
+/// another are marked as moved.
+pub(crate) fn align_items(old: &Side, a: &[Item], new: &Side, b: &[Item]) -> Vec<Step> {
+    let hunks = align::diff_by(a, b, |x, y| same_item(old, x, new, y));
+    let mut steps = Vec::new();
+    let mut from = (0, 0);
+    for hunk in &hunks {
+        steps.extend(
+            (from.0..hunk.a)
+                .zip(from.1..hunk.b)
+                .map(|(i, j)| Step::Pair(i, j)),
+        );
+        pair_similar(old, a, new, b, hunk, &mut steps);
+        from = (hunk.a + hunk.removed, hunk.b + hunk.added);
+    }
+    steps.extend(
+        (from.0..a.len())
+            .zip(from.1..b.len())
+            .map(|(i, j)| Step::Pair(i, j)),
+    );
+
+    let mut inserted: HashMap<String, Vec<usize>> = HashMap::new();
+    for (n, step) in steps.iter().enumerate().rev() {
+        if let Step::Inserted { new: j, .. } = *step
+            && let Item::Block(block) = b[j]
+        {
+            let text = text_of(&new.blocks[block].units);
+            if !text.is_empty() {
+                inserted.entry(text).or_default().push(n);
+            }
+        }
+    }
+    for n in 0..steps.len() {
+        let Step::Removed { old: i, .. } = steps[n] else {
+            continue;
+        };
+        let Item::Block(block) = a[i] else {
+            continue;
+        };
+        let Some(m) = inserted
+            .get_mut(&text_of(&old.blocks[block].units))
+            .and_then(Vec::pop)
+        else {
+            continue;
+        };
+        let Step::Inserted { new: j, .. } = steps[m] else {
+            continue;
+        };
+        steps[n] = Step::Removed {
+            old: i,
+            moved_to: Some(j),
+        };
+        steps[m] = Step::Inserted {
+            new: j,
+            moved_from: Some(i),
+        };
+    }
+    steps
+}
+
+fn pair_similar(
+    old: &Side,
+    a: &[Item],
+    new: &Side,
+    b: &[Item],
+    hunk: &Hunk,
+    steps: &mut Vec<Step>,
+) {
+    let b_end = hunk.b + hunk.added;
+    let pairing = hunk.removed * hunk.added <= MAX_PAIRING;
+    let mut j = hunk.b;
+    for (i, x) in a.iter().enumerate().skip(hunk.a).take(hunk.removed) {
+        let found = pairing
+            .then(|| (j..b_end).find(|&k| similar(old, x, new, &b[k])))
+            .flatten();
+        match found {
+            Some(k) => {
+                steps.extend((j..k).map(|new| Step::Inserted {
+                    new,
+                    moved_from: None,
+                }));
+                steps.push(Step::Pair(i, k));
+                j = k + 1;
+            }
+            None => steps.push(Step::Removed {
+                old: i,
+                moved_to: None,
+            }),
+        }
+    }
+    steps.extend((j..b_end).map(|new| Step::Inserted {
+        new,
+        moved_from: None,
+    }));
+}
+
+fn same_block(old: &Side, x: usize, new: &Side, y: usize) -> bool {
+    let (x, y) = (&old.blocks[x].units, &new.blocks[y].units);
+    x.len() == y.len() && x.iter().zip(y).all(|(u, v)| u.same_content(v))
+}
+
+fn same_item(old: &Side, x: &Item, new: &Side, y: &Item) -> bool {
+    match (x, y) {
+        (Item::Block(x), Item::Block(y)) => same_block(old, *x, new, *y),
+        (Item::Table(x), Item::Table(y)) => {
+            (x.rows, x.columns) == (y.rows, y.columns)
+                && x.spans == y.spans
+                && x.cells.iter().zip(&y.cells).all(|(p, q)| {
+                    p.len() == q.len() && p.iter().zip(q).all(|(i, j)| same_block(old, *i, new, *j))
+                })
+        }
+        _ => false,
+    }
+}
+
+/// Edited rather than replaced: blocks sharing at least half their
+/// characters, tables keeping their rows or their columns.
+fn similar(old: &Side, x: &Item, new: &Side, y: &Item) -> bool {
+    match (x, y) {
+        (Item::Block(x), Item::Block(y)) => {
+            let (x, y) = (&old.blocks[*x].units, &new.blocks[*y].units);
+            let hunks = align::diff_by(x, y, UnitContent::same_content);
+            let common = x.len() - hunks.iter().map(|h| h.removed).sum::<usize>();
+            4 * common >= x.len() + y.len()
+        }
+        (Item::Table(x), Item::Table(y)) => x.rows == y.rows || x.columns == y.columns,
+        _ => false,
+    }
+}
+
+/// How the rows and the columns of two tables line up. Tables with the
+/// same number of columns are aligned by rows, otherwise by columns;
+/// the other dimension is kept. Within a changed stretch rows (or
+/// columns) are paired in order.
+pub(crate) fn align_table(old: &Side, x: &Table, new: &Side, y: &Table) -> (Vec<Step>, Vec<Step>) {
+    let same_cells = |p: &[usize], q: &[usize]| {
+        p.len() == q.len() && p.iter().zip(q).all(|(i, j)| same_block(old, *i, new, *j))
+    };
+    let kept = |n: usize| (0..n).map(|i| Step::Pair(i, i)).collect();
+    if x.columns == y.columns {
+        let rows = line_up(x.rows, y.rows, |r, s| {
+            (0..x.columns).all(|c| same_cells(x.cell(r, c), y.cell(s, c)))
+        });
+        (rows, kept(x.columns))
+    } else {
+        let columns = line_up(x.columns, y.columns, |c, d| {
+            (0..x.rows).all(|r| same_cells(x.cell(r, c), y.cell(r, d)))
+        });
+        (kept(x.rows), columns)
+    }
+}
+
+fn line_up(a: usize, b: usize, eq: impl Fn(usize, usize) -> bool) -> Vec<Step> {
+    let (a_indices, b_indices): (Vec<usize>, Vec<usize>) = ((0..a).collect(), (0..b).collect());
+    let hunks = align::diff_by(&a_indices, &b_indices, |i, j| eq(*i, *j));
+    let mut steps = Vec::new();
+    let mut from = (0, 0);
+    for hunk in hunks.iter().chain(std::iter::once(&Hunk {
+        a,
+        removed: 0,
+        b,
+        added: 0,
+    })) {
+        steps.extend(
+            (from.0..hunk.a)
+                .zip(from.1..hunk.b)
+                .map(|(i, j)| Step::Pair(i, j)),
+        );
+        let paired = hunk.removed.min(hunk.added);
+        steps.extend((0..paired).map(|k| Step::Pair(hunk.a + k, hunk.b + k)));
+        steps.extend((paired..hunk.removed).map(|k| Step::Removed {
+            old: hunk.a + k,
+            moved_to: None,
+        }));
+        steps.extend((paired..hunk.added).map(|k| Step::Inserted {
+            new: hunk.b + k,
+            moved_from: None,
+        }));
+        from = (hunk.a + hunk.removed, hunk.b + hunk.added);
+    }
+    steps
+}
+
+// ── Changes ──────────────────────────────────────────────────
+
+fn changes(old: &Side, a: &[Item], new: &Side, b: &[Item], steps: &[Step]) -> Vec<DiffChange> {
+    let mut changes = Vec::new();
+    for step in steps {
+        match *step {
+            Step::Pair(i, j) => match (&a[i], &b[j]) {
+                (Item::Block(x), Item::Block(y)) => {
+                    if let Some(change) = block_change(&old.blocks[*x], &new.blocks[*y]) {
+                        changes.push(DiffChange::BlockChanged(Box::new(change)));
+                    }
+                }
+                (Item::Table(x), Item::Table(y)) => {
+                    if let Some(change) = table_change(old, x, new, y) {
+                        changes.push(DiffChange::TableChanged(change));
+                    }
+                }
+                _ => unreachable!("only items of one kind are paired"),
+            },
+            Step::Removed { old: i, moved_to } => match (&a[i], moved_to) {
+                (Item::Block(x), Some(j)) => {
+                    let Item::Block(y) = b[j] else {
+                        unreachable!("blocks move to blocks")
+                    };
+                    changes.push(DiffChange::BlockMoved {
+                        old_position: position(&old.blocks[*x]),
+                        new_position: position(&new.blocks[y]),
+                        text: text_of(&old.blocks[*x].units),
+                    });
+                }
+                (Item::Block(x), None) => changes.push(DiffChange::BlockRemoved {
+                    old_position: position(&old.blocks[*x]),
+                    text: text_of(&old.blocks[*x].units),
+                }),
+                (Item::Table(x), _) => changes.push(DiffChange::TableRemoved {
+                    old_position: table_position(old, x),
+                    rows: x.rows,
+                    columns: x.columns,
+                }),
+            },
+            Step::Inserted {
+                moved_from: Some(_),
+                ..
+            } => {}
+            Step::Inserted { new: j, .. } => match &b[j] {
+                Item::Block(y) => changes.push(DiffChange::BlockInserted {
+                    new_position: position(&new.blocks[*y]),
+                    text: text_of(&new.blocks[*y].units),
+                }),
+                Item::Table(y) => changes.push(DiffChange::TableInserted {
+                    new_position: table_position(new, y),
+                    rows: y.rows,
+                    columns: y.columns,
+                }),
+            },
+        }
+    }
+    changes
+}
+
+fn table_position(side: &Side, table: &Table) -> usize {
+    table
+        .cells
+        .iter()
+        .flatten()
+        .next()
+        .map_or(0, |&i| position(&side.blocks[i]))
+}
+
+fn block_change(x: &DocBlock, y: &DocBlock) -> Option<BlockChange> {
+    let hunks = align::diff_by(&x.units, &y.units, UnitContent::same_content);
+    let text_edits = hunks
+        .iter()
+        .map(|h| TextEdit {
+            old_offset: h.a,
+            removed: text_of(&x.units[h.a..h.a + h.removed]),
+            new_offset: h.b,
+            inserted: text_of(&y.units[h.b..h.b + h.added]),
+        })
+        .collect();
+
+    let mut format_changes: Vec<RunFormatChange> = Vec::new();
+    for (i, j) in align::unchanged(&hunks, x.units.len()) {
+        let (Some(had), Some(has)) = (x.units[i].format(), y.units[j].format()) else {
+            continue;
+        };
+        if had == has {
+            continue;
+        }
+        let (had, has) = (TextFormat::from(had), TextFormat::from(has));
+        match format_changes.last_mut() {
+            Some(run)
+                if run.old_offset + run.length == i
+                    && run.new_offset + run.length == j
+                    && run.old == had
+                    && run.new == has =>
+            {
+                run.length += 1;
+            }
+            _ => format_changes.push(RunFormatChange {
+                old_offset: i,
+                new_offset: j,
+                length: 1,
+                old: had,
+                new: has,
+            }),
+        }
+    }
+
+    let (had, has) = (block_format(x), block_format(y));
+    let block_format = (had != has).then_some((had, has));
+    let (had, has) = (
+        x.list.as_ref().map(list_format),
+        y.list.as_ref().map(list_format),
+    );
+    let list = (had != has).then_some((had, has));
+
+    let change = BlockChange {
+        old_position: position(x),
+        new_position: position(y),
+        text_edits,
+        format_changes,
+        block_format,
+        list,
+    };
+    let unchanged = change.text_edits.is_empty()
+        && change.format_changes.is_empty()
+        && change.block_format.is_none()
+        && change.list.is_none();
+    (!unchanged).then_some(change)
+}
+
+fn table_change(old: &Side, x: &Table, new: &Side, y: &Table) -> Option<TableChange> {
+    let (rows, columns) = align_table(old, x, new, y);
+    let mut change = TableChange {
+        old_position: table_position(old, x),
+        new_position: table_position(new, y),
+        rows_removed: Vec::new(),
+        rows_inserted: Vec::new(),
+        columns_removed: Vec::new(),
+        columns_inserted: Vec::new(),
+        spans: Vec::new(),
+        cells: Vec::new(),
+    };
+    for (steps, removed, inserted) in [
+        (&rows, &mut change.rows_removed, &mut change.rows_inserted),
+        (
+            &columns,
+            &mut change.columns_removed,
+            &mut change.columns_inserted,
+        ),
+    ] {
+        for step in steps {
+            match *step {
+                Step::Removed { old, .. } => removed.push(old),
+                Step::Inserted { new, .. } => inserted.push(new),
+                Step::Pair(..) => {}
+            }
+        }
+    }
+    for cell in paired_cells(x, y, &rows, &columns) {
+        let ((r, c), (s, d)) = (cell.old, cell.new);
+        if let Some(had) = cell.span {
+            change.spans.push(SpanChange {
+                old_row: r,
+                old_column: c,
+                new_row: s,
+                new_column: d,
+                old: had,
+                new: y.span(s, d),
+            });
+        }
+        let (p, q) = (old.block_items(&cell.blocks), new.block_items(y.cell(s, d)));
+        let steps = align_items(old, &p, new, &q);
+        let changes = changes(old, &p, new, &q, &steps);
+        if !changes.is_empty() {
+            change.cells.push(CellChange {
+                old_row: r,
+                old_column: c,
+                new_row: s,
+                new_column: d,
+                changes,
+            });
+        }
+    }
+    let unchanged = change.rows_removed.is_empty()
+        && change.rows_inserted.is_empty()
+        && change.columns_removed.is_empty()
+        && change.columns_inserted.is_empty()
+        && change.spans.is_empty()
+        && change.cells.is_empty();
+    (!unchanged).then_some(change)
+}
+
+/// A cell of the new table whose row and column are paired with old
+/// ones.
+struct PairedCell {
+    old: (usize, usize),
+    new: (usize, usize),
+    /// The blocks of the old cells it takes the place of: the cell at
+    /// `old` unless a merged cell covered that, and the cells a new
+    /// merged cell covers.
+    blocks: Vec<usize>,
+    /// The old span, `None` where a merged cell covered the cell, if
+    /// it changed.
+    span: Option<Option<(usize, usize)>>,
+}
+
+/// The cells of `y` paired with cells of `x` along the row and column
+/// alignments, row by row. Cells covered by a merged cell in `y` are
+/// left out.
+fn paired_cells(x: &Table, y: &Table, rows: &[Step], columns: &[Step]) -> Vec<PairedCell> {
+    let mut cells: Vec<PairedCell> = Vec::new();
+    let mut index = HashMap::new();
+    for_paired_cells(rows, columns, |(r, c), (s, d)| {
+        if y.covered(s, d) {
+            return;
+        }
+        let had = (!x.covered(r, c)).then(|| x.span(r, c));
+        index.insert((s, d), cells.len());
+        cells.push(PairedCell {
+            old: (r, c),
+            new: (s, d),
+            blocks: Vec::new(),
+            span: (had != Some(y.span(s, d))).then_some(had),
+        });
+    });
+    let owners = y.owners();
+    for_paired_cells(rows, columns, |(r, c), (s, d)| {
+        if let Some(&k) = index.get(&owners[s * y.columns + d]) {
+            cells[k].blocks.extend_from_slice(x.cell(r, c));
+        }
+    });
+    cells
+}
+
+/// Call `f` with the old and new coordinates of every cell the row and
+/// column alignments pair up, row by row.
+fn for_paired_cells(
+    rows: &[Step],
+    columns: &[Step],
+    mut f: impl FnMut((usize, usize), (usize, usize)),
+) {
+    let pairs = |steps: &[Step]| -> Vec<(usize, usize)> {
+        steps
+            .iter()
+            .filter_map(|step| match *step {
+                Step::Pair(i, j) => Some((i, j)),
+                _ => None,
+            })
+            .collect()
+    };
+    let columns = pairs(columns);
+    for (r, s) in pairs(rows) {
+        for &(c, d) in &columns {
+            f((r, c), (s, d));
+        }
+    }
+}
+
+// ── HTML ─────────────────────────────────────────────────────
+
+struct Render<'a> {
+    old: &'a Side,
+    new: &'a Side,
+}
+
+impl Render<'_> {
+    fn items(&self, html: &mut String, a: &[Item], b: &[Item], steps: &[Step]) {
+        for step in steps {
+            match *step {
+                Step::Pair(i, j) => match (&a[i], &b[j]) {
+                    (Item::Block(x), Item::Block(y)) => {
+                        self.paired_block(html, &self.old.blocks[*x], &self.new.blocks[*y])
+                    }
+                    (Item::Table(x), Item::Table(y)) => self.paired_table(html, x, y),
+                    _ => unreachable!("only items of one kind are paired"),
+                },
+                Step::Removed { old: i, moved_to } => match &a[i] {
+                    Item::Block(x) => {
+                        let class = moved_to.map(|_| "moved");
+                        whole_block(html, &self.old.blocks[*x], "del", class);
+                    }
+                    Item::Table(x) => whole_table(html, self.old, x, "del", "removed"),
+                },
+                Step::Inserted { new: j, moved_from } => match &b[j] {
+                    Item::Block(y) => {
+                        let class = moved_from.map(|_| "moved");
+                        whole_block(html, &self.new.blocks[*y], "ins", class);
+                    }
+                    Item::Table(y) => whole_table(html, self.new, y, "ins", "inserted"),
+                },
+            }
+        }
+    }
+
+    fn paired_block(&self, html: &mut String, x: &DocBlock, y: &DocBlock) {
+        let restyled = block_props(x) != block_props(y)
+            || x.list.as_ref().map(list_format) != y.list.as_ref().map(list_format);
+        let tag = open_block(html, y, restyled.then_some("format-changed"));
+        let hunks = align::diff_by(&x.units, &y.units, UnitContent::same_content);
+        let mut from = (0, 0);
+        for hunk in hunks.iter().chain(std::iter::once(&Hunk {
+            a: x.units.len(),
+            removed: 0,
+            b: y.units.len(),
+            added: 0,
+        })) {
+            // Unchanged units, split where their format changed or not.
+            let mut run = from.1;
+            while run < hunk.b {
+                let changed =
+                    |k: usize| x.units[from.0 + k - from.1].format() != y.units[k].format();
+                let restyled = changed(run);
+                let mut end = run + 1;
+                while end < hunk.b && changed(end) == restyled {
+                    end += 1;
+                }
+                if restyled {
+                    html.push_str("<span class=\"format-changed\">");
+                    units(html, &y.units[run..end]);
+                    html.push_str("</span>");
+                } else {
+                    units(html, &y.units[run..end]);
+                }
+                run = end;
+            }
+            if hunk.removed > 0 {
+                html.push_str("<del>");
+                units(html, &x.units[hunk.a..hunk.a + hunk.removed]);
+                html.push_str("</del>");
+            }
+            if hunk.added > 0 {
+                html.push_str("<ins>");
+                units(html, &y.units[hunk.b..hunk.b + hunk.added]);
+                html.push_str("</ins>");
+            }
+            from = (hunk.a + hunk.removed, hunk.b + hunk.added);
+        }
+        close_block(html, tag);
+    }
+
+    fn paired_table(&self, html: &mut String, x: &Table, y: &Table) {
+        let (rows, columns) = align_table(self.old, x, self.new, y);
+        let cells: HashMap<_, _> = paired_cells(x, y, &rows, &columns)
+            .into_iter()
+            .map(|cell| (cell.new, cell))
+            .collect();
+        html.push_str("<table>\n");
+        for row in &rows {
+            match *row {
+                Step::Removed { old: r, .. } => {
+                    html.push_str("<tr class=\"removed\">");
+                    for c in 0..x.columns {
+                        whole_cell(html, self.old, x, (r, c), "del", None);
+                    }
+                }
+                Step::Inserted { new: s, .. } => {
+                    html.push_str("<tr class=\"inserted\">");
+                    for d in 0..y.columns {
+                        whole_cell(html, self.new, y, (s, d), "ins", None);
+                    }
+                }
+                Step::Pair(r, s) => {
+                    html.push_str("<tr>");
+                    for column in &columns {
+                        match *column {
+                            Step::Removed { old: c, .. } => {
+                                whole_cell(html, self.old, x, (r, c), "del", Some("removed"))
+                            }
+                            Step::Inserted { new: d, .. } => {
+                                whole_cell(html, self.new, y, (s, d), "ins", Some("inserted"))
+                            }
+                            Step::Pair(_, d) => {
+                                let Some(cell) = cells.get(&(s, d)) else {
+                                    continue;
+                                };
+                                let p = self.old.block_items(&cell.blocks);
+                                let q = self.new.block_items(y.cell(s, d));
+                                let steps = align_items(self.old, &p, self.new, &q);
+                                let class = cell.span.map(|_| "format-changed");
+                                open_cell(html, y.span(s, d), class);
+                                self.items(html, &p, &q, &steps);
+                                html.push_str("</td>");
+                            }
+                        }
+                    }
+                }
+            }
+            html.push_str("</tr>\n");
+        }
+        html.push_str("</table>\n");
+    }
+}
+
+/// Open the element for `block` and return its tag.
+fn open_block(html: &mut String, block: &DocBlock, class: Option<&str>) -> String {
+    let tag = match block.block.fmt_heading_level {
+        Some(level @ 1..=6) => format!("h{level}"),
+        _ => "p".to_string(),
+    };
+    html.push('<');
+    html.push_str(&tag);
+    if let Some(class) = class {
+        html.push_str(&format!(" class=\"{class}\""));
+    }
+    html.push('>');
+    tag
+}
+
+fn close_block(html: &mut String, tag: String) {
+    html.push_str(&format!("</{tag}>\n"));
+}
+
+/// A block that is only in one document, its text wrapped in `mark`.
+fn whole_block(html: &mut String, block: &DocBlock, mark: &str, class: Option<&str>) {
+    let tag = open_block(html, block, class);
+    if !block.units.is_empty() {
+        html.push_str(&format!("<{mark}>"));
+        units(html, &block.units);
+        html.push_str(&format!("</{mark}>"));
+    }
+    close_block(html, tag);
+}
+
+/// Open a table cell spanning `span`, rows then columns.
+fn open_cell(html: &mut String, span: (usize, usize), class: Option<&str>) {
+    html.push_str("<td");
+    if span.0 > 1 {
+        html.push_str(&format!(" rowspan=\"{}\"", span.0));
+    }
+    if span.1 > 1 {
+        html.push_str(&format!(" colspan=\"{}\"", span.1));
+    }
+    if let Some(class) = class {
+        html.push_str(&format!(" class=\"{class}\""));
+    }
+    html.push('>');
+}
+
+/// A cell that is only in one document, unless a merged cell covers it.
+fn whole_cell(
+    html: &mut String,
+    side: &Side,
+    table: &Table,
+    (row, column): (usize, usize),
+    mark: &str,
+    class: Option<&str>,
+) {
+    if table.covered(row, column) {
+        return;
+    }
+    open_cell(html, table.span(row, column), class);
+    for &i in table.cell(row, column) {
+        whole_block(html, &side.blocks[i], mark, None);
+    }
+    html.push_str("</td>");
+}
+
+fn whole_table(html: &mut String, side: &Side, table: &Table, mark: &str, class: &str) {
+    html.push_str(&format!("<table class=\"{class}\">\n"));
+    for r in 0..table.rows {
+        html.push_str("<tr>");
+        for c in 0..table.columns {
+            whole_cell(html, side, table, (r, c), mark, None);
+        }
+        html.push_str("</tr>\n");
+    }
+    html.push_str("</table>\n");
+}
+
+fn units(html: &mut String, units: &[UnitContent]) {
+    let mut text = String::new();
+    for unit in units {
+        match unit {
+            UnitContent::Char { ch, .. } => text.push(*ch),
+            UnitContent::Image {
+                name,
+                width,
+                height,
+                ..
+            } => {
+                html.push_str(&escape_html(&std::mem::take(&mut text)));
+                html.push_str(&format!(
+                    "<img src=\"{}\" width=\"{width}\" height=\"{height}\">",
+                    escape_html(name)
+                ));
+            }
+            UnitContent::Break { .. } => {}
+        }
+    }
+    html.push_str(&escape_html(&text));
+}
+
+// ── Applying ─────────────────────────────────────────────────
+
+/// Table cell whose blocks a sequence of items lives in, `None` for the
+/// top level.
+type Container = Option<(EntityId, usize, usize)>;
+
+/// Turn `doc`, read as `target`, into `new` along `steps` with
+/// `cursor`, as one undo step on its stack. On failure the edits made
+/// so far are undone again.
+pub(crate) fn patch(
+    doc: &TextDocument,
+    cursor: &TextCursor,
+    target: &Side,
+    new: &Side,
+    steps: &[Step],
+) -> Result<()> {
+    let patch = Patch {
+        doc,
+        cursor,
+        target,
+        new,
+    };
+    cursor.begin_edit_block();
+    let result = patch
+        .items(&target.items, &new.items, steps, None, 0)
+        .and_then(|()| patch.finish());
+    if result.is_ok() {
+        cursor.end_edit_block();
+    } else {
+        cursor.cancel_edit_block();
+    }
+    result
+}
+
+/// Applies a diff to a document matching its old side. Structure and
+/// text are patched first, last change first so the positions of the
+/// ones before still hold; [`finish`](Self::finish) then sets formats
+/// and lists exactly.
+#[derive(Clone, Copy)]
+struct Patch<'a> {
+    doc: &'a TextDocument,
+    cursor: &'a TextCursor,
+    target: &'a Side,
+    new: &'a Side,
+}
+
+/// Top-level steps between two tables kept in place, or between one
+/// and a paired block or an end of the document.
+#[derive(Default)]
+struct Gap {
+    /// Old items to remove and new items to insert, in order.
+    removed: Vec<usize>,
+    inserted: Vec<usize>,
+    /// Steps of the kept tables before and after.
+    left: Option<usize>,
+    right: Option<usize>,
+}
+
+impl Patch<'_> {
+    /// Patch the items `a` of the target, which start at `start`, into
+    /// the items `b` of the new document.
+    fn items(
+        &self,
+        a: &[Item],
+        b: &[Item],
+        steps: &[Step],
+        container: Container,
+        start: usize,
+    ) -> Result<()> {
+        let tables = |items: &[Item]| items.iter().any(|item| matches!(item, Item::Table(_)));
+        if tables(a) || tables(b) {
+            return self.top_level(a, b, steps);
+        }
+        for (n, step) in steps.iter().enumerate().rev() {
+            match *step {
+                Step::Pair(i, j) => self.block(block_of(&a[i]), block_of(&b[j]))?,
+                Step::Removed { old: i, .. } => self.remove(a, i, container)?,
+                Step::Inserted { new: j, .. } => {
+                    // After the old item before it, which is still in
+                    // place: the steps before this one are yet to run.
+                    let previous = steps[..n].iter().rev().find_map(Step::old);
+                    self.insert(a, previous, block_of(&b[j]), container, start)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Patch the top level of a document with tables. A block can only
+    /// be split off another block, and a table only goes before a
+    /// block, after a non-empty one or after another table. So paired
+    /// blocks stay in place and what lies between them is patched
+    /// together, a stretch at a time from the last.
+    fn top_level(&self, a: &[Item], b: &[Item], steps: &[Step]) -> Result<()> {
+        let paired: Vec<(usize, usize, usize)> = steps
+            .iter()
+            .enumerate()
+            .filter_map(|(n, step)| match *step {
+                Step::Pair(i, j) => match (&a[i], &b[j]) {
+                    (Item::Block(x), Item::Block(y)) => Some((n, *x, *y)),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect();
+        let mut end = steps.len();
+        for k in (0..=paired.len()).rev() {
+            let from = k.checked_sub(1).map_or(0, |k| paired[k].0 + 1);
+            let before = k.checked_sub(1).map(|k| paired[k].1);
+            let after = paired.get(k).map(|&(_, x, _)| x);
+            self.stretch(a, b, &steps[from..end], before, after)?;
+            if let Some(k) = k.checked_sub(1) {
+                let (n, x, y) = paired[k];
+                self.block(x, y)?;
+                end = n;
+            }
+        }
+        Ok(())
+    }
+
+    /// Patch the top-level `steps` between the paired blocks `before`
+    /// and `after` of the target, a gap between kept tables at a time
+    /// from the last. Paired tables are kept in place unless a gap
+    /// beside them could not be patched otherwise.
+    fn stretch(
+        &self,
+        a: &[Item],
+        b: &[Item],
+        steps: &[Step],
+        before: Option<usize>,
+        after: Option<usize>,
+    ) -> Result<()> {
+        let mut kept: Vec<bool> = steps
+            .iter()
+            .map(|step| matches!(step, Step::Pair(..)))
+            .collect();
+        let gaps = loop {
+            let gaps = gaps(steps, &kept);
+            let Some(gap) = gaps.iter().find(|gap| !fillable(a, b, gap, before, after)) else {
+                break gaps;
+            };
+            match gap.right.or(gap.left) {
+                Some(n) => kept[n] = false,
+                None => bail!("cannot patch a document without a paragraph outside its tables"),
+            }
+        };
+        let table = |n: usize| match steps[n] {
+            Step::Pair(i, j) => match (&a[i], &b[j]) {
+                (Item::Table(x), Item::Table(y)) => (x, y),
+                _ => unreachable!("only tables are kept between paired blocks"),
+            },
+            _ => unreachable!("kept tables are paired"),
+        };
+        for gap in gaps.iter().rev() {
+            let left = gap.left.map(|n| table(n).0);
+            self.gap(a, b, gap, left, before, after)?;
+            if let Some(n) = gap.left {
+                let (x, y) = table(n);
+                self.table(x, y)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove the old items of `gap` and insert its new ones, next to
+    /// the kept table `left` or the paired blocks `before` and `after`.
+    fn gap(
+        &self,
+        a: &[Item],
+        b: &[Item],
+        gap: &Gap,
+        left: Option<&Table>,
+        before: Option<usize>,
+        after: Option<usize>,
+    ) -> Result<()> {
+        let before = before.filter(|_| gap.left.is_none());
+        let after = after.filter(|_| gap.right.is_none());
+        let start = match (left, before) {
+            (Some(table), _) => self.table_end(table) + 1,
+            (None, Some(k)) => {
+                position(&self.target.blocks[k]) + self.target.blocks[k].units.len() + 1
+            }
+            (None, None) => 0,
+        };
+
+        let mut length = 0;
+        for &i in &gap.removed {
+            match &a[i] {
+                Item::Block(x) => length += self.target.blocks[*x].units.len() + 1,
+                Item::Table(table) => self.cursor.remove_table(table.id as usize)?,
+            }
+        }
+        // The removed blocks now follow each other: join them to a
+        // paired block beside them, or leave one, empty, to reuse.
+        let spare = length > 0 && before.is_none() && after.is_none();
+        let (from, to) = match (before, after) {
+            _ if length == 0 => (start, start),
+            (Some(_), _) => (start - 1, start + length - 1),
+            (None, Some(_)) => (start, start + length),
+            (None, None) => (start, start + length - 1),
+        };
+        if from < to {
+            self.select(from, to);
+            self.cursor.remove_selected_text()?;
+        }
+
+        let inserted: Vec<&Item> = gap.inserted.iter().map(|&j| &b[j]).collect();
+        if spare {
+            let Some(k) = inserted
+                .iter()
+                .position(|item| matches!(item, Item::Block(_)))
+            else {
+                bail!("cannot remove the block at {start}: it has no block beside it to join");
+            };
+            let units = &self.new.blocks[block_of(inserted[k])].units;
+            self.replace(start, 0, units)?;
+            self.after(start + units.len(), !units.is_empty(), &inserted[k + 1..])?;
+            self.before(start, &inserted[..k])
+        } else if after.is_some() {
+            self.before(start, &inserted)
+        } else if let Some(k) = before {
+            let filled = !self.target.blocks[k].units.is_empty();
+            self.after(start - 1, filled, &inserted)
+        } else if let Some(table) = left {
+            self.hoisted(table, &inserted)
+        } else if inserted.is_empty() {
+            Ok(())
+        } else {
+            bail!("cannot insert before a table that starts the document")
+        }
+    }
+
+    /// Insert `items` of the new document in order before the top-level
+    /// block that starts at `at`.
+    fn before(&self, mut at: usize, items: &[&Item]) -> Result<()> {
+        for &item in items {
+            self.cursor.set_position(at, MoveMode::MoveAnchor);
+            match item {
+                Item::Block(y) => {
+                    self.cursor.insert_block()?;
+                    self.replace(at, 0, &self.new.blocks[*y].units)?;
+                }
+                Item::Table(y) => {
+                    let table = self.cursor.insert_table(y.rows, y.columns)?;
+                    self.fill_table(table.id() as EntityId, y)?;
+                }
+            }
+            at += self.size(item);
+        }
+        Ok(())
+    }
+
+    /// Insert `items` of the new document in order after the top-level
+    /// block that ends at `end`, which is empty unless `filled`.
+    fn after(&self, end: usize, filled: bool, items: &[&Item]) -> Result<()> {
+        for &item in items.iter().rev() {
+            match item {
+                Item::Block(y) => {
+                    self.cursor.set_position(end, MoveMode::MoveAnchor);
+                    self.cursor.insert_block()?;
+                    self.replace(end + 1, 0, &self.new.blocks[*y].units)?;
+                }
+                // A table goes after the block the cursor is in, but
+                // before it from its start.
+                Item::Table(y) if filled || self.block_in(end + 1, None) => {
+                    let at = if filled { end } else { end + 1 };
+                    self.cursor.set_position(at, MoveMode::MoveAnchor);
+                    let table = self.cursor.insert_table(y.rows, y.columns)?;
+                    self.fill_table(table.id() as EntityId, y)?;
+                }
+                Item::Table(y) => {
+                    // Nothing to put it before: fill the block for a
+                    // moment instead.
+                    self.cursor.set_position(end, MoveMode::MoveAnchor);
+                    self.cursor.insert_text(" ")?;
+                    self.cursor.set_position(end + 1, MoveMode::MoveAnchor);
+                    let table = self.cursor.insert_table(y.rows, y.columns)?;
+                    self.select(end, end + 1);
+                    self.cursor.remove_selected_text()?;
+                    self.fill_table(table.id() as EntityId, y)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Insert the tables `items` of the new document in order after
+    /// `table` of the target.
+    fn hoisted(&self, table: &Table, items: &[&Item]) -> Result<()> {
+        let Some(&first) = table.cells.iter().flatten().next() else {
+            bail!("table {} has no cells", table.id);
+        };
+        for &item in items.iter().rev() {
+            let Item::Table(y) = item else {
+                bail!("cannot insert a block right after a table");
+            };
+            // From inside a table, a table goes after it.
+            self.cursor
+                .set_position(position(&self.target.blocks[first]), MoveMode::MoveAnchor);
+            let inserted = self.cursor.insert_table(y.rows, y.columns)?;
+            self.fill_table(inserted.id() as EntityId, y)?;
+        }
+        Ok(())
+    }
+
+    /// Positions `item` of the new document takes.
+    fn size(&self, item: &Item) -> usize {
+        let length = |&i: &usize| self.new.blocks[i].units.len() + 1;
+        match item {
+            Item::Block(y) => length(y),
+            Item::Table(y) => y.cells.iter().flatten().map(length).sum(),
+        }
+    }
+
+    fn block(&self, x: usize, y: usize) -> Result<()> {
+        let (x, y) = (&self.target.blocks[x], &self.new.blocks[y]);
+        let start = position(x);
+        for hunk in align::diff_by(&x.units, &y.units, UnitContent::same_content)
+            .iter()
+            .rev()
+        {
+            self.replace(
+                start + hunk.a,
+                hunk.removed,
+                &y.units[hunk.b..hunk.b + hunk.added],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Reshape table `x` of the target into `y`: merged cells that do
+    /// not carry over are split, rows and columns removed and inserted,
+    /// cells merged as in `y`, and then the cells filled.
+    fn table(&self, x: &Table, y: &Table) -> Result<()> {
+        let (rows, columns) = align_table(self.target, x, self.new, y);
+        let (new_rows, new_columns) = (new_of(&rows, x.rows), new_of(&columns, x.columns));
+        // A merged cell carries over when the rows and columns it spans
+        // are kept, side by side, and it spans the same in `y`.
+        let carried = |r: usize, c: usize| {
+            let (spanned_rows, spanned_columns) = x.span(r, c);
+            new_rows[r].zip(new_columns[c]).filter(|&(s, d)| {
+                !y.covered(s, d)
+                    && y.span(s, d) == (spanned_rows, spanned_columns)
+                    && (0..spanned_rows).all(|k| new_rows.get(r + k) == Some(&Some(s + k)))
+                    && (0..spanned_columns).all(|k| new_columns.get(c + k) == Some(&Some(d + k)))
+            })
+        };
+        let mut kept = HashSet::new();
+        for (n, blocks) in x.cells.iter().enumerate().rev() {
+            let (r, c) = (n / x.columns, n % x.columns);
+            let Some(&first) = blocks.first() else {
+                continue;
+            };
+            if x.span(r, c) == (1, 1) {
+                continue;
+            }
+            match carried(r, c) {
+                Some(cell) => {
+                    kept.insert(cell);
+                }
+                None => {
+                    // Last first, so the positions of the others hold.
+                    let (spanned_rows, spanned_columns) = x.span(r, c);
+                    self.cursor
+                        .set_position(position(&self.target.blocks[first]), MoveMode::MoveAnchor);
+                    self.cursor
+                        .split_current_cell(spanned_rows, spanned_columns)?;
+                }
+            }
+        }
+
+        let table_id = x.id as usize;
+        for step in rows.iter().rev() {
+            if let Step::Removed { old, .. } = *step {
+                self.cursor.remove_table_row(table_id, old)?;
+            }
+        }
+        for step in columns.iter().rev() {
+            if let Step::Removed { old, .. } = *step {
+                self.cursor.remove_table_column(table_id, old)?;
+            }
+        }
+        for step in &rows {
+            if let Step::Inserted { new: s, .. } = *step {
+                self.cursor.insert_table_row(table_id, s)?;
+            }
+        }
+        for step in &columns {
+            if let Step::Inserted { new: d, .. } = *step {
+                self.cursor.insert_table_column(table_id, d)?;
+            }
+        }
+        self.merge_cells(x.id, y, &kept)?;
+        self.cells(x.id, y)
+    }
+
+    /// Merge and fill the cells of the table `id` just inserted for `y`.
+    fn fill_table(&self, id: EntityId, y: &Table) -> Result<()> {
+        self.merge_cells(id, y, &HashSet::new())?;
+        self.cells(id, y)
+    }
+
+    /// Merge the cells of table `id` that `y` merges, except those at
+    /// `kept`, merged already.
+    fn merge_cells(&self, id: EntityId, y: &Table, kept: &HashSet<(usize, usize)>) -> Result<()> {
+        for (n, &(rows, columns)) in y.spans.iter().enumerate() {
+            let (s, d) = (n / y.columns, n % y.columns);
+            if y.covered(s, d) || (rows, columns) == (1, 1) || kept.contains(&(s, d)) {
+                continue;
+            }
+            self.cursor
+                .merge_table_cells(id as usize, s, d, s + rows - 1, d + columns - 1)?;
+        }
+        Ok(())
+    }
+
+    /// Patch the cells of table `id`, shaped like `y` already, into
+    /// those of `y`. The document is read again, as reshaping it moved
+    /// blocks between cells.
+    fn cells(&self, id: EntityId, y: &Table) -> Result<()> {
+        let now = Side::read(self.doc)?;
+        let Some(table) = now.items.iter().find_map(|item| match item {
+            Item::Table(table) if table.id == id => Some(table),
+            _ => None,
+        }) else {
+            bail!("table {id} not found");
+        };
+        if (table.rows, table.columns) != (y.rows, y.columns) {
+            bail!("table {id} does not have the shape of the new one");
+        }
+        let patch = Patch {
+            target: &now,
+            ..*self
+        };
+        for (n, (blocks, wanted)) in table.cells.iter().zip(&y.cells).enumerate().rev() {
+            // Cells merged on one side only are caught by `finish`.
+            let Some(&first) = blocks.first().filter(|_| !wanted.is_empty()) else {
+                continue;
+            };
+            let (p, q) = (now.block_items(blocks), self.new.block_items(wanted));
+            let steps = align_items(&now, &p, self.new, &q);
+            let container = Some((id, n / y.columns, n % y.columns));
+            patch.items(&p, &q, &steps, container, position(&now.blocks[first]))?;
+        }
+        Ok(())
+    }
+
+    fn remove(&self, a: &[Item], i: usize, container: Container) -> Result<()> {
+        let x = &self.target.blocks[block_of(&a[i])];
+        let (start, end) = (position(x), position(x) + x.units.len());
+        // Join the block to the one before it, or failing that to the
+        // one after; formats are set afterwards.
+        if i > 0 {
+            self.select(start - 1, end);
+        } else if self.block_in(end + 1, container) {
+            self.select(start, end + 1);
+        } else {
+            bail!("cannot remove the block at {start}: it has no block beside it to join");
+        }
+        self.cursor.remove_selected_text()?;
+        Ok(())
+    }
+
+    /// Insert block `y` of the new document after the old item
+    /// `previous` of `a`, or at `start`.
+    fn insert(
+        &self,
+        a: &[Item],
+        previous: Option<usize>,
+        y: usize,
+        container: Container,
+        start: usize,
+    ) -> Result<()> {
+        // Split the block before at its end, or the block after at its
+        // start.
+        let (split, at) = match previous {
+            Some(k) => {
+                let x = &self.target.blocks[block_of(&a[k])];
+                let end = position(x) + x.units.len();
+                (end, end + 1)
+            }
+            None => {
+                if !self.block_in(start, container) {
+                    bail!("cannot insert a block at {start}: it has no block beside it to split");
+                }
+                (start, start)
+            }
+        };
+        self.cursor.set_position(split, MoveMode::MoveAnchor);
+        self.cursor.insert_block()?;
+        self.replace(at, 0, &self.new.blocks[y].units)
+    }
+
+    /// Replace `length` positions at `at` with `units`.
+    fn replace(&self, at: usize, length: usize, units: &[UnitContent]) -> Result<()> {
+        if length > 0 {
+            self.select(at, at + length);
+            self.cursor.remove_selected_text()?;
+        }
+        if !units.is_empty() {
+            self.cursor.set_position(at, MoveMode::MoveAnchor);
+            self.cursor.insert_fragment(&fragment_of(units))?;
+        }
+        Ok(())
+    }
+
+    fn select(&self, start: usize, end: usize) {
+        self.cursor.set_position(start, MoveMode::MoveAnchor);
+        self.cursor.set_position(end, MoveMode::KeepAnchor);
+    }
+
+    /// Whether a block of `container` starts at `position`.
+    fn block_in(&self, position: usize, container: Container) -> bool {
+        self.doc.block_at_position(position).is_some_and(|block| {
+            block.position() == position
+                && block
+                    .table_cell()
+                    .map(|c| (c.table.id() as EntityId, c.row, c.column))
+                    == container
+        })
+    }
+
+    fn table_end(&self, table: &Table) -> usize {
+        table
+            .cells
+            .iter()
+            .flatten()
+            .map(|&i| position(&self.target.blocks[i]) + self.target.blocks[i].units.len())
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// With the content in place, give every block the character
+    /// formats, block format and list of its counterpart in the new
+    /// document, then check the result.
+    fn finish(&self) -> Result<()> {
+        let now = read_blocks(&self.doc.inner.lock())?;
+        if !same_blocks(&now, &self.new.blocks, false) {
+            bail!("patched document does not match the new side of the diff");
+        }
+        self.lists(&now)?;
+
+        let stack = self.cursor.undo_stack();
+        for (block, wanted) in now.iter().zip(&self.new.blocks) {
+            let props = block_props(wanted);
+            if block_props(block) != props {
+                write_block_format(self.doc, stack, block, position(block), &props)?;
+            }
+        }
+
+        for (block, wanted) in now.iter().zip(&self.new.blocks).rev() {
+            let start = position(block);
+            let mut runs = Vec::new();
+            let mut i = 0;
+            while i < block.units.len() {
+                let (had, has) = (block.units[i].format(), wanted.units[i].format());
+                if had == has {
+                    i += 1;
+                    continue;
+                }
+                let mut end = i + 1;
+                while end < block.units.len()
+                    && block.units[end].format() == had
+                    && wanted.units[end].format() == has
+                {
+                    end += 1;
+                }
+                runs.push((i, end, had, has));
+                i = end;
+            }
+            for (i, end, had, has) in runs.into_iter().rev() {
+                match (had, has) {
+                    (Some(had), Some(has)) if adds_fields_only(had, has) => {
+                        self.select(start + i, start + end);
+                        self.cursor.set_char_format(&TextFormat::from(has))?;
+                    }
+                    _ => self.replace(start + i, end - i, &wanted.units[i..end])?,
+                }
+            }
+        }
+
+        let now = read_blocks(&self.doc.inner.lock())?;
+        if !same_blocks(&now, &self.new.blocks, true) {
+            bail!("patched document does not match the new side of the diff");
+        }
+        Ok(())
+    }
+
+    /// Put every block in the list its counterpart is in, reusing the
+    /// document's lists where their members carry over.
+    fn lists(&self, now: &[DocBlock]) -> Result<()> {
+        // New list → document list standing for it.
+        let mut lists: HashMap<EntityId, EntityId> = HashMap::new();
+        let mut used = HashSet::new();
+        for (block, wanted) in now.iter().zip(&self.new.blocks) {
+            if let (Some(list), Some(wanted)) = (&block.list, &wanted.list)
+                && !lists.contains_key(&wanted.id)
+                && used.insert(list.id)
+            {
+                lists.insert(wanted.id, list.id);
+            }
+        }
+        for (block, wanted) in now.iter().zip(&self.new.blocks) {
+            let had = block.list.as_ref().map(|l| l.id);
+            let Some(wanted) = &wanted.list else {
+                if had.is_some() {
+                    self.cursor.remove_block_from_list(block.id as usize)?;
+                }
+                continue;
+            };
+            match lists.get(&wanted.id) {
+                Some(&list) if had != Some(list) => {
+                    self.cursor
+                        .add_block_to_list(block.id as usize, list as usize)?;
+                }
+                Some(_) => {}
+                None => {
+                    self.cursor
+                        .set_position(position(block), MoveMode::MoveAnchor);
+                    self.cursor.create_list(wanted.style.clone())?;
+                    if let Some(list) = self.cursor.current_list() {
+                        lists.insert(wanted.id, list.id() as EntityId);
+                    }
+                }
+            }
+        }
+        for wanted in self.new.blocks.iter().filter_map(|b| b.list.as_ref()) {
+            let Some(list) = lists.remove(&wanted.id) else {
+                continue;
+            };
+            let current = list_commands::get_list(&self.doc.inner.lock().ctx, &list)?;
+            if current.is_none_or(|l| list_format(&l) != list_format(wanted)) {
+                self.cursor
+                    .set_list_format(list as usize, &list_format(wanted))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The block an item of a sequence without tables is.
+fn block_of(item: &Item) -> usize {
+    match item {
+        Item::Block(i) => *i,
+        Item::Table(_) => unreachable!("tables are patched a stretch at a time"),
+    }
+}
+
+/// Whether the editing API can patch the old items of `gap` into its
+/// new ones, with the paired blocks `before` and `after` around it.
+fn fillable(
+    a: &[Item],
+    b: &[Item],
+    gap: &Gap,
+    before: Option<usize>,
+    after: Option<usize>,
+) -> bool {
+    let blocks =
+        |items: &[Item], of: &[usize]| of.iter().any(|&i| matches!(items[i], Item::Block(_)));
+    if (gap.left.is_none() && before.is_some()) || (gap.right.is_none() && after.is_some()) {
+        true
+    } else if blocks(a, &gap.removed) {
+        // One of the removed blocks stays, as one of the new ones.
+        blocks(b, &gap.inserted)
+    } else {
+        // Tables can only follow a table.
+        gap.inserted.is_empty() || (gap.left.is_some() && !blocks(b, &gap.inserted))
+    }
+}
+
+/// Split top-level steps at the `kept` tables.
+fn gaps(steps: &[Step], kept: &[bool]) -> Vec<Gap> {
+    let mut gaps = vec![Gap::default()];
+    for (n, step) in steps.iter().enumerate() {
+        let Some(gap) = gaps.last_mut() else {
+            unreachable!("there is always a gap");
+        };
+        match *step {
+            _ if kept[n] => {
+                gap.right = Some(n);
+                gaps.push(Gap {
+                    left: Some(n),
+                    ..Gap::default()
+                });
+            }
+            Step::Pair(i, j) => {
+                gap.removed.push(i);
+                gap.inserted.push(j);
+            }
+            Step::Removed { old, .. } => gap.removed.push(old),
+            Step::Inserted { new, .. } => gap.inserted.push(new),
+        }
+    }
+    gaps
+}
+
+/// The new row or column paired with each of the `len` old ones.
+pub(crate) fn new_of(steps: &[Step], len: usize) -> Vec<Option<usize>> {
+    let mut new = vec![None; len];
+    for step in steps {
+        if let Step::Pair(i, j) = *step {
+            new[i] = Some(j);
+        }
+    }
+    new
+}
@@ -0,0 +1,323 @@
+//! The document as blocks of replica units, shared by the collaboration
+//! layer and document diffs: reading it, and writing units and block
+//! formats back through the document's editing API.
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+use frontend::commands::{
+    block_commands, frame_commands, list_commands, table_cell_commands, table_commands,
+};
+use frontend::common::entities::Block;
+use frontend::common::format_runs::{
+    CharacterFormat, FormatRun, ImageAnchor, InlineContent, InlineSegment,
+    apply_character_format_to_segment, character_format_from_segment, inline_segments_view,
+};
+use frontend::common::parser_tools::fragment_schema::{
+    FragmentBlock, FragmentData, FragmentElement,
+};
+use frontend::common::types::EntityId;
+use frontend::list::dtos::ListDto;
+
+use crate::convert::to_usize;
+use crate::fragment::DocumentFragment;
+use crate::inner::TextDocumentInner;
+use crate::{DocumentEvent, TextDocument, UndoStackId};
+
+/// The content units and block properties blocks are read into. The
+/// replica defines them; diffs and merges use them through here.
+pub(crate) use frontend::common::collab::{BlockProps, UnitContent};
+
+// ── Reading the document ─────────────────────────────────────
+
+/// One block of the document, in the terms the replica uses.
+#[derive(Clone)]
+pub(crate) struct DocBlock {
+    pub(crate) id: EntityId,
+    pub(crate) block: Block,
+    pub(crate) list: Option<ListDto>,
+    pub(crate) cell: Option<DocCell>,
+    /// Document position of the block's first unit.
+    pub(crate) position: usize,
+    /// Characters and images.
+    pub(crate) units: Vec<UnitContent>,
+}
+
+/// Table cell a block sits in, with the table's shape and the rows
+/// and columns the cell spans.
+#[derive(Clone, Copy)]
+pub(crate) struct DocCell {
+    pub(crate) table: EntityId,
+    pub(crate) rows: usize,
+    pub(crate) columns: usize,
+    pub(crate) row: usize,
+    pub(crate) column: usize,
+    pub(crate) row_span: usize,
+    pub(crate) column_span: usize,
+}
+
+/// All blocks in document order.
+pub(crate) fn read_blocks(inner: &TextDocumentInner) -> Result<Vec<DocBlock>> {
+    let ctx = &inner.ctx;
+    let mut cells = HashMap::new();
+    for table in table_commands::get_all_table(ctx)? {
+        for cell_id in &table.cells {
+            let Some(cell) = table_cell_commands::get_table_cell(ctx, cell_id)? else {
+                continue;
+            };
+            let Some(frame) = cell
+                .cell_frame
+                .and_then(|f| frame_commands::get_frame(ctx, &f).ok().flatten())
+            else {
+                continue;
+            };
+            let doc_cell = DocCell {
+                table: table.id,
+                rows: to_usize(table.rows),
+                columns: to_usize(table.columns),
+                row: to_usize(cell.row),
+                column: to_usize(cell.column),
+                row_span: to_usize(cell.row_span),
+                column_span: to_usize(cell.column_span),
+            };
+            for block_id in frame.blocks {
+                cells.insert(block_id, doc_cell);
+            }
+        }
+    }
+
+    let store = inner.ctx.db_context.get_store();
+    let mut dtos = block_commands::get_all_block(ctx)?;
+    crate::inner::refresh_block_positions(&mut dtos, store);
+    dtos.sort_by_key(|b| b.document_position);
+
+    let mut blocks = Vec::with_capacity(dtos.len());
+    let mut position = 0;
+    for dto in dtos {
+        let list = match dto.list {
+            Some(list) => list_commands::get_list(ctx, &list)?,
+            None => None,
+        };
+        let block: Block = dto.into();
+        let text = frontend::common::database::rope_helpers::block_content_via_store(&block, store);
+        let runs: Vec<FormatRun> = store
+            .format_runs
+            .read()
+            .unwrap()
+            .get(&block.id)
+            .cloned()
+            .unwrap_or_default();
+        let images: Vec<ImageAnchor> = store
+            .block_images
+            .read()
+            .unwrap()
+            .get(&block.id)
+            .cloned()
+            .unwrap_or_default();
+        let mut units = Vec::with_capacity(text.len());
+        for segment in inline_segments_view(&text, &runs, &images) {
+            let format = character_format_from_segment(&segment);
+            match segment.content {
+                InlineContent::Text(s) => units.extend(s.chars().map(|ch| UnitContent::Char {
+                    ch,
+                    format: format.clone(),
+                })),
+                InlineContent::Image {
+                    name,
+                    width,
+                    height,
+                    quality,
+                } => units.push(UnitContent::Image {
+                    name,
+                    width,
+                    height,
+                    quality,
+                    format,
+                }),
+                InlineContent::Empty => {}
+            }
+        }
+        let length = units.len();
+        blocks.push(DocBlock {
+            id: block.id,
+            cell: cells.get(&block.id).copied(),
+            block,
+            list,
+            position,
+            units,
+        });
+        position += length + 1;
+    }
+    Ok(blocks)
+}
+
+// ── Writing to the document ──────────────────────────────────
+
+/// Give `block`, which starts at `position`, exactly the format in
+/// `props`, recording the change on `stack`. `set_block_format` cannot
+/// clear a field, so the block is written directly.
+pub(crate) fn write_block_format(
+    doc: &TextDocument,
+    stack: UndoStackId,
+    block: &DocBlock,
+    position: usize,
+    props: &BlockProps,
+) -> Result<()> {
+    let queued = {
+        let mut inner = doc.inner.lock();
+        let Some(dto) = block_commands::get_block(&inner.ctx, &block.id)? else {
+            return Ok(());
+        };
+        let mut entity: Block = dto.into();
+        if BlockProps::from_block(&entity, None, None) == format_only(props) {
+            return Ok(());
+        }
+        props.apply_format_to(&mut entity);
+        block_commands::update_block(&inner.ctx, Some(stack.0), &entity.into())?;
+        inner.modified = true;
+        inner.queue_event(DocumentEvent::FormatChanged {
+            position,
+            length: block.units.len(),
+            kind: crate::flow::FormatChangeKind::Block,
+        });
+        inner.queue_undo_state(stack.0);
+        inner.take_queued_events()
+    };
+    crate::inner::dispatch_queued_events(queued);
+    Ok(())
+}
+
+/// `props` with list and cell left out, for comparing formats.
+fn format_only(props: &BlockProps) -> BlockProps {
+    BlockProps {
+        list: None,
+        cell: None,
+        ..props.clone()
+    }
+}
+
+/// True when setting `to` over `from` with `set_char_format` gives
+/// exactly `to`: it adds or changes fields but clears none, and leaves
+/// the anchor fields alone.
+pub(crate) fn adds_fields_only(from: &CharacterFormat, to: &CharacterFormat) -> bool {
+    let kept = |a: bool, b: bool| !a || b;
+    kept(from.font_family.is_some(), to.font_family.is_some())
+        && kept(from.font_point_size.is_some(), to.font_point_size.is_some())
+        && kept(from.font_weight.is_some(), to.font_weight.is_some())
+        && kept(from.font_bold.is_some(), to.font_bold.is_some())
+        && kept(from.font_italic.is_some(), to.font_italic.is_some())
+        && kept(from.font_underline.is_some(), to.font_underline.is_some())
+        && kept(from.font_overline.is_some(), to.font_overline.is_some())
+        && kept(from.font_strikeout.is_some(), to.font_strikeout.is_some())
+        && kept(from.letter_spacing.is_some(), to.letter_spacing.is_some())
+        && kept(from.word_spacing.is_some(), to.word_spacing.is_some())
+        && kept(from.underline_style.is_some(), to.underline_style.is_some())
+        && kept(
+            from.vertical_alignment.is_some(),
+            to.vertical_alignment.is_some(),
+        )
+        && kept(
+            from.foreground_color.is_some(),
+            to.foreground_color.is_some(),
+        )
+        && kept(
+            from.background_color.is_some(),
+            to.background_color.is_some(),
+        )
+        && kept(from.underline_color.is_some(), to.underline_color.is_some())
+        && from.anchor_href == to.anchor_href
+        && from.anchor_names == to.anchor_names
+        && from.is_anchor == to.is_anchor
+        && from.tooltip == to.tooltip
+}
+
+/// A fragment holding `units`: the units before the first break join
+/// the block at the insertion point, each break starts a block. List
+/// membership is left for `set_block_props`.
+pub(crate) fn fragment_of(units: &[UnitContent]) -> DocumentFragment {
+    let mut blocks = vec![fragment_block(&BlockProps::default())];
+    for unit in units {
+        let block = blocks.last_mut().expect("at least one block");
+        match unit {
+            UnitContent::Char { ch, format } => {
+                block.plain_text.push(*ch);
+                match block.elements.last_mut() {
+                    Some(
+                        element @ FragmentElement {
+                            content: InlineContent::Text(_),
+                            ..
+                        },
+                    ) if element.to_character_format() == *format => {
+                        if let InlineContent::Text(text) = &mut element.content {
+                            text.push(*ch);
+                        }
+                    }
+                    _ => block.elements.push(fragment_element(
+                        InlineContent::Text(ch.to_string()),
+                        format,
+                    )),
+                }
+            }
+            UnitContent::Image {
+                name,
+                width,
+                height,
+                quality,
+                format,
+            } => block.elements.push(fragment_element(
+                InlineContent::Image {
+                    name: name.clone(),
+                    width: *width,
+                    height: *height,
+                    quality: *quality,
+                },
+                format,
+            )),
+            UnitContent::Break { props } => blocks.push(fragment_block(props)),
+        }
+    }
+    let plain_text = blocks
+        .iter()
+        .map(|b| b.plain_text.as_str())
+        .collect::<Vec<_>>()
+        .join("\n");
+    let data = serde_json::to_string(&FragmentData {
+        blocks,
+        tables: vec![],
+    })
+    .expect("fragment serialization should not fail");
+    DocumentFragment::from_raw(data, plain_text)
+}
+
+fn fragment_element(content: InlineContent, format: &CharacterFormat) -> FragmentElement {
+    let mut segment = InlineSegment {
+        content,
+        ..Default::default()
+    };
+    apply_character_format_to_segment(&mut segment, format);
+    FragmentElement::from_segment(&segment)
+}
+
+fn fragment_block(props: &BlockProps) -> FragmentBlock {
+    FragmentBlock {
+        plain_text: String::new(),
+        elements: Vec::new(),
+        heading_level: props.heading_level,
+        list: None,
+        alignment: props.alignment.clone(),
+        indent: props.indent,
+        text_indent: props.text_indent,
+        marker: props.marker.clone(),
+        top_margin: props.top_margin,
+        bottom_margin: props.bottom_margin,
+        left_margin: props.left_margin,
+        right_margin: props.right_margin,
+        tab_positions: props.tab_positions.clone(),
+        line_height: props.line_height,
+        non_breakable_lines: props.non_breakable_lines,
+        direction: props.direction.clone(),
+        background_color: props.background_color.clone(),
+        is_code_block: props.is_code_block,
+        code_language: props.code_language.clone(),
+    }
+}
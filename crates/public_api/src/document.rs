@@ -1,5 +1,6 @@
 //! TextDocument implementation.
 
+use std::path::Path;
 use std::sync::Arc;
 
 use parking_lot::Mutex;
@@ -10,18 +11,28 @@ use base64::engine::general_purpose::STANDARD as BASE64;
 
 use crate::{ResourceType, TextDirection, WrapMode};
 use frontend::commands::{
-    block_commands, document_commands, document_inspection_commands, document_io_commands,
-    document_search_commands, frame_commands, resource_commands, table_cell_commands,
-    table_commands, undo_redo_commands,
+    block_commands, document_commands, document_formatting_commands, document_inspection_commands,
+    document_io_commands, document_search_commands, frame_commands, resource_commands,
+    root_commands, table_cell_commands, table_commands, undo_redo_commands,
 };
 
 use crate::convert::{self, to_i64, to_usize};
-use crate::cursor::TextCursor;
+use crate::cursor::{TextCursor, max_cursor_position};
+use crate::edit_log::{self, EditAction, EditLog};
 use crate::events::{self, DocumentEvent, Subscription};
 use crate::flow::FormatChangeKind;
 use crate::inner::TextDocumentInner;
-use crate::operation::{DocxExportResult, HtmlImportResult, MarkdownImportResult, Operation};
-use crate::{BlockFormat, BlockInfo, DocumentStats, FindMatch, FindOptions};
+use crate::journal::{self, Journal, JournalOptions};
+use crate::operation::{
+    DocxExportResult, DocxImportResult, EpubExportResult, HtmlImportResult, MarkdownImportResult,
+    OdtExportResult, OdtImportResult, Operation, RtfImportResult,
+};
+use crate::search::SearchHits;
+use crate::search_index::SearchIndex;
+use crate::{
+    BlockFormat, BlockInfo, DocumentStats, EpubOptions, FindMatch, FindOptions, FormatQuery,
+    SearchHit, TextFormat, UndoHistory, UndoHistoryEntry, UndoLimit, UndoStackId,
+};
 
 /// A rich text document.
 ///
@@ -168,7 +179,9 @@ impl TextDocument {
                 plain_text: text.into(),
             };
             document_io_commands::import_plain_text(&inner.ctx, &dto)?;
-            undo_redo_commands::clear_stack(&inner.ctx, inner.stack_id);
+            inner.log_edit(None, || EditAction::SetPlainText { text: text.into() });
+            inner.clear_undo_stacks();
+            inner.clear_frame_undo_stacks();
             inner.invalidate_text_cache();
             inner.rehighlight_all();
             inner.queue_event(DocumentEvent::DocumentReset);
@@ -200,6 +213,9 @@ impl TextDocument {
             markdown_text: markdown.into(),
         };
         let op_id = document_io_commands::import_markdown(&inner.ctx, &dto)?;
+        inner.log_edit(None, || EditAction::SetMarkdown {
+            markdown: markdown.into(),
+        });
         Ok(Operation::new(
             op_id,
             &inner.ctx,
@@ -233,6 +249,7 @@ impl TextDocument {
             html_text: html.into(),
         };
         let op_id = document_io_commands::import_html(&inner.ctx, &dto)?;
+        inner.log_edit(None, || EditAction::SetHtml { html: html.into() });
         Ok(Operation::new(
             op_id,
             &inner.ctx,
@@ -267,6 +284,41 @@ impl TextDocument {
         Ok(result.latex_text)
     }
 
+    /// Replace the entire document with the contents of a DOCX file.
+    /// Clears undo history.
+    ///
+    /// Paragraphs, headings, run formatting, lists, tables (including
+    /// merged cells), hyperlinks and embedded images are imported;
+    /// images become [`ResourceType::Image`] resources named after their
+    /// part in the package.
+    ///
+    /// This is a **long operation**. Returns a typed [`Operation`] handle.
+    pub fn set_docx(&self, docx: &[u8]) -> Result<Operation<DocxImportResult>> {
+        let mut inner = self.inner.lock();
+        inner.invalidate_text_cache();
+        let dto = frontend::document_io::ImportDocxDto {
+            docx_data: docx.to_vec(),
+        };
+        let op_id = document_io_commands::import_docx(&inner.ctx, &dto)?;
+        inner.log_edit(None, || EditAction::SetDocx {
+            data_base64: BASE64.encode(docx),
+        });
+        Ok(Operation::new(
+            op_id,
+            &inner.ctx,
+            Box::new(|ctx, id| {
+                document_io_commands::get_import_docx_result(ctx, id)
+                    .ok()
+                    .flatten()
+                    .map(|r| {
+                        Ok(DocxImportResult {
+                            block_count: to_usize(r.block_count),
+                        })
+                    })
+            }),
+        ))
+    }
+
     /// Export the entire document as DOCX to a file path.
     ///
     /// This is a **long operation**. Returns a typed [`Operation`] handle.
@@ -293,6 +345,222 @@ impl TextDocument {
         ))
     }
 
+    /// Replace the entire document with the contents of an ODT file.
+    /// Clears undo history.
+    ///
+    /// Paragraphs, headings, character formatting, lists, tables
+    /// (including spanned cells), hyperlinks and embedded images are
+    /// imported; images become [`ResourceType::Image`] resources named
+    /// after their file in the package.
+    ///
+    /// This is a **long operation**. Returns a typed [`Operation`] handle.
+    pub fn set_odt(&self, odt: &[u8]) -> Result<Operation<OdtImportResult>> {
+        let mut inner = self.inner.lock();
+        inner.invalidate_text_cache();
+        let dto = frontend::document_io::ImportOdtDto {
+            odt_data: odt.to_vec(),
+        };
+        let op_id = document_io_commands::import_odt(&inner.ctx, &dto)?;
+        inner.log_edit(None, || EditAction::SetOdt {
+            data_base64: BASE64.encode(odt),
+        });
+        Ok(Operation::new(
+            op_id,
+            &inner.ctx,
+            Box::new(|ctx, id| {
+                document_io_commands::get_import_odt_result(ctx, id)
+                    .ok()
+                    .flatten()
+                    .map(|r| {
+                        Ok(OdtImportResult {
+                            block_count: to_usize(r.block_count),
+                        })
+                    })
+            }),
+        ))
+    }
+
+    /// Export the entire document as OpenDocument Text to a file path.
+    /// Image resources are embedded in the package.
+    ///
+    /// This is a **long operation**. Returns a typed [`Operation`] handle.
+    pub fn to_odt(&self, output_path: &str) -> Result<Operation<OdtExportResult>> {
+        let inner = self.inner.lock();
+        let dto = frontend::document_io::ExportOdtDto {
+            output_path: output_path.into(),
+        };
+        let op_id = document_io_commands::export_odt(&inner.ctx, &dto)?;
+        Ok(Operation::new(
+            op_id,
+            &inner.ctx,
+            Box::new(|ctx, id| {
+                document_io_commands::get_export_odt_result(ctx, id)
+                    .ok()
+                    .flatten()
+                    .map(|r| {
+                        Ok(OdtExportResult {
+                            file_path: r.file_path,
+                            paragraph_count: to_usize(r.paragraph_count),
+                        })
+                    })
+            }),
+        ))
+    }
+
+    /// Replace the entire document with RTF. Clears undo history.
+    ///
+    /// Paragraph alignment and indents, outline-level headings, character
+    /// formatting, fonts, hyperlinks, tables (including merged cells) and
+    /// PNG/JPEG/metafile `\pict` images are imported; images become
+    /// [`ResourceType::Image`] resources named `image1.png`, `image2.jpg`, ….
+    ///
+    /// This is a **long operation**. Returns a typed [`Operation`] handle.
+    pub fn set_rtf(&self, rtf: &str) -> Result<Operation<RtfImportResult>> {
+        let mut inner = self.inner.lock();
+        inner.invalidate_text_cache();
+        let dto = frontend::document_io::ImportRtfDto {
+            rtf_text: rtf.into(),
+        };
+        let op_id = document_io_commands::import_rtf(&inner.ctx, &dto)?;
+        inner.log_edit(None, || EditAction::SetRtf { rtf: rtf.into() });
+        Ok(Operation::new(
+            op_id,
+            &inner.ctx,
+            Box::new(|ctx, id| {
+                document_io_commands::get_import_rtf_result(ctx, id)
+                    .ok()
+                    .flatten()
+                    .map(|r| {
+                        Ok(RtfImportResult {
+                            block_count: to_usize(r.block_count),
+                        })
+                    })
+            }),
+        ))
+    }
+
+    /// Export the entire document as RTF. PNG and JPEG images are
+    /// embedded as `\pict` groups.
+    pub fn to_rtf(&self) -> Result<String> {
+        let inner = self.inner.lock();
+        let dto = document_io_commands::export_rtf(&inner.ctx)?;
+        Ok(dto.rtf_text)
+    }
+
+    /// Export the entire document as an EPUB 3 book to a file path.
+    ///
+    /// The body is rendered as XHTML (the same markup as
+    /// [`to_html()`](Self::to_html)) and split into one chapter per
+    /// heading of level `options.heading_level` or above. The navigation
+    /// document lists every heading, image resources are packaged with the
+    /// book and the document title goes into the package metadata.
+    ///
+    /// This is a **long operation**. Returns a typed [`Operation`] handle.
+    pub fn to_epub(
+        &self,
+        output_path: &str,
+        options: &EpubOptions,
+    ) -> Result<Operation<EpubExportResult>> {
+        let inner = self.inner.lock();
+        let dto = frontend::document_io::ExportEpubDto {
+            output_path: output_path.into(),
+            heading_level: options.heading_level as i64,
+            author: options.author.clone(),
+            language: options.language.clone(),
+            identifier: options.identifier.clone(),
+        };
+        let op_id = document_io_commands::export_epub(&inner.ctx, &dto)?;
+        Ok(Operation::new(
+            op_id,
+            &inner.ctx,
+            Box::new(|ctx, id| {
+                document_io_commands::get_export_epub_result(ctx, id)
+                    .ok()
+                    .flatten()
+                    .map(|r| {
+                        Ok(EpubExportResult {
+                            file_path: r.file_path,
+                            chapter_count: to_usize(r.chapter_count),
+                        })
+                    })
+            }),
+        ))
+    }
+
+    /// Write the entire document in the lossless native format.
+    ///
+    /// Unlike the Markdown, HTML, LaTeX and DOCX exporters, the native
+    /// format keeps every entity — frames, table and cell formats, list
+    /// prefixes/suffixes, resources, format runs and image anchors — so
+    /// [`load_native()`](Self::load_native) restores an identical document.
+    pub fn save_native(&self, writer: &mut impl std::io::Write) -> Result<()> {
+        let inner = self.inner.lock();
+        let dto = document_io_commands::export_native(&inner.ctx)?;
+        writer.write_all(dto.native_data.as_bytes())?;
+        Ok(())
+    }
+
+    /// Like [`save_native()`](Self::save_native), also writing the undo
+    /// and redo history so [`load_native()`](Self::load_native) can
+    /// restore it.
+    ///
+    /// Saving reads what every entry recorded when it ran; neither the
+    /// document nor its undo stacks are touched and no events are
+    /// emitted. If an entry can't be saved, nothing is written and the
+    /// error is returned.
+    pub fn save_native_with_history(&self, writer: &mut impl std::io::Write) -> Result<()> {
+        let inner = self.inner.lock();
+        let dto =
+            document_io_commands::export_native_with_history(&inner.ctx, Some(inner.stack_id))?;
+        writer.write_all(dto.native_data.as_bytes())?;
+        Ok(())
+    }
+
+    /// Replace the entire document with one written by
+    /// [`save_native()`](Self::save_native) or
+    /// [`save_native_with_history()`](Self::save_native_with_history).
+    ///
+    /// The undo history is replaced by the one saved in the file, if any.
+    /// A saved history from an unsupported version, or one that does not
+    /// match the saved document, is discarded and the history is left
+    /// empty; the document itself still loads.
+    ///
+    /// Files written by an older version of the format are upgraded on
+    /// load; files from a newer version are rejected and the current
+    /// document is left untouched.
+    pub fn load_native(&self, mut reader: impl std::io::Read) -> Result<()> {
+        let mut native_data = String::new();
+        reader.read_to_string(&mut native_data)?;
+        let queued = {
+            let mut inner = self.inner.lock();
+            let dto = frontend::document_io::ImportNativeDto { native_data };
+            document_io_commands::import_native_with_history(&inner.ctx, inner.stack_id, &dto)?;
+            inner.log_edit(None, || EditAction::LoadNative {
+                data: dto.native_data.clone(),
+            });
+            for stack in &inner.undo_stacks {
+                undo_redo_commands::clear_stack(&inner.ctx, *stack);
+            }
+            inner.clear_frame_undo_stacks();
+            // The loaded tree brings its own entity IDs.
+            if let Some(root) = root_commands::get_root(&inner.ctx, &inner.root_id)? {
+                inner.document_id = root.document;
+            }
+            inner.resource_cache.clear();
+            inner.invalidate_text_cache();
+            inner.rehighlight_all();
+            inner.queue_event(DocumentEvent::DocumentReset);
+            inner.check_block_count_changed();
+            inner.reset_cached_child_order();
+            let can_undo = undo_redo_commands::can_undo(&inner.ctx, Some(inner.stack_id));
+            let can_redo = undo_redo_commands::can_redo(&inner.ctx, Some(inner.stack_id));
+            inner.queue_event(DocumentEvent::UndoRedoChanged { can_undo, can_redo });
+            inner.take_queued_events()
+        };
+        crate::inner::dispatch_queued_events(queued);
+        Ok(())
+    }
+
     /// Clear all document content and reset to an empty state.
     pub fn clear(&self) -> Result<()> {
         let queued = {
@@ -301,7 +569,9 @@ impl TextDocument {
                 plain_text: String::new(),
             };
             document_io_commands::import_plain_text(&inner.ctx, &dto)?;
-            undo_redo_commands::clear_stack(&inner.ctx, inner.stack_id);
+            inner.log_edit(None, || EditAction::Clear);
+            inner.clear_undo_stacks();
+            inner.clear_frame_undo_stacks();
             inner.invalidate_text_cache();
             inner.rehighlight_all();
             inner.queue_event(DocumentEvent::DocumentReset);
@@ -676,7 +946,16 @@ impl TextDocument {
         from: usize,
         options: &FindOptions,
     ) -> Result<Option<FindMatch>> {
-        let inner = self.inner.lock();
+        let mut inner = self.inner.lock();
+        if SearchIndex::supports(query, options) {
+            let top_frame_count = inner.top_frame_count();
+            let store = Arc::clone(inner.ctx.db_context.get_store());
+            if let Some(index) = &mut inner.search_index
+                && let Some(found) = index.find(&store, top_frame_count, query, from, options)
+            {
+                return Ok(found);
+            }
+        }
         let dto = options.to_find_text_dto(query, from);
         let result = document_search_commands::find_text(&inner.ctx, &dto)?;
         Ok(convert::find_result_to_match(&result))
@@ -684,12 +963,81 @@ impl TextDocument {
 
     /// Find all occurrences.
     pub fn find_all(&self, query: &str, options: &FindOptions) -> Result<Vec<FindMatch>> {
-        let inner = self.inner.lock();
+        let mut inner = self.inner.lock();
+        if SearchIndex::supports(query, options) {
+            let top_frame_count = inner.top_frame_count();
+            let store = Arc::clone(inner.ctx.db_context.get_store());
+            if let Some(index) = &mut inner.search_index
+                && let Some(found) = index.find_all(&store, top_frame_count, query, options)
+            {
+                return Ok(found);
+            }
+        }
         let dto = options.to_find_all_dto(query);
         let result = document_search_commands::find_all(&inner.ctx, &dto)?;
         Ok(convert::find_all_to_matches(&result))
     }
 
+    /// Like [`find`](Self::find), but with the match's block, frame,
+    /// table cell, surrounding text and capture groups.
+    pub fn find_detailed(
+        &self,
+        query: &str,
+        from: usize,
+        options: &FindOptions,
+    ) -> Result<Option<SearchHit>> {
+        let inner = self.inner.lock();
+        let mut dto = options.to_find_text_dto(query, from);
+        dto.with_details = true;
+        let result = document_search_commands::find_text(&inner.ctx, &dto)?;
+        Ok(convert::find_result_to_hit(&result))
+    }
+
+    /// Like [`find_all`](Self::find_all), but with each match's block,
+    /// frame, table cell, surrounding text and capture groups.
+    pub fn find_all_detailed(&self, query: &str, options: &FindOptions) -> Result<Vec<SearchHit>> {
+        let inner = self.inner.lock();
+        let mut dto = options.to_find_all_dto(query);
+        dto.with_details = true;
+        let result = document_search_commands::find_all(&inner.ctx, &dto)?;
+        Ok(convert::find_all_to_hits(&result))
+    }
+
+    /// Keep a search index so that literal and whole-word
+    /// [`find`](Self::find) and [`find_all`](Self::find_all) read only
+    /// the paragraphs that may contain the query, instead of the whole
+    /// document. The index is updated as the document is edited, at a
+    /// small cost per edit, and takes about a byte per character.
+    ///
+    /// Searches the index can't answer still scan the document: regular
+    /// expressions, fuzzy and folded searches, frame and table scopes,
+    /// queries spanning paragraphs or shorter than three characters, and
+    /// documents containing tables or several top-level frames.
+    pub fn set_search_index_enabled(&self, enabled: bool) {
+        let mut inner = self.inner.lock();
+        if enabled != inner.search_index.is_some() {
+            inner.search_index = enabled.then(SearchIndex::new);
+        }
+    }
+
+    /// Whether a search index is kept. See
+    /// [`set_search_index_enabled`](Self::set_search_index_enabled).
+    pub fn is_search_index_enabled(&self) -> bool {
+        self.inner.lock().search_index.is_some()
+    }
+
+    /// Iterate over the matches lazily, fetching `page_size` detailed
+    /// hits at a time. Each page is searched against the document as it
+    /// is then, so edits made while iterating show up in later pages.
+    ///
+    /// A [`fuzzy`](FindOptions::fuzzy) search is the exception: its
+    /// matches are ranked over the whole document when the first page is
+    /// fetched, and later pages continue that ranking without seeing
+    /// edits made meanwhile.
+    pub fn find_iter(&self, query: &str, options: &FindOptions, page_size: usize) -> SearchHits {
+        SearchHits::new(self.clone(), query, options, page_size)
+    }
+
     /// Replace occurrences. Returns the number of replacements. Undoable.
     pub fn replace_text(
         &self,
@@ -704,6 +1052,12 @@ impl TextDocument {
             let result =
                 document_search_commands::replace_text(&inner.ctx, Some(inner.stack_id), &dto)?;
             let count = to_usize(result.replacements_count);
+            inner.log_edit(None, || EditAction::ReplaceText {
+                query: query.into(),
+                replacement: replacement.into(),
+                replace_all,
+                options: options.clone(),
+            });
             inner.invalidate_text_cache();
             if count > 0 {
                 inner.modified = true;
@@ -730,6 +1084,95 @@ impl TextDocument {
         Ok(count)
     }
 
+    /// Find the text whose format matches `query`. Character conditions
+    /// yield the matching stretches of each block; block conditions
+    /// alone yield the whole text of each matching block.
+    pub fn find_format(&self, query: &FormatQuery) -> Result<Vec<FindMatch>> {
+        let inner = self.inner.lock();
+        let dto = query.to_find_by_format_dto();
+        let result = document_search_commands::find_by_format(&inner.ctx, &dto)?;
+        Ok(convert::find_all_to_matches(&result))
+    }
+
+    /// Merge `text_format` into every range matching `query` and apply
+    /// `block_format` to the blocks containing them. Only the fields set
+    /// in either format change, so turning italics into underline is
+    /// `font_italic: Some(false), font_underline: Some(true)`. Undoable
+    /// as a single step. Returns the number of matched ranges. On failure
+    /// the matches formatted so far are reverted.
+    pub fn replace_format(
+        &self,
+        query: &FormatQuery,
+        text_format: &TextFormat,
+        block_format: &BlockFormat,
+    ) -> Result<usize> {
+        let merge_text = *text_format != TextFormat::default();
+        let set_block = *block_format != BlockFormat::default();
+        let (count, queued) = {
+            let mut inner = self.inner.lock();
+            let dto = query.to_find_by_format_dto();
+            let result = document_search_commands::find_by_format(&inner.ctx, &dto)?;
+            let matches = convert::find_all_to_matches(&result);
+            if matches.is_empty() || !(merge_text || set_block) {
+                return Ok(0);
+            }
+
+            undo_redo_commands::try_begin_composite(&inner.ctx, Some(inner.stack_id))?;
+            let description = match matches.len() {
+                1 => "Replace formatting of 1 occurrence".to_string(),
+                count => format!("Replace formatting of {count} occurrences"),
+            };
+            undo_redo_commands::set_composite_description(&inner.ctx, &description);
+            let applied = matches.iter().try_for_each(|m| -> Result<()> {
+                let (start, end) = (m.position, m.position + m.length);
+                if merge_text {
+                    document_formatting_commands::merge_text_format(
+                        &inner.ctx,
+                        Some(inner.stack_id),
+                        &text_format.to_merge_dto(start, end),
+                    )?;
+                }
+                if set_block {
+                    document_formatting_commands::set_block_format(
+                        &inner.ctx,
+                        Some(inner.stack_id),
+                        &block_format.to_set_dto(start, end),
+                    )?;
+                }
+                Ok(())
+            });
+            if let Err(e) = applied {
+                undo_redo_commands::cancel_composite(&inner.ctx);
+                return Err(e);
+            }
+            undo_redo_commands::end_composite(&inner.ctx);
+            inner.log_edit(None, || EditAction::ReplaceFormat {
+                query: Box::new(query.clone()),
+                text_format: Box::new(text_format.clone()),
+                block_format: Box::new(block_format.clone()),
+            });
+
+            inner.modified = true;
+            let first = matches[0].position;
+            let last = &matches[matches.len() - 1];
+            inner.queue_event(DocumentEvent::FormatChanged {
+                position: first,
+                length: last.position + last.length - first,
+                kind: if set_block {
+                    FormatChangeKind::Block
+                } else {
+                    FormatChangeKind::Character
+                },
+            });
+            let can_undo = undo_redo_commands::can_undo(&inner.ctx, Some(inner.stack_id));
+            let can_redo = undo_redo_commands::can_redo(&inner.ctx, Some(inner.stack_id));
+            inner.queue_event(DocumentEvent::UndoRedoChanged { can_undo, can_redo });
+            (matches.len(), inner.take_queued_events())
+        };
+        crate::inner::dispatch_queued_events(queued);
+        Ok(count)
+    }
+
     // ── Resources ────────────────────────────────────────────
 
     /// Add a resource (image, stylesheet) to the document.
@@ -758,6 +1201,12 @@ impl TextDocument {
             -1,
         )?;
         inner.resource_cache.insert(name.to_string(), created.id);
+        inner.log_edit(None, || EditAction::AddResource {
+            resource_type: dto.resource_type.clone(),
+            name: dto.name.clone(),
+            mime_type: dto.mime_type.clone(),
+            data_base64: dto.data_base64.clone(),
+        });
         Ok(())
     }
 
@@ -793,44 +1242,125 @@ impl TextDocument {
 
     /// Undo the last operation.
     pub fn undo(&self) -> Result<()> {
-        let queued = {
-            let mut inner = self.inner.lock();
-            let before = capture_block_state(&inner);
-            let result = undo_redo_commands::undo(&inner.ctx, Some(inner.stack_id));
-            inner.invalidate_text_cache();
-            result?;
-            inner.rehighlight_all();
-            emit_undo_redo_change_events(&mut inner, &before);
-            inner.check_block_count_changed();
-            inner.check_flow_changed();
-            let can_undo = undo_redo_commands::can_undo(&inner.ctx, Some(inner.stack_id));
-            let can_redo = undo_redo_commands::can_redo(&inner.ctx, Some(inner.stack_id));
-            inner.queue_event(DocumentEvent::UndoRedoChanged { can_undo, can_redo });
-            inner.take_queued_events()
-        };
-        crate::inner::dispatch_queued_events(queued);
-        Ok(())
+        self.step_history(None, HistoryDirection::Undo, |_| 1, |_| EditAction::Undo)
     }
 
     /// Redo the last undone operation.
     pub fn redo(&self) -> Result<()> {
-        let queued = {
+        self.step_history(None, HistoryDirection::Redo, |_| 1, |_| EditAction::Redo)
+    }
+
+    /// The undo history: every undoable entry (oldest first) followed by
+    /// every redoable entry, and the index separating them.
+    pub fn undo_history(&self) -> UndoHistory {
+        self.undo_history_in(self.default_undo_stack())
+    }
+
+    /// Undo until [`UndoHistory::current_index`] equals `index`.
+    ///
+    /// All steps run under one lock and emit a single batch of events.
+    /// Does nothing if `index` is at or after the current index.
+    pub fn undo_to(&self, index: usize) -> Result<()> {
+        self.step_history(
+            None,
+            HistoryDirection::Undo,
+            |current| current.saturating_sub(index),
+            |reached| EditAction::UndoTo { index: reached },
+        )
+    }
+
+    /// Redo until [`UndoHistory::current_index`] equals `index`, clamped
+    /// to the end of the history.
+    ///
+    /// All steps run under one lock and emit a single batch of events.
+    /// Does nothing if `index` is at or before the current index.
+    pub fn redo_to(&self, index: usize) -> Result<()> {
+        self.step_history(
+            None,
+            HistoryDirection::Redo,
+            |current| index.saturating_sub(current),
+            |reached| EditAction::RedoTo { index: reached },
+        )
+    }
+
+    /// Undo or redo the number of times `steps` returns for the current
+    /// history index of `stack` (the default stack when `None`), then
+    /// emit one batch of change events covering all of them. `logged`
+    /// gives the call to record in the edit log for the history index
+    /// the steps reached.
+    ///
+    /// If a step fails, the steps before it stay done: their events are
+    /// still dispatched and they are logged before the error is returned.
+    fn step_history(
+        &self,
+        stack: Option<UndoStackId>,
+        direction: HistoryDirection,
+        steps: impl FnOnce(usize) -> usize,
+        logged: impl FnOnce(usize) -> EditAction,
+    ) -> Result<()> {
+        let (queued, result) = {
             let mut inner = self.inner.lock();
-            let before = capture_block_state(&inner);
-            let result = undo_redo_commands::redo(&inner.ctx, Some(inner.stack_id));
+            let stack = match stack {
+                Some(stack) => known_stack(&inner, stack)?,
+                None => inner.stack_id,
+            };
+            let stack_id = Some(stack);
+            let current = undo_redo_commands::undo_count(&inner.ctx, stack_id);
+            let steps = steps(current);
+            if steps == 0 {
+                return Ok(());
+            }
+            let scope = match direction {
+                HistoryDirection::Undo => {
+                    undo_redo_commands::undo_affected_blocks(&inner.ctx, stack_id, steps)
+                }
+                HistoryDirection::Redo => {
+                    undo_redo_commands::redo_affected_blocks(&inner.ctx, stack_id, steps)
+                }
+            };
+            let before = capture_block_state(&inner, scope.as_deref());
+            let mut result = Ok(());
+            let mut done = 0;
+            for _ in 0..steps {
+                let available = match direction {
+                    HistoryDirection::Undo => undo_redo_commands::can_undo(&inner.ctx, stack_id),
+                    HistoryDirection::Redo => undo_redo_commands::can_redo(&inner.ctx, stack_id),
+                };
+                if !available {
+                    break;
+                }
+                result = match direction {
+                    HistoryDirection::Undo => undo_redo_commands::undo(&inner.ctx, stack_id),
+                    HistoryDirection::Redo => undo_redo_commands::redo(&inner.ctx, stack_id),
+                };
+                if result.is_err() {
+                    break;
+                }
+                done += 1;
+            }
             inner.invalidate_text_cache();
-            result?;
+            // Steps that succeeded before a failure have already changed
+            // the document, so report and log them even when returning
+            // the error.
             inner.rehighlight_all();
-            emit_undo_redo_change_events(&mut inner, &before);
+            emit_undo_redo_change_events(&mut inner, &before, scope.as_deref());
+            if done > 0 {
+                restore_edit_selection(&mut inner, direction, stack_id);
+            }
             inner.check_block_count_changed();
             inner.check_flow_changed();
-            let can_undo = undo_redo_commands::can_undo(&inner.ctx, Some(inner.stack_id));
-            let can_redo = undo_redo_commands::can_redo(&inner.ctx, Some(inner.stack_id));
-            inner.queue_event(DocumentEvent::UndoRedoChanged { can_undo, can_redo });
-            inner.take_queued_events()
+            inner.queue_undo_state(stack);
+            if done > 0 {
+                let reached = match direction {
+                    HistoryDirection::Undo => current - done,
+                    HistoryDirection::Redo => current + done,
+                };
+                inner.log_edit(None, || logged(reached));
+            }
+            (inner.take_queued_events(), result)
         };
         crate::inner::dispatch_queued_events(queued);
-        Ok(())
+        result
     }
 
     /// Returns true if there are operations that can be undone.
@@ -845,10 +1375,349 @@ impl TextDocument {
         undo_redo_commands::can_redo(&inner.ctx, Some(inner.stack_id))
     }
 
-    /// Clear all undo/redo history.
+    /// Cap how much undo history the document keeps, by entry count
+    /// and by approximate memory. The oldest entries are dropped first,
+    /// now if the history is already over the limit and after each edit
+    /// from then on. Emits `UndoRedoChanged` when entries are dropped.
+    ///
+    /// Applies to the default stack; see
+    /// [`set_undo_limit_in`](Self::set_undo_limit_in) for the others.
+    pub fn set_undo_limit(&self, limit: UndoLimit) -> Result<()> {
+        let stack = self.default_undo_stack();
+        self.apply_undo_limit(stack, limit, || EditAction::SetUndoLimit { limit })
+    }
+
+    /// The limit set by [`set_undo_limit`](Self::set_undo_limit).
+    /// Unbounded by default.
+    pub fn undo_limit(&self) -> UndoLimit {
+        self.undo_limit_in(self.default_undo_stack())
+    }
+
+    /// Approximate bytes held by the undo and redo history.
+    pub fn undo_memory_usage(&self) -> usize {
+        self.undo_memory_usage_in(self.default_undo_stack())
+    }
+
+    /// Set the limit of `stack` and drop what is over it, emitting the
+    /// stack's undo state event when entries are dropped.
+    fn apply_undo_limit(
+        &self,
+        stack: UndoStackId,
+        limit: UndoLimit,
+        logged: impl FnOnce() -> EditAction,
+    ) -> Result<()> {
+        let queued = {
+            let mut inner = self.inner.lock();
+            let stack = known_stack(&inner, stack)?;
+            let dropped = undo_redo_commands::set_stack_limit(&inner.ctx, stack, limit)?;
+            inner.log_edit(None, logged);
+            if dropped == 0 {
+                return Ok(());
+            }
+            inner.queue_undo_state(stack);
+            inner.take_queued_events()
+        };
+        crate::inner::dispatch_queued_events(queued);
+        Ok(())
+    }
+
+    /// Clear all undo/redo history, in every undo stack.
     pub fn clear_undo_redo(&self) {
+        let mut inner = self.inner.lock();
+        inner.clear_undo_stacks();
+        inner.log_edit(None, || EditAction::ClearUndoRedo);
+    }
+
+    // ── Undo stacks ──────────────────────────────────────────
+
+    /// The stack that [`undo`](Self::undo), [`redo`](Self::redo) and the
+    /// rest of the history methods above work on. Edits outside any frame
+    /// bound with [`set_frame_undo_stack`](Self::set_frame_undo_stack) go
+    /// here.
+    pub fn default_undo_stack(&self) -> UndoStackId {
+        UndoStackId(self.inner.lock().stack_id)
+    }
+
+    /// Create an empty undo stack. Route edits to it by binding a frame
+    /// ([`set_frame_undo_stack`](Self::set_frame_undo_stack)) or a cursor
+    /// ([`TextCursor::set_undo_stack`]), then step through it with
+    /// [`undo_in`](Self::undo_in) and [`redo_in`](Self::redo_in).
+    ///
+    /// Stacks share one document: undoing an entry is refused while a
+    /// later entry in another stack touched the same blocks, and redoing
+    /// one is refused once another stack has changed the document.
+    pub fn create_undo_stack(&self) -> UndoStackId {
+        let mut inner = self.inner.lock();
+        let stack = undo_redo_commands::create_guarded_stack(&inner.ctx);
+        inner.undo_stacks.push(stack);
+        inner.log_edit(None, || EditAction::CreateUndoStack { stack });
+        UndoStackId(stack)
+    }
+
+    /// Delete a stack made with [`create_undo_stack`](Self::create_undo_stack)
+    /// and its history. Frames and cursors bound to it go back to the
+    /// default routing. The default stack cannot be deleted.
+    pub fn delete_undo_stack(&self, stack: UndoStackId) -> Result<()> {
+        let mut inner = self.inner.lock();
+        if stack.0 == inner.stack_id {
+            return Err(anyhow::anyhow!("the default undo stack cannot be deleted"));
+        }
+        known_stack(&inner, stack)?;
+        undo_redo_commands::delete_stack(&inner.ctx, stack.0)?;
+        inner.undo_stacks.retain(|s| *s != stack.0);
+        inner.frame_undo_stacks.retain(|_, s| *s != stack.0);
+        inner.prune_dead_cursors();
+        for weak in &inner.cursors {
+            if let Some(cursor) = weak.upgrade() {
+                let mut data = cursor.lock();
+                if data.undo_stack == Some(stack.0) {
+                    data.undo_stack = None;
+                }
+            }
+        }
+        inner.log_edit(None, || EditAction::DeleteUndoStack { stack: stack.0 });
+        Ok(())
+    }
+
+    /// Send edits inside frame `frame_id` (and frames nested in it that
+    /// have no binding of their own) to `stack`. `None` removes the
+    /// binding.
+    pub fn set_frame_undo_stack(&self, frame_id: usize, stack: Option<UndoStackId>) -> Result<()> {
+        let mut inner = self.inner.lock();
+        let frame_id = frame_id as frontend::common::types::EntityId;
+        if frame_commands::get_frame(&inner.ctx, &frame_id)?.is_none() {
+            return Err(anyhow::anyhow!("frame {frame_id} not found"));
+        }
+        match stack {
+            Some(stack) => {
+                let stack = known_stack(&inner, stack)?;
+                inner.frame_undo_stacks.insert(frame_id, stack);
+            }
+            None => {
+                inner.frame_undo_stacks.remove(&frame_id);
+            }
+        }
+        inner.log_edit(None, || EditAction::SetFrameUndoStack {
+            frame_id: frame_id as usize,
+            stack: stack.map(|s| s.0),
+        });
+        Ok(())
+    }
+
+    /// The stack bound to frame `frame_id` with
+    /// [`set_frame_undo_stack`](Self::set_frame_undo_stack), if any.
+    pub fn frame_undo_stack(&self, frame_id: usize) -> Option<UndoStackId> {
+        let inner = self.inner.lock();
+        inner
+            .frame_undo_stacks
+            .get(&(frame_id as frontend::common::types::EntityId))
+            .map(|s| UndoStackId(*s))
+    }
+
+    /// Undo the last operation in `stack`.
+    pub fn undo_in(&self, stack: UndoStackId) -> Result<()> {
+        self.step_history(
+            Some(stack),
+            HistoryDirection::Undo,
+            |_| 1,
+            |_| EditAction::UndoIn { stack: stack.0 },
+        )
+    }
+
+    /// Redo the last undone operation in `stack`.
+    pub fn redo_in(&self, stack: UndoStackId) -> Result<()> {
+        self.step_history(
+            Some(stack),
+            HistoryDirection::Redo,
+            |_| 1,
+            |_| EditAction::RedoIn { stack: stack.0 },
+        )
+    }
+
+    /// Returns true if `stack` has operations that can be undone.
+    pub fn can_undo_in(&self, stack: UndoStackId) -> bool {
+        let inner = self.inner.lock();
+        undo_redo_commands::can_undo(&inner.ctx, Some(stack.0))
+    }
+
+    /// Returns true if `stack` has operations that can be redone.
+    pub fn can_redo_in(&self, stack: UndoStackId) -> bool {
+        let inner = self.inner.lock();
+        undo_redo_commands::can_redo(&inner.ctx, Some(stack.0))
+    }
+
+    /// The undo history of `stack`, like [`undo_history`](Self::undo_history)
+    /// for the default stack. Empty for an unknown stack.
+    pub fn undo_history_in(&self, stack: UndoStackId) -> UndoHistory {
+        let inner = self.inner.lock();
+        let undo = undo_redo_commands::undo_descriptions(&inner.ctx, Some(stack.0));
+        let redo = undo_redo_commands::redo_descriptions(&inner.ctx, Some(stack.0));
+        let current_index = undo.len();
+        UndoHistory {
+            entries: undo
+                .into_iter()
+                .chain(redo)
+                .map(|description| UndoHistoryEntry { description })
+                .collect(),
+            current_index,
+        }
+    }
+
+    /// Cap the history kept in `stack`, like
+    /// [`set_undo_limit`](Self::set_undo_limit) does for the default
+    /// stack. Emits `UndoStackChanged` when entries are dropped.
+    pub fn set_undo_limit_in(&self, stack: UndoStackId, limit: UndoLimit) -> Result<()> {
+        self.apply_undo_limit(stack, limit, || EditAction::SetUndoLimitIn {
+            stack: stack.0,
+            limit,
+        })
+    }
+
+    /// The limit set on `stack` with
+    /// [`set_undo_limit_in`](Self::set_undo_limit_in). Unbounded by
+    /// default.
+    pub fn undo_limit_in(&self, stack: UndoStackId) -> UndoLimit {
+        let inner = self.inner.lock();
+        undo_redo_commands::get_stack_limit(&inner.ctx, stack.0)
+    }
+
+    /// Approximate bytes held by the undo and redo history of `stack`.
+    pub fn undo_memory_usage_in(&self, stack: UndoStackId) -> usize {
+        let inner = self.inner.lock();
+        undo_redo_commands::get_stack_memory_usage(&inner.ctx, stack.0)
+    }
+
+    // ── Edit log ─────────────────────────────────────────────
+
+    /// Start recording every mutation made through this document or its
+    /// cursors into an [`EditLog`], discarding any recording in progress.
+    /// The log starts from a snapshot of the document and its default
+    /// undo stack's history, so [`replay`](Self::replay) can rebuild it.
+    pub fn start_recording(&self) -> Result<()> {
+        let mut inner = self.inner.lock();
+        inner.edit_log = Some(edit_log::start(&inner)?);
+        Ok(())
+    }
+
+    /// Stop recording and return the log. `None` if not recording.
+    pub fn stop_recording(&self) -> Option<EditLog> {
+        self.inner.lock().edit_log.take()
+    }
+
+    /// Returns true between [`start_recording`](Self::start_recording)
+    /// and [`stop_recording`](Self::stop_recording).
+    pub fn is_recording(&self) -> bool {
+        self.inner.lock().edit_log.is_some()
+    }
+
+    /// A copy of the log recorded so far, without stopping. `None` if not
+    /// recording.
+    pub fn edit_log(&self) -> Option<EditLog> {
+        self.inner.lock().edit_log.clone()
+    }
+
+    /// Build a new document by loading the snapshot `log` starts from and
+    /// replaying its edits in order. Fails on the first edit that fails,
+    /// naming it.
+    pub fn replay(log: &EditLog) -> Result<TextDocument> {
+        edit_log::replay(log)
+    }
+
+    // ── Crash-recovery journal ───────────────────────────────
+
+    /// Start journaling every mutation to disk, so that
+    /// [`recover`](Self::recover) can rebuild the document after a crash.
+    /// Writes a checkpoint of the document to `options.dir` first.
+    ///
+    /// Fails if `options.dir` already holds a journal, unless this
+    /// document is journaling there or was recovered from it: another
+    /// session's edits would be lost. [`recover`](Self::recover) or
+    /// [`discard_journal`](Self::discard_journal) it first. Also fails
+    /// while a long operation is running.
+    pub fn start_journal(&self, options: JournalOptions) -> Result<()> {
+        let mut inner = self.inner.lock();
+        if inner.long_operation_running() {
+            anyhow::bail!("cannot start a journal while a long operation is running");
+        }
+        let supersede = inner
+            .journal
+            .as_ref()
+            .is_some_and(|j| j.dir() == options.dir)
+            || inner.recovered_journal.as_deref() == Some(options.dir.as_path());
+        let start = edit_log::start(&inner)?;
+        inner.journal = Some(Journal::create(options, start, supersede)?);
+        inner.recovered_journal = None;
+        Ok(())
+    }
+
+    /// Stop journaling and delete the journal files, leaving nothing to
+    /// recover. Call this after saving the document or on a clean
+    /// shutdown. No-op if not journaling.
+    pub fn stop_journal(&self) -> Result<()> {
+        let journal = self.inner.lock().journal.take();
+        match journal {
+            Some(journal) => journal::remove(journal.dir()),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns true between [`start_journal`](Self::start_journal) and
+    /// [`stop_journal`](Self::stop_journal).
+    pub fn is_journaling(&self) -> bool {
+        self.inner.lock().journal.is_some()
+    }
+
+    /// Write a checkpoint now rather than after the configured number of
+    /// edits, e.g. after saving. Fails if not journaling or while a long
+    /// operation is running.
+    pub fn checkpoint_journal(&self) -> Result<()> {
+        let mut inner = self.inner.lock();
+        if inner.long_operation_running() {
+            anyhow::bail!("cannot checkpoint while a long operation is running");
+        }
+        let start = edit_log::start(&inner)?;
+        inner
+            .journal
+            .as_mut()
+            .ok_or_else(|| anyhow::anyhow!("not journaling"))?
+            .checkpoint(start)
+    }
+
+    /// Flush journaled edits to disk, for [`SyncPolicy::Never`](crate::SyncPolicy::Never) and
+    /// [`SyncPolicy::EveryEdits`](crate::SyncPolicy::EveryEdits). No-op if not journaling.
+    pub fn sync_journal(&self) -> Result<()> {
+        match self.inner.lock().journal.as_mut() {
+            Some(journal) => journal.sync(),
+            None => Ok(()),
+        }
+    }
+
+    /// The last failure to write the journal, such as a checkpoint whose
+    /// undo history could not be saved. The next edit tries again by
+    /// writing a checkpoint.
+    pub fn journal_error(&self) -> Option<String> {
         let inner = self.inner.lock();
-        undo_redo_commands::clear_stack(&inner.ctx, inner.stack_id);
+        inner.journal.as_ref()?.error().map(str::to_string)
+    }
+
+    /// Rebuild a document from the journal in `dir`: its checkpoint, then
+    /// the edits made since. A last edit cut short by the crash is
+    /// dropped. Returns `None` if `dir` holds no journal. The recovered
+    /// document is not journaling; call
+    /// [`start_journal`](Self::start_journal) to continue in `dir`.
+    pub fn recover(dir: impl AsRef<Path>) -> Result<Option<TextDocument>> {
+        let dir = dir.as_ref();
+        let recovered = journal::recover(dir)?;
+        if let Some(doc) = &recovered {
+            doc.inner.lock().recovered_journal = Some(dir.to_path_buf());
+        }
+        Ok(recovered)
+    }
+
+    /// Delete the journal in `dir` without recovering it, so a new one
+    /// can be started there. No-op if `dir` holds no journal.
+    pub fn discard_journal(dir: impl AsRef<Path>) -> Result<()> {
+        journal::remove(dir.as_ref())
     }
 
     // ── Modified state ───────────────────────────────────────
@@ -866,6 +1735,7 @@ impl TextDocument {
                 inner.modified = modified;
                 inner.queue_event(DocumentEvent::ModificationChanged(modified));
             }
+            inner.log_edit(None, || EditAction::SetModified { modified });
             inner.take_queued_events()
         };
         crate::inner::dispatch_queued_events(queued);
@@ -885,12 +1755,15 @@ impl TextDocument {
 
     /// Set the document title.
     pub fn set_title(&self, title: &str) -> Result<()> {
-        let inner = self.inner.lock();
+        let mut inner = self.inner.lock();
         let doc = document_commands::get_document(&inner.ctx, &inner.document_id)?
             .ok_or_else(|| anyhow::anyhow!("document not found"))?;
         let mut update: frontend::document::dtos::UpdateDocumentDto = doc.into();
         update.title = title.into();
         document_commands::update_document(&inner.ctx, Some(inner.stack_id), &update)?;
+        inner.log_edit(None, || EditAction::SetTitle {
+            title: update.title.clone(),
+        });
         Ok(())
     }
 
@@ -906,12 +1779,15 @@ impl TextDocument {
 
     /// Set the text direction.
     pub fn set_text_direction(&self, direction: TextDirection) -> Result<()> {
-        let inner = self.inner.lock();
+        let mut inner = self.inner.lock();
         let doc = document_commands::get_document(&inner.ctx, &inner.document_id)?
             .ok_or_else(|| anyhow::anyhow!("document not found"))?;
         let mut update: frontend::document::dtos::UpdateDocumentDto = doc.into();
         update.text_direction = direction;
         document_commands::update_document(&inner.ctx, Some(inner.stack_id), &update)?;
+        inner.log_edit(None, || EditAction::SetTextDirection {
+            direction: update.text_direction.clone(),
+        });
         Ok(())
     }
 
@@ -927,12 +1803,15 @@ impl TextDocument {
 
     /// Set the default wrap mode.
     pub fn set_default_wrap_mode(&self, mode: WrapMode) -> Result<()> {
-        let inner = self.inner.lock();
+        let mut inner = self.inner.lock();
         let doc = document_commands::get_document(&inner.ctx, &inner.document_id)?
             .ok_or_else(|| anyhow::anyhow!("document not found"))?;
         let mut update: frontend::document::dtos::UpdateDocumentDto = doc.into();
         update.default_wrap_mode = mode;
         document_commands::update_document(&inner.ctx, Some(inner.stack_id), &update)?;
+        inner.log_edit(None, || EditAction::SetDefaultWrapMode {
+            mode: update.default_wrap_mode.clone(),
+        });
         Ok(())
     }
 
@@ -1020,8 +1899,52 @@ impl Default for TextDocument {
 
 // ── Undo/redo change detection helpers ─────────────────────────
 
+/// Which stack [`TextDocument::step_history`] pops from.
+#[derive(Clone, Copy)]
+enum HistoryDirection {
+    Undo,
+    Redo,
+}
+
+/// Check that `stack` is the default stack or one from
+/// `create_undo_stack` that has not been deleted.
+fn known_stack(inner: &TextDocumentInner, stack: UndoStackId) -> Result<u64> {
+    if stack.0 == inner.stack_id || inner.undo_stacks.contains(&stack.0) {
+        Ok(stack.0)
+    } else {
+        Err(anyhow::anyhow!("unknown undo stack {}", stack.0))
+    }
+}
+
+/// Put the cursor that made the last entry undone (or redone) back to
+/// its selection from before (or after) that edit.
+fn restore_edit_selection(
+    inner: &mut TextDocumentInner,
+    direction: HistoryDirection,
+    stack_id: Option<u64>,
+) {
+    let (cursor_id, state) = match direction {
+        HistoryDirection::Undo => {
+            match undo_redo_commands::next_redo_selection(&inner.ctx, stack_id) {
+                Some(s) => (s.cursor_id, s.before),
+                None => return,
+            }
+        }
+        HistoryDirection::Redo => {
+            match undo_redo_commands::next_undo_selection(&inner.ctx, stack_id) {
+                Some(s) => (s.cursor_id, s.after),
+                None => return,
+            }
+        }
+    };
+    let max_position = document_inspection_commands::get_document_stats(&inner.ctx)
+        .map(|s| max_cursor_position(&s))
+        .unwrap_or(0);
+    inner.restore_cursor_selection(cursor_id, &state, max_position);
+}
+
 /// Lightweight block state for before/after comparison during undo/redo.
-struct UndoBlockState {
+pub(crate) struct UndoBlockState {
     id: u64,
     position: i64,
     text_length: i64,
@@ -1029,10 +1952,20 @@ struct UndoBlockState {
     format: BlockFormat,
 }
 
-/// Capture the state of all blocks, sorted by document_position.
-fn capture_block_state(inner: &TextDocumentInner) -> Vec<UndoBlockState> {
-    let mut all_blocks =
-        frontend::commands::block_commands::get_all_block(&inner.ctx).unwrap_or_default();
+/// Capture the state of the blocks in `scope` (all blocks when `None`),
+/// sorted by document_position.
+pub(crate) fn capture_block_state(
+    inner: &TextDocumentInner,
+    scope: Option<&[u64]>,
+) -> Vec<UndoBlockState> {
+    let mut all_blocks = match scope {
+        Some(ids) => frontend::commands::block_commands::get_block_multi(&inner.ctx, ids)
+            .unwrap_or_default()
+            .into_iter()
+            .flatten()
+            .collect(),
+        None => frontend::commands::block_commands::get_all_block(&inner.ctx).unwrap_or_default(),
+    };
     let store = inner.ctx.db_context.get_store();
     crate::inner::refresh_block_positions(&mut all_blocks, store);
     let mut states: Vec<UndoBlockState> = all_blocks
@@ -1097,8 +2030,12 @@ fn compute_text_edit(before: &str, after: &str) -> (usize, usize, usize) {
 
 /// Compare block state before and after undo/redo and emit
 /// ContentsChanged / FormatChanged events for affected regions.
-fn emit_undo_redo_change_events(inner: &mut TextDocumentInner, before: &[UndoBlockState]) {
-    let after = capture_block_state(inner);
+pub(crate) fn emit_undo_redo_change_events(
+    inner: &mut TextDocumentInner,
+    before: &[UndoBlockState],
+    scope: Option<&[u64]>,
+) {
+    let after = capture_block_state(inner, scope);
 
     // Build a map of block id → state for the "before" set.
     let before_map: std::collections::HashMap<u64, &UndoBlockState> =
@@ -1170,7 +2107,10 @@ fn emit_undo_redo_change_events(inner: &mut TextDocumentInner, before: &[UndoBlo
         let (edit_offset, precise_removed, precise_added) =
             compute_text_edit(&before_text, &after_text);
         if precise_removed > 0 || precise_added > 0 {
-            inner.adjust_cursors(edit_offset, precise_removed, precise_added);
+            // The texts start at the first captured block, which is the
+            // document start unless the capture was scoped.
+            let base = before.first().map_or(0, |s| s.position.max(0) as usize);
+            inner.adjust_cursors(base + edit_offset, precise_removed, precise_added);
         }
 
         inner.queue_event(DocumentEvent::ContentsChanged {
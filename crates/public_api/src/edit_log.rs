@@ -0,0 +1,616 @@
+//! Recording editing sessions and replaying them.
+//!
+//! While a [`TextDocument`] is recording (see
+//! [`TextDocument::start_recording`]), every public mutation made through
+//! it or one of its cursors is appended to an [`EditLog`] with its
+//! arguments and, for cursor edits, the selection and undo stack it
+//! applied to. [`TextDocument::replay`] loads the snapshot the log starts
+//! from into a fresh document and makes the same calls in order, which
+//! reproduces the recorded document: text, formats, entity IDs and undo
+//! history. Only creation and update timestamps differ.
+//!
+//! Convenience methods that resolve a target and forward to another
+//! public method (`insert_row_above`, `set_current_list_format`,
+//! `remove_list_item`, ...) are logged as the call they forward to.
+
+use std::collections::HashMap;
+
+use anyhow::{Result, anyhow, bail};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use serde::{Deserialize, Serialize};
+
+use frontend::commands::{document_io_commands, undo_redo_commands};
+
+use crate::flow::{CellFormat, TableFormat};
+use crate::fragment::DocumentFragment;
+use crate::inner::TextDocumentInner;
+use crate::{
+    BlockFormat, FindOptions, FormatQuery, FrameFormat, ListFormat, ListStyle, ResourceType,
+    TextCursor, TextDirection, TextDocument, TextFormat, UndoHistory, UndoLimit, UndoStackId,
+    WrapMode,
+};
+
+/// Version written into every [`EditLog`]. Logs from a newer version are
+/// rejected by [`EditLog::from_json`].
+pub const EDIT_LOG_VERSION: u32 = 1;
+
+/// A recorded editing session, from [`TextDocument::stop_recording`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EditLog {
+    pub version: u32,
+    /// The document when recording started, in the native format and
+    /// with its default undo stack's history.
+    pub snapshot: String,
+    /// The modified flag when recording started.
+    pub modified: bool,
+    /// ID of the recorded document's default undo stack, which the
+    /// replayed document's default stack stands in for.
+    pub default_undo_stack: u64,
+    pub edits: Vec<LoggedEdit>,
+}
+
+impl EditLog {
+    /// Serialize the log to JSON.
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    /// Parse a log written by [`to_json`](Self::to_json). Fails on logs
+    /// from a newer version.
+    pub fn from_json(json: &str) -> Result<Self> {
+        let log: EditLog = serde_json::from_str(json)?;
+        if log.version > EDIT_LOG_VERSION {
+            bail!(
+                "edit log version {} is newer than the supported version {EDIT_LOG_VERSION}",
+                log.version
+            );
+        }
+        Ok(log)
+    }
+}
+
+/// One mutation in an [`EditLog`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LoggedEdit {
+    /// The cursor a [`TextCursor`] edit went through; `None` for
+    /// [`TextDocument`] methods.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<LoggedCursor>,
+    pub action: EditAction,
+}
+
+/// State of the cursor an edit was made through, just before the edit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LoggedCursor {
+    pub position: usize,
+    pub anchor: usize,
+    /// The undo stack the edit went to.
+    pub undo_stack: u64,
+}
+
+/// A public mutation and its arguments. Variants mirror the
+/// [`TextCursor`] and [`TextDocument`] methods of the same name; binary
+/// payloads are base64-encoded.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum EditAction {
+    // ── TextCursor ──
+    InsertText {
+        text: String,
+    },
+    InsertFormattedText {
+        text: String,
+        format: TextFormat,
+    },
+    InsertBlock,
+    InsertHtml {
+        html: String,
+    },
+    InsertMarkdown {
+        markdown: String,
+    },
+    InsertRtf {
+        rtf: String,
+    },
+    InsertFragment {
+        data: String,
+        plain_text: String,
+    },
+    InsertImage {
+        name: String,
+        width: u32,
+        height: u32,
+    },
+    InsertFrame,
+    InsertTable {
+        rows: usize,
+        columns: usize,
+    },
+    RemoveTable {
+        table_id: usize,
+    },
+    InsertTableRow {
+        table_id: usize,
+        row_index: usize,
+    },
+    InsertTableColumn {
+        table_id: usize,
+        column_index: usize,
+    },
+    RemoveTableRow {
+        table_id: usize,
+        row_index: usize,
+    },
+    RemoveTableColumn {
+        table_id: usize,
+        column_index: usize,
+    },
+    MergeTableCells {
+        table_id: usize,
+        start_row: usize,
+        start_column: usize,
+        end_row: usize,
+        end_column: usize,
+    },
+    SplitTableCell {
+        cell_id: usize,
+        split_rows: usize,
+        split_columns: usize,
+    },
+    SetTableFormat {
+        table_id: usize,
+        format: TableFormat,
+    },
+    SetTableCellFormat {
+        cell_id: usize,
+        format: CellFormat,
+    },
+    DeleteChar,
+    DeletePreviousChar,
+    RemoveSelectedText,
+    CreateList {
+        style: ListStyle,
+    },
+    InsertList {
+        style: ListStyle,
+    },
+    SetListFormat {
+        list_id: usize,
+        format: ListFormat,
+    },
+    AddBlockToList {
+        block_id: usize,
+        list_id: usize,
+    },
+    RemoveBlockFromList {
+        block_id: usize,
+    },
+    SetCharFormat {
+        format: TextFormat,
+    },
+    MergeCharFormat {
+        format: TextFormat,
+    },
+    SetBlockFormat {
+        format: BlockFormat,
+    },
+    SetFrameFormat {
+        frame_id: usize,
+        format: FrameFormat,
+    },
+    ReplaceInSelection {
+        query: String,
+        replacement: String,
+        options: FindOptions,
+    },
+    BeginEditBlock,
+    EndEditBlock,
+    CancelEditBlock,
+
+    // ── TextDocument ──
+    SetPlainText {
+        text: String,
+    },
+    SetMarkdown {
+        markdown: String,
+    },
+    SetHtml {
+        html: String,
+    },
+    SetDocx {
+        data_base64: String,
+    },
+    SetOdt {
+        data_base64: String,
+    },
+    SetRtf {
+        rtf: String,
+    },
+    LoadNative {
+        data: String,
+    },
+    Clear,
+    ReplaceText {
+        query: String,
+        replacement: String,
+        replace_all: bool,
+        options: FindOptions,
+    },
+    ReplaceFormat {
+        query: Box<FormatQuery>,
+        text_format: Box<TextFormat>,
+        block_format: Box<BlockFormat>,
+    },
+    AddResource {
+        resource_type: ResourceType,
+        name: String,
+        mime_type: String,
+        data_base64: String,
+    },
+    Undo,
+    Redo,
+    UndoTo {
+        index: usize,
+    },
+    RedoTo {
+        index: usize,
+    },
+    UndoIn {
+        stack: u64,
+    },
+    RedoIn {
+        stack: u64,
+    },
+    SetUndoLimit {
+        limit: UndoLimit,
+    },
+    SetUndoLimitIn {
+        stack: u64,
+        limit: UndoLimit,
+    },
+    ClearUndoRedo,
+    CreateUndoStack {
+        stack: u64,
+    },
+    DeleteUndoStack {
+        stack: u64,
+    },
+    SetFrameUndoStack {
+        frame_id: usize,
+        stack: Option<u64>,
+    },
+    SetModified {
+        modified: bool,
+    },
+    SetTitle {
+        title: String,
+    },
+    SetTextDirection {
+        direction: TextDirection,
+    },
+    SetDefaultWrapMode {
+        mode: WrapMode,
+    },
+}
+
+/// An empty log starting from the document as it is now. Reads the
+/// undo history without stepping through it, so starting a log leaves
+/// the document and its stacks alone.
+pub(crate) fn start(inner: &TextDocumentInner) -> Result<EditLog> {
+    let snapshot =
+        document_io_commands::export_native_with_history(&inner.ctx, Some(inner.stack_id))?;
+    Ok(EditLog {
+        version: EDIT_LOG_VERSION,
+        snapshot: snapshot.native_data,
+        modified: inner.modified,
+        default_undo_stack: inner.stack_id,
+        edits: Vec::new(),
+    })
+}
+
+/// Build a fresh document from `log`. See [`TextDocument::replay`].
+pub(crate) fn replay(log: &EditLog) -> Result<TextDocument> {
+    if log.version > EDIT_LOG_VERSION {
+        bail!(
+            "edit log version {} is newer than the supported version {EDIT_LOG_VERSION}",
+            log.version
+        );
+    }
+    let doc = TextDocument::try_new()?;
+    doc.load_native(log.snapshot.as_bytes())?;
+    doc.set_modified(log.modified);
+    let mut replayer = Replayer {
+        doc: &doc,
+        stacks: HashMap::from([(log.default_undo_stack, doc.default_undo_stack())]),
+    };
+    for (index, edit) in log.edits.iter().enumerate() {
+        replayer
+            .apply(edit)
+            .map_err(|e| anyhow!("replaying edit {index} ({:?}): {e}", edit.action))?;
+    }
+    Ok(doc)
+}
+
+fn undo_target(history: &UndoHistory) -> Option<usize> {
+    history.current_index.checked_sub(1)
+}
+
+fn redo_target(history: &UndoHistory) -> Option<usize> {
+    (history.current_index < history.entries.len()).then_some(history.current_index + 1)
+}
+
+struct Replayer<'a> {
+    doc: &'a TextDocument,
+    /// Recorded undo stack ID → the replayed document's stack.
+    stacks: HashMap<u64, UndoStackId>,
+}
+
+impl Replayer<'_> {
+    /// The replayed stack standing in for recorded stack `id`. Stacks
+    /// created before recording started are created on first use.
+    fn stack(&mut self, id: u64) -> UndoStackId {
+        *self
+            .stacks
+            .entry(id)
+            .or_insert_with(|| self.doc.create_undo_stack())
+    }
+
+    /// A cursor with the recorded selection, sending edits to the
+    /// recorded undo stack.
+    fn cursor(&mut self, edit: &LoggedEdit) -> Result<TextCursor> {
+        let logged = edit
+            .cursor
+            .ok_or_else(|| anyhow!("cursor edit logged without a cursor"))?;
+        let stack = self.stack(logged.undo_stack);
+        let cursor = self.doc.cursor();
+        {
+            let mut d = cursor.data.lock();
+            d.position = logged.position;
+            d.anchor = logged.anchor;
+            d.undo_stack = Some(stack.0);
+        }
+        Ok(cursor)
+    }
+
+    /// Take a logged undo or redo on `stack` with `step`. The recording
+    /// only logs steps that were taken, so fail if the history can't
+    /// reach the index `target` gives for it: the replay has drifted.
+    fn history_step(
+        &self,
+        stack: UndoStackId,
+        target: impl FnOnce(&UndoHistory) -> Option<usize>,
+        step: impl FnOnce() -> Result<()>,
+    ) -> Result<()> {
+        let history = self.doc.undo_history_in(stack);
+        let Some(target) = target(&history) else {
+            bail!(
+                "the history has no such step (at {} of {} entries)",
+                history.current_index,
+                history.entries.len()
+            );
+        };
+        step()?;
+        let reached = self.doc.undo_history_in(stack).current_index;
+        if reached != target {
+            bail!("the history reached index {reached} instead of {target}");
+        }
+        Ok(())
+    }
+
+    fn apply(&mut self, edit: &LoggedEdit) -> Result<()> {
+        let doc = self.doc;
+        match &edit.action {
+            EditAction::InsertText { text } => self.cursor(edit)?.insert_text(text),
+            EditAction::InsertFormattedText { text, format } => {
+                self.cursor(edit)?.insert_formatted_text(text, format)
+            }
+            EditAction::InsertBlock => self.cursor(edit)?.insert_block(),
+            EditAction::InsertHtml { html } => self.cursor(edit)?.insert_html(html),
+            EditAction::InsertMarkdown { markdown } => self.cursor(edit)?.insert_markdown(markdown),
+            EditAction::InsertRtf { rtf } => self.cursor(edit)?.insert_rtf(rtf),
+            EditAction::InsertFragment { data, plain_text } => {
+                self.cursor(edit)?
+                    .insert_fragment(&DocumentFragment::from_raw(
+                        data.clone(),
+                        plain_text.clone(),
+                    ))
+            }
+            EditAction::InsertImage {
+                name,
+                width,
+                height,
+            } => self.cursor(edit)?.insert_image(name, *width, *height),
+            EditAction::InsertFrame => self.cursor(edit)?.insert_frame(),
+            EditAction::InsertTable { rows, columns } => {
+                self.cursor(edit)?.insert_table(*rows, *columns).map(|_| ())
+            }
+            EditAction::RemoveTable { table_id } => self.cursor(edit)?.remove_table(*table_id),
+            EditAction::InsertTableRow {
+                table_id,
+                row_index,
+            } => self.cursor(edit)?.insert_table_row(*table_id, *row_index),
+            EditAction::InsertTableColumn {
+                table_id,
+                column_index,
+            } => self
+                .cursor(edit)?
+                .insert_table_column(*table_id, *column_index),
+            EditAction::RemoveTableRow {
+                table_id,
+                row_index,
+            } => self.cursor(edit)?.remove_table_row(*table_id, *row_index),
+            EditAction::RemoveTableColumn {
+                table_id,
+                column_index,
+            } => self
+                .cursor(edit)?
+                .remove_table_column(*table_id, *column_index),
+            EditAction::MergeTableCells {
+                table_id,
+                start_row,
+                start_column,
+                end_row,
+                end_column,
+            } => self.cursor(edit)?.merge_table_cells(
+                *table_id,
+                *start_row,
+                *start_column,
+                *end_row,
+                *end_column,
+            ),
+            EditAction::SplitTableCell {
+                cell_id,
+                split_rows,
+                split_columns,
+            } => self
+                .cursor(edit)?
+                .split_table_cell(*cell_id, *split_rows, *split_columns),
+            EditAction::SetTableFormat { table_id, format } => {
+                self.cursor(edit)?.set_table_format(*table_id, format)
+            }
+            EditAction::SetTableCellFormat { cell_id, format } => {
+                self.cursor(edit)?.set_table_cell_format(*cell_id, format)
+            }
+            EditAction::DeleteChar => self.cursor(edit)?.delete_char(),
+            EditAction::DeletePreviousChar => self.cursor(edit)?.delete_previous_char(),
+            EditAction::RemoveSelectedText => self.cursor(edit)?.remove_selected_text().map(|_| ()),
+            EditAction::CreateList { style } => self.cursor(edit)?.create_list(style.clone()),
+            EditAction::InsertList { style } => self.cursor(edit)?.insert_list(style.clone()),
+            EditAction::SetListFormat { list_id, format } => {
+                self.cursor(edit)?.set_list_format(*list_id, format)
+            }
+            EditAction::AddBlockToList { block_id, list_id } => {
+                self.cursor(edit)?.add_block_to_list(*block_id, *list_id)
+            }
+            EditAction::RemoveBlockFromList { block_id } => {
+                self.cursor(edit)?.remove_block_from_list(*block_id)
+            }
+            EditAction::SetCharFormat { format } => self.cursor(edit)?.set_char_format(format),
+            EditAction::MergeCharFormat { format } => self.cursor(edit)?.merge_char_format(format),
+            EditAction::SetBlockFormat { format } => self.cursor(edit)?.set_block_format(format),
+            EditAction::SetFrameFormat { frame_id, format } => {
+                self.cursor(edit)?.set_frame_format(*frame_id, format)
+            }
+            EditAction::ReplaceInSelection {
+                query,
+                replacement,
+                options,
+            } => self
+                .cursor(edit)?
+                .replace_in_selection(query, replacement, options)
+                .map(|_| ()),
+            EditAction::BeginEditBlock => {
+                self.cursor(edit)?.begin_edit_block();
+                Ok(())
+            }
+            EditAction::EndEditBlock => {
+                let inner = doc.inner.lock();
+                undo_redo_commands::end_composite(&inner.ctx);
+                Ok(())
+            }
+            EditAction::CancelEditBlock => {
+                doc.cursor().cancel_edit_block();
+                Ok(())
+            }
+
+            EditAction::SetPlainText { text } => doc.set_plain_text(text),
+            EditAction::SetMarkdown { markdown } => doc.set_markdown(markdown)?.wait().map(|_| ()),
+            EditAction::SetHtml { html } => doc.set_html(html)?.wait().map(|_| ()),
+            EditAction::SetDocx { data_base64 } => doc
+                .set_docx(&BASE64.decode(data_base64)?)?
+                .wait()
+                .map(|_| ()),
+            EditAction::SetOdt { data_base64 } => doc
+                .set_odt(&BASE64.decode(data_base64)?)?
+                .wait()
+                .map(|_| ()),
+            EditAction::SetRtf { rtf } => doc.set_rtf(rtf)?.wait().map(|_| ()),
+            EditAction::LoadNative { data } => doc.load_native(data.as_bytes()),
+            EditAction::Clear => doc.clear(),
+            EditAction::ReplaceText {
+                query,
+                replacement,
+                replace_all,
+                options,
+            } => doc
+                .replace_text(query, replacement, *replace_all, options)
+                .map(|_| ()),
+            EditAction::ReplaceFormat {
+                query,
+                text_format,
+                block_format,
+            } => doc
+                .replace_format(query, text_format, block_format)
+                .map(|_| ()),
+            EditAction::AddResource {
+                resource_type,
+                name,
+                mime_type,
+                data_base64,
+            } => doc.add_resource(
+                resource_type.clone(),
+                name,
+                mime_type,
+                &BASE64.decode(data_base64)?,
+            ),
+            EditAction::Undo => {
+                let stack = doc.default_undo_stack();
+                self.history_step(stack, undo_target, || doc.undo())
+            }
+            EditAction::Redo => {
+                let stack = doc.default_undo_stack();
+                self.history_step(stack, redo_target, || doc.redo())
+            }
+            EditAction::UndoTo { index } => {
+                let stack = doc.default_undo_stack();
+                let target = |h: &UndoHistory| (*index < h.current_index).then_some(*index);
+                self.history_step(stack, target, || doc.undo_to(*index))
+            }
+            EditAction::RedoTo { index } => {
+                let stack = doc.default_undo_stack();
+                let target = |h: &UndoHistory| {
+                    (*index > h.current_index && *index <= h.entries.len()).then_some(*index)
+                };
+                self.history_step(stack, target, || doc.redo_to(*index))
+            }
+            EditAction::UndoIn { stack } => {
+                let stack = self.stack(*stack);
+                self.history_step(stack, undo_target, || doc.undo_in(stack))
+            }
+            EditAction::RedoIn { stack } => {
+                let stack = self.stack(*stack);
+                self.history_step(stack, redo_target, || doc.redo_in(stack))
+            }
+            EditAction::SetUndoLimit { limit } => doc.set_undo_limit(*limit),
+            EditAction::SetUndoLimitIn { stack, limit } => {
+                let stack = self.stack(*stack);
+                doc.set_undo_limit_in(stack, *limit)
+            }
+            EditAction::ClearUndoRedo => {
+                doc.clear_undo_redo();
+                Ok(())
+            }
+            EditAction::CreateUndoStack { stack } => {
+                let created = doc.create_undo_stack();
+                self.stacks.insert(*stack, created);
+                Ok(())
+            }
+            EditAction::DeleteUndoStack { stack } => {
+                let stack = self.stack(*stack);
+                doc.delete_undo_stack(stack)
+            }
+            EditAction::SetFrameUndoStack { frame_id, stack } => {
+                let stack = stack.map(|s| self.stack(s));
+                doc.set_frame_undo_stack(*frame_id, stack)
+            }
+            EditAction::SetModified { modified } => {
+                doc.set_modified(*modified);
+                Ok(())
+            }
+            EditAction::SetTitle { title } => doc.set_title(title),
+            EditAction::SetTextDirection { direction } => doc.set_text_direction(direction.clone()),
+            EditAction::SetDefaultWrapMode { mode } => doc.set_default_wrap_mode(mode.clone()),
+        }
+    }
+}
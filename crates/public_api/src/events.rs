@@ -56,6 +56,14 @@ pub enum DocumentEvent {
     /// Undo/redo was performed or availability changed.
     UndoRedoChanged { can_undo: bool, can_redo: bool },
 
+    /// Same as `UndoRedoChanged`, for an undo stack other than the
+    /// default one (see [`TextDocument::create_undo_stack`](crate::TextDocument::create_undo_stack)).
+    UndoStackChanged {
+        stack: crate::UndoStackId,
+        can_undo: bool,
+        can_redo: bool,
+    },
+
     /// The modified flag changed.
     ModificationChanged(bool),
 
@@ -3,6 +3,8 @@
 //! The layout engine processes [`FlowElement`]s in order to build its layout
 //! tree. Snapshot types capture consistent views for thread-safe reads.
 
+use serde::{Deserialize, Serialize};
+
 use crate::text_block::TextBlock;
 use crate::text_frame::TextFrame;
 use crate::text_table::TextTable;
@@ -237,7 +239,8 @@ pub enum SelectionKind {
 // ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━
 
 /// Table-level formatting.
-#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct TableFormat {
     pub border: Option<i32>,
     pub cell_spacing: Option<i32>,
@@ -247,7 +250,8 @@ pub struct TableFormat {
 }
 
 /// Cell-level formatting.
-#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct CellFormat {
     pub padding: Option<i32>,
     pub border: Option<i32>,
@@ -256,7 +260,7 @@ pub struct CellFormat {
 }
 
 /// Vertical alignment within a table cell.
-#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub enum CellVerticalAlignment {
     #[default]
     Top,
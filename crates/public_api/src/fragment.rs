@@ -3,7 +3,10 @@
 use crate::{InlineContent, ListStyle};
 use frontend::common::parser_tools::content_parser::{ParsedElement, ParsedSpan};
 use frontend::common::parser_tools::fragment_schema::{
-    FragmentBlock, FragmentData, FragmentElement, FragmentTable, FragmentTableCell,
+    FragmentBlock, FragmentData, FragmentElement, FragmentList, FragmentTable, FragmentTableCell,
+};
+use frontend::common::parser_tools::rich_document::{
+    RichBlock, RichCell, RichDocument, RichElement, RichInline, RichListItem, RichTable,
 };
 
 /// A piece of rich text that can be inserted into a [`TextDocument`](crate::TextDocument).
@@ -53,6 +56,9 @@ impl DocumentFragment {
                     fmt_tooltip: None,
                     fmt_underline_style: None,
                     fmt_vertical_alignment: None,
+                    fmt_foreground_color: None,
+                    fmt_background_color: None,
+                    fmt_underline_color: None,
                 }],
                 heading_level: None,
                 list: None,
@@ -98,6 +104,26 @@ impl DocumentFragment {
         parsed_elements_to_fragment(parsed)
     }
 
+    /// Create a fragment from RTF, e.g. clipboard content.
+    ///
+    /// Images keep their name and size but not their data, since a
+    /// fragment carries no resources; use
+    /// [`TextDocument::set_rtf`](crate::TextDocument::set_rtf) to import
+    /// embedded pictures.
+    pub fn from_rtf(rtf: &str) -> crate::Result<Self> {
+        let rich = frontend::common::parser_tools::rtf_reader::parse_rtf(rtf)?;
+        let fragment_data = rich_document_to_fragment_data(&rich);
+        let plain_text = fragment_data
+            .blocks
+            .iter()
+            .map(|b| b.plain_text.as_str())
+            .collect::<Vec<_>>()
+            .join("\n");
+        let data =
+            serde_json::to_string(&fragment_data).expect("fragment serialization should not fail");
+        Ok(Self { data, plain_text })
+    }
+
     /// Create a fragment from an entire document.
     pub fn from_document(doc: &crate::TextDocument) -> crate::Result<Self> {
         let inner = doc.inner.lock();
@@ -322,6 +348,21 @@ impl DocumentFragment {
         result
     }
 
+    /// Export the fragment as RTF. Images are omitted, since a fragment
+    /// does not carry their data.
+    pub fn to_rtf(&self) -> String {
+        let fragment_data: FragmentData = match serde_json::from_str(&self.data) {
+            Ok(d) => d,
+            Err(_) => FragmentData {
+                blocks: vec![],
+                tables: vec![],
+            },
+        };
+        frontend::common::parser_tools::rtf_writer::write_rtf(&fragment_data_to_rich_document(
+            &fragment_data,
+        ))
+    }
+
     /// Returns true if the fragment contains no text or elements.
     pub fn is_empty(&self) -> bool {
         self.plain_text.is_empty()
@@ -356,7 +397,7 @@ fn is_ordered_list_style(style: &ListStyle) -> bool {
 
 // ── HTML helpers ────────────────────────────────────────────────
 
-fn escape_html(s: &str) -> String {
+pub(crate) fn escape_html(s: &str) -> String {
     let mut out = String::with_capacity(s.len());
     for c in s.chars() {
         match c {
@@ -653,6 +694,9 @@ fn span_to_fragment_element(span: &ParsedSpan) -> FragmentElement {
         fmt_tooltip: None,
         fmt_underline_style: None,
         fmt_vertical_alignment: None,
+        fmt_foreground_color: span.foreground_color.clone(),
+        fmt_background_color: span.background_color.clone(),
+        fmt_underline_color: span.underline_color.clone(),
     }
 }
 
@@ -784,3 +828,181 @@ fn parsed_plain_text_from_data(data: &str) -> String {
         .collect::<Vec<_>>()
         .join("\n")
 }
+
+// ── RTF conversion ──────────────────────────────────────────────
+
+fn rich_document_to_fragment_data(rich: &RichDocument) -> FragmentData {
+    let mut blocks: Vec<FragmentBlock> = Vec::new();
+    let mut tables: Vec<FragmentTable> = Vec::new();
+
+    for element in &rich.elements {
+        match element {
+            RichElement::Block(block) => blocks.push(rich_block_to_fragment(block)),
+            RichElement::Table(table) => tables.push(FragmentTable {
+                rows: table.rows,
+                columns: table.columns,
+                cells: table
+                    .cells
+                    .iter()
+                    .map(|cell| FragmentTableCell {
+                        row: cell.row,
+                        column: cell.column,
+                        row_span: cell.row_span,
+                        column_span: cell.column_span,
+                        blocks: cell.blocks.iter().map(rich_block_to_fragment).collect(),
+                        fmt_padding: None,
+                        fmt_border: None,
+                        fmt_vertical_alignment: None,
+                        fmt_background_color: None,
+                    })
+                    .collect(),
+                block_insert_index: blocks.len(),
+                fmt_border: None,
+                fmt_cell_spacing: None,
+                fmt_cell_padding: None,
+                fmt_width: None,
+                fmt_alignment: None,
+                column_widths: vec![],
+            }),
+        }
+    }
+
+    FragmentData { blocks, tables }
+}
+
+fn rich_block_to_fragment(block: &RichBlock) -> FragmentBlock {
+    let mut plain_text = String::new();
+    let elements = block
+        .inlines
+        .iter()
+        .map(|inline| match inline {
+            RichInline::Text { text, format } => {
+                plain_text.push_str(text);
+                FragmentElement::from_format(InlineContent::Text(text.clone()), format)
+            }
+            RichInline::Image {
+                name,
+                width,
+                height,
+                format,
+            } => {
+                plain_text.push('\u{FFFC}');
+                FragmentElement::from_format(
+                    InlineContent::Image {
+                        name: name.clone(),
+                        width: *width,
+                        height: *height,
+                        quality: 100,
+                    },
+                    format,
+                )
+            }
+        })
+        .collect();
+
+    FragmentBlock {
+        plain_text,
+        elements,
+        heading_level: block.heading_level,
+        list: block.list.as_ref().map(|item| FragmentList {
+            style: item.style.clone(),
+            indent: item.indent as i64,
+            prefix: String::new(),
+            suffix: String::new(),
+        }),
+        alignment: block.alignment.clone(),
+        indent: None,
+        text_indent: block.text_indent,
+        marker: None,
+        top_margin: None,
+        bottom_margin: None,
+        left_margin: block.left_margin,
+        right_margin: block.right_margin,
+        tab_positions: vec![],
+        line_height: None,
+        non_breakable_lines: None,
+        direction: None,
+        background_color: None,
+        is_code_block: block.is_code_block.then_some(true),
+        code_language: None,
+    }
+}
+
+fn fragment_data_to_rich_document(data: &FragmentData) -> RichDocument {
+    let mut elements: Vec<RichElement> = Vec::new();
+    let mut sorted_tables: Vec<&FragmentTable> = data.tables.iter().collect();
+    sorted_tables.sort_by_key(|t| t.block_insert_index);
+    let mut table_cursor = 0;
+
+    let push_table = |elements: &mut Vec<RichElement>, table: &FragmentTable| {
+        elements.push(RichElement::Table(RichTable {
+            rows: table.rows,
+            columns: table.columns,
+            cells: table
+                .cells
+                .iter()
+                .map(|cell| RichCell {
+                    row: cell.row,
+                    column: cell.column,
+                    row_span: cell.row_span,
+                    column_span: cell.column_span,
+                    blocks: cell.blocks.iter().map(fragment_block_to_rich).collect(),
+                })
+                .collect(),
+        }));
+    };
+
+    for (index, block) in data.blocks.iter().enumerate() {
+        while table_cursor < sorted_tables.len()
+            && sorted_tables[table_cursor].block_insert_index <= index
+        {
+            push_table(&mut elements, sorted_tables[table_cursor]);
+            table_cursor += 1;
+        }
+        elements.push(RichElement::Block(fragment_block_to_rich(block)));
+    }
+    for table in &sorted_tables[table_cursor..] {
+        push_table(&mut elements, table);
+    }
+
+    RichDocument {
+        elements,
+        resources: vec![],
+    }
+}
+
+fn fragment_block_to_rich(block: &FragmentBlock) -> RichBlock {
+    let mut rich = RichBlock {
+        heading_level: block.heading_level,
+        alignment: block.alignment.clone(),
+        left_margin: block.left_margin,
+        right_margin: block.right_margin,
+        text_indent: block.text_indent,
+        list: block.list.as_ref().map(|list| RichListItem {
+            key: format!("{:?}", list.style),
+            style: list.style.clone(),
+            indent: list.indent.max(0) as u32,
+        }),
+        is_code_block: block.is_code_block == Some(true),
+        ..RichBlock::default()
+    };
+    for element in &block.elements {
+        let format = element.to_character_format();
+        match &element.content {
+            InlineContent::Text(text) => rich.push_text(text, &format),
+            InlineContent::Image {
+                name,
+                width,
+                height,
+                ..
+            } => rich.inlines.push(RichInline::Image {
+                name: name.clone(),
+                width: *width,
+                height: *height,
+                format,
+            }),
+            InlineContent::Empty => {}
+        }
+    }
+    rich
+}
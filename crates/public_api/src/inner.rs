@@ -13,6 +13,7 @@
 //! call `adjust_cursors()` before releasing the document lock.
 
 use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::atomic::AtomicBool;
 use std::sync::{Arc, Weak};
 
@@ -27,19 +28,62 @@ pub(crate) type QueuedEvents = Vec<(
 use anyhow::Result;
 use frontend::AppContext;
 use frontend::EventHubClient;
+use frontend::common::long_operation::OperationStatus;
 use frontend::common::types::EntityId;
+use frontend::common::undo_redo::{CellRangeState, SelectionState};
 use frontend::event_hub_client::SubscriptionToken;
 
 use crate::DocumentEvent;
+use crate::edit_log::{self, EditAction, EditLog, LoggedCursor, LoggedEdit};
 use crate::highlight::HighlightData;
+use crate::journal::Journal;
+use crate::search_index::SearchIndex;
 
 /// Cursor position data stored inside the document for automatic adjustment.
 pub(crate) struct CursorData {
+    /// Identifies the cursor in the selections recorded with undo entries.
+    pub id: u64,
     pub position: usize,
     pub anchor: usize,
     /// When set, overrides the computed `SelectionKind` to force cell selection.
     /// Cleared whenever position or anchor changes via normal editing/movement.
     pub cell_selection_override: Option<crate::flow::CellRange>,
+    /// Undo stack this cursor's edits go to, overriding the frame bindings.
+    pub undo_stack: Option<u64>,
+}
+
+impl CursorData {
+    /// The selection recorded with undo entries this cursor produces.
+    pub fn selection_state(&self) -> SelectionState {
+        SelectionState {
+            position: self.position,
+            anchor: self.anchor,
+            cell_range: self
+                .cell_selection_override
+                .as_ref()
+                .map(|r| CellRangeState {
+                    table_id: r.table_id as EntityId,
+                    start_row: r.start_row,
+                    start_col: r.start_col,
+                    end_row: r.end_row,
+                    end_col: r.end_col,
+                }),
+        }
+    }
+
+    /// Put the cursor back to a recorded selection, clamping positions
+    /// to `max_position`.
+    pub fn restore_selection(&mut self, state: &SelectionState, max_position: usize) {
+        self.position = state.position.min(max_position);
+        self.anchor = state.anchor.min(max_position);
+        self.cell_selection_override = state.cell_range.as_ref().map(|r| crate::flow::CellRange {
+            table_id: r.table_id as usize,
+            start_row: r.start_row,
+            start_col: r.start_col,
+            end_row: r.end_row,
+            end_col: r.end_col,
+        });
+    }
 }
 
 /// Callback entry for document event subscriptions.
@@ -53,13 +97,23 @@ pub(crate) struct TextDocumentInner {
     pub ctx: AppContext,
     pub event_client: EventHubClient,
     pub stack_id: u64,
-    #[allow(dead_code)] // will be used for entity tree access
+    /// Stacks created with `create_undo_stack`, besides `stack_id`.
+    pub undo_stacks: Vec<u64>,
+    /// Frame → undo stack its edits go to. Nested frames inherit the
+    /// binding of their nearest bound ancestor.
+    pub frame_undo_stacks: HashMap<EntityId, u64>,
+    /// Block → frame holding it and frame → parent frame, so routing an
+    /// edit by frame does not read every frame. Rebuilt when a lookup
+    /// misses or finds the block gone from its cached frame.
+    pub block_frames: HashMap<EntityId, EntityId>,
+    pub frame_parents: HashMap<EntityId, Option<EntityId>>,
     pub root_id: EntityId,
     pub document_id: EntityId,
     pub modified: bool,
 
     // Cursor tracking
     pub cursors: Vec<Weak<Mutex<CursorData>>>,
+    pub next_cursor_id: u64,
 
     // Event dispatch — two independent delivery paths:
     //
@@ -86,6 +140,10 @@ pub(crate) struct TextDocumentInner {
     // Cached plain text for the entire document. Populated lazily, invalidated
     // on any edit or document reset. Avoids O(blocks) reconstruction per search.
     pub plain_text_cache: Option<String>,
+    // Bumped with every invalidation of the text cache, so state kept
+    // across calls (a search being paged through) can tell the document
+    // changed since it was built.
+    pub content_version: u64,
 
     // Last known block count, used to detect changes and emit BlockCountChanged.
     pub last_block_count: usize,
@@ -97,9 +155,25 @@ pub(crate) struct TextDocumentInner {
     // Syntax highlighting state (shadow formatting layer).
     pub highlight: Option<HighlightData>,
 
+    // Trigram index for literal searches, kept current from the queued
+    // ContentsChanged / DocumentReset events. `None` unless enabled.
+    pub search_index: Option<SearchIndex>,
+
     // Holds SubscriptionTokens for LongOperation event bridges. Dropping a
     // token unsubscribes the callback, so these must outlive the document.
     pub long_op_subscriptions: Vec<SubscriptionToken>,
+
+    // Log of the mutations made since `start_recording`. `None` unless
+    // recording.
+    pub edit_log: Option<EditLog>,
+
+    // Crash-recovery journal the mutations are appended to. `None` unless
+    // journaling.
+    pub journal: Option<Journal>,
+
+    // Journal directory this document was recovered from, which
+    // `start_journal` may then supersede.
+    pub recovered_journal: Option<PathBuf>,
 }
 
 impl TextDocumentInner {
@@ -126,13 +200,69 @@ impl TextDocumentInner {
         }
     }
 
+    /// Move the cursor with the given ID, if it is still alive, to a
+    /// selection recorded with an undo entry.
+    pub fn restore_cursor_selection(
+        &mut self,
+        cursor_id: u64,
+        state: &SelectionState,
+        max_position: usize,
+    ) {
+        self.prune_dead_cursors();
+        for weak in &self.cursors {
+            if let Some(cursor) = weak.upgrade() {
+                let mut data = cursor.lock();
+                if data.id == cursor_id {
+                    data.restore_selection(state, max_position);
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Append a mutation to the edit log, if recording, and to the
+    /// journal, if journaling. `action` is only built when it is needed.
+    pub fn log_edit(&mut self, cursor: Option<LoggedCursor>, action: impl FnOnce() -> EditAction) {
+        if self.edit_log.is_none() && self.journal.is_none() {
+            return;
+        }
+        let edit = LoggedEdit {
+            cursor,
+            action: action(),
+        };
+        if let Some(mut journal) = self.journal.take() {
+            let busy = self.long_operation_running();
+            journal.record(&edit, busy, || edit_log::start(self));
+            self.journal = Some(journal);
+        }
+        if let Some(log) = &mut self.edit_log {
+            log.edits.push(edit);
+        }
+    }
+
+    /// Returns true while a long operation (an import or export) is
+    /// still running.
+    pub fn long_operation_running(&self) -> bool {
+        let mgr = match self.ctx.long_operation_manager.lock() {
+            Ok(g) => g,
+            Err(e) => e.into_inner(),
+        };
+        mgr.get_operations_summary()
+            .iter()
+            .any(|(_, status, _)| *status == OperationStatus::Running)
+    }
+
     /// Register a new cursor and return its shared data.
     pub fn register_cursor(&mut self, position: usize) -> Arc<Mutex<CursorData>> {
         self.prune_dead_cursors();
+        let id = self.next_cursor_id;
+        self.next_cursor_id += 1;
         let data = Arc::new(Mutex::new(CursorData {
+            id,
             position,
             anchor: position,
             cell_selection_override: None,
+            undo_stack: None,
         }));
         self.cursors.push(Arc::downgrade(&data));
         data
@@ -142,7 +272,11 @@ impl TextDocumentInner {
     ///
     /// Events are collected while the lock is held, then dispatched
     /// after the lock is released via [`dispatch_queued_events`].
+    /// The search index, when enabled, is brought up to date here.
     pub fn queue_event(&mut self, event: DocumentEvent) {
+        if let Some(index) = &mut self.search_index {
+            index.update(&event, self.ctx.db_context.get_store());
+        }
         self.pending_events.push(event);
     }
 
@@ -212,9 +346,88 @@ impl TextDocumentInner {
         }
     }
 
+    /// Undo stack for an edit at `position`: the stack bound to the
+    /// innermost frame around it that has one, else the default stack.
+    pub fn undo_stack_at(&mut self, position: usize) -> u64 {
+        use frontend::commands::document_inspection_commands;
+        if self.frame_undo_stacks.is_empty() {
+            return self.stack_id;
+        }
+        let dto = frontend::document_inspection::GetBlockAtPositionDto {
+            position: crate::convert::to_i64(position),
+        };
+        let Ok(block) = document_inspection_commands::get_block_at_position(&self.ctx, &dto) else {
+            return self.stack_id;
+        };
+        let mut frame = self.frame_of_block(block.block_id as EntityId);
+        while let Some(f) = frame {
+            if let Some(stack) = self.frame_undo_stacks.get(&f) {
+                return *stack;
+            }
+            frame = self.frame_parents.get(&f).copied().flatten();
+        }
+        self.stack_id
+    }
+
+    /// Frame holding `block_id`, from `block_frames` when the cached
+    /// frame still holds it, else after rebuilding the lookups.
+    fn frame_of_block(&mut self, block_id: EntityId) -> Option<EntityId> {
+        use frontend::commands::frame_commands;
+        if let Some(&frame_id) = self.block_frames.get(&block_id)
+            && let Ok(Some(frame)) = frame_commands::get_frame(&self.ctx, &frame_id)
+            && frame.blocks.contains(&block_id)
+        {
+            return Some(frame_id);
+        }
+        let frames = frame_commands::get_all_frame(&self.ctx).ok()?;
+        self.block_frames = frames
+            .iter()
+            .flat_map(|f| f.blocks.iter().map(move |b| (*b, f.id)))
+            .collect();
+        self.frame_parents = frames.iter().map(|f| (f.id, f.parent_frame)).collect();
+        self.block_frames.get(&block_id).copied()
+    }
+
+    /// Drop the frame bindings of undo stacks and the lookups behind
+    /// them, for operations that replace the whole document: the frames
+    /// they name are gone.
+    pub fn clear_frame_undo_stacks(&mut self) {
+        self.frame_undo_stacks.clear();
+        self.block_frames.clear();
+        self.frame_parents.clear();
+    }
+
+    /// Queue the undo availability of `stack_id`: `UndoRedoChanged` for
+    /// the default stack, `UndoStackChanged` for the others.
+    pub fn queue_undo_state(&mut self, stack_id: u64) {
+        use frontend::commands::undo_redo_commands;
+        let can_undo = undo_redo_commands::can_undo(&self.ctx, Some(stack_id));
+        let can_redo = undo_redo_commands::can_redo(&self.ctx, Some(stack_id));
+        if stack_id == self.stack_id {
+            self.queue_event(DocumentEvent::UndoRedoChanged { can_undo, can_redo });
+        } else {
+            self.queue_event(DocumentEvent::UndoStackChanged {
+                stack: crate::UndoStackId(stack_id),
+                can_undo,
+                can_redo,
+            });
+        }
+    }
+
+    /// Empty the default stack and every stack from `create_undo_stack`,
+    /// for operations that replace the whole document.
+    pub fn clear_undo_stacks(&self) {
+        use frontend::commands::undo_redo_commands;
+        undo_redo_commands::clear_stack(&self.ctx, self.stack_id);
+        for stack in &self.undo_stacks {
+            undo_redo_commands::clear_stack(&self.ctx, *stack);
+        }
+    }
+
     /// Invalidate the cached plain text. Call after any edit.
     pub fn invalidate_text_cache(&mut self) {
         self.plain_text_cache = None;
+        self.content_version += 1;
     }
 
     /// Check the current block count and queue a `BlockCountChanged` event if it changed.
@@ -307,6 +520,16 @@ impl TextDocumentInner {
             .unwrap_or_default()
     }
 
+    /// Number of top-level frames of the document.
+    pub fn top_frame_count(&self) -> usize {
+        frontend::commands::document_commands::get_document_relationship(
+            &self.ctx,
+            &self.document_id,
+            &frontend::document::dtos::DocumentRelationshipField::Frames,
+        )
+        .map_or(0, |frames| frames.len())
+    }
+
     /// Get or lazily build the cached plain text.
     pub fn plain_text(&mut self) -> Result<&str> {
         if self.plain_text_cache.is_none() {
@@ -329,7 +552,7 @@ impl TextDocumentInner {
         let event_client = EventHubClient::new(&ctx.event_hub);
         event_client.start(ctx.shutdown_rx.clone());
 
-        let stack_id = undo_redo_commands::create_new_stack(&ctx);
+        let stack_id = undo_redo_commands::create_guarded_stack(&ctx);
 
         // Create entity tree: Root → Document → Frame → Block
         let root = root_commands::create_orphan_root(&ctx, &CreateRootDto::default())?;
@@ -378,20 +601,30 @@ impl TextDocumentInner {
             ctx,
             event_client,
             stack_id,
+            undo_stacks: Vec::new(),
+            frame_undo_stacks: HashMap::new(),
+            block_frames: HashMap::new(),
+            frame_parents: HashMap::new(),
             root_id: root.id,
             document_id: doc.id,
             modified: false,
             cursors: Vec::new(),
+            next_cursor_id: 0,
             pending_events: Vec::new(),
             callbacks: Vec::new(),
             callback_cursor: 0,
             poll_cursor: 0,
             resource_cache: HashMap::new(),
             plain_text_cache: None,
+            content_version: 0,
             last_block_count: 1, // new document starts with one block
             last_child_order: vec![block.id as i64],
             highlight: None,
+            search_index: None,
             long_op_subscriptions: Vec::new(),
+            edit_log: None,
+            journal: None,
+            recovered_journal: None,
         })
     }
 }
@@ -0,0 +1,327 @@
+//! Crash-recovery journal.
+//!
+//! While a [`TextDocument`] is journaling (see
+//! [`TextDocument::start_journal`]), its journal directory holds a
+//! checkpoint — an [`EditLog`] with no edits, i.e. the document in the
+//! native format with its default undo stack's history — and an
+//! append-only file of the edits made since, one [`LoggedEdit`] per JSON
+//! line. [`TextDocument::recover`] replays the edits onto the checkpoint.
+//!
+//! After [`JournalOptions::checkpoint_every`] edits the next edit writes a
+//! new checkpoint instead and starts an empty edit file, which bounds how
+//! much recovery replays. Every checkpoint carries a generation number
+//! naming the edit file that goes with it and replaces the previous one
+//! by an atomic rename, so a crash at any point leaves a checkpoint and
+//! its matching edits.
+//!
+//! Checkpoints are deferred while a long operation (an import) is
+//! running, since the store is then only partly written.
+//!
+//! Recovery restores content, formats and undo history. One difference:
+//! typing that kept merging into the same undo entry across a checkpoint
+//! comes back as two entries, since a loaded entry is never merged into.
+//! Checkpoints read the history without stepping through it; when it
+//! cannot be saved, the checkpoint fails, [`TextDocument::journal_error`]
+//! says why and edits keep going to the previous checkpoint's file.
+//! Replaying an undo or redo that the recovered history cannot take is
+//! an error, so recovery never quietly differs from the document.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+
+use crate::TextDocument;
+use crate::edit_log::{self, EditLog, LoggedEdit};
+
+const CHECKPOINT_FILE: &str = "checkpoint.json";
+const EDITS_PREFIX: &str = "edits-";
+const EDITS_SUFFIX: &str = ".jsonl";
+
+/// Where and how [`TextDocument::start_journal`] keeps its journal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JournalOptions {
+    /// Directory holding the journal files; created if missing. Use one
+    /// directory per document.
+    pub dir: PathBuf,
+    /// When appended edits are flushed to disk.
+    pub sync: SyncPolicy,
+    /// Number of edits appended after a checkpoint before the next edit
+    /// writes a new one. Recovery replays at most this many edits.
+    pub checkpoint_every: usize,
+}
+
+impl JournalOptions {
+    /// Journal into `dir`, syncing every edit and checkpointing every
+    /// 500 edits.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            sync: SyncPolicy::default(),
+            checkpoint_every: 500,
+        }
+    }
+}
+
+/// When journaled edits are flushed to disk with `fsync`. Checkpoints
+/// are always synced.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SyncPolicy {
+    /// Sync after every edit. A crash loses nothing that was applied.
+    #[default]
+    EveryEdit,
+    /// Sync after every `n` edits. A crash of the machine can lose up to
+    /// the last `n - 1` edits; a crash of the process alone loses none.
+    EveryEdits(usize),
+    /// Leave flushing to the operating system. Call
+    /// [`TextDocument::sync_journal`] to sync at chosen points.
+    Never,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Checkpoint {
+    /// Names the file the edits made after this checkpoint go to.
+    generation: u64,
+    start: EditLog,
+}
+
+/// An open journal, owned by the document while journaling.
+pub(crate) struct Journal {
+    options: JournalOptions,
+    generation: u64,
+    /// The current generation's edit file, opened for appending.
+    file: File,
+    /// Edits appended since the checkpoint.
+    edits: usize,
+    /// Edits appended since the last sync.
+    unsynced: usize,
+    /// The last write failure. While set, the next edit retries the
+    /// checkpoint before it is appended.
+    error: Option<String>,
+    /// Whether an append failed, leaving the edit file short of the
+    /// document: nothing more is appended until a checkpoint succeeds.
+    behind: bool,
+}
+
+impl Journal {
+    /// Open a journal in `options.dir`, starting with a checkpoint of
+    /// `start`. A journal already there is an error unless `supersede`
+    /// is set, as it may hold edits nobody has recovered yet.
+    pub(crate) fn create(options: JournalOptions, start: EditLog, supersede: bool) -> Result<Self> {
+        if !supersede && options.dir.join(CHECKPOINT_FILE).exists() {
+            bail!(
+                "{} holds a journal that was not recovered; recover or discard it first",
+                options.dir.display()
+            );
+        }
+        fs::create_dir_all(&options.dir)
+            .with_context(|| format!("creating {}", options.dir.display()))?;
+        let generation = read_checkpoint(&options.dir)
+            .ok()
+            .flatten()
+            .map_or(0, |c| c.generation)
+            + 1;
+        let file = write_checkpoint(&options.dir, generation, start)?;
+        Ok(Self {
+            options,
+            generation,
+            file,
+            edits: 0,
+            unsynced: 0,
+            error: None,
+            behind: false,
+        })
+    }
+
+    pub(crate) fn dir(&self) -> &Path {
+        &self.options.dir
+    }
+
+    pub(crate) fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    /// Journal an edit that was just applied. `snapshot` gives the
+    /// document as it is now, for when a checkpoint is due; `busy` defers
+    /// checkpoints while a long operation runs. Failures are kept in
+    /// [`error`](Self::error) rather than returned, since the edit itself
+    /// succeeded.
+    pub(crate) fn record(
+        &mut self,
+        edit: &LoggedEdit,
+        busy: bool,
+        snapshot: impl FnOnce() -> Result<EditLog>,
+    ) {
+        if (self.error.is_some() || self.edits >= self.options.checkpoint_every) && !busy {
+            match snapshot().and_then(|start| self.checkpoint(start)) {
+                Ok(()) => return,
+                // The previous checkpoint and its edits still add up to
+                // the document, so keep appending to them.
+                Err(e) => self.error = Some(format!("{e:#}")),
+            }
+        }
+        if self.behind {
+            return;
+        }
+        if let Err(e) = self.append(edit) {
+            self.error = Some(format!("{e:#}"));
+            self.behind = true;
+        }
+    }
+
+    /// Replace the checkpoint with `start` and begin a new, empty edit
+    /// file.
+    pub(crate) fn checkpoint(&mut self, start: EditLog) -> Result<()> {
+        let generation = self.generation + 1;
+        self.file = write_checkpoint(&self.options.dir, generation, start)?;
+        self.generation = generation;
+        self.edits = 0;
+        self.unsynced = 0;
+        self.error = None;
+        self.behind = false;
+        Ok(())
+    }
+
+    /// Flush appended edits to disk.
+    pub(crate) fn sync(&mut self) -> Result<()> {
+        self.file.sync_data()?;
+        self.unsynced = 0;
+        Ok(())
+    }
+
+    fn append(&mut self, edit: &LoggedEdit) -> Result<()> {
+        let mut line = serde_json::to_string(edit)?;
+        line.push('\n');
+        self.file.write_all(line.as_bytes())?;
+        self.edits += 1;
+        self.unsynced += 1;
+        let due = match self.options.sync {
+            SyncPolicy::EveryEdit => true,
+            SyncPolicy::EveryEdits(n) => self.unsynced >= n,
+            SyncPolicy::Never => false,
+        };
+        if due { self.sync() } else { Ok(()) }
+    }
+}
+
+/// Delete the journal files in `dir`, leaving the directory itself.
+pub(crate) fn remove(dir: &Path) -> Result<()> {
+    match fs::remove_file(dir.join(CHECKPOINT_FILE)) {
+        Err(e) if e.kind() != ErrorKind::NotFound => return Err(e.into()),
+        _ => {}
+    }
+    remove_edit_files(dir, None)
+}
+
+/// Rebuild the document journaled in `dir`. `None` if `dir` holds no
+/// checkpoint.
+pub(crate) fn recover(dir: &Path) -> Result<Option<TextDocument>> {
+    let Some(checkpoint) = read_checkpoint(dir)? else {
+        return Ok(None);
+    };
+    let mut log = checkpoint.start;
+    log.edits = read_edits(&edits_path(dir, checkpoint.generation))?;
+    edit_log::replay(&log).map(Some)
+}
+
+fn edits_path(dir: &Path, generation: u64) -> PathBuf {
+    dir.join(format!("{EDITS_PREFIX}{generation}{EDITS_SUFFIX}"))
+}
+
+fn read_checkpoint(dir: &Path) -> Result<Option<Checkpoint>> {
+    let path = dir.join(CHECKPOINT_FILE);
+    let json = match fs::read_to_string(&path) {
+        Ok(json) => json,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let checkpoint = serde_json::from_str(&json)
+        .with_context(|| format!("reading checkpoint {}", path.display()))?;
+    Ok(Some(checkpoint))
+}
+
+/// The edits in an edit file. A missing file holds none. The last line
+/// may have been cut short by a crash and is dropped if it does not
+/// parse; any other bad line is an error.
+fn read_edits(path: &Path) -> Result<Vec<LoggedEdit>> {
+    let data = match fs::read(path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let lines: Vec<&[u8]> = data
+        .split(|&b| b == b'\n')
+        .filter(|line| !line.is_empty())
+        .collect();
+    let mut edits = Vec::with_capacity(lines.len());
+    for (index, line) in lines.iter().enumerate() {
+        match serde_json::from_slice(line) {
+            Ok(edit) => edits.push(edit),
+            Err(_) if index + 1 == lines.len() => {}
+            Err(e) => bail!("{} line {}: {e}", path.display(), index + 1),
+        }
+    }
+    Ok(edits)
+}
+
+/// Write the checkpoint for `generation` and return its empty edit file,
+/// open for appending. The edit file exists before the checkpoint that
+/// names it, and the previous generation's files are removed only after.
+fn write_checkpoint(dir: &Path, generation: u64, start: EditLog) -> Result<File> {
+    let path = edits_path(dir, generation);
+    let file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(true)
+        .open(&path)
+        .with_context(|| format!("creating {}", path.display()))?;
+    file.sync_all()?;
+
+    let json = serde_json::to_vec(&Checkpoint { generation, start })?;
+    let tmp = dir.join(format!("{CHECKPOINT_FILE}.tmp"));
+    let mut out = File::create(&tmp).with_context(|| format!("creating {}", tmp.display()))?;
+    out.write_all(&json)?;
+    out.sync_all()?;
+    fs::rename(&tmp, dir.join(CHECKPOINT_FILE))?;
+    sync_dir(dir)?;
+
+    remove_edit_files(dir, Some(generation))?;
+    Ok(file)
+}
+
+/// Remove the edit files in `dir`, except `keep`'s.
+fn remove_edit_files(dir: &Path, keep: Option<u64>) -> Result<()> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+    for entry in entries {
+        let entry = entry?;
+        let name = entry.file_name();
+        let Some(generation) = name
+            .to_str()
+            .and_then(|n| n.strip_prefix(EDITS_PREFIX))
+            .and_then(|n| n.strip_suffix(EDITS_SUFFIX))
+            .and_then(|n| n.parse::<u64>().ok())
+        else {
+            continue;
+        };
+        if Some(generation) != keep {
+            fs::remove_file(entry.path())?;
+        }
+    }
+    Ok(())
+}
+
+/// Make a rename in `dir` durable. Directories cannot be opened for
+/// syncing on Windows, where renames are already durable.
+fn sync_dir(dir: &Path) -> Result<()> {
+    #[cfg(unix)]
+    File::open(dir)?.sync_all()?;
+    #[cfg(not(unix))]
+    let _ = dir;
+    Ok(())
+}
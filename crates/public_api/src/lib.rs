@@ -24,24 +24,41 @@
 //! doc.undo().unwrap();
 //! ```
 
+mod align;
+mod collab;
 mod convert;
 mod cursor;
+mod diff;
+mod doc_blocks;
 mod document;
+mod edit_log;
 mod events;
 mod flow;
 mod fragment;
 mod highlight;
 mod inner;
+mod journal;
+mod merge;
 mod operation;
+mod search;
+mod search_index;
 mod text_block;
 mod text_frame;
 mod text_list;
 mod text_table;
 
+use serde::{Deserialize, Serialize};
+
 // ── Re-exports from entity DTOs (enums that consumers need) ──────
 pub use frontend::block::dtos::{Alignment, MarkerType};
 pub use frontend::block::dtos::{CharVerticalAlignment, InlineContent, UnderlineStyle};
+pub use frontend::common::collab::{
+    BlockProps, CellProps, ListProps, Operation as CollabOperation,
+    OperationKind as CollabOperationKind, SiteId, UnitContent, UnitId,
+};
+pub use frontend::common::undo_redo::UndoLimit;
 pub use frontend::document::dtos::{TextDirection, WrapMode};
+pub use frontend::document_search::SearchNormalization;
 pub use frontend::frame::dtos::FramePosition;
 pub use frontend::list::dtos::ListStyle;
 pub use frontend::resource::dtos::ResourceType;
@@ -50,12 +67,24 @@ pub use frontend::resource::dtos::ResourceType;
 pub type Result<T> = anyhow::Result<T>;
 
 // ── Public API types ─────────────────────────────────────────────
+pub use collab::CollabSession;
 pub use cursor::TextCursor;
+pub use diff::{
+    BlockChange, CellChange, DiffChange, DocumentDiff, RunFormatChange, SpanChange, TableChange,
+    TextEdit, diff,
+};
 pub use document::TextDocument;
+pub use edit_log::{EDIT_LOG_VERSION, EditAction, EditLog, LoggedCursor, LoggedEdit};
 pub use events::{DocumentEvent, Subscription};
 pub use fragment::DocumentFragment;
 pub use highlight::{HighlightContext, HighlightFormat, HighlightSpan, SyntaxHighlighter};
-pub use operation::{DocxExportResult, HtmlImportResult, MarkdownImportResult, Operation};
+pub use journal::{JournalOptions, SyncPolicy};
+pub use merge::{ConflictKind, DocumentMerge, MergeConflict, MergeSide, merge};
+pub use operation::{
+    DocxExportResult, DocxImportResult, EpubExportResult, HtmlImportResult, MarkdownImportResult,
+    OdtExportResult, OdtImportResult, Operation, RtfImportResult,
+};
+pub use search::SearchHits;
 
 // ── Layout engine API types ─────────────────────────────────────
 pub use flow::{
@@ -80,6 +109,7 @@ const _: () = {
         assert_send_sync::<TextTable>();
         assert_send_sync::<TextTableCell>();
         assert_send_sync::<TextList>();
+        assert_send_sync::<SearchHits>();
     }
 };
 
@@ -88,7 +118,7 @@ const _: () = {
 // ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━
 
 /// An RGBA color value. Each component is 0–255.
-#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Color {
     pub red: u8,
     pub green: u8,
@@ -124,7 +154,8 @@ impl Color {
 
 /// Character/text formatting. All fields are optional: `None` means
 /// "not set — inherit from the block's default or the document's default."
-#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct TextFormat {
     pub font_family: Option<String>,
     pub font_point_size: Option<u32>,
@@ -148,7 +179,8 @@ pub struct TextFormat {
 }
 
 /// Block (paragraph) formatting. All fields are optional.
-#[derive(Debug, Clone, Default, PartialEq)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct BlockFormat {
     pub alignment: Option<Alignment>,
     pub top_margin: Option<i32>,
@@ -170,7 +202,8 @@ pub struct BlockFormat {
 
 /// List formatting. All fields are optional: `None` means
 /// "not set — don't change this property."
-#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ListFormat {
     pub style: Option<ListStyle>,
     pub indent: Option<u8>,
@@ -179,7 +212,8 @@ pub struct ListFormat {
 }
 
 /// Frame formatting. All fields are optional.
-#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct FrameFormat {
     pub height: Option<i32>,
     pub width: Option<i32>,
@@ -257,6 +291,38 @@ pub struct DocumentStats {
     pub table_count: usize,
 }
 
+/// The document's undo history, as returned by
+/// [`TextDocument::undo_history`].
+///
+/// `entries` lists the undoable operations oldest first, followed by
+/// the redoable ones in the order they would be redone.
+/// `current_index` is the number of undoable entries, so
+/// `entries[..current_index]` is the undo stack and
+/// `entries[current_index..]` the redo stack.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UndoHistory {
+    pub entries: Vec<UndoHistoryEntry>,
+    pub current_index: usize,
+}
+
+/// One operation in the [`UndoHistory`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UndoHistoryEntry {
+    /// Human-readable label such as "Typing" or "Insert table".
+    pub description: String,
+}
+
+/// Handle to one of the document's undo stacks.
+///
+/// Every document starts with a default stack
+/// ([`TextDocument::default_undo_stack`]). Extra stacks from
+/// [`TextDocument::create_undo_stack`] give a region — a caption, a
+/// comment, a sidebar frame — its own history: edits routed there are
+/// undone with [`TextDocument::undo_in`] without touching the rest of
+/// the document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct UndoStackId(pub(crate) u64);
+
 /// Info about a block at a given position.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct BlockInfo {
@@ -273,11 +339,197 @@ pub struct FindMatch {
     pub length: usize,
 }
 
+/// A search match with where it sits in the document and what
+/// surrounds it, as returned by [`TextDocument::find_detailed`],
+/// [`TextDocument::find_all_detailed`] and [`TextDocument::find_iter`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchHit {
+    pub position: usize,
+    pub length: usize,
+    pub block_id: usize,
+    /// Offset of the match within its block.
+    pub block_position: usize,
+    pub frame_id: usize,
+    /// The table cell holding the match's block, if any.
+    pub table_cell: Option<TableCellContext>,
+    /// Up to [`FindOptions::context_chars`] characters before the match,
+    /// stopping at the start of its block.
+    pub context_before: String,
+    /// Up to [`FindOptions::context_chars`] characters after the match,
+    /// stopping at the end of its block.
+    pub context_after: String,
+    /// `(position, length)` of each capture group of a regex search,
+    /// group 1 first. `None` for a group that took no part in the match.
+    pub captures: Vec<Option<(usize, usize)>>,
+    /// Edit distance to the query in a fuzzy search, 0 otherwise.
+    pub distance: usize,
+}
+
 /// Options for find / find_all / replace operations.
-#[derive(Debug, Clone, Default)]
+///
+/// Build it from `FindOptions::default()`, either with the `with_*`
+/// setters or as a struct literal ending in `..Default::default()`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct FindOptions {
     pub case_sensitive: bool,
     pub whole_word: bool,
+    /// Treat the query as a regular expression. Replacements may then
+    /// refer to capture groups as `$1` or `${name}` (`$$` for a literal `$`).
     pub use_regex: bool,
     pub search_backward: bool,
+    /// When replacing, adapt the replacement to each match's
+    /// capitalization, word by word: "Foo" → "Bar", "FOO" → "BAR",
+    /// "HELLO world" → "GOODBYE moon". Replacement words past the last
+    /// matched word take that word's case.
+    pub preserve_case: bool,
+    /// When replacing, also replace matches that span paragraph breaks,
+    /// merging the paragraphs; each `\n` in the replacement starts a new
+    /// paragraph, and regex `^` and `$` also match at paragraph
+    /// boundaries. Without it such matches are left untouched.
+    pub cross_block: bool,
+    /// Restrict find / find_all / replace to part of the document.
+    /// `None` searches everything.
+    pub scope: Option<SearchScope>,
+    /// Length of the context snippets in [`SearchHit`]s.
+    pub context_chars: usize,
+    /// Unicode normalization applied to both text and query, so that
+    /// precomposed and decomposed accents compare equal (`Nfc`), as do
+    /// compatibility forms like ligatures (`Nfkc`).
+    pub normalization: SearchNormalization,
+    /// Ignore diacritics: "resume" finds "résumé" and "Đong" finds "dòng".
+    pub fold_accents: bool,
+    /// Treat full-width and half-width forms as their usual forms:
+    /// "ＡＢＣ" finds "ABC".
+    pub fold_width: bool,
+    /// Fuzzy find / find_all: match runs of as many whole words as the
+    /// query has that are at most this many edits (insertions,
+    /// deletions, substitutions) away from it. `find_all` and
+    /// `find_iter` then return the closest matches in the whole document
+    /// first, `find_iter` ranking them all before its first page. Not
+    /// combinable with `use_regex`, and ignored by replace.
+    pub fuzzy: Option<usize>,
+}
+
+impl FindOptions {
+    pub fn with_case_sensitive(mut self, case_sensitive: bool) -> Self {
+        self.case_sensitive = case_sensitive;
+        self
+    }
+
+    pub fn with_whole_word(mut self, whole_word: bool) -> Self {
+        self.whole_word = whole_word;
+        self
+    }
+
+    pub fn with_use_regex(mut self, use_regex: bool) -> Self {
+        self.use_regex = use_regex;
+        self
+    }
+
+    pub fn with_search_backward(mut self, search_backward: bool) -> Self {
+        self.search_backward = search_backward;
+        self
+    }
+
+    pub fn with_preserve_case(mut self, preserve_case: bool) -> Self {
+        self.preserve_case = preserve_case;
+        self
+    }
+
+    pub fn with_cross_block(mut self, cross_block: bool) -> Self {
+        self.cross_block = cross_block;
+        self
+    }
+
+    pub fn with_scope(mut self, scope: Option<SearchScope>) -> Self {
+        self.scope = scope;
+        self
+    }
+
+    pub fn with_context_chars(mut self, context_chars: usize) -> Self {
+        self.context_chars = context_chars;
+        self
+    }
+
+    pub fn with_normalization(mut self, normalization: SearchNormalization) -> Self {
+        self.normalization = normalization;
+        self
+    }
+
+    pub fn with_fold_accents(mut self, fold_accents: bool) -> Self {
+        self.fold_accents = fold_accents;
+        self
+    }
+
+    pub fn with_fold_width(mut self, fold_width: bool) -> Self {
+        self.fold_width = fold_width;
+        self
+    }
+
+    pub fn with_fuzzy(mut self, fuzzy: Option<usize>) -> Self {
+        self.fuzzy = fuzzy;
+        self
+    }
+}
+
+/// Format conditions for [`TextDocument::find_format`] and
+/// [`TextDocument::replace_format`]. Every set field must match; unset
+/// fields match anything. In the document, unset boolean attributes
+/// count as `false` and an unset heading level as 0 (body text).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FormatQuery {
+    /// Compared case-insensitively.
+    pub font_family: Option<String>,
+    pub font_bold: Option<bool>,
+    pub font_italic: Option<bool>,
+    pub font_underline: Option<bool>,
+    pub font_strikeout: Option<bool>,
+    pub foreground_color: Option<Color>,
+    pub background_color: Option<Color>,
+    /// Matches links whose target contains this text.
+    pub anchor_href: Option<String>,
+    pub heading_level: Option<u8>,
+    pub is_code_block: Option<bool>,
+    /// Compared case-insensitively.
+    pub code_language: Option<String>,
+    pub scope: Option<SearchScope>,
+}
+
+/// The part of the document a search is restricted to. Matches must lie
+/// entirely inside it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SearchScope {
+    /// Characters `start..end`, as document positions.
+    Range { start: usize, end: usize },
+    /// The blocks of a frame, including its sub-frames and tables.
+    Frame(usize),
+    /// The blocks of every cell of a table.
+    Table(usize),
+}
+
+/// Options for EPUB export (`to_epub`).
+#[derive(Debug, Clone)]
+pub struct EpubOptions {
+    /// Headings of this level or above (1 = `h1` only) start a new
+    /// chapter. Clamped to 1..=6.
+    pub heading_level: u8,
+    /// `dc:creator`; omitted when empty.
+    pub author: String,
+    /// BCP 47 language tag; `"en"` when empty.
+    pub language: String,
+    /// `dc:identifier`; a random `urn:uuid:` when empty.
+    pub identifier: String,
+}
+
+impl Default for EpubOptions {
+    fn default() -> Self {
+        Self {
+            heading_level: 1,
+            author: String::new(),
+            language: String::new(),
+            identifier: String::new(),
+        }
+    }
 }
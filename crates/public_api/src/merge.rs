@@ -0,0 +1,916 @@
+//! Three-way merges of document versions.
+//!
+//! [`merge`] combines what two versions changed since a common base. It
+//! lines each of them up with the base the way [`diff`](crate::diff)
+//! does: blocks and tables first, then the characters of each block
+//! edited on both sides. Changes in different places are all kept.
+//! Where the sides disagree — both edited the same text, one edited a
+//! block the other removed, they gave the same text or block different
+//! formats, or reshaped a table differently — the merge records a
+//! [`MergeConflict`] and keeps our version there;
+//! [`DocumentMerge::resolve`] switches a conflict to their version.
+//!
+//! A block moved on one side is merged as removed in one place and
+//! inserted in another.
+
+use std::collections::HashMap;
+
+use anyhow::{Result, bail};
+use frontend::common::types::EntityId;
+
+use crate::TextDocument;
+use crate::align::{self, Hunk};
+use crate::diff::{
+    Item, Side, Step, Table, align_items, align_table, block_props, list_format, new_of, patch,
+    same_blocks, text_of,
+};
+use crate::doc_blocks::{DocBlock, DocCell, UnitContent};
+
+/// Merge the changes `ours` and `theirs` made to `base` into a new
+/// document, which starts with an empty undo history. When one version
+/// left `base` as it was, the document is a copy of the other.
+///
+/// ```
+/// use text_document::{ConflictKind, MergeSide, TextDocument};
+///
+/// let base = TextDocument::new();
+/// base.set_plain_text("Report\nThe fox jumps").unwrap();
+/// let (ours, theirs) = (TextDocument::new(), TextDocument::new());
+/// ours.set_plain_text("Report draft\nThe quick fox jumps").unwrap();
+/// theirs.set_plain_text("Report final\nThe fox jumps high").unwrap();
+///
+/// let mut merge = text_document::merge(&base, &ours, &theirs).unwrap();
+/// let doc = merge.document();
+/// assert_eq!(doc.to_plain_text().unwrap(), "Report draft\nThe quick fox jumps high");
+///
+/// let conflict = &merge.conflicts()[0];
+/// assert_eq!(conflict.kind, ConflictKind::Text);
+/// assert_eq!((conflict.start, conflict.end), (6, 12));
+/// assert_eq!((conflict.ours.as_str(), conflict.theirs.as_str()), (" draft", " final"));
+///
+/// merge.resolve(0, MergeSide::Theirs).unwrap();
+/// assert_eq!(
+///     merge.document().to_plain_text().unwrap(),
+///     "Report final\nThe quick fox jumps high"
+/// );
+/// ```
+pub fn merge(
+    base: &TextDocument,
+    ours: &TextDocument,
+    theirs: &TextDocument,
+) -> Result<DocumentMerge> {
+    let versions = [Side::read(base)?, Side::read(ours)?, Side::read(theirs)?];
+    let unchanged = |a: &Side, b: &Side| same_blocks(&a.blocks, &b.blocks, true);
+    let [base_side, ours_side, theirs_side] = &versions;
+
+    // A version that changed nothing leaves the other one as it is.
+    let changed = if unchanged(ours_side, base_side) {
+        Some(theirs)
+    } else if unchanged(theirs_side, base_side) || unchanged(theirs_side, ours_side) {
+        Some(ours)
+    } else {
+        None
+    };
+    let (document, merged, conflicts) = match changed {
+        Some(side) => {
+            let document = copy(side)?;
+            let merged = Side::read(&document)?;
+            (document, merged, Vec::new())
+        }
+        None => {
+            let (merged, conflicts) = build(&versions, &[]);
+            let document = copy(base)?;
+            let target = Side::read(&document)?;
+            let steps = align_items(&target, &target.items, &merged, &merged.items);
+            patch(&document, &document.cursor(), &target, &merged, &steps)?;
+            (document, merged, conflicts)
+        }
+    };
+    document.clear_undo_redo();
+
+    Ok(DocumentMerge {
+        document,
+        conflicts,
+        versions,
+        merged,
+    })
+}
+
+/// A new document holding `doc`'s content.
+fn copy(doc: &TextDocument) -> Result<TextDocument> {
+    let mut data = Vec::new();
+    doc.save_native(&mut data)?;
+    let document = TextDocument::new();
+    document.load_native(data.as_slice())?;
+    Ok(document)
+}
+
+// ── Public types ─────────────────────────────────────────────
+
+/// The result of [`merge`]: the merged document and where the two
+/// versions conflicted.
+pub struct DocumentMerge {
+    document: TextDocument,
+    conflicts: Vec<MergeConflict>,
+    /// Base, ours and theirs.
+    versions: [Side; 3],
+    /// The document as last merged or resolved.
+    merged: Side,
+}
+
+/// A place where the two versions disagree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MergeConflict {
+    pub kind: ConflictKind,
+    /// Start of the region in the merged document, which holds the
+    /// `chosen` version. The region is empty where that version removed
+    /// the blocks.
+    pub start: usize,
+    pub end: usize,
+    pub chosen: MergeSide,
+    /// The region's text in each version, blocks separated by `\n` and
+    /// images shown as U+FFFC.
+    pub base: String,
+    pub ours: String,
+    pub theirs: String,
+}
+
+/// What the versions disagree on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictKind {
+    /// Both edited the same text or inserted different blocks in the
+    /// same place, or one edited blocks the other removed.
+    Text,
+    /// Both changed the character format of the same text, or the block
+    /// format or list of the same block, differently.
+    Format,
+    /// Both added or removed rows or columns of the same table, or
+    /// merged its cells, differently.
+    Table,
+}
+
+/// One of the two versions being merged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MergeSide {
+    #[default]
+    Ours,
+    Theirs,
+}
+
+impl DocumentMerge {
+    /// The merged document.
+    pub fn document(&self) -> &TextDocument {
+        &self.document
+    }
+
+    /// The merged document, leaving the conflicts behind.
+    pub fn into_document(self) -> TextDocument {
+        self.document
+    }
+
+    /// The conflicts, in document order. Resolving one moves the regions
+    /// of those after it.
+    pub fn conflicts(&self) -> &[MergeConflict] {
+        &self.conflicts
+    }
+
+    /// True when the versions did not disagree anywhere.
+    pub fn has_conflicts(&self) -> bool {
+        !self.conflicts.is_empty()
+    }
+
+    /// Put `side`'s version in the region of conflict `index`. The edits
+    /// form one undo step. Fails if the document was edited since it was
+    /// merged or last resolved, or if the patch fails; a failed resolve
+    /// leaves the document and the conflicts as they were.
+    pub fn resolve(&mut self, index: usize, side: MergeSide) -> Result<()> {
+        let Some(conflict) = self.conflicts.get(index) else {
+            bail!("no merge conflict {index}");
+        };
+        if conflict.chosen == side {
+            return Ok(());
+        }
+        let target = Side::read(&self.document)?;
+        if !same_blocks(&target.blocks, &self.merged.blocks, true) {
+            bail!("the merged document was edited since it was merged");
+        }
+        let mut choices: Vec<MergeSide> = self.conflicts.iter().map(|c| c.chosen).collect();
+        choices[index] = side;
+        let (merged, conflicts) = build(&self.versions, &choices);
+        let steps = align_items(&target, &target.items, &merged, &merged.items);
+        patch(
+            &self.document,
+            &self.document.cursor(),
+            &target,
+            &merged,
+            &steps,
+        )?;
+        self.merged = merged;
+        self.conflicts = conflicts;
+        Ok(())
+    }
+}
+
+// ── Merging ──────────────────────────────────────────────────
+
+/// The merged content, taking `choices[n]` in the `n`th conflict and
+/// our version past the end of `choices`. Conflicts are found in the
+/// same order whatever is chosen: a conflicting region is taken whole
+/// from one version.
+fn build(versions: &[Side; 3], choices: &[MergeSide]) -> (Side, Vec<MergeConflict>) {
+    let [base, ours, theirs] = versions;
+    let mut merger = Merger {
+        base,
+        ours,
+        theirs,
+        choices,
+        blocks: Vec::new(),
+        regions: Vec::new(),
+        lists: HashMap::new(),
+    };
+    let items = merger.items(&base.items, &ours.items, &theirs.items, None);
+    merger.finish(items)
+}
+
+/// Where a conflict is, in blocks of the merged content.
+enum Span {
+    /// Blocks `first..end`.
+    Blocks { first: usize, end: usize },
+    /// Units `start..end` of one block.
+    Units {
+        block: usize,
+        start: usize,
+        end: usize,
+    },
+}
+
+struct Region {
+    kind: ConflictKind,
+    chosen: MergeSide,
+    span: Span,
+    texts: [String; 3],
+}
+
+/// Where each base item went in one version, and what that version
+/// inserted before each base item and at the end.
+struct Lineup {
+    kept: Vec<Option<usize>>,
+    inserted: Vec<Vec<usize>>,
+}
+
+impl Lineup {
+    fn new(steps: &[Step], len: usize) -> Lineup {
+        let mut lineup = Lineup {
+            kept: vec![None; len],
+            inserted: vec![Vec::new(); len + 1],
+        };
+        let mut gap = 0;
+        for step in steps {
+            match *step {
+                Step::Pair(i, j) => {
+                    lineup.kept[i] = Some(j);
+                    gap = i + 1;
+                }
+                Step::Removed { old, .. } => gap = old + 1,
+                Step::Inserted { new, .. } => lineup.inserted[gap].push(new),
+            }
+        }
+        lineup
+    }
+}
+
+struct Merger<'a> {
+    base: &'a Side,
+    ours: &'a Side,
+    theirs: &'a Side,
+    choices: &'a [MergeSide],
+    blocks: Vec<DocBlock>,
+    regions: Vec<Region>,
+    /// Their list ids → ours, for lists both versions have a member of
+    /// in the same base block.
+    lists: HashMap<EntityId, EntityId>,
+}
+
+impl<'a> Merger<'a> {
+    fn version(&self, side: MergeSide) -> &'a Side {
+        match side {
+            MergeSide::Ours => self.ours,
+            MergeSide::Theirs => self.theirs,
+        }
+    }
+
+    /// The version to take in the conflict about to be recorded.
+    fn choice(&self) -> MergeSide {
+        self.choices
+            .get(self.regions.len())
+            .copied()
+            .unwrap_or_default()
+    }
+
+    fn conflict(&mut self, kind: ConflictKind, chosen: MergeSide, span: Span, texts: [String; 3]) {
+        self.regions.push(Region {
+            kind,
+            chosen,
+            span,
+            texts,
+        });
+    }
+
+    /// The version `picked`, or when there is none, the one chosen in a
+    /// format conflict over `span`.
+    fn settle(
+        &mut self,
+        picked: Option<MergeSide>,
+        span: Span,
+        texts: impl FnOnce() -> [String; 3],
+    ) -> MergeSide {
+        picked.unwrap_or_else(|| {
+            let chosen = self.choice();
+            self.conflict(ConflictKind::Format, chosen, span, texts());
+            chosen
+        })
+    }
+
+    /// Merge the base items `a` with ours `b` and theirs `c`, putting
+    /// their blocks in `cell`.
+    fn items(&mut self, a: &[Item], b: &[Item], c: &[Item], cell: Option<DocCell>) -> Vec<Item> {
+        let ours = Lineup::new(&align_items(self.base, a, self.ours, b), a.len());
+        let theirs = Lineup::new(&align_items(self.base, a, self.theirs, c), a.len());
+        let mut items = Vec::new();
+        for gap in 0..=a.len() {
+            let (p, q) = (&ours.inserted[gap], &theirs.inserted[gap]);
+            let p: Vec<&Item> = p.iter().map(|&j| &b[j]).collect();
+            let q: Vec<&Item> = q.iter().map(|&k| &c[k]).collect();
+            if q.is_empty() || self.same_items(self.ours, &p, self.theirs, &q) {
+                self.take(MergeSide::Ours, &p, cell, &mut items);
+            } else if p.is_empty() {
+                self.take(MergeSide::Theirs, &q, cell, &mut items);
+            } else {
+                let texts = [
+                    String::new(),
+                    self.text(self.ours, &p),
+                    self.text(self.theirs, &q),
+                ];
+                let chosen = self.choice();
+                let first = self.blocks.len();
+                let taken = if chosen == MergeSide::Ours { &p } else { &q };
+                self.take(chosen, taken, cell, &mut items);
+                let end = self.blocks.len();
+                self.conflict(
+                    ConflictKind::Text,
+                    chosen,
+                    Span::Blocks { first, end },
+                    texts,
+                );
+            }
+
+            let Some(x) = a.get(gap) else {
+                break;
+            };
+            match (ours.kept[gap], theirs.kept[gap]) {
+                (Some(j), Some(k)) => items.push(self.pair(x, &b[j], &c[k], cell)),
+                (None, None) => {}
+                (Some(j), None) => self.removed(x, MergeSide::Ours, &b[j], cell, &mut items),
+                (None, Some(k)) => self.removed(x, MergeSide::Theirs, &c[k], cell, &mut items),
+            }
+        }
+        items
+    }
+
+    /// Base item `x` kept as `kept` by `side` and removed by the other:
+    /// removed, unless `side` changed it.
+    fn removed(
+        &mut self,
+        x: &Item,
+        side: MergeSide,
+        kept: &Item,
+        cell: Option<DocCell>,
+        items: &mut Vec<Item>,
+    ) {
+        let version = self.version(side);
+        if self.same_items(self.base, &[x], version, &[kept]) {
+            return;
+        }
+        let (base, kept_text) = (self.text(self.base, &[x]), self.text(version, &[kept]));
+        let texts = match side {
+            MergeSide::Ours => [base, kept_text, String::new()],
+            MergeSide::Theirs => [base, String::new(), kept_text],
+        };
+        let chosen = self.choice();
+        let first = self.blocks.len();
+        if chosen == side {
+            self.take(side, &[kept], cell, items);
+        }
+        let end = self.blocks.len();
+        self.conflict(
+            ConflictKind::Text,
+            chosen,
+            Span::Blocks { first, end },
+            texts,
+        );
+    }
+
+    fn pair(&mut self, x: &Item, y: &Item, z: &Item, cell: Option<DocCell>) -> Item {
+        match (x, y, z) {
+            (Item::Block(x), Item::Block(y), Item::Block(z)) => {
+                Item::Block(self.block(*x, *y, *z, cell))
+            }
+            (Item::Table(x), Item::Table(y), Item::Table(z)) => self.table(x, y, z),
+            _ => unreachable!("only items of one kind are paired"),
+        }
+    }
+
+    /// Merge base block `x` with ours `y` and theirs `z`.
+    fn block(&mut self, x: usize, y: usize, z: usize, cell: Option<DocCell>) -> usize {
+        let (base, ours, theirs) = (self.base, self.ours, self.theirs);
+        let (x, y, z) = (&base.blocks[x], &ours.blocks[y], &theirs.blocks[z]);
+        let index = self.blocks.len();
+        let ours = align::diff_by(&x.units, &y.units, UnitContent::same_content);
+        let theirs = align::diff_by(&x.units, &z.units, UnitContent::same_content);
+        let (to_y, to_z) = (kept_units(&ours, x), kept_units(&theirs, x));
+
+        let mut units = Vec::new();
+        let (mut i, mut j, mut at) = (0, 0, 0);
+        while let Some(start) = match (ours.get(i), theirs.get(j)) {
+            (Some(h), Some(k)) => Some(h.a.min(k.a)),
+            (h, k) => h.or(k).map(|h| h.a),
+        } {
+            // Hunks of either side overlapping the ones taken so far, or
+            // inserting where they start or end, edit the same text.
+            let (from_i, from_j) = (i, j);
+            let (mut start, mut end) = (start, start);
+            loop {
+                let touches =
+                    |h: &Hunk| h.a < end || (h.a == end && (h.removed == 0 || start == end));
+                if let Some(h) = ours.get(i).filter(|h| touches(h)) {
+                    end = end.max(h.a + h.removed);
+                    i += 1;
+                    continue;
+                } else if let Some(h) = theirs.get(j).filter(|h| touches(h)) {
+                    end = end.max(h.a + h.removed);
+                    j += 1;
+                    continue;
+                }
+                if i == from_i || j == from_j {
+                    break;
+                }
+                // Both versions rewrote this text: conflict on whole words
+                // rather than around the letters they happen to share.
+                let (from, to) = (start, end);
+                let units = &x.units;
+                while start > at
+                    && start < end
+                    && in_word(&units[start - 1])
+                    && in_word(&units[start])
+                {
+                    start -= 1;
+                }
+                while end > start
+                    && end < units.len()
+                    && in_word(&units[end - 1])
+                    && in_word(&units[end])
+                {
+                    end += 1;
+                }
+                if (start, end) == (from, to) {
+                    break;
+                }
+            }
+            self.unchanged(index, at..start, [x, y, z], [&to_y, &to_z], &mut units);
+            let ours_run = replay(y, &to_y, &ours[from_i..i], start..end);
+            let theirs_run = replay(z, &to_z, &theirs[from_j..j], start..end);
+            if j == from_j || ours_run == theirs_run {
+                units.extend(ours_run);
+            } else if i == from_i {
+                units.extend(theirs_run);
+            } else {
+                let chosen = self.choice();
+                let texts = [
+                    text_of(&x.units[start..end]),
+                    text_of(&ours_run),
+                    text_of(&theirs_run),
+                ];
+                let first = units.len();
+                units.extend(match chosen {
+                    MergeSide::Ours => ours_run,
+                    MergeSide::Theirs => theirs_run,
+                });
+                let span = Span::Units {
+                    block: index,
+                    start: first,
+                    end: units.len(),
+                };
+                self.conflict(ConflictKind::Text, chosen, span, texts);
+            }
+            at = end;
+        }
+        self.unchanged(
+            index,
+            at..x.units.len(),
+            [x, y, z],
+            [&to_y, &to_z],
+            &mut units,
+        );
+
+        let whole = |len| Span::Units {
+            block: index,
+            start: 0,
+            end: len,
+        };
+        let texts = || [x, y, z].map(|b| text_of(&b.units));
+        let props = pick(block_props(x), block_props(y), block_props(z));
+        let props = self.settle(props, whole(units.len()), texts);
+        let list = |b: &DocBlock| b.list.as_ref().map(list_format);
+        let list = self.settle(pick(list(x), list(y), list(z)), whole(units.len()), texts);
+        if let (Some(ours), Some(theirs)) = (&y.list, &z.list) {
+            self.lists.entry(theirs.id).or_insert(ours.id);
+        }
+
+        let mut block = match props {
+            MergeSide::Ours => y.clone(),
+            MergeSide::Theirs => z.clone(),
+        };
+        block.units = units;
+        block.list = match list {
+            MergeSide::Ours => y.list.clone().map(|l| tagged(l, MergeSide::Ours)),
+            MergeSide::Theirs => z.list.clone().map(|l| tagged(l, MergeSide::Theirs)),
+        };
+        block.cell = cell;
+        self.blocks.push(block);
+        index
+    }
+
+    /// Base units in `range`, unchanged in content on both sides, with
+    /// the character formats merged one by one.
+    fn unchanged(
+        &mut self,
+        index: usize,
+        range: std::ops::Range<usize>,
+        [x, y, z]: [&'a DocBlock; 3],
+        [to_y, to_z]: [&[Option<usize>]; 2],
+        units: &mut Vec<UnitContent>,
+    ) {
+        let unit = |block: &'a DocBlock, to: &[Option<usize>], i: usize| -> &'a UnitContent {
+            &block.units[to[i].expect("unchanged units are kept")]
+        };
+        let mut i = range.start;
+        while i < range.end {
+            let format = |i: usize| {
+                let (u, v, w) = (&x.units[i], unit(y, to_y, i), unit(z, to_z, i));
+                pick(u.format(), v.format(), w.format())
+            };
+            if let Some(side) = format(i) {
+                units.push(match side {
+                    MergeSide::Ours => unit(y, to_y, i).clone(),
+                    MergeSide::Theirs => unit(z, to_z, i).clone(),
+                });
+                i += 1;
+                continue;
+            }
+            let mut end = i + 1;
+            while end < range.end && format(end).is_none() {
+                end += 1;
+            }
+            let chosen = self.choice();
+            let first = units.len();
+            units.extend((i..end).map(|k| match chosen {
+                MergeSide::Ours => unit(y, to_y, k).clone(),
+                MergeSide::Theirs => unit(z, to_z, k).clone(),
+            }));
+            let text = text_of(&x.units[i..end]);
+            let span = Span::Units {
+                block: index,
+                start: first,
+                end: units.len(),
+            };
+            let texts = [text.clone(), text.clone(), text];
+            self.conflict(ConflictKind::Format, chosen, span, texts);
+            i = end;
+        }
+    }
+
+    /// Merge base table `x` with ours `y` and theirs `z`. The table takes
+    /// the shape of the version that changed it, merged cells included,
+    /// and cells found in all three are merged.
+    fn table(&mut self, x: &Table, y: &Table, z: &Table) -> Item {
+        let [a, b, c] = [x, y, z].map(|t| Item::Table(t.clone()));
+        if self.same_items(self.base, &[&a], self.ours, &[&b]) {
+            return Item::Table(self.take_table(MergeSide::Theirs, z));
+        } else if self.same_items(self.base, &[&a], self.theirs, &[&c]) {
+            return Item::Table(self.take_table(MergeSide::Ours, y));
+        }
+
+        let (ours_rows, ours_columns) = align_table(self.base, x, self.ours, y);
+        let (theirs_rows, theirs_columns) = align_table(self.base, x, self.theirs, z);
+        let reshaped = |t: &Table, rows: &[Step], columns: &[Step]| {
+            (t.rows, t.columns) != (x.rows, x.columns)
+                || t.spans != x.spans
+                || rows
+                    .iter()
+                    .chain(columns)
+                    .any(|step| !matches!(*step, Step::Pair(i, j) if i == j))
+        };
+        let by_ours = reshaped(y, &ours_rows, &ours_columns);
+        let by_theirs = reshaped(z, &theirs_rows, &theirs_columns);
+        if by_ours
+            && by_theirs
+            && (&ours_rows, &ours_columns, &y.spans) != (&theirs_rows, &theirs_columns, &z.spans)
+        {
+            let chosen = self.choice();
+            let texts = [
+                self.text(self.base, &[&a]),
+                self.text(self.ours, &[&b]),
+                self.text(self.theirs, &[&c]),
+            ];
+            let first = self.blocks.len();
+            let table = self.take_table(chosen, if chosen == MergeSide::Ours { y } else { z });
+            let end = self.blocks.len();
+            self.conflict(
+                ConflictKind::Table,
+                chosen,
+                Span::Blocks { first, end },
+                texts,
+            );
+            return Item::Table(table);
+        }
+
+        let (side, shape, rows, columns) = if by_theirs && !by_ours {
+            (MergeSide::Theirs, z, &theirs_rows, &theirs_columns)
+        } else {
+            (MergeSide::Ours, y, &ours_rows, &ours_columns)
+        };
+        let (base_rows, base_columns) = (old_of(rows, shape.rows), old_of(columns, shape.columns));
+        let (ours_rows, ours_columns) =
+            (new_of(&ours_rows, x.rows), new_of(&ours_columns, x.columns));
+        let (theirs_rows, theirs_columns) = (
+            new_of(&theirs_rows, x.rows),
+            new_of(&theirs_columns, x.columns),
+        );
+        let mut cells = Vec::new();
+        for (s, base_row) in base_rows.iter().enumerate() {
+            for (d, base_column) in base_columns.iter().enumerate() {
+                // A merged cell keeps its own blocks only.
+                if shape.covered(s, d) {
+                    cells.push(Vec::new());
+                    continue;
+                }
+                let cell = Some(cell_of(shape, s, d));
+                let found = base_row.zip(*base_column).and_then(|(r, c)| {
+                    Some((
+                        (r, c),
+                        ours_rows[r].zip(ours_columns[c])?,
+                        theirs_rows[r].zip(theirs_columns[c])?,
+                    ))
+                });
+                let blocks = match found {
+                    Some(((r, c), (r1, c1), (r2, c2))) => {
+                        let a = self.base.block_items(x.cell(r, c));
+                        let b = self.ours.block_items(y.cell(r1, c1));
+                        let c = self.theirs.block_items(z.cell(r2, c2));
+                        self.items(&a, &b, &c, cell)
+                            .into_iter()
+                            .map(|item| match item {
+                                Item::Block(i) => i,
+                                Item::Table(_) => unreachable!("table cells hold blocks"),
+                            })
+                            .collect()
+                    }
+                    None => {
+                        let version = self.version(side);
+                        shape
+                            .cell(s, d)
+                            .iter()
+                            .map(|&i| self.push(side, &version.blocks[i], cell))
+                            .collect()
+                    }
+                };
+                cells.push(blocks);
+            }
+        }
+        Item::Table(Table {
+            id: shape.id,
+            rows: shape.rows,
+            columns: shape.columns,
+            cells,
+            spans: shape.spans.clone(),
+        })
+    }
+
+    /// Append `side`'s items as they are.
+    fn take(
+        &mut self,
+        side: MergeSide,
+        items: &[&Item],
+        cell: Option<DocCell>,
+        out: &mut Vec<Item>,
+    ) {
+        let version = self.version(side);
+        for item in items {
+            out.push(match item {
+                Item::Block(i) => Item::Block(self.push(side, &version.blocks[*i], cell)),
+                Item::Table(table) => Item::Table(self.take_table(side, table)),
+            });
+        }
+    }
+
+    fn take_table(&mut self, side: MergeSide, table: &Table) -> Table {
+        let version = self.version(side);
+        let mut cells = Vec::with_capacity(table.cells.len());
+        for (n, blocks) in table.cells.iter().enumerate() {
+            let cell = Some(cell_of(table, n / table.columns, n % table.columns));
+            cells.push(
+                blocks
+                    .iter()
+                    .map(|&i| self.push(side, &version.blocks[i], cell))
+                    .collect(),
+            );
+        }
+        Table {
+            cells,
+            ..table.clone()
+        }
+    }
+
+    fn push(&mut self, side: MergeSide, block: &DocBlock, cell: Option<DocCell>) -> usize {
+        let mut block = block.clone();
+        block.list = block.list.map(|l| tagged(l, side));
+        block.cell = cell;
+        self.blocks.push(block);
+        self.blocks.len() - 1
+    }
+
+    /// Whether the items hold the same content and formats.
+    fn same_items(&self, p_side: &Side, p: &[&Item], q_side: &Side, q: &[&Item]) -> bool {
+        let same = |x: usize, y: usize| {
+            let (x, y) = (&p_side.blocks[x], &q_side.blocks[y]);
+            x.units == y.units
+                && block_props(x) == block_props(y)
+                && x.list.as_ref().map(list_format) == y.list.as_ref().map(list_format)
+        };
+        p.len() == q.len()
+            && p.iter().zip(q).all(|(x, y)| match (x, y) {
+                (Item::Block(x), Item::Block(y)) => same(*x, *y),
+                (Item::Table(x), Item::Table(y)) => {
+                    (x.rows, x.columns) == (y.rows, y.columns)
+                        && x.spans == y.spans
+                        && x.cells.iter().zip(&y.cells).all(|(p, q)| {
+                            p.len() == q.len() && p.iter().zip(q).all(|(x, y)| same(*x, *y))
+                        })
+                }
+                _ => false,
+            })
+    }
+
+    fn text(&self, side: &Side, items: &[&Item]) -> String {
+        let mut lines = Vec::new();
+        for item in items {
+            match item {
+                Item::Block(i) => lines.push(text_of(&side.blocks[*i].units)),
+                Item::Table(table) => lines.extend(
+                    table
+                        .cells
+                        .iter()
+                        .flatten()
+                        .map(|&i| text_of(&side.blocks[i].units)),
+                ),
+            }
+        }
+        lines.join("\n")
+    }
+
+    /// The merged content, with positions and lists settled, and the
+    /// conflicts placed in it.
+    fn finish(mut self, items: Vec<Item>) -> (Side, Vec<MergeConflict>) {
+        let mut position = 0;
+        for block in &mut self.blocks {
+            block.position = position;
+            position += block.units.len() + 1;
+            if let Some(list) = &mut block.list
+                && list.id % 2 == 1
+                && let Some(ours) = self.lists.get(&(list.id / 2))
+            {
+                list.id = ours * 2;
+            }
+        }
+        let blocks = &self.blocks;
+        let end_of = |block: &DocBlock| block.position + block.units.len();
+        let conflicts = self
+            .regions
+            .into_iter()
+            .map(|region| {
+                let (start, end) = match region.span {
+                    Span::Blocks { first, end } if first < end => {
+                        (blocks[first].position, end_of(&blocks[end - 1]))
+                    }
+                    Span::Blocks { first, .. } => {
+                        let at = blocks
+                            .get(first)
+                            .map(|b| b.position)
+                            .or_else(|| blocks.last().map(end_of))
+                            .unwrap_or(0);
+                        (at, at)
+                    }
+                    Span::Units { block, start, end } => {
+                        let at = blocks[block].position;
+                        (at + start, at + end)
+                    }
+                };
+                let [base, ours, theirs] = region.texts;
+                MergeConflict {
+                    kind: region.kind,
+                    start,
+                    end,
+                    chosen: region.chosen,
+                    base,
+                    ours,
+                    theirs,
+                }
+            })
+            .collect();
+        (
+            Side {
+                blocks: self.blocks,
+                items,
+            },
+            conflicts,
+        )
+    }
+}
+
+/// Which version's value to keep: the one that changed it, `None` when
+/// both did, differently.
+fn pick<T: PartialEq>(base: T, ours: T, theirs: T) -> Option<MergeSide> {
+    if theirs == base || ours == theirs {
+        Some(MergeSide::Ours)
+    } else if ours == base {
+        Some(MergeSide::Theirs)
+    } else {
+        None
+    }
+}
+
+fn in_word(unit: &UnitContent) -> bool {
+    matches!(unit, UnitContent::Char { ch, .. } if ch.is_alphanumeric())
+}
+
+/// Where each unit of `x` outside `hunks` is in the other block.
+fn kept_units(hunks: &[Hunk], x: &DocBlock) -> Vec<Option<usize>> {
+    let mut kept = vec![None; x.units.len()];
+    for (i, j) in align::unchanged(hunks, x.units.len()) {
+        kept[i] = Some(j);
+    }
+    kept
+}
+
+/// The base units in `range` as the version `y` has them, given its
+/// hunks there and where it kept the others.
+fn replay(
+    y: &DocBlock,
+    to_y: &[Option<usize>],
+    hunks: &[Hunk],
+    range: std::ops::Range<usize>,
+) -> Vec<UnitContent> {
+    let kept = |i: usize| y.units[to_y[i].expect("units outside hunks are kept")].clone();
+    let mut units = Vec::new();
+    let mut at = range.start;
+    for hunk in hunks {
+        units.extend((at..hunk.a).map(kept));
+        units.extend_from_slice(&y.units[hunk.b..hunk.b + hunk.added]);
+        at = hunk.a + hunk.removed;
+    }
+    units.extend((at..range.end).map(kept));
+    units
+}
+
+/// The old row or column paired with each of the `len` new ones.
+fn old_of(steps: &[Step], len: usize) -> Vec<Option<usize>> {
+    let mut old = vec![None; len];
+    for step in steps {
+        if let Step::Pair(i, j) = *step {
+            old[j] = Some(i);
+        }
+    }
+    old
+}
+
+fn cell_of(table: &Table, row: usize, column: usize) -> DocCell {
+    DocCell {
+        table: table.id,
+        rows: table.rows,
+        columns: table.columns,
+        row,
+        column,
+        row_span: table.span(row, column).0,
+        column_span: table.span(row, column).1,
+    }
+}
+
+/// Keep the lists of the two versions apart: ours get even ids, theirs
+/// odd ones until [`Merger::finish`] maps them to ours.
+fn tagged(
+    mut list: frontend::list::dtos::ListDto,
+    side: MergeSide,
+) -> frontend::list::dtos::ListDto {
+    list.id = list.id * 2 + EntityId::from(side == MergeSide::Theirs);
+    list
+}
@@ -6,6 +6,7 @@ use std::time::Duration;
 use anyhow::Result;
 
 use frontend::AppContext;
+use frontend::common::long_operation::OperationStatus;
 
 /// Function that polls the long-operation manager for a result.
 type ResultFn<T> = Box<dyn Fn(&AppContext, &str) -> Option<Result<T>> + Send>;
@@ -21,7 +22,7 @@ impl OperationState {
     }
 }
 
-/// A handle to a running long operation (Markdown/HTML import, DOCX export).
+/// A handle to a running long operation (Markdown/HTML/DOCX import, DOCX export).
 ///
 /// Provides typed access to progress, cancellation, and the result.
 /// Progress events are also emitted via [`DocumentEvent::LongOperationProgress`](crate::DocumentEvent::LongOperationProgress)
@@ -72,7 +73,29 @@ impl<T> Operation<T> {
 
     /// Returns `true` if the operation has finished (success or failure).
     pub fn is_done(&self) -> bool {
-        (self.result_fn)(&self.state.ctx, &self.id).is_some()
+        self.poll().is_some()
+    }
+
+    /// The typed result once finished. A failed or cancelled operation
+    /// stores no result, so its final status is turned into an error.
+    fn poll(&self) -> Option<Result<T>> {
+        if let Some(result) = (self.result_fn)(&self.state.ctx, &self.id) {
+            return Some(result);
+        }
+        let status = {
+            let mgr = match self.state.ctx.long_operation_manager.lock() {
+                Ok(g) => g,
+                Err(e) => e.into_inner(),
+            };
+            mgr.get_operation_status(&self.id)
+        };
+        match status {
+            Some(OperationStatus::Failed(err)) => Some(Err(anyhow::anyhow!(err))),
+            Some(OperationStatus::Cancelled) => {
+                Some(Err(anyhow::anyhow!("Operation was cancelled")))
+            }
+            _ => None,
+        }
     }
 
     /// Cancel the operation. No-op if already finished.
@@ -86,7 +109,7 @@ impl<T> Operation<T> {
     /// the typed result. Consumes the handle.
     pub fn wait(self) -> Result<T> {
         loop {
-            if let Some(result) = (self.result_fn)(&self.state.ctx, &self.id) {
+            if let Some(result) = self.poll() {
                 return result;
             }
             thread::sleep(Duration::from_millis(50));
@@ -98,7 +121,7 @@ impl<T> Operation<T> {
     pub fn wait_timeout(self, timeout: Duration) -> Option<Result<T>> {
         let deadline = std::time::Instant::now() + timeout;
         loop {
-            if let Some(result) = (self.result_fn)(&self.state.ctx, &self.id) {
+            if let Some(result) = self.poll() {
                 return Some(result);
             }
             if std::time::Instant::now() >= deadline {
@@ -112,7 +135,7 @@ impl<T> Operation<T> {
     /// Non-blocking: returns the result if the operation has completed,
     /// `None` if still running. Can be called repeatedly.
     pub fn try_result(&mut self) -> Option<Result<T>> {
-        (self.result_fn)(&self.state.ctx, &self.id)
+        self.poll()
     }
 }
 
@@ -130,9 +153,41 @@ pub struct HtmlImportResult {
     pub block_count: usize,
 }
 
+/// Result of a DOCX import (`set_docx`).
+#[derive(Debug, Clone)]
+pub struct DocxImportResult {
+    pub block_count: usize,
+}
+
 /// Result of a DOCX export (`to_docx`).
 #[derive(Debug, Clone)]
 pub struct DocxExportResult {
     pub file_path: String,
     pub paragraph_count: usize,
 }
+
+/// Result of an ODT import (`set_odt`).
+#[derive(Debug, Clone)]
+pub struct OdtImportResult {
+    pub block_count: usize,
+}
+
+/// Result of an ODT export (`to_odt`).
+#[derive(Debug, Clone)]
+pub struct OdtExportResult {
+    pub file_path: String,
+    pub paragraph_count: usize,
+}
+
+/// Result of an RTF import (`set_rtf`).
+#[derive(Debug, Clone)]
+pub struct RtfImportResult {
+    pub block_count: usize,
+}
+
+/// Result of an EPUB export (`to_epub`).
+#[derive(Debug, Clone)]
+pub struct EpubExportResult {
+    pub file_path: String,
+    pub chapter_count: usize,
+}
@@ -0,0 +1,87 @@
+//! Lazy iteration over detailed search hits.
+
+use frontend::commands::document_search_commands;
+use frontend::document_search::{FindAllDto, FindAllSession};
+
+use crate::convert::{self, to_i64};
+use crate::{FindOptions, Result, SearchHit, TextDocument};
+
+/// Iterator over the [`SearchHit`]s of a search, created by
+/// [`TextDocument::find_iter`]. Hits are fetched a page at a time, so
+/// stopping early on a large document skips most of the work. The
+/// searched text and the place the scan reached are kept between pages
+/// and only built again after the document is edited.
+pub struct SearchHits {
+    doc: TextDocument,
+    dto: FindAllDto,
+    /// The open search, with the document version it was built from.
+    session: Option<(FindAllSession, u64)>,
+    page: std::vec::IntoIter<SearchHit>,
+    done: bool,
+}
+
+impl SearchHits {
+    pub(crate) fn new(
+        doc: TextDocument,
+        query: &str,
+        options: &FindOptions,
+        page_size: usize,
+    ) -> Self {
+        let mut dto = options.to_find_all_dto(query);
+        dto.with_details = true;
+        dto.start_position = Some(0);
+        dto.max_results = Some(to_i64(page_size.max(1)));
+        SearchHits {
+            doc,
+            dto,
+            session: None,
+            page: Vec::new().into_iter(),
+            done: false,
+        }
+    }
+
+    fn fetch_page(&mut self) -> Result<()> {
+        let result = {
+            let inner = self.doc.inner.lock();
+            let version = inner.content_version;
+            // Fuzzy matches were all ranked when the search started, so
+            // its later pages come from that ranking.
+            let fuzzy = self.dto.max_edits.is_some();
+            let session = match &mut self.session {
+                Some((session, built)) if *built == version || fuzzy => session,
+                stale => {
+                    let session = document_search_commands::start_find_all(&inner.ctx, &self.dto)?;
+                    &mut stale.insert((session, version)).0
+                }
+            };
+            session.next_page()
+        };
+        let hits = convert::find_all_to_hits(&result);
+        self.done = !result.has_more;
+        // Literal matches may overlap, so resume right after the last
+        // match's start rather than after its end.
+        if let Some(last) = hits.iter().map(|hit| hit.position).max() {
+            self.dto.start_position = Some(to_i64(last + 1));
+        }
+        self.page = hits.into_iter();
+        Ok(())
+    }
+}
+
+impl Iterator for SearchHits {
+    type Item = Result<SearchHit>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(hit) = self.page.next() {
+            return Some(Ok(hit));
+        }
+        if self.done {
+            return None;
+        }
+        if let Err(e) = self.fetch_page() {
+            self.done = true;
+            return Some(Err(e));
+        }
+        self.page.next().map(Ok)
+    }
+}
@@ -0,0 +1,390 @@
+//! Incremental search index.
+//!
+//! An optional per-block trigram filter that lets literal and
+//! whole-word searches skip the blocks that cannot contain the query,
+//! instead of rebuilding the whole document text for every search.
+//! Each block keeps a small Bloom filter of the trigrams of its
+//! lowercased text; a search probes every filter (a few bit tests per
+//! block) and reads from the rope only the blocks whose filter may hold
+//! all trigrams of the query. Filters are refreshed for the blocks an
+//! edit touched when its `ContentsChanged` event is queued, and rebuilt
+//! lazily after a reset.
+//!
+//! The index only serves searches whose positions it can derive from
+//! the rope: flat documents (no tables, no unmirrored sub-frames, a
+//! single top-level frame). Anything else falls back to the regular
+//! search commands.
+
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+
+use frontend::common::database::Store;
+use frontend::common::database::block_offset_index::OffsetMarker;
+use frontend::common::database::rope_helpers::rope_positions_match_flow;
+use frontend::common::types::EntityId;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{DocumentEvent, FindMatch, FindOptions, SearchNormalization, SearchScope};
+
+/// Filter bits per trigram of a block. With two probes this keeps the
+/// false-positive rate of a single trigram under 5%.
+const BITS_PER_TRIGRAM: usize = 8;
+
+pub(crate) struct SearchIndex {
+    filters: HashMap<EntityId, TrigramFilter>,
+    /// Set when the filters no longer describe the document (reset,
+    /// edits of unknown extent, tables) and must be rebuilt before use.
+    stale: bool,
+}
+
+/// A candidate block: its char position in the document and content.
+struct IndexedBlock {
+    position: usize,
+    text: String,
+}
+
+impl SearchIndex {
+    pub fn new() -> Self {
+        SearchIndex {
+            filters: HashMap::new(),
+            stale: true,
+        }
+    }
+
+    /// Bring the filters up to date with a queued document event.
+    pub fn update(&mut self, event: &DocumentEvent, store: &Store) {
+        match event {
+            DocumentEvent::DocumentReset => self.invalidate(),
+            // Scattered edits (replace all, inline objects) don't say
+            // where the text changed.
+            DocumentEvent::ContentsChanged {
+                chars_removed: 0,
+                chars_added: 0,
+                ..
+            } => self.invalidate(),
+            DocumentEvent::ContentsChanged {
+                position,
+                chars_added,
+                ..
+            } => {
+                if self.stale {
+                    return;
+                }
+                if !rope_positions_match_flow(store) {
+                    self.invalidate();
+                    return;
+                }
+                self.refresh_range(store, *position, position + chars_added);
+            }
+            _ => {}
+        }
+    }
+
+    fn invalidate(&mut self) {
+        self.filters.clear();
+        self.stale = true;
+    }
+
+    /// Re-index the blocks overlapping chars `start..=end`, the text an
+    /// edit left behind, and forget the blocks it removed.
+    fn refresh_range(&mut self, store: &Store, start: usize, end: usize) {
+        let (entries, total_bytes) = {
+            let offsets = store.block_offsets.read().unwrap();
+            (offsets.entries.clone(), offsets.total_bytes())
+        };
+        let rope = store.rope.read().unwrap();
+        let byte = rope.char_to_byte(start.min(rope.len_chars())) as u32;
+        let first = entries
+            .partition_point(|&(_, bs)| bs <= byte)
+            .saturating_sub(1);
+        for (i, &(marker, byte_start)) in entries.iter().enumerate().skip(first) {
+            if rope.byte_to_char(byte_start as usize) > end {
+                break;
+            }
+            if let OffsetMarker::Block(id) = marker {
+                let text = rope
+                    .byte_slice(content_range(&entries, total_bytes, i))
+                    .to_string();
+                self.filters.insert(id, TrigramFilter::new(&text));
+            }
+        }
+        drop(rope);
+
+        if self.filters.len() > entries.len() {
+            let live: HashSet<EntityId> =
+                entries.iter().filter_map(|(m, _)| m.as_block()).collect();
+            self.filters.retain(|id, _| live.contains(id));
+        }
+    }
+
+    /// Whether a search with these options can be answered from the
+    /// index, whatever the document holds.
+    pub fn supports(query: &str, options: &FindOptions) -> bool {
+        !options.use_regex
+            && options.fuzzy.is_none()
+            && options.normalization == SearchNormalization::NoNormalization
+            && !options.fold_accents
+            && !options.fold_width
+            && matches!(options.scope, None | Some(SearchScope::Range { .. }))
+            && !query.contains('\n')
+            && folded_chars(query).nth(2).is_some()
+    }
+
+    /// All matches of `query`, or `None` when the document layout
+    /// doesn't allow an indexed search. `top_frame_count` is the
+    /// document's number of top-level frames.
+    pub fn find_all(
+        &mut self,
+        store: &Store,
+        top_frame_count: usize,
+        query: &str,
+        options: &FindOptions,
+    ) -> Option<Vec<FindMatch>> {
+        let mut matches = Vec::new();
+        self.search(store, top_frame_count, query, 0, options, |block| {
+            matches.extend(block_matches(block, query, options));
+            false
+        })?;
+        Some(matches)
+    }
+
+    /// The first match at or after `from`, or the last one before it
+    /// when searching backward. `None` as for [`find_all`](Self::find_all).
+    pub fn find(
+        &mut self,
+        store: &Store,
+        top_frame_count: usize,
+        query: &str,
+        from: usize,
+        options: &FindOptions,
+    ) -> Option<Option<FindMatch>> {
+        if options.search_backward {
+            // Blocks come in document order, so the last match before
+            // `from` is in the last block holding one.
+            let mut found = None;
+            self.search(store, top_frame_count, query, 0, options, |block| {
+                if block.position >= from {
+                    return true;
+                }
+                if let Some(m) = block_matches(block, query, options)
+                    .filter(|m| m.position < from)
+                    .last()
+                {
+                    found = Some(m);
+                }
+                false
+            })?;
+            return Some(found);
+        }
+        let mut found = None;
+        self.search(store, top_frame_count, query, from, options, |block| {
+            found = block_matches(block, query, options).find(|m| m.position >= from);
+            found.is_some()
+        })?;
+        Some(found)
+    }
+
+    /// Feed the candidate blocks for `query`, in document order from the
+    /// one holding char `from`, to `visit` until it returns `true`.
+    fn search(
+        &mut self,
+        store: &Store,
+        top_frame_count: usize,
+        query: &str,
+        from: usize,
+        options: &FindOptions,
+        mut visit: impl FnMut(&IndexedBlock) -> bool,
+    ) -> Option<()> {
+        if top_frame_count != 1 || !rope_positions_match_flow(store) {
+            self.invalidate();
+            return None;
+        }
+        let (entries, total_bytes) = {
+            let offsets = store.block_offsets.read().unwrap();
+            (offsets.entries.clone(), offsets.total_bytes())
+        };
+        let rope = store.rope.read().unwrap();
+        if self.stale {
+            self.filters.clear();
+            for (i, &(marker, _)) in entries.iter().enumerate() {
+                if let OffsetMarker::Block(id) = marker {
+                    let text = rope
+                        .byte_slice(content_range(&entries, total_bytes, i))
+                        .to_string();
+                    self.filters.insert(id, TrigramFilter::new(&text));
+                }
+            }
+            self.stale = false;
+        }
+
+        let hashes = trigram_hashes(query);
+        let from = match options.scope {
+            Some(SearchScope::Range { start, .. }) => from.max(start),
+            _ => from,
+        };
+        let byte = rope.char_to_byte(from.min(rope.len_chars())) as u32;
+        let first = entries
+            .partition_point(|&(_, bs)| bs <= byte)
+            .saturating_sub(1);
+        for (i, &(marker, byte_start)) in entries.iter().enumerate().skip(first) {
+            let OffsetMarker::Block(id) = marker else {
+                continue;
+            };
+            if self
+                .filters
+                .get(&id)
+                .is_some_and(|f| !f.may_contain(&hashes))
+            {
+                continue;
+            }
+            let position = rope.byte_to_char(byte_start as usize);
+            if let Some(SearchScope::Range { end, .. }) = options.scope
+                && position > end
+            {
+                break;
+            }
+            let text = rope
+                .byte_slice(content_range(&entries, total_bytes, i))
+                .to_string();
+            // A block created without an event is indexed on first sight.
+            self.filters
+                .entry(id)
+                .or_insert_with(|| TrigramFilter::new(&text));
+            if visit(&IndexedBlock { position, text }) {
+                break;
+            }
+        }
+        Some(())
+    }
+}
+
+/// Byte range of the content of the block at `entries[index]`, without
+/// its trailing boundary `\n`.
+fn content_range(
+    entries: &[(OffsetMarker, u32)],
+    total_bytes: u32,
+    index: usize,
+) -> std::ops::Range<usize> {
+    let start = entries[index].1 as usize;
+    let end = match entries.get(index + 1) {
+        Some(&(_, next)) if next as usize > start => next as usize - 1,
+        Some(_) => start,
+        None => total_bytes as usize,
+    };
+    start..end
+}
+
+/// The matches of `query` in `block`, with document positions. Mirrors
+/// the literal scan of the search commands: case-insensitive searches
+/// compare lowercased text, matches may overlap, and whole words are
+/// delimited by Unicode word boundaries.
+fn block_matches<'a>(
+    block: &'a IndexedBlock,
+    query: &str,
+    options: &'a FindOptions,
+) -> impl Iterator<Item = FindMatch> + 'a {
+    let (text, query): (Cow<str>, String) = if options.case_sensitive {
+        (Cow::Borrowed(&block.text), query.to_string())
+    } else {
+        (Cow::Owned(block.text.to_lowercase()), query.to_lowercase())
+    };
+    let length = query.chars().count();
+    let boundaries: Option<HashSet<usize>> = options.whole_word.then(|| {
+        let mut set = HashSet::from([0, block.text.chars().count()]);
+        let (mut byte_end, mut char_end) = (0, 0);
+        for (byte_start, word) in block.text.unicode_word_indices() {
+            let start = char_end + block.text[byte_end..byte_start].chars().count();
+            char_end = start + word.chars().count();
+            byte_end = byte_start + word.len();
+            set.insert(start);
+            set.insert(char_end);
+        }
+        set
+    });
+
+    let mut found = Vec::new();
+    let (mut byte, mut char_pos) = (0, 0);
+    while let Some(offset) = text[byte..].find(query.as_str()) {
+        char_pos += text[byte..byte + offset].chars().count();
+        byte += offset;
+        if boundaries
+            .as_ref()
+            .is_none_or(|b| b.contains(&char_pos) && b.contains(&(char_pos + length)))
+        {
+            found.push(block.position + char_pos);
+        }
+        byte += text[byte..].chars().next().map_or(1, char::len_utf8);
+        char_pos += 1;
+    }
+
+    found
+        .into_iter()
+        .map(move |position| FindMatch { position, length })
+        .filter(move |m| match options.scope {
+            Some(SearchScope::Range { start, end }) => {
+                m.position >= start && m.position + m.length <= end
+            }
+            _ => true,
+        })
+}
+
+/// The chars trigrams are taken from: lowercased one char at a time,
+/// with final sigma folded into sigma so that lowercasing a whole
+/// string, which depends on context only for that letter, still yields
+/// the same trigrams.
+fn folded_chars(text: &str) -> impl Iterator<Item = char> + '_ {
+    text.chars()
+        .flat_map(char::to_lowercase)
+        .map(|c| if c == 'ς' { 'σ' } else { c })
+}
+
+fn trigram_hashes(text: &str) -> Vec<u64> {
+    let chars: Vec<char> = folded_chars(text).collect();
+    chars.windows(3).map(trigram_hash).collect()
+}
+
+fn trigram_hash(trigram: &[char]) -> u64 {
+    // Chars fit in 21 bits; mix with the splitmix64 finalizer.
+    let mut h = trigram[0] as u64 | (trigram[1] as u64) << 21 | (trigram[2] as u64) << 42;
+    h = (h ^ (h >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    h = (h ^ (h >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    h ^ (h >> 31)
+}
+
+/// Bloom filter of the trigrams of one block.
+struct TrigramFilter {
+    bits: Vec<u64>,
+}
+
+impl TrigramFilter {
+    fn new(text: &str) -> Self {
+        let hashes = trigram_hashes(text);
+        let words = (hashes.len() * BITS_PER_TRIGRAM)
+            .div_ceil(64)
+            .next_power_of_two();
+        let mut filter = TrigramFilter {
+            bits: vec![0; words],
+        };
+        for hash in hashes {
+            for bit in filter.probes(hash) {
+                filter.bits[bit / 64] |= 1 << (bit % 64);
+            }
+        }
+        filter
+    }
+
+    fn probes(&self, hash: u64) -> [usize; 2] {
+        let mask = self.bits.len() * 64 - 1;
+        [hash as usize & mask, (hash >> 32) as usize & mask]
+    }
+
+    /// Whether the block may contain every trigram of `hashes`. Never
+    /// `false` for a block that does.
+    fn may_contain(&self, hashes: &[u64]) -> bool {
+        hashes.iter().all(|&hash| {
+            self.probes(hash)
+                .iter()
+                .all(|&bit| self.bits[bit / 64] & (1 << (bit % 64)) != 0)
+        })
+    }
+}
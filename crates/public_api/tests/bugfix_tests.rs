@@ -6,9 +6,11 @@
 //! - plain text cache invalidation
 //! - Operation::wait_timeout
 //! - join_previous_edit_block behaves as begin_edit_block
+//! - char formats after a same-block delete land on the selected text
+//! - table row/column edits keep cell positions past non-empty cells
 
 use std::sync::{Arc, Mutex};
-use text_document::{DocumentEvent, ListStyle, MoveMode, MoveOperation, TextDocument};
+use text_document::{DocumentEvent, ListStyle, MoveMode, MoveOperation, TextDocument, TextFormat};
 
 fn new_doc(text: &str) -> TextDocument {
     let doc = TextDocument::new();
@@ -298,3 +300,45 @@ fn delete_previous_char_after_crossblock_edit_keeps_invariant() {
         plain
     );
 }
+
+// ── Formatting after a same-block delete hits the right chars ───
+
+#[test]
+fn char_format_after_delete_targets_selected_text() {
+    // A delete inside one block leaves the stored positions of later
+    // blocks stale; formatting used them and bolded one char early.
+    let doc = new_doc("alpha\ngamma");
+    let cursor = doc.cursor_at(0);
+    cursor.set_position(1, MoveMode::KeepAnchor);
+    cursor.remove_selected_text().unwrap();
+
+    let cursor = doc.cursor_at(5);
+    cursor.set_position(6, MoveMode::KeepAnchor);
+    cursor
+        .set_char_format(&TextFormat {
+            font_bold: Some(true),
+            ..Default::default()
+        })
+        .unwrap();
+    assert_eq!(doc.to_plain_text().unwrap(), "lpha\ngamma");
+    let gamma = doc.block_by_number(1).unwrap();
+    assert_eq!(gamma.char_format_at(0).unwrap().font_bold, Some(true));
+    assert_ne!(gamma.char_format_at(1).unwrap().font_bold, Some(true));
+    let lpha = doc.block_by_number(0).unwrap();
+    assert_ne!(lpha.char_format_at(3).unwrap().font_bold, Some(true));
+}
+
+// ── Table structure edits count cell text in positions ──────────
+
+#[test]
+fn insert_table_row_keeps_positions_past_cell_text() {
+    let doc = new_doc("Intro");
+    let table = doc.cursor_at(5).insert_table(2, 2).unwrap();
+    doc.cursor_at(6).insert_text("abc").unwrap();
+    let before: Vec<usize> = doc.blocks().iter().map(|b| b.position()).collect();
+    assert_eq!(before, [0, 6, 10, 11, 12]);
+
+    doc.cursor().insert_table_row(table.id(), 2).unwrap();
+    let after: Vec<usize> = doc.blocks().iter().map(|b| b.position()).collect();
+    assert_eq!(after, [0, 6, 10, 11, 12, 13, 14]);
+}
@@ -0,0 +1,459 @@
+//! Tests for collaborative editing: sites exchanging operations converge,
+//! concurrent edits merge, remote edits stay off the local undo stack,
+//! session undo takes back local edits only,
+//! and random edits delivered in random order end in the same document.
+
+use proptest::prelude::*;
+use text_document::{
+    Alignment, BlockFormat, CollabOperation, CollabSession, ListStyle, MoveMode, TextDocument,
+    TextFormat,
+};
+
+fn new_site(text: &str, site: u64) -> (TextDocument, CollabSession) {
+    let doc = TextDocument::new();
+    doc.set_plain_text(text).unwrap();
+    let session = CollabSession::new(&doc, site).unwrap();
+    (doc, session)
+}
+
+/// Exchange operations until neither site has anything left to send.
+fn sync(a: &mut CollabSession, b: &mut CollabSession) {
+    for _ in 0..8 {
+        let from_a = a.local_operations().unwrap();
+        let from_b = b.local_operations().unwrap();
+        if from_a.is_empty() && from_b.is_empty() {
+            return;
+        }
+        b.apply_remote(from_a).unwrap();
+        a.apply_remote(from_b).unwrap();
+    }
+    panic!("sites did not settle");
+}
+
+fn select(doc: &TextDocument, start: usize, end: usize) -> text_document::TextCursor {
+    let cursor = doc.cursor_at(start);
+    cursor.set_position(end, MoveMode::KeepAnchor);
+    cursor
+}
+
+#[test]
+fn concurrent_typing_converges() {
+    let (a, mut site_a) = new_site("Hello\nWorld", 1);
+    let (b, mut site_b) = new_site("Hello\nWorld", 2);
+
+    a.cursor_at(5).insert_text(" there").unwrap();
+    b.cursor_at(0).insert_text("Oh, ").unwrap();
+    b.cursor_at(15).insert_text("!").unwrap();
+    sync(&mut site_a, &mut site_b);
+
+    assert_eq!(a.to_plain_text().unwrap(), "Oh, Hello there\nWorld!");
+    assert_eq!(b.to_plain_text().unwrap(), "Oh, Hello there\nWorld!");
+}
+
+#[test]
+fn concurrent_formats_on_different_fields_merge() {
+    let (a, mut site_a) = new_site("abcdef", 1);
+    let (b, mut site_b) = new_site("abcdef", 2);
+
+    select(&a, 0, 4)
+        .set_char_format(&TextFormat {
+            font_bold: Some(true),
+            ..Default::default()
+        })
+        .unwrap();
+    select(&b, 2, 6)
+        .set_char_format(&TextFormat {
+            font_italic: Some(true),
+            ..Default::default()
+        })
+        .unwrap();
+    sync(&mut site_a, &mut site_b);
+
+    assert_eq!(a.to_html().unwrap(), b.to_html().unwrap());
+    let format = a.blocks()[0].char_format_at(3).unwrap();
+    assert_eq!(format.font_bold, Some(true));
+    assert_eq!(format.font_italic, Some(true));
+}
+
+#[test]
+fn block_formats_and_lists_replicate() {
+    let (a, mut site_a) = new_site("Title\none\ntwo", 1);
+    let (b, mut site_b) = new_site("Title\none\ntwo", 2);
+
+    a.cursor_at(0)
+        .set_block_format(&BlockFormat {
+            heading_level: Some(1),
+            alignment: Some(Alignment::Center),
+            ..Default::default()
+        })
+        .unwrap();
+    select(&b, 6, 13).create_list(ListStyle::Decimal).unwrap();
+    sync(&mut site_a, &mut site_b);
+
+    assert_eq!(a.to_html().unwrap(), b.to_html().unwrap());
+    let blocks = a.blocks();
+    assert_eq!(blocks[0].block_format().heading_level, Some(1));
+    let list = blocks[1].list().expect("list on site a");
+    assert_eq!(list.style(), ListStyle::Decimal);
+    assert_eq!(blocks[2].list().map(|l| l.id()), Some(list.id()));
+
+    // Taking an item out of the list replicates too.
+    b.cursor_at(10).remove_current_block_from_list().unwrap();
+    sync(&mut site_a, &mut site_b);
+    assert!(a.blocks()[2].list().is_none());
+    assert_eq!(a.to_html().unwrap(), b.to_html().unwrap());
+}
+
+#[test]
+fn tables_replicate_with_their_cell_text() {
+    let (a, mut site_a) = new_site("Before\nAfter", 1);
+    let (b, mut site_b) = new_site("Before\nAfter", 2);
+
+    a.cursor_at(6).insert_table(2, 2).unwrap();
+    sync(&mut site_a, &mut site_b);
+    let table = b.blocks().into_iter().find_map(|block| block.table_cell());
+    let table = table.expect("table on site b").table;
+    assert_eq!((table.rows(), table.columns()), (2, 2));
+
+    let cell = |doc: &TextDocument, row, column| {
+        doc.blocks()
+            .into_iter()
+            .find(|block| {
+                block
+                    .table_cell()
+                    .is_some_and(|c| (c.row, c.column) == (row, column))
+            })
+            .unwrap()
+            .position()
+    };
+    b.cursor_at(cell(&b, 0, 1)).insert_text("B").unwrap();
+    a.cursor_at(cell(&a, 1, 0)).insert_text("A").unwrap();
+    sync(&mut site_a, &mut site_b);
+
+    let texts = |doc: &TextDocument| doc.blocks().iter().map(|b| b.text()).collect::<Vec<_>>();
+    assert_eq!(texts(&a), vec!["Before", "", "B", "A", "", "After"]);
+    assert_eq!(texts(&a), texts(&b));
+}
+
+#[test]
+fn table_starting_the_document_settles() {
+    let (a, mut site_a) = new_site("Alpha\nBeta", 1);
+    let (b, mut site_b) = new_site("Alpha\nBeta", 2);
+
+    a.cursor_at(0).insert_table(2, 2).unwrap();
+    sync(&mut site_a, &mut site_b);
+    assert!(site_a.local_operations().unwrap().is_empty());
+    assert!(site_b.local_operations().unwrap().is_empty());
+    assert_eq!(a.to_html().unwrap(), b.to_html().unwrap());
+}
+
+#[test]
+fn remote_edits_stay_off_the_local_undo_stack() {
+    let (a, mut site_a) = new_site("Local\nShared", 1);
+    let (b, mut site_b) = new_site("Local\nShared", 2);
+
+    a.cursor_at(5).insert_text(" edit").unwrap();
+    let follower = a.cursor_at(12);
+    b.cursor_at(6).insert_text("Remote ").unwrap();
+    sync(&mut site_a, &mut site_b);
+    assert_eq!(a.to_plain_text().unwrap(), "Local edit\nRemote Shared");
+    assert_eq!(follower.position(), 19);
+    assert!(a.can_undo_in(site_a.remote_undo_stack()));
+
+    // Undo takes back the local edit only, and that goes out as well.
+    a.undo().unwrap();
+    assert!(!a.can_undo());
+    assert_eq!(a.to_plain_text().unwrap(), "Local\nRemote Shared");
+    sync(&mut site_a, &mut site_b);
+    assert_eq!(b.to_plain_text().unwrap(), "Local\nRemote Shared");
+}
+
+#[test]
+fn session_undo_keeps_remote_edits_to_the_same_paragraph() {
+    let (a, mut site_a) = new_site("Hello world", 1);
+    let (b, mut site_b) = new_site("Hello world", 2);
+
+    a.cursor_at(5).insert_text(" there").unwrap();
+    sync(&mut site_a, &mut site_b);
+    b.cursor_at(0).insert_text("Oh, ").unwrap();
+    select(&b, 21, 21).insert_text("!").unwrap();
+    sync(&mut site_a, &mut site_b);
+    assert_eq!(a.to_plain_text().unwrap(), "Oh, Hello there world!");
+    // The document's own undo is blocked by the remote edits.
+    assert!(a.undo().is_err());
+
+    site_a.undo().unwrap();
+    assert_eq!(a.to_plain_text().unwrap(), "Oh, Hello world!");
+    assert!(site_a.can_redo());
+    sync(&mut site_a, &mut site_b);
+    assert_eq!(b.to_plain_text().unwrap(), "Oh, Hello world!");
+
+    site_a.redo().unwrap();
+    sync(&mut site_a, &mut site_b);
+    assert_eq!(a.to_plain_text().unwrap(), "Oh, Hello there world!");
+    assert_eq!(b.to_plain_text().unwrap(), "Oh, Hello there world!");
+}
+
+#[test]
+fn session_undo_restores_deleted_text_and_formats() {
+    let (a, mut site_a) = new_site("Hello brave world", 1);
+    let (b, mut site_b) = new_site("Hello brave world", 2);
+
+    select(&a, 5, 11).remove_selected_text().unwrap();
+    sync(&mut site_a, &mut site_b);
+    select(&a, 0, 5)
+        .set_char_format(&TextFormat {
+            font_bold: Some(true),
+            ..Default::default()
+        })
+        .unwrap();
+    b.cursor_at(17).insert_text("!").unwrap();
+    select(&b, 0, 5)
+        .set_char_format(&TextFormat {
+            font_italic: Some(true),
+            ..Default::default()
+        })
+        .unwrap();
+    sync(&mut site_a, &mut site_b);
+    assert_eq!(a.to_plain_text().unwrap(), "Hello world!");
+
+    // Bold goes, the italic from the other site stays.
+    site_a.undo().unwrap();
+    let format = a.blocks()[0].char_format_at(1).unwrap();
+    assert_ne!(format.font_bold, Some(true));
+    assert_eq!(format.font_italic, Some(true));
+
+    site_a.undo().unwrap();
+    assert!(!site_a.can_undo());
+    sync(&mut site_a, &mut site_b);
+    assert_eq!(a.to_plain_text().unwrap(), "Hello brave world!");
+    assert_eq!(a.to_html().unwrap(), b.to_html().unwrap());
+}
+
+#[test]
+fn operations_survive_serialization_and_redelivery() {
+    let (a, mut site_a) = new_site("abc", 1);
+    let (b, mut site_b) = new_site("abc", 2);
+
+    a.cursor_at(3).insert_text("d").unwrap();
+    let first = site_a.local_operations().unwrap();
+    a.cursor_at(0).insert_text("_").unwrap();
+    let second = site_a.local_operations().unwrap();
+
+    let json = serde_json::to_string(&second).unwrap();
+    let second: Vec<CollabOperation> = serde_json::from_str(&json).unwrap();
+    site_b.apply_remote(second).unwrap();
+    assert_eq!(site_b.pending_count(), 0);
+    site_b.apply_remote(first.clone()).unwrap();
+    site_b.apply_remote(first).unwrap();
+
+    assert_eq!(b.to_plain_text().unwrap(), "_abcd");
+    assert_eq!(site_b.version_vector(), site_a.version_vector());
+}
+
+// ── Random interleavings ────────────────────────────────────────
+
+#[derive(Debug, Clone)]
+enum Step {
+    /// Type `text`, then start a new block after it if `split`.
+    Type {
+        at: usize,
+        text: String,
+        split: bool,
+    },
+    Delete {
+        at: usize,
+        len: usize,
+    },
+    Bold {
+        at: usize,
+        len: usize,
+    },
+    Italic {
+        at: usize,
+        len: usize,
+    },
+    Align {
+        at: usize,
+    },
+    Heading {
+        at: usize,
+        level: u8,
+    },
+    List {
+        at: usize,
+    },
+    Unlist {
+        at: usize,
+    },
+    Table {
+        at: usize,
+        rows: usize,
+        columns: usize,
+    },
+    /// Remove the table around `at`, if there is one.
+    Untable {
+        at: usize,
+    },
+    Undo,
+    /// Undo or redo through the session.
+    SessionUndo,
+    SessionRedo,
+    /// Hand the site's local operations to the other sites' inboxes.
+    Send,
+    /// Apply one batch from the site's inbox, picked by index.
+    Receive {
+        pick: usize,
+    },
+}
+
+fn step() -> impl Strategy<Value = Step> {
+    prop_oneof![
+        4 => (any::<usize>(), "[a-z]{1,3}", any::<bool>())
+            .prop_map(|(at, text, split)| Step::Type { at, text, split }),
+        2 => (any::<usize>(), 1..4usize).prop_map(|(at, len)| Step::Delete { at, len }),
+        1 => (any::<usize>(), 1..5usize).prop_map(|(at, len)| Step::Bold { at, len }),
+        1 => (any::<usize>(), 1..5usize).prop_map(|(at, len)| Step::Italic { at, len }),
+        1 => any::<usize>().prop_map(|at| Step::Align { at }),
+        1 => (any::<usize>(), 1..3u8).prop_map(|(at, level)| Step::Heading { at, level }),
+        1 => any::<usize>().prop_map(|at| Step::List { at }),
+        1 => any::<usize>().prop_map(|at| Step::Unlist { at }),
+        1 => (any::<usize>(), 1..3usize, 1..3usize)
+            .prop_map(|(at, rows, columns)| Step::Table { at, rows, columns }),
+        1 => any::<usize>().prop_map(|at| Step::Untable { at }),
+        1 => Just(Step::Undo),
+        1 => Just(Step::SessionUndo),
+        1 => Just(Step::SessionRedo),
+        3 => Just(Step::Send),
+        3 => any::<usize>().prop_map(|pick| Step::Receive { pick }),
+    ]
+}
+
+fn edit(doc: &TextDocument, step: Step) {
+    let len = doc.to_plain_text().unwrap().chars().count();
+    let range = |at: usize, n: usize| {
+        let start = at % (len + 1);
+        (start, (start + n).min(len))
+    };
+    // Edits the document refuses (an undo blocked by a remote edit,
+    // say) are part of the game.
+    let _ = match step {
+        Step::Type { at, text, split } => {
+            let cursor = doc.cursor_at(at % (len + 1));
+            cursor
+                .insert_text(&text)
+                .and_then(|()| if split { cursor.insert_block() } else { Ok(()) })
+        }
+        Step::Delete { at, len: n } => {
+            let (start, end) = range(at, n);
+            select(doc, start, end).remove_selected_text().map(drop)
+        }
+        Step::Bold { at, len: n } | Step::Italic { at, len: n } => {
+            let (start, end) = range(at, n);
+            let bold = matches!(step, Step::Bold { .. });
+            let format = TextFormat {
+                font_bold: bold.then_some(true),
+                font_italic: (!bold).then_some(true),
+                ..Default::default()
+            };
+            select(doc, start, end).set_char_format(&format)
+        }
+        Step::Align { at } => doc
+            .cursor_at(at % (len + 1))
+            .set_block_format(&BlockFormat {
+                alignment: Some(Alignment::Right),
+                ..Default::default()
+            }),
+        Step::Heading { at, level } => {
+            doc.cursor_at(at % (len + 1))
+                .set_block_format(&BlockFormat {
+                    heading_level: Some(level),
+                    ..Default::default()
+                })
+        }
+        Step::List { at } => doc.cursor_at(at % (len + 1)).create_list(ListStyle::Disc),
+        Step::Unlist { at } => doc
+            .cursor_at(at % (len + 1))
+            .remove_current_block_from_list(),
+        Step::Table { at, rows, columns } => doc
+            .cursor_at(at % (len + 1))
+            .insert_table(rows, columns)
+            .map(drop),
+        Step::Untable { at } => doc.cursor_at(at % (len + 1)).remove_current_table(),
+        Step::Undo => doc.undo(),
+        Step::SessionUndo | Step::SessionRedo | Step::Send | Step::Receive { .. } => Ok(()),
+    };
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(48))]
+
+    #[test]
+    fn sites_converge_under_random_interleavings(
+        steps in proptest::collection::vec((0..3usize, step()), 1..40)
+    ) {
+        let start = "one\ntwo three";
+        let sites: Vec<(TextDocument, CollabSession)> =
+            (0..3).map(|i| new_site(start, i as u64 + 1)).collect();
+        let (docs, mut sessions): (Vec<_>, Vec<_>) = sites.into_iter().unzip();
+        let mut inboxes: Vec<Vec<Vec<CollabOperation>>> = vec![Vec::new(); 3];
+
+        for (site, step) in steps {
+            match step {
+                Step::Send => {
+                    let ops = sessions[site].local_operations().unwrap();
+                    for (other, inbox) in inboxes.iter_mut().enumerate() {
+                        if other != site && !ops.is_empty() {
+                            inbox.push(ops.clone());
+                        }
+                    }
+                }
+                Step::Receive { pick } => {
+                    if !inboxes[site].is_empty() {
+                        let n = pick % inboxes[site].len();
+                        let batch = inboxes[site].remove(n);
+                        sessions[site].apply_remote(batch).unwrap();
+                    }
+                }
+                Step::SessionUndo => sessions[site].undo().unwrap(),
+                Step::SessionRedo => sessions[site].redo().unwrap(),
+                step => edit(&docs[site], step),
+            }
+        }
+
+        // Deliver everything, including what the deliveries produce.
+        for _ in 0..8 {
+            let mut quiet = true;
+            for (site, session) in sessions.iter_mut().enumerate() {
+                let ops = session.local_operations().unwrap();
+                if !ops.is_empty() {
+                    quiet = false;
+                    for (other, inbox) in inboxes.iter_mut().enumerate() {
+                        if other != site {
+                            inbox.push(ops.clone());
+                        }
+                    }
+                }
+            }
+            for (session, inbox) in sessions.iter_mut().zip(&mut inboxes) {
+                for batch in std::mem::take(inbox) {
+                    quiet = false;
+                    session.apply_remote(batch).unwrap();
+                }
+            }
+            if quiet {
+                break;
+            }
+        }
+
+        // Once everything is delivered, the sites have nothing to add.
+        for session in &mut sessions {
+            prop_assert_eq!(session.pending_count(), 0);
+            prop_assert!(session.local_operations().unwrap().is_empty());
+        }
+        for site in 1..3 {
+            prop_assert_eq!(docs[0].to_plain_text().unwrap(), docs[site].to_plain_text().unwrap());
+            prop_assert_eq!(docs[0].to_html().unwrap(), docs[site].to_html().unwrap());
+        }
+    }
+}
@@ -0,0 +1,179 @@
+//! Tests for foreground, background and underline colors in character
+//! formats: editing, undo, fragments and the import/export formats.
+
+use text_document::{Color, MoveMode, TextDocument, TextFormat};
+
+fn colored_doc() -> TextDocument {
+    let doc = TextDocument::new();
+    doc.set_plain_text("plain red tail").unwrap();
+    let cursor = doc.cursor_at(6);
+    cursor.set_position(9, MoveMode::KeepAnchor);
+    cursor
+        .set_char_format(&TextFormat {
+            font_underline: Some(true),
+            foreground_color: Some(Color::rgb(255, 0, 0)),
+            background_color: Some(Color::rgb(255, 255, 0)),
+            underline_color: Some(Color::rgb(0, 0, 255)),
+            ..Default::default()
+        })
+        .unwrap();
+    doc
+}
+
+fn format_at(doc: &TextDocument, position: usize) -> TextFormat {
+    doc.cursor_at(position).char_format().unwrap()
+}
+
+fn assert_colored(doc: &TextDocument, position: usize, with_underline_color: bool) {
+    let fmt = format_at(doc, position);
+    assert_eq!(fmt.foreground_color, Some(Color::rgb(255, 0, 0)));
+    assert_eq!(fmt.background_color, Some(Color::rgb(255, 255, 0)));
+    if with_underline_color {
+        assert_eq!(fmt.underline_color, Some(Color::rgb(0, 0, 255)));
+    }
+}
+
+#[test]
+fn colors_persist_with_undo_and_redo() {
+    let doc = colored_doc();
+    assert_colored(&doc, 7, true);
+    assert_eq!(format_at(&doc, 2).foreground_color, None);
+
+    let translucent = Color {
+        red: 0,
+        green: 128,
+        blue: 0,
+        alpha: 64,
+    };
+    let cursor = doc.cursor_at(0);
+    cursor.set_position(5, MoveMode::KeepAnchor);
+    cursor
+        .merge_char_format(&TextFormat {
+            foreground_color: Some(translucent),
+            ..Default::default()
+        })
+        .unwrap();
+    assert_eq!(format_at(&doc, 2).foreground_color, Some(translucent));
+
+    doc.undo().unwrap();
+    assert_eq!(format_at(&doc, 2).foreground_color, None);
+    doc.undo().unwrap();
+    assert_eq!(format_at(&doc, 7).foreground_color, None);
+    doc.redo().unwrap();
+    assert_colored(&doc, 7, true);
+}
+
+#[test]
+fn colors_survive_fragment_copy_paste() {
+    let doc = colored_doc();
+    let cursor = doc.cursor_at(6);
+    cursor.set_position(9, MoveMode::KeepAnchor);
+    let fragment = cursor.selection();
+
+    let target = TextDocument::new();
+    target.set_plain_text("x").unwrap();
+    target.cursor_at(1).insert_fragment(&fragment).unwrap();
+    assert_eq!(target.to_plain_text().unwrap(), "xred");
+    assert_colored(&target, 2, true);
+}
+
+#[test]
+fn html_export_and_import_colors() {
+    let html = colored_doc().to_html().unwrap();
+    assert!(html.contains("color: #ff0000"), "{html}");
+    assert!(html.contains("background-color: #ffff00"), "{html}");
+    assert!(html.contains("text-decoration-color: #0000ff"), "{html}");
+
+    let doc = TextDocument::new();
+    doc.set_html(&html).unwrap().wait().unwrap();
+    assert_colored(&doc, 7, true);
+
+    let doc = TextDocument::new();
+    doc.set_html(concat!(
+        r#"<p><font color="navy">a</font>"#,
+        r#"<span style="color: rgb(0, 128, 0); background: #abc">b</span>"#,
+        r#"<span style="color: inherit">c</span></p>"#,
+    ))
+    .unwrap()
+    .wait()
+    .unwrap();
+    assert_eq!(
+        format_at(&doc, 0).foreground_color,
+        Some(Color::rgb(0, 0, 128))
+    );
+    let b = format_at(&doc, 1);
+    assert_eq!(b.foreground_color, Some(Color::rgb(0, 128, 0)));
+    assert_eq!(b.background_color, Some(Color::rgb(0xaa, 0xbb, 0xcc)));
+    assert_eq!(format_at(&doc, 2).foreground_color, None);
+}
+
+#[test]
+fn latex_export_uses_xcolor() {
+    let latex = colored_doc().to_latex("article", true).unwrap();
+    assert!(latex.contains("xcolor"), "{latex}");
+    assert!(latex.contains(r"\textcolor[HTML]{FF0000}{"), "{latex}");
+    assert!(latex.contains(r"\colorbox[HTML]{FFFF00}{"), "{latex}");
+}
+
+#[test]
+fn docx_roundtrip_keeps_colors() {
+    let path = std::env::temp_dir().join("test_color_roundtrip.docx");
+    colored_doc()
+        .to_docx(path.to_str().unwrap())
+        .unwrap()
+        .wait()
+        .unwrap();
+    let bytes = std::fs::read(&path).unwrap();
+    let _ = std::fs::remove_file(&path);
+
+    let doc = TextDocument::new();
+    doc.set_docx(&bytes).unwrap().wait().unwrap();
+    // DOCX export has no underline color.
+    assert_colored(&doc, 7, false);
+    assert_eq!(format_at(&doc, 2).foreground_color, None);
+}
+
+#[test]
+fn odt_roundtrip_keeps_colors() {
+    let path = std::env::temp_dir().join("test_color_roundtrip.odt");
+    colored_doc()
+        .to_odt(path.to_str().unwrap())
+        .unwrap()
+        .wait()
+        .unwrap();
+    let bytes = std::fs::read(&path).unwrap();
+    let _ = std::fs::remove_file(&path);
+
+    let doc = TextDocument::new();
+    doc.set_odt(&bytes).unwrap().wait().unwrap();
+    assert_colored(&doc, 7, true);
+    assert_eq!(format_at(&doc, 2).foreground_color, None);
+}
+
+#[test]
+fn rtf_roundtrip_keeps_colors() {
+    let rtf = colored_doc().to_rtf().unwrap();
+    assert!(
+        rtf.contains(
+            r"{\colortbl;\red255\green0\blue0;\red255\green255\blue0;\red0\green0\blue255;}"
+        ),
+        "{rtf}"
+    );
+
+    let doc = TextDocument::new();
+    doc.set_rtf(&rtf).unwrap().wait().unwrap();
+    assert_colored(&doc, 7, true);
+    assert_eq!(format_at(&doc, 2).foreground_color, None);
+
+    let doc = TextDocument::new();
+    doc.set_rtf(r"{\rtf1{\colortbl;\red0\green0\blue128;}a{\cf1 b}{\cf0 c}}")
+        .unwrap()
+        .wait()
+        .unwrap();
+    assert_eq!(format_at(&doc, 0).foreground_color, None);
+    assert_eq!(
+        format_at(&doc, 1).foreground_color,
+        Some(Color::rgb(0, 0, 128))
+    );
+    assert_eq!(format_at(&doc, 2).foreground_color, None);
+}
@@ -248,6 +248,46 @@ fn insert_fragment_with_selection_replaces() {
     assert_eq!(plain, "Hello Rust");
 }
 
+#[test]
+fn insert_multi_block_fragment_keeps_character_count() {
+    // The text after the cursor moves to a new tail block and must
+    // still be counted, or later cursor positions get clamped short.
+    for (position, expected) in [(0, "\nSome text"), (5, "Some \ntext"), (9, "Some text\n")] {
+        let doc = TextDocument::new();
+        doc.set_plain_text("Some text").unwrap();
+
+        let cursor = doc.cursor_at(position);
+        cursor
+            .insert_fragment(&DocumentFragment::from_plain_text("\n"))
+            .unwrap();
+
+        assert_eq!(doc.to_plain_text().unwrap(), expected);
+        assert_eq!(doc.character_count(), 9);
+        assert_eq!(doc.block_count(), 2);
+    }
+}
+
+#[test]
+fn insert_multi_block_fragment_after_split_keeps_block_order() {
+    let doc = TextDocument::new();
+    doc.set_plain_text("one\ntwo three").unwrap();
+    let cursor = doc.cursor_at(4);
+    cursor.set_position(6, MoveMode::KeepAnchor);
+    cursor.remove_selected_text().unwrap();
+    let cursor = doc.cursor_at(3);
+    cursor.insert_text("aa").unwrap();
+    cursor.insert_block().unwrap();
+
+    doc.cursor_at(7)
+        .insert_fragment(&DocumentFragment::from_plain_text("a\n"))
+        .unwrap();
+
+    assert_eq!(doc.to_plain_text().unwrap(), "oneaa\n\na\no three");
+    let html = doc.to_html().unwrap();
+    let (a, rest) = (html.find("<p>a</p>"), html.find("<p>o three</p>"));
+    assert!(a.is_some() && a < rest, "blocks out of order: {html}");
+}
+
 #[test]
 fn insert_table_outside_table_creates_new() {
     let doc = TextDocument::new();
@@ -0,0 +1,451 @@
+//! Tests for structural diffs between documents and applying them as
+//! patches.
+
+use proptest::prelude::*;
+use text_document::{
+    BlockFormat, DiffChange, DocumentDiff, ListStyle, MoveMode, SpanChange, TextDocument,
+    TextFormat, diff,
+};
+
+fn doc(text: &str) -> TextDocument {
+    let doc = TextDocument::new();
+    doc.set_plain_text(text).unwrap();
+    doc
+}
+
+/// A second document with the same content as `doc`.
+fn copy(doc: &TextDocument) -> TextDocument {
+    let mut data = Vec::new();
+    doc.save_native(&mut data).unwrap();
+    let copy = TextDocument::new();
+    copy.load_native(data.as_slice()).unwrap();
+    copy
+}
+
+fn bold() -> TextFormat {
+    TextFormat {
+        font_bold: Some(true),
+        ..Default::default()
+    }
+}
+
+/// Apply the diff from `old` to `new` on a copy of `old` and check that
+/// it becomes `new`.
+fn assert_patches(old: &TextDocument, new: &TextDocument) -> DocumentDiff {
+    let d = diff(old, new).unwrap();
+    let patched = copy(old);
+    d.apply(&patched).unwrap();
+    assert_eq!(
+        patched.to_plain_text().unwrap(),
+        new.to_plain_text().unwrap()
+    );
+    assert_eq!(patched.to_html().unwrap(), new.to_html().unwrap());
+    let left = diff(&patched, new).unwrap();
+    assert!(left.is_empty(), "left after patching: {:?}", left.changes());
+    d
+}
+
+#[test]
+fn identical_documents_have_no_changes() {
+    let a = doc("One\nTwo");
+    let d = diff(&a, &copy(&a)).unwrap();
+    assert!(d.is_empty());
+    assert_eq!(d.to_html(), "<p>One</p>\n<p>Two</p>\n");
+}
+
+#[test]
+fn edited_inserted_and_removed_blocks() {
+    let old = doc("Intro\nThe quick fox\nGone for good\nEnd");
+    let new = doc("Intro\nThe quick brown fox\nEnd\nAppendix");
+    let d = assert_patches(&old, &new);
+
+    let changes = d.changes();
+    assert_eq!(changes.len(), 3, "{changes:?}");
+    let DiffChange::BlockChanged(edit) = &changes[0] else {
+        panic!("{changes:?}");
+    };
+    assert_eq!((edit.old_position, edit.new_position), (6, 6));
+    assert_eq!(edit.text_edits.len(), 1);
+    assert_eq!(edit.text_edits[0].old_offset, 10);
+    assert_eq!(edit.text_edits[0].inserted, "brown ");
+    assert_eq!(
+        changes[1],
+        DiffChange::BlockRemoved {
+            old_position: 20,
+            text: "Gone for good".into()
+        }
+    );
+    assert_eq!(
+        changes[2],
+        DiffChange::BlockInserted {
+            new_position: 30,
+            text: "Appendix".into()
+        }
+    );
+    assert_eq!(
+        d.to_html(),
+        "<p>Intro</p>\n<p>The quick <ins>brown </ins>fox</p>\n\
+         <p><del>Gone for good</del></p>\n<p>End</p>\n<p><ins>Appendix</ins></p>\n"
+    );
+}
+
+#[test]
+fn format_only_changes_are_reported() {
+    let old = doc("Plain words\nTitle");
+    let new = copy(&old);
+    let cursor = new.cursor_at(6);
+    cursor.set_position(11, MoveMode::KeepAnchor);
+    cursor.set_char_format(&bold()).unwrap();
+    new.cursor_at(12)
+        .set_block_format(&BlockFormat {
+            heading_level: Some(1),
+            ..Default::default()
+        })
+        .unwrap();
+    let d = assert_patches(&old, &new);
+
+    let [
+        DiffChange::BlockChanged(words),
+        DiffChange::BlockChanged(title),
+    ] = d.changes()
+    else {
+        panic!("{:?}", d.changes());
+    };
+    assert!(words.text_edits.is_empty());
+    assert_eq!(words.format_changes.len(), 1);
+    let run = &words.format_changes[0];
+    assert_eq!((run.old_offset, run.length), (6, 5));
+    assert_eq!(run.new.font_bold, Some(true));
+    let (had, has) = title.block_format.as_ref().unwrap();
+    assert_eq!((had.heading_level, has.heading_level), (None, Some(1)));
+    assert_eq!(
+        d.to_html(),
+        "<p>Plain <span class=\"format-changed\">words</span></p>\n\
+         <h1 class=\"format-changed\">Title</h1>\n"
+    );
+
+    // Formats that lose a field are applied exactly too.
+    assert_patches(&new, &old);
+}
+
+#[test]
+fn moved_blocks_are_detected() {
+    let old = doc("First point\nSecond point\nThird point");
+    let new = doc("Second point\nThird point\nFirst point");
+    let d = assert_patches(&old, &new);
+    assert_eq!(
+        d.changes(),
+        [DiffChange::BlockMoved {
+            old_position: 0,
+            new_position: 25,
+            text: "First point".into()
+        }]
+    );
+    assert!(
+        d.to_html()
+            .starts_with("<p class=\"moved\"><del>First point</del></p>")
+    );
+}
+
+#[test]
+fn list_membership_changes() {
+    let old = doc("Intro\napples\npears");
+    let new = copy(&old);
+    let cursor = new.cursor_at(6);
+    cursor.create_list(ListStyle::Decimal).unwrap();
+    let list = cursor.current_list().unwrap();
+    cursor
+        .add_block_to_list(new.block_by_number(2).unwrap().id(), list.id())
+        .unwrap();
+    let d = assert_patches(&old, &new);
+    assert_eq!(d.changes().len(), 2);
+    let DiffChange::BlockChanged(change) = &d.changes()[0] else {
+        panic!("{:?}", d.changes());
+    };
+    let (had, has) = change.list.as_ref().unwrap();
+    assert!(had.is_none());
+    assert_eq!(has.as_ref().unwrap().style, Some(ListStyle::Decimal));
+
+    assert_patches(&new, &old);
+}
+
+#[test]
+fn table_cells_and_rows() {
+    let old = doc("Intro");
+    let table = old.cursor_at(5).insert_table(2, 2).unwrap();
+    old.cursor_at(6).insert_text("a").unwrap();
+    old.cursor_at(8).insert_text("b").unwrap();
+
+    let new = copy(&old);
+    let table_id = new.cursor_at(6).current_table().unwrap().id();
+    new.cursor_at(6).insert_text("x").unwrap();
+    new.cursor().insert_table_row(table_id, 2).unwrap();
+    let last_row = new
+        .blocks()
+        .into_iter()
+        .find(|b| b.table_cell().is_some_and(|c| (c.row, c.column) == (2, 1)))
+        .unwrap();
+    new.cursor_at(last_row.position())
+        .insert_text("new")
+        .unwrap();
+    let d = assert_patches(&old, &new);
+
+    let [DiffChange::TableChanged(change)] = d.changes() else {
+        panic!("{:?}", d.changes());
+    };
+    assert_eq!(change.rows_inserted, [2]);
+    assert!(change.rows_removed.is_empty() && change.columns_inserted.is_empty());
+    assert_eq!(change.cells.len(), 1);
+    let cell = &change.cells[0];
+    assert_eq!((cell.old_row, cell.old_column), (0, 0));
+    let [DiffChange::BlockChanged(text)] = cell.changes.as_slice() else {
+        panic!("{:?}", cell.changes);
+    };
+    assert_eq!(text.text_edits[0].inserted, "x");
+    let html = d.to_html();
+    assert!(html.contains("<tr class=\"inserted\">"), "{html}");
+    assert!(html.contains("<td><p><ins>x</ins>a</p>\n</td>"), "{html}");
+
+    // Removing the row and a column back again.
+    assert_patches(&new, &old);
+    let narrow = copy(&old);
+    narrow.cursor().remove_table_column(table.id(), 1).unwrap();
+    let d = assert_patches(&old, &narrow);
+    let [DiffChange::TableChanged(change)] = d.changes() else {
+        panic!("{:?}", d.changes());
+    };
+    assert_eq!(change.columns_removed, [1]);
+}
+
+#[test]
+fn reshaped_table_is_replaced() {
+    let old = doc("Intro");
+    old.cursor_at(5).insert_table(2, 2).unwrap();
+    let new = doc("Intro");
+    new.cursor_at(5).insert_table(3, 3).unwrap();
+    let d = assert_patches(&old, &new);
+    assert_eq!(
+        d.changes(),
+        [
+            DiffChange::TableRemoved {
+                old_position: 6,
+                rows: 2,
+                columns: 2
+            },
+            DiffChange::TableInserted {
+                new_position: 6,
+                rows: 3,
+                columns: 3
+            }
+        ]
+    );
+}
+
+#[test]
+fn tables_at_the_start_side_by_side_and_before_an_empty_block() {
+    let old = doc("abc lorem\nipsum abc\nthird");
+    let new = copy(&old);
+    new.cursor_at(0).insert_table(1, 2).unwrap();
+    new.cursor_at(1).insert_text("x").unwrap();
+    // From inside a cell, a table goes right after the one it is in.
+    new.cursor_at(0).insert_table(2, 1).unwrap();
+    let end = new.character_count() + new.block_count() - 1;
+    let third = new
+        .blocks()
+        .into_iter()
+        .find(|b| b.text() == "third")
+        .unwrap();
+    new.cursor_at(third.position() + 5).insert_block().unwrap();
+    new.cursor_at(end + 1).insert_table(1, 1).unwrap();
+    let html = new.to_html().unwrap();
+    assert!(html.contains("<body><table>"), "{html}");
+    assert!(html.contains("</table><table>"), "{html}");
+    assert!(html.contains("</table><p></p></body>"), "{html}");
+
+    assert_patches(&old, &new);
+    assert_patches(&new, &old);
+}
+
+#[test]
+fn merged_cells_change_spans() {
+    let old = doc("Intro");
+    let table = old.cursor_at(5).insert_table(2, 2).unwrap();
+    old.cursor_at(6).insert_text("a").unwrap();
+    let new = copy(&old);
+    new.cursor()
+        .merge_table_cells(table.id(), 0, 0, 0, 1)
+        .unwrap();
+    let d = assert_patches(&old, &new);
+
+    let [DiffChange::TableChanged(change)] = d.changes() else {
+        panic!("{:?}", d.changes());
+    };
+    assert!(change.rows_removed.is_empty() && change.columns_removed.is_empty());
+    assert_eq!(
+        change.spans,
+        [SpanChange {
+            old_row: 0,
+            old_column: 0,
+            new_row: 0,
+            new_column: 0,
+            old: Some((1, 1)),
+            new: (1, 2),
+        }]
+    );
+    // The merged cell keeps the blocks of the first cell it covers.
+    assert_eq!(change.cells.len(), 1);
+    assert_eq!(
+        change.cells[0].changes,
+        [DiffChange::BlockRemoved {
+            old_position: 8,
+            text: String::new()
+        }]
+    );
+
+    let d = assert_patches(&new, &old);
+    let [DiffChange::TableChanged(change)] = d.changes() else {
+        panic!("{:?}", d.changes());
+    };
+    assert_eq!(change.spans.len(), 2, "{:?}", change.spans);
+    assert_eq!(change.spans[1].old, None);
+}
+
+#[test]
+fn apply_is_one_undo_step_and_checks_the_document() {
+    let old = doc("One\nTwo");
+    let new = doc("One\nTwo and more\nThree");
+    let d = diff(&old, &new).unwrap();
+
+    let other = doc("Something else");
+    assert!(d.apply(&other).is_err());
+    assert_eq!(other.to_plain_text().unwrap(), "Something else");
+
+    d.apply(&old).unwrap();
+    assert_eq!(old.to_plain_text().unwrap(), "One\nTwo and more\nThree");
+    old.undo().unwrap();
+    assert_eq!(old.to_plain_text().unwrap(), "One\nTwo");
+}
+
+#[test]
+fn failed_apply_leaves_the_document_alone() {
+    // A document that is a table alone cannot be reached: the editing
+    // API keeps a paragraph outside the table.
+    let old = doc("End");
+    let new = TextDocument::new();
+    new.set_html("<table><tr><td>a</td><td>b</td></tr></table>")
+        .unwrap()
+        .wait()
+        .unwrap();
+    let d = diff(&old, &new).unwrap();
+
+    old.cursor_at(0).insert_text(">").unwrap();
+    old.undo().unwrap();
+    let html = old.to_html().unwrap();
+    old.poll_events();
+
+    assert!(d.apply(&old).is_err());
+    assert_eq!(old.to_plain_text().unwrap(), "End");
+    assert_eq!(old.to_html().unwrap(), html);
+    assert!(!old.can_undo());
+    assert!(old.can_redo());
+    assert!(diff(&old, &doc("End")).unwrap().is_empty());
+}
+
+#[derive(Debug, Clone)]
+enum Step {
+    Type {
+        at: usize,
+        text: String,
+    },
+    Split {
+        at: usize,
+    },
+    Delete {
+        at: usize,
+        len: usize,
+    },
+    Bold {
+        at: usize,
+        len: usize,
+    },
+    Table {
+        at: usize,
+        rows: usize,
+        columns: usize,
+    },
+    MergeCells {
+        at: usize,
+    },
+}
+
+fn step() -> impl Strategy<Value = Step> {
+    prop_oneof![
+        4 => (any::<usize>(), "[a-z ]{1,4}").prop_map(|(at, text)| Step::Type { at, text }),
+        2 => any::<usize>().prop_map(|at| Step::Split { at }),
+        2 => (any::<usize>(), 1..12usize).prop_map(|(at, len)| Step::Delete { at, len }),
+        1 => (any::<usize>(), 1..4usize).prop_map(|(at, len)| Step::Bold { at, len }),
+        1 => (any::<usize>(), 1..3usize, 1..3usize)
+            .prop_map(|(at, rows, columns)| Step::Table { at, rows, columns }),
+        1 => any::<usize>().prop_map(|at| Step::MergeCells { at }),
+    ]
+}
+
+/// A copy of `doc` with `steps` run on it. Deletes may span tables.
+fn edited(doc: &TextDocument, steps: &[Step]) -> TextDocument {
+    let doc = copy(doc);
+    for step in steps {
+        let end = doc.character_count() + doc.block_count() - 1;
+        let cursor = doc.cursor();
+        match *step {
+            Step::Type { at, ref text } => {
+                cursor.set_position(at % (end + 1), MoveMode::MoveAnchor);
+                cursor.insert_text(text).unwrap();
+            }
+            Step::Split { at } => {
+                cursor.set_position(at % (end + 1), MoveMode::MoveAnchor);
+                cursor.insert_block().unwrap();
+            }
+            Step::Delete { at, len } | Step::Bold { at, len } if end > 0 => {
+                let at = at % end;
+                cursor.set_position(at, MoveMode::MoveAnchor);
+                cursor.set_position((at + len).min(end), MoveMode::KeepAnchor);
+                if matches!(step, Step::Delete { .. }) {
+                    cursor.remove_selected_text().unwrap();
+                } else {
+                    cursor.merge_char_format(&bold()).unwrap();
+                }
+            }
+            Step::Table { at, rows, columns } => {
+                cursor.set_position(at % (end + 1), MoveMode::MoveAnchor);
+                cursor.insert_table(rows, columns).unwrap();
+            }
+            Step::MergeCells { at } => {
+                cursor.set_position(at % (end + 1), MoveMode::MoveAnchor);
+                // The cells can only be merged once.
+                if let Some(table) = cursor.current_table()
+                    && table.columns() > 1
+                    && table.cell(0, 0).is_some_and(|cell| cell.column_span() == 1)
+                {
+                    cursor.merge_table_cells(table.id(), 0, 0, 0, 1).unwrap();
+                }
+            }
+            _ => {}
+        }
+    }
+    doc
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(48))]
+
+    #[test]
+    fn random_edits_patch_exactly(
+        before in proptest::collection::vec(step(), 0..8),
+        steps in proptest::collection::vec(step(), 1..16),
+    ) {
+        let old = edited(&doc("alpha beta\ngamma\ndelta epsilon\nzeta"), &before);
+        let new = edited(&old, &steps);
+        assert_patches(&old, &new);
+        assert_patches(&new, &old);
+    }
+}
@@ -0,0 +1,280 @@
+//! Tests for DOCX import (`set_docx`).
+
+use std::io::Write;
+
+use text_document::{
+    Alignment, FlowElement, FragmentContent, ListStyle, MoveMode, TextDocument, TextFormat,
+};
+
+const W_NS: &str = "http://schemas.openxmlformats.org/wordprocessingml/2006/main";
+const R_NS: &str = "http://schemas.openxmlformats.org/officeDocument/2006/relationships";
+
+/// Build a DOCX package from a `w:body` inner XML and optional parts.
+fn docx(body: &str, parts: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut buf = std::io::Cursor::new(Vec::new());
+    {
+        let mut zip = zip::ZipWriter::new(&mut buf);
+        let options = zip::write::FileOptions::default();
+        zip.start_file("word/document.xml", options).unwrap();
+        write!(
+            zip,
+            r#"<?xml version="1.0" encoding="UTF-8"?><w:document xmlns:w="{W_NS}" xmlns:r="{R_NS}" xmlns:wp="http://schemas.openxmlformats.org/drawingml/2006/wordprocessingDrawing" xmlns:a="http://schemas.openxmlformats.org/drawingml/2006/main"><w:body>{body}</w:body></w:document>"#
+        )
+        .unwrap();
+        for (name, data) in parts {
+            zip.start_file(*name, options).unwrap();
+            zip.write_all(data).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+    buf.into_inner()
+}
+
+fn rels(entries: &str) -> Vec<u8> {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?><Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">{entries}</Relationships>"#
+    )
+    .into_bytes()
+}
+
+fn import(bytes: &[u8]) -> TextDocument {
+    let doc = TextDocument::new();
+    doc.set_docx(bytes).unwrap().wait().unwrap();
+    doc
+}
+
+#[test]
+fn docx_import_paragraphs_and_run_formatting() {
+    let bytes = docx(
+        concat!(
+            r#"<w:p><w:r><w:t xml:space="preserve">Plain </w:t></w:r>"#,
+            r#"<w:r><w:rPr><w:b/><w:i/></w:rPr><w:t>bold</w:t></w:r></w:p>"#,
+            r#"<w:p><w:pPr><w:jc w:val="center"/></w:pPr>"#,
+            r#"<w:r><w:rPr><w:u w:val="wave"/><w:strike/><w:rFonts w:ascii="Georgia"/><w:sz w:val="28"/></w:rPr><w:t>styled</w:t></w:r>"#,
+            r#"<w:r><w:rPr><w:vertAlign w:val="superscript"/><w:b w:val="0"/></w:rPr><w:t>2</w:t></w:r></w:p>"#,
+        ),
+        &[],
+    );
+    let doc = import(&bytes);
+    assert_eq!(doc.to_plain_text().unwrap(), "Plain bold\nstyled2");
+
+    let bold = doc.cursor_at(7).char_format().unwrap();
+    assert_eq!(bold.font_bold, Some(true));
+    assert_eq!(bold.font_italic, Some(true));
+    assert_eq!(
+        doc.cursor_at(2).char_format().unwrap(),
+        TextFormat::default()
+    );
+
+    let styled = doc.cursor_at(13).char_format().unwrap();
+    assert_eq!(styled.font_underline, Some(true));
+    assert_eq!(styled.font_strikeout, Some(true));
+    assert_eq!(styled.font_family.as_deref(), Some("Georgia"));
+    assert_eq!(styled.font_point_size, Some(14));
+    assert!(styled.underline_style.is_some());
+
+    let sup = doc.cursor_at(17).char_format().unwrap();
+    assert!(sup.vertical_alignment.is_some());
+    assert_eq!(sup.font_bold, None);
+
+    let second = doc.block_at_position(11).unwrap();
+    assert_eq!(second.block_format().alignment, Some(Alignment::Center));
+}
+
+#[test]
+fn docx_import_headings_from_styles_and_line_breaks() {
+    let styles = format!(
+        r#"<w:styles xmlns:w="{W_NS}"><w:style w:type="paragraph" w:styleId="Titre2"><w:name w:val="heading 2"/></w:style></w:styles>"#
+    );
+    let bytes = docx(
+        concat!(
+            r#"<w:p><w:pPr><w:pStyle w:val="Heading1"/></w:pPr><w:r><w:t>Top</w:t></w:r></w:p>"#,
+            r#"<w:p><w:pPr><w:pStyle w:val="Titre2"/></w:pPr><w:r><w:t>Sub</w:t></w:r></w:p>"#,
+            r#"<w:p><w:r><w:t>one</w:t><w:br/><w:t>two</w:t></w:r></w:p>"#,
+        ),
+        &[("word/styles.xml", styles.as_bytes())],
+    );
+    let doc = import(&bytes);
+    assert_eq!(doc.to_plain_text().unwrap(), "Top\nSub\none\ntwo");
+    let blocks = doc.blocks();
+    assert_eq!(blocks[0].block_format().heading_level, Some(1));
+    assert_eq!(blocks[1].block_format().heading_level, Some(2));
+    assert_eq!(blocks[2].block_format().heading_level, None);
+}
+
+#[test]
+fn docx_import_numbered_and_bulleted_lists() {
+    let numbering = format!(
+        concat!(
+            r#"<w:numbering xmlns:w="{}">"#,
+            r#"<w:abstractNum w:abstractNumId="0"><w:lvl w:ilvl="0"><w:numFmt w:val="decimal"/></w:lvl></w:abstractNum>"#,
+            r#"<w:abstractNum w:abstractNumId="1"><w:lvl w:ilvl="0"><w:numFmt w:val="bullet"/></w:lvl><w:lvl w:ilvl="1"><w:numFmt w:val="lowerRoman"/></w:lvl></w:abstractNum>"#,
+            r#"<w:num w:numId="1"><w:abstractNumId w:val="0"/></w:num>"#,
+            r#"<w:num w:numId="2"><w:abstractNumId w:val="1"/></w:num>"#,
+            r#"</w:numbering>"#
+        ),
+        W_NS
+    );
+    let item = |num: u32, lvl: u32, text: &str| {
+        format!(
+            r#"<w:p><w:pPr><w:numPr><w:ilvl w:val="{lvl}"/><w:numId w:val="{num}"/></w:numPr></w:pPr><w:r><w:t>{text}</w:t></w:r></w:p>"#
+        )
+    };
+    let body = [
+        item(1, 0, "first"),
+        item(1, 0, "second"),
+        item(2, 0, "bullet"),
+        item(2, 1, "nested"),
+    ]
+    .concat();
+    let doc = import(&docx(
+        &body,
+        &[("word/numbering.xml", numbering.as_bytes())],
+    ));
+
+    let blocks = doc.blocks();
+    let first = blocks[0].list().unwrap();
+    assert_eq!(first.style(), ListStyle::Decimal);
+    assert_eq!(blocks[1].list().unwrap().id(), first.id());
+    assert_eq!(blocks[1].list_item_index(), Some(1));
+    assert_eq!(blocks[2].list().unwrap().style(), ListStyle::Disc);
+    let nested = blocks[3].list().unwrap();
+    assert_eq!(nested.style(), ListStyle::LowerRoman);
+    assert_eq!(nested.indent(), 1);
+}
+
+#[test]
+fn docx_import_table_with_merged_cells() {
+    let cell = |props: &str, text: &str| {
+        format!(r#"<w:tc><w:tcPr>{props}</w:tcPr><w:p><w:r><w:t>{text}</w:t></w:r></w:p></w:tc>"#)
+    };
+    let body = format!(
+        r#"<w:p><w:r><w:t>Before</w:t></w:r></w:p><w:tbl><w:tblGrid><w:gridCol/><w:gridCol/><w:gridCol/></w:tblGrid><w:tr>{}{}</w:tr><w:tr>{}{}{}</w:tr><w:tr>{}{}{}</w:tr></w:tbl><w:p><w:r><w:t>After</w:t></w:r></w:p>"#,
+        cell(r#"<w:gridSpan w:val="2"/>"#, "wide"),
+        cell(r#"<w:vMerge w:val="restart"/>"#, "tall"),
+        cell("", "a"),
+        cell("", "b"),
+        cell("<w:vMerge/>", ""),
+        cell("", "c"),
+        cell("", "d"),
+        cell("", "e"),
+    );
+    let doc = import(&docx(&body, &[]));
+
+    let table = doc
+        .flow()
+        .into_iter()
+        .find_map(|e| match e {
+            FlowElement::Table(t) => Some(t),
+            _ => None,
+        })
+        .expect("table in flow");
+    assert_eq!(table.rows(), 3);
+    assert_eq!(table.columns(), 3);
+
+    let wide = table.cell(0, 0).unwrap();
+    assert_eq!(wide.column_span(), 2);
+    assert_eq!(wide.blocks()[0].text(), "wide");
+    let tall = table.cell(0, 2).unwrap();
+    assert_eq!(tall.row_span(), 2);
+    assert_eq!(tall.blocks()[0].text(), "tall");
+    assert_eq!(table.cell(2, 2).unwrap().blocks()[0].text(), "e");
+
+    let text = doc.to_plain_text().unwrap();
+    for part in ["Before", "After", "wide", "tall", "a", "b", "c", "d", "e"] {
+        assert!(
+            text.lines().any(|l| l == part),
+            "{part} missing from {text:?}"
+        );
+    }
+}
+
+#[test]
+fn docx_import_hyperlinks_and_images() {
+    let body = concat!(
+        r#"<w:p><w:r><w:t xml:space="preserve">See </w:t></w:r>"#,
+        r#"<w:hyperlink r:id="rLink"><w:r><w:t>site</w:t></w:r></w:hyperlink>"#,
+        r#"<w:r><w:drawing><wp:inline><wp:extent cx="952500" cy="476250"/>"#,
+        r#"<a:graphic><a:graphicData><a:blip r:embed="rImg"/></a:graphicData></a:graphic>"#,
+        r#"</wp:inline></w:drawing></w:r><w:r><w:t>!</w:t></w:r></w:p>"#,
+    );
+    let relationships = rels(concat!(
+        r#"<Relationship Id="rLink" Type="hyperlink" Target="https://example.com" TargetMode="External"/>"#,
+        r#"<Relationship Id="rImg" Type="image" Target="media/image1.png"/>"#,
+    ));
+    let png: &[u8] = &[137, 80, 78, 71, 1, 2, 3];
+    let doc = import(&docx(
+        body,
+        &[
+            ("word/_rels/document.xml.rels", &relationships),
+            ("word/media/image1.png", png),
+        ],
+    ));
+
+    let link = doc.cursor_at(5).char_format().unwrap();
+    assert_eq!(link.anchor_href.as_deref(), Some("https://example.com"));
+
+    let block = doc.block_at_position(0).unwrap();
+    let image = block
+        .fragments()
+        .into_iter()
+        .find_map(|f| match f {
+            FragmentContent::Image {
+                name,
+                width,
+                height,
+                ..
+            } => Some((name, width, height)),
+            _ => None,
+        })
+        .expect("image fragment");
+    assert_eq!(image, ("image1.png".to_string(), 100, 50));
+    assert_eq!(doc.resource("image1.png").unwrap(), Some(png.to_vec()));
+    assert!(doc.to_html().unwrap().contains(r#"<img src="image1.png""#));
+}
+
+#[test]
+fn docx_import_roundtrips_own_export() {
+    let source = TextDocument::new();
+    source
+        .set_plain_text("Heading text\nSome bold words")
+        .unwrap();
+    let c = source.cursor_at(18);
+    c.set_position(22, MoveMode::KeepAnchor);
+    c.set_char_format(&TextFormat {
+        font_bold: Some(true),
+        ..Default::default()
+    })
+    .unwrap();
+
+    let path = std::env::temp_dir().join("test_docx_import_roundtrip.docx");
+    source
+        .to_docx(path.to_str().unwrap())
+        .unwrap()
+        .wait()
+        .unwrap();
+    let bytes = std::fs::read(&path).unwrap();
+    let _ = std::fs::remove_file(&path);
+
+    let doc = TextDocument::new();
+    let result = doc.set_docx(&bytes).unwrap().wait().unwrap();
+    assert_eq!(result.block_count, 2);
+    assert_eq!(
+        doc.to_plain_text().unwrap(),
+        "Heading text\nSome bold words"
+    );
+    assert_eq!(
+        doc.cursor_at(20).char_format().unwrap().font_bold,
+        Some(true)
+    );
+    assert_eq!(doc.cursor_at(15).char_format().unwrap().font_bold, None);
+}
+
+#[test]
+fn docx_import_rejects_invalid_data_and_keeps_document() {
+    let doc = TextDocument::new();
+    doc.set_plain_text("keep me").unwrap();
+    let err = doc.set_docx(b"not a zip").unwrap().wait().unwrap_err();
+    assert!(format!("{err:#}").contains("DOCX"), "{err:#}");
+    assert_eq!(doc.to_plain_text().unwrap(), "keep me");
+}
@@ -0,0 +1,346 @@
+//! Tests for recording editing sessions and replaying them into a fresh
+//! document.
+
+use proptest::prelude::*;
+use test_harness::replay::{assert_replay_matches, assert_replays_to};
+use text_document::{
+    BlockFormat, CellFormat, EditAction, EditLog, FindOptions, FormatQuery, ListFormat, ListStyle,
+    MoveMode, MoveOperation, ResourceType, TextDocument, TextFormat, UndoLimit,
+};
+
+fn recording(text: &str) -> TextDocument {
+    let doc = TextDocument::new();
+    doc.set_plain_text(text).unwrap();
+    doc.start_recording().unwrap();
+    doc
+}
+
+fn bold() -> TextFormat {
+    TextFormat {
+        font_bold: Some(true),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn typing_formatting_and_undo_replay() {
+    let doc = recording("Hello");
+    let cursor = doc.cursor_at(5);
+    cursor.insert_text(" world").unwrap();
+    cursor.insert_block().unwrap();
+    cursor.insert_formatted_text("Second", &bold()).unwrap();
+    cursor.set_position(0, MoveMode::MoveAnchor);
+    cursor.move_position(MoveOperation::EndOfWord, MoveMode::KeepAnchor, 1);
+    cursor.set_char_format(&bold()).unwrap();
+    cursor
+        .set_block_format(&BlockFormat {
+            heading_level: Some(2),
+            ..Default::default()
+        })
+        .unwrap();
+    cursor.delete_previous_char().unwrap();
+    doc.undo().unwrap();
+    doc.undo().unwrap();
+    doc.redo().unwrap();
+    doc.set_title("Notes").unwrap();
+
+    let log = assert_replay_matches(&doc);
+    assert!(!doc.is_recording());
+    assert_eq!(log.edits.len(), 10);
+    assert_eq!(
+        log.edits[0].action,
+        EditAction::InsertText {
+            text: " world".into()
+        }
+    );
+    assert_eq!(log.edits[0].cursor.unwrap().position, 5);
+    assert!(log.edits[7].cursor.is_none());
+}
+
+#[test]
+fn table_and_list_edits_replay() {
+    let doc = recording("Intro");
+    let cursor = doc.cursor_at(5);
+    cursor.insert_block().unwrap();
+    let table = cursor.insert_table(2, 2).unwrap();
+    cursor.insert_text("a1").unwrap();
+    cursor.insert_row_below().unwrap();
+    cursor.insert_column_after().unwrap();
+    cursor
+        .set_current_cell_format(&CellFormat {
+            padding: Some(4),
+            ..Default::default()
+        })
+        .unwrap();
+    cursor.merge_table_cells(table.id(), 1, 0, 1, 1).unwrap();
+    cursor.remove_current_column().unwrap();
+
+    let list_cursor = doc.cursor_at(0);
+    list_cursor.create_list(ListStyle::Decimal).unwrap();
+    list_cursor
+        .set_current_list_format(&ListFormat {
+            prefix: Some("(".into()),
+            ..Default::default()
+        })
+        .unwrap();
+
+    let log = assert_replay_matches(&doc);
+    // Convenience wrappers are logged as the call they forward to.
+    assert!(
+        log.edits
+            .iter()
+            .any(|e| matches!(e.action, EditAction::InsertTableRow { row_index: 1, .. }))
+    );
+}
+
+#[test]
+fn imports_fragments_and_resources_replay() {
+    let doc = recording("");
+    doc.set_markdown("# Title\n\nSome *text*")
+        .unwrap()
+        .wait()
+        .unwrap();
+    doc.add_resource(ResourceType::Image, "dot.png", "image/png", &[1, 2, 3])
+        .unwrap();
+    let cursor = doc.cursor_at(5);
+    cursor.insert_image("dot.png", 4, 4).unwrap();
+    cursor.insert_html("<p>one</p><p><b>two</b></p>").unwrap();
+    cursor.insert_markdown("**three**").unwrap();
+    let source = doc.cursor_at(0);
+    source.move_position(MoveOperation::EndOfBlock, MoveMode::KeepAnchor, 1);
+    let fragment = source.selection();
+    cursor.insert_fragment(&fragment).unwrap();
+    doc.replace_text("text", "words", true, &FindOptions::default())
+        .unwrap();
+    let italic = TextFormat {
+        font_italic: Some(true),
+        ..Default::default()
+    };
+    let query = FormatQuery {
+        font_bold: Some(true),
+        ..Default::default()
+    };
+    doc.replace_format(&query, &italic, &BlockFormat::default())
+        .unwrap();
+
+    let log = assert_replay_matches(&doc);
+    assert!(
+        log.edits
+            .iter()
+            .any(|e| matches!(&e.action, EditAction::InsertHtml { .. }))
+    );
+}
+
+#[test]
+fn undo_into_history_from_before_recording_replays() {
+    let doc = TextDocument::new();
+    doc.set_plain_text("abc").unwrap();
+    doc.cursor_at(3).insert_text("def").unwrap();
+    let cursor = doc.cursor_at(0);
+    cursor.set_position(3, MoveMode::KeepAnchor);
+    cursor.set_char_format(&bold()).unwrap();
+
+    doc.start_recording().unwrap();
+    doc.undo().unwrap();
+    doc.undo().unwrap();
+    doc.redo().unwrap();
+    doc.cursor_at(0).insert_text(">").unwrap();
+
+    let replayed = assert_replays_to(&doc.stop_recording().unwrap(), &doc);
+    assert_eq!(replayed.to_plain_text().unwrap(), ">abcdef");
+}
+
+#[test]
+fn separate_undo_stacks_replay() {
+    let doc = recording("Caption\nBody");
+    let stack = doc.create_undo_stack();
+    let caption = doc.cursor_at(7);
+    caption.set_undo_stack(Some(stack));
+    caption.insert_text(" one").unwrap();
+    caption.begin_edit_block();
+    caption.insert_text(" two").unwrap();
+    caption.insert_text(" three").unwrap();
+    caption.end_edit_block();
+    doc.cursor_at(doc.character_count() + 1)
+        .insert_text(" text")
+        .unwrap();
+    doc.undo_in(stack).unwrap();
+    doc.set_undo_limit(UndoLimit {
+        max_entries: Some(8),
+        max_bytes: None,
+    })
+    .unwrap();
+    doc.set_undo_limit_in(
+        stack,
+        UndoLimit {
+            max_entries: Some(1),
+            max_bytes: None,
+        },
+    )
+    .unwrap();
+
+    let replayed = assert_replays_to(&doc.edit_log().unwrap(), &doc);
+    assert_eq!(replayed.to_plain_text().unwrap(), "Caption one\nBody text");
+    // Peeking at the log does not stop recording.
+    assert!(doc.is_recording());
+}
+
+#[test]
+fn start_recording_with_a_second_stack_leaves_history_alone() {
+    let doc = TextDocument::new();
+    doc.set_plain_text("Caption\nBody").unwrap();
+    let stack = doc.create_undo_stack();
+    let caption = doc.cursor_at(7);
+    caption.set_undo_stack(Some(stack));
+    caption.insert_text(" one").unwrap();
+    doc.cursor_at(doc.character_count() + 1)
+        .insert_text(" text")
+        .unwrap();
+    caption.insert_text(" two").unwrap();
+
+    doc.start_recording().unwrap();
+    assert_eq!(doc.to_plain_text().unwrap(), "Caption one two\nBody text");
+    assert!(doc.can_undo());
+    assert!(doc.can_undo_in(stack));
+
+    doc.undo().unwrap();
+    let replayed = assert_replays_to(&doc.stop_recording().unwrap(), &doc);
+    assert_eq!(replayed.to_plain_text().unwrap(), "Caption one two\nBody");
+
+    // The second stack's history is still whole in the recorded document.
+    doc.undo_in(stack).unwrap();
+    assert_eq!(doc.to_plain_text().unwrap(), "Caption\nBody");
+}
+
+#[test]
+fn cancelled_edit_block_replays() {
+    let doc = recording("Hello");
+    let cursor = doc.cursor_at(5);
+    cursor.insert_text(" world").unwrap();
+    cursor.begin_edit_block();
+    cursor.insert_text("!").unwrap();
+    cursor.insert_block().unwrap();
+    cursor.cancel_edit_block();
+    assert_eq!(doc.to_plain_text().unwrap(), "Hello world");
+    assert_eq!(doc.undo_history().entries.len(), 1);
+
+    let replayed = assert_replays_to(&doc.stop_recording().unwrap(), &doc);
+    assert_eq!(replayed.to_plain_text().unwrap(), "Hello world");
+}
+
+#[test]
+fn replaying_a_history_step_that_cannot_be_taken_fails() {
+    let doc = recording("Hello");
+    doc.cursor_at(5).insert_text(" world").unwrap();
+    doc.undo().unwrap();
+    doc.redo().unwrap();
+    doc.undo_to(0).unwrap();
+    let log = doc.stop_recording().unwrap();
+    assert!(TextDocument::replay(&log).is_ok());
+
+    // Without the typing there is nothing to undo.
+    let mut drifted = log.clone();
+    drifted.edits.remove(0);
+    assert!(TextDocument::replay(&drifted).is_err());
+
+    // Without the undo there is nothing to redo.
+    let mut drifted = log.clone();
+    drifted.edits.remove(1);
+    assert!(TextDocument::replay(&drifted).is_err());
+
+    // Without the redo the history is already at index 0.
+    let mut drifted = log;
+    drifted.edits.remove(2);
+    assert!(TextDocument::replay(&drifted).is_err());
+}
+
+#[test]
+fn log_serializes_as_tagged_actions() {
+    let doc = recording("x");
+    doc.cursor_at(1).insert_text("y").unwrap();
+    let log = doc.stop_recording().unwrap();
+    let json = log.to_json().unwrap();
+    assert!(json.contains(r#""op":"insert_text""#), "{json}");
+    assert_eq!(EditLog::from_json(&json).unwrap(), log);
+
+    let newer = EditLog {
+        version: text_document::EDIT_LOG_VERSION + 1,
+        ..log
+    };
+    assert!(EditLog::from_json(&newer.to_json().unwrap()).is_err());
+    assert!(TextDocument::replay(&newer).is_err());
+}
+
+#[test]
+fn nothing_is_logged_outside_recording() {
+    let doc = TextDocument::new();
+    doc.cursor().insert_text("before").unwrap();
+    assert!(doc.stop_recording().is_none());
+
+    doc.start_recording().unwrap();
+    doc.cursor().insert_text("during ").unwrap();
+    let log = doc.stop_recording().unwrap();
+    doc.cursor().insert_text("after ").unwrap();
+    assert_eq!(log.edits.len(), 1);
+
+    let replayed = TextDocument::replay(&log).unwrap();
+    assert_eq!(replayed.to_plain_text().unwrap(), "during before");
+}
+
+#[derive(Debug, Clone)]
+enum Step {
+    Type { at: usize, text: String },
+    Split { at: usize },
+    Delete { at: usize, len: usize },
+    Bold { at: usize, len: usize },
+    Undo,
+    Redo,
+}
+
+fn step() -> impl Strategy<Value = Step> {
+    prop_oneof![
+        4 => (any::<usize>(), "[a-z ]{1,4}").prop_map(|(at, text)| Step::Type { at, text }),
+        1 => any::<usize>().prop_map(|at| Step::Split { at }),
+        2 => (any::<usize>(), 1..4usize).prop_map(|(at, len)| Step::Delete { at, len }),
+        1 => (any::<usize>(), 1..4usize).prop_map(|(at, len)| Step::Bold { at, len }),
+        1 => Just(Step::Undo),
+        1 => Just(Step::Redo),
+    ]
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(32))]
+
+    #[test]
+    fn random_sessions_replay_exactly(steps in proptest::collection::vec(step(), 1..24)) {
+        let doc = recording("one\ntwo");
+        for step in steps {
+            let end = doc.character_count() + doc.block_count() - 1;
+            let cursor = doc.cursor();
+            match step {
+                Step::Type { at, text } => {
+                    cursor.set_position(at % (end + 1), MoveMode::MoveAnchor);
+                    cursor.insert_text(&text).unwrap();
+                }
+                Step::Split { at } => {
+                    cursor.set_position(at % (end + 1), MoveMode::MoveAnchor);
+                    cursor.insert_block().unwrap();
+                }
+                Step::Delete { at, len } | Step::Bold { at, len } if end > 0 => {
+                    let at = at % end;
+                    cursor.set_position(at, MoveMode::MoveAnchor);
+                    cursor.set_position((at + len).min(end), MoveMode::KeepAnchor);
+                    if matches!(step, Step::Delete { .. }) {
+                        cursor.remove_selected_text().unwrap();
+                    } else {
+                        cursor.merge_char_format(&bold()).unwrap();
+                    }
+                }
+                Step::Undo => { let _ = doc.undo(); }
+                Step::Redo => { let _ = doc.redo(); }
+                _ => {}
+            }
+        }
+        assert_replay_matches(&doc);
+    }
+}
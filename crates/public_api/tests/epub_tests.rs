@@ -0,0 +1,163 @@
+//! Tests for EPUB 3 export (`to_epub`).
+
+use std::io::Read;
+
+use text_document::{EpubOptions, ResourceType, TextDocument};
+
+fn export(doc: &TextDocument, name: &str, options: &EpubOptions) -> (usize, Vec<u8>) {
+    let path = std::env::temp_dir().join(name);
+    let result = doc
+        .to_epub(path.to_str().unwrap(), options)
+        .unwrap()
+        .wait()
+        .unwrap();
+    let bytes = std::fs::read(&path).unwrap();
+    let _ = std::fs::remove_file(&path);
+    (result.chapter_count, bytes)
+}
+
+fn read_part(archive: &mut zip::ZipArchive<std::io::Cursor<Vec<u8>>>, name: &str) -> String {
+    let mut text = String::new();
+    archive
+        .by_name(name)
+        .unwrap_or_else(|_| panic!("missing {name}"))
+        .read_to_string(&mut text)
+        .unwrap();
+    text
+}
+
+fn book_document() -> TextDocument {
+    let doc = TextDocument::new();
+    doc.set_html(concat!(
+        "<p>Preface text</p>",
+        "<h1>First &amp; foremost</h1><p>One</p>",
+        "<h2>Details</h2><p>More</p>",
+        "<h1>Second</h1><p>Two</p>",
+    ))
+    .unwrap()
+    .wait()
+    .unwrap();
+    doc.set_title("My <Book>").unwrap();
+    doc
+}
+
+#[test]
+fn epub_export_writes_valid_container() {
+    let doc = book_document();
+    let png: &[u8] = &[137, 80, 78, 71, 1, 2];
+    doc.add_resource(ResourceType::Image, "logo.png", "image/png", png)
+        .unwrap();
+    doc.cursor_at(3).insert_image("logo.png", 20, 10).unwrap();
+
+    let options = EpubOptions {
+        author: "Jane Doe".into(),
+        language: "fr".into(),
+        identifier: "isbn:123".into(),
+        ..Default::default()
+    };
+    let (_, bytes) = export(&doc, "test_epub_export_container.epub", &options);
+    let mut archive = zip::ZipArchive::new(std::io::Cursor::new(bytes)).unwrap();
+
+    let mimetype = archive.by_index(0).unwrap();
+    assert_eq!(mimetype.name(), "mimetype");
+    assert_eq!(mimetype.compression(), zip::CompressionMethod::Stored);
+    drop(mimetype);
+    assert_eq!(read_part(&mut archive, "mimetype"), "application/epub+zip");
+    assert!(
+        read_part(&mut archive, "META-INF/container.xml")
+            .contains(r#"full-path="OEBPS/content.opf""#)
+    );
+
+    let opf = read_part(&mut archive, "OEBPS/content.opf");
+    assert!(opf.contains(r#"version="3.0""#), "{opf}");
+    assert!(
+        opf.contains("<dc:title>My &lt;Book&gt;</dc:title>"),
+        "{opf}"
+    );
+    assert!(opf.contains("<dc:creator>Jane Doe</dc:creator>"), "{opf}");
+    assert!(opf.contains("<dc:language>fr</dc:language>"), "{opf}");
+    assert!(opf.contains(">isbn:123</dc:identifier>"), "{opf}");
+    assert!(opf.contains(r#"property="dcterms:modified""#), "{opf}");
+    assert!(opf.contains(r#"properties="nav""#), "{opf}");
+    assert!(
+        opf.contains(r#"href="images/logo.png" media-type="image/png""#),
+        "{opf}"
+    );
+    assert!(opf.contains(r#"<itemref idref="chapter-3"/>"#), "{opf}");
+
+    let mut image = Vec::new();
+    archive
+        .by_name("OEBPS/images/logo.png")
+        .unwrap()
+        .read_to_end(&mut image)
+        .unwrap();
+    assert_eq!(image, png);
+    let preface = read_part(&mut archive, "OEBPS/chapter-1.xhtml");
+    assert!(
+        preface.contains(r#"<img src="images/logo.png""#),
+        "{preface}"
+    );
+    assert!(preface.contains(r#"xmlns="http://www.w3.org/1999/xhtml""#));
+}
+
+#[test]
+fn epub_export_splits_chapters_at_heading_level() {
+    let doc = book_document();
+
+    let (count, bytes) = export(
+        &doc,
+        "test_epub_export_split_h1.epub",
+        &EpubOptions::default(),
+    );
+    assert_eq!(count, 3);
+    let mut archive = zip::ZipArchive::new(std::io::Cursor::new(bytes)).unwrap();
+    let preface = read_part(&mut archive, "OEBPS/chapter-1.xhtml");
+    assert!(
+        preface.contains("<title>My &lt;Book&gt;</title>"),
+        "{preface}"
+    );
+    assert!(preface.contains("Preface text"));
+    let first = read_part(&mut archive, "OEBPS/chapter-2.xhtml");
+    assert!(
+        first.contains("<title>First &amp; foremost</title>"),
+        "{first}"
+    );
+    assert!(first.contains(r#"<h2 id="h-2">Details</h2>"#), "{first}");
+    assert!(!first.contains("Two"));
+    assert!(read_part(&mut archive, "OEBPS/chapter-3.xhtml").contains("Two"));
+    assert!(archive.by_name("OEBPS/chapter-4.xhtml").is_err());
+
+    let nav = read_part(&mut archive, "OEBPS/nav.xhtml");
+    assert!(nav.contains(r#"epub:type="toc""#), "{nav}");
+    assert!(
+        nav.contains(concat!(
+            r#"<li><a href="chapter-2.xhtml#h-1">First &amp; foremost</a>"#,
+            r#"<ol><li><a href="chapter-2.xhtml#h-2">Details</a></li></ol></li>"#,
+        )),
+        "{nav}"
+    );
+    assert!(nav.contains(r#"<a href="chapter-1.xhtml">My &lt;Book&gt;</a>"#));
+
+    let (count, _) = export(
+        &doc,
+        "test_epub_export_split_h2.epub",
+        &EpubOptions {
+            heading_level: 2,
+            ..Default::default()
+        },
+    );
+    assert_eq!(count, 4);
+}
+
+#[test]
+fn epub_export_of_empty_document_has_one_chapter() {
+    let doc = TextDocument::new();
+    let (count, bytes) = export(&doc, "test_epub_export_empty.epub", &EpubOptions::default());
+    assert_eq!(count, 1);
+    let mut archive = zip::ZipArchive::new(std::io::Cursor::new(bytes)).unwrap();
+    let opf = read_part(&mut archive, "OEBPS/content.opf");
+    assert!(opf.contains("<dc:title>Untitled</dc:title>"), "{opf}");
+    assert!(opf.contains("<dc:language>en</dc:language>"), "{opf}");
+    assert!(opf.contains(">urn:uuid:"), "{opf}");
+    assert!(!opf.contains("dc:creator"), "{opf}");
+}
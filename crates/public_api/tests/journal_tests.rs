@@ -0,0 +1,296 @@
+//! Tests for the crash-recovery journal.
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use test_harness::replay::assert_same_document;
+use text_document::{FlowElement, JournalOptions, MoveMode, SyncPolicy, TextDocument, TextFormat};
+
+/// A fresh, empty directory for one test.
+fn journal_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir()
+        .join("text-document-journal-tests")
+        .join(format!("{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+fn edit_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)
+        .unwrap()
+        .map(|e| e.unwrap().path())
+        .filter(|p| p.extension().is_some_and(|e| e == "jsonl"))
+        .collect();
+    files.sort();
+    files
+}
+
+fn bold() -> TextFormat {
+    TextFormat {
+        font_bold: Some(true),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn recover_rebuilds_document_after_crash() {
+    let dir = journal_dir("crash");
+    let doc = TextDocument::new();
+    doc.set_plain_text("Draft").unwrap();
+    doc.start_journal(JournalOptions::new(&dir)).unwrap();
+    assert!(doc.is_journaling());
+
+    let cursor = doc.cursor_at(5);
+    cursor.insert_text(" one").unwrap();
+    cursor.insert_block().unwrap();
+    cursor.insert_text("two").unwrap();
+    cursor.set_position(0, MoveMode::KeepAnchor);
+    cursor.set_char_format(&bold()).unwrap();
+    doc.undo().unwrap();
+    doc.set_title("Notes").unwrap();
+
+    // Nothing is stopped or saved: the process "crashes" here.
+    let recovered = TextDocument::recover(&dir).unwrap().unwrap();
+    assert_same_document(&recovered, &doc);
+    assert_eq!(recovered.to_plain_text().unwrap(), "Draft one\ntwo");
+    assert!(recovered.is_modified());
+    assert!(!recovered.is_journaling());
+}
+
+#[test]
+fn checkpoints_bound_the_edits_to_replay() {
+    let dir = journal_dir("checkpoint");
+    let doc = TextDocument::new();
+    doc.start_journal(JournalOptions {
+        checkpoint_every: 3,
+        ..JournalOptions::new(&dir)
+    })
+    .unwrap();
+    let cursor = doc.cursor();
+    // Typing split by new blocks, so that no undo entry spans a
+    // checkpoint (see the journal module docs).
+    for word in ["a", "b", "c", "d", "e"] {
+        cursor.insert_text(word).unwrap();
+        cursor.insert_block().unwrap();
+    }
+
+    let files = edit_files(&dir);
+    assert_eq!(files.len(), 1, "old edit files are removed: {files:?}");
+    let lines = fs::read_to_string(&files[0]).unwrap().lines().count();
+    assert!(lines <= 3, "{lines} edits since the last checkpoint");
+    assert_same_document(&TextDocument::recover(&dir).unwrap().unwrap(), &doc);
+
+    doc.checkpoint_journal().unwrap();
+    assert_eq!(fs::read_to_string(&edit_files(&dir)[0]).unwrap(), "");
+    assert_same_document(&TextDocument::recover(&dir).unwrap().unwrap(), &doc);
+}
+
+#[test]
+fn checkpoints_alongside_a_second_undo_stack() {
+    let dir = journal_dir("second-stack");
+    let doc = TextDocument::new();
+    doc.set_plain_text("Caption\nBody").unwrap();
+    doc.start_journal(JournalOptions {
+        checkpoint_every: 2,
+        ..JournalOptions::new(&dir)
+    })
+    .unwrap();
+    let stack = doc.create_undo_stack();
+    let caption = doc.cursor_at(7);
+    caption.set_undo_stack(Some(stack));
+    for word in [" one", " two", " three"] {
+        caption.insert_text(word).unwrap();
+        doc.cursor_at(doc.character_count() + 1)
+            .insert_text(word)
+            .unwrap();
+        doc.cursor_at(doc.character_count() + 1)
+            .insert_block()
+            .unwrap();
+    }
+
+    assert_eq!(doc.journal_error(), None);
+    assert!(doc.can_undo_in(stack));
+    let recovered = TextDocument::recover(&dir).unwrap().unwrap();
+    let text = doc.to_plain_text().unwrap();
+    assert!(text.starts_with("Caption one two three\nBody one two three"));
+    assert_eq!(recovered.to_plain_text().unwrap(), text);
+    assert_eq!(recovered.to_html().unwrap(), doc.to_html().unwrap());
+    recovered.undo().unwrap();
+    doc.undo().unwrap();
+    assert_eq!(recovered.to_html().unwrap(), doc.to_html().unwrap());
+}
+
+#[test]
+fn torn_last_edit_is_dropped() {
+    let dir = journal_dir("torn");
+    let doc = TextDocument::new();
+    doc.start_journal(JournalOptions::new(&dir)).unwrap();
+    doc.cursor().insert_text("kept").unwrap();
+
+    let path = edit_files(&dir).remove(0);
+    let mut file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+    file.write_all(br#"{"action":{"op":"insert_te"#).unwrap();
+    drop(file);
+    let recovered = TextDocument::recover(&dir).unwrap().unwrap();
+    assert_eq!(recovered.to_plain_text().unwrap(), "kept");
+
+    // A bad line followed by good ones is corruption, not a torn write.
+    let good = fs::read_to_string(&path).unwrap();
+    let good = good.lines().next().unwrap();
+    fs::write(&path, format!("garbage\n{good}\n")).unwrap();
+    assert!(TextDocument::recover(&dir).is_err());
+}
+
+#[test]
+fn stop_journal_leaves_nothing_to_recover() {
+    let dir = journal_dir("stop");
+    assert!(TextDocument::recover(&dir).unwrap().is_none());
+
+    let doc = TextDocument::new();
+    doc.start_journal(JournalOptions::new(&dir)).unwrap();
+    doc.cursor().insert_text("saved").unwrap();
+    doc.stop_journal().unwrap();
+    assert!(!doc.is_journaling());
+    doc.cursor().insert_text("not journaled ").unwrap();
+
+    assert!(TextDocument::recover(&dir).unwrap().is_none());
+    assert!(fs::read_dir(&dir).unwrap().next().is_none());
+}
+
+#[test]
+fn recovered_document_continues_journaling() {
+    let dir = journal_dir("continue");
+    let doc = TextDocument::new();
+    doc.start_journal(JournalOptions {
+        sync: SyncPolicy::Never,
+        ..JournalOptions::new(&dir)
+    })
+    .unwrap();
+    doc.cursor().insert_text("first").unwrap();
+    doc.sync_journal().unwrap();
+    drop(doc);
+
+    let doc = TextDocument::recover(&dir).unwrap().unwrap();
+    doc.start_journal(JournalOptions {
+        sync: SyncPolicy::EveryEdits(2),
+        ..JournalOptions::new(&dir)
+    })
+    .unwrap();
+    doc.cursor_at(5).insert_text(" second").unwrap();
+    assert_eq!(edit_files(&dir).len(), 1);
+
+    let recovered = TextDocument::recover(&dir).unwrap().unwrap();
+    assert_eq!(recovered.to_plain_text().unwrap(), "first second");
+    assert!(doc.journal_error().is_none());
+}
+
+#[test]
+fn start_journal_refuses_an_unrecovered_journal() {
+    let dir = journal_dir("unrecovered");
+    let crashed = TextDocument::new();
+    crashed.start_journal(JournalOptions::new(&dir)).unwrap();
+    crashed.cursor().insert_text("unsaved work").unwrap();
+    drop(crashed);
+
+    // Another document must not overwrite the edits left behind
+    let doc = TextDocument::new();
+    assert!(doc.start_journal(JournalOptions::new(&dir)).is_err());
+    assert!(!doc.is_journaling());
+    let recovered = TextDocument::recover(&dir).unwrap().unwrap();
+    assert_eq!(recovered.to_plain_text().unwrap(), "unsaved work");
+
+    // Restarting in its own directory is fine
+    recovered.start_journal(JournalOptions::new(&dir)).unwrap();
+    recovered.start_journal(JournalOptions::new(&dir)).unwrap();
+    drop(recovered);
+
+    TextDocument::discard_journal(&dir).unwrap();
+    assert!(TextDocument::recover(&dir).unwrap().is_none());
+    doc.start_journal(JournalOptions::new(&dir)).unwrap();
+    assert!(doc.is_journaling());
+}
+
+#[test]
+fn imports_are_journaled() {
+    let dir = journal_dir("imports");
+    let doc = TextDocument::new();
+    doc.start_journal(JournalOptions {
+        checkpoint_every: 1,
+        ..JournalOptions::new(&dir)
+    })
+    .unwrap();
+    doc.set_markdown("# Title\n\nBody").unwrap().wait().unwrap();
+    doc.cursor_at(0).insert_text("New ").unwrap();
+    doc.set_modified(false);
+
+    assert_same_document(&TextDocument::recover(&dir).unwrap().unwrap(), &doc);
+    assert!(doc.checkpoint_journal().is_ok());
+    assert!(TextDocument::new().checkpoint_journal().is_err());
+}
+
+#[test]
+fn entity_ids_survive_checkpoints() {
+    let dir = journal_dir("ids");
+    let doc = TextDocument::new();
+    doc.set_plain_text("Intro").unwrap();
+    doc.start_journal(JournalOptions {
+        checkpoint_every: 1,
+        ..JournalOptions::new(&dir)
+    })
+    .unwrap();
+    let cursor = doc.cursor_at(5);
+    cursor.insert_block().unwrap();
+    cursor.insert_table(2, 2).unwrap();
+    doc.undo().unwrap();
+    // Redoing hands the table new IDs; edits after the checkpoint that
+    // follows must name them the same way in the recovered copy.
+    doc.redo().unwrap();
+    let table = doc
+        .flow()
+        .into_iter()
+        .find_map(|e| match e {
+            FlowElement::Table(t) => Some(t),
+            _ => None,
+        })
+        .unwrap();
+    cursor.insert_table_row(table.id(), 1).unwrap();
+    cursor.insert_table_column(table.id(), 0).unwrap();
+    doc.undo().unwrap();
+    cursor.remove_table_row(table.id(), 0).unwrap();
+
+    assert_same_document(&TextDocument::recover(&dir).unwrap().unwrap(), &doc);
+}
+
+#[test]
+fn undo_after_a_checkpoint_past_a_table_delete() {
+    let dir = journal_dir("table-delete");
+    let doc = TextDocument::new();
+    doc.set_plain_text("abc lorem\nipsum abc\nthird").unwrap();
+    doc.start_journal(JournalOptions::new(&dir)).unwrap();
+    doc.cursor_at(4).insert_table(2, 2).unwrap();
+    let cursor = doc.cursor_at(2);
+    cursor.set_position(doc.character_count() - 3, MoveMode::KeepAnchor);
+    cursor.remove_selected_text().unwrap();
+    let cursor = doc.cursor_at(0);
+    cursor.set_position(2, MoveMode::KeepAnchor);
+    cursor.set_char_format(&bold()).unwrap();
+
+    doc.checkpoint_journal().unwrap();
+    doc.undo().unwrap();
+    doc.undo().unwrap();
+
+    // The checkpoint kept the history, so both undos replay.
+    assert_eq!(doc.journal_error(), None);
+    let recovered = TextDocument::recover(&dir).unwrap().unwrap();
+    assert_same_document(&recovered, &doc);
+    assert!(
+        recovered
+            .flow()
+            .iter()
+            .any(|e| matches!(e, FlowElement::Table(_)))
+    );
+    recovered.undo().unwrap();
+    doc.undo().unwrap();
+    assert_same_document(&recovered, &doc);
+}
@@ -1,8 +1,8 @@
 use text_document::{
     Alignment, BlockFormat, BlockInfo, CharVerticalAlignment, DocumentFragment, DocumentStats,
     FindMatch, FindOptions, FrameFormat, FramePosition, ListStyle, MarkerType, MoveMode,
-    MoveOperation, ResourceType, SelectionType, TextDirection, TextDocument, TextFormat,
-    UnderlineStyle, WrapMode,
+    MoveOperation, ResourceType, SearchNormalization, SelectionType, TextDirection, TextDocument,
+    TextFormat, UnderlineStyle, WrapMode,
 };
 
 // ── Re-exported enums ────────────────────────────────────────────
@@ -297,12 +297,34 @@ fn find_options_debug_clone() {
         whole_word: true,
         use_regex: false,
         search_backward: true,
+        ..Default::default()
     };
     let cloned = opts.clone();
     assert_eq!(opts.case_sensitive, cloned.case_sensitive);
     let _ = format!("{:?}", opts);
 }
 
+#[test]
+fn find_options_builder() {
+    let opts = FindOptions::default()
+        .with_case_sensitive(true)
+        .with_whole_word(true)
+        .with_normalization(SearchNormalization::Nfc)
+        .with_fold_accents(true)
+        .with_fuzzy(Some(1));
+    assert_eq!(
+        opts,
+        FindOptions {
+            case_sensitive: true,
+            whole_word: true,
+            normalization: SearchNormalization::Nfc,
+            fold_accents: true,
+            fuzzy: Some(1),
+            ..Default::default()
+        }
+    );
+}
+
 // ── TextDocument: Default impl ───────────────────────────────────
 
 #[test]
@@ -0,0 +1,620 @@
+//! Tests for the lossless native save/load format.
+
+use proptest::prelude::*;
+use text_document::{
+    FrameFormat, ListFormat, ListStyle, MoveMode, ResourceType, TextDocument, TextFormat,
+};
+
+fn save(doc: &TextDocument) -> Vec<u8> {
+    let mut buf = Vec::new();
+    doc.save_native(&mut buf).unwrap();
+    buf
+}
+
+/// A document exercising everything the text exporters drop.
+fn rich_doc() -> TextDocument {
+    let doc = TextDocument::new();
+    doc.set_plain_text("Bold start\nList item\nAfter table")
+        .unwrap();
+    doc.set_title("Native").unwrap();
+
+    let c = doc.cursor();
+    c.set_position(4, MoveMode::KeepAnchor);
+    c.set_char_format(&TextFormat {
+        font_bold: Some(true),
+        font_family: Some("Serif".into()),
+        ..Default::default()
+    })
+    .unwrap();
+
+    let c = doc.cursor_at(12);
+    c.create_list(ListStyle::UpperRoman).unwrap();
+    let list = c.current_list().unwrap();
+    c.set_list_format(
+        list.id(),
+        &ListFormat {
+            prefix: Some("(".into()),
+            suffix: Some(")".into()),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    let c = doc.cursor_at(doc.character_count());
+    c.insert_table(2, 2).unwrap();
+    c.insert_frame().unwrap();
+
+    doc.add_resource(
+        ResourceType::Image,
+        "pixel.png",
+        "image/png",
+        &[137, 80, 78, 71],
+    )
+    .unwrap();
+    doc
+}
+
+#[test]
+fn native_roundtrip_preserves_flow_and_text() {
+    let doc = rich_doc();
+    let bytes = save(&doc);
+
+    let loaded = TextDocument::new();
+    loaded.load_native(bytes.as_slice()).unwrap();
+
+    assert_eq!(
+        loaded.to_plain_text().unwrap(),
+        doc.to_plain_text().unwrap()
+    );
+    assert_eq!(loaded.snapshot_flow(), doc.snapshot_flow());
+    assert_eq!(loaded.stats(), doc.stats());
+    assert_eq!(loaded.title(), "Native");
+}
+
+#[test]
+fn native_roundtrip_preserves_formats_lists_and_resources() {
+    let doc = rich_doc();
+    let loaded = TextDocument::new();
+    loaded.load_native(save(&doc).as_slice()).unwrap();
+
+    let c = loaded.cursor_at(2);
+    let fmt = c.char_format().unwrap();
+    assert_eq!(fmt.font_bold, Some(true));
+    assert_eq!(fmt.font_family.as_deref(), Some("Serif"));
+
+    let list = loaded.cursor_at(12).current_list().unwrap();
+    assert_eq!(list.style(), ListStyle::UpperRoman);
+    assert_eq!(list.prefix(), "(");
+    assert_eq!(list.suffix(), ")");
+
+    assert_eq!(
+        loaded.resource("pixel.png").unwrap(),
+        Some(vec![137, 80, 78, 71])
+    );
+}
+
+#[test]
+fn native_save_is_deterministic() {
+    let doc = rich_doc();
+    let first = save(&doc);
+    let loaded = TextDocument::new();
+    loaded.load_native(first.as_slice()).unwrap();
+    assert_eq!(save(&loaded), first);
+}
+
+#[test]
+fn native_load_clears_undo_and_allows_editing() {
+    let doc = rich_doc();
+    let loaded = TextDocument::new();
+    loaded.set_plain_text("old").unwrap();
+    loaded.cursor_at(3).insert_text("!").unwrap();
+    assert!(loaded.can_undo());
+
+    loaded.load_native(save(&doc).as_slice()).unwrap();
+    assert!(!loaded.can_undo());
+
+    loaded.cursor_at(0).insert_text(">> ").unwrap();
+    assert!(loaded.to_plain_text().unwrap().starts_with(">> Bold start"));
+    loaded.undo().unwrap();
+    assert!(loaded.to_plain_text().unwrap().starts_with("Bold start"));
+
+    let c = loaded.cursor_at(0);
+    c.insert_frame().unwrap();
+    let frame = loaded.block_at_position(c.position()).unwrap().frame();
+    c.set_frame_format(
+        frame.id(),
+        &FrameFormat {
+            border: Some(2),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+}
+
+#[test]
+fn native_roundtrip_after_deleting_across_a_table() {
+    let doc = TextDocument::new();
+    doc.set_plain_text("Hello world\nSecond").unwrap();
+    doc.cursor_at(3).insert_table(2, 2).unwrap();
+    let c = doc.cursor_at(0);
+    c.set_position(doc.character_count(), MoveMode::KeepAnchor);
+    c.remove_selected_text().unwrap();
+
+    for bytes in [save(&doc), save_with_history(&doc).into_bytes()] {
+        let loaded = TextDocument::new();
+        loaded.load_native(bytes.as_slice()).unwrap();
+        assert_eq!(loaded.to_html().unwrap(), doc.to_html().unwrap());
+        assert_eq!(loaded.snapshot_flow(), doc.snapshot_flow());
+
+        let end = loaded.character_count();
+        loaded.cursor_at(end).insert_text("!").unwrap();
+        assert_eq!(
+            loaded.to_plain_text().unwrap(),
+            doc.to_plain_text().unwrap() + "!"
+        );
+    }
+}
+
+#[test]
+fn native_load_rejects_newer_version_and_keeps_document() {
+    let doc = TextDocument::new();
+    doc.set_plain_text("keep me").unwrap();
+
+    let text = String::from_utf8(save(&doc)).unwrap();
+    let newer = text.replacen("\"version\":1", "\"version\":999", 1);
+    let err = doc.load_native(newer.as_bytes()).unwrap_err();
+    assert!(format!("{err:#}").contains("newer"), "{err:#}");
+    assert_eq!(doc.to_plain_text().unwrap(), "keep me");
+}
+
+#[test]
+fn native_load_rejects_unknown_older_version() {
+    let doc = TextDocument::new();
+    doc.set_plain_text("keep me").unwrap();
+
+    let text = String::from_utf8(save(&doc)).unwrap();
+    let older = text.replacen("\"version\":1", "\"version\":0", 1);
+    let err = format!("{:#}", doc.load_native(older.as_bytes()).unwrap_err());
+    assert!(err.contains("Unknown native format version 0"), "{err}");
+    assert!(!err.contains("newer"), "{err}");
+    assert_eq!(doc.to_plain_text().unwrap(), "keep me");
+}
+
+#[test]
+fn native_load_rejects_garbage() {
+    let doc = TextDocument::new();
+    doc.set_plain_text("keep me").unwrap();
+    assert!(doc.load_native(&b"# not native"[..]).is_err());
+    assert!(
+        doc.load_native(&br#"{"format":"something-else","version":1}"#[..])
+            .is_err()
+    );
+    assert_eq!(doc.to_plain_text().unwrap(), "keep me");
+}
+
+fn save_with_history(doc: &TextDocument) -> String {
+    let mut buf = Vec::new();
+    doc.save_native_with_history(&mut buf).unwrap();
+    String::from_utf8(buf).unwrap()
+}
+
+fn descriptions(doc: &TextDocument) -> Vec<String> {
+    doc.undo_history()
+        .entries
+        .into_iter()
+        .map(|e| e.description)
+        .collect()
+}
+
+/// A document with undoable and redoable edits of several kinds.
+fn edited_doc() -> TextDocument {
+    let doc = TextDocument::new();
+    doc.set_plain_text("Hello").unwrap();
+    let c = doc.cursor_at(5);
+    c.insert_text(" world").unwrap();
+    c.insert_block().unwrap();
+    c.insert_text("Second").unwrap();
+    c.set_position(0, MoveMode::MoveAnchor);
+    c.set_position(5, MoveMode::KeepAnchor);
+    c.set_char_format(&TextFormat {
+        font_bold: Some(true),
+        ..Default::default()
+    })
+    .unwrap();
+    doc.cursor_at(doc.character_count())
+        .insert_table(2, 2)
+        .unwrap();
+    doc.undo().unwrap();
+    doc
+}
+
+#[test]
+fn native_history_roundtrip_restores_undo_and_redo() {
+    let doc = edited_doc();
+    let history = doc.undo_history();
+    let text = doc.to_plain_text().unwrap();
+
+    let data = save_with_history(&doc);
+    // Capturing the history leaves the document and its history as they were.
+    assert_eq!(doc.to_plain_text().unwrap(), text);
+    assert_eq!(doc.undo_history(), history);
+
+    let loaded = TextDocument::new();
+    loaded.load_native(data.as_bytes()).unwrap();
+    assert_eq!(loaded.to_plain_text().unwrap(), text);
+    assert_eq!(descriptions(&loaded), descriptions(&doc));
+    assert_eq!(loaded.undo_history().current_index, history.current_index);
+
+    while doc.can_undo() {
+        doc.undo().unwrap();
+        loaded.undo().unwrap();
+        assert_eq!(loaded.to_html().unwrap(), doc.to_html().unwrap());
+    }
+    assert!(!loaded.can_undo());
+    while doc.can_redo() {
+        doc.redo().unwrap();
+        loaded.redo().unwrap();
+        // Replayed edits reuse the saved entity IDs, so compare content.
+        assert_eq!(loaded.to_html().unwrap(), doc.to_html().unwrap());
+    }
+    assert!(!loaded.can_redo());
+}
+
+#[test]
+fn native_history_restored_edits_keep_formatting() {
+    let doc = edited_doc();
+    let loaded = TextDocument::new();
+    loaded
+        .load_native(save_with_history(&doc).as_bytes())
+        .unwrap();
+
+    assert_eq!(
+        loaded.cursor_at(2).char_format().unwrap().font_bold,
+        Some(true)
+    );
+    loaded.undo().unwrap();
+    assert_ne!(
+        loaded.cursor_at(2).char_format().unwrap().font_bold,
+        Some(true)
+    );
+    loaded.redo().unwrap();
+    assert_eq!(
+        loaded.cursor_at(2).char_format().unwrap().font_bold,
+        Some(true)
+    );
+
+    // New edits stack on top of the restored history.
+    loaded.cursor_at(0).insert_text(">").unwrap();
+    assert!(!loaded.can_redo());
+    loaded.undo().unwrap();
+    loaded.undo().unwrap();
+    assert_eq!(loaded.to_plain_text().unwrap(), "Hello world\nSecond");
+}
+
+#[test]
+fn native_history_save_keeps_entity_ids() {
+    let doc = edited_doc();
+    doc.redo().unwrap();
+    let without_counters = |doc: &TextDocument| {
+        let mut value: serde_json::Value = serde_json::from_slice(&save(doc)).unwrap();
+        value.as_object_mut().unwrap().remove("counters");
+        value
+    };
+    let before = without_counters(&doc);
+    save_with_history(&doc);
+    assert_eq!(without_counters(&doc), before);
+}
+
+#[test]
+fn native_history_save_leaves_commands_alone() {
+    let doc = edited_doc();
+    let twin = edited_doc();
+    save_with_history(&doc);
+
+    // The stack still holds the original commands: undo and redo behave
+    // as on a document that was never saved.
+    while twin.can_undo() {
+        twin.undo().unwrap();
+        doc.undo().unwrap();
+        assert_eq!(doc.to_html().unwrap(), twin.to_html().unwrap());
+    }
+    while twin.can_redo() {
+        twin.redo().unwrap();
+        doc.redo().unwrap();
+        assert_eq!(doc.to_html().unwrap(), twin.to_html().unwrap());
+    }
+
+    // Typing after a save still merges into the typing before it.
+    let doc = TextDocument::new();
+    let c = doc.cursor();
+    c.insert_text("Hel").unwrap();
+    save_with_history(&doc);
+    c.insert_text("lo").unwrap();
+    assert_eq!(descriptions(&doc), vec!["Typing"]);
+}
+
+#[test]
+fn native_history_saves_with_a_second_stack() {
+    let doc = TextDocument::new();
+    doc.set_plain_text("Caption\nBody").unwrap();
+    let caption_stack = doc.create_undo_stack();
+    let caption = doc.cursor_at(7);
+    caption.set_undo_stack(Some(caption_stack));
+    caption.insert_text(" one").unwrap();
+    doc.cursor_at(doc.character_count() + 1)
+        .insert_text(" text")
+        .unwrap();
+
+    let data = save_with_history(&doc);
+    assert_eq!(doc.to_plain_text().unwrap(), "Caption one\nBody text");
+    assert!(doc.can_undo_in(caption_stack));
+
+    let loaded = TextDocument::new();
+    loaded.load_native(data.as_bytes()).unwrap();
+    assert_eq!(descriptions(&loaded), descriptions(&doc));
+    loaded.undo().unwrap();
+    assert_eq!(loaded.to_plain_text().unwrap(), "Caption one\nBody");
+
+    // Both stacks still work on the saved document.
+    doc.undo_in(caption_stack).unwrap();
+    doc.undo().unwrap();
+    assert_eq!(doc.to_plain_text().unwrap(), "Caption\nBody");
+}
+
+#[test]
+fn native_history_resaves_after_new_edits() {
+    let loaded = TextDocument::new();
+    loaded
+        .load_native(save_with_history(&edited_doc()).as_bytes())
+        .unwrap();
+    let c = loaded.cursor_at(0);
+    c.insert_text(">").unwrap();
+    c.insert_block().unwrap();
+
+    let resaved = TextDocument::new();
+    resaved
+        .load_native(save_with_history(&loaded).as_bytes())
+        .unwrap();
+    assert_eq!(descriptions(&resaved), descriptions(&loaded));
+    while loaded.can_undo() {
+        loaded.undo().unwrap();
+        resaved.undo().unwrap();
+        assert_eq!(resaved.to_html().unwrap(), loaded.to_html().unwrap());
+    }
+}
+
+#[test]
+fn native_history_survives_a_delete_across_a_table() {
+    let doc = TextDocument::new();
+    doc.set_plain_text("abc lorem\nipsum abc\nthird").unwrap();
+    doc.cursor_at(4).insert_table(2, 2).unwrap();
+    let c = doc.cursor_at(2);
+    c.set_position(doc.character_count() - 3, MoveMode::KeepAnchor);
+    c.remove_selected_text().unwrap();
+    assert_eq!(doc.to_plain_text().unwrap(), "ab\nabc\nthird");
+    let c = doc.cursor_at(0);
+    c.set_position(2, MoveMode::KeepAnchor);
+    c.set_char_format(&TextFormat {
+        font_bold: Some(true),
+        ..Default::default()
+    })
+    .unwrap();
+    assert_eq!(descriptions(&doc).len(), 3);
+
+    let loaded = TextDocument::new();
+    loaded
+        .load_native(save_with_history(&doc).as_bytes())
+        .unwrap();
+    assert_eq!(descriptions(&loaded), descriptions(&doc));
+    while doc.can_undo() {
+        doc.undo().unwrap();
+        loaded.undo().unwrap();
+        assert_eq!(loaded.to_html().unwrap(), doc.to_html().unwrap());
+    }
+}
+
+#[test]
+fn native_history_is_optional() {
+    let doc = edited_doc();
+    let plain = String::from_utf8(save(&doc)).unwrap();
+    assert!(!plain.contains("undo_history"));
+
+    let loaded = TextDocument::new();
+    loaded.load_native(plain.as_bytes()).unwrap();
+    assert!(!loaded.can_undo());
+    assert!(!loaded.can_redo());
+}
+
+#[test]
+fn native_history_with_unknown_version_is_discarded() {
+    let doc = edited_doc();
+    let data = save_with_history(&doc);
+    let newer = data.replace("\"version\":2}}", "\"version\":3}}");
+    assert_ne!(newer, data);
+
+    let loaded = TextDocument::new();
+    loaded.load_native(newer.as_bytes()).unwrap();
+    assert_eq!(
+        loaded.to_plain_text().unwrap(),
+        doc.to_plain_text().unwrap()
+    );
+    assert!(!loaded.can_undo());
+    assert!(!loaded.can_redo());
+}
+
+#[test]
+fn native_history_not_matching_document_is_discarded() {
+    let doc = edited_doc();
+    let with_history = save_with_history(&doc);
+    let history = &with_history[with_history.find(",\"undo_history\":").unwrap()..];
+
+    // Graft the history onto a different document.
+    let other = TextDocument::new();
+    other.set_plain_text("Something else entirely").unwrap();
+    let other_data = String::from_utf8(save(&other)).unwrap();
+    let grafted = format!("{}{history}", &other_data[..other_data.len() - 1]);
+
+    let loaded = TextDocument::new();
+    loaded.load_native(grafted.as_bytes()).unwrap();
+    assert_eq!(loaded.to_plain_text().unwrap(), "Something else entirely");
+    assert!(!loaded.can_undo());
+    assert!(!loaded.can_redo());
+}
+
+/// Size of the undo history saved with a document of `paragraphs`
+/// paragraphs after a few edits in its middle, one of them undone.
+fn saved_history_size(paragraphs: usize) -> usize {
+    let paragraph = "Lorem ipsum dolor sit amet, consectetur adipiscing elit.";
+    let doc = TextDocument::new();
+    doc.set_plain_text(&vec![paragraph; paragraphs].join("\n"))
+        .unwrap();
+    let middle = paragraphs / 2 * (paragraph.len() + 1) + 5;
+    doc.cursor_at(middle).insert_text("x").unwrap();
+    doc.cursor_at(middle).insert_block().unwrap();
+    doc.cursor_at(middle)
+        .insert_html("<p>one</p><p>two</p>")
+        .unwrap();
+    doc.cursor_at(middle).insert_table(2, 2).unwrap();
+    let c = doc.cursor_at(middle);
+    c.set_position(middle + 3, MoveMode::KeepAnchor);
+    c.set_char_format(&TextFormat {
+        font_bold: Some(true),
+        ..Default::default()
+    })
+    .unwrap();
+    doc.undo().unwrap();
+
+    let data = save_with_history(&doc);
+    let loaded = TextDocument::new();
+    loaded.load_native(data.as_bytes()).unwrap();
+    assert_eq!(descriptions(&loaded), descriptions(&doc));
+    data.len() - save(&doc).len()
+}
+
+#[test]
+fn native_history_size_follows_the_edits_not_the_document() {
+    let small = saved_history_size(100);
+    let large = saved_history_size(1000);
+    assert!(large <= small + 256, "{small} -> {large} bytes");
+}
+
+#[derive(Debug, Clone)]
+enum Step {
+    Type {
+        at: usize,
+        len: usize,
+        text: String,
+    },
+    Split {
+        at: usize,
+    },
+    Delete {
+        at: usize,
+        len: usize,
+    },
+    Bold {
+        at: usize,
+        len: usize,
+    },
+    List {
+        at: usize,
+    },
+    Table {
+        at: usize,
+        rows: usize,
+        columns: usize,
+    },
+    MergeCells {
+        at: usize,
+    },
+    Undo,
+    Redo,
+}
+
+fn step() -> impl Strategy<Value = Step> {
+    prop_oneof![
+        3 => (any::<usize>(), 0..3usize, "[a-z ]{1,4}")
+            .prop_map(|(at, len, text)| Step::Type { at, len, text }),
+        1 => any::<usize>().prop_map(|at| Step::Split { at }),
+        2 => (any::<usize>(), 1..12usize).prop_map(|(at, len)| Step::Delete { at, len }),
+        1 => (any::<usize>(), 1..6usize).prop_map(|(at, len)| Step::Bold { at, len }),
+        1 => any::<usize>().prop_map(|at| Step::List { at }),
+        1 => (any::<usize>(), 1..3usize, 1..3usize)
+            .prop_map(|(at, rows, columns)| Step::Table { at, rows, columns }),
+        1 => any::<usize>().prop_map(|at| Step::MergeCells { at }),
+        1 => Just(Step::Undo),
+        1 => Just(Step::Redo),
+    ]
+}
+
+/// Run `steps` on a small document, including deletes across tables.
+fn edited_session(steps: Vec<Step>) -> TextDocument {
+    let doc = TextDocument::new();
+    doc.set_plain_text("Hello world\nSecond para\nThird")
+        .unwrap();
+    for step in steps {
+        let end = doc.character_count();
+        let cursor = doc.cursor();
+        let select = |at: usize, len: usize| {
+            cursor.set_position(at % (end + 1), MoveMode::MoveAnchor);
+            cursor.set_position((at % (end + 1) + len).min(end), MoveMode::KeepAnchor);
+        };
+        match step {
+            Step::Type { at, len, text } => {
+                select(at, len);
+                cursor.insert_text(&text).unwrap();
+            }
+            Step::Split { at } => {
+                select(at, 0);
+                cursor.insert_block().unwrap();
+            }
+            Step::Delete { at, len } => {
+                select(at, len);
+                cursor.remove_selected_text().unwrap();
+            }
+            Step::Bold { at, len } => {
+                select(at, len);
+                cursor
+                    .set_char_format(&TextFormat {
+                        font_bold: Some(true),
+                        ..Default::default()
+                    })
+                    .unwrap();
+            }
+            Step::List { at } => {
+                select(at, 0);
+                cursor.create_list(ListStyle::Decimal).unwrap();
+            }
+            Step::Table { at, rows, columns } => {
+                select(at, 0);
+                cursor.insert_table(rows, columns).unwrap();
+            }
+            Step::MergeCells { at } => {
+                select(at, 0);
+                if let Some(table) = cursor.current_table()
+                    && table.columns() > 1
+                {
+                    cursor.merge_table_cells(table.id(), 0, 0, 0, 1).unwrap();
+                }
+            }
+            Step::Undo => doc.undo().unwrap(),
+            Step::Redo => doc.redo().unwrap(),
+        }
+    }
+    doc
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn native_save_load_save_is_byte_identical(
+        steps in proptest::collection::vec(step(), 1..16)
+    ) {
+        let doc = edited_session(steps);
+        let first = save(&doc);
+        let loaded = TextDocument::new();
+        loaded.load_native(first.as_slice()).unwrap();
+        prop_assert_eq!(String::from_utf8(save(&loaded)).unwrap(), String::from_utf8(first).unwrap());
+    }
+}
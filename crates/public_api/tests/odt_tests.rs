@@ -0,0 +1,388 @@
+//! Tests for ODT import (`set_odt`) and export (`to_odt`).
+
+use std::io::{Read, Write};
+
+use text_document::{
+    Alignment, FlowElement, FragmentContent, ListStyle, MoveMode, ResourceType, TextDocument,
+    TextFormat, TextTable,
+};
+
+const NAMESPACES: &str = concat!(
+    r#"xmlns:office="urn:oasis:names:tc:opendocument:xmlns:office:1.0" "#,
+    r#"xmlns:style="urn:oasis:names:tc:opendocument:xmlns:style:1.0" "#,
+    r#"xmlns:text="urn:oasis:names:tc:opendocument:xmlns:text:1.0" "#,
+    r#"xmlns:table="urn:oasis:names:tc:opendocument:xmlns:table:1.0" "#,
+    r#"xmlns:draw="urn:oasis:names:tc:opendocument:xmlns:drawing:1.0" "#,
+    r#"xmlns:fo="urn:oasis:names:tc:opendocument:xmlns:xsl-fo-compatible:1.0" "#,
+    r#"xmlns:xlink="http://www.w3.org/1999/xlink" "#,
+    r#"xmlns:svg="urn:oasis:names:tc:opendocument:xmlns:svg-compatible:1.0""#,
+);
+
+/// Build an ODT package from automatic styles and `office:text` content.
+fn odt(styles: &str, body: &str, parts: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut buf = std::io::Cursor::new(Vec::new());
+    {
+        let mut zip = zip::ZipWriter::new(&mut buf);
+        let options = zip::write::FileOptions::default();
+        zip.start_file("mimetype", options).unwrap();
+        zip.write_all(b"application/vnd.oasis.opendocument.text")
+            .unwrap();
+        zip.start_file("content.xml", options).unwrap();
+        write!(
+            zip,
+            r#"<?xml version="1.0" encoding="UTF-8"?><office:document-content {NAMESPACES}><office:automatic-styles>{styles}</office:automatic-styles><office:body><office:text>{body}</office:text></office:body></office:document-content>"#
+        )
+        .unwrap();
+        for (name, data) in parts {
+            zip.start_file(*name, options).unwrap();
+            zip.write_all(data).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+    buf.into_inner()
+}
+
+fn import(bytes: &[u8]) -> TextDocument {
+    let doc = TextDocument::new();
+    doc.set_odt(bytes).unwrap().wait().unwrap();
+    doc
+}
+
+fn export(doc: &TextDocument, name: &str) -> Vec<u8> {
+    let path = std::env::temp_dir().join(name);
+    doc.to_odt(path.to_str().unwrap()).unwrap().wait().unwrap();
+    let bytes = std::fs::read(&path).unwrap();
+    let _ = std::fs::remove_file(&path);
+    bytes
+}
+
+fn first_table(doc: &TextDocument) -> TextTable {
+    doc.flow()
+        .into_iter()
+        .find_map(|e| match e {
+            FlowElement::Table(t) => Some(t),
+            _ => None,
+        })
+        .expect("table in flow")
+}
+
+#[test]
+fn odt_import_paragraphs_and_character_styles() {
+    let styles = concat!(
+        r#"<style:style style:name="T1" style:family="text"><style:text-properties fo:font-weight="bold" fo:font-style="italic"/></style:style>"#,
+        r#"<style:style style:name="T2" style:family="text"><style:text-properties style:text-underline-style="wave" style:text-line-through-style="solid" fo:font-family="'DejaVu Serif'" fo:font-size="14pt"/></style:style>"#,
+        r#"<style:style style:name="T3" style:family="text"><style:text-properties style:text-position="super 58%"/></style:style>"#,
+        r#"<style:style style:name="P1" style:family="paragraph"><style:paragraph-properties fo:text-align="center"/></style:style>"#,
+    );
+    let body = concat!(
+        r#"<text:p>Plain <text:span text:style-name="T1">bold</text:span></text:p>"#,
+        r#"<text:p text:style-name="P1"><text:span text:style-name="T2">styled</text:span><text:span text:style-name="T3">2</text:span></text:p>"#,
+    );
+    let doc = import(&odt(styles, body, &[]));
+    assert_eq!(doc.to_plain_text().unwrap(), "Plain bold\nstyled2");
+
+    let bold = doc.cursor_at(7).char_format().unwrap();
+    assert_eq!(bold.font_bold, Some(true));
+    assert_eq!(bold.font_italic, Some(true));
+    assert_eq!(
+        doc.cursor_at(2).char_format().unwrap(),
+        TextFormat::default()
+    );
+
+    let styled = doc.cursor_at(13).char_format().unwrap();
+    assert_eq!(styled.font_underline, Some(true));
+    assert_eq!(styled.font_strikeout, Some(true));
+    assert_eq!(styled.font_family.as_deref(), Some("DejaVu Serif"));
+    assert_eq!(styled.font_point_size, Some(14));
+    assert!(styled.underline_style.is_some());
+    assert!(
+        doc.cursor_at(17)
+            .char_format()
+            .unwrap()
+            .vertical_alignment
+            .is_some()
+    );
+
+    let second = doc.block_at_position(11).unwrap();
+    assert_eq!(second.block_format().alignment, Some(Alignment::Center));
+}
+
+#[test]
+fn odt_import_whitespace_headings_and_line_breaks() {
+    let body = concat!(
+        r#"<text:h text:outline-level="2">Title</text:h>"#,
+        "<text:p>  a \n  b<text:s text:c=\"2\"/>c<text:tab/>d</text:p>",
+        r#"<text:p>one<text:line-break/>two</text:p>"#,
+    );
+    let doc = import(&odt("", body, &[]));
+    assert_eq!(doc.to_plain_text().unwrap(), "Title\na b  c\td\none\ntwo");
+    let blocks = doc.blocks();
+    assert_eq!(blocks[0].block_format().heading_level, Some(2));
+    assert_eq!(blocks[1].block_format().heading_level, None);
+}
+
+#[test]
+fn odt_import_nested_lists() {
+    let styles = concat!(
+        r#"<text:list-style style:name="L1">"#,
+        r#"<text:list-level-style-number text:level="1" style:num-format="1"/>"#,
+        r#"<text:list-level-style-number text:level="2" style:num-format="i"/>"#,
+        r#"</text:list-style>"#,
+        r#"<text:list-style style:name="L2"><text:list-level-style-bullet text:level="1" text:bullet-char="•"/></text:list-style>"#,
+    );
+    let body = concat!(
+        r#"<text:list text:style-name="L1">"#,
+        r#"<text:list-item><text:p>first</text:p></text:list-item>"#,
+        r#"<text:list-item><text:p>second</text:p>"#,
+        r#"<text:list><text:list-item><text:p>nested</text:p></text:list-item></text:list>"#,
+        r#"</text:list-item></text:list>"#,
+        r#"<text:list text:style-name="L2"><text:list-item><text:p>bullet</text:p></text:list-item></text:list>"#,
+    );
+    let doc = import(&odt(styles, body, &[]));
+
+    let blocks = doc.blocks();
+    let first = blocks[0].list().unwrap();
+    assert_eq!(first.style(), ListStyle::Decimal);
+    assert_eq!(blocks[1].list().unwrap().id(), first.id());
+    assert_eq!(blocks[1].list_item_index(), Some(1));
+    let nested = blocks[2].list().unwrap();
+    assert_eq!(nested.style(), ListStyle::LowerRoman);
+    assert_eq!(nested.indent(), 1);
+    assert_eq!(blocks[3].list().unwrap().style(), ListStyle::Disc);
+}
+
+#[test]
+fn odt_import_table_with_spanned_cells() {
+    let cell = |attrs: &str, text: &str| {
+        format!(r#"<table:table-cell {attrs}><text:p>{text}</text:p></table:table-cell>"#)
+    };
+    let covered = "<table:covered-table-cell/>";
+    let body = format!(
+        concat!(
+            r#"<text:p>Before</text:p><table:table table:name="T">"#,
+            r#"<table:table-column table:number-columns-repeated="3"/>"#,
+            r#"<table:table-row>{}{}{}</table:table-row>"#,
+            r#"<table:table-row>{}{}{}</table:table-row>"#,
+            r#"<table:table-row>{}{}{}</table:table-row>"#,
+            r#"</table:table><text:p>After</text:p>"#
+        ),
+        cell(r#"table:number-columns-spanned="2""#, "wide"),
+        covered,
+        cell(r#"table:number-rows-spanned="2""#, "tall"),
+        cell("", "a"),
+        cell("", "b"),
+        covered,
+        cell("", "c"),
+        cell("", "d"),
+        cell("", "e"),
+    );
+    let doc = import(&odt("", &body, &[]));
+
+    let table = first_table(&doc);
+    assert_eq!(table.rows(), 3);
+    assert_eq!(table.columns(), 3);
+    let wide = table.cell(0, 0).unwrap();
+    assert_eq!(wide.column_span(), 2);
+    assert_eq!(wide.blocks()[0].text(), "wide");
+    let tall = table.cell(0, 2).unwrap();
+    assert_eq!(tall.row_span(), 2);
+    assert_eq!(tall.blocks()[0].text(), "tall");
+    assert_eq!(table.cell(2, 2).unwrap().blocks()[0].text(), "e");
+}
+
+#[test]
+fn odt_import_links_and_images() {
+    let body = concat!(
+        r#"<text:p>See <text:a xlink:type="simple" xlink:href="https://example.com">site</text:a>"#,
+        r#"<draw:frame text:anchor-type="as-char" svg:width="1in" svg:height="0.5in">"#,
+        r#"<draw:image xlink:href="Pictures/pic.png"/></draw:frame>!</text:p>"#,
+    );
+    let png: &[u8] = &[137, 80, 78, 71, 1, 2, 3];
+    let doc = import(&odt("", body, &[("Pictures/pic.png", png)]));
+
+    let link = doc.cursor_at(5).char_format().unwrap();
+    assert_eq!(link.anchor_href.as_deref(), Some("https://example.com"));
+
+    let image = doc
+        .block_at_position(0)
+        .unwrap()
+        .fragments()
+        .into_iter()
+        .find_map(|f| match f {
+            FragmentContent::Image {
+                name,
+                width,
+                height,
+                ..
+            } => Some((name, width, height)),
+            _ => None,
+        })
+        .expect("image fragment");
+    assert_eq!(image, ("pic.png".to_string(), 96, 48));
+    assert_eq!(doc.resource("pic.png").unwrap(), Some(png.to_vec()));
+}
+
+#[test]
+fn odt_export_writes_valid_package() {
+    let doc = TextDocument::new();
+    doc.set_plain_text("Hello  world").unwrap();
+    let png: &[u8] = &[137, 80, 78, 71, 9, 9];
+    doc.add_resource(ResourceType::Image, "logo.png", "image/png", png)
+        .unwrap();
+    doc.cursor_at(5).insert_image("logo.png", 20, 10).unwrap();
+
+    let bytes = export(&doc, "test_odt_export_package.odt");
+    let mut archive = zip::ZipArchive::new(std::io::Cursor::new(bytes)).unwrap();
+
+    let mimetype = archive.by_index(0).unwrap();
+    assert_eq!(mimetype.name(), "mimetype");
+    assert_eq!(mimetype.compression(), zip::CompressionMethod::Stored);
+    drop(mimetype);
+
+    let mut manifest = String::new();
+    archive
+        .by_name("META-INF/manifest.xml")
+        .unwrap()
+        .read_to_string(&mut manifest)
+        .unwrap();
+    assert!(manifest.contains(r#"manifest:full-path="Pictures/logo.png""#));
+
+    let mut content = String::new();
+    archive
+        .by_name("content.xml")
+        .unwrap()
+        .read_to_string(&mut content)
+        .unwrap();
+    assert!(content.contains("world"));
+    assert!(content.contains("<text:s/>"), "{content}");
+    assert!(content.contains(r#"xlink:href="Pictures/logo.png""#));
+
+    let mut picture = Vec::new();
+    archive
+        .by_name("Pictures/logo.png")
+        .unwrap()
+        .read_to_end(&mut picture)
+        .unwrap();
+    assert_eq!(picture, png);
+}
+
+#[test]
+fn odt_roundtrip_preserves_structure_and_formatting() {
+    let source = TextDocument::new();
+    source
+        .set_html(concat!(
+            "<h1>Report</h1>",
+            "<p>Some <b>bold</b>, <i>italic</i> and <a href=\"https://example.com\">linked</a> text</p>",
+            "<ol><li>one</li><li>two<ul><li>inner</li></ul></li></ol>",
+            "<pre><code>let  x = 1;</code></pre>",
+            "<p>End</p>",
+        ))
+        .unwrap()
+        .wait()
+        .unwrap();
+    let c = source.cursor_at(0);
+    c.set_position(6, MoveMode::KeepAnchor);
+    c.set_char_format(&TextFormat {
+        font_point_size: Some(20),
+        ..Default::default()
+    })
+    .unwrap();
+    source
+        .add_resource(ResourceType::Image, "img.png", "image/png", &[1, 2, 3])
+        .unwrap();
+    let end = source.to_plain_text().unwrap().chars().count();
+    source
+        .cursor_at(end)
+        .insert_image("img.png", 30, 40)
+        .unwrap();
+
+    let doc = TextDocument::new();
+    doc.set_odt(&export(&source, "test_odt_roundtrip.odt"))
+        .unwrap()
+        .wait()
+        .unwrap();
+
+    assert_eq!(
+        doc.to_plain_text().unwrap().replace('\u{FFFC}', ""),
+        source.to_plain_text().unwrap().replace('\u{FFFC}', "")
+    );
+    let blocks = doc.blocks();
+    let source_blocks = source.blocks();
+    assert_eq!(blocks.len(), source_blocks.len());
+    for (block, expected) in blocks.iter().zip(&source_blocks) {
+        assert_eq!(block.text(), expected.text());
+        assert_eq!(
+            block.block_format().heading_level,
+            expected.block_format().heading_level
+        );
+        assert_eq!(
+            block.block_format().is_code_block,
+            expected.block_format().is_code_block,
+            "{}",
+            block.text()
+        );
+        assert_eq!(
+            block.list().map(|l| (l.style(), l.indent())),
+            expected.list().map(|l| (l.style(), l.indent())),
+            "{}",
+            block.text()
+        );
+    }
+    assert_eq!(
+        blocks[2].list().unwrap().id(),
+        blocks[3].list().unwrap().id()
+    );
+
+    let text = doc.to_plain_text().unwrap();
+    let at = |needle: &str| text.find(needle).unwrap();
+    let format_at = |needle: &str| doc.cursor_at(at(needle) + 1).char_format().unwrap();
+    assert_eq!(format_at("Report").font_point_size, Some(20));
+    assert_eq!(format_at("bold").font_bold, Some(true));
+    assert_eq!(format_at("italic").font_italic, Some(true));
+    assert_eq!(
+        format_at("linked").anchor_href.as_deref(),
+        Some("https://example.com")
+    );
+    assert_eq!(format_at("Some").font_bold, None);
+
+    assert_eq!(doc.resource("img.png").unwrap(), Some(vec![1, 2, 3]));
+    assert!(doc.to_html().unwrap().contains(r#"<img src="img.png""#));
+}
+
+#[test]
+fn odt_roundtrip_preserves_table_spans() {
+    let cell = |attrs: &str, text: &str| {
+        format!(
+            r#"<table:table-cell {attrs}><text:p>{text}</text:p><text:p>more {text}</text:p></table:table-cell>"#
+        )
+    };
+    let body = format!(
+        concat!(
+            r#"<table:table><table:table-column table:number-columns-repeated="2"/>"#,
+            r#"<table:table-row>{}<table:covered-table-cell/></table:table-row>"#,
+            r#"<table:table-row>{}{}</table:table-row></table:table>"#
+        ),
+        cell(r#"table:number-columns-spanned="2""#, "top"),
+        cell("", "left"),
+        cell("", "right"),
+    );
+    let source = import(&odt("", &body, &[]));
+    let doc = import(&export(&source, "test_odt_roundtrip_table.odt"));
+
+    let table = first_table(&doc);
+    assert_eq!((table.rows(), table.columns()), (2, 2));
+    let top = table.cell(0, 0).unwrap();
+    assert_eq!(top.column_span(), 2);
+    let texts: Vec<String> = top.blocks().iter().map(|b| b.text()).collect();
+    assert_eq!(texts, ["top", "more top"]);
+    assert_eq!(table.cell(1, 1).unwrap().blocks()[0].text(), "right");
+}
+
+#[test]
+fn odt_import_rejects_invalid_data_and_keeps_document() {
+    let doc = TextDocument::new();
+    doc.set_plain_text("keep me").unwrap();
+    let err = doc.set_odt(b"not a zip").unwrap().wait().unwrap_err();
+    assert!(format!("{err:#}").contains("ODT"), "{err:#}");
+    assert_eq!(doc.to_plain_text().unwrap(), "keep me");
+}
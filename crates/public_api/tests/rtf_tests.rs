@@ -0,0 +1,285 @@
+//! Tests for RTF import (`set_rtf`), export (`to_rtf`) and the
+//! `DocumentFragment` RTF conversions used for copy/paste.
+
+use text_document::{
+    Alignment, BlockFormat, CharVerticalAlignment, DocumentFragment, FlowElement, FragmentContent,
+    MoveMode, ResourceType, TextDocument, TextFormat, TextTable, UnderlineStyle,
+};
+
+fn import(rtf: &str) -> TextDocument {
+    let doc = TextDocument::new();
+    doc.set_rtf(rtf).unwrap().wait().unwrap();
+    doc
+}
+
+fn first_table(doc: &TextDocument) -> TextTable {
+    doc.flow()
+        .into_iter()
+        .find_map(|e| match e {
+            FlowElement::Table(t) => Some(t),
+            _ => None,
+        })
+        .expect("table in flow")
+}
+
+#[test]
+fn rtf_import_character_formatting_and_fonts() {
+    let doc = import(concat!(
+        r"{\rtf1\ansi\deff0{\fonttbl{\f0\froman Times New Roman;}{\f1\fswiss Arial;}}",
+        r"{\colortbl;\red255\green0\blue0;}",
+        r"\pard Plain {\b bold}{\i\ul italic}\par",
+        r"{\f1\fs28 sized}{\strike gone}{\ulwave wave}x{\super sup}{\sub sub}\b0 caf\'e9 \u8364?}",
+    ));
+    assert_eq!(
+        doc.to_plain_text().unwrap(),
+        "Plain bolditalic\nsizedgonewavexsupsubcafé €"
+    );
+
+    assert_eq!(
+        doc.cursor_at(2).char_format().unwrap(),
+        TextFormat::default()
+    );
+    assert_eq!(
+        doc.cursor_at(7).char_format().unwrap().font_bold,
+        Some(true)
+    );
+    let italic = doc.cursor_at(12).char_format().unwrap();
+    assert_eq!(italic.font_italic, Some(true));
+    assert_eq!(italic.font_underline, Some(true));
+
+    let sized = doc.cursor_at(19).char_format().unwrap();
+    assert_eq!(sized.font_family.as_deref(), Some("Arial"));
+    assert_eq!(sized.font_point_size, Some(14));
+    assert_eq!(
+        doc.cursor_at(24).char_format().unwrap().font_strikeout,
+        Some(true)
+    );
+    assert_eq!(
+        doc.cursor_at(28).char_format().unwrap().underline_style,
+        Some(UnderlineStyle::WaveUnderline)
+    );
+    assert_eq!(
+        doc.cursor_at(32).char_format().unwrap().vertical_alignment,
+        Some(CharVerticalAlignment::SuperScript)
+    );
+    assert_eq!(
+        doc.cursor_at(35).char_format().unwrap().vertical_alignment,
+        Some(CharVerticalAlignment::SubScript)
+    );
+}
+
+#[test]
+fn rtf_import_paragraph_formatting() {
+    let doc = import(concat!(
+        r"{\rtf1\ansi",
+        r"\pard\outlinelevel0 Title\par",
+        r"\pard\qc\li300\ri150\fi-150 Centered\par",
+        r"\pard\qj one\line two}",
+    ));
+    assert_eq!(doc.to_plain_text().unwrap(), "Title\nCentered\none\ntwo");
+    let blocks = doc.blocks();
+    assert_eq!(blocks[0].block_format().heading_level, Some(1));
+    let centered = blocks[1].block_format();
+    assert_eq!(centered.alignment, Some(Alignment::Center));
+    assert_eq!(centered.left_margin, Some(20));
+    assert_eq!(centered.right_margin, Some(10));
+    assert_eq!(centered.text_indent, Some(-10));
+    assert_eq!(blocks[3].block_format().alignment, Some(Alignment::Justify));
+}
+
+#[test]
+fn rtf_import_table_with_merged_cells() {
+    let doc = import(concat!(
+        r"{\rtf1\ansi\pard Before\par",
+        r"\trowd\cellx2000\clvmgf\cellx4000",
+        r"\pard\intbl a\cell\pard\intbl tall\cell\row",
+        r"\trowd\cellx2000\clvmrg\cellx4000",
+        r"\pard\intbl b\cell\pard\intbl\cell\row",
+        r"\trowd\cellx4000",
+        r"\pard\intbl wide\par second\cell\row",
+        r"\pard After}",
+    ));
+    let table = first_table(&doc);
+    assert_eq!((table.rows(), table.columns()), (3, 2));
+    assert_eq!(table.cell(0, 1).unwrap().row_span(), 2);
+    assert_eq!(table.cell(1, 0).unwrap().blocks()[0].text(), "b");
+    let wide = table.cell(2, 0).unwrap();
+    assert_eq!(wide.column_span(), 2);
+    let texts: Vec<String> = wide.blocks().iter().map(|b| b.text()).collect();
+    assert_eq!(texts, ["wide", "second"]);
+
+    let flow = doc.flow();
+    assert!(matches!(&flow[0], FlowElement::Block(b) if b.text() == "Before"));
+    assert!(matches!(&flow[1], FlowElement::Table(_)));
+    assert!(matches!(&flow[2], FlowElement::Block(b) if b.text() == "After"));
+}
+
+#[test]
+fn rtf_roundtrip_preserves_merged_cells() {
+    let source = import(concat!(
+        r"{\rtf1\ansi",
+        r"\trowd\clmgf\cellx1000\clmrg\cellx2000\cellx3000",
+        r"\pard\intbl top\cell\pard\intbl\cell\pard\intbl tall\cell\row",
+        r"\trowd\cellx1000\cellx2000\clvmrg\cellx3000",
+        r"\pard\intbl a\cell\pard\intbl b\cell\pard\intbl\cell\row}",
+    ));
+    let table = first_table(&source);
+    assert_eq!(table.cell(0, 0).unwrap().column_span(), 2);
+
+    let doc = import(&source.to_rtf().unwrap());
+    let table = first_table(&doc);
+    assert_eq!((table.rows(), table.columns()), (2, 3));
+    let top = table.cell(0, 0).unwrap();
+    assert_eq!(top.column_span(), 2);
+    assert_eq!(top.blocks()[0].text(), "top");
+    let tall = table.cell(0, 2).unwrap();
+    assert_eq!(tall.row_span(), 2);
+    assert_eq!(tall.blocks()[0].text(), "tall");
+    assert_eq!(table.cell(1, 1).unwrap().blocks()[0].text(), "b");
+}
+
+#[test]
+fn rtf_import_hyperlinks_and_pictures() {
+    let doc = import(concat!(
+        r#"{\rtf1\ansi\pard See {\field{\*\fldinst{HYPERLINK "https://example.com"}}{\fldrslt{\ul site}}}"#,
+        r"{\*\shppict{\pict\pngblip\picw10\pich5\picwgoal1440\pichgoal720 89504e47",
+        "\n0102}}{\\nonshppict{\\pict\\wmetafile8 0000}}!}",
+    ));
+    assert_eq!(
+        doc.to_plain_text().unwrap().replace('\u{FFFC}', ""),
+        "See site!"
+    );
+    let link = doc.cursor_at(5).char_format().unwrap();
+    assert_eq!(link.anchor_href.as_deref(), Some("https://example.com"));
+    assert_eq!(doc.cursor_at(1).char_format().unwrap().anchor_href, None);
+
+    let image = doc
+        .block_at_position(0)
+        .unwrap()
+        .fragments()
+        .into_iter()
+        .find_map(|f| match f {
+            FragmentContent::Image {
+                name,
+                width,
+                height,
+                ..
+            } => Some((name, width, height)),
+            _ => None,
+        })
+        .expect("image fragment");
+    assert_eq!(image, ("image1.png".to_string(), 96, 48));
+    assert_eq!(
+        doc.resource("image1.png").unwrap(),
+        Some(vec![0x89, 0x50, 0x4e, 0x47, 0x01, 0x02])
+    );
+}
+
+#[test]
+fn rtf_roundtrip_preserves_formatting_tables_and_images() {
+    let source = TextDocument::new();
+    source
+        .set_html(concat!(
+            "<h2>Heading</h2>",
+            "<p>Some <b>bold</b>, <i>italic</i> and <a href=\"https://example.com\">linked</a> {text}\\</p>",
+            "<table><tr><td>a</td><td>b</td></tr><tr><td>c</td><td>d</td></tr></table>",
+            "<p>Ünïcödé — 🎉</p>",
+        ))
+        .unwrap()
+        .wait()
+        .unwrap();
+    let c = source.cursor_at(0);
+    c.set_position(4, MoveMode::KeepAnchor);
+    c.set_char_format(&TextFormat {
+        font_family: Some("Courier New".into()),
+        font_point_size: Some(18),
+        ..Default::default()
+    })
+    .unwrap();
+    source
+        .cursor_at(9)
+        .set_block_format(&BlockFormat {
+            alignment: Some(Alignment::Right),
+            left_margin: Some(40),
+            ..Default::default()
+        })
+        .unwrap();
+    source
+        .add_resource(ResourceType::Image, "dot.png", "image/png", &[7, 8, 9])
+        .unwrap();
+    source.cursor_at(1).insert_image("dot.png", 30, 40).unwrap();
+
+    let rtf = source.to_rtf().unwrap();
+    assert!(rtf.starts_with(r"{\rtf1"), "{rtf}");
+    assert!(rtf.contains("Courier New"), "{rtf}");
+    let doc = import(&rtf);
+
+    assert_eq!(
+        doc.to_plain_text().unwrap(),
+        source.to_plain_text().unwrap()
+    );
+    let blocks = doc.blocks();
+    assert_eq!(blocks[0].block_format().heading_level, Some(2));
+    let para = blocks[1].block_format();
+    assert_eq!(para.alignment, Some(Alignment::Right));
+    assert_eq!(para.left_margin, Some(40));
+
+    let text = doc.to_plain_text().unwrap();
+    let format_at = |needle: &str| {
+        doc.cursor_at(text.chars().count() - text[text.find(needle).unwrap()..].chars().count() + 1)
+            .char_format()
+            .unwrap()
+    };
+    let heading = format_at("ead");
+    assert_eq!(heading.font_family.as_deref(), Some("Courier New"));
+    assert_eq!(heading.font_point_size, Some(18));
+    assert_eq!(format_at("bold").font_bold, Some(true));
+    assert_eq!(format_at("italic").font_italic, Some(true));
+    assert_eq!(
+        format_at("linked").anchor_href.as_deref(),
+        Some("https://example.com")
+    );
+
+    let table = first_table(&doc);
+    assert_eq!((table.rows(), table.columns()), (2, 2));
+    assert_eq!(table.cell(0, 1).unwrap().blocks()[0].text(), "b");
+    assert_eq!(table.cell(1, 0).unwrap().blocks()[0].text(), "c");
+
+    assert_eq!(doc.resource("image1.png").unwrap(), Some(vec![7, 8, 9]));
+}
+
+#[test]
+fn rtf_fragment_copy_paste() {
+    let source = TextDocument::new();
+    source
+        .set_html("<p>Hello <b>bold</b> world</p><p>Second</p>")
+        .unwrap()
+        .wait()
+        .unwrap();
+    let c = source.cursor_at(6);
+    c.set_position(20, MoveMode::KeepAnchor);
+    let rtf = c.selection().to_rtf();
+    assert!(rtf.contains(r"{\b bold}"), "{rtf}");
+
+    let fragment = DocumentFragment::from_rtf(&rtf).unwrap();
+    assert_eq!(fragment.to_plain_text(), "bold world\nSec");
+
+    let target = TextDocument::new();
+    target.set_plain_text("[]").unwrap();
+    target.cursor_at(1).insert_rtf(&rtf).unwrap();
+    assert_eq!(target.to_plain_text().unwrap(), "[bold world\nSec]");
+    assert_eq!(
+        target.cursor_at(2).char_format().unwrap().font_bold,
+        Some(true)
+    );
+}
+
+#[test]
+fn rtf_import_rejects_invalid_data_and_keeps_document() {
+    let doc = TextDocument::new();
+    doc.set_plain_text("keep me").unwrap();
+    let err = doc.set_rtf("plain text").unwrap().wait().unwrap_err();
+    assert!(format!("{err:#}").contains("RTF"), "{err:#}");
+    assert_eq!(doc.to_plain_text().unwrap(), "keep me");
+    assert!(DocumentFragment::from_rtf("{\\html}").is_err());
+}
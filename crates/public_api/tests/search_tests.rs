@@ -1,4 +1,7 @@
-use text_document::{FindOptions, TextDocument};
+use text_document::{
+    BlockFormat, FindOptions, FlowElement, FormatQuery, MoveMode, SearchNormalization, SearchScope,
+    TextDocument, TextFormat,
+};
 
 fn new_doc_with_text(text: &str) -> TextDocument {
     let doc = TextDocument::new();
@@ -77,3 +80,528 @@ fn replace_text_is_undoable() {
     doc.undo().unwrap();
     assert_eq!(doc.to_plain_text().unwrap(), "foo bar foo");
 }
+
+#[test]
+fn replace_text_regex_capture_groups_and_preserve_case() {
+    let doc = new_doc_with_text("Color colour COLOUR");
+    let opts = FindOptions::default()
+        .with_use_regex(true)
+        .with_preserve_case(true);
+    let count = doc
+        .replace_text("col(ou?)r", "hue-$1", true, &opts)
+        .unwrap();
+    assert_eq!(count, 3);
+    assert_eq!(doc.to_plain_text().unwrap(), "Hue-o hue-ou HUE-OU");
+
+    doc.undo().unwrap();
+    assert_eq!(doc.to_plain_text().unwrap(), "Color colour COLOUR");
+    doc.redo().unwrap();
+    assert_eq!(doc.to_plain_text().unwrap(), "Hue-o hue-ou HUE-OU");
+}
+
+#[test]
+fn replace_text_cross_block_keeps_surrounding_formats() {
+    let doc = new_doc_with_text("keep bold\n\ntail kept");
+    let bold = TextFormat {
+        font_bold: Some(true),
+        ..Default::default()
+    };
+    for (start, end) in [(5, 9), (16, 20)] {
+        let cursor = doc.cursor_at(start);
+        cursor.set_position(end, MoveMode::KeepAnchor);
+        cursor.set_char_format(&bold).unwrap();
+    }
+
+    let opts = FindOptions::default()
+        .with_use_regex(true)
+        .with_cross_block(true);
+    let count = doc
+        .replace_text(r"ld\n\ntail ", "LD\nnew ", true, &opts)
+        .unwrap();
+    assert_eq!(count, 1);
+    assert_eq!(doc.to_plain_text().unwrap(), "keep boLD\nnew kept");
+    assert_eq!(doc.block_count(), 2);
+    let second = doc.block_at(12).unwrap();
+    assert_eq!((second.start, second.length), (10, 8));
+
+    let bold_at = |position: usize| doc.cursor_at(position).char_format().unwrap().font_bold;
+    assert_ne!(bold_at(2), Some(true));
+    assert_eq!(bold_at(8), Some(true));
+    assert_eq!(bold_at(12), Some(true));
+    assert_eq!(bold_at(16), Some(true));
+
+    doc.undo().unwrap();
+    assert_eq!(doc.to_plain_text().unwrap(), "keep bold\n\ntail kept");
+    assert_eq!(doc.block_count(), 3);
+    assert_eq!(bold_at(18), Some(true));
+}
+
+#[test]
+fn find_all_scoped_to_table_and_frame() {
+    let doc = new_doc_with_text("x before\nx after");
+    let table = doc.cursor_at(8).insert_table(1, 2).unwrap();
+    doc.cursor_at(9).insert_text("x one").unwrap();
+    doc.cursor_at(15).insert_text("x two").unwrap();
+
+    let scoped = |scope| FindOptions::default().with_scope(Some(scope));
+    let positions = |options: &FindOptions| -> Vec<usize> {
+        doc.find_all("x", options)
+            .unwrap()
+            .iter()
+            .map(|m| m.position)
+            .collect()
+    };
+    assert_eq!(
+        positions(&scoped(SearchScope::Table(table.id()))),
+        vec![9, 15]
+    );
+    let main_frame = doc.blocks()[0].frame().id();
+    assert_eq!(
+        positions(&scoped(SearchScope::Frame(main_frame))),
+        vec![0, 9, 15, 21]
+    );
+    assert_eq!(
+        positions(&scoped(SearchScope::Range { start: 1, end: 20 })),
+        vec![9, 15]
+    );
+}
+
+#[test]
+fn cursor_find_and_replace_in_selection() {
+    let doc = new_doc_with_text("cat cat\ncat cat");
+    let cursor = doc.cursor_at(4);
+    cursor.set_position(11, MoveMode::KeepAnchor);
+
+    let opts = FindOptions::default();
+    let matches = cursor.find_all_in_selection("cat", &opts).unwrap();
+    let positions: Vec<usize> = matches.iter().map(|m| m.position).collect();
+    assert_eq!(positions, vec![4, 8]);
+
+    let count = cursor.replace_in_selection("cat", "tiger", &opts).unwrap();
+    assert_eq!(count, 2);
+    assert_eq!(doc.to_plain_text().unwrap(), "cat tiger\ntiger cat");
+    assert_eq!((cursor.anchor(), cursor.position()), (4, 15));
+    assert_eq!(cursor.selected_text().unwrap(), "tiger\ntiger");
+
+    doc.undo().unwrap();
+    assert_eq!(doc.to_plain_text().unwrap(), "cat cat\ncat cat");
+
+    let empty = doc.cursor_at(0);
+    assert!(
+        empty
+            .find_all_in_selection("cat", &opts)
+            .unwrap()
+            .is_empty()
+    );
+    assert_eq!(empty.replace_in_selection("cat", "dog", &opts).unwrap(), 0);
+}
+
+#[test]
+fn find_all_detailed_reports_blocks_cells_and_captures() {
+    let doc = new_doc_with_text("x before\nx after");
+    let table = doc.cursor_at(8).insert_table(1, 2).unwrap();
+    doc.cursor_at(10).insert_text("x two").unwrap();
+
+    let opts = FindOptions::default()
+        .with_use_regex(true)
+        .with_context_chars(4);
+    let hits = doc.find_all_detailed(r"x (t(w)?)", &opts).unwrap();
+    assert_eq!(hits.len(), 1);
+    let hit = &hits[0];
+    assert_eq!((hit.position, hit.length), (10, 4));
+    assert_eq!(hit.block_position, 0);
+    assert_eq!(hit.context_before, "");
+    assert_eq!(hit.context_after, "o");
+    assert_eq!(hit.captures, vec![Some((12, 2)), Some((13, 1))]);
+    let cell = hit.table_cell.as_ref().unwrap();
+    assert_eq!((cell.table_id, cell.row, cell.column), (table.id(), 0, 1));
+    assert_eq!(doc.block_by_id(hit.block_id).unwrap().position(), 10);
+
+    let hit = doc
+        .find_detailed("after", 0, &opts)
+        .unwrap()
+        .expect("match");
+    assert_eq!(hit.context_before, "x ");
+    assert!(hit.table_cell.is_none());
+    assert!(hit.captures.is_empty());
+}
+
+#[test]
+fn find_iter_pages_lazily() {
+    let doc = new_doc_with_text("aaaa\nab ab\naa");
+    let opts = FindOptions::default();
+    let all: Vec<usize> = doc
+        .find_all("aa", &opts)
+        .unwrap()
+        .iter()
+        .map(|m| m.position)
+        .collect();
+    assert_eq!(all, vec![0, 1, 2, 11]);
+
+    for page_size in [1, 2, 10] {
+        let paged: Vec<usize> = doc
+            .find_iter("aa", &opts, page_size)
+            .map(|hit| hit.unwrap().position)
+            .collect();
+        assert_eq!(paged, all, "page size {page_size}");
+    }
+
+    let mut hits = doc.find_iter("ab", &opts, 1);
+    assert_eq!(hits.next().unwrap().unwrap().position, 5);
+    assert_eq!(hits.next().unwrap().unwrap().position, 8);
+    assert!(hits.next().is_none());
+
+    let invalid = FindOptions::default().with_use_regex(true);
+    let mut hits = doc.find_iter("(", &invalid, 5);
+    assert!(hits.next().unwrap().is_err());
+    assert!(hits.next().is_none());
+}
+
+#[test]
+fn find_iter_sees_edits_between_pages() {
+    let doc = new_doc_with_text("ab ab\nab ab");
+    let opts = FindOptions::default();
+    let mut hits = doc.find_iter("ab", &opts, 2);
+    assert_eq!(hits.next().unwrap().unwrap().position, 0);
+    assert_eq!(hits.next().unwrap().unwrap().position, 3);
+
+    // An edit behind the scan shifts the hits still to come.
+    doc.cursor_at(0).insert_text("ab ").unwrap();
+    let rest: Vec<usize> = hits.map(|hit| hit.unwrap().position).collect();
+    assert_eq!(rest, [6, 9, 12]);
+}
+
+#[test]
+fn find_and_replace_ignoring_accents_and_normalization() {
+    let doc = new_doc_with_text("Le re\u{301}sume\u{301} et le résumé");
+    let plain = FindOptions::default();
+    assert_eq!(doc.find_all("résumé", &plain).unwrap().len(), 1);
+
+    let nfc = FindOptions::default().with_normalization(SearchNormalization::Nfc);
+    assert_eq!(
+        ranges(&doc.find_all("résumé", &nfc).unwrap()),
+        [(3, 8), (18, 6)]
+    );
+
+    let folded = FindOptions::default()
+        .with_fold_accents(true)
+        .with_preserve_case(true);
+    assert_eq!(doc.replace_text("RESUME", "cv", true, &folded).unwrap(), 2);
+    assert_eq!(doc.to_plain_text().unwrap(), "Le cv et le cv");
+}
+
+#[test]
+fn fuzzy_find_all_returns_closest_matches_first() {
+    let doc = new_doc_with_text("the recieve step, then receive it, or recive");
+    let opts = FindOptions::default().with_fuzzy(Some(2));
+    assert_eq!(
+        ranges(&doc.find_all("receive", &opts).unwrap()),
+        [(23, 7), (38, 6), (4, 7)]
+    );
+
+    let hits = doc.find_all_detailed("receive", &opts).unwrap();
+    let distances: Vec<usize> = hits.iter().map(|h| h.distance).collect();
+    assert_eq!(distances, [0, 1, 2]);
+
+    let exact = doc
+        .find_all_detailed("receive", &FindOptions::default())
+        .unwrap();
+    assert_eq!(exact.len(), 1);
+    assert_eq!(exact[0].distance, 0);
+
+    // Paged results are ranked over the whole document, not page by page
+    let paged: Vec<(usize, usize)> = doc
+        .find_iter("receive", &opts, 1)
+        .map(|hit| hit.map(|h| (h.position, h.distance)).unwrap())
+        .collect();
+    assert_eq!(paged, [(23, 0), (38, 1), (4, 2)]);
+}
+
+fn html_doc(html: &str) -> TextDocument {
+    let doc = TextDocument::new();
+    doc.set_html(html).unwrap().wait().unwrap();
+    doc
+}
+
+fn ranges(matches: &[text_document::FindMatch]) -> Vec<(usize, usize)> {
+    matches.iter().map(|m| (m.position, m.length)).collect()
+}
+
+#[test]
+fn find_format_by_character_and_block_format() {
+    let doc = html_doc(concat!(
+        "<h1>Title</h1>",
+        "<p>some <b>bold</b> and <a href=\"https://example.com/x\">a link</a></p>",
+        "<h2>Part</h2>",
+        "<pre><code class=\"language-rust\">fn main() {}</code></pre>",
+    ));
+    assert_eq!(
+        doc.to_plain_text().unwrap(),
+        "Title\nsome bold and a link\nPart\nfn main() {}"
+    );
+
+    let bold = doc
+        .find_format(&FormatQuery {
+            font_bold: Some(true),
+            heading_level: Some(0),
+            ..Default::default()
+        })
+        .unwrap();
+    assert_eq!(ranges(&bold), vec![(11, 4)]);
+
+    let links = doc
+        .find_format(&FormatQuery {
+            anchor_href: Some("example.com".into()),
+            ..Default::default()
+        })
+        .unwrap();
+    assert_eq!(ranges(&links), vec![(20, 6)]);
+
+    let headings = doc
+        .find_format(&FormatQuery {
+            heading_level: Some(2),
+            ..Default::default()
+        })
+        .unwrap();
+    assert_eq!(ranges(&headings), vec![(27, 4)]);
+
+    let code = doc
+        .find_format(&FormatQuery {
+            is_code_block: Some(true),
+            code_language: Some("Rust".into()),
+            ..Default::default()
+        })
+        .unwrap();
+    assert_eq!(ranges(&code), vec![(32, 12)]);
+}
+
+#[test]
+fn replace_format_is_one_undo_step() {
+    let doc = html_doc("<p><i>one</i> two <i>three</i></p><h2>Part</h2>");
+    let italic = FormatQuery {
+        font_italic: Some(true),
+        ..Default::default()
+    };
+    let format_at = |position: usize| doc.cursor_at(position).char_format().unwrap();
+
+    let count = doc
+        .replace_format(
+            &italic,
+            &TextFormat {
+                font_italic: Some(false),
+                font_underline: Some(true),
+                ..Default::default()
+            },
+            &BlockFormat::default(),
+        )
+        .unwrap();
+    assert_eq!(count, 2);
+    assert!(doc.find_format(&italic).unwrap().is_empty());
+    let underlined = doc
+        .find_format(&FormatQuery {
+            font_underline: Some(true),
+            ..Default::default()
+        })
+        .unwrap();
+    assert_eq!(ranges(&underlined), vec![(0, 3), (8, 5)]);
+    assert_ne!(format_at(5).font_underline, Some(true));
+
+    let count = doc
+        .replace_format(
+            &FormatQuery {
+                heading_level: Some(2),
+                ..Default::default()
+            },
+            &TextFormat::default(),
+            &BlockFormat {
+                heading_level: Some(3),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    assert_eq!(count, 1);
+    assert_eq!(doc.blocks()[1].block_format().heading_level, Some(3));
+
+    doc.undo().unwrap();
+    assert_eq!(doc.blocks()[1].block_format().heading_level, Some(2));
+    doc.undo().unwrap();
+    assert_eq!(
+        ranges(&doc.find_format(&italic).unwrap()),
+        vec![(0, 3), (8, 5)]
+    );
+    assert_ne!(format_at(2).font_underline, Some(true));
+}
+
+#[test]
+fn failed_replace_format_leaves_the_document_alone() {
+    // An edit block open on a region's stack keeps the default stack
+    // from recording the replacement, so it fails.
+    let doc = html_doc("<p><i>Body</i></p><blockquote><p><i>Quote</i></p></blockquote>");
+    let frame = doc
+        .flow()
+        .into_iter()
+        .find_map(|e| match e {
+            FlowElement::Frame(f) => Some(f),
+            _ => None,
+        })
+        .unwrap();
+    let stack = doc.create_undo_stack();
+    doc.set_frame_undo_stack(frame.id(), Some(stack)).unwrap();
+    let cursor = doc.cursor_at(6);
+    cursor.begin_edit_block();
+    cursor.insert_text("x").unwrap();
+    let html = doc.to_html().unwrap();
+
+    let italic = FormatQuery {
+        font_italic: Some(true),
+        ..Default::default()
+    };
+    let bold = TextFormat {
+        font_bold: Some(true),
+        ..Default::default()
+    };
+    assert!(
+        doc.replace_format(&italic, &bold, &BlockFormat::default())
+            .is_err()
+    );
+    assert_eq!(doc.to_html().unwrap(), html);
+    assert!(!doc.can_undo());
+
+    // The region's edit block is still open and undoes as one step.
+    cursor.insert_text("y").unwrap();
+    cursor.end_edit_block();
+    assert_eq!(doc.to_plain_text().unwrap(), "Body\nQxyuote");
+    doc.undo_in(stack).unwrap();
+    assert_eq!(doc.to_plain_text().unwrap(), "Body\nQuote");
+    assert_eq!(
+        doc.replace_format(&italic, &bold, &BlockFormat::default())
+            .unwrap(),
+        2
+    );
+}
+
+fn assert_index_agrees(doc: &TextDocument, query: &str, opts: &FindOptions) {
+    // Detailed searches never use the index.
+    let scanned: Vec<(usize, usize)> = doc
+        .find_all_detailed(query, opts)
+        .unwrap()
+        .iter()
+        .map(|h| (h.position, h.length))
+        .collect();
+    let indexed: Vec<(usize, usize)> = doc
+        .find_all(query, opts)
+        .unwrap()
+        .iter()
+        .map(|m| (m.position, m.length))
+        .collect();
+    assert_eq!(indexed, scanned, "query {query:?}");
+    for from in [0, 7, 30] {
+        let scanned = doc.find_detailed(query, from, opts).unwrap();
+        let indexed = doc.find(query, from, opts).unwrap();
+        assert_eq!(
+            indexed.map(|m| m.position),
+            scanned.map(|h| h.position),
+            "query {query:?} from {from}"
+        );
+    }
+}
+
+#[test]
+fn search_index_follows_edits() {
+    let doc = new_doc_with_text("The needle here.\nNo match.\nAnother needle and a Needle.");
+    doc.set_search_index_enabled(true);
+    assert!(doc.is_search_index_enabled());
+    let opts = FindOptions::default();
+    assert_index_agrees(&doc, "needle", &opts);
+
+    // Typing creates a match in a block that had none.
+    let cursor = doc.cursor_at(17);
+    cursor.insert_text("needle ").unwrap();
+    assert_index_agrees(&doc, "needle", &opts);
+    assert_eq!(doc.find_all("needle", &opts).unwrap().len(), 4);
+
+    // Splitting a paragraph inside a match removes it.
+    doc.cursor_at(7).insert_block().unwrap();
+    assert_index_agrees(&doc, "needle", &opts);
+    assert_index_agrees(&doc, "needle here", &opts);
+
+    // Joining the paragraphs again restores it.
+    doc.cursor_at(8).delete_previous_char().unwrap();
+    assert_index_agrees(&doc, "needle here", &opts);
+    assert_eq!(
+        doc.find("needle here", 0, &opts).unwrap().unwrap().position,
+        4
+    );
+
+    // A selection spanning paragraphs merges them.
+    let cursor = doc.cursor_at(10);
+    cursor.set_position(30, MoveMode::KeepAnchor);
+    cursor.remove_selected_text().unwrap();
+    assert_index_agrees(&doc, "needle", &opts);
+    assert_index_agrees(&doc, "nedle", &opts);
+
+    doc.undo().unwrap();
+    assert_index_agrees(&doc, "needle", &opts);
+    doc.redo().unwrap();
+    assert_index_agrees(&doc, "needle", &opts);
+
+    doc.replace_text("needle", "pin", true, &opts).unwrap();
+    assert_index_agrees(&doc, "needle", &opts);
+    assert_index_agrees(&doc, "pin", &opts);
+
+    doc.set_plain_text("haystack\nneedle").unwrap();
+    assert_index_agrees(&doc, "needle", &opts);
+    assert_eq!(doc.find_all("needle", &opts).unwrap().len(), 1);
+}
+
+#[test]
+fn search_index_options() {
+    let doc = new_doc_with_text("Word words\nsword WORD\nword, and word");
+    doc.set_search_index_enabled(true);
+    let whole_word = FindOptions::default().with_whole_word(true);
+    assert_index_agrees(&doc, "word", &whole_word);
+    assert_eq!(doc.find_all("word", &whole_word).unwrap().len(), 4);
+
+    let case_sensitive = FindOptions::default().with_case_sensitive(true);
+    assert_index_agrees(&doc, "WORD", &case_sensitive);
+    assert_index_agrees(&doc, "word", &case_sensitive);
+
+    let backward = FindOptions::default().with_search_backward(true);
+    assert_index_agrees(&doc, "word", &backward);
+    assert_eq!(
+        doc.find("word", 22, &backward).unwrap().unwrap().position,
+        17
+    );
+
+    let scoped = FindOptions::default().with_scope(Some(SearchScope::Range { start: 3, end: 21 }));
+    assert_index_agrees(&doc, "word", &scoped);
+    assert_eq!(doc.find_all("word", &scoped).unwrap().len(), 3);
+
+    // Searches the index can't answer still work.
+    let regex = FindOptions::default().with_use_regex(true);
+    assert_eq!(doc.find_all("w.rd", &regex).unwrap().len(), 6);
+    assert_eq!(
+        doc.find_all("wo", &FindOptions::default()).unwrap().len(),
+        6
+    );
+
+    doc.set_search_index_enabled(false);
+    assert!(!doc.is_search_index_enabled());
+    assert_eq!(doc.find_all("word", &whole_word).unwrap().len(), 4);
+}
+
+#[test]
+fn search_index_falls_back_for_tables() {
+    let doc = new_doc_with_text("needle before");
+    doc.set_search_index_enabled(true);
+    let opts = FindOptions::default();
+    assert_eq!(doc.find_all("needle", &opts).unwrap().len(), 1);
+
+    let cursor = doc.cursor_at(13);
+    cursor.insert_block().unwrap();
+    cursor.insert_table(1, 1).unwrap();
+    assert_index_agrees(&doc, "needle", &opts);
+
+    doc.undo().unwrap();
+    assert_index_agrees(&doc, "needle", &opts);
+}
@@ -1450,3 +1450,94 @@ fn rich_text_editor_demo_end_to_end_insert_table_at_end() {
         ),
     }
 }
+
+#[test]
+fn insert_table_from_added_row_lands_after_containing_table() {
+    // Cells added by insert_table_row belong to the table as much as
+    // the ones it was created with: a table inserted from one of them
+    // goes after the whole table, not into the cell.
+    let doc = doc_with_table_and_text();
+    let table_id = match &doc.flow()[1] {
+        FlowElement::Table(t) => t.id(),
+        _ => panic!("expected the table second in the flow"),
+    };
+    doc.cursor().insert_table_row(table_id, 2).unwrap();
+    let (pos, _) = cell_block_position(&doc, 2, 0).expect("cell (2,0)");
+    doc.cursor_at(pos).insert_table(1, 1).unwrap();
+
+    assert_doc_pos_matches_snapshot(&doc, "after insert from an added row");
+    let kinds: Vec<&'static str> = doc
+        .flow()
+        .iter()
+        .map(|el| match el {
+            FlowElement::Block(_) => "block",
+            FlowElement::Table(_) => "table",
+            FlowElement::Frame(_) => "frame",
+        })
+        .collect();
+    assert_eq!(kinds, vec!["block", "table", "table", "block"]);
+}
+
+#[test]
+fn remove_table_row_with_text_keeps_later_positions() {
+    let doc = doc_with_table_and_text();
+    let table_id = match &doc.flow()[1] {
+        FlowElement::Table(t) => t.id(),
+        _ => panic!("expected the table second in the flow"),
+    };
+    doc.cursor().remove_table_row(table_id, 0).unwrap();
+    assert_doc_pos_matches_snapshot(&doc, "after removing row 0");
+    doc.cursor().remove_table_column(table_id, 0).unwrap();
+    assert_doc_pos_matches_snapshot(&doc, "after removing column 0");
+}
+
+#[test]
+fn split_in_cell_keeps_block_order() {
+    let doc = doc_with_empty_table();
+    let (pos, _) = cell_block_position(&doc, 0, 0).expect("cell (0,0)");
+    let cursor = doc.cursor_at(pos);
+    cursor.insert_text("onetwo").unwrap();
+    cursor.insert_block().unwrap();
+    cursor.insert_text("three").unwrap();
+    doc.cursor_at(pos + 3).insert_block().unwrap();
+
+    let texts: Vec<String> = all_block_positions(&doc)
+        .into_iter()
+        .map(|(_, _, text)| text)
+        .filter(|text| ["one", "two", "three"].contains(&text.as_str()))
+        .collect();
+    assert_eq!(texts, ["one", "two", "three"]);
+    let html = doc.to_html().unwrap();
+    let at = |text: &str| html.find(text).unwrap();
+    assert!(at("one") < at("two") && at("two") < at("three"), "{html}");
+}
+
+#[test]
+fn undo_split_in_cell_after_edit_on_other_stack_restores_positions() {
+    let doc = TextDocument::new();
+    doc.set_plain_text("Before\nAfter").unwrap();
+    doc.cursor_at(7).insert_table(2, 2).unwrap();
+    let after_position = || {
+        doc.blocks()
+            .iter()
+            .find(|b| b.text().ends_with("After"))
+            .expect("'After' block")
+            .position()
+    };
+    let (pos, _) = cell_block_position(&doc, 1, 1).expect("cell (1,1)");
+    let cursor = doc.cursor_at(pos);
+    cursor.insert_text("x").unwrap();
+    let position_before = after_position();
+    cursor.insert_block().unwrap();
+
+    // An edit on another stack that moves the cell's text in the rope
+    // but not the cell itself.
+    let other = doc.create_undo_stack();
+    let other_cursor = doc.cursor_at(after_position());
+    other_cursor.set_undo_stack(Some(other));
+    other_cursor.insert_text("Just ").unwrap();
+
+    doc.undo().unwrap();
+    assert_doc_pos_matches_snapshot(&doc, "after undoing the split");
+    assert_eq!(after_position(), position_before);
+}
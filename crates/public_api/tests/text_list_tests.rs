@@ -528,3 +528,17 @@ fn remove_list_item_out_of_range_errors() {
     let cursor = doc.cursor();
     assert!(cursor.remove_list_item(list.id(), 99).is_err());
 }
+
+#[test]
+fn create_list_after_delete_targets_the_block_at_the_cursor() {
+    let doc = TextDocument::new();
+    doc.set_plain_text("one\ntwo three").unwrap();
+    let cursor = doc.cursor_at(0);
+    cursor.set_position(2, MoveMode::KeepAnchor);
+    cursor.remove_selected_text().unwrap(); // "e\ntwo three"
+
+    doc.cursor_at(2).create_list(ListStyle::Disc).unwrap();
+
+    assert!(doc.block_by_number(0).unwrap().list().is_none());
+    assert!(doc.block_by_number(1).unwrap().list().is_some());
+}
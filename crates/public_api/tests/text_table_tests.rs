@@ -339,6 +339,21 @@ fn remove_table_removes_from_flow() {
     assert!(find_table(&doc).is_none(), "table should be gone from flow");
 }
 
+#[test]
+fn remove_only_table_leaves_empty_paragraph() {
+    let doc = TextDocument::new();
+    doc.set_html("<table><tr><td>a</td></tr></table>")
+        .unwrap()
+        .wait()
+        .unwrap();
+    let table = find_table(&doc).unwrap();
+    doc.cursor().remove_table(table.id()).unwrap();
+    assert!(find_table(&doc).is_none());
+    assert_eq!(doc.block_count(), 1);
+    doc.cursor_at(0).insert_text("x").unwrap();
+    assert_eq!(doc.to_plain_text().unwrap(), "x");
+}
+
 #[test]
 fn insert_table_row_is_undoable() {
     let doc = new_doc_with_table();
@@ -635,3 +650,128 @@ fn mixed_fragment_html_has_text_before_table() {
         "'Before' text should appear before <table> in HTML"
     );
 }
+
+#[test]
+fn inserted_tables_are_exported_once_in_flow_order() {
+    let doc = new_doc_with_table();
+    // A second table, inserted before the first one.
+    doc.cursor_at(6).insert_table(1, 1).unwrap();
+    doc.cursor_at(7).insert_text("second").unwrap();
+
+    let html = doc.to_html().unwrap();
+    assert_eq!(html.matches("<table").count(), 2, "{html}");
+    let second = html.find("second").expect("should contain 'second'");
+    let first = html.rfind("<table").unwrap();
+    assert!(second < first, "tables out of flow order: {html}");
+
+    let markdown = doc.to_markdown().unwrap();
+    let separators: Vec<usize> = markdown
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| line.starts_with('|') && line.contains("---"))
+        .map(|(i, _)| i)
+        .collect();
+    assert_eq!(separators.len(), 2, "{markdown}");
+    let second = markdown.lines().position(|line| line.contains("second"));
+    assert!(
+        second < Some(separators[1]),
+        "tables out of flow order: {markdown}"
+    );
+}
+
+#[test]
+fn splitting_a_block_in_a_cell_keeps_both_halves_in_the_cell() {
+    let doc = TextDocument::new();
+    doc.set_plain_text("abc").unwrap();
+    doc.cursor_at(3).insert_table(1, 2).unwrap();
+    doc.cursor_at(4).insert_text("xy").unwrap();
+    doc.cursor_at(7).insert_text("zz").unwrap();
+
+    doc.cursor_at(5).insert_block().unwrap();
+
+    let cells: Vec<_> = doc
+        .blocks()
+        .iter()
+        .map(|b| (b.text(), b.table_cell().map(|c| (c.row, c.column))))
+        .collect();
+    assert_eq!(
+        cells,
+        [
+            ("abc".to_string(), None),
+            ("x".to_string(), Some((0, 0))),
+            ("y".to_string(), Some((0, 0))),
+            ("zz".to_string(), Some((0, 1))),
+        ]
+    );
+    assert_eq!(doc.to_html().unwrap().matches("<table").count(), 1);
+
+    doc.undo().unwrap();
+    assert_eq!(doc.to_plain_text().unwrap(), "abc\nxy\nzz");
+}
+
+#[test]
+fn plain_text_lists_cells_where_the_flow_places_them() {
+    let doc = TextDocument::new();
+    doc.set_plain_text("ab\ncd").unwrap();
+    doc.cursor_at(2).insert_table(1, 2).unwrap();
+    doc.cursor_at(3).insert_text("x").unwrap();
+    doc.cursor_at(5).insert_text("y").unwrap();
+
+    assert_eq!(doc.to_plain_text().unwrap(), "ab\nx\ny\ncd");
+}
+
+#[test]
+fn removing_a_table_with_text_keeps_the_positions_after_it() {
+    let doc = TextDocument::new();
+    doc.set_plain_text("ab\ncd\nef").unwrap();
+    // A table that stays, so positions cannot be re-read from the text.
+    doc.cursor_at(2).insert_table(1, 1).unwrap();
+    let table = doc.cursor_at(6).insert_table(1, 1).unwrap();
+    doc.cursor_at(7).insert_text("xyz").unwrap();
+
+    doc.cursor().remove_table(table.id()).unwrap();
+
+    // Join "cd" to "ef": the cell text no longer sits before "ef".
+    let cursor = doc.cursor_at(6);
+    cursor.set_position(7, MoveMode::KeepAnchor);
+    cursor.remove_selected_text().unwrap();
+    assert_eq!(doc.to_plain_text().unwrap(), "ab\n\ncdef");
+}
+
+#[test]
+fn removing_a_last_table_after_an_empty_block_keeps_the_blocks_before_it() {
+    let doc = TextDocument::new();
+    doc.set_plain_text("ab").unwrap();
+    let table = doc.cursor_at(2).insert_table(1, 1).unwrap();
+    doc.cursor_at(2).insert_block().unwrap();
+    assert_eq!(
+        doc.to_html().unwrap().matches("<p></p><table").count(),
+        1,
+        "{}",
+        doc.to_html().unwrap()
+    );
+
+    doc.cursor().remove_table(table.id()).unwrap();
+
+    let texts: Vec<String> = doc.blocks().iter().map(|b| b.text()).collect();
+    assert_eq!(texts, ["ab", ""]);
+    doc.cursor_at(3).insert_text("c").unwrap();
+    assert_eq!(doc.to_plain_text().unwrap(), "ab\nc");
+}
+
+#[test]
+fn merging_a_cell_of_several_blocks_keeps_the_positions_after_the_table() {
+    let doc = TextDocument::new();
+    doc.set_plain_text("ab\ncd").unwrap();
+    let table = doc.cursor_at(2).insert_table(1, 2).unwrap();
+    doc.cursor_at(4).insert_block().unwrap();
+
+    doc.cursor()
+        .merge_table_cells(table.id(), 0, 0, 0, 1)
+        .unwrap();
+
+    let cd = doc.blocks().into_iter().find(|b| b.text() == "cd").unwrap();
+    assert_eq!(cd.position(), 4);
+    doc.cursor_at(4).insert_text("x").unwrap();
+    assert_eq!(doc.to_plain_text().unwrap(), "ab\n\nxcd");
+}
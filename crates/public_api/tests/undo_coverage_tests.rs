@@ -1,8 +1,11 @@
 //! Tests that exercise undo/redo paths on all undoable editing and formatting operations.
 
+use std::sync::{Arc, Mutex};
+
 use text_document::{
-    Alignment, BlockFormat, CharVerticalAlignment, FindOptions, ListStyle, MarkerType, MoveMode,
-    MoveOperation, SelectionType, TextDocument, TextFormat, UnderlineStyle,
+    Alignment, BlockFormat, CharVerticalAlignment, DocumentEvent, EditAction, FindOptions,
+    FormatQuery, ListStyle, MarkerType, MoveMode, MoveOperation, SelectionType, TextDocument,
+    TextFormat, UnderlineStyle, UndoLimit,
 };
 
 fn new_doc(text: &str) -> TextDocument {
@@ -500,3 +503,629 @@ fn clear_undo_redo_stack() {
     assert!(!doc.can_undo());
     assert!(!doc.can_redo());
 }
+
+// ── in-block undo deltas ────────────────────────────────────────
+
+#[test]
+fn undo_typing_over_selection_restores_runs_and_images() {
+    let doc = new_doc("Hello world");
+    let c = doc.cursor();
+    c.move_position(MoveOperation::NextCharacter, MoveMode::KeepAnchor, 5);
+    c.set_char_format(&TextFormat {
+        font_bold: Some(true),
+        ..Default::default()
+    })
+    .unwrap();
+    doc.cursor_at(11).insert_image("test.png", 10, 10).unwrap();
+    let chars_before = doc.character_count();
+
+    // Select "lo wor", straddling the end of the bold run.
+    let c = doc.cursor_at(3);
+    c.set_position(9, MoveMode::KeepAnchor);
+    c.insert_text("p").unwrap();
+    assert_eq!(doc.to_plain_text().unwrap(), "Helpld\u{FFFC}");
+
+    doc.undo().unwrap();
+    assert_eq!(doc.to_plain_text().unwrap(), "Hello world\u{FFFC}");
+    assert_eq!(doc.character_count(), chars_before);
+    assert_eq!(doc.stats().image_count, 1);
+    assert_eq!(
+        doc.cursor_at(4).char_format().unwrap().font_bold,
+        Some(true)
+    );
+    assert_ne!(
+        doc.cursor_at(8).char_format().unwrap().font_bold,
+        Some(true)
+    );
+
+    doc.redo().unwrap();
+    assert_eq!(doc.to_plain_text().unwrap(), "Helpld\u{FFFC}");
+    doc.undo().unwrap();
+    assert_eq!(doc.to_plain_text().unwrap(), "Hello world\u{FFFC}");
+}
+
+#[test]
+fn undo_in_block_edit_reports_only_that_block() {
+    let doc = new_doc("First\nSecond block\nThird");
+    let c = doc.cursor_at(13);
+    c.set_position(18, MoveMode::KeepAnchor);
+    c.insert_text("line").unwrap();
+    assert_eq!(doc.to_plain_text().unwrap(), "First\nSecond line\nThird");
+    let tail = doc.cursor_at(21);
+    doc.poll_events();
+
+    doc.undo().unwrap();
+    assert_eq!(doc.to_plain_text().unwrap(), "First\nSecond block\nThird");
+    let events = doc.poll_events();
+    assert!(
+        events.iter().any(|e| matches!(
+            e,
+            DocumentEvent::ContentsChanged {
+                position: 6,
+                blocks_affected: 1,
+                ..
+            }
+        )),
+        "expected ContentsChanged for the second block, got: {:?}",
+        events
+    );
+    // A cursor after the edit follows the restored text.
+    assert_eq!(tail.position(), 22);
+}
+
+#[test]
+fn undo_merged_deletes_across_block_boundary() {
+    let doc = new_doc("ab\ncd");
+    let c = doc.cursor_at(4);
+    // Deletes "c", then the block boundary, then "b".
+    c.delete_previous_char().unwrap();
+    c.delete_previous_char().unwrap();
+    c.delete_previous_char().unwrap();
+    assert_eq!(doc.to_plain_text().unwrap(), "ad");
+
+    while doc.can_undo() {
+        doc.undo().unwrap();
+    }
+    assert_eq!(doc.to_plain_text().unwrap(), "ab\ncd");
+    assert_eq!(doc.block_count(), 2);
+}
+
+#[test]
+fn undo_typing_over_selection_in_table_document() {
+    let doc = TextDocument::new();
+    doc.set_markdown("Before\n\n| A | B |\n|---|---|\n| c | d |\n\nAfter words")
+        .unwrap()
+        .wait()
+        .unwrap();
+    let text_before = doc.to_plain_text().unwrap();
+    let opts = FindOptions::default();
+    let m = doc.find("words", 0, &opts).unwrap().unwrap();
+
+    let c = doc.cursor_at(m.position);
+    c.set_position(m.position + m.length, MoveMode::KeepAnchor);
+    c.insert_text("text").unwrap();
+    assert!(doc.find("After text", 0, &opts).unwrap().is_some());
+
+    doc.undo().unwrap();
+    assert_eq!(doc.to_plain_text().unwrap(), text_before);
+    assert_eq!(doc.find("words", 0, &opts).unwrap(), Some(m.clone()));
+
+    doc.redo().unwrap();
+    assert!(doc.find("After text", 0, &opts).unwrap().is_some());
+}
+
+#[test]
+fn undo_all_table_inserts_and_cross_table_edits() {
+    let doc = new_doc("Hello world\nSecond para here\nThird");
+    doc.clear_undo_redo();
+    let select = |start: usize, end: usize| {
+        let c = doc.cursor_at(start);
+        c.set_position(end, MoveMode::KeepAnchor);
+        c
+    };
+    let state = || (doc.to_plain_text().unwrap(), doc.to_html().unwrap());
+    let mut states = vec![state()];
+
+    select(28, 30).insert_table(2, 2).unwrap();
+    states.push(state());
+    select(18, 20).insert_table(2, 2).unwrap();
+    states.push(state());
+    select(26, 27).remove_selected_text().unwrap();
+    states.push(state());
+    select(25, 31).insert_text("ab").unwrap();
+    states.push(state());
+    select(18, 20).create_list(ListStyle::Decimal).unwrap();
+    states.push(state());
+    select(12, 19).delete_previous_char().unwrap();
+    states.push(state());
+    select(21, 24).insert_text("ab").unwrap();
+    states.push(state());
+    doc.cursor_at(3).insert_text("xy").unwrap();
+
+    while let Some(expected) = states.pop() {
+        doc.undo().unwrap();
+        assert_eq!(state(), expected);
+    }
+    assert!(!doc.can_undo());
+}
+
+#[test]
+fn undo_delete_before_empty_last_block() {
+    let doc = new_doc("");
+    let c = doc.cursor();
+    c.insert_text("a").unwrap();
+    c.insert_block().unwrap();
+    c.set_position(0, MoveMode::MoveAnchor);
+    c.delete_char().unwrap();
+    assert_eq!(doc.to_plain_text().unwrap(), "\n");
+
+    doc.undo().unwrap();
+    assert_eq!(doc.to_plain_text().unwrap(), "a\n");
+    assert_eq!(doc.block_count(), 2);
+    assert_eq!(doc.character_count(), 1);
+}
+
+// ── undo history inspection ─────────────────────────────────────
+
+fn history_labels(doc: &TextDocument) -> Vec<String> {
+    doc.undo_history()
+        .entries
+        .into_iter()
+        .map(|e| e.description)
+        .collect()
+}
+
+#[test]
+fn undo_history_lists_entries_and_current_index() {
+    let doc = new_doc("Hello");
+    assert!(doc.undo_history().entries.is_empty());
+
+    let c = doc.cursor_at(5);
+    c.insert_text(" world").unwrap();
+    c.insert_block().unwrap();
+    c.insert_table(2, 2).unwrap();
+
+    let history = doc.undo_history();
+    assert_eq!(history.current_index, 3);
+    assert_eq!(
+        history_labels(&doc),
+        ["Typing", "Insert paragraph", "Insert table"]
+    );
+
+    doc.undo().unwrap();
+    let history = doc.undo_history();
+    assert_eq!(history.current_index, 2);
+    // Undone entries stay listed after the current index.
+    assert_eq!(
+        history_labels(&doc),
+        ["Typing", "Insert paragraph", "Insert table"]
+    );
+}
+
+#[test]
+fn undo_to_and_redo_to_jump_several_steps() {
+    let doc = new_doc("Hello");
+    let c = doc.cursor_at(5);
+    c.insert_text(" world").unwrap();
+    c.insert_block().unwrap();
+    c.insert_text("Next").unwrap();
+    assert_eq!(doc.to_plain_text().unwrap(), "Hello world\nNext");
+    assert_eq!(doc.undo_history().current_index, 3);
+
+    doc.undo_to(1).unwrap();
+    assert_eq!(doc.to_plain_text().unwrap(), "Hello world");
+    assert_eq!(doc.undo_history().current_index, 1);
+
+    // Moving "forward" with undo_to is a no-op.
+    doc.undo_to(2).unwrap();
+    assert_eq!(doc.undo_history().current_index, 1);
+
+    doc.redo_to(3).unwrap();
+    assert_eq!(doc.to_plain_text().unwrap(), "Hello world\nNext");
+
+    doc.undo_to(0).unwrap();
+    assert_eq!(doc.to_plain_text().unwrap(), "Hello");
+    assert!(!doc.can_undo());
+
+    // Past the end clamps to the last redoable entry.
+    doc.redo_to(usize::MAX).unwrap();
+    assert_eq!(doc.to_plain_text().unwrap(), "Hello world\nNext");
+    assert!(!doc.can_redo());
+}
+
+#[test]
+fn undo_to_emits_one_batch_of_events() {
+    let doc = new_doc("Hello");
+    let c = doc.cursor_at(5);
+    c.insert_text(" world").unwrap();
+    c.insert_block().unwrap();
+    c.insert_text("Next").unwrap();
+    doc.poll_events();
+
+    doc.undo_to(0).unwrap();
+    let events = doc.poll_events();
+    let undo_redo_changed = events
+        .iter()
+        .filter(|e| matches!(e, DocumentEvent::UndoRedoChanged { .. }))
+        .count();
+    assert_eq!(undo_redo_changed, 1, "events: {:?}", events);
+    let block_count_changed = events
+        .iter()
+        .filter(|e| matches!(e, DocumentEvent::BlockCountChanged(_)))
+        .count();
+    assert_eq!(block_count_changed, 1, "events: {:?}", events);
+    assert!(events.iter().any(|e| matches!(
+        e,
+        DocumentEvent::UndoRedoChanged {
+            can_undo: false,
+            can_redo: true
+        }
+    )));
+}
+
+#[test]
+fn undo_to_reports_steps_done_before_a_failure() {
+    let doc = new_doc("Caption\nBody");
+    doc.cursor_at(7).insert_text(" one").unwrap();
+    let stack = doc.create_undo_stack();
+    let region = doc.cursor_at(11);
+    region.set_undo_stack(Some(stack));
+    region.insert_text(" two").unwrap();
+    doc.cursor_at(doc.character_count() + 1)
+        .insert_text(" text")
+        .unwrap();
+    assert_eq!(doc.undo_history().current_index, 2);
+
+    doc.start_recording().unwrap();
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let r = received.clone();
+    let _sub = doc.on_change(move |e| r.lock().unwrap().push(e));
+
+    // The body edit undoes; the caption edit is refused because the
+    // region stack changed the caption since.
+    assert!(doc.undo_to(0).is_err());
+    assert_eq!(doc.to_plain_text().unwrap(), "Caption one two\nBody");
+    assert_eq!(doc.undo_history().current_index, 1);
+
+    let events = received.lock().unwrap();
+    assert!(
+        events
+            .iter()
+            .any(|e| matches!(e, DocumentEvent::ContentsChanged { .. })),
+        "events: {:?}",
+        *events
+    );
+    let log = doc.stop_recording().unwrap();
+    assert!(matches!(
+        log.edits.as_slice(),
+        [edit] if edit.action == EditAction::UndoTo { index: 1 }
+    ));
+}
+
+#[test]
+fn replace_format_is_one_labelled_history_entry() {
+    let doc = TextDocument::new();
+    doc.set_html("<p><i>one</i> two <i>three</i></p>")
+        .unwrap()
+        .wait()
+        .unwrap();
+    doc.replace_format(
+        &FormatQuery {
+            font_italic: Some(true),
+            ..Default::default()
+        },
+        &TextFormat {
+            font_bold: Some(true),
+            ..Default::default()
+        },
+        &BlockFormat::default(),
+    )
+    .unwrap();
+    assert_eq!(
+        history_labels(&doc),
+        ["Replace formatting of 2 occurrences"]
+    );
+}
+
+#[test]
+fn replace_text_label_counts_replacements() {
+    let doc = new_doc("one two one three one");
+    let options = FindOptions::default();
+    doc.replace_text("one", "1", true, &options).unwrap();
+    doc.replace_text("two", "2", false, &options).unwrap();
+    assert_eq!(
+        history_labels(&doc),
+        ["Replace 3 occurrences", "Replace 1 occurrence"]
+    );
+}
+
+// ── selection restore ───────────────────────────────────────────
+
+#[test]
+fn undo_restores_selection_of_editing_cursor() {
+    let doc = new_doc("Hello world");
+    let c = doc.cursor_at(6);
+    c.set_position(11, MoveMode::KeepAnchor);
+    c.insert_text("there").unwrap();
+    assert_eq!(doc.to_plain_text().unwrap(), "Hello there");
+    assert_eq!((c.anchor(), c.position()), (11, 11));
+
+    doc.undo().unwrap();
+    assert_eq!(doc.to_plain_text().unwrap(), "Hello world");
+    assert_eq!((c.anchor(), c.position()), (6, 11));
+    assert_eq!(c.selected_text().unwrap(), "world");
+
+    doc.redo().unwrap();
+    assert_eq!(doc.to_plain_text().unwrap(), "Hello there");
+    assert_eq!((c.anchor(), c.position()), (11, 11));
+}
+
+#[test]
+fn undo_restores_selection_before_merged_typing() {
+    let doc = new_doc("abc");
+    let c = doc.cursor_at(3);
+    c.insert_text("d").unwrap();
+    c.insert_text("e").unwrap();
+    c.insert_text("f").unwrap();
+    assert_eq!(doc.undo_history().entries.len(), 1);
+    c.set_position(0, MoveMode::MoveAnchor);
+
+    doc.undo().unwrap();
+    assert_eq!(doc.to_plain_text().unwrap(), "abc");
+    assert_eq!(c.position(), 3);
+
+    doc.redo().unwrap();
+    assert_eq!(c.position(), 6);
+}
+
+#[test]
+fn undo_moves_only_the_cursor_that_edited() {
+    let doc = new_doc("one two three");
+    let editor = doc.cursor_at(4);
+    editor.set_position(7, MoveMode::KeepAnchor);
+    let other = doc.cursor_at(13);
+    other.set_position(8, MoveMode::KeepAnchor);
+
+    editor.remove_selected_text().unwrap();
+    assert_eq!(doc.to_plain_text().unwrap(), "one  three");
+    assert_eq!((other.anchor(), other.position()), (10, 5));
+
+    doc.undo().unwrap();
+    assert_eq!((editor.anchor(), editor.position()), (4, 7));
+    // Other cursors are only shifted by the restored text.
+    assert_eq!((other.anchor(), other.position()), (13, 8));
+}
+
+#[test]
+fn undo_to_restores_selection_before_oldest_step() {
+    let doc = new_doc("Hello");
+    let c = doc.cursor_at(0);
+    c.set_position(5, MoveMode::KeepAnchor);
+    c.set_char_format(&TextFormat {
+        font_bold: Some(true),
+        ..Default::default()
+    })
+    .unwrap();
+    c.set_position(5, MoveMode::MoveAnchor);
+    c.insert_block().unwrap();
+    c.insert_text("More").unwrap();
+
+    doc.undo_to(0).unwrap();
+    assert_eq!(doc.to_plain_text().unwrap(), "Hello");
+    assert_eq!((c.anchor(), c.position()), (0, 5));
+
+    doc.redo_to(3).unwrap();
+    assert_eq!(doc.to_plain_text().unwrap(), "Hello\nMore");
+    assert_eq!((c.anchor(), c.position()), (10, 10));
+}
+
+#[test]
+fn undo_restores_cell_selection() {
+    let doc = new_doc("");
+    let c = doc.cursor();
+    let table = c.insert_table(2, 2).unwrap();
+    c.select_cell_range(table.id(), 0, 0, 1, 1);
+    let range = c.selected_cell_range().unwrap();
+    c.merge_selected_cells().unwrap();
+    c.clear_cell_selection();
+    c.set_position(0, MoveMode::MoveAnchor);
+
+    doc.undo().unwrap();
+    assert_eq!(c.selected_cell_range(), Some(range));
+}
+
+// ── history limits ──────────────────────────────────────────────
+
+#[test]
+fn undo_limit_drops_oldest_entries() {
+    let doc = new_doc("");
+    doc.set_undo_limit(UndoLimit {
+        max_entries: Some(2),
+        max_bytes: None,
+    })
+    .unwrap();
+    let c = doc.cursor();
+    c.insert_text("a").unwrap();
+    c.insert_block().unwrap();
+    c.insert_text("b").unwrap();
+    c.insert_block().unwrap();
+    assert_eq!(
+        history_labels(&doc),
+        ["Typing", "Insert paragraph"],
+        "only the two newest entries are kept"
+    );
+
+    while doc.can_undo() {
+        doc.undo().unwrap();
+    }
+    assert_eq!(doc.to_plain_text().unwrap(), "a\n");
+}
+
+#[test]
+fn lowering_undo_limit_emits_undo_redo_changed() {
+    let doc = new_doc("Hello");
+    let c = doc.cursor_at(5);
+    c.insert_text("!").unwrap();
+    c.insert_block().unwrap();
+    doc.poll_events();
+
+    doc.set_undo_limit(UndoLimit {
+        max_entries: Some(0),
+        max_bytes: None,
+    })
+    .unwrap();
+    assert!(!doc.can_undo());
+    let events = doc.poll_events();
+    assert!(
+        events.iter().any(|e| matches!(
+            e,
+            DocumentEvent::UndoRedoChanged {
+                can_undo: false,
+                can_redo: false
+            }
+        )),
+        "events: {:?}",
+        events
+    );
+
+    // Raising the limit drops nothing and emits nothing.
+    doc.set_undo_limit(UndoLimit::default()).unwrap();
+    assert!(doc.poll_events().is_empty());
+    assert_eq!(doc.undo_limit(), UndoLimit::default());
+}
+
+#[test]
+fn undo_memory_budget_bounds_structural_history() {
+    let text = "Lorem ipsum dolor sit amet. ".repeat(200);
+    let doc = new_doc(&text);
+    let c = doc.cursor_at(0);
+    c.insert_block().unwrap();
+    let one_entry = doc.undo_memory_usage();
+    // A structural edit keeps what it changed, not the whole document.
+    assert!(one_entry < text.len(), "usage: {one_entry}");
+
+    doc.set_undo_limit(UndoLimit {
+        max_entries: None,
+        max_bytes: Some(one_entry * 3),
+    })
+    .unwrap();
+    for _ in 0..10 {
+        c.insert_block().unwrap();
+        assert!(doc.undo_memory_usage() <= one_entry * 3);
+    }
+    assert!(doc.can_undo());
+    assert!(doc.undo_history().entries.len() < 10);
+}
+
+#[test]
+fn cross_block_delete_undo_keeps_only_the_change() {
+    let paragraph = "Lorem ipsum dolor sit amet. ".repeat(20);
+    let text = vec![paragraph.as_str(); 20].join("\n");
+    let doc = new_doc(&text);
+    let c = doc.cursor_at(paragraph.len() - 5);
+    c.set_position(paragraph.len() + 6, MoveMode::KeepAnchor);
+    c.remove_selected_text().unwrap();
+    assert!(
+        doc.undo_memory_usage() < text.len(),
+        "usage: {}",
+        doc.undo_memory_usage()
+    );
+
+    doc.undo().unwrap();
+    assert_eq!(doc.to_plain_text().unwrap(), text);
+    doc.redo().unwrap();
+    assert_eq!(doc.block_count(), 19);
+}
+
+/// An edit made at a character position.
+type Edit = fn(&TextDocument, usize);
+
+/// Undo memory used by one edit made in the middle of a document of
+/// `paragraphs` paragraphs.
+fn entry_size(paragraphs: usize, edit: Edit) -> usize {
+    let paragraph = "Lorem ipsum dolor sit amet, consectetur adipiscing elit.";
+    let doc = new_doc(&vec![paragraph; paragraphs].join("\n"));
+    edit(&doc, paragraphs / 2 * (paragraph.len() + 1) + 5);
+    doc.undo_memory_usage()
+}
+
+#[test]
+fn undo_entry_size_does_not_grow_with_the_document() {
+    let edits: [(&str, Edit); 4] = [
+        ("insert_block", |doc, at| {
+            doc.cursor_at(at).insert_block().unwrap();
+        }),
+        ("insert_table", |doc, at| {
+            doc.cursor_at(at).insert_table(2, 2).unwrap();
+        }),
+        ("insert_html", |doc, at| {
+            doc.cursor_at(at)
+                .insert_html("<p>one</p><p>two</p>")
+                .unwrap();
+        }),
+        ("insert_text", |doc, at| {
+            doc.cursor_at(at).insert_text("x").unwrap();
+        }),
+    ];
+    for (name, edit) in edits {
+        let small = entry_size(100, edit);
+        let large = entry_size(1000, edit);
+        // Blocks after the edit only move, which costs nothing per block.
+        assert!(large <= small + 64, "{name}: {small} -> {large} bytes");
+    }
+}
+
+/// Undo memory used by one edit made after a table, in a document of
+/// `paragraphs` paragraphs. With a table, later blocks keep stored
+/// positions that the edit moves.
+fn entry_size_after_table(paragraphs: usize, edit: Edit) -> usize {
+    let paragraph = "Lorem ipsum dolor sit amet, consectetur adipiscing elit.";
+    let doc = new_doc(&vec![paragraph; paragraphs].join("\n"));
+    doc.cursor_at(0).insert_table(2, 2).unwrap();
+    doc.clear_undo_redo();
+    let at = doc.block_by_number(paragraphs / 2).unwrap().position() + 5;
+    let before = doc.to_plain_text().unwrap();
+    edit(&doc, at);
+    let size = doc.undo_memory_usage();
+    let after = doc.to_plain_text().unwrap();
+    while doc.can_undo() {
+        doc.undo().unwrap();
+    }
+    assert_eq!(doc.to_plain_text().unwrap(), before);
+    while doc.can_redo() {
+        doc.redo().unwrap();
+    }
+    assert_eq!(doc.to_plain_text().unwrap(), after);
+    size
+}
+
+#[test]
+fn typing_undo_entry_size_does_not_grow_with_a_table_document() {
+    let edits: [(&str, Edit); 4] = [
+        ("insert_text", |doc, at| {
+            let c = doc.cursor_at(at);
+            c.insert_text("x").unwrap();
+            c.insert_text("y").unwrap();
+        }),
+        ("replace_selection", |doc, at| {
+            let c = doc.cursor_at(at);
+            c.set_position(at + 3, MoveMode::KeepAnchor);
+            c.insert_text("xyz").unwrap();
+        }),
+        ("delete_previous_char", |doc, at| {
+            let c = doc.cursor_at(at);
+            c.delete_previous_char().unwrap();
+            c.delete_previous_char().unwrap();
+        }),
+        ("insert_formatted_text", |doc, at| {
+            doc.cursor_at(at)
+                .insert_formatted_text("x", &TextFormat::default())
+                .unwrap();
+        }),
+    ];
+    for (name, edit) in edits {
+        let small = entry_size_after_table(100, edit);
+        let large = entry_size_after_table(1000, edit);
+        assert_eq!(small, large, "{name}: {small} -> {large} bytes");
+    }
+}
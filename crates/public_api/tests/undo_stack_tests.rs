@@ -0,0 +1,281 @@
+//! Tests for independent undo stacks: routing edits by cursor and by
+//! frame, undoing one region without the other, and refusing steps that
+//! would clobber another stack's later edits.
+
+use text_document::{
+    BlockFormat, DocumentEvent, FlowElement, MoveMode, TextDocument, TextFormat, TextFrame,
+    UndoLimit,
+};
+
+fn new_doc(text: &str) -> TextDocument {
+    let doc = TextDocument::new();
+    doc.set_plain_text(text).unwrap();
+    doc
+}
+
+fn quote_doc() -> (TextDocument, TextFrame) {
+    let doc = TextDocument::new();
+    doc.set_html("<p>Body</p><blockquote><p>Quote</p></blockquote>")
+        .unwrap()
+        .wait()
+        .unwrap();
+    let frame = doc
+        .flow()
+        .into_iter()
+        .find_map(|e| match e {
+            FlowElement::Frame(f) => Some(f),
+            _ => None,
+        })
+        .expect("blockquote frame in the flow");
+    (doc, frame)
+}
+
+#[test]
+fn cursor_stacks_undo_independently() {
+    let doc = new_doc("Caption\nBody");
+    let caption_stack = doc.create_undo_stack();
+
+    let caption = doc.cursor_at(7);
+    caption.set_undo_stack(Some(caption_stack));
+    caption.insert_text(" one").unwrap();
+
+    let body = doc.cursor_at(doc.character_count() + 1);
+    body.insert_text(" text").unwrap();
+    assert_eq!(doc.to_plain_text().unwrap(), "Caption one\nBody text");
+
+    // Undoing the caption leaves the later body edit alone.
+    doc.undo_in(caption_stack).unwrap();
+    assert_eq!(doc.to_plain_text().unwrap(), "Caption\nBody text");
+    assert!(!doc.can_undo_in(caption_stack));
+    assert!(doc.can_redo_in(caption_stack));
+
+    doc.undo().unwrap();
+    assert_eq!(doc.to_plain_text().unwrap(), "Caption\nBody");
+
+    doc.redo().unwrap();
+    assert_eq!(doc.to_plain_text().unwrap(), "Caption\nBody text");
+}
+
+#[test]
+fn frame_binding_routes_edits_inside_the_frame() {
+    let (doc, frame) = quote_doc();
+    let quote = match &frame.flow()[0] {
+        FlowElement::Block(b) => b.clone(),
+        _ => panic!("expected a block in the frame"),
+    };
+    let stack = doc.create_undo_stack();
+    doc.set_frame_undo_stack(frame.id(), Some(stack)).unwrap();
+    assert_eq!(doc.frame_undo_stack(frame.id()), Some(stack));
+
+    let inside = doc.cursor_at(quote.position() + 5);
+    assert_eq!(inside.undo_stack(), stack);
+    inside.insert_text("d").unwrap();
+    assert!(doc.can_undo_in(stack));
+    assert!(!doc.can_undo());
+
+    let outside = doc.cursor_at(0);
+    assert_eq!(outside.undo_stack(), doc.default_undo_stack());
+    outside.insert_text("My ").unwrap();
+    assert!(doc.can_undo());
+
+    doc.undo_in(stack).unwrap();
+    assert_eq!(quote.text(), "Quote");
+    assert!(doc.to_plain_text().unwrap().starts_with("My Body"));
+}
+
+#[test]
+fn region_structure_and_format_undo_past_edits_elsewhere() {
+    let (doc, frame) = quote_doc();
+    let stack = doc.create_undo_stack();
+    doc.set_frame_undo_stack(frame.id(), Some(stack)).unwrap();
+    let quote = || doc.block_by_number(1).unwrap();
+    let body = || doc.cursor_at(0);
+
+    doc.cursor_at(quote().position() + 3)
+        .insert_block()
+        .unwrap();
+    body().insert_text("My ").unwrap();
+    doc.cursor_at(quote().position())
+        .set_block_format(&BlockFormat {
+            heading_level: Some(2),
+            ..Default::default()
+        })
+        .unwrap();
+    body().insert_text("A ").unwrap();
+    let cursor = doc.cursor_at(quote().position());
+    cursor.set_position(quote().position() + 3, MoveMode::KeepAnchor);
+    cursor
+        .set_char_format(&TextFormat {
+            font_bold: Some(true),
+            ..Default::default()
+        })
+        .unwrap();
+    body().insert_text("So ").unwrap();
+    assert_eq!(doc.to_plain_text().unwrap(), "So A My Body\nQuo\nte");
+
+    for _ in 0..3 {
+        doc.undo_in(stack).unwrap();
+    }
+    assert!(!doc.can_undo_in(stack));
+    assert_eq!(doc.to_plain_text().unwrap(), "So A My Body\nQuote");
+    assert_eq!(quote().block_format().heading_level, None);
+    assert_ne!(quote().char_format_at(0).unwrap().font_bold, Some(true));
+
+    for _ in 0..3 {
+        doc.undo().unwrap();
+    }
+    assert_eq!(doc.to_plain_text().unwrap(), "Body\nQuote");
+}
+
+#[test]
+fn undo_refused_when_another_stack_changed_the_same_block() {
+    let doc = new_doc("Hello");
+    let stack = doc.create_undo_stack();
+
+    let first = doc.cursor_at(5);
+    first.set_undo_stack(Some(stack));
+    first.insert_text(" there").unwrap();
+    doc.cursor_at(0).insert_text("Oh, ").unwrap();
+
+    assert!(doc.undo_in(stack).is_err());
+    assert_eq!(doc.to_plain_text().unwrap(), "Oh, Hello there");
+
+    // Once the later edit is undone the way is clear again.
+    doc.undo().unwrap();
+    doc.undo_in(stack).unwrap();
+    assert_eq!(doc.to_plain_text().unwrap(), "Hello");
+}
+
+#[test]
+fn redo_refused_after_another_stack_edited() {
+    let doc = new_doc("Caption\nBody");
+    let stack = doc.create_undo_stack();
+
+    let caption = doc.cursor_at(7);
+    caption.set_undo_stack(Some(stack));
+    caption.insert_text(" one").unwrap();
+    doc.undo_in(stack).unwrap();
+
+    doc.cursor_at(doc.character_count() + 1)
+        .insert_text(" text")
+        .unwrap();
+    assert!(doc.redo_in(stack).is_err());
+    assert_eq!(doc.to_plain_text().unwrap(), "Caption\nBody text");
+}
+
+#[test]
+fn region_edits_emit_undo_stack_changed() {
+    let doc = new_doc("Hello");
+    let stack = doc.create_undo_stack();
+    doc.poll_events();
+
+    let cursor = doc.cursor_at(5);
+    cursor.set_undo_stack(Some(stack));
+    cursor.insert_text("!").unwrap();
+
+    let events = doc.poll_events();
+    assert!(events.contains(&DocumentEvent::UndoStackChanged {
+        stack,
+        can_undo: true,
+        can_redo: false,
+    }));
+    assert!(
+        !events
+            .iter()
+            .any(|e| matches!(e, DocumentEvent::UndoRedoChanged { .. }))
+    );
+}
+
+#[test]
+fn delete_undo_stack_unbinds_cursors() {
+    let doc = new_doc("Hello");
+    let stack = doc.create_undo_stack();
+    let cursor = doc.cursor_at(5);
+    cursor.set_undo_stack(Some(stack));
+
+    assert!(doc.delete_undo_stack(doc.default_undo_stack()).is_err());
+    doc.delete_undo_stack(stack).unwrap();
+    assert_eq!(cursor.undo_stack(), doc.default_undo_stack());
+    assert!(doc.undo_in(stack).is_err());
+
+    cursor.insert_text("!").unwrap();
+    assert!(doc.can_undo());
+}
+
+#[test]
+fn frame_binding_follows_new_blocks_in_the_frame() {
+    let (doc, frame) = quote_doc();
+    let stack = doc.create_undo_stack();
+    doc.set_frame_undo_stack(frame.id(), Some(stack)).unwrap();
+
+    let end = doc.character_count() + 1;
+    assert_eq!(doc.cursor_at(end).undo_stack(), stack);
+    doc.cursor_at(end).insert_block().unwrap();
+    let end = doc.character_count() + 2;
+    assert_eq!(doc.cursor_at(end).undo_stack(), stack);
+    assert_eq!(doc.cursor_at(0).undo_stack(), doc.default_undo_stack());
+}
+
+#[test]
+fn replacing_the_document_drops_frame_bindings() {
+    let replacements: [fn(&TextDocument); 3] = [
+        |doc| doc.set_plain_text("Plain").unwrap(),
+        |doc| doc.clear().unwrap(),
+        |doc| {
+            let source = new_doc("Loaded");
+            let mut bytes = Vec::new();
+            source.save_native(&mut bytes).unwrap();
+            doc.load_native(bytes.as_slice()).unwrap();
+        },
+    ];
+    for replace in replacements {
+        let (doc, frame) = quote_doc();
+        let stack = doc.create_undo_stack();
+        doc.set_frame_undo_stack(frame.id(), Some(stack)).unwrap();
+
+        replace(&doc);
+        assert_eq!(doc.frame_undo_stack(frame.id()), None);
+        let cursor = doc.cursor_at(0);
+        assert_eq!(cursor.undo_stack(), doc.default_undo_stack());
+        cursor.insert_text("x").unwrap();
+        assert!(doc.can_undo());
+        assert!(!doc.can_undo_in(stack));
+    }
+}
+
+#[test]
+fn region_stack_has_its_own_limit() {
+    let doc = new_doc("Caption\nBody");
+    let stack = doc.create_undo_stack();
+    let caption = doc.cursor_at(7);
+    caption.set_undo_stack(Some(stack));
+    for word in [" one", " two", " three"] {
+        caption.insert_text(word).unwrap();
+        caption.insert_block().unwrap();
+    }
+    doc.cursor_at(0).insert_text(">").unwrap();
+    assert_eq!(doc.undo_history_in(stack).entries.len(), 6);
+    let before = doc.undo_memory_usage_in(stack);
+    assert!(before > 0);
+    doc.poll_events();
+
+    let limit = UndoLimit {
+        max_entries: Some(2),
+        max_bytes: None,
+    };
+    doc.set_undo_limit_in(stack, limit).unwrap();
+    assert_eq!(doc.undo_limit_in(stack), limit);
+    assert_eq!(doc.undo_history_in(stack).entries.len(), 2);
+    assert!(doc.undo_memory_usage_in(stack) < before);
+    assert!(doc.poll_events().iter().any(|e| matches!(
+        e,
+        DocumentEvent::UndoStackChanged { stack: s, .. } if *s == stack
+    )));
+
+    // The default stack keeps its own, unbounded history.
+    assert_eq!(doc.undo_limit(), UndoLimit::default());
+    assert_eq!(doc.undo_history().entries.len(), 1);
+
+    doc.delete_undo_stack(stack).unwrap();
+    assert!(doc.set_undo_limit_in(stack, limit).is_err());
+}
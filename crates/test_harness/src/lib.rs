@@ -6,6 +6,10 @@
 //! and export directly via entity controllers, so it does **not** depend
 //! on `document_io` or any feature crate, breaking the circular
 //! dev-dependency chain.
+//!
+//! The [`replay`] module, behind the `replay` feature, is the exception:
+//! it checks `text_document::EditLog` replays for the public API's tests
+//! and depends on the public API.
 
 use anyhow::Result;
 use common::database::db_context::DbContext;
@@ -14,6 +18,9 @@ use common::types::EntityId;
 use common::undo_redo::UndoRedoManager;
 use std::sync::Arc;
 
+#[cfg(feature = "replay")]
+pub mod replay;
+
 // Re-export commonly used types and controllers for convenience
 pub use common::direct_access::block::block_repository::BlockRelationshipField;
 pub use common::direct_access::document::document_repository::DocumentRelationshipField;
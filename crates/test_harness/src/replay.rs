@@ -0,0 +1,83 @@
+//! Assertions for replaying recorded editing sessions.
+//!
+//! Record with [`TextDocument::start_recording`], edit, then call
+//! [`assert_replay_matches`] to check that the log rebuilds the same
+//! document. Logs go through their JSON form first, so a log that only
+//! replays before serialization fails the assertion too.
+
+use serde_json::Value;
+use text_document::{EditLog, TextDocument};
+
+/// Stop recording on `doc`, replay the log into a fresh document and
+/// assert that it matches `doc`. Returns the log.
+///
+/// # Panics
+///
+/// Panics if `doc` is not recording, if the replay fails, or if the
+/// replayed document differs.
+pub fn assert_replay_matches(doc: &TextDocument) -> EditLog {
+    let log = doc
+        .stop_recording()
+        .expect("document is not recording; call start_recording first");
+    assert_replays_to(&log, doc);
+    log
+}
+
+/// Replay `log` into a fresh document and assert that it matches
+/// `expected`. Returns the replayed document.
+pub fn assert_replays_to(log: &EditLog, expected: &TextDocument) -> TextDocument {
+    let json = log.to_json().expect("edit log serializes");
+    let parsed = EditLog::from_json(&json).expect("edit log parses back");
+    assert_eq!(&parsed, log, "edit log changed through JSON");
+    let replayed = TextDocument::replay(&parsed).unwrap_or_else(|e| panic!("replay failed: {e}"));
+    assert_same_document(&replayed, expected);
+    replayed
+}
+
+/// Assert that two documents hold the same content — the whole native
+/// store with its entity IDs, creation and update timestamps aside — and
+/// the same default undo history and modified flag.
+pub fn assert_same_document(actual: &TextDocument, expected: &TextDocument) {
+    assert_eq!(
+        actual.to_plain_text().unwrap(),
+        expected.to_plain_text().unwrap(),
+        "plain text differs"
+    );
+    assert_eq!(
+        actual.to_html().unwrap(),
+        expected.to_html().unwrap(),
+        "HTML differs"
+    );
+    assert_eq!(native(actual), native(expected), "native content differs");
+    assert_eq!(
+        actual.undo_history(),
+        expected.undo_history(),
+        "undo history differs"
+    );
+    assert_eq!(
+        actual.is_modified(),
+        expected.is_modified(),
+        "modified flag differs"
+    );
+}
+
+/// The document in the native format, without timestamps.
+fn native(doc: &TextDocument) -> Value {
+    let mut data = Vec::new();
+    doc.save_native(&mut data).expect("native export");
+    let mut value: Value = serde_json::from_slice(&data).expect("native export is JSON");
+    strip_timestamps(&mut value);
+    value
+}
+
+fn strip_timestamps(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            map.remove("created_at");
+            map.remove("updated_at");
+            map.values_mut().for_each(strip_timestamps);
+        }
+        Value::Array(items) => items.iter_mut().for_each(strip_timestamps),
+        _ => {}
+    }
+}